@@ -192,6 +192,7 @@ impl From<&am::ObjType> for AMobjType {
             List => Self::List,
             Map | Table => Self::Map,
             Text => Self::Text,
+            _ => Self::Default,
         }
     }
 }
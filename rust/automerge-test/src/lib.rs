@@ -361,6 +361,8 @@ pub fn realize_obj<R: ReadDoc>(
             }
             RealizedObject::Sequence(result)
         }
+        // unknown object types, and any added later, can't be read into
+        _ => RealizedObject::Value(OrdScalarValue::Null),
     }
 }
 
@@ -12,6 +12,7 @@ export type MapObjType = { [key: string]: ObjType | Value }
 export type ObjInfo = { id: ObjID, type: ObjTypeName, path?: Prop[] };
 export type Span = { type: "text", value: string, "marks"?: MarkSet } 
   | { type: "block", value: {[key: string]: MaterializeValue} }
+  | { type: "embed", value: {[key: string]: MaterializeValue} }
 export type ListObjType = Array<ObjType | Value>
 export type ObjType = string | ListObjType | MapObjType
 export type FullValue =
@@ -326,17 +326,17 @@ impl TryFrom<JS> for am::sync::State {
                 None
             }
         };
-        Ok(am::sync::State {
-            shared_heads,
-            last_sent_heads,
-            their_heads,
-            their_need,
-            their_have,
-            sent_hashes,
-            in_flight,
-            have_responded,
-            their_capabilities,
-        })
+        let mut state = am::sync::State::new();
+        state.shared_heads = shared_heads;
+        state.last_sent_heads = last_sent_heads;
+        state.their_heads = their_heads;
+        state.their_need = their_need;
+        state.their_have = their_have;
+        state.sent_hashes = sent_hashes;
+        state.in_flight = in_flight;
+        state.have_responded = have_responded;
+        state.their_capabilities = their_capabilities;
+        Ok(state)
     }
 }
 
@@ -438,14 +438,14 @@ impl TryFrom<JS> for am::sync::Message {
             .try_into()
             .map_err(error::BadSyncMessage::BadJSChanges)?;
 
-        Ok(am::sync::Message {
-            heads,
-            need,
-            have,
-            changes,
-            supported_capabilities,
-            version,
-        })
+        let mut message = am::sync::Message::default();
+        message.heads = heads;
+        message.need = need;
+        message.have = have;
+        message.changes = changes;
+        message.supported_capabilities = supported_capabilities;
+        message.version = version;
+        Ok(message)
     }
 }
 
@@ -529,7 +529,13 @@ impl From<&[am::sync::Capability]> for AR {
             .filter_map(|c| match c {
                 am::sync::Capability::MessageV1 => Some(JsValue::from_str("message-v1")),
                 am::sync::Capability::MessageV2 => Some(JsValue::from_str("message-v2")),
-                am::sync::Capability::Unknown(_) => None,
+                am::sync::Capability::StateSnapshot => Some(JsValue::from_str("state-snapshot")),
+                am::sync::Capability::Ephemeral => Some(JsValue::from_str("ephemeral")),
+                am::sync::Capability::Chunking => Some(JsValue::from_str("chunking")),
+                am::sync::Capability::Compression => Some(JsValue::from_str("compression")),
+                am::sync::Capability::Shallow => Some(JsValue::from_str("shallow")),
+                am::sync::Capability::Keepalive => Some(JsValue::from_str("keepalive")),
+                _ => None,
             })
             .collect())
     }
@@ -587,6 +593,12 @@ impl TryFrom<JS> for Vec<Capability> {
                 match as_str.as_str() {
                     "message-v1" => Ok(Capability::MessageV1),
                     "message-v2" => Ok(Capability::MessageV2),
+                    "state-snapshot" => Ok(Capability::StateSnapshot),
+                    "ephemeral" => Ok(Capability::Ephemeral),
+                    "chunking" => Ok(Capability::Chunking),
+                    "compression" => Ok(Capability::Compression),
+                    "shallow" => Ok(Capability::Shallow),
+                    "keepalive" => Ok(Capability::Keepalive),
                     other => Err(error::BadCapabilities::ElemNotValid(i, other.to_string())),
                 }
             })
@@ -1369,6 +1381,7 @@ pub(crate) fn alloc(value: &Value<'_>, text_rep: TextRepresentation) -> (Datatyp
                 TextRepresentation::String => (Datatype::Text, "".into()),
                 TextRepresentation::Array => (Datatype::Text, Array::new().into()),
             },
+            _ => (Datatype::Null, JsValue::null()),
         },
         am::Value::Scalar(s) => alloc_scalar(s.as_ref()),
     }
@@ -1452,6 +1465,12 @@ pub(crate) fn export_span(
             js_set(&result, "value", export_hydrate(doc, cache, b.into()))?;
             Ok(result)
         }
+        Span::Embed(e) => {
+            let result = Object::new();
+            js_set(&result, "type", "embed")?;
+            js_set(&result, "value", export_hydrate(doc, cache, e.into()))?;
+            Ok(result)
+        }
     }
 }
 
@@ -1710,6 +1729,7 @@ pub(super) fn js_val_to_hydrate(
                 };
                 Ok(am::hydrate::Value::Text(obj.into()))
             }
+            _ => Err(error::JsValToHydrate::UnknownType),
         }
     } else if let Some(val) = doc.import_scalar(&value, datatype) {
         Ok(am::hydrate::Value::Scalar(val))
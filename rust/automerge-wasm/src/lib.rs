@@ -1035,8 +1035,13 @@ impl Automerge {
 
     #[wasm_bindgen(js_name = emptyChange)]
     pub fn empty_change(&mut self, message: Option<String>, time: Option<f64>) -> JsValue {
-        let time = time.map(|f| f as i64);
-        let options = CommitOptions { message, time };
+        let mut options = CommitOptions::default();
+        if let Some(message) = message {
+            options.set_message(message);
+        }
+        if let Some(time) = time {
+            options.set_time(time as i64);
+        }
         let hash = self.doc.empty_change(options);
         JsValue::from_str(&hex::encode(hash))
     }
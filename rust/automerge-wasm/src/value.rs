@@ -42,6 +42,8 @@ impl From<ObjType> for Datatype {
             ObjType::List => Self::List,
             ObjType::Table => Self::Table,
             ObjType::Text => Self::Text,
+            // objects created by newer versions of automerge are opaque
+            _ => Self::Null,
         }
     }
 }
@@ -1,7 +1,7 @@
 use automerge::{
     sync::{self, SyncDoc},
     transaction::Transactable,
-    Automerge, ROOT,
+    Automerge, ObjType, ROOT,
 };
 use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
 
@@ -30,6 +30,63 @@ fn increasing_put(n: u64) -> Automerge {
     doc
 }
 
+fn typed_text(n: u64) -> Automerge {
+    let mut doc = Automerge::new();
+    let mut tx = doc.transaction();
+    let text = tx.put_object(ROOT, "text", ObjType::Text).unwrap();
+    tx.commit();
+    for i in 0..n {
+        let mut tx = doc.transaction();
+        tx.splice_text(&text, (i * 5) as usize, 0, "text ").unwrap();
+        tx.commit();
+    }
+    doc
+}
+
+// like `sync` but passing the messages through their encoding, with the given compression for
+// the changes, returning the number of bytes sent
+fn sync_encoded(
+    doc1: &mut DocWithSync,
+    doc2: &mut DocWithSync,
+    compression: sync::Compression,
+) -> usize {
+    let mut config1 = sync::Config::default();
+    config1.compression = compression;
+    let mut config2 = sync::Config::default();
+    config2.compression = compression;
+    let mut sent = 0;
+    loop {
+        let mut done = true;
+        if let Some(message) = doc1
+            .doc
+            .generate_sync_message_with_config(&mut doc1.peer_state, &mut config1)
+        {
+            let encoded = message.encode();
+            sent += encoded.len();
+            let message = sync::Message::decode(&encoded).unwrap();
+            doc2.doc
+                .receive_sync_message_with_config(&mut doc2.peer_state, &config2, message)
+                .unwrap();
+            done = false;
+        }
+        if let Some(message) = doc2
+            .doc
+            .generate_sync_message_with_config(&mut doc2.peer_state, &mut config2)
+        {
+            let encoded = message.encode();
+            sent += encoded.len();
+            let message = sync::Message::decode(&encoded).unwrap();
+            doc1.doc
+                .receive_sync_message_with_config(&mut doc1.peer_state, &config1, message)
+                .unwrap();
+            done = false;
+        }
+        if done {
+            return sent;
+        }
+    }
+}
+
 // keep syncing until doc1 no longer generates a sync message for doc2.
 fn sync(doc1: &mut DocWithSync, doc2: &mut DocWithSync) {
     while let Some(message1) = doc1.doc.generate_sync_message(&mut doc1.peer_state) {
@@ -89,6 +146,37 @@ fn criterion_benchmark(c: &mut Criterion) {
         );
     }
     group.finish();
+
+    // compression costs time on both ends in return for fewer bytes on the wire, print the sizes
+    // alongside the timings so the two can be compared
+    let mut group = c.benchmark_group("sync text compression");
+    for size in &sizes {
+        group.throughput(criterion::Throughput::Elements(*size));
+        for compression in [sync::Compression::None, sync::Compression::Deflate] {
+            let sent = sync_encoded(
+                &mut typed_text(*size).into(),
+                &mut DocWithSync::default(),
+                compression,
+            );
+            println!(
+                "{:?} compression, {} changes: {} bytes",
+                compression, size, sent
+            );
+
+            group.bench_with_input(
+                BenchmarkId::new(format!("{:?}", compression), size),
+                size,
+                |b, &size| {
+                    b.iter_batched(
+                        || (typed_text(size), DocWithSync::default()),
+                        |(doc1, mut doc2)| sync_encoded(&mut doc1.into(), &mut doc2, compression),
+                        criterion::BatchSize::LargeInput,
+                    )
+                },
+            );
+        }
+    }
+    group.finish();
 }
 
 criterion_group!(benches, criterion_benchmark);
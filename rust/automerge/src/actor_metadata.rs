@@ -0,0 +1,134 @@
+//! Display metadata for the actors which edit a document
+//!
+//! Metadata set with [`Transactable::set_actor_metadata()`] is stored in the document itself, in a
+//! map at [`ACTOR_METADATA_KEY`] in the [system object](crate::system). The map is keyed by the
+//! hex encoded actor ID and each entry is a map with the optional keys `name`, `device` and
+//! `public_key`, so other implementations can read it without this module.
+use crate::exid::ExId;
+use crate::system::{self, ReadSystem};
+use crate::transaction::Transactable;
+use crate::{ActorId, AutomergeError, ObjType, ScalarValue, Value};
+
+/// The key in the system object under which actor metadata is stored
+pub const ACTOR_METADATA_KEY: &str = "actors";
+
+const NAME: &str = "name";
+const DEVICE: &str = "device";
+const PUBLIC_KEY: &str = "public_key";
+
+/// Display metadata for an actor, see [`crate::Automerge::actor_metadata()`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ActorMetadata {
+    /// A human readable name for the person or service using the actor
+    pub name: Option<String>,
+    /// A description of the device the actor is used on
+    pub device: Option<String>,
+    /// A public key which the actor signs changes with
+    pub public_key: Option<Vec<u8>>,
+}
+
+pub(crate) fn set_actor_metadata<T: Transactable + ReadSystem + ?Sized>(
+    tx: &mut T,
+    actor: &ActorId,
+    metadata: &ActorMetadata,
+) -> Result<(), AutomergeError> {
+    let key = actor.to_hex_string();
+    let entry = match find_entry(tx, &key)? {
+        Some(entry) => entry,
+        None => {
+            let registry = system::system_map(tx, ACTOR_METADATA_KEY)?;
+            tx.put_object(&registry, key, ObjType::Map)?
+        }
+    };
+    if let Some(name) = &metadata.name {
+        tx.put(&entry, NAME, name.as_str())?;
+    }
+    if let Some(device) = &metadata.device {
+        tx.put(&entry, DEVICE, device.as_str())?;
+    }
+    if let Some(public_key) = &metadata.public_key {
+        tx.put(&entry, PUBLIC_KEY, ScalarValue::Bytes(public_key.clone()))?;
+    }
+    Ok(())
+}
+
+pub(crate) fn actor_metadata<R: ReadSystem + ?Sized>(
+    doc: &R,
+    actor: &ActorId,
+) -> Result<Option<ActorMetadata>, AutomergeError> {
+    let Some(entry) = find_entry(doc, &actor.to_hex_string())? else {
+        return Ok(None);
+    };
+    let scalar = |prop: &str| -> Result<Option<ScalarValue>, AutomergeError> {
+        Ok(match doc.get(&entry, prop)? {
+            Some((Value::Scalar(s), _)) => Some(s.into_owned()),
+            _ => None,
+        })
+    };
+    Ok(Some(ActorMetadata {
+        name: scalar(NAME)?.and_then(|s| s.into_string().ok()),
+        device: scalar(DEVICE)?.and_then(|s| s.into_string().ok()),
+        public_key: scalar(PUBLIC_KEY)?.and_then(|s| s.into_bytes().ok()),
+    }))
+}
+
+fn find_entry<R: ReadSystem + ?Sized>(doc: &R, key: &str) -> Result<Option<ExId>, AutomergeError> {
+    // the metadata for an actor is in whichever registry the actor first wrote it to
+    for registry in doc.system_maps(ACTOR_METADATA_KEY)? {
+        if let Some((Value::Object(ObjType::Map), entry)) = doc.get(&registry, key)? {
+            return Ok(Some(entry));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ActorMetadata;
+    use crate::{transaction::Transactable, ActorId, AutoCommit};
+
+    #[test]
+    fn metadata_is_shared_with_peers() {
+        let alice = ActorId::from(b"alice".to_vec());
+        let bob = ActorId::from(b"bob".to_vec());
+        let mut doc = AutoCommit::new().with_actor(alice.clone());
+        let mut other = AutoCommit::new().with_actor(bob.clone());
+
+        let metadata = ActorMetadata {
+            name: Some("Alice".to_string()),
+            device: Some("laptop".to_string()),
+            public_key: Some(vec![1, 2, 3]),
+        };
+        doc.set_actor_metadata(&alice, &metadata).unwrap();
+        other
+            .set_actor_metadata(
+                &bob,
+                &ActorMetadata {
+                    name: Some("Bob".to_string()),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        doc.merge(&mut other).unwrap();
+
+        assert_eq!(doc.actor_metadata(&alice).unwrap(), Some(metadata));
+        assert_eq!(
+            doc.actor_metadata(&bob).unwrap().unwrap().name.as_deref(),
+            Some("Bob")
+        );
+        assert_eq!(doc.actor_metadata(&ActorId::random()).unwrap(), None);
+
+        // fields which are not given are left as they were
+        doc.set_actor_metadata(
+            &alice,
+            &ActorMetadata {
+                device: Some("phone".to_string()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let updated = doc.actor_metadata(&alice).unwrap().unwrap();
+        assert_eq!(updated.name.as_deref(), Some("Alice"));
+        assert_eq!(updated.device.as_deref(), Some("phone"));
+    }
+}
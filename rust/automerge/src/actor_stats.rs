@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+
+use crate::{ActorId, Automerge};
+
+/// Statistics about the changes and operations created by one actor, returned by
+/// [`Automerge::actor_stats()`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActorStats {
+    pub actor: ActorId,
+    /// The number of operations in changes by this actor
+    pub ops: usize,
+    /// The number of changes by this actor
+    pub changes: usize,
+    /// The largest timestamp of any change by this actor
+    pub last_timestamp: i64,
+    /// The number of operations by this actor which still contribute to the current state of
+    /// the document
+    ///
+    /// An operation contributes if it is a visible value, a mark which has not been deleted, or
+    /// an increment of a visible counter.
+    pub live_ops: usize,
+}
+
+impl ActorStats {
+    /// Whether every operation by this actor has been overwritten or deleted
+    ///
+    /// The changes of an orphaned actor are still needed to merge with peers but the actor no
+    /// longer contributes anything to the current state of the document.
+    pub fn is_orphaned(&self) -> bool {
+        self.live_ops == 0
+    }
+}
+
+impl Automerge {
+    /// Statistics for every actor which has made a change to this document, ordered by actor ID
+    pub fn actor_stats(&self) -> Vec<ActorStats> {
+        let mut stats = HashMap::<&ActorId, ActorStats>::new();
+        for change in self.get_changes(&[]) {
+            let entry = stats
+                .entry(change.actor_id())
+                .or_insert_with(|| ActorStats {
+                    actor: change.actor_id().clone(),
+                    ops: 0,
+                    changes: 0,
+                    last_timestamp: i64::MIN,
+                    live_ops: 0,
+                });
+            entry.ops += change.len();
+            entry.changes += 1;
+            entry.last_timestamp = entry.last_timestamp.max(change.timestamp());
+        }
+
+        let mut live = HashMap::<&ActorId, usize>::new();
+        for (_, _, op) in self.ops().iter() {
+            if op.visible() || (op.is_mark() && op.succ().len() == 0) {
+                *live.entry(op.actor()).or_default() += 1;
+            }
+            if op.is_counter() && op.visible() {
+                for inc in op.succ().filter(|o| o.is_inc()) {
+                    *live.entry(inc.actor()).or_default() += 1;
+                }
+            }
+        }
+
+        let mut stats = stats
+            .into_values()
+            .map(|mut s| {
+                s.live_ops = live.get(&s.actor).copied().unwrap_or(0);
+                s
+            })
+            .collect::<Vec<_>>();
+        stats.sort_by(|a, b| a.actor.cmp(&b.actor));
+        stats
+    }
+
+    /// The actors whose operations no longer contribute anything to the current state of the
+    /// document, see [`ActorStats::is_orphaned()`]
+    pub fn orphaned_actors(&self) -> Vec<ActorId> {
+        self.actor_stats()
+            .into_iter()
+            .filter(ActorStats::is_orphaned)
+            .map(|s| s.actor)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{transaction::Transactable, ActorId, AutoCommit, ScalarValue, ROOT};
+
+    #[test]
+    fn actors_whose_ops_are_overwritten_are_orphaned() {
+        let mut doc = AutoCommit::new().with_actor(ActorId::from(b"aa".to_vec()));
+        doc.put(&ROOT, "a", 1).unwrap();
+        doc.put(&ROOT, "counter", ScalarValue::counter(0)).unwrap();
+        doc.commit();
+
+        let mut other = doc.fork().with_actor(ActorId::from(b"bb".to_vec()));
+        other.put(&ROOT, "b", 1).unwrap();
+        other.put(&ROOT, "b", 2).unwrap();
+        other.commit();
+        let mut incrementer = doc.fork().with_actor(ActorId::from(b"cc".to_vec()));
+        incrementer.increment(&ROOT, "counter", 5).unwrap();
+        doc.merge(&mut other).unwrap();
+        doc.merge(&mut incrementer).unwrap();
+
+        let stats = doc.actor_stats();
+        assert_eq!(stats.len(), 3);
+        assert_eq!(
+            (stats[0].ops, stats[0].changes, stats[0].live_ops),
+            (2, 1, 2)
+        );
+        assert_eq!(
+            (stats[1].ops, stats[1].changes, stats[1].live_ops),
+            (2, 1, 1)
+        );
+        assert_eq!(
+            (stats[2].ops, stats[2].changes, stats[2].live_ops),
+            (1, 1, 1)
+        );
+        assert!(doc.orphaned_actors().is_empty());
+
+        doc.delete(&ROOT, "b").unwrap();
+        assert_eq!(doc.orphaned_actors(), vec![ActorId::from(b"bb".to_vec())]);
+    }
+}
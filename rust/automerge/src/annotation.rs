@@ -0,0 +1,158 @@
+//! Annotations, such as comments, on ranges of text
+//!
+//! An annotation is a mark with a name made from [`ANNOTATION_PREFIX`] and a unique
+//! [`AnnotationId`], so it moves with the text it covers as the text around it is edited and
+//! never merges with other annotations, even ones with the same payload. Inserts at either end of
+//! an annotation are not added to it.
+use std::fmt;
+
+use crate::exid::ExId;
+use crate::marks::{ExpandMark, Mark};
+use crate::transaction::Transactable;
+use crate::{AutomergeError, ReadDoc, ScalarValue};
+
+/// The prefix of the names of the marks which record annotations, in the same `automerge:`
+/// namespace as [`crate::system::SYSTEM_KEY`] so it doesn't clash with marks applications add
+pub const ANNOTATION_PREFIX: &str = "automerge:annotation:";
+
+/// The identity of an annotation, which does not change as the text is edited
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AnnotationId(String);
+
+impl AnnotationId {
+    fn new() -> Self {
+        Self(hex::encode(uuid::Uuid::new_v4().as_bytes()))
+    }
+
+    fn mark_name(&self) -> String {
+        format!("{}{}", ANNOTATION_PREFIX, self.0)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for AnnotationId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<&str> for AnnotationId {
+    fn from(s: &str) -> Self {
+        Self(s.to_string())
+    }
+}
+
+/// An annotation returned by [`crate::ReadDoc::annotations()`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    pub id: AnnotationId,
+    /// The index of the first character the annotation covers
+    pub start: usize,
+    /// The index after the last character the annotation covers
+    pub end: usize,
+    pub payload: ScalarValue,
+}
+
+pub(crate) fn add_annotation<T: Transactable + ?Sized>(
+    tx: &mut T,
+    obj: &ExId,
+    start: usize,
+    end: usize,
+    payload: ScalarValue,
+) -> Result<AnnotationId, AutomergeError> {
+    // a null payload would be read as removing the annotation
+    if payload.is_null() {
+        return Err(AutomergeError::InvalidValueType {
+            expected: "a non null payload".to_string(),
+            unexpected: "null".to_string(),
+        });
+    }
+    let id = AnnotationId::new();
+    tx.mark(
+        obj,
+        Mark::new(id.mark_name(), payload, start, end),
+        ExpandMark::None,
+    )?;
+    Ok(id)
+}
+
+pub(crate) fn remove_annotation<T: Transactable + ?Sized>(
+    tx: &mut T,
+    obj: &ExId,
+    id: &AnnotationId,
+) -> Result<(), AutomergeError> {
+    if let Some(annotation) = annotations(tx, obj)?.into_iter().find(|a| &a.id == id) {
+        tx.unmark(
+            obj,
+            &id.mark_name(),
+            annotation.start,
+            annotation.end,
+            ExpandMark::None,
+        )?;
+    }
+    Ok(())
+}
+
+pub(crate) fn annotations<R: ReadDoc + ?Sized>(
+    doc: &R,
+    obj: &ExId,
+) -> Result<Vec<Annotation>, AutomergeError> {
+    let mut result: Vec<Annotation> = Vec::new();
+    for mark in doc.marks(obj)? {
+        let Some(id) = mark.name().strip_prefix(ANNOTATION_PREFIX) else {
+            continue;
+        };
+        // an annotation which has been partly unmarked may be in several pieces
+        match result.iter_mut().find(|a| a.id.as_str() == id) {
+            Some(annotation) => {
+                annotation.start = annotation.start.min(mark.start);
+                annotation.end = annotation.end.max(mark.end);
+            }
+            None => result.push(Annotation {
+                id: AnnotationId::from(id),
+                start: mark.start,
+                end: mark.end,
+                payload: mark.value().clone(),
+            }),
+        }
+    }
+    result.sort_by(|a, b| (a.start, a.end, &a.id).cmp(&(b.start, b.end, &b.id)));
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{transaction::Transactable, AutoCommit, ObjType, ReadDoc, ROOT};
+
+    #[test]
+    fn annotations_follow_their_text() {
+        let mut doc = AutoCommit::new();
+        let text = doc.put_object(&ROOT, "text", ObjType::Text).unwrap();
+        doc.splice_text(&text, 0, 0, "the quick fox").unwrap();
+        let first = doc.add_annotation(&text, 4, 9, "check this").unwrap();
+        let second = doc.add_annotation(&text, 4, 9, "check this").unwrap();
+        assert_ne!(first, second);
+
+        let mut other = doc.fork();
+        other.splice_text(&text, 0, 0, "see ").unwrap();
+        doc.splice_text(&text, 13, 0, "es").unwrap();
+        doc.merge(&mut other).unwrap();
+
+        let annotations = doc.annotations(&text).unwrap();
+        assert_eq!(annotations.len(), 2);
+        for annotation in &annotations {
+            assert_eq!((annotation.start, annotation.end), (8, 13));
+            assert_eq!(annotation.payload.to_str(), Some("check this"));
+        }
+
+        doc.remove_annotation(&text, &first).unwrap();
+        let annotations = doc.annotations(&text).unwrap();
+        assert_eq!(annotations.len(), 1);
+        assert_eq!(annotations[0].id, second);
+        // a null payload would be read as an unmark
+        assert!(doc.add_annotation(&text, 0, 1, ()).is_err());
+    }
+}
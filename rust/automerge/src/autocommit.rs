@@ -1,22 +1,39 @@
+use std::io::Read;
 use std::ops::RangeBounds;
 
+use crate::actor_metadata::{self, ActorMetadata};
+use crate::annotation::{self, AnnotationId};
 use crate::automerge::SaveOptions;
 use crate::automerge::{current_state, diff};
+use crate::change_store::ChangeStore;
+use crate::counter_overflow;
 use crate::exid::ExId;
+use crate::expiry;
 use crate::iter::Spans;
-use crate::iter::{Keys, ListRange, MapRange, Values};
-use crate::marks::{ExpandMark, Mark, MarkSet};
-use crate::patches::{PatchLog, TextRepresentation};
-use crate::sync::SyncDoc;
-use crate::transaction::{CommitOptions, Transactable};
+use crate::iter::{
+    Keys, KeysRange, ListRange, MapPage, MapPageToken, MapRange, MarkBoundaries, TextChunks, Values,
+};
+use crate::lines;
+use crate::marks::{self, ExpandMark, Mark, MarkMerge, MarkSet};
+use crate::multi_value;
+use crate::patches::{PatchLog, TextEncoding, TextRepresentation};
+use crate::sync::{SyncDoc, SyncSource};
+use crate::system::ReadSystem;
+use crate::text_attribution::{self, TextAttribution};
+use crate::text_index::{self, ReadTextIndex};
+use crate::text_search::TextMatch;
+use crate::transaction::{CanonicalizedLineEnding, CommitOptions, SpliceItem, Transactable};
 use crate::types::Clock;
 use crate::{hydrate, OnPartialLoad};
-use crate::{sync, ObjType, Parents, Patch, ReadDoc, ScalarValue};
+use crate::{
+    sync, ActorStats, Conflicts, CounterOverflow, Frontier, LastModified, MergeReport, ObjType,
+    OpMeta, Parents, Patch, Path, PathStats, ReadDoc, RecordDiff, ScalarValue, ViewAt,
+};
 use crate::{
     transaction::TransactionInner, ActorId, Automerge, AutomergeError, Change, ChangeHash, Cursor,
     Prop, Value,
 };
-use crate::{LoadOptions, VerificationMode};
+use crate::{Affinity, LoadOptions, VerificationMode};
 
 /// An automerge document that automatically manages transactions.
 ///
@@ -99,6 +116,70 @@ impl AutoCommit {
         })
     }
 
+    /// Load a document from `reader` one chunk at a time, see [`Automerge::load_from_reader()`]
+    pub fn load_from_reader<R: std::io::Read>(reader: R) -> Result<Self, AutomergeError> {
+        let doc = Automerge::load_from_reader(reader)?;
+        Ok(Self {
+            doc,
+            transaction: None,
+            patch_log: PatchLog::inactive(TextRepresentation::default()),
+            diff_cursor: Vec::new(),
+            diff_cache: None,
+            save_cursor: Vec::new(),
+            isolation: None,
+        })
+    }
+
+    /// Load a document saved with [`Self::save_encrypted()`], see [`Automerge::load_encrypted()`]
+    pub fn load_encrypted<C: crate::sync::PayloadCipher + ?Sized>(
+        data: &[u8],
+        cipher: &C,
+    ) -> Result<Self, AutomergeError> {
+        let doc = Automerge::load_encrypted(data, cipher)?;
+        Ok(Self {
+            doc,
+            transaction: None,
+            patch_log: PatchLog::inactive(TextRepresentation::default()),
+            diff_cursor: Vec::new(),
+            diff_cache: None,
+            save_cursor: Vec::new(),
+            isolation: None,
+        })
+    }
+
+    /// Load a document saved with [`Self::save_chunked()`], see [`Automerge::load_chunked()`]
+    pub fn load_chunked<F, E>(manifest: &[u8], fetch: F) -> Result<Self, AutomergeError>
+    where
+        F: FnMut(&crate::BlobId) -> Result<Vec<u8>, E>,
+        E: Into<Box<dyn std::error::Error + Send + Sync + 'static>>,
+    {
+        let doc = Automerge::load_chunked(manifest, fetch)?;
+        Ok(Self {
+            doc,
+            transaction: None,
+            patch_log: PatchLog::inactive(TextRepresentation::default()),
+            diff_cursor: Vec::new(),
+            diff_cache: None,
+            save_cursor: Vec::new(),
+            isolation: None,
+        })
+    }
+
+    /// Load as much of a damaged document as possible, see [`Automerge::load_salvage()`]
+    pub fn load_salvage(data: &[u8]) -> (Self, crate::SalvageReport) {
+        let (doc, report) = Automerge::load_salvage(data);
+        let doc = Self {
+            doc,
+            transaction: None,
+            patch_log: PatchLog::inactive(TextRepresentation::default()),
+            diff_cursor: Vec::new(),
+            diff_cache: None,
+            save_cursor: Vec::new(),
+            isolation: None,
+        };
+        (doc, report)
+    }
+
     pub fn load_unverified_heads(data: &[u8]) -> Result<Self, AutomergeError> {
         let doc = Automerge::load_unverified_heads(data)?;
         Ok(Self {
@@ -175,6 +256,15 @@ impl AutoCommit {
         self.doc.make_patches(patch_log)
     }
 
+    /// Generate the patches recorded in `patch_log` one at a time, see
+    /// [`Automerge::make_patches_iter()`]
+    pub fn make_patches_iter<'a>(
+        &'a self,
+        patch_log: &'a mut PatchLog,
+    ) -> impl Iterator<Item = Patch> + 'a {
+        self.doc.make_patches_iter(patch_log)
+    }
+
     /// Generates a diff from `before` to `after`
     ///
     /// By default the diff requires a sequental scan of all the ops in the doc.
@@ -245,6 +335,18 @@ impl AutoCommit {
         self.diff_cache.as_ref().unwrap().1.clone()
     }
 
+    /// Compare the rows of the list `obj` as at `before` and `after`, see
+    /// [`Automerge::diff_records()`]
+    pub fn diff_records(
+        &mut self,
+        obj: &ExId,
+        before: &[ChangeHash],
+        after: &[ChangeHash],
+    ) -> Result<Vec<RecordDiff>, AutomergeError> {
+        self.ensure_transaction_closed();
+        self.doc.diff_records(obj, before, after)
+    }
+
     /// This is a convience function that encapsulates the following common pattern
     /// ```
     /// use automerge::AutoCommit;
@@ -278,9 +380,19 @@ impl AutoCommit {
     }
 
     pub fn fork_at(&mut self, heads: &[ChangeHash]) -> Result<Self, AutomergeError> {
+        self.fork_at_with_actor(heads, ActorId::random())
+    }
+
+    /// Fork this document at the given heads using `actor` as the actor ID of the fork, see
+    /// [`Automerge::fork_at_with_actor()`]
+    pub fn fork_at_with_actor(
+        &mut self,
+        heads: &[ChangeHash],
+        actor: ActorId,
+    ) -> Result<Self, AutomergeError> {
         self.ensure_transaction_closed();
         Ok(Self {
-            doc: self.doc.fork_at(heads)?,
+            doc: self.doc.fork_at_with_actor(heads, actor)?,
             transaction: self.transaction.clone(),
             patch_log: PatchLog::inactive(self.patch_log.text_rep()),
             diff_cursor: vec![],
@@ -336,7 +448,7 @@ impl AutoCommit {
     fn ensure_transaction_closed(&mut self) {
         if let Some((patch_log, tx)) = self.transaction.take() {
             self.patch_log.merge(patch_log);
-            let hash = tx.commit(&mut self.doc, None, None);
+            let hash = tx.commit(&mut self.doc, CommitOptions::default());
             if self.isolation.is_some() && hash.is_some() {
                 self.isolation = hash.map(|h| vec![h])
             }
@@ -375,6 +487,21 @@ impl AutoCommit {
         }
     }
 
+    /// See [`Automerge::apply_changes_from_store()`]
+    pub fn apply_changes_from_store<B: AsRef<[u8]>>(
+        &mut self,
+        store: &mut ChangeStore<B>,
+    ) -> Result<(), AutomergeError> {
+        self.ensure_transaction_closed();
+        if self.isolation.is_some() {
+            self.doc
+                .apply_changes_from_store_log_patches(store, &mut PatchLog::null())
+        } else {
+            self.doc
+                .apply_changes_from_store_log_patches(store, &mut self.patch_log)
+        }
+    }
+
     /// Takes all the changes in `other` which are not in `self` and applies them
     pub fn merge(&mut self, other: &mut AutoCommit) -> Result<Vec<ChangeHash>, AutomergeError> {
         self.ensure_transaction_closed();
@@ -388,11 +515,62 @@ impl AutoCommit {
         }
     }
 
+    /// Work out which properties would be conflicted if `other` were merged into this document,
+    /// see [`Automerge::merge_report()`]
+    pub fn merge_report(&mut self, other: &mut AutoCommit) -> Result<MergeReport, AutomergeError> {
+        self.ensure_transaction_closed();
+        other.ensure_transaction_closed();
+        self.doc.merge_report(&other.doc)
+    }
+
+    /// Replay the changes in `fork` which are not in `self` on top of `self` as new changes, see
+    /// [`Automerge::rebase_from()`]
+    pub fn rebase_from(&mut self, mut fork: AutoCommit) -> Result<Vec<ChangeHash>, AutomergeError> {
+        self.ensure_transaction_closed();
+        fork.ensure_transaction_closed();
+        if self.isolation.is_some() {
+            self.doc
+                .rebase_from_and_log_patches(fork.doc, &mut PatchLog::null())
+        } else {
+            self.doc
+                .rebase_from_and_log_patches(fork.doc, &mut self.patch_log)
+        }
+    }
+
     /// Save the entirety of this document in a compact form.
     pub fn save(&mut self) -> Vec<u8> {
         self.save_with_options(SaveOptions::default())
     }
 
+    /// Save the entirety of this document to `writer`, see [`Automerge::save_to_writer()`]
+    pub fn save_to_writer<W: std::io::Write>(&mut self, writer: W) -> std::io::Result<()> {
+        self.ensure_transaction_closed();
+        self.doc.save_to_writer(writer)?;
+        self.save_cursor = self.doc.get_heads();
+        Ok(())
+    }
+
+    /// Save the entirety of this document encrypted with `cipher`, see
+    /// [`Automerge::save_encrypted()`]
+    pub fn save_encrypted<C: crate::sync::PayloadCipher + ?Sized>(
+        &mut self,
+        cipher: &C,
+    ) -> Vec<u8> {
+        self.ensure_transaction_closed();
+        let bytes = self.doc.save_encrypted(cipher);
+        self.save_cursor = self.doc.get_heads();
+        bytes
+    }
+
+    /// Save this document as blobs of about `target_size` bytes, see
+    /// [`Automerge::save_chunked()`]
+    pub fn save_chunked(&mut self, target_size: usize) -> crate::ChunkedSave {
+        self.ensure_transaction_closed();
+        let save = self.doc.save_chunked(target_size);
+        self.save_cursor = self.doc.get_heads();
+        save
+    }
+
     pub fn save_with_options(&mut self, options: SaveOptions) -> Vec<u8> {
         self.ensure_transaction_closed();
         let bytes = self.doc.save_with_options(options);
@@ -454,6 +632,150 @@ impl AutoCommit {
         self.doc.get_changes(have_deps)
     }
 
+    /// See [`Automerge::actor_stats()`]
+    pub fn actor_stats(&mut self) -> Vec<ActorStats> {
+        self.ensure_transaction_closed();
+        self.doc.actor_stats()
+    }
+
+    /// See [`Automerge::size_by_path()`]
+    pub fn size_by_path(&mut self, depth: usize) -> Vec<PathStats> {
+        self.ensure_transaction_closed();
+        self.doc.size_by_path(depth)
+    }
+
+    /// See [`Automerge::orphaned_actors()`]
+    pub fn orphaned_actors(&mut self) -> Vec<ActorId> {
+        self.ensure_transaction_closed();
+        self.doc.orphaned_actors()
+    }
+
+    /// Set how a counter is read once increments have taken it past `i64::MIN` or `i64::MAX`
+    ///
+    /// This is recorded in the document, see [`crate::counter_overflow`], so every peer uses the
+    /// same setting. Patterns set with [`Self::set_counter_overflow_for_keys()`] take precedence
+    /// over this.
+    pub fn set_counter_overflow(
+        &mut self,
+        overflow: CounterOverflow,
+    ) -> Result<(), AutomergeError> {
+        counter_overflow::set_counter_overflow(self, overflow)?;
+        self.doc.refresh_counter_overflow()
+    }
+
+    /// Set how a counter at a map key matching `pattern` is read once increments have taken it
+    /// past `i64::MIN` or `i64::MAX`
+    ///
+    /// A pattern ending in `*` matches every key which starts with the rest of the pattern, any
+    /// other pattern only matches the key which is equal to it. If several patterns match a key
+    /// the most recently set one is used.
+    pub fn set_counter_overflow_for_keys(
+        &mut self,
+        pattern: &str,
+        overflow: CounterOverflow,
+    ) -> Result<(), AutomergeError> {
+        counter_overflow::set_counter_overflow_for_keys(self, pattern, overflow)?;
+        self.doc.refresh_counter_overflow()
+    }
+
+    /// See [`Automerge::missing_deps()`]
+    pub fn missing_deps(&mut self) -> Vec<ChangeHash> {
+        self.ensure_transaction_closed();
+        self.doc.missing_deps()
+    }
+
+    /// See [`Automerge::pending_changes_len()`]
+    pub fn pending_changes_len(&self) -> usize {
+        self.doc.pending_changes_len()
+    }
+
+    /// See [`Automerge::pending_changes()`]
+    pub fn pending_changes(&self) -> &[Change] {
+        self.doc.pending_changes()
+    }
+
+    /// See [`Automerge::take_pending_changes()`]
+    pub fn take_pending_changes(&mut self) -> Vec<Change> {
+        self.doc.take_pending_changes()
+    }
+
+    /// See [`Automerge::set_max_pending_changes()`]
+    pub fn set_max_pending_changes(&mut self, max: Option<usize>) {
+        self.doc.set_max_pending_changes(max)
+    }
+
+    /// See [`Automerge::state_hash()`]
+    pub fn state_hash(&mut self) -> [u8; 32] {
+        crate::automerge::hash_heads(&self.get_heads())
+    }
+
+    /// See [`Automerge::same_state_as()`]
+    pub fn same_state_as(&mut self, other: &mut AutoCommit) -> bool {
+        self.get_heads() == other.get_heads()
+    }
+
+    /// Set whether `\r\n` is replaced with `\n` in text inserted into text objects
+    ///
+    /// This applies to [`Transactable::splice_text()`] and [`Transactable::update_text()`], so
+    /// text pasted on Windows and on other platforms ends up the same. Line endings which are
+    /// split across two splices are left alone. Where replacements were made in a transaction
+    /// can be found with [`Self::canonicalized_line_endings()`] or
+    /// [`crate::transaction::Transaction::canonicalized_line_endings()`]. The setting is
+    /// recorded in the document, see [`crate::transaction::LINE_ENDINGS_KEY`], so every peer
+    /// uses it.
+    pub fn set_canonicalize_line_endings(&mut self, enabled: bool) -> Result<(), AutomergeError> {
+        crate::transaction::set_canonicalize_line_endings(self, enabled)
+    }
+
+    /// See [`Automerge::canonicalizes_line_endings()`]
+    pub fn canonicalizes_line_endings(&self) -> Result<bool, AutomergeError> {
+        crate::transaction::canonicalizes_line_endings(self)
+    }
+
+    /// Where `\r\n` was replaced with `\n` in the transaction in progress, see
+    /// [`Self::set_canonicalize_line_endings()`]
+    pub fn canonicalized_line_endings(&self) -> &[CanonicalizedLineEnding] {
+        self.transaction
+            .as_ref()
+            .map(|(_, tx)| tx.canonicalized_line_endings())
+            .unwrap_or_default()
+    }
+
+    /// Set the expand behaviour used by [`Transactable::mark_with_default_expand()`] for marks
+    /// named `name`
+    ///
+    /// This is recorded in the document, see [`marks::MARK_EXPAND_KEY`], so every peer uses the
+    /// same default. The expand behaviour of a mark is also recorded in the mark itself, so
+    /// concurrent inserts at the boundaries of the mark are expanded into it the same way on
+    /// every peer.
+    pub fn set_mark_expand(
+        &mut self,
+        name: &str,
+        expand: ExpandMark,
+    ) -> Result<(), AutomergeError> {
+        marks::set_mark_expand(self, name, expand)
+    }
+
+    /// See [`Automerge::mark_expand()`]
+    pub fn mark_expand(&self, name: &str) -> Result<ExpandMark, AutomergeError> {
+        marks::mark_expand(self, name)
+    }
+
+    /// Set how the values of overlapping marks named `name` are combined when reading marks
+    ///
+    /// By default the value of the most recent mark is used, see [`MarkMerge`] for the
+    /// alternatives. This is recorded in the document, see [`marks::MARK_MERGE_KEY`], so every
+    /// peer reads the marks the same way.
+    pub fn set_mark_merge(&mut self, name: &str, merge: MarkMerge) -> Result<(), AutomergeError> {
+        marks::set_mark_merge(self, name, merge)?;
+        self.doc.refresh_mark_merge()
+    }
+
+    /// See [`Automerge::set_collapse_identical_conflicts()`]
+    pub fn set_collapse_identical_conflicts(&mut self, enabled: bool) {
+        self.doc.set_collapse_identical_conflicts(enabled)
+    }
+
     pub fn get_change_by_hash(&mut self, hash: &ChangeHash) -> Option<&Change> {
         self.ensure_transaction_closed();
         self.doc.get_change_by_hash(hash)
@@ -493,6 +815,22 @@ impl AutoCommit {
         self.doc.visualise_optree(objects)
     }
 
+    /// See [`Automerge::view_at()`]
+    pub fn view_at(&self, heads: &[ChangeHash]) -> ViewAt<'_> {
+        self.doc.view_at(heads)
+    }
+
+    /// See [`Automerge::clock_at()`]
+    pub fn clock_at(&self, heads: &[ChangeHash]) -> Clock {
+        self.doc.clock_at(heads)
+    }
+
+    /// See [`Automerge::hydrate_tagged()`]
+    #[cfg(feature = "json")]
+    pub fn hydrate_tagged(&self, heads: Option<&[ChangeHash]>) -> serde_json::Value {
+        self.doc.hydrate_tagged(heads)
+    }
+
     /// Get the current heads of the document.
     ///
     /// This closes the transaction first, if one is in progress.
@@ -505,6 +843,11 @@ impl AutoCommit {
         }
     }
 
+    /// Get the current heads of the document as a [`Frontier`], see [`Self::get_heads()`]
+    pub fn get_frontier(&mut self) -> Frontier {
+        self.get_heads().into()
+    }
+
     pub fn set_text_rep(&mut self, text_rep: TextRepresentation) {
         self.patch_log.set_text_rep(text_rep)
     }
@@ -547,7 +890,7 @@ impl AutoCommit {
         self.ensure_transaction_open();
         let (patch_log, tx) = self.transaction.take().unwrap();
         self.patch_log.merge(patch_log);
-        let hash = tx.commit(&mut self.doc, options.message, options.time);
+        let hash = tx.commit(&mut self.doc, options);
         if self.isolation.is_some() && hash.is_some() {
             self.isolation = hash.map(|h| vec![h])
         }
@@ -574,7 +917,7 @@ impl AutoCommit {
     pub fn empty_change(&mut self, options: CommitOptions) -> ChangeHash {
         self.ensure_transaction_closed();
         let args = self.doc.transaction_args(None);
-        TransactionInner::empty(&mut self.doc, args, options.message, options.time)
+        TransactionInner::empty(&mut self.doc, args, options)
     }
 
     /// An implementation of [`crate::sync::SyncDoc`] for this autocommit
@@ -627,6 +970,260 @@ impl AutoCommit {
     }
 }
 
+impl ReadSystem for AutoCommit {
+    fn system_objects(&self) -> Vec<ExId> {
+        self.doc.system_objects_for(self.get_scope(None))
+    }
+}
+
+impl ReadTextIndex for AutoCommit {
+    fn convert_text_index(
+        &self,
+        obj: &ExId,
+        heads: Option<&[ChangeHash]>,
+        index: usize,
+        from: TextEncoding,
+        to: TextEncoding,
+    ) -> Result<usize, AutomergeError> {
+        self.doc
+            .convert_text_index_for(obj, index, from, to, self.get_scope(heads))
+    }
+}
+
+impl AutoCommit {
+    /// See [`Automerge::keys_range()`]
+    pub fn keys_range<'a, O: AsRef<ExId>, R: RangeBounds<String> + 'a>(
+        &'a self,
+        obj: O,
+        range: R,
+    ) -> KeysRange<'a, R> {
+        self.doc
+            .keys_range_for(obj.as_ref(), range, self.get_scope(None))
+    }
+
+    /// See [`Automerge::keys_range_at()`]
+    pub fn keys_range_at<'a, O: AsRef<ExId>, R: RangeBounds<String> + 'a>(
+        &'a self,
+        obj: O,
+        range: R,
+        heads: &[ChangeHash],
+    ) -> KeysRange<'a, R> {
+        self.doc
+            .keys_range_for(obj.as_ref(), range, self.get_scope(Some(heads)))
+    }
+
+    /// See [`Automerge::map_page()`]
+    pub fn map_page<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        prefix: &str,
+        limit: usize,
+        continue_from: Option<&MapPageToken>,
+    ) -> MapPage<'_> {
+        self.doc.map_page_for(
+            obj.as_ref(),
+            prefix,
+            limit,
+            continue_from,
+            self.get_scope(None),
+        )
+    }
+
+    /// See [`Automerge::map_page_at()`]
+    pub fn map_page_at<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        prefix: &str,
+        limit: usize,
+        continue_from: Option<&MapPageToken>,
+        heads: &[ChangeHash],
+    ) -> MapPage<'_> {
+        self.doc.map_page_for(
+            obj.as_ref(),
+            prefix,
+            limit,
+            continue_from,
+            self.get_scope(Some(heads)),
+        )
+    }
+
+    /// See [`Automerge::text_chunks()`]
+    pub fn text_chunks<O: AsRef<ExId>>(&self, obj: O) -> Result<TextChunks<'_>, AutomergeError> {
+        self.doc.text_chunks_for(obj.as_ref(), self.get_scope(None))
+    }
+
+    /// See [`Automerge::text_display_width()`]
+    pub fn text_display_width<O: AsRef<ExId>, R: RangeBounds<usize>>(
+        &self,
+        obj: O,
+        range: R,
+    ) -> Result<usize, AutomergeError> {
+        self.doc
+            .text_display_width_for(obj.as_ref(), range, self.get_scope(None))
+    }
+
+    /// See [`Automerge::find_text()`]
+    pub fn find_text<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        needle: &str,
+    ) -> Result<Vec<TextMatch>, AutomergeError> {
+        self.doc
+            .find_text_for(obj.as_ref(), needle, self.get_scope(None))
+    }
+
+    /// See [`Automerge::get_unexpired()`]
+    pub fn get_unexpired<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+        now: i64,
+    ) -> Result<Option<(Value<'_>, ExId)>, AutomergeError> {
+        expiry::get_unexpired(self, obj.as_ref(), prop.into(), now)
+    }
+
+    /// See [`Automerge::actor_metadata()`]
+    pub fn actor_metadata(&self, actor: &ActorId) -> Result<Option<ActorMetadata>, AutomergeError> {
+        actor_metadata::actor_metadata(self, actor)
+    }
+
+    /// See [`Automerge::is_multi_value()`]
+    pub fn is_multi_value<O: AsRef<ExId>>(&self, obj: O) -> Result<bool, AutomergeError> {
+        multi_value::is_multi_value(self, obj.as_ref())
+    }
+
+    /// See [`Automerge::get_all_values()`]
+    pub fn get_all_values<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+    ) -> Result<Vec<(Value<'_>, ExId)>, AutomergeError> {
+        multi_value::get_all_values(self, obj.as_ref(), prop.into())
+    }
+
+    /// See [`Automerge::mark_boundaries()`]
+    pub fn mark_boundaries<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+    ) -> Result<MarkBoundaries<'_>, AutomergeError> {
+        self.doc
+            .mark_boundaries_for(obj.as_ref(), self.get_scope(None))
+    }
+
+    /// See [`Automerge::mark_boundaries_at()`]
+    pub fn mark_boundaries_at<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        heads: &[ChangeHash],
+    ) -> Result<MarkBoundaries<'_>, AutomergeError> {
+        self.doc
+            .mark_boundaries_for(obj.as_ref(), self.get_scope(Some(heads)))
+    }
+
+    /// See [`Automerge::get_by_elem_id()`]
+    pub fn get_by_elem_id<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        elem_id: &ExId,
+    ) -> Result<Option<(usize, Value<'_>, ExId)>, AutomergeError> {
+        self.doc
+            .get_by_elem_id_for(obj.as_ref(), elem_id, self.get_scope(None))
+    }
+
+    /// See [`Automerge::is_sealed()`]
+    pub fn is_sealed<O: AsRef<ExId>>(&self, obj: O) -> Result<bool, AutomergeError> {
+        self.doc.is_sealed_for(obj.as_ref(), self.get_scope(None))
+    }
+
+    /// See [`Automerge::attribute_text()`]
+    pub fn attribute_text<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        heads: Option<&[ChangeHash]>,
+    ) -> Result<Vec<TextAttribution>, AutomergeError> {
+        text_attribution::attribute_text(self, &self.doc, obj.as_ref(), heads)
+    }
+
+    /// See [`Automerge::get_cursor_with_encoding()`]
+    pub fn get_cursor_with_encoding<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        position: usize,
+        at: Option<&[ChangeHash]>,
+        encoding: TextEncoding,
+    ) -> Result<Cursor, AutomergeError> {
+        text_index::get_cursor(self, obj.as_ref(), position, at, encoding)
+    }
+
+    /// See [`Automerge::get_cursor_position_with_encoding()`]
+    pub fn get_cursor_position_with_encoding<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        cursor: &Cursor,
+        at: Option<&[ChangeHash]>,
+        encoding: TextEncoding,
+    ) -> Result<usize, AutomergeError> {
+        text_index::get_cursor_position(self, obj.as_ref(), cursor, at, encoding)
+    }
+
+    /// See [`Automerge::text_len()`]
+    pub fn text_len<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        encoding: TextEncoding,
+    ) -> Result<usize, AutomergeError> {
+        self.doc
+            .text_width_for(obj.as_ref(), encoding, self.get_scope(None))
+    }
+
+    /// See [`Automerge::get_conflicts()`]
+    pub fn get_conflicts<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+    ) -> Result<Conflicts<'_>, AutomergeError> {
+        self.doc
+            .get_conflicts_for(obj.as_ref(), prop.into(), self.get_scope(None))
+    }
+
+    /// See [`Automerge::get_conflicts_at()`]
+    pub fn get_conflicts_at<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+        heads: &[ChangeHash],
+    ) -> Result<Conflicts<'_>, AutomergeError> {
+        self.doc
+            .get_conflicts_for(obj.as_ref(), prop.into(), self.get_scope(Some(heads)))
+    }
+
+    /// See [`Automerge::last_modified()`]
+    pub fn last_modified<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+    ) -> Result<Option<LastModified>, AutomergeError> {
+        self.doc
+            .last_modified_for(obj.as_ref(), prop.into(), self.get_scope(None))
+    }
+
+    /// See [`Automerge::last_modified_at()`]
+    pub fn last_modified_at<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+        heads: &[ChangeHash],
+    ) -> Result<Option<LastModified>, AutomergeError> {
+        self.doc
+            .last_modified_for(obj.as_ref(), prop.into(), self.get_scope(Some(heads)))
+    }
+
+    /// See [`Automerge::op_meta()`]
+    pub fn op_meta(&self, id: &ExId) -> Option<OpMeta> {
+        self.doc.op_meta_for(id)
+    }
+}
+
 impl ReadDoc for AutoCommit {
     fn parents<O: AsRef<ExId>>(&self, obj: O) -> Result<Parents<'_>, AutomergeError> {
         self.doc.parents_for(obj.as_ref(), self.get_scope(None))
@@ -866,6 +1463,164 @@ impl Transactable for AutoCommit {
         tx.put_object(&mut self.doc, patch_log, obj.as_ref(), prop, value)
     }
 
+    #[cfg(feature = "json")]
+    fn put_json<O: AsRef<ExId>, P: Into<Prop>>(
+        &mut self,
+        obj: O,
+        prop: P,
+        value: &serde_json::Value,
+    ) -> Result<Vec<ExId>, AutomergeError> {
+        self.ensure_transaction_open();
+        let (patch_log, tx) = self.transaction.as_mut().unwrap();
+        tx.put_json(&mut self.doc, patch_log, obj.as_ref(), prop.into(), value)
+    }
+
+    fn put_at_path<V: Into<ScalarValue>>(
+        &mut self,
+        path: &Path,
+        value: V,
+    ) -> Result<(), AutomergeError> {
+        self.ensure_transaction_open();
+        let (patch_log, tx) = self.transaction.as_mut().unwrap();
+        tx.put_at_path(&mut self.doc, patch_log, path, value)
+    }
+
+    fn delete_at_path(&mut self, path: &Path) -> Result<(), AutomergeError> {
+        self.ensure_transaction_open();
+        let (patch_log, tx) = self.transaction.as_mut().unwrap();
+        tx.delete_at_path(&mut self.doc, patch_log, path)
+    }
+
+    fn put_with_expiry<O: AsRef<ExId>, P: Into<Prop>, V: Into<ScalarValue>>(
+        &mut self,
+        obj: O,
+        prop: P,
+        value: V,
+        expires_at: i64,
+    ) -> Result<ExId, AutomergeError> {
+        expiry::put_with_expiry(self, obj.as_ref(), prop.into(), value.into(), expires_at)
+    }
+
+    fn gc_expired(&mut self, now: i64) -> Result<usize, AutomergeError> {
+        expiry::gc_expired(self, now)
+    }
+
+    fn set_actor_metadata(
+        &mut self,
+        actor: &ActorId,
+        metadata: &ActorMetadata,
+    ) -> Result<(), AutomergeError> {
+        actor_metadata::set_actor_metadata(self, actor, metadata)
+    }
+
+    fn set_multi_value<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        enabled: bool,
+    ) -> Result<(), AutomergeError> {
+        multi_value::set_multi_value(self, obj.as_ref(), enabled)
+    }
+
+    fn seal_object<O: AsRef<ExId>>(&mut self, obj: O) -> Result<(), AutomergeError> {
+        self.ensure_transaction_open();
+        let (_, tx) = self.transaction.as_mut().unwrap();
+        tx.seal_object(&mut self.doc, obj.as_ref())
+    }
+
+    fn splice_text_from_reader<O: AsRef<ExId>, R: Read>(
+        &mut self,
+        obj: O,
+        pos: usize,
+        del: isize,
+        reader: R,
+        limit: Option<usize>,
+    ) -> Result<usize, AutomergeError> {
+        text_index::splice_text_from_reader(self, obj.as_ref(), pos, del, reader, limit)
+    }
+
+    fn insert_line<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        index: usize,
+        line: &str,
+    ) -> Result<(), AutomergeError> {
+        lines::insert_line(self, obj.as_ref(), index, line)
+    }
+
+    fn insert_embed<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        index: usize,
+    ) -> Result<ExId, AutomergeError> {
+        text_index::insert_embed(self, obj.as_ref(), index)
+    }
+
+    fn unmark_where<O, F>(&mut self, obj: O, predicate: F) -> Result<(), AutomergeError>
+    where
+        O: AsRef<ExId>,
+        F: FnMut(&Mark<'_>) -> bool,
+    {
+        marks::unmark_where(self, obj.as_ref(), predicate)
+    }
+
+    fn add_annotation<O: AsRef<ExId>, V: Into<ScalarValue>>(
+        &mut self,
+        obj: O,
+        start: usize,
+        end: usize,
+        payload: V,
+    ) -> Result<AnnotationId, AutomergeError> {
+        annotation::add_annotation(self, obj.as_ref(), start, end, payload.into())
+    }
+
+    fn remove_annotation<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        id: &AnnotationId,
+    ) -> Result<(), AutomergeError> {
+        annotation::remove_annotation(self, obj.as_ref(), id)
+    }
+
+    fn splice_text_with_encoding<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        pos: usize,
+        del: isize,
+        text: &str,
+        encoding: TextEncoding,
+    ) -> Result<(), AutomergeError> {
+        text_index::splice_text(self, obj.as_ref(), pos, del, text, encoding)
+    }
+
+    fn splice_text_with_cursors<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        pos: usize,
+        del: isize,
+        text: &str,
+        affinity: Affinity,
+    ) -> Result<(Cursor, Cursor), AutomergeError> {
+        text_index::splice_text_with_cursors(self, obj.as_ref(), pos, del, text, affinity)
+    }
+
+    fn mark_with_encoding<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        mark: Mark<'_>,
+        expand: ExpandMark,
+        encoding: TextEncoding,
+    ) -> Result<(), AutomergeError> {
+        text_index::mark(self, obj.as_ref(), mark, expand, encoding)
+    }
+
+    fn apply_unified_diff<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        diff: &str,
+    ) -> Result<(), crate::error::UnifiedDiffError> {
+        crate::transaction::apply_unified_diff(self, obj.as_ref(), diff)
+    }
+
     fn insert<O: AsRef<ExId>, V: Into<ScalarValue>>(
         &mut self,
         obj: O,
@@ -923,6 +1678,18 @@ impl Transactable for AutoCommit {
         tx.splice(&mut self.doc, patch_log, obj.as_ref(), pos, del, vals)
     }
 
+    fn splice_full<O: AsRef<ExId>, I: IntoIterator<Item = SpliceItem>>(
+        &mut self,
+        obj: O,
+        pos: usize,
+        del: isize,
+        items: I,
+    ) -> Result<Vec<ExId>, AutomergeError> {
+        self.ensure_transaction_open();
+        let (patch_log, tx) = self.transaction.as_mut().unwrap();
+        tx.splice_full(&mut self.doc, patch_log, obj.as_ref(), pos, del, items)
+    }
+
     fn splice_text<O: AsRef<ExId>>(
         &mut self,
         obj: O,
@@ -947,6 +1714,15 @@ impl Transactable for AutoCommit {
         tx.mark(&mut self.doc, patch_log, obj.as_ref(), mark, expand)
     }
 
+    fn mark_with_default_expand<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        mark: Mark<'_>,
+    ) -> Result<(), AutomergeError> {
+        let expand = marks::mark_expand(self, mark.name())?;
+        self.mark(obj, mark, expand)
+    }
+
     fn unmark<O: AsRef<ExId>>(
         &mut self,
         obj: O,
@@ -1042,6 +1818,44 @@ impl<'a> SyncDoc for SyncWrapper<'a> {
         self.inner.doc.generate_sync_message(sync_state)
     }
 
+    fn generate_sync_message_with_max_size(
+        &self,
+        sync_state: &mut sync::State,
+        max_message_size: usize,
+    ) -> Option<sync::Message> {
+        self.inner
+            .doc
+            .generate_sync_message_with_max_size(sync_state, max_message_size)
+    }
+
+    fn generate_sync_messages_iter<'b>(
+        &'b self,
+        sync_state: &'b mut sync::State,
+        max_message_size: usize,
+    ) -> sync::SyncMessages<'b> {
+        self.inner
+            .doc
+            .generate_sync_messages_iter(sync_state, max_message_size)
+    }
+
+    fn generate_sync_message_for<O: AsRef<ExId>>(
+        &self,
+        sync_state: &mut sync::State,
+        obj: O,
+    ) -> Result<Option<sync::Message>, AutomergeError> {
+        self.inner.doc.generate_sync_message_for(sync_state, obj)
+    }
+
+    fn generate_sync_message_with_config(
+        &self,
+        sync_state: &mut sync::State,
+        config: &mut sync::Config,
+    ) -> Option<sync::Message> {
+        self.inner
+            .doc
+            .generate_sync_message_with_config(sync_state, config)
+    }
+
     fn receive_sync_message(
         &mut self,
         sync_state: &mut sync::State,
@@ -1075,6 +1889,30 @@ impl<'a> SyncDoc for SyncWrapper<'a> {
             .doc
             .receive_sync_message_log_patches(sync_state, message, patch_log)
     }
+
+    fn receive_sync_message_with_config(
+        &mut self,
+        sync_state: &mut sync::State,
+        config: &sync::Config,
+        message: sync::Message,
+    ) -> Result<(), AutomergeError> {
+        self.inner.ensure_transaction_closed();
+        if self.inner.isolation.is_some() {
+            self.inner.doc.receive_sync_message_inner(
+                sync_state,
+                config,
+                message,
+                &mut PatchLog::null(),
+            )
+        } else {
+            self.inner.doc.receive_sync_message_inner(
+                sync_state,
+                config,
+                message,
+                &mut self.inner.patch_log,
+            )
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
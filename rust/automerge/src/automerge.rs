@@ -2,21 +2,40 @@ use std::cmp::Ordering;
 use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fmt::Debug;
 use std::num::NonZeroU64;
-use std::ops::RangeBounds;
+use std::ops::{Bound, RangeBounds};
 
 use itertools::Itertools;
+use sha2::{Digest, Sha256};
 
+use crate::actor_metadata::{self, ActorMetadata};
 use crate::change_graph::ChangeGraph;
+use crate::change_store::ChangeStore;
+use crate::clock::ClockData;
 use crate::columnar::Key as EncodedKey;
+use crate::counter_overflow::{self, CounterOverflow, CounterOverflowPolicy};
+use crate::cursor::{Affinity, Anchor};
 use crate::exid::ExId;
-use crate::iter::{Keys, ListRange, MapRange, Spans, Values};
-use crate::marks::{Mark, MarkAccumulator, MarkSet, MarkStateMachine};
-use crate::op_set::{OpSet, OpSetData};
+use crate::expiry;
+use crate::iter::{
+    Keys, KeysRange, ListRange, MapPage, MapPageToken, MapRange, MarkBoundaries, Spans, TextChunks,
+    Values,
+};
+use crate::marks::{self, ExpandMark, Mark, MarkAccumulator, MarkSet, MarkStateMachine};
+use crate::multi_value;
+use crate::op_set::{OpIdx, OpSet, OpSetData};
+use crate::op_tree::FoundOpWithPatchLog;
 use crate::parents::Parents;
-use crate::patches::{Patch, PatchLog, TextRepresentation};
+use crate::patches::{Patch, PatchLog, TextEncoding, TextRepresentation};
 use crate::query;
 use crate::read::ReadDocInternal;
-use crate::storage::{self, load, CompressConfig, VerificationMode};
+use crate::storage::{
+    self, change::DEFLATE_MIN_SIZE, load, BlobId, ChunkedSave, Codec, CompressConfig, Compression,
+    SalvageReport, VerificationMode,
+};
+use crate::system;
+use crate::text_attribution::{self, TextAttribution};
+use crate::text_index;
+use crate::text_search::{self, TextMatch};
 use crate::transaction::{
     self, CommitOptions, Failure, Success, Transactable, Transaction, TransactionArgs,
 };
@@ -25,10 +44,15 @@ use crate::types::{
     ObjMeta, OpBuilder, OpId, OpIds, OpType, Value,
 };
 use crate::{hydrate, ScalarValue};
-use crate::{AutomergeError, Change, Cursor, ObjType, Prop, ReadDoc};
+use crate::{
+    AutomergeError, Change, Conflicts, Cursor, Frontier, LastModified, ObjType, OpMeta, Prop,
+    ReadDoc,
+};
 
 pub(crate) mod current_state;
 pub(crate) mod diff;
+mod view_at;
+pub use view_at::ViewAt;
 
 #[cfg(test)]
 mod tests;
@@ -184,6 +208,10 @@ pub struct Automerge {
     actor: Actor,
     /// The maximum operation counter this document has seen.
     max_op: u64,
+    /// Whether conflicting values which are identical scalars are reported as one value
+    collapse_identical_conflicts: bool,
+    /// The most changes which may wait in `queue`
+    max_pending_changes: Option<usize>,
 }
 
 impl Automerge {
@@ -199,6 +227,8 @@ impl Automerge {
             deps: Default::default(),
             actor: Actor::Unused(ActorId::random()),
             max_op: 0,
+            collapse_identical_conflicts: false,
+            max_pending_changes: None,
         }
     }
 
@@ -454,26 +484,53 @@ impl Automerge {
     ///
     /// This will create a new actor ID for the forked document
     pub fn fork_at(&self, heads: &[ChangeHash]) -> Result<Self, AutomergeError> {
+        self.fork_at_with_actor(heads, ActorId::random())
+    }
+
+    /// Fork this document at the given heads, using `actor` as the actor ID of the fork
+    ///
+    /// Forking at the current heads of the document is as cheap as [`Self::fork()`]. Forking at
+    /// historical heads replays the changes which are ancestors of `heads` in the order they were
+    /// applied to this document, so none of them have to wait in the queue for their
+    /// dependencies.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AutomergeError::InvalidHash`] if any of `heads` are not in this document
+    pub fn fork_at_with_actor(
+        &self,
+        heads: &[ChangeHash],
+        actor: ActorId,
+    ) -> Result<Self, AutomergeError> {
+        let mut sorted_heads = heads.to_vec();
+        sorted_heads.sort_unstable();
+        sorted_heads.dedup();
+        if self.queue.is_empty() && sorted_heads == self.get_heads() {
+            let mut f = self.clone();
+            f.set_actor(actor);
+            return Ok(f);
+        }
         let mut seen = heads.iter().cloned().collect::<HashSet<_>>();
         let mut heads = heads.to_vec();
-        let mut changes = vec![];
+        let mut indices = vec![];
         while let Some(hash) = heads.pop() {
             if let Some(idx) = self.history_index.get(&hash) {
                 let change = &self.history[*idx];
                 for dep in change.deps() {
-                    if !seen.contains(dep) {
+                    if seen.insert(*dep) {
                         heads.push(*dep);
                     }
                 }
-                changes.push(change);
-                seen.insert(hash);
+                indices.push(*idx);
             } else {
                 return Err(AutomergeError::InvalidHash(hash));
             }
         }
+        // `history` is in the order changes were applied, which is a topological order
+        indices.sort_unstable();
         let mut f = Self::new();
-        f.set_actor(ActorId::random());
-        f.apply_changes(changes.into_iter().rev().cloned())?;
+        f.set_actor(actor);
+        f.apply_changes(indices.into_iter().map(|idx| self.history[idx].clone()))?;
         Ok(f)
     }
 
@@ -508,17 +565,29 @@ impl Automerge {
         cursor: &Cursor,
         clock: Option<&Clock>,
     ) -> Result<OpId, AutomergeError> {
-        if let Some(idx) = self.ops.osd.actors.lookup(cursor.actor()) {
-            let opid = OpId::new(cursor.ctr(), idx);
-            match clock {
-                Some(clock) if !clock.covers(&opid) => {
-                    Err(AutomergeError::InvalidCursor(cursor.clone()))
-                }
-                _ => Ok(opid),
-            }
-        } else {
-            Err(AutomergeError::InvalidCursor(cursor.clone()))
+        self.elem_to_opid(cursor.ctr(), cursor.actor(), clock)
+            .ok_or_else(|| AutomergeError::InvalidCursor(cursor.clone()))
+    }
+
+    fn elem_to_opid(&self, ctr: u64, actor: &ActorId, clock: Option<&Clock>) -> Option<OpId> {
+        let opid = OpId::new(ctr, self.ops.osd.actors.lookup(actor)?);
+        match clock {
+            Some(clock) if !clock.covers(&opid) => None,
+            _ => Some(opid),
+        }
+    }
+
+    /// Like [`Self::exid_to_obj()`] but fails if the object has been sealed
+    pub(crate) fn exid_to_writable_obj(&self, id: &ExId) -> Result<ObjMeta, AutomergeError> {
+        let obj = self.exid_to_obj(id)?;
+        // we don't know what operations are valid in objects of unknown types
+        if let ObjType::Unknown(_) = obj.typ {
+            return Err(AutomergeError::InvalidOp(obj.typ));
+        }
+        if self.ops.seals.is_sealed(&obj.id, None) {
+            return Err(AutomergeError::ObjectSealed(id.clone()));
         }
+        Ok(obj)
     }
 
     pub(crate) fn exid_to_obj(&self, id: &ExId) -> Result<ObjMeta, AutomergeError> {
@@ -536,6 +605,40 @@ impl Automerge {
         Self::load_with_options(data, Default::default())
     }
 
+    /// Load a document from `reader` one chunk at a time
+    ///
+    /// A saved document is a sequence of chunks, a document chunk followed by any changes
+    /// appended to it with [`Self::save_after()`] or [`crate::save_diff()`]. [`Self::load()`]
+    /// needs all of them in memory at once, while this only holds the chunk it is loading, which
+    /// matters for documents of hundreds of megabytes. The result is the same as passing
+    /// everything `reader` returns to [`Self::load()`].
+    pub fn load_from_reader<R: std::io::Read>(mut reader: R) -> Result<Self, AutomergeError> {
+        let Some((first_type, first_chunk)) = load::read_chunk(&mut reader)? else {
+            return Ok(Self::new());
+        };
+        // missing dependencies are checked once all the chunks have been loaded
+        let mut doc = Self::load_with_options(
+            &first_chunk,
+            LoadOptions::new().on_partial_load(OnPartialLoad::Ignore),
+        )?;
+        drop(first_chunk);
+        while let Some((_, chunk)) = load::read_chunk(&mut reader)? {
+            match load::load_changes(storage::parse::Input::new(&chunk)) {
+                load::LoadedChanges::Complete(changes) => doc.apply_changes(changes)?,
+                load::LoadedChanges::Partial { error, .. } => return Err(error.into()),
+            }
+        }
+        // Only allow missing deps if the first chunk was a document chunk, as in `load`
+        let first_is_document = matches!(
+            first_type,
+            storage::ChunkType::Document | storage::ChunkType::ZstdDocument
+        );
+        if !doc.queue.is_empty() && !first_is_document {
+            return Err(AutomergeError::MissingDeps);
+        }
+        Ok(doc)
+    }
+
     /// Load a document without verifying the head hashes
     ///
     /// This is useful for debugging as it allows you to examine a corrupted document.
@@ -661,6 +764,18 @@ impl Automerge {
         patch_log.make_patches(self)
     }
 
+    /// Create the patches from a [`PatchLog`] one at a time
+    ///
+    /// This produces the same patches as [`Self::make_patches()`] but builds each patch as it is
+    /// requested rather than collecting them all into a [`Vec`], so a large merge doesn't have to
+    /// hold every patch in memory before the first can be processed.
+    pub fn make_patches_iter<'a>(
+        &'a self,
+        patch_log: &'a mut PatchLog,
+    ) -> impl Iterator<Item = Patch> + 'a {
+        patch_log.make_patches_iter(self)
+    }
+
     /// Get a set of [`Patch`]es which materialize the current state of the document
     ///
     /// This is a convienence method for [`doc.diff(&[], current_heads)`][diff]
@@ -744,17 +859,66 @@ impl Automerge {
         )
     }
 
+    /// Apply the changes in `store` which are not in this document
+    ///
+    /// Only the changes this document doesn't have are decompressed, so bringing a document up to
+    /// date from a store holding its whole history only costs as much as the new changes. The
+    /// applied changes are kept in the document's history like any others.
+    pub fn apply_changes_from_store<B: AsRef<[u8]>>(
+        &mut self,
+        store: &mut ChangeStore<B>,
+    ) -> Result<(), AutomergeError> {
+        self.apply_changes_from_store_log_patches(
+            store,
+            &mut PatchLog::inactive(TextRepresentation::default()),
+        )
+    }
+
+    /// Like [`Self::apply_changes_from_store()`] but log the resulting changes to the current
+    /// state of the document to `patch_log`
+    pub fn apply_changes_from_store_log_patches<B: AsRef<[u8]>>(
+        &mut self,
+        store: &mut ChangeStore<B>,
+        patch_log: &mut PatchLog,
+    ) -> Result<(), AutomergeError> {
+        let missing = store
+            .hashes()
+            .filter(|hash| !self.history_index.contains_key(hash))
+            .collect::<Vec<_>>();
+        let mut changes = Vec::with_capacity(missing.len());
+        for hash in missing {
+            changes.extend(store.get_change(&hash)?);
+        }
+        self.apply_changes_log_patches(changes, patch_log)
+    }
+
     /// Like [`Self::apply_changes()`] but log the resulting changes to the current state of the
     /// document to `patch_log`
     pub fn apply_changes_log_patches<I: IntoIterator<Item = Change>>(
         &mut self,
         changes: I,
         patch_log: &mut PatchLog,
+    ) -> Result<(), AutomergeError> {
+        self.apply_changes_log_patches_multi(changes, &mut [patch_log])
+    }
+
+    /// Like [`Self::apply_changes_log_patches()`] but log the resulting changes to every log in
+    /// `patch_logs`
+    ///
+    /// This is for servers which send patches to clients which use different
+    /// [`TextRepresentation`]s. The changes are applied once and the work of finding where each
+    /// operation goes is shared between all the logs with the same text representation, which is
+    /// much cheaper than applying the changes to a copy of the document for each log.
+    pub fn apply_changes_log_patches_multi<I: IntoIterator<Item = Change>>(
+        &mut self,
+        changes: I,
+        patch_logs: &mut [&mut PatchLog],
     ) -> Result<(), AutomergeError> {
         // Record this so we can avoid observing each individual change and instead just observe
         // the final state after all the changes have been applied. We can only do this for an
         // empty document right now, once we have logic to produce the diffs between arbitrary
         // states of the OpSet we can make this cleaner.
+        let mut queue_full = false;
         for c in changes {
             if !self.history_index.contains_key(&c.hash()) {
                 if self.duplicate_seq(&c) {
@@ -764,7 +928,13 @@ impl Automerge {
                     ));
                 }
                 if self.is_causally_ready(&c) {
-                    self.apply_change(c, patch_log)?;
+                    self.apply_change(c, patch_logs)?;
+                } else if self
+                    .max_pending_changes
+                    .map(|max| self.queue.len() >= max)
+                    .unwrap_or(false)
+                {
+                    queue_full = true;
                 } else {
                     self.queue.push(c);
                 }
@@ -772,25 +942,184 @@ impl Automerge {
         }
         while let Some(c) = self.pop_next_causally_ready_change() {
             if !self.history_index.contains_key(&c.hash()) {
-                self.apply_change(c, patch_log)?;
+                self.apply_change(c, patch_logs)?;
             }
         }
-        Ok(())
+        self.refresh_mark_merge()?;
+        self.refresh_counter_overflow()?;
+        match self.max_pending_changes {
+            Some(max) if queue_full => Err(AutomergeError::TooManyPendingChanges(max)),
+            _ => Ok(()),
+        }
     }
 
     fn apply_change(
         &mut self,
         change: Change,
-        patch_log: &mut PatchLog,
+        patch_logs: &mut [&mut PatchLog],
     ) -> Result<(), AutomergeError> {
         let ops = self.import_ops(&change);
         self.update_history(change, ops.len());
         for (obj, op, pred) in ops {
-            self.insert_op(&obj, op, &pred, patch_log)?;
+            self.insert_op(&obj, op, &pred, patch_logs)?;
         }
         Ok(())
     }
 
+    /// The hashes of the changes which the changes waiting in the queue depend on but which we
+    /// don't have
+    ///
+    /// Changes whose dependencies are missing wait in a queue until the dependencies arrive,
+    /// these are the changes to ask a peer for.
+    pub fn missing_deps(&self) -> Vec<ChangeHash> {
+        self.get_missing_deps(&[])
+    }
+
+    /// The number of changes waiting for their dependencies to arrive
+    pub fn pending_changes_len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// The changes waiting for their dependencies to arrive
+    pub fn pending_changes(&self) -> &[Change] {
+        &self.queue
+    }
+
+    /// Remove and return the changes waiting for their dependencies to arrive, e.g. to store them
+    /// elsewhere or to give up on a peer which never sent the dependencies
+    pub fn take_pending_changes(&mut self) -> Vec<Change> {
+        std::mem::take(&mut self.queue)
+    }
+
+    /// Limit the number of changes which can wait for their dependencies to arrive
+    ///
+    /// A peer can send changes whose dependencies never arrive, and those changes are kept in
+    /// memory until they do. With a limit set, changes which arrive while the queue is full are
+    /// dropped and applying them fails with [`AutomergeError::TooManyPendingChanges`], after the
+    /// changes which could be applied have been. `None`, the default, means no limit.
+    pub fn set_max_pending_changes(&mut self, max: Option<usize>) {
+        self.max_pending_changes = max;
+    }
+
+    /// Report concurrent writes of identical values to the same property as a single value
+    ///
+    /// When several peers idempotently write the same value to a property, e.g. a job which runs
+    /// on several servers, the property has a conflict between values which are all the same.
+    /// With this enabled [`ReadDoc::get_all()`] and [`Self::get_conflicts()`] only return the
+    /// winning value out of a set of identical scalar values, and [`ReadDoc::map_range()`] and
+    /// [`Self::map_page()`] don't flag a conflict between them. Counters are never collapsed as
+    /// their values change independently. This only changes what is read, every write is still
+    /// in the history of the document, and patches still report these conflicts as they describe
+    /// the ops which were applied.
+    pub fn set_collapse_identical_conflicts(&mut self, enabled: bool) {
+        self.collapse_identical_conflicts = enabled;
+    }
+
+    /// The expand behaviour for marks named `name`, which is [`ExpandMark::default()`] unless
+    /// something else was set with [`crate::AutoCommit::set_mark_expand()`]
+    pub fn mark_expand(&self, name: &str) -> Result<ExpandMark, AutomergeError> {
+        marks::mark_expand(self, name)
+    }
+
+    /// Whether `\r\n` is replaced with `\n` in inserted text, see
+    /// [`crate::AutoCommit::set_canonicalize_line_endings()`]
+    pub fn canonicalizes_line_endings(&self) -> Result<bool, AutomergeError> {
+        transaction::canonicalizes_line_endings(self)
+    }
+
+    /// Load the [`marks::MarkMerge`] settings recorded in the document into the op set, which uses
+    /// them to read marks
+    ///
+    /// This is called whenever changes may have altered the settings.
+    pub(crate) fn refresh_mark_merge(&mut self) -> Result<(), AutomergeError> {
+        self.ops.osd.mark_merge = marks::mark_merge_settings(self)?;
+        Ok(())
+    }
+
+    /// Work out the ops covered by the seals added to the op set since this was last called
+    ///
+    /// This needs the change graph, so it is called once ops have been added rather than by the
+    /// op set itself.
+    pub(crate) fn clock_seals(&mut self) {
+        for idx in self.ops.seals.take_unclocked() {
+            let op = idx.as_op(&self.ops.osd);
+            let OpType::Seal(heads) = op.action() else {
+                continue;
+            };
+            let id = *op.id();
+            let sealed = op.pred().map(|pred| ObjId(*pred.id())).collect::<Vec<_>>();
+            let mut clock = self.clock_at(heads);
+            // the ops made in the same change before the seal
+            clock.include(
+                id.actor(),
+                ClockData {
+                    max_op: id.counter(),
+                    seq: 0,
+                },
+            );
+            for obj in sealed {
+                self.ops.seals.insert(obj, id, clock.clone());
+            }
+        }
+    }
+
+    /// Whether `obj` has been sealed as at `clock`
+    pub(crate) fn is_sealed_for(
+        &self,
+        obj: &ExId,
+        clock: Option<Clock>,
+    ) -> Result<bool, AutomergeError> {
+        let obj = self.exid_to_obj(obj)?;
+        Ok(self.ops.seals.is_sealed(&obj.id, clock.as_ref()))
+    }
+
+    /// Check that the [`CounterOverflow`] settings allow a local increment of `prop` in `obj`
+    /// by `inc`
+    pub(crate) fn check_counter_increment(
+        &self,
+        obj: &ObjMeta,
+        prop: &Prop,
+        inc: i64,
+    ) -> Result<(), AutomergeError> {
+        let overflow = self.ops.osd.counter_overflow.for_key(match prop {
+            Prop::Map(key) => Some(key.as_str()),
+            Prop::Seq(_) => None,
+        });
+        if overflow != CounterOverflow::Error {
+            return Ok(());
+        }
+        let ops = self
+            .ops
+            .seek_ops_by_prop(
+                &obj.id,
+                prop.clone(),
+                TextRepresentation::String.encoding(obj.typ),
+                None,
+            )
+            .ops;
+        // every conflicting counter gets the increment so it must be allowed for all of them
+        for op in ops {
+            if let OpType::Put(ScalarValue::Counter(c)) = op.action() {
+                if !counter_overflow::allows_local_increment(overflow, c, inc) {
+                    return Err(AutomergeError::CounterOverflow {
+                        value: c.saturated().value(),
+                        increment: inc,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Load the [`CounterOverflow`] settings recorded in the document into the op set, which uses
+    /// them to read counters
+    ///
+    /// This is called whenever changes may have altered the settings.
+    pub(crate) fn refresh_counter_overflow(&mut self) -> Result<(), AutomergeError> {
+        self.ops.osd.counter_overflow = CounterOverflowPolicy::load(self)?;
+        Ok(())
+    }
+
     fn is_causally_ready(&self, change: &Change) -> bool {
         change
             .deps()
@@ -890,14 +1219,112 @@ impl Automerge {
         Ok(self.get_heads())
     }
 
+    /// Like [`Self::merge_and_log_patches()`] but log the resulting changes to every log in
+    /// `patch_logs`, see [`Self::apply_changes_log_patches_multi()`]
+    pub fn merge_and_log_patches_multi(
+        &mut self,
+        other: &mut Self,
+        patch_logs: &mut [&mut PatchLog],
+    ) -> Result<Vec<ChangeHash>, AutomergeError> {
+        let changes = self
+            .get_changes_added(other)
+            .into_iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        self.apply_changes_log_patches_multi(changes, patch_logs)?;
+        Ok(self.get_heads())
+    }
+
+    /// Replay the changes in `fork` which are not in `self` on top of `self` as new changes
+    ///
+    /// This is for "optimistic offline edit" flows where a fork of a document has been edited
+    /// but the history of the fork must not be merged directly, for example because the fork's
+    /// changes were made with a temporary actor ID. Each change in `fork` which is not in `self`
+    /// is rewritten to be a change by the actor of `self`, depending on the current heads of
+    /// `self`, and applied. The operations in the rewritten changes refer to the same objects and
+    /// elements as the originals so the edits land exactly where they did in the fork, rather than
+    /// at whatever index they happened to be at.
+    ///
+    /// The timestamps and messages of the original changes are preserved. Note that rebased
+    /// operations only overwrite the values the fork knew about, so a key which has been
+    /// concurrently modified in `self` since the fork was made will end up conflicted, just as it
+    /// would with [`Self::merge()`].
+    ///
+    /// Returns the hashes of the new changes
+    pub fn rebase_from(&mut self, fork: Automerge) -> Result<Vec<ChangeHash>, AutomergeError> {
+        self.rebase_from_and_log_patches(
+            fork,
+            &mut PatchLog::inactive(TextRepresentation::default()),
+        )
+    }
+
+    /// Like [`Self::rebase_from()`] but log the resulting changes to the current state of the
+    /// document to `patch_log`
+    pub fn rebase_from_and_log_patches(
+        &mut self,
+        fork: Automerge,
+        patch_log: &mut PatchLog,
+    ) -> Result<Vec<ChangeHash>, AutomergeError> {
+        use crate::legacy::{ElementId, Key, ObjectId, OpId};
+
+        let actor = self.get_actor().clone();
+        let mut remapped: HashMap<OpId, OpId> = HashMap::new();
+        let mut hashes = Vec::new();
+        // `history` is in the order the changes were applied, which is a topological order
+        for change in fork.history.iter() {
+            if self.history_index.contains_key(&change.hash()) {
+                continue;
+            }
+            let mut expanded = change.decode();
+            let start_op = self.max_op + 1;
+            for i in 0..expanded.operations.len() {
+                remapped.insert(
+                    OpId::new(expanded.start_op.get() + i as u64, &expanded.actor_id),
+                    OpId::new(start_op + i as u64, &actor),
+                );
+            }
+            let remap = |id: OpId| remapped.get(&id).cloned().unwrap_or(id);
+            for op in expanded.operations.iter_mut() {
+                if let ObjectId::Id(id) = &op.obj {
+                    op.obj = ObjectId::Id(remap(id.clone()));
+                }
+                if let Key::Seq(ElementId::Id(id)) = &op.key {
+                    op.key = Key::Seq(ElementId::Id(remap(id.clone())));
+                }
+                op.pred = op.pred.iter().cloned().map(remap).collect();
+            }
+            expanded.actor_id = actor.clone();
+            expanded.seq = self.next_seq_for(&actor);
+            // SAFETY: this unwrap is safe as we always add 1
+            expanded.start_op = NonZeroU64::new(start_op).unwrap();
+            expanded.deps = self.get_heads();
+            expanded.hash = None;
+            let rebased = Change::from(expanded);
+            hashes.push(rebased.hash());
+            self.apply_changes_log_patches([rebased], patch_log)?;
+        }
+        Ok(hashes)
+    }
+
+    fn next_seq_for(&self, actor: &ActorId) -> u64 {
+        self.ops
+            .osd
+            .actors
+            .lookup(actor)
+            .and_then(|index| self.states.get(&index))
+            .map_or(0, |changes| changes.len()) as u64
+            + 1
+    }
+
     /// Save the entirety of this document in a compact form.
     pub fn save_with_options(&self, options: SaveOptions) -> Vec<u8> {
         let heads = self.get_heads();
         let c = self.history.iter();
-        let compress = if options.deflate {
-            None
-        } else {
-            Some(CompressConfig::None)
+        let compress = match options.compression.map(Option::<Codec>::from) {
+            Some(Some(codec)) => Some(CompressConfig::Threshold(DEFLATE_MIN_SIZE, codec)),
+            Some(None) => Some(CompressConfig::None),
+            None if options.deflate => None,
+            None => Some(CompressConfig::None),
         };
         let mut bytes = crate::storage::save::save_document(
             c,
@@ -920,6 +1347,153 @@ impl Automerge {
         self.save_with_options(SaveOptions::default())
     }
 
+    /// Save the entirety of this document to `writer`, producing the same bytes as [`Self::save()`]
+    ///
+    /// The columns of the document still have to be encoded before anything is written, but they
+    /// are written as they are rather than being copied into one buffer holding the whole saved
+    /// document, so saving a large document straight to a file or socket needs far less memory.
+    pub fn save_to_writer<W: std::io::Write>(&self, mut writer: W) -> std::io::Result<()> {
+        let heads = self.get_heads();
+        crate::storage::save::save_document_to_writer(
+            self.history.iter(),
+            self.ops.iter().map(|(objid, _, op)| (objid, op)),
+            &self.ops.osd.actors,
+            &self.ops.osd.props,
+            &heads,
+            None,
+            &mut writer,
+        )?;
+        for orphaned in self.queue.iter() {
+            writer.write_all(orphaned.raw_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Save the entirety of this document with the body of each chunk encrypted by `cipher`
+    ///
+    /// This is for storing documents somewhere which shouldn't be able to read them, such as a
+    /// shared server or a synced folder. `cipher` is an authenticated cipher as used for sealing
+    /// sync messages, see [`crate::sync::PayloadCipher`], which should use a fresh nonce for each
+    /// chunk. Each chunk is sealed whole behind a header with the checksum of the sealed bytes, so
+    /// the file can still be recognised as an Automerge document without revealing anything about
+    /// its contents, but [`Self::load()`] rejects it, load it with [`Self::load_encrypted()`] and
+    /// the same key.
+    pub fn save_encrypted<C: crate::sync::PayloadCipher + ?Sized>(&self, cipher: &C) -> Vec<u8> {
+        let document = self.save_with_options(SaveOptions {
+            retain_orphans: false,
+            ..Default::default()
+        });
+        let chunks = std::iter::once(document.as_slice())
+            .chain(self.queue.iter().map(|change| change.raw_bytes()))
+            .collect::<Vec<_>>();
+        storage::encrypted::encrypt_chunks(chunks, cipher)
+    }
+
+    /// Load a document saved with [`Self::save_encrypted()`]
+    ///
+    /// Fails if any chunk can't be decrypted by `cipher`, including chunks which are not
+    /// encrypted at all, so an unencrypted chunk can't be slipped into an encrypted document.
+    pub fn load_encrypted<C: crate::sync::PayloadCipher + ?Sized>(
+        data: &[u8],
+        cipher: &C,
+    ) -> Result<Self, AutomergeError> {
+        Self::load(&storage::encrypted::decrypt_chunks(data, cipher)?)
+    }
+
+    /// Save this document as blobs of about `target_size` bytes, and a manifest listing them
+    ///
+    /// This is for stores which hold blobs rather than files, such as S3 or IndexedDB, where one
+    /// large value is slow or impossible to write. Each blob is named by the hash of its contents,
+    /// see [`BlobId`], so a blob which a previous save already stored doesn't need to be written
+    /// again, and as the changes are grouped in the order they were applied adding changes only
+    /// replaces the last blob and adds new ones. A change larger than `target_size` gets a blob of
+    /// its own. Load the document with [`Self::load_chunked()`].
+    ///
+    /// The blobs hold the changes of the document rather than the compact document format of
+    /// [`Self::save()`], so together they are larger than a single save.
+    pub fn save_chunked(&self, target_size: usize) -> ChunkedSave {
+        let changes = self
+            .history
+            .iter()
+            .chain(self.queue.iter())
+            .map(|change| change.clone().bytes().into_owned());
+        let blobs = storage::chunked::split(changes, target_size);
+        let manifest = storage::chunked::Manifest {
+            heads: self.get_heads(),
+            blobs: blobs.iter().map(|(id, _)| *id).collect(),
+        };
+        ChunkedSave {
+            manifest: manifest.encode(),
+            blobs,
+        }
+    }
+
+    /// Load a document saved with [`Self::save_chunked()`], calling `fetch` for each of the blobs
+    /// listed in `manifest`
+    ///
+    /// Blobs are fetched and applied one at a time, in the order they were saved, and each is
+    /// checked against its ID before it is used.
+    pub fn load_chunked<F, E>(manifest: &[u8], mut fetch: F) -> Result<Self, AutomergeError>
+    where
+        F: FnMut(&BlobId) -> Result<Vec<u8>, E>,
+        E: Into<Box<dyn std::error::Error + Send + Sync + 'static>>,
+    {
+        let manifest = storage::chunked::Manifest::decode(manifest)?;
+        let mut doc = Self::new();
+        for id in &manifest.blobs {
+            let blob = fetch(id).map_err(|e| load::Error::Fetch {
+                blob: *id,
+                error: e.into(),
+            })?;
+            if BlobId::of(&blob) != *id {
+                return Err(load::Error::BlobMismatch(*id).into());
+            }
+            match load::load_changes(storage::parse::Input::new(&blob)) {
+                load::LoadedChanges::Complete(changes) => doc.apply_changes(changes)?,
+                load::LoadedChanges::Partial { error, .. } => return Err(error.into()),
+            }
+        }
+        let mut heads = manifest.heads;
+        heads.sort();
+        if doc.get_heads() != heads {
+            return Err(load::Error::ManifestHeads.into());
+        }
+        Ok(doc)
+    }
+
+    /// Load as much of a damaged document as possible
+    ///
+    /// [`Self::load()`] fails if any chunk of the input is corrupt, so a single flipped bit can
+    /// make a whole file unloadable. This instead skips every chunk which fails to load, applies
+    /// the changes from the rest, and drops any change whose dependencies were lost with the
+    /// skipped chunks or which can't be applied. The returned [`SalvageReport`] says what was
+    /// skipped and dropped.
+    ///
+    /// A document loaded this way may be missing changes other peers have, syncing it with them
+    /// restores those changes.
+    pub fn load_salvage(data: &[u8]) -> (Self, SalvageReport) {
+        let (changes, skipped) = storage::salvage::read_chunks(data);
+        let mut doc = Self::new();
+        let mut dropped_changes = Vec::new();
+        // one at a time so one change which can't be applied doesn't lose the rest
+        for change in changes {
+            let hash = change.hash();
+            if doc.apply_changes([change]).is_err() {
+                dropped_changes.push(hash);
+            }
+        }
+        let missing_deps = doc.get_missing_deps(&[]);
+        dropped_changes.extend(std::mem::take(&mut doc.queue).iter().map(Change::hash));
+        (
+            doc,
+            SalvageReport {
+                skipped,
+                dropped_changes,
+                missing_deps,
+            },
+        )
+    }
+
     /// Save the document and attempt to load it before returning - slow!
     pub fn save_and_verify(&self) -> Result<Vec<u8>, AutomergeError> {
         let bytes = self.save();
@@ -1006,7 +1580,18 @@ impl Automerge {
             .find(|c| c.actor_id() == self.get_actor());
     }
 
-    pub(crate) fn clock_at(&self, heads: &[ChangeHash]) -> Clock {
+    /// A read only view of the document as at `heads`
+    ///
+    /// The view implements [`ReadDoc`] so it can be passed to code which reads documents
+    /// generically, see [`ViewAt`]
+    pub fn view_at(&self, heads: &[ChangeHash]) -> ViewAt<'_> {
+        ViewAt::new(self, heads)
+    }
+
+    /// The vector clock of the changes which are causally before `heads`
+    ///
+    /// See [`Clock`] for how to compare clocks
+    pub fn clock_at(&self, heads: &[ChangeHash]) -> Clock {
         self.change_graph.clock_for_heads(heads)
     }
 
@@ -1165,6 +1750,7 @@ impl Automerge {
                     format!("mark({},{})", name, value)
                 }
                 OpType::MarkEnd(_) => "/mark".to_string(),
+                OpType::Seal(_) => "seal".to_string(),
             };
             let pred: Vec<_> = op.pred().map(|op| self.to_short_string(*op.id())).collect();
             let succ: Vec<_> = op.succ().map(|op| self.to_short_string(*op.id())).collect();
@@ -1207,21 +1793,33 @@ impl Automerge {
         obj: &ObjId,
         op: OpBuilder,
         pred: &OpIds,
-        patch_log: &mut PatchLog,
+        patch_logs: &mut [&mut PatchLog],
     ) -> Result<(), AutomergeError> {
         let is_delete = op.is_delete();
+        let is_seal = op.action.is_seal();
         let idx = self.ops.load(*obj, op);
         let op = idx.as_op(&self.ops.osd);
 
-        let (pos, succ) = if patch_log.is_active() {
+        // ops hidden by a seal are still recorded but there is nothing to tell the application
+        let (pos, succ) = if patch_logs.iter().any(|log| log.is_active())
+            && !self.ops.seals.hides(obj, op.id())
+        {
             let obj = self.get_obj_meta(*obj)?;
-            let found = self.ops.find_op_with_patch_log(
-                &obj,
-                patch_log.text_rep().encoding(obj.typ),
-                op,
-                pred,
-            );
-            found.log_patches(&obj, op, pred, self, patch_log);
+            let mut found: Vec<(ListEncoding, FoundOpWithPatchLog<'_>)> = Vec::new();
+            for patch_log in patch_logs.iter_mut().filter(|log| log.is_active()) {
+                let encoding = patch_log.text_rep().encoding(obj.typ);
+                let i = match found.iter().position(|(e, _)| *e == encoding) {
+                    Some(i) => i,
+                    None => {
+                        let f = self.ops.find_op_with_patch_log(&obj, encoding, op, pred);
+                        found.push((encoding, f));
+                        found.len() - 1
+                    }
+                };
+                found[i].1.log_patches(&obj, op, pred, self, patch_log);
+            }
+            // the position of the op does not depend on the encoding
+            let (_, found) = found.swap_remove(0);
             (found.pos, found.succ)
         } else {
             let found = self.ops.find_op_without_patch_log(obj, op, pred);
@@ -1233,6 +1831,49 @@ impl Automerge {
         if !is_delete {
             self.ops.insert(pos, obj, idx);
         }
+        if is_seal && patch_logs.iter().any(|log| log.is_active()) {
+            self.log_seal(idx, patch_logs)?;
+        } else {
+            self.clock_seals();
+        }
+        Ok(())
+    }
+
+    /// Add the seal `idx` to the seals and log the values it hides, which are the values set by
+    /// ops already in the document which were made concurrently with the seal
+    fn log_seal(
+        &mut self,
+        idx: OpIdx,
+        patch_logs: &mut [&mut PatchLog],
+    ) -> Result<(), AutomergeError> {
+        let op = idx.as_op(&self.ops.osd);
+        let id = *op.id();
+        let sealed = op.pred().map(|pred| ObjId(*pred.id())).collect::<Vec<_>>();
+        // every op in the document, including those of the change being applied
+        let mut all = self.clock_at(&self.get_heads());
+        all.include(
+            id.actor(),
+            ClockData {
+                max_op: id.counter(),
+                seq: 0,
+            },
+        );
+        let before = sealed
+            .iter()
+            .map(|obj| self.ops.seals.clock(obj, Some(all.clone())))
+            .collect::<Vec<_>>();
+        self.clock_seals();
+        for (obj, before) in sealed.iter().zip(before) {
+            let (Some(before), Some(after)) =
+                (before, self.ops.seals.clock(obj, Some(all.clone())))
+            else {
+                continue;
+            };
+            let obj = self.get_obj_meta(*obj)?;
+            for patch_log in patch_logs.iter_mut().filter(|log| log.is_active()) {
+                diff::log_obj_diff(self, &obj, &before, &after, patch_log);
+            }
+        }
         Ok(())
     }
 
@@ -1260,6 +1901,25 @@ impl Automerge {
         deps
     }
 
+    /// Get the heads of this document as a [`Frontier`]
+    pub fn get_frontier(&self) -> Frontier {
+        self.deps.iter().copied().collect()
+    }
+
+    /// A hash of the heads of this document
+    ///
+    /// Two documents have the same state if and only if they have the same heads, so this is a
+    /// compact identifier for the state of the document which is stable across processes and
+    /// versions of automerge, e.g. for use as a cache key.
+    pub fn state_hash(&self) -> [u8; 32] {
+        hash_heads(&self.get_heads())
+    }
+
+    /// Whether this document and `other` have the same heads and so the same state
+    pub fn same_state_as(&self, other: &Automerge) -> bool {
+        self.deps == other.deps
+    }
+
     pub fn get_changes(&self, have_deps: &[ChangeHash]) -> Vec<&Change> {
         self.get_changes_clock(have_deps)
     }
@@ -1330,6 +1990,7 @@ impl Automerge {
         clock: Option<Clock>,
     ) -> Result<Vec<Mark<'_>>, AutomergeError> {
         let obj = self.exid_to_obj(obj.as_ref())?;
+        let clock = self.ops.seals.clock(&obj.id, clock);
         let ops_by_key = self.ops().iter_ops(&obj.id).chunk_by(|o| o.elemid_or_key());
         let mut index = 0;
         let mut marks = MarkStateMachine::default();
@@ -1360,7 +2021,7 @@ impl Automerge {
                     OpType::MarkEnd(_) => {
                         marks.mark_end(*o.id(), &self.ops.osd);
                     }
-                    OpType::Increment(_) | OpType::Delete => {}
+                    OpType::Increment(_) | OpType::Delete | OpType::Seal(_) => {}
                 }
             }
         }
@@ -1376,17 +2037,31 @@ impl Automerge {
         self.hydrate_map(&ObjId::root(), clock.as_ref())
     }
 
+    /// Hydrate the document as of `heads` and convert it to the JSON representation described in
+    /// [`hydrate::Value::to_tagged_json()`]
+    ///
+    /// Use [`hydrate::Value::from_tagged_json()`] to convert the result back to a
+    /// [`hydrate::Value`]. This requires the `json` feature.
+    #[cfg(feature = "json")]
+    pub fn hydrate_tagged(&self, heads: Option<&[ChangeHash]>) -> serde_json::Value {
+        self.hydrate(heads).to_tagged_json()
+    }
+
     pub(crate) fn hydrate_obj(
         &self,
         obj: &crate::ObjId,
         heads: Option<&[ChangeHash]>,
     ) -> Result<hydrate::Value, AutomergeError> {
         let obj = self.exid_to_obj(obj)?;
-        let clock = heads.map(|heads| self.clock_at(heads));
+        let clock = self
+            .ops
+            .seals
+            .clock(&obj.id, heads.map(|heads| self.clock_at(heads)));
         Ok(match obj.typ {
             ObjType::Map | ObjType::Table => self.hydrate_map(&obj.id, clock.as_ref()),
             ObjType::List => self.hydrate_list(&obj.id, clock.as_ref()),
             ObjType::Text => self.hydrate_text(&obj.id, clock.as_ref()),
+            ObjType::Unknown(_) => hydrate::Value::Scalar(ScalarValue::Null),
         })
     }
 
@@ -1405,7 +2080,22 @@ impl Automerge {
     pub(crate) fn keys_for(&self, obj: &ExId, clock: Option<Clock>) -> Keys<'_> {
         self.exid_to_obj(obj)
             .ok()
-            .map(|obj| self.ops.keys(&obj.id, clock))
+            .map(|obj| self.ops.keys(&obj.id, self.ops.seals.clock(&obj.id, clock)))
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn keys_range_for<'a, R: RangeBounds<String> + 'a>(
+        &'a self,
+        obj: &ExId,
+        range: R,
+        clock: Option<Clock>,
+    ) -> KeysRange<'a, R> {
+        self.exid_to_obj(obj)
+            .ok()
+            .map(|obj| {
+                let clock = self.ops.seals.clock(&obj.id, clock);
+                self.ops.keys_range(&obj.id, range, clock)
+            })
             .unwrap_or_default()
     }
 
@@ -1417,10 +2107,48 @@ impl Automerge {
     ) -> MapRange<'a, R> {
         self.exid_to_obj(obj)
             .ok()
-            .map(|obj| self.ops.map_range(&obj.id, range, clock))
+            .map(|obj| {
+                let clock = self.ops.seals.clock(&obj.id, clock);
+                let range = self.ops.map_range(&obj.id, range, clock);
+                if self.collapse_identical_conflicts {
+                    range.collapse_identical(obj.id)
+                } else {
+                    range
+                }
+            })
             .unwrap_or_default()
     }
 
+    pub(crate) fn map_page_for(
+        &self,
+        obj: &ExId,
+        prefix: &str,
+        limit: usize,
+        continue_from: Option<&MapPageToken>,
+        clock: Option<Clock>,
+    ) -> MapPage<'_> {
+        let start = match continue_from {
+            Some(token) if token.key() >= prefix => Bound::Excluded(token.key().to_string()),
+            _ => Bound::Included(prefix.to_string()),
+        };
+        let mut entries = self
+            .map_range_for(obj, (start, Bound::Unbounded), clock)
+            .take_while(|item| item.key.starts_with(prefix))
+            .peekable();
+        // a page of nothing would return the token it was given and never finish
+        let limit = if limit == 0 { usize::MAX } else { limit };
+        let items = entries.by_ref().take(limit).collect::<Vec<_>>();
+        let next = if entries.peek().is_some() {
+            items
+                .last()
+                .map(|item| MapPageToken::from(item.key))
+                .or_else(|| continue_from.cloned())
+        } else {
+            None
+        };
+        MapPage { items, next }
+    }
+
     pub(crate) fn list_range_for<R: RangeBounds<usize>>(
         &self,
         obj: &ExId,
@@ -1430,6 +2158,7 @@ impl Automerge {
         self.exid_to_obj(obj)
             .ok()
             .map(|obj| {
+                let clock = self.ops.seals.clock(&obj.id, clock);
                 self.ops.list_range(
                     &obj.id,
                     range,
@@ -1443,7 +2172,10 @@ impl Automerge {
     pub(crate) fn values_for(&self, obj: &ExId, clock: Option<Clock>) -> Values<'_> {
         self.exid_to_obj(obj)
             .ok()
-            .map(|obj| Values::new(self.ops.top_ops(&obj.id, clock.clone()), clock))
+            .map(|obj| {
+                let clock = self.ops.seals.clock(&obj.id, clock);
+                Values::new(self.ops.top_ops(&obj.id, clock.clone()), clock)
+            })
             .unwrap_or_default()
     }
 
@@ -1451,6 +2183,7 @@ impl Automerge {
         // FIXME - is doc.length() for a text always the string length?
         self.exid_to_obj(obj)
             .map(|obj| {
+                let clock = self.ops.seals.clock(&obj.id, clock);
                 self.ops
                     .length(&obj.id, TextRepresentation::String.encoding(obj.typ), clock)
             })
@@ -1463,19 +2196,118 @@ impl Automerge {
         clock: Option<Clock>,
     ) -> Result<String, AutomergeError> {
         let obj = self.exid_to_obj(obj)?;
+        let clock = self.ops.seals.clock(&obj.id, clock);
         Ok(self.ops.text(&obj.id, clock))
     }
 
+    pub(crate) fn text_chunks_for(
+        &self,
+        obj: &ExId,
+        clock: Option<Clock>,
+    ) -> Result<TextChunks<'_>, AutomergeError> {
+        let obj = self.exid_to_obj(obj)?;
+        let clock = self.ops.seals.clock(&obj.id, clock);
+        Ok(self.ops.text_chunks(&obj.id, clock))
+    }
+
+    pub(crate) fn find_text_for(
+        &self,
+        obj: &ExId,
+        needle: &str,
+        clock: Option<Clock>,
+    ) -> Result<Vec<TextMatch>, AutomergeError> {
+        text_search::find_text(self, obj, needle, clock)
+    }
+
+    pub(crate) fn text_width_for(
+        &self,
+        obj: &ExId,
+        encoding: TextEncoding,
+        clock: Option<Clock>,
+    ) -> Result<usize, AutomergeError> {
+        let obj = self.exid_to_obj(obj)?;
+        let clock = self.ops.seals.clock(&obj.id, clock);
+        if obj.typ != ObjType::Text {
+            return Ok(self.ops.length(&obj.id, ListEncoding::List, clock));
+        }
+        Ok(self.ops.text_width(&obj.id, encoding, clock))
+    }
+
+    pub(crate) fn convert_text_index_for(
+        &self,
+        obj: &ExId,
+        index: usize,
+        from: TextEncoding,
+        to: TextEncoding,
+        clock: Option<Clock>,
+    ) -> Result<usize, AutomergeError> {
+        let obj = self.exid_to_obj(obj)?;
+        let clock = self.ops.seals.clock(&obj.id, clock);
+        if from == to || obj.typ != ObjType::Text {
+            return Ok(index);
+        }
+        let query = self.ops.search(
+            &obj.id,
+            query::ConvertTextIndex::new(index, from, to, clock),
+        );
+        // an index past the end or in the middle of a character does not exist in `to`
+        if query.from_width == index {
+            Ok(query.to_width)
+        } else {
+            Err(AutomergeError::InvalidIndex(index))
+        }
+    }
+
+    pub(crate) fn text_display_width_for<R: RangeBounds<usize>>(
+        &self,
+        obj: &ExId,
+        range: R,
+        clock: Option<Clock>,
+    ) -> Result<usize, AutomergeError> {
+        let obj = self.exid_to_obj(obj)?;
+        let clock = self.ops.seals.clock(&obj.id, clock);
+        if obj.typ != ObjType::Text {
+            return Err(AutomergeError::InvalidOp(obj.typ));
+        }
+        let start = match range.start_bound() {
+            Bound::Included(n) => *n,
+            Bound::Excluded(n) => n + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(n) => n + 1,
+            Bound::Excluded(n) => *n,
+            Bound::Unbounded => usize::MAX,
+        };
+        let query = self
+            .ops
+            .search(&obj.id, query::DisplayWidth::new(start, end, clock));
+        Ok(query.width)
+    }
+
     pub(crate) fn spans_for(
         &self,
         obj: &ExId,
         clock: Option<Clock>,
     ) -> Result<Spans<'_>, AutomergeError> {
         let obj = self.exid_to_obj(obj)?;
+        let clock = self.ops.seals.clock(&obj.id, clock);
         let iter = self.ops.iter_obj(&obj.id);
         Ok(Spans::new(iter, self, clock))
     }
 
+    pub(crate) fn mark_boundaries_for(
+        &self,
+        obj: &ExId,
+        clock: Option<Clock>,
+    ) -> Result<MarkBoundaries<'_>, AutomergeError> {
+        let obj = self.exid_to_obj(obj)?;
+        let clock = self.ops.seals.clock(&obj.id, clock);
+        let iter = self.ops.iter_obj(&obj.id);
+        let encoding = TextRepresentation::String.encoding(obj.typ);
+        Ok(MarkBoundaries::new(iter, &self.ops.osd, encoding, clock))
+    }
+
     pub(crate) fn get_cursor_for(
         &self,
         obj: &ExId,
@@ -1483,6 +2315,7 @@ impl Automerge {
         clock: Option<Clock>,
     ) -> Result<Cursor, AutomergeError> {
         let obj = self.exid_to_obj(obj)?;
+        let clock = self.ops.seals.clock(&obj.id, clock);
         if !obj.typ.is_sequence() {
             Err(AutomergeError::InvalidOp(obj.typ))
         } else {
@@ -1492,11 +2325,10 @@ impl Automerge {
                 TextRepresentation::String.encoding(obj.typ),
                 clock.as_ref(),
             );
-            if let Some(op) = found.ops.last() {
-                Ok(Cursor::new(*op.id(), &self.ops.osd))
-            } else {
-                Err(AutomergeError::InvalidIndex(position))
-            }
+            let Some(op) = found.ops.last() else {
+                return Err(AutomergeError::InvalidIndex(position));
+            };
+            Ok(Cursor::new(*op.id(), &self.ops.osd))
         }
     }
 
@@ -1507,6 +2339,40 @@ impl Automerge {
         clock: Option<Clock>,
     ) -> Result<usize, AutomergeError> {
         let obj = self.exid_to_obj(obj)?;
+        let clock = self.ops.seals.clock(&obj.id, clock);
+        let encoding = TextRepresentation::String.encoding(obj.typ);
+        if cursor.affinity() == Affinity::BeforeInsert {
+            match cursor.before() {
+                Some(Anchor::Start) => return Ok(0),
+                Some(Anchor::Elem { ctr, actor }) => {
+                    // the cursor is just after the element before it, wherever that is now
+                    let found = self
+                        .elem_to_opid(*ctr, actor, clock.as_ref())
+                        .and_then(|opid| {
+                            self.ops
+                                .seek_list_opid(&obj.id, opid, encoding, clock.as_ref())
+                        });
+                    if let Some(found) = found {
+                        // the element may have been overwritten rather than deleted, in which
+                        // case it is the visible value at its index
+                        let at_index = self.ops.seek_ops_by_prop(
+                            &obj.id,
+                            found.index.into(),
+                            encoding,
+                            clock.as_ref(),
+                        );
+                        let width = match at_index.ops.last() {
+                            Some(op) if op.elemid_or_key() == found.op.elemid_or_key() => {
+                                op.width(encoding)
+                            }
+                            _ => 0,
+                        };
+                        return Ok(found.index + width);
+                    }
+                }
+                None => {}
+            }
+        }
         let opid = self.cursor_to_opid(cursor, clock.as_ref())?;
         let found = self
             .ops
@@ -1534,7 +2400,11 @@ impl Automerge {
         prop: Prop,
         clock: Option<Clock>,
     ) -> Result<Option<(Value<'_>, ExId)>, AutomergeError> {
+        if system::is_system_prop(obj, &prop) {
+            return Ok(None);
+        }
         let obj = self.exid_to_obj(obj)?;
+        let clock = self.ops.seals.clock(&obj.id, clock);
         Ok(self
             .ops
             .seek_ops_by_prop(
@@ -1549,6 +2419,45 @@ impl Automerge {
             .map(|op| op.tagged_value(clock.as_ref())))
     }
 
+    pub(crate) fn get_by_elem_id_for(
+        &self,
+        obj: &ExId,
+        elem_id: &ExId,
+        clock: Option<Clock>,
+    ) -> Result<Option<(usize, Value<'_>, ExId)>, AutomergeError> {
+        let obj = self.exid_to_obj(obj)?;
+        let clock = self.ops.seals.clock(&obj.id, clock);
+        if !obj.typ.is_sequence() {
+            return Err(AutomergeError::InvalidOp(obj.typ));
+        }
+        let encoding = TextRepresentation::Array.encoding(obj.typ);
+        let Some(found) = self
+            .exid_to_opid(elem_id)
+            .ok()
+            .filter(|opid| clock.as_ref().map_or(true, |c| c.covers(opid)))
+            .and_then(|opid| {
+                self.ops
+                    .seek_list_opid(&obj.id, opid, encoding, clock.as_ref())
+            })
+        else {
+            return Ok(None);
+        };
+        // the insert op may have been overwritten, in which case the element is visible through
+        // the op which overwrote it
+        let at_index =
+            self.ops
+                .seek_ops_by_prop(&obj.id, found.index.into(), encoding, clock.as_ref());
+        Ok(at_index
+            .ops
+            .into_iter()
+            .last()
+            .filter(|op| op.elemid_or_key() == found.op.elemid_or_key())
+            .map(|op| {
+                let (value, id) = op.tagged_value(clock.as_ref());
+                (found.index, value, id)
+            }))
+    }
+
     pub(crate) fn get_all_for<O: AsRef<ExId>, P: Into<Prop>>(
         &self,
         obj: O,
@@ -1556,7 +2465,11 @@ impl Automerge {
         clock: Option<Clock>,
     ) -> Result<Vec<(Value<'_>, ExId)>, AutomergeError> {
         let prop = prop.into();
+        if system::is_system_prop(obj.as_ref(), &prop) {
+            return Ok(Vec::new());
+        }
         let obj = self.exid_to_obj(obj.as_ref())?;
+        let clock = self.ops.seals.clock(&obj.id, clock);
         let values = self
             .ops
             .seek_ops_by_prop(
@@ -1569,6 +2482,11 @@ impl Automerge {
             .into_iter()
             .map(|op| op.tagged_value(clock.as_ref()))
             .collect::<Vec<_>>();
+        let values = if self.collapse_identical_conflicts {
+            collapse_identical(values)
+        } else {
+            values
+        };
         // this is a test to make sure opid and exid are always sorting the same way
         assert_eq!(
             values.iter().map(|v| &v.1).collect::<Vec<_>>(),
@@ -1584,6 +2502,7 @@ impl Automerge {
         clock: Option<Clock>,
     ) -> Result<MarkSet, AutomergeError> {
         let obj = self.exid_to_obj(obj.as_ref())?;
+        let clock = self.ops.seals.clock(&obj.id, clock);
         let result = self
             .ops
             .search(
@@ -1677,7 +2596,7 @@ impl Automerge {
             }
             for op_idx in ops {
                 let op = op_idx.as_op(self.osd());
-                if op.visible_at(at.as_ref()) {
+                if op.visible_at(at.as_ref()) && !op.is_hidden() {
                     if let OpType::Make(_) = op.action() {
                         visible_objs.insert(op.id().into());
                         let (mut path, parent_obj_id) = if obj.id.is_root() {
@@ -1716,6 +2635,300 @@ impl Automerge {
     }
 }
 
+impl Automerge {
+    /// Get the keys of the map `obj` which are in `range`
+    ///
+    /// Only the keys in the range are visited, so this is cheaper than filtering the result of
+    /// [`Self::keys()`] for large maps. If `obj` is a list then this will return an empty iterator.
+    pub fn keys_range<'a, O: AsRef<ExId>, R: RangeBounds<String> + 'a>(
+        &'a self,
+        obj: O,
+        range: R,
+    ) -> KeysRange<'a, R> {
+        self.keys_range_for(obj.as_ref(), range, None)
+    }
+
+    /// Get the keys of the map `obj` which are in `range` as at `heads`
+    ///
+    /// See [`Self::keys_range()`]
+    pub fn keys_range_at<'a, O: AsRef<ExId>, R: RangeBounds<String> + 'a>(
+        &'a self,
+        obj: O,
+        range: R,
+        heads: &[ChangeHash],
+    ) -> KeysRange<'a, R> {
+        let clock = self.clock_at(heads);
+        self.keys_range_for(obj.as_ref(), range, Some(clock))
+    }
+
+    /// Get a page of at most `limit` entries of the map `obj` whose keys start with `prefix`
+    ///
+    /// Pass an empty `prefix` to page through every key. To get the next page pass the
+    /// [`MapPage::next`] token of the previous page as `continue_from`. Only the entries on the
+    /// page are visited so paging through a large map does not require iterating over all of it.
+    /// A `limit` of zero means there is no limit, so the page holds every remaining entry.
+    ///
+    /// If the object correspoding to `obj` is a list then this will return an empty page
+    pub fn map_page<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        prefix: &str,
+        limit: usize,
+        continue_from: Option<&MapPageToken>,
+    ) -> MapPage<'_> {
+        self.map_page_for(obj.as_ref(), prefix, limit, continue_from, None)
+    }
+
+    /// Get a page of the entries of the map `obj` as at `heads`
+    ///
+    /// See [`Self::map_page()`]
+    pub fn map_page_at<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        prefix: &str,
+        limit: usize,
+        continue_from: Option<&MapPageToken>,
+        heads: &[ChangeHash],
+    ) -> MapPage<'_> {
+        let clock = self.clock_at(heads);
+        self.map_page_for(obj.as_ref(), prefix, limit, continue_from, Some(clock))
+    }
+
+    /// Iterate over the string represented by the given text object in pieces borrowed from the
+    /// document
+    ///
+    /// Concatenating the pieces gives the same string as [`Self::text()`], but nothing is copied,
+    /// which makes this the better choice for writing out large text objects.
+    pub fn text_chunks<O: AsRef<ExId>>(&self, obj: O) -> Result<TextChunks<'_>, AutomergeError> {
+        self.text_chunks_for(obj.as_ref(), None)
+    }
+
+    /// Get the number of terminal columns taken up by the characters of the text object `obj`
+    /// whose indexes are in `range`
+    ///
+    /// Wide characters such as CJK ideographs count as two columns and zero width characters,
+    /// including newlines, as none. This is meant for terminal renderers which need to work out
+    /// where to wrap or page a large text without reading all of it. With the
+    /// `display-width-index` feature the widths are kept in the op tree so that this only reads
+    /// the ops at the ends of the range, otherwise every character in the range is visited.
+    pub fn text_display_width<O: AsRef<ExId>, R: RangeBounds<usize>>(
+        &self,
+        obj: O,
+        range: R,
+    ) -> Result<usize, AutomergeError> {
+        self.text_display_width_for(obj.as_ref(), range, None)
+    }
+
+    /// Find every occurrence of `needle` in the text object `obj`
+    ///
+    /// The text is searched as it is read from the document rather than first being collected
+    /// into a string, so this is cheap even for very large text objects. Matches do not overlap
+    /// and are returned in order, each with its index and a [`Cursor`] which follows it through
+    /// later edits. An empty `needle` matches nothing.
+    pub fn find_text<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        needle: &str,
+    ) -> Result<Vec<TextMatch>, AutomergeError> {
+        self.find_text_for(obj.as_ref(), needle, None)
+    }
+
+    /// Get the value of `prop` in `obj`, treating it as absent if it was written with
+    /// [`crate::transaction::Transactable::put_with_expiry()`] and has expired as at `now`
+    ///
+    /// `now` is in the same units as the `expires_at` passed to `put_with_expiry()`, usually
+    /// milliseconds since the unix epoch.
+    pub fn get_unexpired<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+        now: i64,
+    ) -> Result<Option<(Value<'_>, ExId)>, AutomergeError> {
+        expiry::get_unexpired(self, obj.as_ref(), prop.into(), now)
+    }
+
+    /// Get the display metadata recorded for `actor` with
+    /// [`crate::transaction::Transactable::set_actor_metadata()`], if any
+    pub fn actor_metadata(&self, actor: &ActorId) -> Result<Option<ActorMetadata>, AutomergeError> {
+        actor_metadata::actor_metadata(self, actor)
+    }
+
+    /// Whether `obj` has been marked as a multi-value register with
+    /// [`crate::transaction::Transactable::set_multi_value()`]
+    pub fn is_multi_value<O: AsRef<ExId>>(&self, obj: O) -> Result<bool, AutomergeError> {
+        multi_value::is_multi_value(self, obj.as_ref())
+    }
+
+    /// Get the values of `prop` in `obj` as a set
+    ///
+    /// If `obj` is a multi-value register (see [`crate::multi_value`]) this returns every value
+    /// written concurrently, with identical scalar values only appearing once. For other objects
+    /// it returns the value [`Self::get()`] would, if any.
+    pub fn get_all_values<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+    ) -> Result<Vec<(Value<'_>, ExId)>, AutomergeError> {
+        multi_value::get_all_values(self, obj.as_ref(), prop.into())
+    }
+
+    /// The start and end of every mark in the sequence `obj`, in the order they appear
+    ///
+    /// This is a lower level alternative to [`Self::marks()`] and [`Self::spans()`] for
+    /// applications which want to process marks as a stream of events. Marks are not merged or
+    /// resolved against each other, so overlapping marks with the same name are reported
+    /// separately and the [`crate::iter::MarkBoundary::id`] of a boundary says which mark it
+    /// belongs to.
+    pub fn mark_boundaries<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+    ) -> Result<MarkBoundaries<'_>, AutomergeError> {
+        self.mark_boundaries_for(obj.as_ref(), None)
+    }
+
+    /// The start and end of every mark in the sequence `obj` as at `heads`, see
+    /// [`Self::mark_boundaries()`]
+    pub fn mark_boundaries_at<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        heads: &[ChangeHash],
+    ) -> Result<MarkBoundaries<'_>, AutomergeError> {
+        let clock = self.clock_at(heads);
+        self.mark_boundaries_for(obj.as_ref(), Some(clock))
+    }
+
+    /// Look up a list element by the ID of the element rather than by its index
+    ///
+    /// The element ID is the `elem_id` of a [`crate::iter::ListRangeItem`] and stays the same
+    /// however the list is spliced, so it can be used to keep track of an item as other items are
+    /// inserted, deleted or moved around it. Like any [`ExId`] it can be stored using
+    /// [`ExId::to_bytes()`] or [`ExId::to_token()`].
+    ///
+    /// Returns the current index of the element (counting elements rather than characters for
+    /// text), its value and the ID of the op which set the value, or `None` if the element has
+    /// been deleted.
+    pub fn get_by_elem_id<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        elem_id: &ExId,
+    ) -> Result<Option<(usize, Value<'_>, ExId)>, AutomergeError> {
+        self.get_by_elem_id_for(obj.as_ref(), elem_id, None)
+    }
+
+    /// Whether `obj` has been sealed with [`crate::transaction::Transactable::seal_object()`]
+    pub fn is_sealed<O: AsRef<ExId>>(&self, obj: O) -> Result<bool, AutomergeError> {
+        self.is_sealed_for(obj.as_ref(), None)
+    }
+
+    /// Split the text object `obj` into runs of text which were each inserted by one change
+    ///
+    /// If `heads` is `Some` the text is read as at those heads. This is meant for showing who
+    /// wrote which part of a text, see [`TextAttribution`].
+    pub fn attribute_text<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        heads: Option<&[ChangeHash]>,
+    ) -> Result<Vec<TextAttribution>, AutomergeError> {
+        text_attribution::attribute_text(self, self, obj.as_ref(), heads)
+    }
+
+    /// Like [`Self::get_cursor()`] but with `position` in `encoding` rather than the encoding
+    /// this build uses for text indices
+    pub fn get_cursor_with_encoding<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        position: usize,
+        at: Option<&[ChangeHash]>,
+        encoding: TextEncoding,
+    ) -> Result<Cursor, AutomergeError> {
+        text_index::get_cursor(self, obj.as_ref(), position, at, encoding)
+    }
+
+    /// Like [`Self::get_cursor_position()`] but returning the position in `encoding` rather
+    /// than the encoding this build uses for text indices
+    pub fn get_cursor_position_with_encoding<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        cursor: &Cursor,
+        at: Option<&[ChangeHash]>,
+        encoding: TextEncoding,
+    ) -> Result<usize, AutomergeError> {
+        text_index::get_cursor_position(self, obj.as_ref(), cursor, at, encoding)
+    }
+
+    /// The length of the text object `obj` in `encoding`
+    ///
+    /// For objects other than text this is the same as [`Self::length()`].
+    pub fn text_len<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        encoding: TextEncoding,
+    ) -> Result<usize, AutomergeError> {
+        self.text_width_for(obj.as_ref(), encoding, None)
+    }
+
+    /// Get all the conflicting values for a key along with the actor, change hash, timestamp and
+    /// message of the change which wrote each value
+    ///
+    /// See [`Conflicts`] for details
+    pub fn get_conflicts<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+    ) -> Result<Conflicts<'_>, AutomergeError> {
+        self.get_conflicts_for(obj.as_ref(), prop.into(), None)
+    }
+
+    /// Get the conflicting values for a key with their provenance as at `heads`
+    ///
+    /// See [`Self::get_conflicts()`]
+    pub fn get_conflicts_at<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+        heads: &[ChangeHash],
+    ) -> Result<Conflicts<'_>, AutomergeError> {
+        let clock = Some(self.clock_at(heads));
+        self.get_conflicts_for(obj.as_ref(), prop.into(), clock)
+    }
+
+    /// Get the provenance of the currently visible value of a key
+    ///
+    /// Returns `None` if there is no value at `prop`. See [`LastModified`] for details.
+    pub fn last_modified<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+    ) -> Result<Option<LastModified>, AutomergeError> {
+        self.last_modified_for(obj.as_ref(), prop.into(), None)
+    }
+
+    /// Get the provenance of the visible value of a key as at `heads`
+    ///
+    /// See [`Self::last_modified()`]
+    pub fn last_modified_at<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+        heads: &[ChangeHash],
+    ) -> Result<Option<LastModified>, AutomergeError> {
+        let clock = Some(self.clock_at(heads));
+        self.last_modified_for(obj.as_ref(), prop.into(), clock)
+    }
+
+    /// Get metadata about the operation with ID `id`
+    ///
+    /// This is intended for use with the IDs returned alongside values by methods such as
+    /// [`Self::get_all()`] and [`Self::values()`], for example to show when each of a set of
+    /// conflicting values was written. The metadata is only looked up when this is called so
+    /// iterating over values does not pay for it.
+    ///
+    /// Returns `None` if `id` is the root object or is not an operation in this document
+    pub fn op_meta(&self, id: &ExId) -> Option<OpMeta> {
+        self.op_meta_for(id)
+    }
+}
+
 impl ReadDoc for Automerge {
     fn parents<O: AsRef<ExId>>(&self, obj: O) -> Result<Parents<'_>, AutomergeError> {
         self.parents_for(obj.as_ref(), None)
@@ -1958,6 +3171,26 @@ impl ReadDoc for Automerge {
     }
 }
 
+impl system::ReadSystem for Automerge {
+    fn system_objects(&self) -> Vec<ExId> {
+        self.system_objects_for(None)
+    }
+}
+
+impl text_index::ReadTextIndex for Automerge {
+    fn convert_text_index(
+        &self,
+        obj: &ExId,
+        heads: Option<&[ChangeHash]>,
+        index: usize,
+        from: TextEncoding,
+        to: TextEncoding,
+    ) -> Result<usize, AutomergeError> {
+        let clock = heads.map(|heads| self.clock_at(heads));
+        self.convert_text_index_for(obj, index, from, to, clock)
+    }
+}
+
 impl ReadDocInternal for Automerge {
     fn live_obj_paths(&self) -> HashMap<ExId, Vec<(ExId, Prop)>> {
         self.visible_obj_paths(None)
@@ -1977,6 +3210,9 @@ pub struct SaveOptions {
     pub deflate: bool,
     /// Whether to save changes which we do not have the dependencies for
     pub retain_orphans: bool,
+    /// The codec and level to compress the columns with, which takes precedence over `deflate`
+    /// if set
+    pub compression: Option<Compression>,
 }
 
 impl std::default::Default for SaveOptions {
@@ -1984,6 +3220,7 @@ impl std::default::Default for SaveOptions {
         Self {
             deflate: true,
             retain_orphans: true,
+            compression: None,
         }
     }
 }
@@ -1995,6 +3232,73 @@ pub(crate) struct Isolation {
     clock: Clock,
 }
 
+/// Hash the sorted `heads` of a document, see [`Automerge::state_hash()`]
+pub(crate) fn hash_heads(heads: &[ChangeHash]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update((heads.len() as u64).to_be_bytes());
+    for head in heads {
+        hasher.update(head.as_bytes());
+    }
+    hasher.finalize().into()
+}
+
+/// The changes in `new` which aren't in the saved document `old`, in a form
+/// [`Automerge::load_incremental()`] accepts
+///
+/// This is for keeping a document in a file which is synced by some other means, such as a
+/// shared folder. Rather than replacing the whole file every time the document changes, the
+/// output of this can be appended to it, or stored next to it, and loading the result gives the
+/// same document as loading the output of [`Automerge::save()`]. The document in `old` is never
+/// loaded, we only read the heads and change hashes of its chunks, so this is cheap even for
+/// large documents. Changes in `old` which `new` doesn't have are ignored.
+///
+/// ```
+/// # use automerge::{save_diff, transaction::Transactable, AutoCommit, Automerge, ROOT};
+/// let mut doc = AutoCommit::new();
+/// doc.put(ROOT, "key", "value").unwrap();
+/// let mut file = doc.save();
+/// doc.put(ROOT, "other", "value").unwrap();
+/// file.extend(save_diff(&file, doc.document()).unwrap());
+/// assert_eq!(Automerge::load(&file).unwrap().get_heads(), doc.get_heads());
+/// ```
+pub fn save_diff(old: &[u8], new: &Automerge) -> Result<Vec<u8>, AutomergeError> {
+    let mut have = Vec::new();
+    let mut input = storage::parse::Input::new(old);
+    while !input.is_empty() {
+        let (remaining, chunk) =
+            storage::Chunk::parse(input).map_err(|e| load::Error::Parse(Box::new(e)))?;
+        if !chunk.checksum_valid() {
+            return Err(load::Error::BadChecksum.into());
+        }
+        match chunk {
+            storage::Chunk::Document(d) => have.extend(d.heads()),
+            storage::Chunk::Change(c) => have.push(c.hash()),
+            storage::Chunk::CompressedChange(c, _) => have.push(c.hash()),
+        }
+        input = remaining.reset();
+    }
+    have.retain(|hash| new.history_index.contains_key(hash));
+    Ok(new.save_after(&have))
+}
+
+/// Remove values which are identical to a later value, leaving the last, winning, value of each
+/// set of identical scalars
+fn collapse_identical(values: Vec<(Value<'_>, ExId)>) -> Vec<(Value<'_>, ExId)> {
+    let duplicated = values
+        .iter()
+        .enumerate()
+        .map(|(i, (value, _))| {
+            matches!(value, Value::Scalar(s) if !s.is_counter())
+                && values[i + 1..].iter().any(|(v, _)| v == value)
+        })
+        .collect::<Vec<_>>();
+    values
+        .into_iter()
+        .zip(duplicated)
+        .filter_map(|(value, duplicated)| (!duplicated).then_some(value))
+        .collect()
+}
+
 pub(crate) fn reconstruct_document<'a>(
     doc: &'a storage::Document<'a>,
     mode: VerificationMode,
@@ -2019,7 +3323,7 @@ pub(crate) fn reconstruct_document<'a>(
         change_graph.add_change(change, actor_index)?;
     }
     let history_index = hashes_by_index.into_iter().map(|(k, v)| (v, k)).collect();
-    Ok(Automerge {
+    let mut doc = Automerge {
         queue: vec![],
         history: changes,
         history_index,
@@ -2029,5 +3333,11 @@ pub(crate) fn reconstruct_document<'a>(
         deps: heads.into_iter().collect(),
         actor: Actor::Unused(ActorId::random()),
         max_op,
-    })
+        collapse_identical_conflicts: false,
+        max_pending_changes: None,
+    };
+    doc.refresh_mark_merge()?;
+    doc.refresh_counter_overflow()?;
+    doc.clock_seals();
+    Ok(doc)
 }
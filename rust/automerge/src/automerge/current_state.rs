@@ -2,8 +2,10 @@ use std::borrow::Cow;
 
 use itertools::Itertools;
 
+use super::diff;
 use crate::iter::{SpanInternal, SpansInternal};
 use crate::{
+    clock::Clock,
     patches::{PatchLog, TextRepresentation},
     types::{Key, ObjMeta, Op, OpId},
     Automerge, ObjType, OpType, Value,
@@ -32,7 +34,16 @@ pub(crate) fn log_current_state_patches(doc: &Automerge, patch_log: &mut PatchLo
     // Effectively then we iterate over each object, then we group the operations in the object by
     // key and for each key find the visible operations for that key. Then we notify the patch log
     // for each of those visible operations.
+    let mut all = None;
     for (obj, ops) in doc.ops().iter_objs() {
+        if doc.ops().seals.is_sealed(&obj.id, None) {
+            // the visible ops of a sealed object are those made before it was sealed
+            let all = all.get_or_insert_with(|| doc.clock_at(&doc.get_heads()));
+            if let Some(sealed) = doc.ops().seals.sealed_clock(&obj.id, all) {
+                diff::log_obj_diff(doc, &obj, &Clock::new(), &sealed, patch_log);
+                continue;
+            }
+        }
         let ops = ops.map(|i| i.as_op(doc.osd()));
         if obj.typ == ObjType::Text && matches!(patch_log.text_rep(), TextRepresentation::String) {
             log_text_patches(doc, patch_log, &obj, ops)
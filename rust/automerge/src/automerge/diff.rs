@@ -54,6 +54,7 @@ fn process<'a, T: Iterator<Item = Op<'a>>>(
 fn push_top<'a>(top: &mut Option<Winner<'a>>, op: Op<'a>, cross_visible: bool, clock: &'a Clock) {
     match op.action() {
         OpType::Increment(_) => {} // can ignore - info captured inside Counter
+        OpType::Seal(_) => {}      // doesn't change the value at the key
         _ => {
             top.replace(Winner {
                 op,
@@ -118,25 +119,49 @@ impl<'a> Patch<'a> {
 }
 
 pub(crate) fn log_diff(doc: &Automerge, before: &Clock, after: &Clock, patch_log: &mut PatchLog) {
-    for (obj, ops) in doc.ops().iter_objs() {
-        let mut diff = RichTextDiff::new(doc);
-        let ops_by_key = ops.chunk_by(|o| o.as_op(doc.osd()).elemid_or_key());
-        let diffs = ops_by_key.into_iter().filter_map(|(_key, key_ops)| {
-            process(
-                key_ops.map(|i| i.as_op(doc.osd())),
-                before,
-                after,
-                &mut diff,
-            )
-        });
+    for (obj, _) in doc.ops().iter_objs() {
+        // a sealed object only shows the ops which were made before it was sealed
+        let seals = &doc.ops().seals;
+        let sealed_before = seals.sealed_clock(&obj.id, before);
+        let sealed_after = seals.sealed_clock(&obj.id, after);
+        log_obj_diff(
+            doc,
+            &obj,
+            sealed_before.as_ref().unwrap_or(before),
+            sealed_after.as_ref().unwrap_or(after),
+            patch_log,
+        );
+    }
+}
 
-        if obj.typ == ObjType::Text && matches!(patch_log.text_rep(), TextRepresentation::String) {
-            log_text_diff(patch_log, &obj, diffs)
-        } else if obj.typ.is_sequence() {
-            log_list_diff(patch_log, &obj, diffs);
-        } else {
-            log_map_diff(doc, patch_log, &obj, diffs);
-        }
+/// Log the changes to `obj` between `before` and `after`
+pub(crate) fn log_obj_diff(
+    doc: &Automerge,
+    obj: &ObjMeta,
+    before: &Clock,
+    after: &Clock,
+    patch_log: &mut PatchLog,
+) {
+    let Some(ops) = doc.ops().iter_obj(&obj.id) else {
+        return;
+    };
+    let mut diff = RichTextDiff::new(doc);
+    let ops_by_key = ops.chunk_by(|o| o.as_op(doc.osd()).elemid_or_key());
+    let diffs = ops_by_key.into_iter().filter_map(|(_key, key_ops)| {
+        process(
+            key_ops.map(|i| i.as_op(doc.osd())),
+            before,
+            after,
+            &mut diff,
+        )
+    });
+
+    if obj.typ == ObjType::Text && matches!(patch_log.text_rep(), TextRepresentation::String) {
+        log_text_diff(patch_log, obj, diffs)
+    } else if obj.typ.is_sequence() {
+        log_list_diff(patch_log, obj, diffs);
+    } else {
+        log_map_diff(doc, patch_log, obj, diffs);
     }
 }
 
@@ -270,7 +295,10 @@ fn get_prop<'a>(doc: &'a Automerge, op: Op<'a>) -> Option<&'a str> {
 
 fn get_inc(before: &Winner<'_>, after: &Winner<'_>) -> Option<i64> {
     if before.op.is_counter() && after.op.is_counter() {
-        let n = after.op.inc_at(after.clock) - before.op.inc_at(before.clock);
+        // compare the values rather than the increments so bounded counters report the change
+        // in their clamped value
+        let n = after.op.value_at(Some(after.clock)).to_i64()?
+            - before.op.value_at(Some(before.clock)).to_i64()?;
         if n != 0 {
             return Some(n);
         }
@@ -1791,3 +1791,58 @@ fn hash_for_opid() {
     assert_eq!(doc.hash_for_opid(&id1), hash1);
     assert_eq!(doc.hash_for_opid(&id2), hash2);
 }
+
+#[test]
+fn objects_of_unknown_types_are_loaded_and_saved_unchanged() {
+    let mut doc = AutoCommit::new();
+    let future = doc.put_object(ROOT, "future", ObjType::Map).unwrap();
+    doc.put(&future, "inner", 1).unwrap();
+    doc.put(ROOT, "known", "value").unwrap();
+    doc.commit();
+
+    // pretend the object was created by a newer version with an action we don't know about
+    let mut expanded = doc.get_changes(&[])[0].decode();
+    expanded.operations[0].action = crate::legacy::OpType::Make(ObjType::Unknown(9));
+    expanded.hash = None;
+    let change = Change::from(expanded);
+
+    let mut doc = Automerge::new();
+    doc.apply_changes([change.clone()]).unwrap();
+    let (value, id) = doc.get(ROOT, "future").unwrap().unwrap();
+    assert_eq!(value, Value::Object(ObjType::Unknown(9)));
+    assert_eq!(doc.object_type(&id).unwrap(), ObjType::Unknown(9));
+    assert_eq!(
+        doc.get(ROOT, "known").unwrap().unwrap().0,
+        Value::from("value")
+    );
+
+    let mut tx = doc.transaction();
+    assert!(matches!(
+        tx.put(&id, "inner", 2),
+        Err(AutomergeError::InvalidOp(ObjType::Unknown(9)))
+    ));
+    assert!(tx.put_object(ROOT, "new", ObjType::Unknown(9)).is_err());
+    tx.commit();
+
+    let loaded = Automerge::load(&doc.save()).unwrap();
+    assert_eq!(
+        loaded.get(ROOT, "future").unwrap().unwrap().0,
+        Value::Object(ObjType::Unknown(9))
+    );
+    assert_eq!(loaded.get_changes(&[])[0].hash(), change.hash());
+}
+
+#[test]
+fn unknown_actions_outside_the_object_range_are_rejected() {
+    let mut doc = AutoCommit::new();
+    doc.put(ROOT, "key", ScalarValue::Null).unwrap();
+    doc.commit();
+
+    // an action which isn't reserved for new object types can't be preserved, even when the
+    // value looks like that of an op which makes an object
+    let mut expanded = doc.get_changes(&[])[0].decode();
+    expanded.operations[0].action = crate::legacy::OpType::Make(ObjType::Unknown(20));
+    expanded.hash = None;
+    let bytes = Change::from(expanded).raw_bytes().to_vec();
+    assert!(Change::try_from(bytes.as_slice()).is_err());
+}
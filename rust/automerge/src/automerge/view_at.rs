@@ -0,0 +1,523 @@
+use std::ops::RangeBounds;
+
+use crate::actor_metadata::{self, ActorMetadata};
+use crate::expiry;
+use crate::hydrate;
+use crate::iter::{
+    Keys, KeysRange, ListRange, MapPage, MapPageToken, MapRange, MarkBoundaries, Spans, TextChunks,
+    Values,
+};
+use crate::marks::{Mark, MarkSet};
+use crate::multi_value;
+use crate::patches::TextEncoding;
+use crate::read::Stats;
+use crate::system::ReadSystem;
+use crate::text_attribution::{self, TextAttribution};
+use crate::text_index::{self, ReadTextIndex};
+use crate::text_search::TextMatch;
+use crate::types::Clock;
+use crate::{
+    ActorId, Automerge, AutomergeError, Change, ChangeHash, Conflicts, Cursor, LastModified,
+    ObjId as ExId, ObjType, OpMeta, Parents, Prop, ReadDoc, Value,
+};
+
+/// A read only view of a document as at some heads, returned by [`Automerge::view_at()`]
+///
+/// Every method of [`ReadDoc`] which does not take heads reads the document as at the heads of
+/// the view, so a view can be passed to code which is generic over [`ReadDoc`] to have it read
+/// historical state. The same goes for the read methods the view shares with [`Automerge`], such
+/// as [`Self::find_text()`]. The methods which do take heads ignore the heads of the view.
+///
+/// The view works out which changes are visible once when it is created rather than on every
+/// read, so reading many values from a view is cheaper than calling the `_at` methods on the
+/// document with the same heads.
+///
+/// ```
+/// # use automerge::{AutoCommit, ReadDoc, ROOT, transaction::Transactable};
+/// let mut doc = AutoCommit::new();
+/// doc.put(&ROOT, "key", "before").unwrap();
+/// let heads = doc.get_heads();
+/// doc.put(&ROOT, "key", "after").unwrap();
+///
+/// let view = doc.document().view_at(&heads);
+/// let (value, _) = view.get(&ROOT, "key").unwrap().unwrap();
+/// assert_eq!(value.to_str(), Some("before"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ViewAt<'a> {
+    doc: &'a Automerge,
+    heads: Vec<ChangeHash>,
+    clock: Clock,
+}
+
+impl<'a> ViewAt<'a> {
+    pub(crate) fn new(doc: &'a Automerge, heads: &[ChangeHash]) -> Self {
+        Self {
+            doc,
+            heads: heads.to_vec(),
+            clock: doc.clock_at(heads),
+        }
+    }
+
+    /// The heads this view reads the document at
+    pub fn heads(&self) -> &[ChangeHash] {
+        &self.heads
+    }
+
+    /// The document this is a view of
+    pub fn document(&self) -> &'a Automerge {
+        self.doc
+    }
+
+    fn clock(&self) -> Option<Clock> {
+        Some(self.clock.clone())
+    }
+}
+
+impl<'a> ReadSystem for ViewAt<'a> {
+    fn system_objects(&self) -> Vec<ExId> {
+        self.doc.system_objects_for(self.clock())
+    }
+}
+
+impl<'a> ReadTextIndex for ViewAt<'a> {
+    fn convert_text_index(
+        &self,
+        obj: &ExId,
+        heads: Option<&[ChangeHash]>,
+        index: usize,
+        from: TextEncoding,
+        to: TextEncoding,
+    ) -> Result<usize, AutomergeError> {
+        let clock = match heads {
+            Some(heads) => Some(self.doc.clock_at(heads)),
+            None => self.clock(),
+        };
+        self.doc.convert_text_index_for(obj, index, from, to, clock)
+    }
+}
+
+impl<'a> ViewAt<'a> {
+    /// See [`Automerge::keys_range()`]
+    pub fn keys_range<'b, O: AsRef<ExId>, R: RangeBounds<String> + 'b>(
+        &'b self,
+        obj: O,
+        range: R,
+    ) -> KeysRange<'b, R> {
+        self.doc.keys_range_for(obj.as_ref(), range, self.clock())
+    }
+
+    /// See [`Automerge::keys_range_at()`]
+    pub fn keys_range_at<'b, O: AsRef<ExId>, R: RangeBounds<String> + 'b>(
+        &'b self,
+        obj: O,
+        range: R,
+        heads: &[ChangeHash],
+    ) -> KeysRange<'b, R> {
+        self.doc.keys_range_at(obj, range, heads)
+    }
+
+    /// See [`Automerge::map_page()`]
+    pub fn map_page<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        prefix: &str,
+        limit: usize,
+        continue_from: Option<&MapPageToken>,
+    ) -> MapPage<'_> {
+        self.doc
+            .map_page_for(obj.as_ref(), prefix, limit, continue_from, self.clock())
+    }
+
+    /// See [`Automerge::map_page_at()`]
+    pub fn map_page_at<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        prefix: &str,
+        limit: usize,
+        continue_from: Option<&MapPageToken>,
+        heads: &[ChangeHash],
+    ) -> MapPage<'_> {
+        self.doc
+            .map_page_at(obj, prefix, limit, continue_from, heads)
+    }
+
+    /// See [`Automerge::text_chunks()`]
+    pub fn text_chunks<O: AsRef<ExId>>(&self, obj: O) -> Result<TextChunks<'_>, AutomergeError> {
+        self.doc.text_chunks_for(obj.as_ref(), self.clock())
+    }
+
+    /// See [`Automerge::text_display_width()`]
+    pub fn text_display_width<O: AsRef<ExId>, R: RangeBounds<usize>>(
+        &self,
+        obj: O,
+        range: R,
+    ) -> Result<usize, AutomergeError> {
+        self.doc
+            .text_display_width_for(obj.as_ref(), range, self.clock())
+    }
+
+    /// See [`Automerge::find_text()`]
+    pub fn find_text<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        needle: &str,
+    ) -> Result<Vec<TextMatch>, AutomergeError> {
+        self.doc.find_text_for(obj.as_ref(), needle, self.clock())
+    }
+
+    /// See [`Automerge::get_unexpired()`]
+    pub fn get_unexpired<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+        now: i64,
+    ) -> Result<Option<(Value<'_>, ExId)>, AutomergeError> {
+        expiry::get_unexpired(self, obj.as_ref(), prop.into(), now)
+    }
+
+    /// See [`Automerge::actor_metadata()`]
+    pub fn actor_metadata(&self, actor: &ActorId) -> Result<Option<ActorMetadata>, AutomergeError> {
+        actor_metadata::actor_metadata(self, actor)
+    }
+
+    /// See [`Automerge::is_multi_value()`]
+    pub fn is_multi_value<O: AsRef<ExId>>(&self, obj: O) -> Result<bool, AutomergeError> {
+        multi_value::is_multi_value(self, obj.as_ref())
+    }
+
+    /// See [`Automerge::get_all_values()`]
+    pub fn get_all_values<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+    ) -> Result<Vec<(Value<'_>, ExId)>, AutomergeError> {
+        multi_value::get_all_values(self, obj.as_ref(), prop.into())
+    }
+
+    /// See [`Automerge::mark_boundaries()`]
+    pub fn mark_boundaries<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+    ) -> Result<MarkBoundaries<'_>, AutomergeError> {
+        self.doc.mark_boundaries_for(obj.as_ref(), self.clock())
+    }
+
+    /// See [`Automerge::mark_boundaries_at()`]
+    pub fn mark_boundaries_at<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        heads: &[ChangeHash],
+    ) -> Result<MarkBoundaries<'_>, AutomergeError> {
+        self.doc.mark_boundaries_at(obj, heads)
+    }
+
+    /// See [`Automerge::get_by_elem_id()`]
+    pub fn get_by_elem_id<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        elem_id: &ExId,
+    ) -> Result<Option<(usize, Value<'_>, ExId)>, AutomergeError> {
+        self.doc
+            .get_by_elem_id_for(obj.as_ref(), elem_id, self.clock())
+    }
+
+    /// See [`Automerge::is_sealed()`]
+    pub fn is_sealed<O: AsRef<ExId>>(&self, obj: O) -> Result<bool, AutomergeError> {
+        self.doc.is_sealed_for(obj.as_ref(), self.clock())
+    }
+
+    /// See [`Automerge::attribute_text()`]
+    pub fn attribute_text<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        heads: Option<&[ChangeHash]>,
+    ) -> Result<Vec<TextAttribution>, AutomergeError> {
+        text_attribution::attribute_text(self, self.doc, obj.as_ref(), heads)
+    }
+
+    /// See [`Automerge::get_cursor_with_encoding()`]
+    pub fn get_cursor_with_encoding<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        position: usize,
+        at: Option<&[ChangeHash]>,
+        encoding: TextEncoding,
+    ) -> Result<Cursor, AutomergeError> {
+        text_index::get_cursor(self, obj.as_ref(), position, at, encoding)
+    }
+
+    /// See [`Automerge::get_cursor_position_with_encoding()`]
+    pub fn get_cursor_position_with_encoding<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        cursor: &Cursor,
+        at: Option<&[ChangeHash]>,
+        encoding: TextEncoding,
+    ) -> Result<usize, AutomergeError> {
+        text_index::get_cursor_position(self, obj.as_ref(), cursor, at, encoding)
+    }
+
+    /// See [`Automerge::text_len()`]
+    pub fn text_len<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        encoding: TextEncoding,
+    ) -> Result<usize, AutomergeError> {
+        self.doc
+            .text_width_for(obj.as_ref(), encoding, self.clock())
+    }
+
+    /// See [`Automerge::get_conflicts()`]
+    pub fn get_conflicts<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+    ) -> Result<Conflicts<'_>, AutomergeError> {
+        self.doc
+            .get_conflicts_for(obj.as_ref(), prop.into(), self.clock())
+    }
+
+    /// See [`Automerge::get_conflicts_at()`]
+    pub fn get_conflicts_at<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+        heads: &[ChangeHash],
+    ) -> Result<Conflicts<'_>, AutomergeError> {
+        self.doc.get_conflicts_at(obj, prop, heads)
+    }
+
+    /// See [`Automerge::last_modified()`]
+    pub fn last_modified<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+    ) -> Result<Option<LastModified>, AutomergeError> {
+        self.doc
+            .last_modified_for(obj.as_ref(), prop.into(), self.clock())
+    }
+
+    /// See [`Automerge::last_modified_at()`]
+    pub fn last_modified_at<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+        heads: &[ChangeHash],
+    ) -> Result<Option<LastModified>, AutomergeError> {
+        self.doc.last_modified_at(obj, prop, heads)
+    }
+
+    /// See [`Automerge::op_meta()`]
+    pub fn op_meta(&self, id: &ExId) -> Option<OpMeta> {
+        self.doc.op_meta(id)
+    }
+}
+
+impl<'a> ReadDoc for ViewAt<'a> {
+    fn parents<O: AsRef<ExId>>(&self, obj: O) -> Result<Parents<'_>, AutomergeError> {
+        self.doc.parents_for(obj.as_ref(), self.clock())
+    }
+
+    fn parents_at<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        heads: &[ChangeHash],
+    ) -> Result<Parents<'_>, AutomergeError> {
+        self.doc.parents_at(obj, heads)
+    }
+
+    fn keys<O: AsRef<ExId>>(&self, obj: O) -> Keys<'_> {
+        self.doc.keys_for(obj.as_ref(), self.clock())
+    }
+
+    fn keys_at<O: AsRef<ExId>>(&self, obj: O, heads: &[ChangeHash]) -> Keys<'_> {
+        self.doc.keys_at(obj, heads)
+    }
+
+    fn map_range<'b, O: AsRef<ExId>, R: RangeBounds<String> + 'b>(
+        &'b self,
+        obj: O,
+        range: R,
+    ) -> MapRange<'b, R> {
+        self.doc.map_range_for(obj.as_ref(), range, self.clock())
+    }
+
+    fn map_range_at<'b, O: AsRef<ExId>, R: RangeBounds<String> + 'b>(
+        &'b self,
+        obj: O,
+        range: R,
+        heads: &[ChangeHash],
+    ) -> MapRange<'b, R> {
+        self.doc.map_range_at(obj, range, heads)
+    }
+
+    fn list_range<O: AsRef<ExId>, R: RangeBounds<usize>>(
+        &self,
+        obj: O,
+        range: R,
+    ) -> ListRange<'_, R> {
+        self.doc.list_range_for(obj.as_ref(), range, self.clock())
+    }
+
+    fn list_range_at<O: AsRef<ExId>, R: RangeBounds<usize>>(
+        &self,
+        obj: O,
+        range: R,
+        heads: &[ChangeHash],
+    ) -> ListRange<'_, R> {
+        self.doc.list_range_at(obj, range, heads)
+    }
+
+    fn values<O: AsRef<ExId>>(&self, obj: O) -> Values<'_> {
+        self.doc.values_for(obj.as_ref(), self.clock())
+    }
+
+    fn values_at<O: AsRef<ExId>>(&self, obj: O, heads: &[ChangeHash]) -> Values<'_> {
+        self.doc.values_at(obj, heads)
+    }
+
+    fn length<O: AsRef<ExId>>(&self, obj: O) -> usize {
+        self.doc.length_for(obj.as_ref(), self.clock())
+    }
+
+    fn length_at<O: AsRef<ExId>>(&self, obj: O, heads: &[ChangeHash]) -> usize {
+        self.doc.length_at(obj, heads)
+    }
+
+    fn object_type<O: AsRef<ExId>>(&self, obj: O) -> Result<ObjType, AutomergeError> {
+        self.doc.object_type(obj)
+    }
+
+    fn marks<O: AsRef<ExId>>(&self, obj: O) -> Result<Vec<Mark<'_>>, AutomergeError> {
+        self.doc.marks_for(obj.as_ref(), self.clock())
+    }
+
+    fn marks_at<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        heads: &[ChangeHash],
+    ) -> Result<Vec<Mark<'_>>, AutomergeError> {
+        self.doc.marks_at(obj, heads)
+    }
+
+    fn get_marks<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        index: usize,
+        heads: Option<&[ChangeHash]>,
+    ) -> Result<MarkSet, AutomergeError> {
+        match heads {
+            Some(heads) => self.doc.get_marks(obj, index, Some(heads)),
+            None => self.doc.get_marks_for(obj, index, self.clock()),
+        }
+    }
+
+    fn text<O: AsRef<ExId>>(&self, obj: O) -> Result<String, AutomergeError> {
+        self.doc.text_for(obj.as_ref(), self.clock())
+    }
+
+    fn text_at<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        heads: &[ChangeHash],
+    ) -> Result<String, AutomergeError> {
+        self.doc.text_at(obj, heads)
+    }
+
+    fn spans<O: AsRef<ExId>>(&self, obj: O) -> Result<Spans<'_>, AutomergeError> {
+        self.doc.spans_for(obj.as_ref(), self.clock())
+    }
+
+    fn spans_at<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        heads: &[ChangeHash],
+    ) -> Result<Spans<'_>, AutomergeError> {
+        self.doc.spans_at(obj, heads)
+    }
+
+    fn get_cursor<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        position: usize,
+        at: Option<&[ChangeHash]>,
+    ) -> Result<Cursor, AutomergeError> {
+        match at {
+            Some(heads) => self.doc.get_cursor(obj, position, Some(heads)),
+            None => self
+                .doc
+                .get_cursor_for(obj.as_ref(), position, self.clock()),
+        }
+    }
+
+    fn get_cursor_position<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        cursor: &Cursor,
+        at: Option<&[ChangeHash]>,
+    ) -> Result<usize, AutomergeError> {
+        match at {
+            Some(heads) => self.doc.get_cursor_position(obj, cursor, Some(heads)),
+            None => self
+                .doc
+                .get_cursor_position_for(obj.as_ref(), cursor, self.clock()),
+        }
+    }
+
+    fn get<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+    ) -> Result<Option<(Value<'_>, ExId)>, AutomergeError> {
+        self.doc.get_for(obj.as_ref(), prop.into(), self.clock())
+    }
+
+    fn get_at<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+        heads: &[ChangeHash],
+    ) -> Result<Option<(Value<'_>, ExId)>, AutomergeError> {
+        self.doc.get_at(obj, prop, heads)
+    }
+
+    fn hydrate<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        heads: Option<&[ChangeHash]>,
+    ) -> Result<hydrate::Value, AutomergeError> {
+        self.doc
+            .hydrate_obj(obj.as_ref(), Some(heads.unwrap_or(&self.heads)))
+    }
+
+    fn get_all<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+    ) -> Result<Vec<(Value<'_>, ExId)>, AutomergeError> {
+        self.doc
+            .get_all_for(obj.as_ref(), prop.into(), self.clock())
+    }
+
+    fn get_all_at<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+        heads: &[ChangeHash],
+    ) -> Result<Vec<(Value<'_>, ExId)>, AutomergeError> {
+        self.doc.get_all_at(obj, prop, heads)
+    }
+
+    fn get_missing_deps(&self, heads: &[ChangeHash]) -> Vec<ChangeHash> {
+        self.doc.get_missing_deps(heads)
+    }
+
+    fn get_change_by_hash(&self, hash: &ChangeHash) -> Option<&Change> {
+        self.doc.get_change_by_hash(hash)
+    }
+
+    fn stats(&self) -> Stats {
+        self.doc.stats()
+    }
+}
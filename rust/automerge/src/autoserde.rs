@@ -122,6 +122,7 @@ impl<'a, R: crate::ReadDoc> serde::Serialize for AutoSerdeVal<'a, R> {
                 };
                 seq.serialize(serializer)
             }
+            Value::Object(ObjType::Unknown(_)) => serializer.serialize_unit(),
             Value::Scalar(v) => v.serialize(serializer),
         }
     }
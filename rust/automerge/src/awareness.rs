@@ -0,0 +1,278 @@
+//! Ranges of text to share with peers outside of the document
+//!
+//! Applications often want to show peers things which are not part of the document - search
+//! results, selections, highlights - which refer to ranges of a text object. Sending raw indices
+//! for these ranges is wrong the moment either side edits the text, so instead
+//! [`RemoteRanges`] anchors each range to a pair of [`Cursor`]s. The sender encodes the ranges
+//! with [`RemoteRanges::to_bytes()`] and sends them over whatever ephemeral channel it uses for
+//! awareness data, the receiver decodes them and calls [`RemoteRanges::resolve()`] to get index
+//! ranges in terms of its own state of the document.
+//!
+//! ```
+//! # use automerge::{AutoCommit, ObjType, ReadDoc, ROOT, transaction::Transactable};
+//! # use automerge::awareness::{AnchoredRange, RemoteRanges};
+//! let mut doc = AutoCommit::new();
+//! let text = doc.put_object(&ROOT, "text", ObjType::Text).unwrap();
+//! doc.splice_text(&text, 0, 0, "hello world").unwrap();
+//! let mut peer = doc.fork();
+//!
+//! // Highlight "world"
+//! let mut ranges = RemoteRanges::new();
+//! ranges.push(AnchoredRange::new(&doc, &text, 6..11, "search").unwrap());
+//! let bytes = ranges.to_bytes();
+//!
+//! // Meanwhile the peer has inserted some text before the highlighted word
+//! peer.splice_text(&text, 0, 0, "oh, ").unwrap();
+//!
+//! let received = RemoteRanges::try_from(bytes.as_slice()).unwrap();
+//! let resolved = received.resolve(&peer).unwrap();
+//! assert_eq!(resolved[0].range, 10..15);
+//! assert_eq!(resolved[0].label, "search");
+//! ```
+use std::ops::Range;
+
+use crate::exid::ExId;
+use crate::storage::parse;
+use crate::{AutomergeError, Cursor, ReadDoc};
+
+const SERIALIZATION_VERSION_TAG: u8 = 0;
+
+/// A non empty range of a list or text object anchored by a pair of [`Cursor`]s
+///
+/// The start cursor points at the first element in the range and the end cursor points at the
+/// last element in the range (rather than one past it, which may not exist).
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnchoredRange {
+    /// The list or text object this range is in
+    pub obj: ExId,
+    /// The cursor for the first element of the range
+    pub start: Cursor,
+    /// The cursor for the last element of the range
+    pub end: Cursor,
+    /// An application defined label for the range, e.g. `"search"` or `"selection"`
+    pub label: String,
+}
+
+impl AnchoredRange {
+    /// Anchor the index range `range` in `obj`
+    ///
+    /// # Errors
+    ///
+    /// * [`AutomergeError::InvalidIndex`] if the range is empty or extends past the end of `obj`
+    /// * Any error returned by [`ReadDoc::get_cursor()`]
+    pub fn new<R: ReadDoc, O: AsRef<ExId>, S: Into<String>>(
+        doc: &R,
+        obj: O,
+        range: Range<usize>,
+        label: S,
+    ) -> Result<Self, AutomergeError> {
+        if range.is_empty() {
+            return Err(AutomergeError::InvalidIndex(range.start));
+        }
+        let obj = obj.as_ref();
+        let start = doc.get_cursor(obj, range.start, None)?;
+        let end = doc.get_cursor(obj, range.end - 1, None)?;
+        Ok(Self {
+            obj: obj.clone(),
+            start,
+            end,
+            label: label.into(),
+        })
+    }
+
+    /// Resolve this range against the current state of `doc`
+    ///
+    /// If the elements the cursors point at have been deleted the range shrinks accordingly, if
+    /// all the elements in the range have been deleted the result will be an empty range.
+    pub fn resolve<R: ReadDoc>(&self, doc: &R) -> Result<Range<usize>, AutomergeError> {
+        let start = doc.get_cursor_position(&self.obj, &self.start, None)?;
+        let end = doc.get_cursor_position(&self.obj, &self.end, None)?;
+        let end_element_visible =
+            doc.get_cursor(&self.obj, end, None).ok().as_ref() == Some(&self.end);
+        let end = if end_element_visible { end + 1 } else { end };
+        Ok(start..end.max(start))
+    }
+}
+
+/// A range from [`RemoteRanges::resolve()`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedRange<'a> {
+    /// The list or text object this range is in
+    pub obj: &'a ExId,
+    /// The indices of the range in the local document
+    pub range: Range<usize>,
+    /// The label the range was created with
+    pub label: &'a str,
+}
+
+/// A set of [`AnchoredRange`]s which can be sent to and resolved by a peer
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RemoteRanges {
+    ranges: Vec<AnchoredRange>,
+}
+
+impl RemoteRanges {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, range: AnchoredRange) {
+        self.ranges.push(range)
+    }
+
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &AnchoredRange> {
+        self.ranges.iter()
+    }
+
+    /// Resolve all the ranges against the current state of `doc`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the objects or cursors are not in `doc`, which usually means
+    /// the peer which sent the ranges has changes which have not been synced yet.
+    pub fn resolve<'a, R: ReadDoc>(
+        &'a self,
+        doc: &R,
+    ) -> Result<Vec<ResolvedRange<'a>>, AutomergeError> {
+        self.ranges
+            .iter()
+            .map(|r| {
+                Ok(ResolvedRange {
+                    obj: &r.obj,
+                    range: r.resolve(doc)?,
+                    label: &r.label,
+                })
+            })
+            .collect()
+    }
+
+    /// Serialize these ranges to bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        // The serialized format is a version byte (currently always `0`), the uLEB encoded number
+        // of ranges and then for each range the object ID, start cursor, end cursor and label,
+        // each of which is prefixed by its uLEB encoded length
+        let mut bytes = vec![SERIALIZATION_VERSION_TAG];
+        leb128::write::unsigned(&mut bytes, self.ranges.len() as u64).unwrap();
+        for range in &self.ranges {
+            for part in [
+                range.obj.to_bytes(),
+                range.start.to_bytes(),
+                range.end.to_bytes(),
+                range.label.as_bytes().to_vec(),
+            ] {
+                leb128::write::unsigned(&mut bytes, part.len() as u64).unwrap();
+                bytes.extend(part);
+            }
+        }
+        bytes
+    }
+}
+
+impl FromIterator<AnchoredRange> for RemoteRanges {
+    fn from_iter<T: IntoIterator<Item = AnchoredRange>>(iter: T) -> Self {
+        Self {
+            ranges: iter.into_iter().collect(),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DecodeRemoteRangesError {
+    #[error("invalid version tag")]
+    InvalidVersion,
+    #[error("not enough bytes")]
+    NotEnoughBytes,
+    #[error("invalid object id: {0}")]
+    InvalidObjId(#[from] crate::ObjIdFromBytesError),
+    #[error("invalid cursor")]
+    InvalidCursor,
+    #[error("label was not valid UTF-8")]
+    InvalidLabel,
+}
+
+impl<'a> TryFrom<&'a [u8]> for RemoteRanges {
+    type Error = DecodeRemoteRangesError;
+
+    fn try_from(value: &'a [u8]) -> Result<Self, Self::Error> {
+        let i = parse::Input::new(value);
+        let (i, version) =
+            parse::take1::<()>(i).map_err(|_| DecodeRemoteRangesError::NotEnoughBytes)?;
+        if version != SERIALIZATION_VERSION_TAG {
+            return Err(DecodeRemoteRangesError::InvalidVersion);
+        }
+        let (mut i, count) = parse::leb128_u64::<parse::leb128::Error>(i)
+            .map_err(|_| DecodeRemoteRangesError::NotEnoughBytes)?;
+        let mut ranges = Vec::new();
+        for _ in 0..count {
+            let mut parts: [&[u8]; 4] = [&[]; 4];
+            for part in parts.iter_mut() {
+                let (next, bytes) = parse::length_prefixed_bytes::<parse::leb128::Error>(i)
+                    .map_err(|_| DecodeRemoteRangesError::NotEnoughBytes)?;
+                *part = bytes;
+                i = next;
+            }
+            let [obj, start, end, label] = parts;
+            ranges.push(AnchoredRange {
+                obj: ExId::try_from(obj)?,
+                start: Cursor::try_from(start)
+                    .map_err(|_| DecodeRemoteRangesError::InvalidCursor)?,
+                end: Cursor::try_from(end).map_err(|_| DecodeRemoteRangesError::InvalidCursor)?,
+                label: String::from_utf8(label.to_vec())
+                    .map_err(|_| DecodeRemoteRangesError::InvalidLabel)?,
+            });
+        }
+        Ok(Self { ranges })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AnchoredRange, RemoteRanges};
+    use crate::{transaction::Transactable, AutoCommit, ObjType, ROOT};
+
+    #[test]
+    fn ranges_round_trip_through_bytes() {
+        let mut doc = AutoCommit::new();
+        let text = doc.put_object(&ROOT, "text", ObjType::Text).unwrap();
+        doc.splice_text(&text, 0, 0, "the quick brown fox").unwrap();
+        let ranges = [(4..9, "search"), (10..15, "selection")]
+            .into_iter()
+            .map(|(r, label)| AnchoredRange::new(&doc, &text, r, label).unwrap())
+            .collect::<RemoteRanges>();
+        let decoded = RemoteRanges::try_from(ranges.to_bytes().as_slice()).unwrap();
+        assert_eq!(decoded, ranges);
+    }
+
+    #[test]
+    fn ranges_shrink_when_text_is_deleted() {
+        let mut doc = AutoCommit::new();
+        let text = doc.put_object(&ROOT, "text", ObjType::Text).unwrap();
+        doc.splice_text(&text, 0, 0, "the quick brown fox").unwrap();
+        let range = AnchoredRange::new(&doc, &text, 4..15, "search").unwrap();
+
+        // delete "brown"
+        doc.splice_text(&text, 10, 5, "").unwrap();
+        assert_eq!(range.resolve(&doc).unwrap(), 4..10);
+
+        // delete "quick "
+        doc.splice_text(&text, 4, 6, "").unwrap();
+        assert_eq!(range.resolve(&doc).unwrap(), 4..4);
+    }
+
+    #[test]
+    fn empty_ranges_are_rejected() {
+        let mut doc = AutoCommit::new();
+        let text = doc.put_object(&ROOT, "text", ObjType::Text).unwrap();
+        doc.splice_text(&text, 0, 0, "abc").unwrap();
+        assert!(AnchoredRange::new(&doc, &text, 1..1, "search").is_err());
+        assert!(AnchoredRange::new(&doc, &text, 1..4, "search").is_err());
+    }
+}
@@ -2,11 +2,13 @@ use std::{borrow::Cow, num::NonZeroU64};
 
 use crate::{
     columnar::Key as StoredKey,
+    error::InvalidChangeMetadata,
     storage::{
         change::{Unverified, Verified},
         parse, Change as StoredChange, ChangeOp, Chunk, Compressed, ReadChangeOpError,
     },
     types::{ActorId, ChangeHash, ElemId},
+    ChangeMetadata,
 };
 
 #[derive(Clone, Debug, PartialEq)]
@@ -116,6 +118,11 @@ impl Change {
         self.stored.extra_bytes()
     }
 
+    /// The extra bytes of this change parsed as [`ChangeMetadata`]
+    pub fn metadata(&self) -> Result<ChangeMetadata, InvalidChangeMetadata> {
+        ChangeMetadata::from_bytes(self.extra_bytes())
+    }
+
     // TODO replace all uses of this with TryFrom<&[u8]>
     pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, LoadError> {
         Self::try_from(&bytes[..])
@@ -0,0 +1,139 @@
+use crate::error::InvalidChangeMetadata;
+
+/// Typed metadata stored in the extra bytes of a change
+///
+/// The extra bytes of a change are an opaque byte array as far as automerge is concerned, which
+/// means that two libraries which both want to store something in them will overwrite each
+/// other. `ChangeMetadata` gives them a shared structure: a sequence of entries each made up of a
+/// tag, the length of the value and the value, with the tag and length encoded as unsigned
+/// LEB128 integers. Each library picks a tag and only reads and writes the entries with that
+/// tag, entries with tags it does not know about are kept as they are.
+///
+/// Metadata is attached to a change using [`crate::transaction::CommitOptions::with_metadata()`]
+/// and read back with [`crate::Change::metadata()`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChangeMetadata {
+    entries: Vec<(u64, Vec<u8>)>,
+}
+
+impl ChangeMetadata {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse the extra bytes of a change
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the bytes are not a sequence of tag-length-value entries, for example
+    /// because the change was made by an application which used the extra bytes for something
+    /// else.
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, InvalidChangeMetadata> {
+        let mut entries = Vec::new();
+        while !bytes.is_empty() {
+            let tag = leb128::read::unsigned(&mut bytes).map_err(|_| InvalidChangeMetadata)?;
+            let len = leb128::read::unsigned(&mut bytes).map_err(|_| InvalidChangeMetadata)?;
+            let len = usize::try_from(len).map_err(|_| InvalidChangeMetadata)?;
+            if len > bytes.len() {
+                return Err(InvalidChangeMetadata);
+            }
+            let (value, rest) = bytes.split_at(len);
+            entries.push((tag, value.to_vec()));
+            bytes = rest;
+        }
+        Ok(Self { entries })
+    }
+
+    /// Encode the metadata as the extra bytes of a change
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for (tag, value) in &self.entries {
+            leb128::write::unsigned(&mut bytes, *tag).unwrap();
+            leb128::write::unsigned(&mut bytes, value.len() as u64).unwrap();
+            bytes.extend_from_slice(value);
+        }
+        bytes
+    }
+
+    /// The value stored under `tag`, if there is one
+    pub fn get(&self, tag: u64) -> Option<&[u8]> {
+        self.entries
+            .iter()
+            .find(|(t, _)| *t == tag)
+            .map(|(_, value)| value.as_slice())
+    }
+
+    /// Store `value` under `tag`, returning the value which was previously stored there
+    pub fn insert<V: Into<Vec<u8>>>(&mut self, tag: u64, value: V) -> Option<Vec<u8>> {
+        let value = value.into();
+        match self.entries.iter_mut().find(|(t, _)| *t == tag) {
+            Some((_, old)) => Some(std::mem::replace(old, value)),
+            None => {
+                self.entries.push((tag, value));
+                None
+            }
+        }
+    }
+
+    /// Remove the value stored under `tag`, returning it
+    pub fn remove(&mut self, tag: u64) -> Option<Vec<u8>> {
+        let index = self.entries.iter().position(|(t, _)| *t == tag)?;
+        Some(self.entries.remove(index).1)
+    }
+
+    /// The tags and values in the order they are encoded
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &[u8])> {
+        self.entries
+            .iter()
+            .map(|(tag, value)| (*tag, value.as_slice()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChangeMetadata;
+    use crate::transaction::{CommitOptions, Transactable};
+    use crate::{AutoCommit, ReadDoc, ROOT};
+
+    #[test]
+    fn metadata_round_trips_through_a_change() {
+        let mut doc = AutoCommit::new();
+        doc.put(&ROOT, "a", 1).unwrap();
+        let hash = doc
+            .commit_with(
+                CommitOptions::default()
+                    .with_message("first")
+                    .with_metadata(1, "author=alice")
+                    .with_metadata(300, vec![0, 1, 2]),
+            )
+            .unwrap();
+        let change = doc.get_change_by_hash(&hash).unwrap();
+        let metadata = change.metadata().unwrap();
+        assert_eq!(metadata.get(1), Some(&b"author=alice"[..]));
+        assert_eq!(metadata.get(300), Some(&[0, 1, 2][..]));
+        assert_eq!(metadata.get(2), None);
+        assert_eq!(change.message().map(String::as_str), Some("first"));
+
+        // another library can add its own entry without losing ours
+        let mut updated = ChangeMetadata::from_bytes(change.extra_bytes()).unwrap();
+        updated.insert(7, "other");
+        let reparsed = ChangeMetadata::from_bytes(&updated.to_bytes()).unwrap();
+        assert_eq!(
+            reparsed.iter().map(|(t, _)| t).collect::<Vec<_>>(),
+            [1, 300, 7]
+        );
+
+        // changes without extra bytes have empty metadata
+        doc.put(&ROOT, "b", 2).unwrap();
+        let hash = doc.commit().unwrap();
+        let change = doc.get_change_by_hash(&hash).unwrap();
+        assert!(change.metadata().unwrap().is_empty());
+        assert!(doc.get(&ROOT, "b").unwrap().is_some());
+
+        assert!(ChangeMetadata::from_bytes(&[1, 5, 0]).is_err());
+    }
+}
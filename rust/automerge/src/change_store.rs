@@ -0,0 +1,347 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::storage::{self, load, parse};
+use crate::{AutomergeError, Change, ChangeHash};
+
+const DEFAULT_CACHE_CAPACITY: usize = 256;
+
+/// A read only store of changes which leaves the encoded changes in their original buffer
+///
+/// The output of [`crate::Automerge::save_after()`] (or any concatenation of
+/// [`Change::bytes()`]) is a sequence of individually (and usually DEFLATE compressed) change
+/// chunks. Most of these chunks are very rarely looked at once a document has been loaded, so
+/// rather than inflating all of them into memory this store indexes the chunks once when it is
+/// created and then only decompresses a change when it is requested via [`Self::get_change()`] or
+/// [`Self::get_changes()`].
+///
+/// The backing buffer can be anything which implements `AsRef<[u8]>`. The intended use is a
+/// memory mapped file (e.g. a `memmap2::Mmap`) so that the compressed bytes stay on disk and are
+/// paged in by the OS as required, but a `Vec<u8>` works just as well.
+///
+/// Decompressed changes are kept in a least recently used cache, the size of which can be set
+/// with [`Self::with_cache_capacity()`]. Use [`crate::Automerge::apply_changes_from_store()`] to
+/// bring a document up to date from a store, which only decompresses the changes the document
+/// doesn't have.
+///
+/// The store is separate from any document. A document which changes are applied to still
+/// inflates them and keeps every change in its own history, so loading a document uses as much
+/// memory as before. The saving is for code which can answer requests for changes (e.g. a relay
+/// serving peers) from a store instead of from a loaded document.
+///
+/// Document chunks (the output of [`crate::Automerge::save()`]) are not supported as they do not
+/// store changes individually, [`Self::new()`] will return an error if it encounters one.
+#[derive(Debug)]
+pub struct ChangeStore<B> {
+    bytes: B,
+    entries: Vec<Entry>,
+    index: HashMap<ChangeHash, usize>,
+    heads: Vec<ChangeHash>,
+    cache: ChangeCache,
+}
+
+#[derive(Debug)]
+struct Entry {
+    hash: ChangeHash,
+    deps: Vec<ChangeHash>,
+    start: usize,
+    end: usize,
+}
+
+impl<B: AsRef<[u8]>> ChangeStore<B> {
+    /// Index the change chunks in `bytes`
+    ///
+    /// Every chunk is parsed (and if necessary decompressed) once in order to verify its checksum
+    /// and read its hash and dependencies, after which the decompressed data is discarded.
+    ///
+    /// # Errors
+    ///
+    /// * [`AutomergeError::Load`] if any chunk fails to parse or has a bad checksum
+    /// * [`AutomergeError::NonChangeCompressed`] if `bytes` contains a document chunk
+    pub fn new(bytes: B) -> Result<Self, AutomergeError> {
+        Self::with_cache_capacity(bytes, DEFAULT_CACHE_CAPACITY)
+    }
+
+    /// Like [`Self::new()`] but keep at most `capacity` decompressed changes in memory
+    pub fn with_cache_capacity(bytes: B, capacity: usize) -> Result<Self, AutomergeError> {
+        let mut entries = Vec::new();
+        let mut index = HashMap::new();
+        let data = bytes.as_ref();
+        let mut offset = 0;
+        while offset < data.len() {
+            let (remaining, chunk) = storage::Chunk::parse(parse::Input::new(&data[offset..]))
+                .map_err(|e| load::Error::Parse(Box::new(e)))?;
+            if !chunk.checksum_valid() {
+                return Err(load::Error::BadChecksum.into());
+            }
+            let (hash, deps) = match chunk {
+                storage::Chunk::Change(c) => (c.hash(), c.dependencies().to_vec()),
+                storage::Chunk::CompressedChange(c, _) => (c.hash(), c.dependencies().to_vec()),
+                storage::Chunk::Document(_) => return Err(AutomergeError::NonChangeCompressed),
+            };
+            let end = data.len() - remaining.unconsumed_bytes().len();
+            if let std::collections::hash_map::Entry::Vacant(e) = index.entry(hash) {
+                e.insert(entries.len());
+                entries.push(Entry {
+                    hash,
+                    deps,
+                    start: offset,
+                    end,
+                });
+            }
+            offset = end;
+        }
+
+        let mut heads = entries.iter().map(|e| e.hash).collect::<HashSet<_>>();
+        for entry in &entries {
+            for dep in &entry.deps {
+                heads.remove(dep);
+            }
+        }
+        let mut heads = heads.into_iter().collect::<Vec<_>>();
+        heads.sort_unstable();
+
+        Ok(Self {
+            bytes,
+            entries,
+            index,
+            heads,
+            cache: ChangeCache::new(capacity),
+        })
+    }
+
+    /// The number of changes in this store
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether this store contains any changes
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Whether this store contains the change with hash `hash`
+    pub fn contains(&self, hash: &ChangeHash) -> bool {
+        self.index.contains_key(hash)
+    }
+
+    /// The hashes of all the changes in this store, in the order they appear in the buffer
+    pub fn hashes(&self) -> impl Iterator<Item = ChangeHash> + '_ {
+        self.entries.iter().map(|e| e.hash)
+    }
+
+    /// The dependencies of the change with hash `hash`, without decompressing it
+    pub fn deps(&self, hash: &ChangeHash) -> Option<&[ChangeHash]> {
+        self.index
+            .get(hash)
+            .map(|idx| self.entries[*idx].deps.as_slice())
+    }
+
+    /// The changes in this store which no other change in this store depends on
+    ///
+    /// Note that dependencies which are not in the store are not considered
+    pub fn get_heads(&self) -> Vec<ChangeHash> {
+        self.heads.clone()
+    }
+
+    /// The number of decompressed changes currently held in memory
+    pub fn cached_len(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Get a change by its hash, decompressing it if it is not in the cache
+    ///
+    /// Returns `Ok(None)` if there is no change with this hash in the store
+    pub fn get_change(&mut self, hash: &ChangeHash) -> Result<Option<Change>, AutomergeError> {
+        let Some(idx) = self.index.get(hash).copied() else {
+            return Ok(None);
+        };
+        if let Some(change) = self.cache.get(hash) {
+            return Ok(Some(change.clone()));
+        }
+        let entry = &self.entries[idx];
+        let change = Change::try_from(&self.bytes.as_ref()[entry.start..entry.end])?;
+        self.cache.insert(*hash, change.clone());
+        Ok(Some(change))
+    }
+
+    /// Get all the changes in this store which are not transitive dependencies of `have_deps`
+    ///
+    /// This is the equivalent of [`crate::Automerge::get_changes()`] and only decompresses the
+    /// changes which are returned. Changes are returned in the order they appear in the buffer.
+    pub fn get_changes(&mut self, have_deps: &[ChangeHash]) -> Result<Vec<Change>, AutomergeError> {
+        let mut have = HashSet::new();
+        let mut stack = have_deps
+            .iter()
+            .filter(|h| self.index.contains_key(h))
+            .copied()
+            .collect::<Vec<_>>();
+        while let Some(hash) = stack.pop() {
+            if have.insert(hash) {
+                if let Some(deps) = self.deps(&hash) {
+                    stack.extend(deps.iter().filter(|d| !have.contains(*d)));
+                }
+            }
+        }
+        let wanted = self
+            .entries
+            .iter()
+            .map(|e| e.hash)
+            .filter(|h| !have.contains(h))
+            .collect::<Vec<_>>();
+        let mut changes = Vec::with_capacity(wanted.len());
+        for hash in wanted {
+            changes.extend(self.get_change(&hash)?);
+        }
+        Ok(changes)
+    }
+
+    /// Drop all the decompressed changes held in memory
+    pub fn clear_cache(&mut self) {
+        self.cache.clear();
+    }
+
+    /// Consume the store and return the backing buffer
+    pub fn into_inner(self) -> B {
+        self.bytes
+    }
+}
+
+/// A least recently used cache of decompressed changes
+///
+/// Each change is stamped with the time it was last used, and `order` maps the stamps back to
+/// the changes, so finding the least recently used change doesn't visit the rest of the cache.
+#[derive(Debug)]
+struct ChangeCache {
+    capacity: usize,
+    changes: HashMap<ChangeHash, (Change, u64)>,
+    order: BTreeMap<u64, ChangeHash>,
+    clock: u64,
+}
+
+impl ChangeCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            changes: HashMap::new(),
+            order: BTreeMap::new(),
+            clock: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    fn get(&mut self, hash: &ChangeHash) -> Option<&Change> {
+        self.touch(hash);
+        self.changes.get(hash).map(|(change, _)| change)
+    }
+
+    fn insert(&mut self, hash: ChangeHash, change: Change) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.clock += 1;
+        if let Some((_, used)) = self.changes.insert(hash, (change, self.clock)) {
+            self.order.remove(&used);
+        }
+        self.order.insert(self.clock, hash);
+        while self.changes.len() > self.capacity {
+            if let Some((_, evicted)) = self.order.pop_first() {
+                self.changes.remove(&evicted);
+            }
+        }
+    }
+
+    fn touch(&mut self, hash: &ChangeHash) {
+        if let Some((_, used)) = self.changes.get_mut(hash) {
+            self.order.remove(used);
+            self.clock += 1;
+            *used = self.clock;
+            self.order.insert(self.clock, *hash);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.changes.clear();
+        self.order.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChangeStore;
+    use crate::{transaction::Transactable, AutoCommit, ReadDoc, ROOT};
+
+    fn doc_with_changes(n: usize) -> AutoCommit {
+        let mut doc = AutoCommit::new();
+        for i in 0..n {
+            doc.put(ROOT, format!("key{}", i), i as i64).unwrap();
+            doc.commit();
+        }
+        doc
+    }
+
+    #[test]
+    fn indexes_changes_without_caching_them() {
+        let mut doc = doc_with_changes(5);
+        let bytes = doc.save_after(&[]);
+        let store = ChangeStore::new(bytes).unwrap();
+        assert_eq!(store.len(), 5);
+        assert_eq!(store.cached_len(), 0);
+        assert_eq!(store.get_heads(), doc.get_heads());
+    }
+
+    #[test]
+    fn get_changes_only_returns_missing_changes() {
+        let mut doc = doc_with_changes(3);
+        let heads = doc.get_heads();
+        doc.put(ROOT, "more", "stuff").unwrap();
+        doc.commit();
+        let mut store = ChangeStore::new(doc.save_after(&[])).unwrap();
+
+        let changes = store.get_changes(&heads).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(store.cached_len(), 1);
+
+        let all = store.get_changes(&[]).unwrap();
+        let mut loaded = AutoCommit::new();
+        loaded.apply_changes(all).unwrap();
+        assert_eq!(loaded.get_heads(), doc.get_heads());
+        assert_eq!(loaded.hydrate(&ROOT, None), doc.hydrate(&ROOT, None));
+    }
+
+    #[test]
+    fn evicts_least_recently_used_changes() {
+        let mut doc = doc_with_changes(4);
+        let mut store = ChangeStore::with_cache_capacity(doc.save_after(&[]), 2).unwrap();
+        let hashes = store.hashes().collect::<Vec<_>>();
+        store.get_change(&hashes[0]).unwrap().unwrap();
+        store.get_change(&hashes[1]).unwrap().unwrap();
+        store.get_change(&hashes[0]).unwrap().unwrap();
+        store.get_change(&hashes[2]).unwrap().unwrap();
+        assert_eq!(store.cached_len(), 2);
+        assert!(store.cache.changes.contains_key(&hashes[0]));
+        assert!(!store.cache.changes.contains_key(&hashes[1]));
+    }
+
+    #[test]
+    fn documents_only_decompress_the_changes_they_are_missing() {
+        let mut doc = doc_with_changes(3);
+        let mut behind = doc.fork();
+        doc.put(ROOT, "more", "stuff").unwrap();
+        doc.commit();
+        let mut store = ChangeStore::new(doc.save_after(&[])).unwrap();
+
+        behind.apply_changes_from_store(&mut store).unwrap();
+        assert_eq!(store.cached_len(), 1);
+        assert_eq!(behind.get_heads(), doc.get_heads());
+        assert_eq!(behind.get(ROOT, "more").unwrap().unwrap().0, "stuff".into());
+    }
+
+    #[test]
+    fn rejects_document_chunks() {
+        let mut doc = doc_with_changes(1);
+        assert!(ChangeStore::new(doc.save()).is_err());
+    }
+}
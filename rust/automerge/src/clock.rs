@@ -17,9 +17,14 @@ impl PartialOrd for ClockData {
     }
 }
 
-/// Vector clock mapping actor indices to the max op counter of the changes created by that actor.
+/// A vector clock, describing the set of changes which are causally before a set of heads
+///
+/// Create a clock with [`crate::Automerge::clock_at()`] and use [`Self::is_ancestor_of()`] and
+/// [`Self::concurrent_with()`] (or the [`PartialOrd`] implementation) to compare it with other
+/// clocks. Clocks record actors by their index in a particular document so only clocks from the
+/// same document can be compared.
 #[derive(Default, Debug, Clone, PartialEq)]
-pub(crate) struct Clock(im::HashMap<usize, ClockData, FxBuildHasher>);
+pub struct Clock(im::HashMap<usize, ClockData, FxBuildHasher>);
 
 // A general clock is greater if it has one element the other does not or has a counter higher than
 // the other for a given actor.
@@ -47,6 +52,21 @@ impl Clock {
         Clock(Default::default())
     }
 
+    /// Whether every change included in this clock is also included in `other`
+    ///
+    /// This is true if the clocks are equal
+    pub fn is_ancestor_of(&self, other: &Clock) -> bool {
+        matches!(
+            self.partial_cmp(other),
+            Some(Ordering::Less | Ordering::Equal)
+        )
+    }
+
+    /// Whether each of this clock and `other` include changes which the other does not
+    pub fn concurrent_with(&self, other: &Clock) -> bool {
+        self.partial_cmp(other).is_none()
+    }
+
     pub(crate) fn merge(a: &Clock, b: &Clock) -> Clock {
         if a.0.len() > b.0.len() {
             Self::merge(b, a)
@@ -67,6 +87,22 @@ impl Clock {
         }
     }
 
+    /// The clock containing the operations which are in both `a` and `b`
+    pub(crate) fn intersect(a: &Clock, b: &Clock) -> Clock {
+        let mut intersection = Clock::new();
+        for (key, a_value) in a.0.iter() {
+            if let Some(b_value) = b.0.get(key) {
+                let value = if a_value.max_op < b_value.max_op {
+                    a_value
+                } else {
+                    b_value
+                };
+                intersection.0.insert(*key, *value);
+            }
+        }
+        intersection
+    }
+
     pub(crate) fn include(&mut self, actor_index: usize, data: ClockData) {
         self.0
             .entry(actor_index)
@@ -187,5 +223,11 @@ mod tests {
 
         assert_eq!(after_clock.partial_cmp(&new_actor_clock), None);
         assert_eq!(new_actor_clock.partial_cmp(&after_clock), None);
+
+        assert!(base_clock.is_ancestor_of(&after_clock));
+        assert!(base_clock.is_ancestor_of(&base_clock));
+        assert!(!after_clock.is_ancestor_of(&base_clock));
+        assert!(after_clock.concurrent_with(&new_actor_clock));
+        assert!(!base_clock.concurrent_with(&after_clock));
     }
 }
@@ -245,9 +245,21 @@ impl<'a> Iterator for ValueIter<'a> {
                         let val = f64::from_le_bytes(raw);
                         Ok(ScalarValue::F64(val))
                     }),
-                    ValueType::Counter => self.parse_input(val_meta, |input| {
-                        leb128_i64(input).map(|(i, n)| (i, ScalarValue::Counter(n.into())))
-                    }),
+                    ValueType::Counter => {
+                        self.parse_raw(val_meta, |bytes| match parse_counter(Input::new(bytes)) {
+                            Err(e) => Err(DecodeColumnError::invalid_value("value", e.to_string())),
+                            Ok((i, _)) if !i.is_empty() => {
+                                Err(DecodeColumnError::invalid_value("value", "extra bytes"))
+                            }
+                            Ok((_, (start, None))) => Ok(ScalarValue::Counter(start.into())),
+                            Ok((_, (_, Some((min, max))))) if min > max => Err(
+                                DecodeColumnError::invalid_value("value", "invalid counter bounds"),
+                            ),
+                            Ok((_, (start, Some((min, max))))) => {
+                                Ok(ScalarValue::bounded_counter(start, min, max))
+                            }
+                        })
+                    }
                     ValueType::Timestamp => self.parse_input(val_meta, |input| {
                         leb128_i64(input).map(|(i, n)| (i, ScalarValue::Timestamp(n)))
                     }),
@@ -312,6 +324,19 @@ impl<'a> ValueIter<'a> {
     }
 }
 
+/// A counter is its starting value, followed by its minimum and maximum if it is bounded
+type CounterParts = (i64, Option<(i64, i64)>);
+
+fn parse_counter(input: Input<'_>) -> ParseResult<'_, CounterParts, DecodeError> {
+    let (input, start) = leb128_i64(input)?;
+    if input.is_empty() {
+        return Ok((input, (start, None)));
+    }
+    let (input, min) = leb128_i64(input)?;
+    let (input, max) = leb128_i64(input)?;
+    Ok((input, (start, Some((min, max)))))
+}
+
 /// Appends values row-wise. That is to say, this struct manages two separate chunks of memory, one
 /// for the value metadata and one for the raw values. To use it, create a new encoder using
 /// `ValueEncoder::new`, sequentially append values using `ValueEncoder::append`, and finallly
@@ -361,6 +386,9 @@ fn encode_val<S: Sink>(out: &mut RawEncoder<S>, val: &ScalarValue) -> usize {
         ScalarValue::Boolean(_) => 0,
         ScalarValue::Timestamp(i) => out.append(*i),
         ScalarValue::F64(f) => out.append(*f),
+        ScalarValue::Counter(i) if i.is_bounded() => {
+            out.append(i.start) + out.append(i.min()) + out.append(i.max())
+        }
         ScalarValue::Counter(i) => out.append(i.start),
         ScalarValue::Str(s) => out.append(RawBytes::from(s.as_bytes())),
         ScalarValue::Bytes(b) => out.append(RawBytes::from(&b[..])),
@@ -409,6 +437,11 @@ impl ValueMeta {
     }
 }
 
+/// The number of bytes `value` takes up when encoded in a value column
+pub(crate) fn encoded_value_len(value: &ScalarValue) -> usize {
+    ValueMeta::from(value).length()
+}
+
 impl From<&ScalarValue> for ValueMeta {
     fn from(p: &ScalarValue) -> Self {
         match p {
@@ -421,6 +454,9 @@ impl From<&ScalarValue> for ValueMeta {
             }),
             ScalarValue::Timestamp(i) => Self((lebsize(*i) << 4) | 9),
             ScalarValue::F64(_) => Self((8 << 4) | 5),
+            ScalarValue::Counter(i) if i.is_bounded() => {
+                Self(((lebsize(i.start) + lebsize(i.min()) + lebsize(i.max())) << 4) | 8)
+            }
             ScalarValue::Counter(i) => Self((lebsize(i.start) << 4) | 8),
             ScalarValue::Str(s) => Self(((s.as_bytes().len() as u64) << 4) | 6),
             ScalarValue::Bytes(b) => Self(((b.len() as u64) << 4) | 7),
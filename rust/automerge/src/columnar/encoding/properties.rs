@@ -168,6 +168,9 @@ pub(crate) fn scalar_value() -> impl Strategy<Value = ScalarValue> + Clone {
         smol_str().prop_map(ScalarValue::Str),
         any::<Vec<u8>>().prop_map(ScalarValue::Bytes),
         encodable_int().prop_map(|i| ScalarValue::Counter(i.into())),
+        (encodable_int(), encodable_int(), encodable_int()).prop_map(|(i, a, b)| {
+            ScalarValue::bounded_counter(i, a.min(b), a.max(b))
+        }),
         encodable_int().prop_map(ScalarValue::Timestamp),
         (10..15_u8, any::<Vec<u8>>()).prop_map(|(c, b)| ScalarValue::Unknown { type_code: c, bytes: b }),
     }
@@ -0,0 +1,182 @@
+use crate::exid::ExId;
+use crate::types::Clock;
+use crate::{ActorId, Automerge, AutomergeError, ChangeHash, Prop, ReadDoc, Value};
+
+/// The conflicting values for a property, returned by [`crate::Automerge::get_conflicts()`]
+///
+/// This contains the same values as [`ReadDoc::get_all()`] but each value is annotated with
+/// information about the change which wrote it, which is useful for rendering "edited by X at
+/// time T" style conflict UIs.
+///
+/// The values are in the same order as [`ReadDoc::get_all()`], which means the last value is the
+/// one which [`ReadDoc::get()`] returns.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Conflicts<'a> {
+    values: Vec<Conflict<'a>>,
+}
+
+/// Metadata about the operation which created a value, returned by [`crate::Automerge::op_meta()`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpMeta {
+    /// The actor which created the operation
+    pub actor: ActorId,
+    /// The counter of the operation
+    pub counter: u64,
+    /// The hash of the change which contains the operation, `None` if the operation is part of
+    /// a transaction which has not yet been committed
+    pub hash: Option<ChangeHash>,
+    /// The timestamp of the change which contains the operation, `None` if the change is not yet
+    /// committed
+    pub timestamp: Option<i64>,
+}
+
+/// The provenance of the currently visible value of a property, returned by
+/// [`crate::Automerge::last_modified()`]
+///
+/// Note that incrementing a counter does not change the operation which produced its value, so
+/// this describes the change which created the counter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LastModified {
+    /// The ID of the operation which wrote the value
+    pub id: ExId,
+    /// The actor which wrote the value
+    pub actor: ActorId,
+    /// The hash of the change which contains the operation which wrote the value
+    ///
+    /// This is `None` if the operation is part of a transaction which has not yet been committed
+    pub hash: Option<ChangeHash>,
+    /// The timestamp of the change which wrote the value, `None` if the change is not yet
+    /// committed
+    pub timestamp: Option<i64>,
+}
+
+/// One of the values in a [`Conflicts`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict<'a> {
+    /// The value
+    pub value: Value<'a>,
+    /// The ID of the operation which created the value
+    pub id: ExId,
+    /// The actor which wrote the value
+    pub actor: ActorId,
+    /// The hash of the change which contains the operation which wrote the value
+    ///
+    /// This is `None` if the operation is part of a transaction which has not yet been committed
+    pub hash: Option<ChangeHash>,
+    /// The timestamp of the change which wrote the value, `None` if the change is not yet
+    /// committed
+    pub timestamp: Option<i64>,
+    /// The message of the change which wrote the value, if any
+    pub message: Option<String>,
+}
+
+impl<'a> Conflicts<'a> {
+    /// The number of values, this is greater than one if there is a conflict
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Whether there are no values at all
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Whether there is more than one value
+    pub fn is_conflicted(&self) -> bool {
+        self.values.len() > 1
+    }
+
+    /// The value which [`ReadDoc::get()`] would return
+    pub fn winner(&self) -> Option<&Conflict<'a>> {
+        self.values.last()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Conflict<'a>> {
+        self.values.iter()
+    }
+}
+
+impl<'a> IntoIterator for Conflicts<'a> {
+    type Item = Conflict<'a>;
+    type IntoIter = std::vec::IntoIter<Conflict<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.values.into_iter()
+    }
+}
+
+impl Automerge {
+    pub(crate) fn get_conflicts_for(
+        &self,
+        obj: &ExId,
+        prop: Prop,
+        clock: Option<Clock>,
+    ) -> Result<Conflicts<'_>, AutomergeError> {
+        let values = self
+            .get_all_for(obj, prop, clock)?
+            .into_iter()
+            .map(|(value, id)| {
+                let LastModified {
+                    id,
+                    actor,
+                    hash,
+                    timestamp,
+                } = self.provenance(id);
+                Conflict {
+                    value,
+                    actor,
+                    hash,
+                    timestamp,
+                    message: hash
+                        .and_then(|h| self.get_change_by_hash(&h))
+                        .and_then(|c| c.message().cloned()),
+                    id,
+                }
+            })
+            .collect();
+        Ok(Conflicts { values })
+    }
+
+    pub(crate) fn last_modified_for(
+        &self,
+        obj: &ExId,
+        prop: Prop,
+        clock: Option<Clock>,
+    ) -> Result<Option<LastModified>, AutomergeError> {
+        Ok(self
+            .get_for(obj, prop, clock)?
+            .map(|(_, id)| self.provenance(id)))
+    }
+
+    pub(crate) fn op_meta_for(&self, id: &ExId) -> Option<OpMeta> {
+        let ExId::Id(counter, actor, _) = id else {
+            return None;
+        };
+        self.exid_to_opid(id).ok()?;
+        let change = self
+            .hash_for_opid(id)
+            .and_then(|hash| self.get_change_by_hash(&hash));
+        Some(OpMeta {
+            actor: actor.clone(),
+            counter: *counter,
+            hash: change.map(|c| c.hash()),
+            timestamp: change.map(|c| c.timestamp()),
+        })
+    }
+
+    fn provenance(&self, id: ExId) -> LastModified {
+        let actor = match &id {
+            ExId::Id(_, actor, _) => actor.clone(),
+            ExId::Root => ActorId::from(&[][..]),
+        };
+        let change = self
+            .hash_for_opid(&id)
+            .and_then(|hash| self.get_change_by_hash(&hash));
+        LastModified {
+            actor,
+            hash: change.map(|c| c.hash()),
+            timestamp: change.map(|c| c.timestamp()),
+            id,
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! What happens when an increment would overflow a counter
+//!
+//! The [`CounterOverflow`] settings are recorded in the document itself so that every peer
+//! applies the same rules, in a map at [`COUNTER_OVERFLOW_KEY`] in the
+//! [system object](crate::system). The map has the setting for the whole document at the key
+//! `default` and a map at the key `keys` from key patterns to the setting for counters at map
+//! keys which match them. Settings are recorded as the strings `wrap`, `saturate` and `error`.
+//!
+//! Every change is applied whatever the settings are. The settings only decide how a counter
+//! whose total has passed a limit is read, so peers which have the same changes read the same
+//! values, although they may read different values while a change to the settings is still on
+//! its way to some of them.
+use crate::exid::ExId;
+use crate::system::{self, ReadSystem};
+use crate::transaction::Transactable;
+use crate::value::Counter;
+use crate::{AutomergeError, ObjType, Value};
+
+/// The key in the system object under which the counter overflow settings are stored
+pub const COUNTER_OVERFLOW_KEY: &str = "counter_overflow";
+
+const DEFAULT: &str = "default";
+const KEYS: &str = "keys";
+
+/// What to do when an increment would take a counter past `i64::MIN` or `i64::MAX`, see
+/// [`crate::AutoCommit::set_counter_overflow()`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CounterOverflow {
+    /// Wrap around, as two's complement arithmetic does
+    #[default]
+    Wrap,
+    /// Stop at the limit the counter passed
+    ///
+    /// As for a bounded counter (see [`crate::ScalarValue::bounded_counter()`]) the total of the
+    /// counter is clamped rather than each increment, so concurrent increments give the same value
+    /// whatever order they are applied in and a counter must be moved back by the excess before
+    /// its value moves away from the limit.
+    Saturate,
+    /// Return [`crate::AutomergeError::CounterOverflow`] from local increments which would take
+    /// the counter past a limit
+    ///
+    /// Increments made concurrently by other peers can still take the total of the counter past
+    /// a limit, in which case it is read as for [`Self::Saturate`].
+    Error,
+}
+
+impl CounterOverflow {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Wrap => "wrap",
+            Self::Saturate => "saturate",
+            Self::Error => "error",
+        }
+    }
+
+    fn from_value(value: &Value<'_>) -> Option<Self> {
+        match value.to_str()? {
+            "wrap" => Some(Self::Wrap),
+            "saturate" => Some(Self::Saturate),
+            "error" => Some(Self::Error),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) fn set_counter_overflow<T: Transactable + ReadSystem + ?Sized>(
+    tx: &mut T,
+    overflow: CounterOverflow,
+) -> Result<(), AutomergeError> {
+    let settings = system::system_map(tx, COUNTER_OVERFLOW_KEY)?;
+    tx.put(&settings, DEFAULT, overflow.as_str())
+}
+
+pub(crate) fn set_counter_overflow_for_keys<T: Transactable + ReadSystem + ?Sized>(
+    tx: &mut T,
+    pattern: &str,
+    overflow: CounterOverflow,
+) -> Result<(), AutomergeError> {
+    let settings = system::system_map(tx, COUNTER_OVERFLOW_KEY)?;
+    let patterns = match tx.get(&settings, KEYS)? {
+        Some((Value::Object(ObjType::Map), patterns)) => patterns,
+        _ => tx.put_object(&settings, KEYS, ObjType::Map)?,
+    };
+    tx.put(&patterns, pattern, overflow.as_str())
+}
+
+/// The [`CounterOverflow`] behaviour for a document and for map keys matching patterns
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CounterOverflowPolicy {
+    default: CounterOverflow,
+    patterns: Vec<(String, CounterOverflow)>,
+}
+
+impl CounterOverflowPolicy {
+    /// The settings recorded in `doc`
+    pub(crate) fn load<R: ReadSystem + ?Sized>(doc: &R) -> Result<Self, AutomergeError> {
+        // the settings are applied in the order they were made so the latest one wins when
+        // several peers made them concurrently
+        let mut settings: Vec<(ExId, Option<String>, CounterOverflow)> = Vec::new();
+        for map in doc.system_maps(COUNTER_OVERFLOW_KEY)? {
+            if let Some((value, id)) = doc.get(&map, DEFAULT)? {
+                if let Some(overflow) = CounterOverflow::from_value(&value) {
+                    settings.push((id, None, overflow));
+                }
+            }
+            if let Some((Value::Object(ObjType::Map), patterns)) = doc.get(&map, KEYS)? {
+                for item in doc.map_range(&patterns, ..) {
+                    if let Some(overflow) = CounterOverflow::from_value(&item.value) {
+                        settings.push((item.id, Some(item.key.to_string()), overflow));
+                    }
+                }
+            }
+        }
+        settings.sort_by(|(a, _, _), (b, _, _)| a.cmp(b));
+        let mut policy = Self::default();
+        for (_, pattern, overflow) in settings {
+            match pattern {
+                Some(pattern) => policy.set_for_keys(&pattern, overflow),
+                None => policy.set_default(overflow),
+            }
+        }
+        Ok(policy)
+    }
+
+    fn set_default(&mut self, overflow: CounterOverflow) {
+        self.default = overflow;
+    }
+
+    fn set_for_keys(&mut self, pattern: &str, overflow: CounterOverflow) {
+        self.patterns.retain(|(p, _)| p != pattern);
+        self.patterns.push((pattern.to_string(), overflow));
+    }
+
+    /// The behaviour for a counter at the map key `key`, or in a list if `key` is `None`
+    ///
+    /// A pattern ending in `*` matches every key starting with the rest of the pattern, other
+    /// patterns must match the key exactly. The most recently set matching pattern wins.
+    pub(crate) fn for_key(&self, key: Option<&str>) -> CounterOverflow {
+        key.and_then(|key| {
+            self.patterns
+                .iter()
+                .rev()
+                .find(|(pattern, _)| match pattern.strip_suffix('*') {
+                    Some(prefix) => key.starts_with(prefix),
+                    None => key == pattern,
+                })
+                .map(|(_, overflow)| *overflow)
+        })
+        .unwrap_or(self.default)
+    }
+}
+
+/// Whether `overflow` allows a local increment of `counter` by `inc`
+pub(crate) fn allows_local_increment(
+    overflow: CounterOverflow,
+    counter: &Counter,
+    inc: i64,
+) -> bool {
+    overflow != CounterOverflow::Error || counter.saturated().value().checked_add(inc).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{allows_local_increment, CounterOverflow, CounterOverflowPolicy};
+    use crate::value::Counter;
+
+    #[test]
+    fn later_patterns_take_precedence() {
+        let mut policy = CounterOverflowPolicy::default();
+        policy.set_default(CounterOverflow::Saturate);
+        policy.set_for_keys("billing_*", CounterOverflow::Error);
+        policy.set_for_keys("billing_scratch", CounterOverflow::Wrap);
+        assert_eq!(policy.for_key(None), CounterOverflow::Saturate);
+        assert_eq!(policy.for_key(Some("likes")), CounterOverflow::Saturate);
+        assert_eq!(
+            policy.for_key(Some("billing_total")),
+            CounterOverflow::Error
+        );
+        assert_eq!(
+            policy.for_key(Some("billing_scratch")),
+            CounterOverflow::Wrap
+        );
+    }
+
+    #[test]
+    fn saturated_counters_clamp_their_total() {
+        let mut counter = Counter::from(i64::MAX - 2);
+        counter.increment(5);
+        assert_eq!(counter.value(), i64::MIN + 2);
+        assert_eq!(counter.saturated().value(), i64::MAX);
+        assert!(!allows_local_increment(CounterOverflow::Error, &counter, 1));
+        assert!(allows_local_increment(CounterOverflow::Error, &counter, -1));
+        assert!(allows_local_increment(
+            CounterOverflow::Saturate,
+            &counter,
+            1
+        ));
+        // the excess has to be taken off before the value moves
+        counter.increment(-3);
+        assert_eq!(counter.saturated().value(), i64::MAX);
+        counter.increment(-1);
+        assert_eq!(counter.saturated().value(), i64::MAX - 1);
+        assert_eq!(counter.value(), i64::MAX - 1);
+    }
+}
@@ -16,20 +16,79 @@ use std::fmt;
 ///
 /// A cursor is obtained from [`ReadDoc::get_cursor()`] and dereferenced with
 /// [`ReadDoc::get_cursor_position()`].
+///
+/// A cursor sits between two elements. When elements are inserted concurrently at exactly that
+/// spot the [`Affinity`] of the cursor decides whether it ends up before or after them, see
+/// [`Self::with_affinity()`].
 #[derive(Clone, PartialEq, Debug)]
 pub struct Cursor {
     ctr: u64,
     actor: ActorId,
+    before: Option<Anchor>,
+    affinity: Affinity,
+}
+
+/// Where a [`Cursor`] ends up when elements are inserted concurrently at its position
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Affinity {
+    /// The cursor stays where it was and the inserted elements appear after it, as the caret of
+    /// a user typing at the end of a word should not jump over text someone else inserted there
+    BeforeInsert,
+    /// The cursor moves to after the inserted elements, this is how cursors have always behaved
+    #[default]
+    AfterInsert,
+}
+
+/// The element before the position of a cursor, used to resolve [`Affinity::BeforeInsert`]
+#[derive(Clone, PartialEq, Debug)]
+pub(crate) enum Anchor {
+    Start,
+    Elem { ctr: u64, actor: ActorId },
 }
 
 const SERIALIZATION_VERSION_TAG: u8 = 0;
+const SERIALIZATION_VERSION_TAG_WITH_AFFINITY: u8 = 1;
 
 impl Cursor {
     pub(crate) fn new(id: OpId, osd: &OpSetData) -> Self {
         Self {
             ctr: id.counter(),
             actor: osd.actors.cache[id.actor()].clone(),
+            before: None,
+            affinity: Affinity::default(),
+        }
+    }
+
+    /// Record `before`, the cursor of the element before the position of this cursor, `None` if
+    /// this cursor is at the start of the sequence
+    pub(crate) fn with_before(mut self, before: Option<Cursor>) -> Self {
+        self.before = Some(match before {
+            Some(before) => Anchor::Elem {
+                ctr: before.ctr,
+                actor: before.actor,
+            },
+            None => Anchor::Start,
+        });
+        self
+    }
+
+    /// Set what happens to this cursor when elements are inserted concurrently at its position
+    ///
+    /// [`Affinity::BeforeInsert`] needs to know the element before the cursor, which only
+    /// cursors from [`ReadDoc::get_cursor_with_affinity()`] record. Other cursors always behave
+    /// as [`Affinity::AfterInsert`]. Setting [`Affinity::AfterInsert`] forgets the element
+    /// before the cursor, so the cursor is encoded in the format used before affinities existed.
+    pub fn with_affinity(mut self, affinity: Affinity) -> Self {
+        if affinity == Affinity::AfterInsert {
+            self.before = None;
         }
+        self.affinity = affinity;
+        self
+    }
+
+    /// What happens to this cursor when elements are inserted concurrently at its position
+    pub fn affinity(&self) -> Affinity {
+        self.affinity
     }
 
     pub(crate) fn actor(&self) -> &ActorId {
@@ -40,11 +99,33 @@ impl Cursor {
         self.ctr
     }
 
+    pub(crate) fn before(&self) -> Option<&Anchor> {
+        self.before.as_ref()
+    }
+
     fn from_str(s: &str) -> Option<Self> {
-        let n = s.find('@')?;
-        let ctr = s[0..n].parse().ok()?;
-        let actor = s[(n + 1)..].try_into().ok()?;
-        Some(Cursor { ctr, actor })
+        let (affinity, s) = match s.strip_prefix('<') {
+            Some(rest) => (Affinity::BeforeInsert, rest),
+            None => (Affinity::AfterInsert, s),
+        };
+        let (anchor, before) = match s.split_once('~') {
+            Some((anchor, "^")) => (anchor, Some(Anchor::Start)),
+            Some((anchor, before)) => {
+                let (ctr, actor) = parse_elem(before)?;
+                (anchor, Some(Anchor::Elem { ctr, actor }))
+            }
+            None => (s, None),
+        };
+        let (ctr, actor) = parse_elem(anchor)?;
+        Some(
+            Cursor {
+                ctr,
+                actor,
+                before,
+                affinity,
+            }
+            .with_affinity(affinity),
+        )
     }
 
     pub fn to_bytes(&self) -> Vec<u8> {
@@ -56,21 +137,80 @@ impl Cursor {
         // |  1 byte   | unsigned leb128 | variable      | unsigned leb128  |
         // '----------------------------------------------------------------'
         //
-        // Version is currently always `0`
+        // Version `1` is followed by
+        //
+        // .----------------------------------------------------------------.
+        // | affinity | before tag | actorId len     | actorId   | counter  |
+        // +----------------------------------------------------------------+
+        // |  1 byte  |   1 byte   | unsigned leb128 | variable  | leb128   |
+        // '----------------------------------------------------------------'
+        //
+        // where the actor ID and counter of the element before the cursor are only present if
+        // the before tag is `2`, a tag of `1` means the cursor is at the start of the sequence
+        // and `0` that the element before is not known. Only cursors with
+        // `Affinity::BeforeInsert` use version `1`, so other cursors can be read by versions of
+        // automerge which don't know about affinities.
         //
         let actor_bytes = self.actor.to_bytes();
         let mut bytes = Vec::with_capacity(actor_bytes.len() + 4 + 4 + 1);
-        bytes.push(SERIALIZATION_VERSION_TAG);
-        leb128::write::unsigned(&mut bytes, actor_bytes.len() as u64).unwrap();
-        bytes.extend_from_slice(actor_bytes);
-        leb128::write::unsigned(&mut bytes, self.ctr).unwrap();
+        let extended = self.affinity == Affinity::BeforeInsert;
+        if extended {
+            bytes.push(SERIALIZATION_VERSION_TAG_WITH_AFFINITY);
+        } else {
+            bytes.push(SERIALIZATION_VERSION_TAG);
+        }
+        write_elem(&mut bytes, &self.actor, self.ctr);
+        if extended {
+            bytes.push(match self.affinity {
+                Affinity::AfterInsert => 0,
+                Affinity::BeforeInsert => 1,
+            });
+            match &self.before {
+                None => bytes.push(0),
+                Some(Anchor::Start) => bytes.push(1),
+                Some(Anchor::Elem { ctr, actor }) => {
+                    bytes.push(2);
+                    write_elem(&mut bytes, actor, *ctr);
+                }
+            }
+        }
         bytes
     }
 }
 
+fn parse_elem(s: &str) -> Option<(u64, ActorId)> {
+    let (ctr, actor) = s.split_once('@')?;
+    Some((ctr.parse().ok()?, actor.try_into().ok()?))
+}
+
+fn write_elem(bytes: &mut Vec<u8>, actor: &ActorId, ctr: u64) {
+    let actor_bytes = actor.to_bytes();
+    leb128::write::unsigned(bytes, actor_bytes.len() as u64).unwrap();
+    bytes.extend_from_slice(actor_bytes);
+    leb128::write::unsigned(bytes, ctr).unwrap();
+}
+
+fn read_elem(i: parse::Input<'_>) -> Result<(parse::Input<'_>, (u64, ActorId)), AutomergeError> {
+    let (i, len) = parse::leb128_u64::<parse::leb128::Error>(i)
+        .map_err(|_| AutomergeError::InvalidCursorFormat)?;
+    let (i, actor) =
+        parse::take_n::<()>(len as usize, i).map_err(|_| AutomergeError::InvalidCursorFormat)?;
+    let (i, ctr) = parse::leb128_u64::<parse::leb128::Error>(i)
+        .map_err(|_| AutomergeError::InvalidCursorFormat)?;
+    Ok((i, (ctr, actor.into())))
+}
+
 impl fmt::Display for Cursor {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}@{}", self.ctr, self.actor)
+        if self.affinity == Affinity::BeforeInsert {
+            write!(f, "<")?;
+        }
+        write!(f, "{}@{}", self.ctr, self.actor)?;
+        match &self.before {
+            None => Ok(()),
+            Some(Anchor::Start) => write!(f, "~^"),
+            Some(Anchor::Elem { ctr, actor }) => write!(f, "~{}@{}", ctr, actor),
+        }
     }
 }
 
@@ -97,19 +237,41 @@ impl<'a> TryFrom<&'a [u8]> for Cursor {
         let i = parse::Input::new(value);
         let (i, version) =
             parse::take1::<()>(i).map_err(|_| AutomergeError::InvalidCursorFormat)?;
-        if version != SERIALIZATION_VERSION_TAG {
+        if version != SERIALIZATION_VERSION_TAG
+            && version != SERIALIZATION_VERSION_TAG_WITH_AFFINITY
+        {
             return Err(AutomergeError::InvalidCursorFormat);
         }
-        let (i, len) = parse::leb128_u64::<parse::leb128::Error>(i)
-            .map_err(|_| AutomergeError::InvalidCursorFormat)?;
-        let (i, actor) = parse::take_n::<()>(len as usize, i)
-            .map_err(|_| AutomergeError::InvalidCursorFormat)?;
-        let (_i, ctr) = parse::leb128_u64::<parse::leb128::Error>(i)
-            .map_err(|_| AutomergeError::InvalidCursorFormat)?;
-        Ok(Self {
+        let (i, (ctr, actor)) = read_elem(i)?;
+        let mut cursor = Self {
             ctr,
-            actor: actor.into(),
-        })
+            actor,
+            before: None,
+            affinity: Affinity::AfterInsert,
+        };
+        if version == SERIALIZATION_VERSION_TAG_WITH_AFFINITY {
+            let (i, affinity) =
+                parse::take1::<()>(i).map_err(|_| AutomergeError::InvalidCursorFormat)?;
+            cursor.affinity = match affinity {
+                0 => Affinity::AfterInsert,
+                1 => Affinity::BeforeInsert,
+                _ => return Err(AutomergeError::InvalidCursorFormat),
+            };
+            let (i, before) =
+                parse::take1::<()>(i).map_err(|_| AutomergeError::InvalidCursorFormat)?;
+            cursor.before = match before {
+                0 => None,
+                1 => Some(Anchor::Start),
+                2 => {
+                    let (_i, (ctr, actor)) = read_elem(i)?;
+                    Some(Anchor::Elem { ctr, actor })
+                }
+                _ => return Err(AutomergeError::InvalidCursorFormat),
+            };
+        }
+        // an older writer may have recorded the element before a cursor which follows inserts
+        let affinity = cursor.affinity;
+        Ok(cursor.with_affinity(affinity))
     }
 }
 
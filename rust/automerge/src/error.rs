@@ -1,3 +1,4 @@
+use crate::exid::ExId;
 use crate::storage::load::Error as LoadError;
 use crate::types::{ActorId, ScalarValue};
 use crate::value::DataType;
@@ -30,6 +31,24 @@ pub enum AutomergeError {
     InvalidObjIdFormat(String),
     #[error("invalid op for object of type `{0}`")]
     InvalidOp(ObjType),
+    #[error("object `{0}` has been sealed and cannot be changed")]
+    ObjectSealed(ExId),
+    #[error("the root object cannot be sealed")]
+    SealRoot,
+    #[error("a line cannot contain a newline")]
+    NewlineInLine,
+    #[error("failed to read text to splice: {0}")]
+    ReadText(#[source] std::io::Error),
+    #[error("text to splice is longer than the limit of {0} bytes")]
+    SpliceTooLarge(usize),
+    #[error("the sync policy does not allow {} of the changes sent by the peer", .0.len())]
+    RejectedChanges(Vec<crate::sync::RejectedChange>),
+    #[error("more than {0} changes are waiting for their dependencies")]
+    TooManyPendingChanges(usize),
+    #[error("invalid sync message envelope: {0}")]
+    InvalidEnvelope(String),
+    #[error("path `{0}` does not refer to a value in this document")]
+    InvalidPath(String),
     #[error("seq {0} is out of bounds")]
     InvalidSeq(u64),
     #[error("cursor {0} is invalid")]
@@ -47,6 +66,8 @@ pub enum AutomergeError {
     LoadChangeError(#[from] LoadChangeError),
     #[error("increment operations must be against a counter value")]
     MissingCounter,
+    #[error("incrementing a counter with value {value} by {increment} would overflow")]
+    CounterOverflow { value: i64, increment: i64 },
     #[error("hash {0} does not correspond to a change in this document")]
     MissingHash(ChangeHash),
     #[error("change's deps should already be in the document")]
@@ -85,6 +106,10 @@ pub(crate) struct InvalidScalarValue {
     pub(crate) expected: String,
 }
 
+#[derive(Error, Debug, Eq, PartialEq)]
+#[error("the extra bytes of the change are not valid change metadata")]
+pub struct InvalidChangeMetadata;
+
 #[derive(Error, Debug, Eq, PartialEq)]
 #[error("Invalid change hash slice: {0:?}")]
 pub struct InvalidChangeHashSlice(pub Vec<u8>);
@@ -138,3 +163,13 @@ pub enum UpdateObjectError {
     #[error(transparent)]
     Automerge(#[from] AutomergeError),
 }
+
+#[derive(Error, Debug)]
+pub enum UnifiedDiffError {
+    #[error("line {line} of the diff is not valid: {reason}")]
+    Parse { line: usize, reason: &'static str },
+    #[error("the hunk at line {line} of the diff does not match the text")]
+    ContextMismatch { line: usize },
+    #[error(transparent)]
+    Automerge(#[from] AutomergeError),
+}
@@ -3,6 +3,7 @@ use crate::types::{ObjId, OpId};
 use crate::ActorId;
 use serde::Serialize;
 use serde::Serializer;
+use sha2::{Digest, Sha256};
 use std::cmp::{Ord, Ordering};
 use std::fmt;
 use std::hash::{Hash, Hasher};
@@ -21,6 +22,10 @@ const SERIALIZATION_VERSION_TAG: u8 = 0;
 const TYPE_ROOT: u8 = 0;
 const TYPE_ID: u8 = 1;
 
+const TOKEN_VERSION: char = '0';
+const TOKEN_CHECKSUM_LEN: usize = 4;
+const BASE64_URL: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
 impl ExId {
     /// Serialize this object ID to a byte array.
     ///
@@ -68,6 +73,69 @@ impl ExId {
         }
     }
 
+    /// Encode this object ID as a compact string which is safe to embed in URLs
+    ///
+    /// The token is a version character followed by the unpadded URL safe base64 encoding of
+    /// [`Self::to_bytes()`] and a checksum of those bytes, so [`Self::from_token()`] will reject
+    /// tokens which have been truncated or mistyped rather than decoding them to the wrong ID.
+    pub fn to_token(&self) -> String {
+        let mut bytes = self.to_bytes();
+        let checksum = token_checksum(&bytes);
+        bytes.extend_from_slice(&checksum);
+        let mut token = String::with_capacity(1 + (bytes.len() * 4 + 2) / 3);
+        token.push(TOKEN_VERSION);
+        for chunk in bytes.chunks(3) {
+            let n = chunk
+                .iter()
+                .enumerate()
+                .fold(0_u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+            for i in 0..=chunk.len() {
+                token.push(BASE64_URL[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            }
+        }
+        token
+    }
+
+    /// Parse a token produced by [`Self::to_token()`]
+    pub fn from_token(token: &str) -> Result<Self, ExIdFromTokenError> {
+        let mut chars = token.chars();
+        match chars.next() {
+            Some(TOKEN_VERSION) => {}
+            Some(other) => return Err(ExIdFromTokenError::InvalidVersion(other)),
+            None => return Err(ExIdFromTokenError::Truncated),
+        }
+        let digits = chars
+            .map(|c| {
+                BASE64_URL
+                    .iter()
+                    .position(|b| *b as char == c)
+                    .map(|d| d as u32)
+                    .ok_or(ExIdFromTokenError::InvalidCharacter(c))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if digits.len() % 4 == 1 {
+            return Err(ExIdFromTokenError::Truncated);
+        }
+        let mut bytes = Vec::with_capacity(digits.len() * 3 / 4);
+        for chunk in digits.chunks(4) {
+            let n = chunk
+                .iter()
+                .enumerate()
+                .fold(0_u32, |n, (i, d)| n | d << (18 - 6 * i));
+            for i in 0..chunk.len() - 1 {
+                bytes.push((n >> (16 - 8 * i)) as u8);
+            }
+        }
+        if bytes.len() <= TOKEN_CHECKSUM_LEN {
+            return Err(ExIdFromTokenError::Truncated);
+        }
+        let (bytes, checksum) = bytes.split_at(bytes.len() - TOKEN_CHECKSUM_LEN);
+        if token_checksum(bytes) != checksum {
+            return Err(ExIdFromTokenError::ChecksumMismatch);
+        }
+        Ok(Self::try_from(bytes)?)
+    }
+
     pub(crate) fn to_internal_obj(&self) -> ObjId {
         match self {
             ExId::Root => ObjId::root(),
@@ -94,6 +162,27 @@ pub enum ObjIdFromBytesError {
     ParseActorIdxHint(String),
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum ExIdFromTokenError {
+    #[error("invalid token version `{0}`")]
+    InvalidVersion(char),
+    #[error("invalid character `{0}` in token")]
+    InvalidCharacter(char),
+    #[error("token is truncated")]
+    Truncated,
+    #[error("token checksum does not match")]
+    ChecksumMismatch,
+    #[error(transparent)]
+    InvalidId(#[from] ObjIdFromBytesError),
+}
+
+fn token_checksum(bytes: &[u8]) -> [u8; TOKEN_CHECKSUM_LEN] {
+    let digest = Sha256::digest(bytes);
+    let mut checksum = [0; TOKEN_CHECKSUM_LEN];
+    checksum.copy_from_slice(&digest[..TOKEN_CHECKSUM_LEN]);
+    checksum
+}
+
 impl<'a> TryFrom<&'a [u8]> for ExId {
     type Error = ObjIdFromBytesError;
 
@@ -195,7 +284,7 @@ impl PartialOrd for ExId {
 
 #[cfg(test)]
 mod tests {
-    use super::ExId;
+    use super::{ExId, ExIdFromTokenError};
     use proptest::prelude::*;
 
     use crate::ActorId;
@@ -221,6 +310,32 @@ mod tests {
             let objid2 = ExId::try_from(&bytes[..]).unwrap();
             assert_eq!(objid, objid2);
         }
+
+        #[test]
+        fn objid_token_roundtrip(objid in gen_obji()) {
+            let token = objid.to_token();
+            assert!(token.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+            assert_eq!(ExId::from_token(&token).unwrap(), objid);
+        }
+    }
+
+    #[test]
+    fn corrupted_tokens_are_rejected() {
+        let id = ExId::Id(12, ActorId::from(b"some actor".to_vec()), 0);
+        let token = id.to_token();
+        for len in 0..token.len() {
+            assert!(ExId::from_token(&token[..len]).is_err());
+        }
+        let mut typo = token.clone().into_bytes();
+        typo[5] = if typo[5] == b'A' { b'B' } else { b'A' };
+        assert!(matches!(
+            ExId::from_token(std::str::from_utf8(&typo).unwrap()),
+            Err(ExIdFromTokenError::ChecksumMismatch)
+        ));
+        assert!(matches!(
+            ExId::from_token(&format!("1{}", &token[1..])),
+            Err(ExIdFromTokenError::InvalidVersion('1'))
+        ));
     }
 
     #[test]
@@ -0,0 +1,172 @@
+//! Values which expire at a given time
+//!
+//! The expiry time of a value written with [`Transactable::put_with_expiry()`] is recorded in a
+//! map at [`EXPIRY_KEY`] in the [system object](crate::system). The map is keyed by the token of
+//! the ID of the operation which wrote the value (see [`ExId::to_token()`]) so a value which is
+//! overwritten stops being subject to the expiry of the value it replaced.
+use crate::exid::ExId;
+use crate::system::{self, ReadSystem};
+use crate::transaction::Transactable;
+use crate::{AutomergeError, ObjType, Prop, ReadDoc, ScalarValue, Value};
+
+/// The key in the system object under which expiry times are stored
+pub const EXPIRY_KEY: &str = "expiry";
+
+const OBJ: &str = "obj";
+const KEY: &str = "key";
+const AT: &str = "at";
+
+pub(crate) fn put_with_expiry<T: Transactable + ReadSystem + ?Sized>(
+    tx: &mut T,
+    obj: &ExId,
+    prop: Prop,
+    value: ScalarValue,
+    expires_at: i64,
+) -> Result<ExId, AutomergeError> {
+    tx.put(obj, prop.clone(), value)?;
+    // the value we just wrote overwrote every visible value so it is the winner
+    let (_, id) = tx
+        .get(obj, prop.clone())?
+        .ok_or(AutomergeError::InvalidOp(tx.object_type(obj)?))?;
+    let expiries = system::system_map(tx, EXPIRY_KEY)?;
+    let entry = tx.put_object(&expiries, id.to_token(), ObjType::Map)?;
+    tx.put(&entry, OBJ, obj.to_token())?;
+    if let Prop::Map(key) = prop {
+        tx.put(&entry, KEY, key)?;
+    }
+    tx.put(&entry, AT, ScalarValue::Timestamp(expires_at))?;
+    Ok(id)
+}
+
+pub(crate) fn get_unexpired<'a, R: ReadSystem + ?Sized>(
+    doc: &'a R,
+    obj: &ExId,
+    prop: Prop,
+    now: i64,
+) -> Result<Option<(Value<'a>, ExId)>, AutomergeError> {
+    let Some((value, id)) = doc.get(obj, prop)? else {
+        return Ok(None);
+    };
+    match expires_at(doc, &id)? {
+        Some(at) if at <= now => Ok(None),
+        _ => Ok(Some((value, id))),
+    }
+}
+
+fn expires_at<R: ReadSystem + ?Sized>(doc: &R, id: &ExId) -> Result<Option<i64>, AutomergeError> {
+    let token = id.to_token();
+    for expiries in doc.system_maps(EXPIRY_KEY)? {
+        if let Some((_, entry)) = doc.get(&expiries, token.as_str())? {
+            return entry_expiry(doc, &entry);
+        }
+    }
+    Ok(None)
+}
+
+fn entry_expiry<R: ReadDoc + ?Sized>(doc: &R, entry: &ExId) -> Result<Option<i64>, AutomergeError> {
+    Ok(match doc.get(entry, AT)? {
+        Some((Value::Scalar(s), _)) => match s.as_ref() {
+            ScalarValue::Timestamp(at) => Some(*at),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+fn entry_str<R: ReadDoc + ?Sized>(
+    doc: &R,
+    entry: &ExId,
+    key: &str,
+) -> Result<Option<String>, AutomergeError> {
+    Ok(match doc.get(entry, key)? {
+        Some((Value::Scalar(s), _)) => s.to_str().map(String::from),
+        _ => None,
+    })
+}
+
+pub(crate) fn gc_expired<T: Transactable + ReadSystem + ?Sized>(
+    tx: &mut T,
+    now: i64,
+) -> Result<usize, AutomergeError> {
+    let mut stale = Vec::new();
+    for expiries in tx.system_maps(EXPIRY_KEY)? {
+        for item in tx.map_range(&expiries, ..) {
+            stale.push((expiries.clone(), item.key.to_string(), item.id));
+        }
+    }
+    let mut deleted = 0;
+    for (expiries, token, entry) in stale {
+        let Ok(id) = ExId::from_token(&token) else {
+            continue;
+        };
+        let Some(obj) = entry_str(tx, &entry, OBJ)?.and_then(|t| ExId::from_token(&t).ok()) else {
+            continue;
+        };
+        let key = entry_str(tx, &entry, KEY)?;
+        let expired = entry_expiry(tx, &entry)?.is_some_and(|at| at <= now);
+        // find where the value is now, if it is still the visible value
+        let prop = match key {
+            Some(key) => match tx.get(&obj, key.as_str()) {
+                Ok(Some((_, current))) if current == id => Some(Prop::Map(key)),
+                _ => None,
+            },
+            None => tx
+                .list_range(&obj, ..)
+                .find(|item| item.id == id)
+                .map(|item| Prop::Seq(item.index)),
+        };
+        match prop {
+            Some(prop) if expired => {
+                tx.delete(&obj, prop)?;
+                tx.delete(&expiries, token)?;
+                deleted += 1;
+            }
+            Some(_) => {}
+            // the value has been overwritten or deleted so its expiry no longer applies
+            None => tx.delete(&expiries, token)?,
+        }
+    }
+    Ok(deleted)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::system::ReadSystem;
+    use crate::{transaction::Transactable, AutoCommit, ObjType, ReadDoc, Value, ROOT};
+
+    #[test]
+    fn expired_values_are_absent_and_collected() {
+        let mut doc = AutoCommit::new();
+        let presence = doc.put_object(&ROOT, "presence", ObjType::Map).unwrap();
+        doc.put_with_expiry(&presence, "alice", "typing", 100)
+            .unwrap();
+        doc.put_with_expiry(&presence, "bob", "idle", 200).unwrap();
+        let cursors = doc.put_object(&ROOT, "cursors", ObjType::List).unwrap();
+        doc.insert(&cursors, 0, "keep").unwrap();
+        doc.insert(&cursors, 1, "placeholder").unwrap();
+        doc.put_with_expiry(&cursors, 1, 5, 100).unwrap();
+
+        assert_eq!(
+            doc.get_unexpired(&presence, "alice", 99)
+                .unwrap()
+                .unwrap()
+                .0,
+            Value::str("typing")
+        );
+        assert!(doc
+            .get_unexpired(&presence, "alice", 100)
+            .unwrap()
+            .is_none());
+        assert!(doc.get_unexpired(&cursors, 1, 150).unwrap().is_none());
+
+        // overwriting a value removes its expiry
+        doc.put(&presence, "bob", "active").unwrap();
+        assert!(doc.get_unexpired(&presence, "bob", 300).unwrap().is_some());
+
+        assert_eq!(doc.gc_expired(150).unwrap(), 2);
+        assert_eq!(doc.keys(&presence).collect::<Vec<_>>(), vec!["bob"]);
+        assert_eq!(doc.length(&cursors), 1);
+        let expiries = doc.system_maps(super::EXPIRY_KEY).unwrap();
+        assert_eq!(doc.length(&expiries[0]), 0);
+    }
+}
@@ -0,0 +1,315 @@
+//! Hooks for transforming the values of particular fields as they are written and read
+//!
+//! The main use for this is end-to-end encryption of individual fields. A [`FieldTransform`] is
+//! registered for a [`PathPattern`] and every scalar written to a path matching the pattern with
+//! [`FieldTransforms::put_at_path()`] is encoded, passed to
+//! [`FieldTransform::encrypt_on_write()`] and stored as [`ScalarValue::Bytes`]. Reading the value
+//! back with [`FieldTransforms::get_at_path()`] reverses the process, so the original type of the
+//! value is preserved. Only the leaves of the document are transformed, the maps and lists
+//! containing them are stored as normal so concurrent changes still merge. Values are encoded
+//! as tagged JSON, so this module requires the `json` feature.
+//!
+//! ```
+//! # use automerge::{AutoCommit, Path, ReadDoc, ScalarValue, Value, ROOT};
+//! # use automerge::field_transform::{BoxedError, FieldTransform, FieldTransforms};
+//! # use automerge::transaction::Transactable;
+//! struct Xor(u8);
+//!
+//! impl FieldTransform for Xor {
+//!     fn encrypt_on_write(&self, _: &Path, plaintext: &[u8]) -> Result<Vec<u8>, BoxedError> {
+//!         Ok(plaintext.iter().map(|b| b ^ self.0).collect())
+//!     }
+//!
+//!     fn decrypt_on_read(&self, _: &Path, ciphertext: &[u8]) -> Result<Vec<u8>, BoxedError> {
+//!         Ok(ciphertext.iter().map(|b| b ^ self.0).collect())
+//!     }
+//! }
+//!
+//! let mut transforms = FieldTransforms::new();
+//! transforms.register("/users/*/ssn".parse().unwrap(), Xor(42));
+//!
+//! let mut doc = AutoCommit::new();
+//! doc.put_json(ROOT, "users", &serde_json::json!([{"name": "alice"}])).unwrap();
+//! let path = "/users/0/ssn".parse().unwrap();
+//! transforms.put_at_path(&mut doc, &path, "123-45-6789").unwrap();
+//!
+//! // The document only contains the ciphertext
+//! let (stored, _) = doc.get_at_path(&path).unwrap().unwrap();
+//! assert!(stored.to_scalar().unwrap().is_bytes());
+//! let (value, _) = transforms.get_at_path(&doc, &path).unwrap().unwrap();
+//! assert_eq!(value, Value::str("123-45-6789"));
+//! ```
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::exid::ExId;
+use crate::transaction::Transactable;
+use crate::{hydrate, AutomergeError, ParsePathError, Path, Prop, ReadDoc, ScalarValue, Value};
+
+pub type BoxedError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// A pair of functions which transform the encoded value of a field as it is written and read
+pub trait FieldTransform {
+    /// Transform the encoded value about to be written to `path`
+    fn encrypt_on_write(&self, path: &Path, plaintext: &[u8]) -> Result<Vec<u8>, BoxedError>;
+
+    /// Reverse [`Self::encrypt_on_write()`] for a value read from `path`
+    fn decrypt_on_read(&self, path: &Path, ciphertext: &[u8]) -> Result<Vec<u8>, BoxedError>;
+}
+
+/// A pattern matching [`Path`]s
+///
+/// Patterns are written like paths except that a segment of `*` matches any key or index, so
+/// `/users/*/email` matches `/users/0/email` and `/users/alice/email`. Use `~2` for a segment
+/// which is a literal `*`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathPattern(Vec<Option<String>>);
+
+impl PathPattern {
+    /// Whether `path` matches this pattern
+    pub fn matches(&self, path: &Path) -> bool {
+        self.0.len() == path.len()
+            && self
+                .0
+                .iter()
+                .zip(path.iter())
+                .all(|(segment, prop)| match (segment, prop) {
+                    (None, _) => true,
+                    (Some(segment), Prop::Map(key)) => segment == key,
+                    (Some(segment), Prop::Seq(index)) => *segment == index.to_string(),
+                })
+    }
+}
+
+impl FromStr for PathPattern {
+    type Err = ParsePathError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Ok(Self(Vec::new()));
+        }
+        let Some(rest) = s.strip_prefix('/') else {
+            return Err(ParsePathError::MissingLeadingSlash);
+        };
+        rest.split('/')
+            .map(parse_segment)
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+fn parse_segment(segment: &str) -> Result<Option<String>, ParsePathError> {
+    if segment == "*" {
+        return Ok(None);
+    }
+    let mut key = String::with_capacity(segment.len());
+    let mut chars = segment.chars();
+    while let Some(c) = chars.next() {
+        if c == '~' {
+            match chars.next() {
+                Some('0') => key.push('~'),
+                Some('1') => key.push('/'),
+                Some('2') => key.push('*'),
+                _ => return Err(ParsePathError::InvalidEscape(segment.to_string())),
+            }
+        } else {
+            key.push(c);
+        }
+    }
+    Ok(Some(key))
+}
+
+impl fmt::Display for PathPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for segment in &self.0 {
+            match segment {
+                None => write!(f, "/*")?,
+                Some(key) => write!(
+                    f,
+                    "/{}",
+                    key.replace('~', "~0").replace('/', "~1").replace('*', "~2")
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum FieldTransformError {
+    #[error(transparent)]
+    Automerge(#[from] AutomergeError),
+    #[error("error transforming value at {path}: {error}")]
+    Transform { path: String, error: BoxedError },
+    #[error("the value at {0} was not a transformed value")]
+    InvalidValue(String),
+}
+
+/// A set of [`FieldTransform`]s registered by [`PathPattern`]
+#[derive(Default)]
+pub struct FieldTransforms {
+    transforms: Vec<(PathPattern, Box<dyn FieldTransform>)>,
+}
+
+impl fmt::Debug for FieldTransforms {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.transforms.iter().map(|(pattern, _)| pattern))
+            .finish()
+    }
+}
+
+impl FieldTransforms {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use `transform` for every path which matches `pattern`
+    ///
+    /// If a path matches more than one pattern the transform which was registered first is used.
+    pub fn register<T: FieldTransform + 'static>(&mut self, pattern: PathPattern, transform: T) {
+        self.transforms.push((pattern, Box::new(transform)))
+    }
+
+    /// The transform which applies to `path`, if any
+    pub fn transform_for(&self, path: &Path) -> Option<&dyn FieldTransform> {
+        self.transforms
+            .iter()
+            .find(|(pattern, _)| pattern.matches(path))
+            .map(|(_, transform)| transform.as_ref())
+    }
+
+    /// Set the value at `path`, transforming it if `path` matches a registered pattern
+    ///
+    /// See [`Transactable::put_at_path()`]
+    pub fn put_at_path<T: Transactable, V: Into<ScalarValue>>(
+        &self,
+        tx: &mut T,
+        path: &Path,
+        value: V,
+    ) -> Result<(), FieldTransformError> {
+        let value = value.into();
+        let Some(transform) = self.transform_for(path) else {
+            tx.put_at_path(path, value)?;
+            return Ok(());
+        };
+        let plaintext = hydrate::Value::Scalar(value).to_tagged_json().to_string();
+        let ciphertext = transform
+            .encrypt_on_write(path, plaintext.as_bytes())
+            .map_err(|error| FieldTransformError::Transform {
+                path: path.to_string(),
+                error,
+            })?;
+        tx.put_at_path(path, ciphertext)?;
+        Ok(())
+    }
+
+    /// Get the value at `path`, reversing the transform if `path` matches a registered pattern
+    ///
+    /// See [`ReadDoc::get_at_path()`]
+    pub fn get_at_path<R: ReadDoc>(
+        &self,
+        doc: &R,
+        path: &Path,
+    ) -> Result<Option<(Value<'static>, ExId)>, FieldTransformError> {
+        let Some((value, id)) = doc.get_at_path(path)? else {
+            return Ok(None);
+        };
+        let Some(transform) = self.transform_for(path) else {
+            return Ok(Some((value.into_owned(), id)));
+        };
+        Ok(Some((self.decrypt(transform, path, &value)?, id)))
+    }
+
+    fn decrypt(
+        &self,
+        transform: &dyn FieldTransform,
+        path: &Path,
+        value: &Value<'_>,
+    ) -> Result<Value<'static>, FieldTransformError> {
+        let invalid = || FieldTransformError::InvalidValue(path.to_string());
+        let Value::Scalar(scalar) = value else {
+            return Err(invalid());
+        };
+        let ScalarValue::Bytes(ciphertext) = scalar.as_ref() else {
+            return Err(invalid());
+        };
+        let plaintext = transform
+            .decrypt_on_read(path, ciphertext)
+            .map_err(|error| FieldTransformError::Transform {
+                path: path.to_string(),
+                error,
+            })?;
+        let json = serde_json::from_slice(&plaintext).map_err(|_| invalid())?;
+        match hydrate::Value::from_tagged_json(&json) {
+            Ok(hydrate::Value::Scalar(scalar)) => Ok(Value::Scalar(Cow::Owned(scalar))),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BoxedError, FieldTransform, FieldTransformError, FieldTransforms, PathPattern};
+    use crate::{transaction::Transactable, AutoCommit, Path, ReadDoc, ScalarValue, Value, ROOT};
+
+    struct Reverse;
+
+    impl FieldTransform for Reverse {
+        fn encrypt_on_write(&self, _: &Path, plaintext: &[u8]) -> Result<Vec<u8>, BoxedError> {
+            Ok(plaintext.iter().rev().copied().collect())
+        }
+
+        fn decrypt_on_read(&self, _: &Path, ciphertext: &[u8]) -> Result<Vec<u8>, BoxedError> {
+            Ok(ciphertext.iter().rev().copied().collect())
+        }
+    }
+
+    #[test]
+    fn patterns_match_paths() {
+        let pattern: PathPattern = "/users/*/a~1b".parse().unwrap();
+        assert!(pattern.matches(&"/users/0/a~1b".parse().unwrap()));
+        assert!(pattern.matches(&"/users/alice/a~1b".parse().unwrap()));
+        assert!(!pattern.matches(&"/users/alice".parse().unwrap()));
+        assert!(!pattern.matches(&"/teams/alice/a~1b".parse().unwrap()));
+        assert_eq!(pattern.to_string(), "/users/*/a~1b");
+
+        let literal: PathPattern = "/~2".parse().unwrap();
+        assert!(literal.matches(&"/*".parse().unwrap()));
+        assert!(!literal.matches(&"/x".parse().unwrap()));
+    }
+
+    #[test]
+    fn transformed_values_keep_their_type() {
+        let mut transforms = FieldTransforms::new();
+        transforms.register("/secret".parse().unwrap(), Reverse);
+        let mut doc = AutoCommit::new();
+        let secret: Path = "/secret".parse().unwrap();
+        let public: Path = "/public".parse().unwrap();
+        transforms
+            .put_at_path(&mut doc, &secret, ScalarValue::counter(5))
+            .unwrap();
+        transforms.put_at_path(&mut doc, &public, 5).unwrap();
+
+        assert!(matches!(
+            doc.get(ROOT, "secret").unwrap(),
+            Some((Value::Scalar(s), _)) if s.is_bytes()
+        ));
+        assert_eq!(
+            transforms.get_at_path(&doc, &secret).unwrap().unwrap().0,
+            Value::counter(5)
+        );
+        assert_eq!(
+            transforms.get_at_path(&doc, &public).unwrap().unwrap().0,
+            Value::int(5)
+        );
+
+        // a plain value at a transformed path is an error rather than being returned as is
+        doc.put(ROOT, "secret", "oops").unwrap();
+        assert!(matches!(
+            transforms.get_at_path(&doc, &secret),
+            Err(FieldTransformError::InvalidValue(_))
+        ));
+    }
+}
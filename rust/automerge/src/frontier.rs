@@ -0,0 +1,190 @@
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::automerge::hash_heads;
+use crate::error::InvalidChangeHashSlice;
+use crate::types::HASH_SIZE;
+use crate::ChangeHash;
+
+/// A set of heads, e.g. the heads of a document, kept sorted so that two frontiers with the
+/// same heads compare equal regardless of the order the heads were given in
+///
+/// Equality and hashing use a digest of the heads which is computed once when the frontier is
+/// created, so comparing two frontiers takes constant time however many heads they have. The
+/// digest is the same as [`crate::Automerge::state_hash()`] for the heads of a document.
+///
+/// A frontier dereferences to a slice of [`ChangeHash`] so it can be passed to any method which
+/// takes heads, e.g. [`crate::Automerge::diff()`].
+#[derive(Clone)]
+pub struct Frontier {
+    heads: Vec<ChangeHash>,
+    digest: [u8; 32],
+}
+
+impl Frontier {
+    /// A frontier with no heads, i.e. the state of an empty document
+    pub fn empty() -> Self {
+        Self::from(Vec::new())
+    }
+
+    /// The heads of the frontier, sorted and without duplicates
+    pub fn heads(&self) -> &[ChangeHash] {
+        &self.heads
+    }
+
+    pub fn into_vec(self) -> Vec<ChangeHash> {
+        self.heads
+    }
+
+    /// A digest of the heads which two frontiers share if and only if they have the same heads
+    pub fn digest(&self) -> [u8; 32] {
+        self.digest
+    }
+
+    /// Whether `hash` is one of the heads of this frontier
+    ///
+    /// This does not know about the history of the heads, use [`crate::Automerge::clock_at()`]
+    /// to check whether a change is an ancestor of a frontier.
+    pub fn covers(&self, hash: &ChangeHash) -> bool {
+        self.heads.binary_search(hash).is_ok()
+    }
+
+    /// The frontier containing the heads of both `self` and `other`
+    ///
+    /// Without the change graph we can't tell whether a head of one frontier is an ancestor of a
+    /// head of the other, so the result may contain heads which a document with both sets of
+    /// changes would not report.
+    pub fn union(&self, other: &Frontier) -> Frontier {
+        self.heads
+            .iter()
+            .chain(other.heads.iter())
+            .copied()
+            .collect()
+    }
+
+    /// Encode the frontier as the concatenation of its heads
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.heads
+            .iter()
+            .flat_map(|h| h.as_bytes().iter().copied())
+            .collect()
+    }
+}
+
+impl From<Vec<ChangeHash>> for Frontier {
+    fn from(mut heads: Vec<ChangeHash>) -> Self {
+        heads.sort_unstable();
+        heads.dedup();
+        let digest = hash_heads(&heads);
+        Self { heads, digest }
+    }
+}
+
+impl From<&[ChangeHash]> for Frontier {
+    fn from(heads: &[ChangeHash]) -> Self {
+        Self::from(heads.to_vec())
+    }
+}
+
+impl From<Frontier> for Vec<ChangeHash> {
+    fn from(frontier: Frontier) -> Self {
+        frontier.heads
+    }
+}
+
+impl FromIterator<ChangeHash> for Frontier {
+    fn from_iter<I: IntoIterator<Item = ChangeHash>>(iter: I) -> Self {
+        Self::from(iter.into_iter().collect::<Vec<_>>())
+    }
+}
+
+impl TryFrom<&[u8]> for Frontier {
+    type Error = InvalidChangeHashSlice;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        if bytes.len() % HASH_SIZE != 0 {
+            return Err(InvalidChangeHashSlice(bytes.to_vec()));
+        }
+        bytes
+            .chunks_exact(HASH_SIZE)
+            .map(ChangeHash::try_from)
+            .collect()
+    }
+}
+
+impl Deref for Frontier {
+    type Target = [ChangeHash];
+
+    fn deref(&self) -> &Self::Target {
+        &self.heads
+    }
+}
+
+impl AsRef<[ChangeHash]> for Frontier {
+    fn as_ref(&self) -> &[ChangeHash] {
+        &self.heads
+    }
+}
+
+impl PartialEq for Frontier {
+    fn eq(&self, other: &Self) -> bool {
+        self.digest == other.digest
+    }
+}
+
+impl Eq for Frontier {}
+
+impl Hash for Frontier {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.digest.hash(state)
+    }
+}
+
+impl std::fmt::Debug for Frontier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Frontier").field(&self.heads).finish()
+    }
+}
+
+impl Serialize for Frontier {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.heads.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Frontier {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::<ChangeHash>::deserialize(deserializer).map(Frontier::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Frontier;
+    use crate::ChangeHash;
+
+    fn hash(n: u8) -> ChangeHash {
+        ChangeHash([n; 32])
+    }
+
+    #[test]
+    fn order_of_heads_does_not_matter() {
+        let a = Frontier::from(vec![hash(2), hash(1)]);
+        let b = Frontier::from(vec![hash(1), hash(2), hash(1)]);
+        assert_eq!(a, b);
+        assert_eq!(a.heads(), &[hash(1), hash(2)]);
+        assert!(a.covers(&hash(2)));
+        assert!(!a.covers(&hash(3)));
+
+        let c = a.union(&Frontier::from(vec![hash(3)]));
+        assert_eq!(c.heads(), &[hash(1), hash(2), hash(3)]);
+        assert_ne!(a, c);
+
+        assert_eq!(Frontier::try_from(c.to_bytes().as_slice()).unwrap(), c);
+        assert!(Frontier::try_from(&[1, 2, 3][..]).is_err());
+        let json = serde_json::to_string(&c).unwrap();
+        assert_eq!(serde_json::from_str::<Frontier>(&json).unwrap(), c);
+    }
+}
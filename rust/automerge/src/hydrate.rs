@@ -5,6 +5,8 @@ use std::collections::HashMap;
 
 mod list;
 mod map;
+#[cfg(feature = "json")]
+mod tagged;
 mod text;
 
 #[cfg(test)]
@@ -12,6 +14,8 @@ mod tests;
 
 pub use list::{List, ListValue};
 pub use map::{Map, MapValue};
+#[cfg(feature = "json")]
+pub use tagged::TaggedJsonError;
 pub use text::Text;
 
 #[derive(Clone, Debug, PartialEq)]
@@ -84,6 +88,8 @@ impl From<value::Value<'_>> for Value {
             value::Value::Object(ObjType::List) => Value::List(List::default()),
             value::Value::Object(ObjType::Text) => Value::Text(Text::default()),
             value::Value::Object(ObjType::Table) => Value::Map(Map::default()),
+            // the contents of objects we don't understand can't be represented
+            value::Value::Object(ObjType::Unknown(_)) => Value::Scalar(ScalarValue::Null),
             value::Value::Scalar(s) => Value::Scalar(s.into_owned()),
         }
     }
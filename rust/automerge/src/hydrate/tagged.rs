@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+
+use serde_json::{json, Value as Json};
+
+use super::{List, Map, Text, Value};
+use crate::ScalarValue;
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum TaggedJsonError {
+    #[error("expected an object with a \"type\" field")]
+    MissingType,
+    #[error("unknown type tag `{0}`")]
+    UnknownType(String),
+    #[error("invalid value for type `{0}`")]
+    InvalidValue(&'static str),
+}
+
+impl Value {
+    /// Convert this value to a JSON representation in which every value carries a type tag
+    ///
+    /// Plain JSON has no way to distinguish a counter or a timestamp from a number, or bytes
+    /// from a list of numbers. The tagged representation can be passed through systems which
+    /// only understand JSON and converted back with [`Value::from_tagged_json()`] without losing
+    /// information.
+    ///
+    /// The shape of each value is `{"type": <tag>, "value": <value>}` where the tags are
+    ///
+    /// | tag           | value                                                             |
+    /// |---------------|-------------------------------------------------------------------|
+    /// | `"map"`       | an object whose values are tagged values                          |
+    /// | `"list"`      | an array of tagged values                                         |
+    /// | `"text"`      | a string                                                          |
+    /// | `"str"`       | a string                                                          |
+    /// | `"int"`       | an integer                                                        |
+    /// | `"uint"`      | a non negative integer                                            |
+    /// | `"f64"`       | a number, or one of the strings `"NaN"`, `"Infinity"`, `"-Infinity"` |
+    /// | `"counter"`   | an integer                                                        |
+    /// | `"timestamp"` | an integer, milliseconds since the unix epoch                     |
+    /// | `"boolean"`   | a boolean                                                         |
+    /// | `"bytes"`     | a hex encoded string                                              |
+    /// | `"null"`      | `null`                                                            |
+    /// | `"unknown"`   | a hex encoded string, with the type code in a `"typeCode"` field  |
+    pub fn to_tagged_json(&self) -> Json {
+        match self {
+            Value::Map(map) => {
+                let values = map
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.value.to_tagged_json()))
+                    .collect::<serde_json::Map<_, _>>();
+                json!({"type": "map", "value": values})
+            }
+            Value::List(list) => {
+                let values = list
+                    .iter()
+                    .map(|v| v.value.to_tagged_json())
+                    .collect::<Vec<_>>();
+                json!({"type": "list", "value": values})
+            }
+            Value::Text(text) => json!({"type": "text", "value": text.to_string()}),
+            Value::Scalar(scalar) => scalar_to_tagged_json(scalar),
+        }
+    }
+
+    /// Parse the representation produced by [`Value::to_tagged_json()`]
+    pub fn from_tagged_json(json: &Json) -> Result<Self, TaggedJsonError> {
+        let typ = json
+            .get("type")
+            .and_then(Json::as_str)
+            .ok_or(TaggedJsonError::MissingType)?;
+        let value = json.get("value").unwrap_or(&Json::Null);
+        let scalar = match typ {
+            "map" => {
+                let values = value
+                    .as_object()
+                    .ok_or(TaggedJsonError::InvalidValue("map"))?
+                    .iter()
+                    .map(|(k, v)| Ok((k.clone(), Value::from_tagged_json(v)?)))
+                    .collect::<Result<HashMap<_, _>, TaggedJsonError>>()?;
+                return Ok(Value::Map(Map::from(values)));
+            }
+            "list" => {
+                let values = value
+                    .as_array()
+                    .ok_or(TaggedJsonError::InvalidValue("list"))?
+                    .iter()
+                    .map(Value::from_tagged_json)
+                    .collect::<Result<Vec<_>, _>>()?;
+                return Ok(Value::List(List::from(values)));
+            }
+            "text" => {
+                let text = value
+                    .as_str()
+                    .ok_or(TaggedJsonError::InvalidValue("text"))?;
+                return Ok(Value::Text(Text::from(text)));
+            }
+            "str" => ScalarValue::Str(
+                value
+                    .as_str()
+                    .ok_or(TaggedJsonError::InvalidValue("str"))?
+                    .into(),
+            ),
+            "int" => ScalarValue::Int(value.as_i64().ok_or(TaggedJsonError::InvalidValue("int"))?),
+            "uint" => ScalarValue::Uint(
+                value
+                    .as_u64()
+                    .ok_or(TaggedJsonError::InvalidValue("uint"))?,
+            ),
+            "f64" => ScalarValue::F64(match value.as_str() {
+                Some("NaN") => f64::NAN,
+                Some("Infinity") => f64::INFINITY,
+                Some("-Infinity") => f64::NEG_INFINITY,
+                _ => value.as_f64().ok_or(TaggedJsonError::InvalidValue("f64"))?,
+            }),
+            "counter" => {
+                let n = value
+                    .as_i64()
+                    .ok_or(TaggedJsonError::InvalidValue("counter"))?;
+                let bound = |key, default| match json.get(key) {
+                    None => Ok(default),
+                    Some(b) => b.as_i64().ok_or(TaggedJsonError::InvalidValue("counter")),
+                };
+                let (min, max) = (bound("min", i64::MIN)?, bound("max", i64::MAX)?);
+                if min > max {
+                    return Err(TaggedJsonError::InvalidValue("counter"));
+                }
+                ScalarValue::bounded_counter(n, min, max)
+            }
+            "timestamp" => ScalarValue::Timestamp(
+                value
+                    .as_i64()
+                    .ok_or(TaggedJsonError::InvalidValue("timestamp"))?,
+            ),
+            "boolean" => ScalarValue::Boolean(
+                value
+                    .as_bool()
+                    .ok_or(TaggedJsonError::InvalidValue("boolean"))?,
+            ),
+            "bytes" => {
+                ScalarValue::Bytes(decode_hex(value).ok_or(TaggedJsonError::InvalidValue("bytes"))?)
+            }
+            "null" => ScalarValue::Null,
+            "unknown" => ScalarValue::Unknown {
+                type_code: json
+                    .get("typeCode")
+                    .and_then(Json::as_u64)
+                    .and_then(|c| u8::try_from(c).ok())
+                    .ok_or(TaggedJsonError::InvalidValue("unknown"))?,
+                bytes: decode_hex(value).ok_or(TaggedJsonError::InvalidValue("unknown"))?,
+            },
+            other => return Err(TaggedJsonError::UnknownType(other.to_string())),
+        };
+        Ok(Value::Scalar(scalar))
+    }
+}
+
+fn scalar_to_tagged_json(scalar: &ScalarValue) -> Json {
+    match scalar {
+        ScalarValue::Str(s) => json!({"type": "str", "value": s.as_str()}),
+        ScalarValue::Int(i) => json!({"type": "int", "value": i}),
+        ScalarValue::Uint(u) => json!({"type": "uint", "value": u}),
+        ScalarValue::F64(f) if f.is_nan() => json!({"type": "f64", "value": "NaN"}),
+        ScalarValue::F64(f) if f.is_infinite() && *f > 0.0 => {
+            json!({"type": "f64", "value": "Infinity"})
+        }
+        ScalarValue::F64(f) if f.is_infinite() => json!({"type": "f64", "value": "-Infinity"}),
+        ScalarValue::F64(f) => json!({"type": "f64", "value": f}),
+        ScalarValue::Counter(c) if c.is_bounded() => {
+            json!({"type": "counter", "value": i64::from(c), "min": c.min(), "max": c.max()})
+        }
+        ScalarValue::Counter(c) => json!({"type": "counter", "value": i64::from(c)}),
+        ScalarValue::Timestamp(t) => json!({"type": "timestamp", "value": t}),
+        ScalarValue::Boolean(b) => json!({"type": "boolean", "value": b}),
+        ScalarValue::Bytes(b) => json!({"type": "bytes", "value": hex::encode(b)}),
+        ScalarValue::Null => json!({"type": "null", "value": null}),
+        ScalarValue::Unknown { type_code, bytes } => {
+            json!({"type": "unknown", "typeCode": type_code, "value": hex::encode(bytes)})
+        }
+    }
+}
+
+fn decode_hex(value: &Json) -> Option<Vec<u8>> {
+    value.as_str().and_then(|s| hex::decode(s).ok())
+}
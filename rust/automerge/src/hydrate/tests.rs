@@ -38,3 +38,60 @@ fn simple_hydrate() -> Result<(), AutomergeError> {
     );
     Ok(())
 }
+
+#[cfg(feature = "json")]
+#[test]
+fn tagged_json_round_trip() -> Result<(), AutomergeError> {
+    let mut doc = AutoCommit::default();
+    doc.put(&ObjId::Root, "count", ScalarValue::counter(3))?;
+    doc.put(
+        &ObjId::Root,
+        "when",
+        ScalarValue::Timestamp(1_700_000_000_000),
+    )?;
+    doc.put(&ObjId::Root, "blob", vec![1_u8, 2, 255])?;
+    doc.put(&ObjId::Root, "ratio", f64::INFINITY)?;
+    let list = doc.put_object(&ObjId::Root, "list", ObjType::List)?;
+    doc.insert(&list, 0, 1_u64)?;
+    doc.insert(&list, 1, ScalarValue::Null)?;
+    let text = doc.put_object(&ObjId::Root, "text", ObjType::Text)?;
+    doc.splice_text(&text, 0, 0, "hello")?;
+
+    let tagged = doc.hydrate_tagged(None);
+    assert_eq!(
+        tagged["value"]["count"],
+        serde_json::json!({"type": "counter", "value": 3})
+    );
+    assert_eq!(
+        tagged["value"]["blob"],
+        serde_json::json!({"type": "bytes", "value": "0102ff"})
+    );
+    assert_eq!(
+        tagged["value"]["ratio"],
+        serde_json::json!({"type": "f64", "value": "Infinity"})
+    );
+
+    let reparsed: serde_json::Value = serde_json::from_str(&tagged.to_string()).unwrap();
+    let imported = hydrate::Value::from_tagged_json(&reparsed).unwrap();
+    assert_eq!(imported, doc.hydrate(ExId::Root, None)?);
+    Ok(())
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn tagged_json_errors() {
+    use hydrate::TaggedJsonError;
+    let from = |v| hydrate::Value::from_tagged_json(&v);
+    assert_eq!(
+        from(serde_json::json!(1)),
+        Err(TaggedJsonError::MissingType)
+    );
+    assert_eq!(
+        from(serde_json::json!({"type": "date", "value": 1})),
+        Err(TaggedJsonError::UnknownType("date".into()))
+    );
+    assert_eq!(
+        from(serde_json::json!({"type": "counter", "value": "1"})),
+        Err(TaggedJsonError::InvalidValue("counter"))
+    );
+}
@@ -1,14 +1,22 @@
 mod keys;
+mod keys_range;
 mod list_range;
+mod map_page;
 mod map_range;
+mod mark_boundaries;
 mod spans;
+mod text_chunks;
 mod top_ops;
 mod values;
 
 pub use keys::Keys;
+pub use keys_range::KeysRange;
 pub use list_range::{ListRange, ListRangeItem};
+pub use map_page::{MapPage, MapPageToken};
 pub use map_range::{MapRange, MapRangeItem};
-pub use spans::{Span, Spans};
+pub use mark_boundaries::{MarkBoundaries, MarkBoundary, MarkEvent};
+pub use spans::{Span, Spans, EMBED_KEY};
+pub use text_chunks::TextChunks;
 pub use values::Values;
 
 pub(crate) use spans::{SpanInternal, SpansInternal};
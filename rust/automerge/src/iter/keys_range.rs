@@ -0,0 +1,66 @@
+use std::fmt;
+use std::ops::{Bound, RangeBounds};
+
+use crate::op_set::OpSet;
+use crate::types::Key;
+
+use super::TopOps;
+
+/// Iterator created by the [`crate::Automerge::keys_range()`] and
+/// [`crate::Automerge::keys_range_at()`] methods
+pub struct KeysRange<'a, R: RangeBounds<String>> {
+    iter: Option<(TopOps<'a>, &'a OpSet, R)>,
+}
+
+impl<'a, R: RangeBounds<String>> KeysRange<'a, R> {
+    pub(crate) fn new(iter: TopOps<'a>, op_set: &'a OpSet, range: R) -> Self {
+        KeysRange {
+            iter: Some((iter, op_set, range)),
+        }
+    }
+}
+
+impl<'a, R: RangeBounds<String>> Default for KeysRange<'a, R> {
+    fn default() -> Self {
+        KeysRange { iter: None }
+    }
+}
+
+impl<'a, R: RangeBounds<String>> fmt::Debug for KeysRange<'a, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeysRange").finish()
+    }
+}
+
+impl<'a, R: RangeBounds<String>> Iterator for KeysRange<'a, R> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (iter, op_set, range) = self.iter.as_mut()?;
+        for top in iter.by_ref() {
+            let Key::Map(n) = top.op.key() else {
+                continue;
+            };
+            let Some(prop) = op_set.osd.props.safe_get(*n) else {
+                continue;
+            };
+            if past_end(range, prop) {
+                // map ops are sorted by key so nothing after this is in the range
+                self.iter = None;
+                return None;
+            }
+            if range.contains(prop) {
+                return Some(prop.as_str());
+            }
+        }
+        None
+    }
+}
+
+pub(crate) fn past_end<R: RangeBounds<String>>(range: &R, key: &String) -> bool {
+    match range.end_bound() {
+        Bound::Included(end) => key > end,
+        Bound::Excluded(end) => key >= end,
+        Bound::Unbounded => false,
+    }
+}
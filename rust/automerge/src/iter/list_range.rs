@@ -72,10 +72,12 @@ impl<'a, R: RangeBounds<usize>> Iterator for ListRange<'a, R> {
                 let value = op.value_at(inner.clock.as_ref());
                 let id = op.exid();
                 if inner.range.contains(&index) {
+                    let elem_id = op.elem_exid().unwrap_or(ExId::Root);
                     return Some(ListRangeItem {
                         index,
                         value,
                         id,
+                        elem_id,
                         conflict,
                         marks,
                     });
@@ -91,6 +93,10 @@ pub struct ListRangeItem<'a> {
     pub index: usize,
     pub value: Value<'a>,
     pub id: ExId,
+    /// The ID of the list element, which unlike `index` and `id` stays the same when other
+    /// elements are inserted or deleted and when the element is overwritten, see
+    /// [`crate::Automerge::get_by_elem_id()`]
+    pub elem_id: ExId,
     pub conflict: bool,
     pub(crate) marks: Option<Arc<MarkSet>>,
 }
@@ -0,0 +1,47 @@
+use std::fmt;
+
+use super::MapRangeItem;
+
+/// A page of the entries of a map, returned by [`crate::Automerge::map_page()`] and
+/// [`crate::Automerge::map_page_at()`]
+#[derive(Debug, PartialEq)]
+pub struct MapPage<'a> {
+    /// The entries in this page, in key order
+    pub items: Vec<MapRangeItem<'a>>,
+    /// A token to pass to the next call to [`crate::Automerge::map_page()`] to get the following
+    /// page, `None` if this is the last page
+    pub next: Option<MapPageToken>,
+}
+
+/// A position in the keys of a map to continue paging from
+///
+/// The token records the last key which was returned rather than an index, so it remains valid
+/// when the map is modified between pages. Keys inserted before the token will not be returned by
+/// subsequent pages and keys inserted after it will be.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MapPageToken(String);
+
+impl MapPageToken {
+    /// The last key of the page this token was created from
+    pub fn key(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for MapPageToken {
+    fn from(key: String) -> Self {
+        Self(key)
+    }
+}
+
+impl From<&str> for MapPageToken {
+    fn from(key: &str) -> Self {
+        Self(key.to_string())
+    }
+}
+
+impl fmt::Display for MapPageToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
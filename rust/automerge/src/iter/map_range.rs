@@ -3,9 +3,10 @@ use std::ops::RangeBounds;
 
 use crate::exid::ExId;
 use crate::op_set::OpSet;
-use crate::types::{Clock, Key};
+use crate::types::{Clock, Key, ObjId};
 use crate::value::Value;
 
+use super::keys_range::past_end;
 use super::TopOps;
 
 /// Iterator created by the [`crate::ReadDoc::map_range()`] and [`crate::ReadDoc::map_range_at()`] methods
@@ -20,6 +21,9 @@ struct MapRangeInner<'a, R: RangeBounds<String>> {
     op_set: &'a OpSet,
     range: R,
     clock: Option<Clock>,
+    /// The object being iterated over if conflicts between identical values are not reported,
+    /// see [`crate::Automerge::set_collapse_identical_conflicts()`]
+    collapse_identical: Option<ObjId>,
 }
 
 impl<'a, R: RangeBounds<String>> MapRange<'a, R> {
@@ -30,9 +34,36 @@ impl<'a, R: RangeBounds<String>> MapRange<'a, R> {
                 op_set,
                 range,
                 clock,
+                collapse_identical: None,
             }),
         }
     }
+
+    /// Don't flag a conflict between values of `obj` which are all the same
+    pub(crate) fn collapse_identical(mut self, obj: ObjId) -> Self {
+        if let Some(inner) = self.iter.as_mut() {
+            inner.collapse_identical = Some(obj);
+        }
+        self
+    }
+}
+
+impl<'a, R: RangeBounds<String>> MapRangeInner<'a, R> {
+    /// Whether the visible values at `key` are all the same scalar
+    fn identical(&self, obj: &ObjId, key: Key) -> bool {
+        let clock = self.clock.as_ref();
+        let mut values = self
+            .op_set
+            .ops_at_key(obj, key)
+            .filter(|op| op.visible_at(clock))
+            .map(|op| op.value_at(clock));
+        match values.next() {
+            Some(first) => {
+                matches!(&first, Value::Scalar(s) if !s.is_counter()) && values.all(|v| v == first)
+            }
+            None => true,
+        }
+    }
 }
 
 impl<'a, R: RangeBounds<String>> Default for MapRange<'a, R> {
@@ -55,12 +86,20 @@ impl<'a, R: RangeBounds<String>> Iterator for MapRange<'a, R> {
             for top in inner.iter.by_ref() {
                 if let Key::Map(n) = top.op.key() {
                     if let Some(prop) = inner.op_set.osd.props.safe_get(*n) {
+                        if past_end(&inner.range, prop) {
+                            // map ops are sorted by key so nothing after this is in the range
+                            return None;
+                        }
                         if inner.range.contains(prop) {
+                            let conflict = top.conflict
+                                && !inner
+                                    .collapse_identical
+                                    .is_some_and(|obj| inner.identical(&obj, *top.op.key()));
                             return Some(MapRangeItem {
                                 key: prop.as_str(),
                                 value: top.op.value_at(inner.clock.as_ref()),
                                 id: top.op.exid(),
-                                conflict: top.conflict,
+                                conflict,
                             });
                         }
                     }
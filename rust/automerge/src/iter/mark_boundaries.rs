@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::exid::ExId;
+use crate::marks::MarkData;
+use crate::op_set::Op;
+use crate::op_tree::{OpSetData, OpTreeIter, OpTreeOpIter};
+use crate::types::{Clock, Key, ListEncoding, OpId, OpType};
+use crate::value::ScalarValue;
+
+/// Whether a [`MarkBoundary`] is where a mark starts or where it ends
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkEvent {
+    Start,
+    End,
+}
+
+/// The start or end of one mark, returned by [`MarkBoundaries`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarkBoundary<'a> {
+    /// The index in the sequence at which the boundary falls
+    pub index: usize,
+    pub event: MarkEvent,
+    /// The ID of the mark, which is the same for the start and the end of a mark and different
+    /// for every call to [`crate::transaction::Transactable::mark()`]
+    pub id: ExId,
+    data: &'a MarkData,
+}
+
+impl<'a> MarkBoundary<'a> {
+    pub fn name(&self) -> &str {
+        self.data.name.as_str()
+    }
+
+    /// The value of the mark, which is [`ScalarValue::Null`] for a mark created by
+    /// [`crate::transaction::Transactable::unmark()`]
+    pub fn value(&self) -> &ScalarValue {
+        &self.data.value
+    }
+}
+
+/// Iterator created by the [`crate::Automerge::mark_boundaries()`] and
+/// [`crate::Automerge::mark_boundaries_at()`] methods
+///
+/// Unlike [`crate::ReadDoc::marks()`] this does not resolve overlapping or conflicting marks,
+/// every mark which was created in the sequence is reported as a start and an end in the order
+/// they appear in the sequence.
+pub struct MarkBoundaries<'a> {
+    iter: Option<OpTreeOpIter<'a>>,
+    clock: Option<Clock>,
+    encoding: ListEncoding,
+    index: usize,
+    /// The key and width of the last visible element we have seen, which is not added to
+    /// `index` until we move on to the next key as an element can have several visible ops
+    element: Option<(Key, usize)>,
+    open: HashMap<OpId, (ExId, &'a MarkData)>,
+}
+
+impl<'a> fmt::Debug for MarkBoundaries<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MarkBoundaries")
+            .field("index", &self.index)
+            .finish()
+    }
+}
+
+impl<'a> MarkBoundaries<'a> {
+    pub(crate) fn new(
+        iter: Option<OpTreeIter<'a>>,
+        osd: &'a OpSetData,
+        encoding: ListEncoding,
+        clock: Option<Clock>,
+    ) -> Self {
+        Self {
+            iter: iter.map(|i| OpTreeOpIter::new(i, osd)),
+            clock,
+            encoding,
+            index: 0,
+            element: None,
+            open: HashMap::new(),
+        }
+    }
+
+    fn process(&mut self, op: Op<'a>) -> Option<MarkBoundary<'a>> {
+        let key = op.elemid_or_key();
+        if let Some((last, width)) = self.element {
+            if last != key {
+                self.index += width;
+                self.element = None;
+            }
+        }
+        match op.action() {
+            OpType::MarkBegin(_, data) => {
+                let id = op.exid();
+                self.open.insert(*op.id(), (id.clone(), data));
+                Some(MarkBoundary {
+                    index: self.index,
+                    event: MarkEvent::Start,
+                    id,
+                    data,
+                })
+            }
+            OpType::MarkEnd(_) => {
+                let (id, data) = self.open.remove(&op.id().prev())?;
+                Some(MarkBoundary {
+                    index: self.index,
+                    event: MarkEvent::End,
+                    id,
+                    data,
+                })
+            }
+            OpType::Make(_) | OpType::Put(_) => {
+                self.element = Some((key, op.width(self.encoding)));
+                None
+            }
+            OpType::Increment(_) | OpType::Delete | OpType::Seal(_) => None,
+        }
+    }
+}
+
+impl<'a> Iterator for MarkBoundaries<'a> {
+    type Item = MarkBoundary<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(op) = self.iter.as_mut()?.next() {
+            if !op.visible_or_mark(self.clock.as_ref()) {
+                continue;
+            }
+            if let Some(boundary) = self.process(op) {
+                return Some(boundary);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MarkEvent;
+    use crate::marks::{ExpandMark, Mark};
+    use crate::{transaction::Transactable, AutoCommit, ObjType, ReadDoc, ScalarValue, ROOT};
+
+    #[test]
+    fn overlapping_marks_are_reported_separately() {
+        let mut doc = AutoCommit::new();
+        let text = doc.put_object(&ROOT, "text", ObjType::Text).unwrap();
+        doc.splice_text(&text, 0, 0, "hello world").unwrap();
+        doc.mark(
+            &text,
+            Mark::new("bold".into(), true, 0, 5),
+            ExpandMark::None,
+        )
+        .unwrap();
+        doc.mark(
+            &text,
+            Mark::new("bold".into(), true, 3, 8),
+            ExpandMark::None,
+        )
+        .unwrap();
+        doc.unmark(&text, "bold", 4, 6, ExpandMark::None).unwrap();
+        let heads = doc.get_heads();
+        doc.delete(&text, 0).unwrap();
+
+        let events = doc
+            .mark_boundaries(&text)
+            .unwrap()
+            .map(|b| (b.index, b.event, b.value().clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            [
+                (0, MarkEvent::Start, ScalarValue::from(true)),
+                (2, MarkEvent::Start, ScalarValue::from(true)),
+                (3, MarkEvent::Start, ScalarValue::Null),
+                (4, MarkEvent::End, ScalarValue::from(true)),
+                (5, MarkEvent::End, ScalarValue::Null),
+                (7, MarkEvent::End, ScalarValue::from(true)),
+            ]
+        );
+        // `marks()` only reports the ranges which end up bold
+        assert_eq!(doc.marks(&text).unwrap().len(), 2);
+
+        let boundaries = doc.mark_boundaries_at(&text, &heads).unwrap();
+        let ids = boundaries.map(|b| (b.index, b.id)).collect::<Vec<_>>();
+        assert_eq!(ids[0].0, 0);
+        assert_eq!(ids[3].0, 5);
+        // the first mark to end is the first mark to start
+        assert_eq!(ids[3].1, ids[0].1);
+    }
+}
@@ -5,7 +5,7 @@ use crate::op_set::Op;
 use crate::op_tree::{OpTreeIter, OpTreeOpIter};
 use crate::types::Clock;
 use crate::types::{Key, ListEncoding, ObjType, OpId, OpType};
-use crate::Automerge;
+use crate::{Automerge, ScalarValue};
 
 use std::sync::Arc;
 
@@ -55,12 +55,24 @@ pub(crate) enum SpanInternal {
     Obj(OpId, usize),
 }
 
+/// The key which a map in a text object has set to `true` to be an embed rather than a block
+/// marker, see [`crate::transaction::Transactable::insert_embed()`]
+///
+/// The key is reserved, it is namespaced so that it doesn't collide with the properties
+/// applications give their blocks.
+pub const EMBED_KEY: &str = "automerge:embed";
+
 #[derive(Debug, PartialEq)]
 pub enum Span {
     /// A span of text and the marks that were active for that span
     Text(String, Option<Arc<MarkSet>>),
     /// A block marker
     Block(crate::hydrate::Map),
+    /// An object embedded in the text, such as a reference to an image
+    ///
+    /// The map includes [`EMBED_KEY`], so it stays an embed if it is passed back to
+    /// [`crate::transaction::Transactable::update_spans()`] as a block.
+    Embed(crate::hydrate::Map),
 }
 
 impl<'a, I> SpansInternal<'a, I>
@@ -160,7 +172,9 @@ where
             return Some(block);
         }
         for op in &mut self.iter {
-            if !(op.is_mark() || op.visible_at(self.clock.as_ref())) {
+            // marks made after the clock we are reading at don't apply
+            let is_mark = op.is_mark() && self.clock.as_ref().map_or(true, |c| c.covers(op.id()));
+            if !(is_mark || op.visible_at(self.clock.as_ref())) {
                 continue;
             }
             let key = op.elemid_or_key();
@@ -218,7 +232,14 @@ impl<'a> Iterator for Spans<'a> {
                         tracing::warn!("unexpected non map object in text");
                         return None;
                     };
-                    Some(Span::Block(value))
+                    if matches!(
+                        value.get(EMBED_KEY),
+                        Some(crate::hydrate::Value::Scalar(ScalarValue::Boolean(true)))
+                    ) {
+                        Some(Span::Embed(value))
+                    } else {
+                        Some(Span::Block(value))
+                    }
                 }
                 None => None,
             })
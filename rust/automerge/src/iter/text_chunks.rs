@@ -0,0 +1,26 @@
+use std::fmt;
+
+use super::TopOps;
+
+/// Iterator created by the [`crate::Automerge::text_chunks()`] method
+///
+/// Each item is borrowed from the document rather than copied into a new string, so a large text
+/// object can be written out without first being collected into one [`String`].
+#[derive(Default)]
+pub struct TextChunks<'a> {
+    pub(crate) iter: TopOps<'a>,
+}
+
+impl<'a> fmt::Debug for TextChunks<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TextChunks").finish()
+    }
+}
+
+impl<'a> Iterator for TextChunks<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|top| top.op.as_str())
+    }
+}
@@ -56,7 +56,7 @@ impl<'a> Iterator for TopOps<'a> {
     fn next(&mut self) -> Option<Self::Item> {
         match self {
             Self::Empty => None,
-            Self::Ops(top) => top.next(),
+            Self::Ops(top) => top.find(|top| !top.op.is_hidden()),
         }
     }
 }
@@ -3,6 +3,7 @@ mod utility_impls;
 
 use std::num::NonZeroU64;
 
+use crate::types::SEAL_ACTION;
 pub(crate) use crate::types::{ActorId, ChangeHash, ObjType, ScalarValue};
 pub(crate) use crate::value::DataType;
 
@@ -217,6 +218,7 @@ pub enum OpType {
     Put(ScalarValue),
     MarkBegin(MarkData),
     MarkEnd(bool),
+    Seal(Vec<ChangeHash>),
 }
 
 impl OpType {
@@ -228,9 +230,9 @@ impl OpType {
     ///
     /// # Panics
     ///
-    /// * If The action index is unrecognized
     /// * If the action index indicates that the value should be numeric but the value is not a
     ///   number
+    /// * If the action is a seal and the value is not bytes
     pub(crate) fn from_parts(
         OpTypeParts {
             action,
@@ -259,7 +261,11 @@ impl OpType {
                 }),
                 None => Self::MarkEnd(expand),
             },
-            other => panic!("unknown action type {}", other),
+            SEAL_ACTION => match value {
+                ScalarValue::Bytes(heads) => Self::Seal(crate::seal::heads_from_bytes(&heads)),
+                _ => panic!("non bytes value for seal action"),
+            },
+            other => Self::Make(ObjType::Unknown(other)),
         }
     }
 
@@ -273,6 +279,8 @@ impl OpType {
             Self::Increment(_) => 5,
             Self::Make(ObjType::Table) => 6,
             Self::MarkBegin(_) | Self::MarkEnd(_) => 7,
+            Self::Make(ObjType::Unknown(code)) => *code,
+            Self::Seal(_) => SEAL_ACTION,
         }
     }
 
@@ -306,6 +314,7 @@ impl Op {
             OpType::Put(v) => Some(v.clone()),
             OpType::MarkBegin(MarkData { value, .. }) => Some(value.clone()),
             OpType::Increment(i) => Some(ScalarValue::Int(*i)),
+            OpType::Seal(heads) => Some(crate::seal::heads_to_value(heads)),
             _ => None,
         }
     }
@@ -62,6 +62,9 @@ impl Serialize for Op {
                 op.serialize_field("expand", &expand)?
             }
             OpType::MarkEnd(expand) => op.serialize_field("expand", &expand)?,
+            OpType::Seal(heads) => {
+                op.serialize_field("value", &crate::seal::heads_to_value(heads))?
+            }
             _ => {}
         }
         op.serialize_field("pred", &self.pred)?;
@@ -85,6 +88,7 @@ pub(crate) enum RawOpType {
     Set,
     MarkBegin,
     MarkEnd,
+    Seal,
 }
 
 impl Serialize for RawOpType {
@@ -102,6 +106,7 @@ impl Serialize for RawOpType {
             RawOpType::Set => "set",
             RawOpType::MarkBegin => "markBegin",
             RawOpType::MarkEnd => "markEnd",
+            RawOpType::Seal => "seal",
         };
         serializer.serialize_str(s)
     }
@@ -122,6 +127,7 @@ impl<'de> Deserialize<'de> for RawOpType {
             "set",
             "markBegin",
             "markEnd",
+            "seal",
         ];
         // TODO: Probably more efficient to deserialize to a `&str`
         let raw_type = String::deserialize(deserializer)?;
@@ -135,6 +141,7 @@ impl<'de> Deserialize<'de> for RawOpType {
             "set" => Ok(RawOpType::Set),
             "markBegin" => Ok(RawOpType::MarkBegin),
             "markEnd" => Ok(RawOpType::MarkEnd),
+            "seal" => Ok(RawOpType::Seal),
             other => Err(Error::unknown_variant(other, VARIANTS)),
         }
     }
@@ -243,6 +250,13 @@ impl<'de> Deserialize<'de> for Op {
                         })
                     }
                     RawOpType::MarkEnd => OpType::MarkEnd(expand.unwrap_or(false)),
+                    RawOpType::Seal => match value.flatten() {
+                        Some(ScalarValue::Bytes(heads)) => {
+                            OpType::Seal(crate::seal::heads_from_bytes(&heads))
+                        }
+                        Some(_) => return Err(Error::custom("the value of a seal must be bytes")),
+                        None => return Err(Error::missing_field("value")),
+                    },
                 };
                 Ok(Op {
                     action,
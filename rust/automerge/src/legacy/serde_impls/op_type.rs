@@ -20,6 +20,13 @@ impl Serialize for OpType {
             OpType::Put(_) => RawOpType::Set,
             OpType::MarkBegin(_) => RawOpType::MarkBegin,
             OpType::MarkEnd(_) => RawOpType::MarkEnd,
+            OpType::Seal(_) => RawOpType::Seal,
+            OpType::Make(ObjType::Unknown(code)) => {
+                return Err(serde::ser::Error::custom(format!(
+                    "cannot serialize unknown action {}",
+                    code
+                )))
+            }
         };
         raw_type.serialize(serializer)
     }
@@ -257,32 +257,58 @@ macro_rules! __log {
      }
  }
 
+pub mod actor_metadata;
+mod actor_stats;
+pub mod annotation;
 mod autocommit;
 mod automerge;
 mod autoserde;
+pub mod awareness;
 mod change;
 mod change_graph;
+mod change_metadata;
+mod change_store;
 mod clock;
 mod columnar;
+mod conflicts;
 mod convert;
+pub mod counter_overflow;
 mod cursor;
 pub mod error;
 mod exid;
+pub mod expiry;
+#[cfg(feature = "json")]
+pub mod field_transform;
+mod frontier;
 pub mod hydrate;
 mod indexed_cache;
 pub mod iter;
 mod legacy;
+mod lines;
 pub mod marks;
+mod merge_report;
+pub mod multi_value;
 mod op_set;
 pub mod op_tree;
 mod parents;
 pub mod patches;
+mod path;
+mod path_stats;
 mod query;
 mod read;
+mod records;
+pub mod seal;
 mod sequence_tree;
 mod storage;
+mod structural;
 pub mod sync;
+pub mod system;
+mod text_attribution;
 mod text_diff;
+pub mod text_export;
+mod text_index;
+mod text_navigation;
+mod text_search;
 mod text_value;
 pub mod transaction;
 mod types;
@@ -290,21 +316,41 @@ mod value;
 #[cfg(feature = "optree-visualisation")]
 mod visualisation;
 
-pub use crate::automerge::{Automerge, LoadOptions, OnPartialLoad, SaveOptions, StringMigration};
+pub use crate::automerge::{
+    save_diff, Automerge, LoadOptions, OnPartialLoad, SaveOptions, StringMigration, ViewAt,
+};
+pub use actor_stats::ActorStats;
 pub use autocommit::AutoCommit;
 pub use autoserde::AutoSerde;
 pub use change::{Change, LoadError as LoadChangeError};
-pub use cursor::Cursor;
+pub use change_metadata::ChangeMetadata;
+pub use change_store::ChangeStore;
+pub use clock::Clock;
+pub use conflicts::{Conflict, Conflicts, LastModified, OpMeta};
+pub use counter_overflow::CounterOverflow;
+pub use cursor::{Affinity, Cursor};
 pub use error::AutomergeError;
 pub use error::InvalidActorId;
 pub use error::InvalidChangeHashSlice;
-pub use exid::{ExId as ObjId, ObjIdFromBytesError};
+pub use error::InvalidChangeMetadata;
+pub use exid::{ExId as ObjId, ExIdFromTokenError, ObjIdFromBytesError};
+pub use frontier::Frontier;
 pub use legacy::Change as ExpandedChange;
+pub use merge_report::{MergeConflict, MergeReport};
 pub use parents::{Parent, Parents};
 pub use patches::{Patch, PatchAction, PatchLog};
+pub use path::{ParsePathError, Path};
+pub use path_stats::PathStats;
 pub use read::ReadDoc;
+pub use records::{FieldDiff, RecordDiff};
 pub use sequence_tree::SequenceTree;
-pub use storage::VerificationMode;
+pub use storage::{
+    inspect, validate, BlobId, ChunkKind, ChunkReport, ChunkedSave, ColumnReport, ColumnSection,
+    Compression, DocumentInfo, InspectError, SalvageReport, SaveReport, SkippedChunk,
+    ValidateError, VerificationMode,
+};
+pub use text_attribution::TextAttribution;
+pub use text_search::TextMatch;
 pub use transaction::BlockOrText;
 pub use types::{ActorId, ChangeHash, ObjType, OpType, ParseChangeHashError, Prop};
 pub use value::{ScalarValue, Value};
@@ -0,0 +1,66 @@
+//! Line oriented text stored as a list of strings
+//!
+//! A text object stores every character as its own operation, which is a lot of overhead for
+//! content which is only ever edited a whole line at a time, such as logs or the output of a
+//! program. A lines object is a [`crate::ObjType::List`] in which each element is a string
+//! holding one line without its line ending, so a line costs a single operation. Lines are added
+//! with [`crate::transaction::Transactable::insert_line()`] and read with
+//! [`crate::ReadDoc::line_count()`] and [`crate::ReadDoc::lines_range()`]. The usual list
+//! methods can be used to replace or delete lines, and marks can be applied to ranges of lines
+//! with [`crate::transaction::Transactable::mark()`], the indices of which are line numbers.
+use std::ops::RangeBounds;
+
+use crate::exid::ExId;
+use crate::transaction::Transactable;
+use crate::{AutomergeError, ObjType, ReadDoc, ScalarValue, Value};
+
+fn check_lines<R: ReadDoc + ?Sized>(doc: &R, obj: &ExId) -> Result<(), AutomergeError> {
+    match doc.object_type(obj)? {
+        ObjType::List => Ok(()),
+        typ => Err(AutomergeError::InvalidOp(typ)),
+    }
+}
+
+pub(crate) fn insert_line<T: Transactable + ?Sized>(
+    tx: &mut T,
+    obj: &ExId,
+    index: usize,
+    line: &str,
+) -> Result<(), AutomergeError> {
+    check_lines(tx, obj)?;
+    if line.contains('\n') {
+        return Err(AutomergeError::NewlineInLine);
+    }
+    tx.insert(obj, index, line)
+}
+
+pub(crate) fn line_count<R: ReadDoc + ?Sized>(
+    doc: &R,
+    obj: &ExId,
+) -> Result<usize, AutomergeError> {
+    check_lines(doc, obj)?;
+    Ok(doc.length(obj))
+}
+
+pub(crate) fn lines_range<R: ReadDoc + ?Sized, B: RangeBounds<usize>>(
+    doc: &R,
+    obj: &ExId,
+    range: B,
+) -> Result<Vec<String>, AutomergeError> {
+    check_lines(doc, obj)?;
+    doc.list_range(obj, range)
+        .map(|item| match &item.value {
+            Value::Scalar(s) => match s.as_ref() {
+                ScalarValue::Str(line) => Ok(line.to_string()),
+                other => Err(AutomergeError::InvalidValueType {
+                    expected: "a string".to_string(),
+                    unexpected: other.to_string(),
+                }),
+            },
+            Value::Object(typ) => Err(AutomergeError::InvalidValueType {
+                expected: "a string".to_string(),
+                unexpected: typ.to_string(),
+            }),
+        })
+        .collect()
+}
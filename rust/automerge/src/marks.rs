@@ -3,12 +3,17 @@ use std::fmt;
 use std::fmt::Display;
 use std::sync::Arc;
 
+use crate::exid::ExId;
 use crate::op_tree::OpSetData;
 use crate::query::RichTextQueryState;
+use crate::system::{self, ReadSystem};
+use crate::text_value::TextValue;
+use crate::transaction::Transactable;
 use crate::types::{OpId, OpType};
-use crate::value::ScalarValue;
+use crate::value::{ScalarValue, Value};
+use crate::{AutomergeError, ChangeHash, ObjType, ReadDoc};
 use std::borrow::Cow;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 /// Marks let you store out-of-bound information about sequences.
 ///
@@ -239,6 +244,11 @@ impl<'a> MarkStateMachine<'a> {
             None => return false,
         };
 
+        if let Some(merge) = osd.mark_merge.get(&mark.name) {
+            self.state.insert(index, (id, mark));
+            return self.merge_values(&mark.name, *merge);
+        }
+
         if Self::mark_above(&self.state, index, mark).is_none() {
             if let Some(below) = Self::mark_below(&mut self.state, index, mark) {
                 if below.value != mark.value {
@@ -266,6 +276,10 @@ impl<'a> MarkStateMachine<'a> {
 
         let mark = self.state.remove(index).1;
 
+        if let Some(merge) = osd.mark_merge.get(&mark.name) {
+            return self.merge_values(&mark.name, *merge);
+        }
+
         if Self::mark_above(&self.state, index, mark).is_none() {
             match Self::mark_below(&mut self.state, index, mark) {
                 Some(below) if below.value == mark.value => {}
@@ -284,6 +298,39 @@ impl<'a> MarkStateMachine<'a> {
         result
     }
 
+    /// Set the current value of the marks named `name` to the values of all the marks with that
+    /// name combined with `merge`, returning whether it changed
+    fn merge_values(&mut self, name: &SmolStr, merge: MarkMerge) -> bool {
+        let values = self
+            .state
+            .iter()
+            .filter(|(_, m)| &m.name == name)
+            .map(|(_, m)| &m.value)
+            .collect::<Vec<_>>();
+        // an unmark removes the marks made before it
+        let start = values
+            .iter()
+            .rposition(|v| v.is_null())
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        let merged = if values.is_empty() {
+            None
+        } else if start == values.len() {
+            Some(ScalarValue::Null)
+        } else {
+            Some(merge.merge(&values[start..]))
+        };
+        if self.current.inner().get(name) == merged.as_ref() {
+            return false;
+        }
+        let current = Arc::make_mut(&mut self.current);
+        match merged {
+            Some(value) => current.insert(name.clone(), value),
+            None => current.remove(name),
+        }
+        true
+    }
+
     fn find(&self, target: OpId, osd: &OpSetData) -> Result<usize, usize> {
         self.state
             .binary_search_by(|probe| osd.lamport_cmp(probe.0, target))
@@ -312,6 +359,75 @@ impl<'a> MarkStateMachine<'a> {
     }
 }
 
+/// How the values of overlapping marks with the same name are combined
+///
+/// Set for a mark name with [`crate::AutoCommit::set_mark_merge()`], which records it in the
+/// document so every peer combines the values the same way. Whatever the setting, an unmark
+/// removes the marks with the same name which were made before it.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum MarkMerge {
+    /// The value of the most recent mark is used
+    #[default]
+    LastWriterWins,
+    /// The largest numeric value is used, non numeric values are ignored unless there are no
+    /// numeric values
+    Max,
+    /// Each string value is treated as a whitespace separated set of words and the value is the
+    /// sorted set of all of the words, non string values are ignored unless there are no string
+    /// values
+    Union,
+}
+
+impl MarkMerge {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::LastWriterWins => "last_writer_wins",
+            Self::Max => "max",
+            Self::Union => "union",
+        }
+    }
+
+    fn from_value(value: &Value<'_>) -> Option<Self> {
+        match value.to_str()? {
+            "last_writer_wins" => Some(Self::LastWriterWins),
+            "max" => Some(Self::Max),
+            "union" => Some(Self::Union),
+            _ => None,
+        }
+    }
+
+    /// Combine `values`, which are in the order the marks were made and are not empty
+    fn merge(&self, values: &[&ScalarValue]) -> ScalarValue {
+        let last = values[values.len() - 1];
+        match self {
+            Self::LastWriterWins => last.clone(),
+            Self::Max => {
+                let mut max: Option<(f64, &ScalarValue)> = None;
+                for value in values {
+                    if let Some(n) = value.to_f64() {
+                        if max.map_or(true, |(m, _)| n >= m) {
+                            max = Some((n, value));
+                        }
+                    }
+                }
+                max.map_or(last, |(_, v)| v).clone()
+            }
+            Self::Union => {
+                let words = values
+                    .iter()
+                    .filter_map(|v| v.to_str())
+                    .flat_map(|s| s.split_whitespace())
+                    .collect::<BTreeSet<_>>();
+                if values.iter().any(|v| v.to_str().is_some()) {
+                    ScalarValue::Str(words.into_iter().collect::<Vec<_>>().join(" ").into())
+                } else {
+                    last.clone()
+                }
+            }
+        }
+    }
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub struct MarkData {
     pub name: SmolStr,
@@ -357,4 +473,208 @@ impl ExpandMark {
     pub fn after(&self) -> bool {
         matches!(self, Self::After | Self::Both)
     }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Before => "before",
+            Self::After => "after",
+            Self::Both => "both",
+            Self::None => "none",
+        }
+    }
+
+    fn from_value(value: &Value<'_>) -> Option<Self> {
+        match value.to_str()? {
+            "before" => Some(Self::Before),
+            "after" => Some(Self::After),
+            "both" => Some(Self::Both),
+            "none" => Some(Self::None),
+            _ => None,
+        }
+    }
+}
+
+/// The key in the [system object](crate::system) under which the expand behaviour set for each
+/// mark name is stored
+///
+/// The map at this key is keyed by mark name and the values are the strings `before`, `after`,
+/// `both` and `none`.
+pub const MARK_EXPAND_KEY: &str = "mark_expand";
+
+/// The key in the [system object](crate::system) under which the [`MarkMerge`] set for each mark
+/// name is stored
+///
+/// The map at this key is keyed by mark name and the values are the strings
+/// `last_writer_wins`, `max` and `union`.
+pub const MARK_MERGE_KEY: &str = "mark_merge";
+
+pub(crate) fn set_mark_merge<T: Transactable + ReadSystem + ?Sized>(
+    tx: &mut T,
+    name: &str,
+    merge: MarkMerge,
+) -> Result<(), AutomergeError> {
+    let settings = system::system_map(tx, MARK_MERGE_KEY)?;
+    tx.put(&settings, name, merge.as_str())
+}
+
+/// The mark names which have a [`MarkMerge`] other than [`MarkMerge::LastWriterWins`]
+pub(crate) fn mark_merge_settings<R: ReadSystem + ?Sized>(
+    doc: &R,
+) -> Result<HashMap<SmolStr, MarkMerge>, AutomergeError> {
+    let mut merges = HashMap::new();
+    for settings in doc.system_maps(MARK_MERGE_KEY)? {
+        for item in doc.map_range(&settings, ..) {
+            if let Some(merge) = MarkMerge::from_value(&item.value) {
+                merges.entry(SmolStr::from(item.key)).or_insert(merge);
+            }
+        }
+    }
+    merges.retain(|_, merge| *merge != MarkMerge::LastWriterWins);
+    Ok(merges)
+}
+
+pub(crate) fn set_mark_expand<T: Transactable + ReadSystem + ?Sized>(
+    tx: &mut T,
+    name: &str,
+    expand: ExpandMark,
+) -> Result<(), AutomergeError> {
+    let settings = system::system_map(tx, MARK_EXPAND_KEY)?;
+    tx.put(&settings, name, expand.as_str())
+}
+
+pub(crate) fn mark_expand<R: ReadSystem + ?Sized>(
+    doc: &R,
+    name: &str,
+) -> Result<ExpandMark, AutomergeError> {
+    for settings in doc.system_maps(MARK_EXPAND_KEY)? {
+        if let Some(expand) = doc
+            .get(&settings, name)?
+            .and_then(|(value, _)| ExpandMark::from_value(&value))
+        {
+            return Ok(expand);
+        }
+    }
+    Ok(ExpandMark::default())
+}
+
+/// A range of a sequence over which the value of a mark differs between two versions, returned
+/// by [`crate::ReadDoc::mark_diff()`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarkChange {
+    /// The name of the mark
+    pub name: String,
+    /// The index of the start of the range in the later version
+    pub start: usize,
+    /// The index of the end of the range in the later version
+    pub end: usize,
+    /// The value of the mark in the earlier version, `None` if the mark was added
+    pub before: Option<ScalarValue>,
+    /// The value of the mark in the later version, `None` if the mark was removed
+    pub after: Option<ScalarValue>,
+}
+
+pub(crate) fn mark_diff<R: ReadDoc + ?Sized>(
+    doc: &R,
+    obj: &ExId,
+    from: &[ChangeHash],
+    to: &[ChangeHash],
+) -> Result<Vec<MarkChange>, AutomergeError> {
+    let typ = doc.object_type(obj)?;
+    if !typ.is_sequence() {
+        return Err(AutomergeError::InvalidOp(typ));
+    }
+    let values = |marks: Option<&MarkSet>| -> BTreeMap<String, ScalarValue> {
+        marks
+            .iter()
+            .flat_map(|m| m.iter())
+            .filter(|(_, value)| !value.is_null())
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect()
+    };
+    let before = doc
+        .list_range_at(obj, .., from)
+        .map(|item| (item.elem_id.clone(), values(item.marks())))
+        .collect::<HashMap<_, _>>();
+
+    let mut result = Vec::new();
+    // the changes which may still be extended by the next element, by name
+    let mut open: BTreeMap<String, MarkChange> = BTreeMap::new();
+    for item in doc.list_range_at(obj, .., to) {
+        let width = match (typ, &item.value) {
+            (ObjType::Text, Value::Scalar(s)) => match s.as_ref() {
+                ScalarValue::Str(s) => TextValue::width(s),
+                _ => 1,
+            },
+            _ => 1,
+        };
+        // elements inserted since `from` have no earlier formatting to compare with
+        let Some(old) = before.get(&item.elem_id) else {
+            result.extend(std::mem::take(&mut open).into_values());
+            continue;
+        };
+        let new = values(item.marks());
+        let names = old.keys().chain(new.keys()).collect::<BTreeSet<_>>();
+        let mut still_open = BTreeMap::new();
+        for name in names {
+            let (before, after) = (old.get(name), new.get(name));
+            if before == after {
+                continue;
+            }
+            let change = match open.remove(name) {
+                Some(mut change)
+                    if change.end == item.index
+                        && change.before.as_ref() == before
+                        && change.after.as_ref() == after =>
+                {
+                    change.end += width;
+                    change
+                }
+                other => {
+                    result.extend(other);
+                    MarkChange {
+                        name: name.clone(),
+                        start: item.index,
+                        end: item.index + width,
+                        before: before.cloned(),
+                        after: after.cloned(),
+                    }
+                }
+            };
+            still_open.insert(name.clone(), change);
+        }
+        result.extend(std::mem::replace(&mut open, still_open).into_values());
+    }
+    result.extend(open.into_values());
+    result.sort_by(|a, b| (a.start, &a.name).cmp(&(b.start, &b.name)));
+    Ok(result)
+}
+
+pub(crate) fn unmark_where<T, F>(
+    tx: &mut T,
+    obj: &ExId,
+    mut predicate: F,
+) -> Result<(), AutomergeError>
+where
+    T: Transactable + ?Sized,
+    F: FnMut(&Mark<'_>) -> bool,
+{
+    let mut ranges = tx
+        .marks(obj)?
+        .into_iter()
+        .filter(|mark| predicate(mark))
+        .map(|mark| (mark.name().to_string(), mark.start, mark.end))
+        .collect::<Vec<_>>();
+    ranges.sort();
+    // unmark touching ranges of the same name together so each name gets as few ops as possible
+    let mut merged: Vec<(String, usize, usize)> = Vec::new();
+    for (name, start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if last.0 == name && start <= last.2 => last.2 = last.2.max(end),
+            _ => merged.push((name, start, end)),
+        }
+    }
+    for (name, start, end) in merged {
+        tx.unmark(obj, &name, start, end, ExpandMark::None)?;
+    }
+    Ok(())
 }
@@ -0,0 +1,153 @@
+use std::collections::BTreeMap;
+
+use crate::exid::ExId;
+use crate::patches::TextRepresentation;
+use crate::{Automerge, AutomergeError, Patch, PatchAction, PatchLog, Path, Prop, ReadDoc, Value};
+
+/// The conflicts which merging one document into another would produce, returned by
+/// [`Automerge::merge_report()`]
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MergeReport {
+    conflicts: BTreeMap<Path, MergeConflict>,
+}
+
+/// A property which would have conflicting values after a merge
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergeConflict {
+    /// The object containing the conflicted property
+    pub obj: ExId,
+    /// The conflicted property, list indices are indices in the merged document
+    pub prop: Prop,
+    /// The conflicting values, in the same order as [`ReadDoc::get_all()`] would return them in
+    /// the merged document
+    pub values: Vec<(Value<'static>, ExId)>,
+}
+
+impl MergeReport {
+    /// Whether the merge would not produce any conflicts
+    pub fn is_empty(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+
+    /// The number of conflicted properties
+    pub fn len(&self) -> usize {
+        self.conflicts.len()
+    }
+
+    /// The conflict at `path`, if any
+    pub fn get(&self, path: &Path) -> Option<&MergeConflict> {
+        self.conflicts.get(path)
+    }
+
+    /// Iterate over the conflicts in path order
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, &MergeConflict)> {
+        self.conflicts.iter()
+    }
+}
+
+impl Automerge {
+    /// Work out which properties would have conflicting values if `other` were merged into this
+    /// document, without modifying either document
+    ///
+    /// Only properties which the merge changes are reported, a property which is already
+    /// conflicted in `self` and which `other` does not touch is not included.
+    pub fn merge_report(&self, other: &Automerge) -> Result<MergeReport, AutomergeError> {
+        let changes = self
+            .get_changes_added(other)
+            .into_iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut merged = self.clone();
+        let mut patch_log = PatchLog::active(TextRepresentation::default());
+        merged.apply_changes_log_patches(changes, &mut patch_log)?;
+
+        let mut conflicts = BTreeMap::new();
+        for patch in merged.make_patches(&mut patch_log) {
+            for prop in conflicted_props(&patch) {
+                let path = patch
+                    .path
+                    .iter()
+                    .map(|(_, p)| p.clone())
+                    .chain(std::iter::once(prop.clone()))
+                    .collect::<Vec<_>>();
+                let values = merged
+                    .get_all(&patch.obj, prop.clone())?
+                    .into_iter()
+                    .map(|(value, id)| (value.into_owned(), id))
+                    .collect::<Vec<_>>();
+                if values.len() > 1 {
+                    conflicts.insert(
+                        Path::from(path),
+                        MergeConflict {
+                            obj: patch.obj.clone(),
+                            prop,
+                            values,
+                        },
+                    );
+                }
+            }
+        }
+        Ok(MergeReport { conflicts })
+    }
+}
+
+fn conflicted_props(patch: &Patch) -> Vec<Prop> {
+    match &patch.action {
+        PatchAction::PutMap {
+            key,
+            conflict: true,
+            ..
+        } => vec![Prop::Map(key.clone())],
+        PatchAction::PutSeq {
+            index,
+            conflict: true,
+            ..
+        } => vec![Prop::Seq(*index)],
+        PatchAction::Insert { index, values } => values
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, _, conflict))| *conflict)
+            .map(|(i, _)| Prop::Seq(index + i))
+            .collect(),
+        PatchAction::Conflict { prop } => vec![prop.clone()],
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{transaction::Transactable, AutoCommit, Path, ReadDoc, Value, ROOT};
+
+    #[test]
+    fn reports_conflicts_without_merging() {
+        let mut doc = AutoCommit::new();
+        let list = doc.put_object(&ROOT, "list", crate::ObjType::List).unwrap();
+        doc.insert(&list, 0, "a").unwrap();
+        doc.put(&ROOT, "untouched", 1).unwrap();
+        let mut other = doc.fork();
+        doc.put(&ROOT, "title", "ours").unwrap();
+        doc.put(&list, 0, "b").unwrap();
+        other.put(&ROOT, "title", "theirs").unwrap();
+        other.put(&list, 0, "c").unwrap();
+        other.put(&ROOT, "untouched", 2).unwrap();
+        let heads = doc.get_heads();
+
+        let report = doc.merge_report(&mut other).unwrap();
+        assert_eq!(report.len(), 2);
+        let title = report.get(&"/title".parse::<Path>().unwrap()).unwrap();
+        let values = title
+            .values
+            .iter()
+            .map(|(v, _)| v.clone())
+            .collect::<Vec<_>>();
+        assert!(values.contains(&Value::str("ours")));
+        assert!(values.contains(&Value::str("theirs")));
+        assert!(report.get(&"/list/0".parse::<Path>().unwrap()).is_some());
+
+        assert_eq!(doc.get_heads(), heads);
+        assert_eq!(
+            doc.get(&ROOT, "untouched").unwrap().unwrap().0,
+            Value::int(1)
+        );
+    }
+}
@@ -0,0 +1,99 @@
+//! Objects whose conflicting values are read as a set
+//!
+//! Normally when several peers concurrently write to the same key [`crate::ReadDoc::get()`]
+//! returns the value written by the operation with the highest ID, which is arbitrary from the
+//! point of view of the application. An object marked with [`Transactable::set_multi_value()`] is
+//! a multi-value register: [`crate::Automerge::get_all_values()`] returns every concurrently
+//! written value so the application can decide how to combine them.
+//!
+//! Which objects are multi-value registers is recorded in the document itself, in a map at
+//! [`MULTI_VALUE_KEY`] in the [system object](crate::system) keyed by the token of the ID of each
+//! object (see [`ExId::to_token()`]), so every peer agrees on how to read them.
+use crate::exid::ExId;
+use crate::system::{self, ReadSystem};
+use crate::transaction::Transactable;
+use crate::{AutomergeError, Prop, ScalarValue, Value};
+
+/// The key in the system object under which the multi-value objects are recorded
+pub const MULTI_VALUE_KEY: &str = "multi_value";
+
+pub(crate) fn set_multi_value<T: Transactable + ReadSystem + ?Sized>(
+    tx: &mut T,
+    obj: &ExId,
+    enabled: bool,
+) -> Result<(), AutomergeError> {
+    // check the object exists before recording anything about it
+    tx.object_type(obj)?;
+    let registry = system::system_map(tx, MULTI_VALUE_KEY)?;
+    tx.put(&registry, obj.to_token(), enabled)
+}
+
+pub(crate) fn is_multi_value<R: ReadSystem + ?Sized>(
+    doc: &R,
+    obj: &ExId,
+) -> Result<bool, AutomergeError> {
+    let token = obj.to_token();
+    for registry in doc.system_maps(MULTI_VALUE_KEY)? {
+        if let Some((Value::Scalar(s), _)) = doc.get(&registry, token.as_str())? {
+            return Ok(matches!(s.as_ref(), ScalarValue::Boolean(true)));
+        }
+    }
+    Ok(false)
+}
+
+pub(crate) fn get_all_values<'a, R: ReadSystem + ?Sized>(
+    doc: &'a R,
+    obj: &ExId,
+    prop: Prop,
+) -> Result<Vec<(Value<'a>, ExId)>, AutomergeError> {
+    if !is_multi_value(doc, obj)? {
+        return Ok(doc.get(obj, prop)?.into_iter().collect());
+    }
+    let mut values: Vec<(Value<'a>, ExId)> = Vec::new();
+    for (value, id) in doc.get_all(obj, prop)? {
+        // peers which concurrently wrote the same scalar agree, so it is one member of the set
+        let duplicate = value.is_scalar() && values.iter().any(|(v, _)| *v == value);
+        if !duplicate {
+            values.push((value, id));
+        }
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{transaction::Transactable, AutoCommit, ObjType, ROOT};
+
+    #[test]
+    fn concurrent_puts_are_all_returned() {
+        let mut doc = AutoCommit::new();
+        let register = doc.put_object(&ROOT, "register", ObjType::Map).unwrap();
+        let plain = doc.put_object(&ROOT, "plain", ObjType::Map).unwrap();
+        doc.set_multi_value(&register, true).unwrap();
+        let mut other = doc.fork();
+        let mut third = doc.fork();
+        for obj in [&register, &plain] {
+            doc.put(obj, "color", "red").unwrap();
+            other.put(obj, "color", "blue").unwrap();
+            third.put(obj, "color", "red").unwrap();
+        }
+        doc.merge(&mut other).unwrap();
+        doc.merge(&mut third).unwrap();
+
+        assert!(doc.is_multi_value(&register).unwrap());
+        assert!(!doc.is_multi_value(&plain).unwrap());
+        let mut colors = doc
+            .get_all_values(&register, "color")
+            .unwrap()
+            .into_iter()
+            .map(|(v, _)| v.to_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+        colors.sort();
+        assert_eq!(colors, ["blue", "red"]);
+        assert_eq!(doc.get_all_values(&plain, "color").unwrap().len(), 1);
+
+        // a single value is a set of one
+        doc.put(&register, "color", "green").unwrap();
+        assert_eq!(doc.get_all_values(&register, "color").unwrap().len(), 1);
+    }
+}
@@ -1,27 +1,31 @@
 use crate::clock::Clock;
+use crate::counter_overflow::CounterOverflowPolicy;
 use crate::error::AutomergeError;
 use crate::exid::ExId;
 use crate::indexed_cache::IndexedCache;
-use crate::iter::{Keys, ListRange, MapRange, TopOps};
-use crate::marks::MarkSet;
+use crate::iter::{Keys, KeysRange, ListRange, MapRange, TextChunks, TopOps};
+use crate::marks::{MarkMerge, MarkSet};
 use crate::op_tree::OpTreeIter;
 use crate::op_tree::{
     self, FoundOpId, FoundOpWithPatchLog, FoundOpWithoutPatchLog, LastInsert, OpTree,
     OpTreeInternal, OpsFound,
 };
 use crate::parents::Parents;
-use crate::patches::TextRepresentation;
+use crate::patches::{TextEncoding, TextRepresentation};
 use crate::query::{ChangeVisibility, TreeQuery};
+use crate::seal::Seals;
+use crate::system::SYSTEM_KEY;
 use crate::text_value::TextValue;
 use crate::types::{
     self, ActorId, Export, Exportable, Key, ListEncoding, ObjId, ObjMeta, OpId, OpIds, OpType, Prop,
 };
 use crate::ObjType;
 use fxhash::FxBuildHasher;
+use smol_str::SmolStr;
 use std::borrow::Borrow;
 use std::cmp::Ordering;
 use std::collections::HashMap;
-use std::ops::RangeBounds;
+use std::ops::{Bound, RangeBounds};
 use std::sync::Arc;
 
 mod op;
@@ -50,6 +54,8 @@ pub(crate) struct OpSetInternal {
     length: usize,
     /// Metadata about the operations in this opset.
     pub(crate) osd: OpSetData,
+    /// The objects which have been sealed and the ops which are visible in them
+    pub(crate) seals: Seals,
 }
 
 impl OpSetInternal {
@@ -60,6 +66,7 @@ impl OpSetInternal {
             trees,
             length: 0,
             osd: OpSetData::from_actors(actors),
+            seals: Seals::default(),
         }
     }
 
@@ -74,7 +81,10 @@ impl OpSetInternal {
                 props: IndexedCache::new(),
                 ops: Vec::new(),
                 op_deps: Vec::new(),
+                mark_merge: HashMap::new(),
+                counter_overflow: CounterOverflowPolicy::default(),
             },
+            seals: Seals::default(),
         }
     }
 
@@ -179,7 +189,7 @@ impl OpSetInternal {
         let obj = *found.op.obj();
         let typ = self.obj_type(&obj)?;
         let prop = found.op.map_prop().unwrap_or(Prop::Seq(found.index));
-        let visible = found.visible;
+        let visible = found.visible && !found.op.is_hidden();
         Some(Parent {
             obj,
             prop,
@@ -204,6 +214,17 @@ impl OpSetInternal {
             .unwrap_or_default()
     }
 
+    pub(crate) fn ops_at_key<'a>(
+        &'a self,
+        obj: &ObjId,
+        key: Key,
+    ) -> impl Iterator<Item = Op<'a>> + 'a {
+        self.trees
+            .get(obj)
+            .into_iter()
+            .flat_map(move |tree| tree.internal.ops_at_key(key, &self.osd))
+    }
+
     pub(crate) fn op_iter<'a>(&'a self, obj: &ObjId) -> Option<OpIter<'a>> {
         self.trees.get(obj).map(|tree| OpIter {
             iter: tree.iter(),
@@ -312,8 +333,12 @@ impl OpSetInternal {
         tree.last_insert = None;
         let idx = tree.internal.remove(index, &self.osd);
         let op = idx.as_op(&self.osd);
-        if let OpType::Make(_) = op.action() {
-            self.trees.remove(&op.id().into());
+        match op.action() {
+            OpType::Make(_) => {
+                self.trees.remove(&op.id().into());
+            }
+            OpType::Seal(_) => self.seals.removed(idx, op.id()),
+            _ => {}
         }
     }
 
@@ -378,6 +403,8 @@ impl OpSetInternal {
                     parent: Some(idx),
                 },
             );
+        } else if op.action().is_seal() {
+            self.seals.added(idx);
         }
 
         if let Some(tree) = self.trees.get_mut(obj) {
@@ -401,6 +428,8 @@ impl OpSetInternal {
                     parent: Some(idx),
                 },
             );
+        } else if op.action().is_seal() {
+            self.seals.added(idx);
         }
 
         if let Some(tree) = self.trees.get_mut(obj) {
@@ -413,6 +442,12 @@ impl OpSetInternal {
         }
     }
 
+    /// The op which created `obj`
+    pub(crate) fn object_op(&self, obj: &ObjId) -> Option<Op<'_>> {
+        let idx = self.trees.get(obj)?.parent?;
+        Some(idx.as_op(&self.osd))
+    }
+
     pub(crate) fn object_type(&self, id: &ObjId) -> Option<ObjType> {
         self.trees.get(id).map(|tree| tree.objtype)
     }
@@ -444,6 +479,12 @@ impl OpSetInternal {
         String::from_utf8_lossy(&out[..]).to_string()
     }
 
+    /// Whether `obj` has the key of the system object, which the index counts but reads should
+    /// not, see [`crate::system`]
+    fn has_hidden_key(&self, obj: &ObjId) -> bool {
+        obj.is_root() && self.osd.props.lookup(&SYSTEM_KEY.to_string()).is_some()
+    }
+
     pub(crate) fn length(
         &self,
         obj: &ObjId,
@@ -452,8 +493,8 @@ impl OpSetInternal {
     ) -> usize {
         if let Some(tree) = self.trees.get(obj) {
             match (&clock, tree.index(encoding)) {
-                // no clock and a clean index? - use it
-                (None, Some(index)) => index.visible_len(encoding),
+                // no clock and a clean index? - use it, unless it counts a hidden key
+                (None, Some(index)) if !self.has_hidden_key(obj) => index.visible_len(encoding),
                 // do it the hard way - walk each op
                 _ => self
                     .top_ops(obj, clock)
@@ -464,12 +505,38 @@ impl OpSetInternal {
         }
     }
 
+    /// The width of the visible text in `obj` in `encoding`
+    pub(crate) fn text_width(
+        &self,
+        obj: &ObjId,
+        encoding: TextEncoding,
+        clock: Option<Clock>,
+    ) -> usize {
+        if clock.is_none() {
+            if let Some(index) = self
+                .trees
+                .get(obj)
+                .and_then(|tree| tree.index(ListEncoding::Text))
+            {
+                return index.text_width(encoding);
+            }
+        }
+        self.top_ops(obj, clock)
+            .fold(0, |acc, top| acc + top.op.text_width(encoding))
+    }
+
     pub(crate) fn text(&self, obj: &ObjId, clock: Option<Clock>) -> String {
         self.top_ops(obj, clock)
             .map(|top| top.op.as_str())
             .collect()
     }
 
+    pub(crate) fn text_chunks<'a>(&'a self, obj: &ObjId, clock: Option<Clock>) -> TextChunks<'a> {
+        TextChunks {
+            iter: self.top_ops(obj, clock),
+        }
+    }
+
     pub(crate) fn keys<'a>(&'a self, obj: &ObjId, clock: Option<Clock>) -> Keys<'a> {
         Keys {
             iter: Some((self.top_ops(obj, clock), self)),
@@ -491,7 +558,46 @@ impl OpSetInternal {
         range: R,
         clock: Option<Clock>,
     ) -> MapRange<'_, R> {
-        MapRange::new(self.top_ops(obj, clock.clone()), self, range, clock)
+        match self.map_top_ops_from(obj, range.start_bound(), clock.clone()) {
+            Some(iter) => MapRange::new(iter, self, range, clock),
+            None => MapRange::default(),
+        }
+    }
+
+    pub(crate) fn keys_range<R: RangeBounds<String>>(
+        &self,
+        obj: &ObjId,
+        range: R,
+        clock: Option<Clock>,
+    ) -> KeysRange<'_, R> {
+        match self.map_top_ops_from(obj, range.start_bound(), clock) {
+            Some(iter) => KeysRange::new(iter, self, range),
+            None => KeysRange::default(),
+        }
+    }
+
+    fn map_top_ops_from(
+        &self,
+        obj: &ObjId,
+        start: Bound<&String>,
+        clock: Option<Clock>,
+    ) -> Option<TopOps<'_>> {
+        let tree = self.trees.get(obj)?;
+        // Map ops are sorted by key so we can skip straight to the start of the range
+        let pos = match start {
+            Bound::Included(key) | Bound::Excluded(key) => {
+                tree.internal.seek_map_key_from(&self.osd, key)
+            }
+            Bound::Unbounded => 0,
+        };
+        let mut iter = OpIter {
+            iter: tree.iter(),
+            osd: &self.osd,
+        };
+        if pos > 0 {
+            iter.nth(pos - 1);
+        }
+        Some(TopOps::new(iter, clock))
     }
 
     pub(crate) fn to_string<E: Exportable>(&self, id: E) -> String {
@@ -576,6 +682,10 @@ pub(crate) struct OpSetData {
     pub(crate) props: IndexedCache<String>,
     ops: Vec<OpRaw>,
     op_deps: Vec<OpDepRaw>,
+    /// How the values of overlapping marks are combined, by mark name
+    pub(crate) mark_merge: HashMap<SmolStr, MarkMerge>,
+    /// How counters which have passed the limits of an `i64` are read
+    pub(crate) counter_overflow: CounterOverflowPolicy,
 }
 
 impl Default for OpSetData {
@@ -585,6 +695,8 @@ impl Default for OpSetData {
             props: IndexedCache::new(),
             ops: Vec::new(),
             op_deps: Vec::new(),
+            mark_merge: HashMap::new(),
+            counter_overflow: CounterOverflowPolicy::default(),
         }
     }
 }
@@ -820,6 +932,8 @@ impl OpSetData {
             actors: actors.into_iter().collect(),
             ops: Vec::new(),
             op_deps: Vec::new(),
+            mark_merge: HashMap::new(),
+            counter_overflow: CounterOverflowPolicy::default(),
         }
     }
 
@@ -1,10 +1,14 @@
 use crate::clock::Clock;
+use crate::counter_overflow::CounterOverflow;
 use crate::exid::ExId;
 use crate::op_set::OpSetData;
+use crate::patches::TextEncoding;
+use crate::system::SYSTEM_KEY;
 use crate::types::{self, ActorId, ElemId, Key, ListEncoding, ObjId, OpId, OpType, Prop};
 use crate::value::{ScalarValue, Value};
 use std::borrow::Cow;
 use std::cmp::Ordering;
+use unicode_width::UnicodeWidthStr;
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub(crate) struct OpIdx(u32);
@@ -116,7 +120,7 @@ impl<'a> Op<'a> {
     }
 
     pub(crate) fn succ_iter(&self) -> impl Iterator<Item = Op<'a>> {
-        self.succ().filter(|op| !op.is_inc())
+        self.succ().filter(|op| !op.is_inc() && !op.is_seal())
     }
 
     pub(crate) fn osd(&self) -> &'a OpSetData {
@@ -169,10 +173,11 @@ impl<'a> Op<'a> {
 
     pub(crate) fn visible_at(&self, clock: Option<&Clock>) -> bool {
         if let Some(clock) = clock {
-            if self.is_inc() || self.is_mark() {
+            if self.is_inc() || self.is_mark() || self.is_seal() {
                 false
             } else {
-                clock.covers(&self.op().id) && !self.succ().any(|i| clock.covers(i.id()))
+                clock.covers(&self.op().id)
+                    && !self.succ().any(|i| !i.is_seal() && clock.covers(i.id()))
             }
         } else {
             self.visible()
@@ -180,24 +185,28 @@ impl<'a> Op<'a> {
     }
 
     pub(crate) fn visible_or_mark(&self, clock: Option<&Clock>) -> bool {
-        if self.is_inc() {
+        if self.is_inc() || self.is_seal() {
             false
         } else if let Some(clock) = clock {
-            clock.covers(&self.op().id) && self.succ().all(|o| o.is_inc() || !clock.covers(o.id()))
+            clock.covers(&self.op().id)
+                && self
+                    .succ()
+                    .all(|o| o.is_inc() || o.is_seal() || !clock.covers(o.id()))
         } else if self.is_counter() {
-            self.succ().all(|op| op.is_inc())
+            self.succ().all(|op| op.is_inc() || op.is_seal())
         } else {
-            self.succ().len() == 0
+            self.succ().all(|op| op.is_seal())
         }
     }
 
     pub(crate) fn visible(&self) -> bool {
-        if self.is_inc() || self.is_mark() {
+        if self.is_inc() || self.is_mark() || self.is_seal() {
             false
         } else if self.is_counter() {
-            self.succ().all(|op| op.is_inc())
+            self.succ().all(|op| op.is_inc() || op.is_seal())
         } else {
-            self.succ().len() == 0
+            // a seal doesn't overwrite the object it seals
+            self.succ().all(|op| op.is_seal())
         }
     }
 
@@ -221,6 +230,10 @@ impl<'a> Op<'a> {
         self.op().is_mark()
     }
 
+    pub(crate) fn is_seal(&self) -> bool {
+        self.op().action.is_seal()
+    }
+
     pub(crate) fn as_str(&self) -> &'a str {
         self.op().to_str()
     }
@@ -233,6 +246,23 @@ impl<'a> Op<'a> {
         }
     }
 
+    /// The width of this op as text in `encoding`, which is what [`Self::width()`] returns for
+    /// [`ListEncoding::Text`] when `encoding` is the one this build indexes text with
+    pub(crate) fn text_width(&self, encoding: TextEncoding) -> usize {
+        let s = self.as_str();
+        match encoding {
+            TextEncoding::Utf8 => s.len(),
+            TextEncoding::Utf16 => s.encode_utf16().count(),
+            TextEncoding::CodePoint => s.chars().count(),
+        }
+    }
+
+    /// The number of terminal columns this op takes up when displayed, see
+    /// [`crate::Automerge::text_display_width()`]
+    pub(crate) fn display_width(&self) -> usize {
+        UnicodeWidthStr::width(self.as_str())
+    }
+
     pub(crate) fn insert(&self) -> bool {
         self.op().insert
     }
@@ -242,10 +272,15 @@ impl<'a> Op<'a> {
     }
 
     pub(crate) fn value(&self) -> Value<'a> {
-        self.op().value()
+        match &self.op().action {
+            OpType::Put(ScalarValue::Counter(c)) if c.has_wrapped() && self.saturates() => {
+                Value::Scalar(Cow::Owned(ScalarValue::Counter(c.saturated())))
+            }
+            _ => self.op().value(),
+        }
     }
 
-    pub(crate) fn inc_at(&self, clock: &Clock) -> i64 {
+    pub(crate) fn inc_at(&self, clock: &Clock) -> i128 {
         self.succ()
             .filter_map(|o| {
                 if clock.covers(o.id()) {
@@ -254,18 +289,33 @@ impl<'a> Op<'a> {
                     None
                 }
             })
+            .map(i128::from)
             .sum()
     }
 
     pub(crate) fn value_at(&self, clock: Option<&Clock>) -> Value<'a> {
         if let Some(clock) = clock {
             if let OpType::Put(ScalarValue::Counter(c)) = &self.op().action {
-                return Value::counter(c.start + self.inc_at(clock));
+                let mut counter = c.with_increment(self.inc_at(clock));
+                if counter.has_wrapped() && self.saturates() {
+                    counter = counter.saturated();
+                }
+                return Value::Scalar(Cow::Owned(ScalarValue::Counter(counter)));
             }
         }
         self.value()
     }
 
+    /// Whether a counter set by this op is read as [`crate::value::Counter::saturated()`]
+    /// rather than wrapping, following the document's [`crate::CounterOverflow`] settings
+    pub(crate) fn saturates(&self) -> bool {
+        let key = match self.op().key {
+            Key::Map(m) => Some(self.osd.props.get(m).as_str()),
+            Key::Seq(_) => None,
+        };
+        self.osd.counter_overflow.for_key(key) != CounterOverflow::Wrap
+    }
+
     pub(crate) fn tagged_value(&self, clock: Option<&Clock>) -> (Value<'a>, ExId) {
         (self.value_at(clock), self.exid())
     }
@@ -278,6 +328,15 @@ impl<'a> Op<'a> {
         self.succ_iter().any(|op| clock.covers(op.id()))
     }
 
+    /// Whether this op is at the key of the system object, which is hidden from reads, see
+    /// [`crate::system`]
+    pub(crate) fn is_hidden(&self) -> bool {
+        match self.op().key {
+            Key::Map(m) => self.obj().is_root() && self.osd.props.get(m) == SYSTEM_KEY,
+            Key::Seq(_) => false,
+        }
+    }
+
     pub(crate) fn exid(&self) -> ExId {
         let id = self.op().id;
         if id == types::ROOT {
@@ -291,6 +350,16 @@ impl<'a> Op<'a> {
         }
     }
 
+    /// The ID of the list element this op inserted or updated, i.e. the ID of the insert op
+    pub(crate) fn elem_exid(&self) -> Option<ExId> {
+        let ElemId(id) = self.elemid()?;
+        Some(ExId::Id(
+            id.counter(),
+            self.osd.actors.cache[id.actor()].clone(),
+            id.actor(),
+        ))
+    }
+
     pub(crate) fn get_increment_value(&self) -> Option<i64> {
         self.op().get_increment_value()
     }
@@ -418,7 +487,7 @@ pub(crate) struct OpBuilder {
 impl OpBuilder {
     pub(crate) fn increment(&mut self, n: i64) {
         if let OpType::Put(ScalarValue::Counter(c)) = &mut self.action {
-            c.current += n;
+            c.increment(n);
         }
     }
 
@@ -494,6 +563,7 @@ impl OpBuilder {
             OpType::Delete => "del".to_string(),
             OpType::MarkBegin(_, _) => "markBegin".to_string(),
             OpType::MarkEnd(_) => "markEnd".to_string(),
+            OpType::Seal(_) => "seal".to_string(),
         }
     }
 
@@ -9,7 +9,7 @@ use crate::{
 };
 use crate::{
     types::{Key, ListEncoding, ObjMeta, OpId, OpIds, Prop},
-    ObjType, OpType,
+    ObjType, OpType, ScalarValue,
 };
 use std::cmp::Ordering;
 use std::sync::Arc;
@@ -108,6 +108,10 @@ impl<'a> FoundOpWithPatchLog<'a> {
         doc: &Automerge,
         patch_log: &mut PatchLog,
     ) {
+        if op.is_seal() {
+            // sealing an object doesn't change what is visible at its key
+            return;
+        }
         if op.insert() {
             if op.is_mark() {
                 if let OpType::MarkEnd(_) = op.action() {
@@ -167,6 +171,13 @@ impl<'a> FoundOpWithPatchLog<'a> {
             if self.after.is_none() {
                 if let Some(counter) = self.overwritten {
                     if pred.overwrites(counter.id()) {
+                        // the increment has not been applied to the counter yet
+                        let value = match counter.action() {
+                            OpType::Put(ScalarValue::Counter(c)) => {
+                                c.value_change(value, counter.saturates())
+                            }
+                            _ => value,
+                        };
                         patch_log.increment(obj.id, &key, value, *op.id());
                     }
                 }
@@ -382,6 +393,28 @@ impl OpTreeInternal {
         })
     }
 
+    /// The ops at `key`, which for a list is every op at the element `key` identifies
+    pub(crate) fn ops_at_key<'a>(
+        &'a self,
+        key: Key,
+        osd: &'a OpSetData,
+    ) -> impl Iterator<Item = Op<'a>> + 'a {
+        let pos = match key {
+            Key::Map(_) => Some(self.binary_search_by(osd, |o| o.key_cmp(&key))),
+            Key::Seq(elemid) if elemid.is_head() => None,
+            Key::Seq(elemid) => self
+                .search(
+                    query::OpIdSearch::opid(elemid.0, ListEncoding::List, None),
+                    osd,
+                )
+                .found(),
+        };
+        pos.into_iter()
+            .flat_map(move |pos| self.iter().skip(pos))
+            .map(move |idx| idx.as_op(osd))
+            .take_while(move |op| op.elemid_or_key() == key)
+    }
+
     pub(crate) fn find_op_with_patch_log<'a>(
         &'a self,
         op: Op<'a>,
@@ -464,6 +497,14 @@ impl OpTreeInternal {
         })
     }
 
+    /// The position of the first op in this map whose key is not less than `key`
+    pub(crate) fn seek_map_key_from(&self, osd: &OpSetData, key: &str) -> usize {
+        self.binary_search_by(osd, |o| match o.key() {
+            Key::Map(n) => osd.props[*n].as_str().cmp(key),
+            Key::Seq(_) => Ordering::Less,
+        })
+    }
+
     pub(crate) fn seek_ops_by_index<'a>(
         &'a self,
         osd: &'a OpSetData,
@@ -1,8 +1,10 @@
+mod convert;
 mod patch;
 mod patch_builder;
 mod patch_log;
+pub use convert::{TextEncoding, TextIndexConverter};
 pub use patch::{Patch, PatchAction};
-pub(crate) use patch_builder::PatchBuilder;
+pub(crate) use patch_builder::{PatchBuilder, PatchBuilderState};
 pub use patch_log::PatchLog;
 
 use crate::{types::ListEncoding, ObjType};
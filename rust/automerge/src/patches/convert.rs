@@ -0,0 +1,444 @@
+use std::collections::HashMap;
+
+use crate::marks::Mark;
+use crate::{AutomergeError, ChangeHash, ObjId, ObjType, Prop, ReadDoc, ScalarValue, Value};
+
+use super::{Patch, PatchAction};
+
+/// The unit which indices into text are measured in
+///
+/// Which unit the indices in patches and in methods like
+/// [`crate::transaction::Transactable::splice_text()`] use is decided by the `utf8-indexing` and
+/// `utf16-indexing` features, the [`Default`] is whichever unit this build uses. Use
+/// [`TextIndexConverter`] to translate patches for clients which use a different unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextEncoding {
+    /// Indices are byte offsets into the UTF-8 encoding of the text
+    #[cfg_attr(feature = "utf8-indexing", default)]
+    Utf8,
+    /// Indices are offsets into the UTF-16 encoding of the text, as in JavaScript
+    #[cfg_attr(
+        all(feature = "utf16-indexing", not(feature = "utf8-indexing")),
+        default
+    )]
+    Utf16,
+    /// Indices count unicode code points
+    #[cfg_attr(
+        not(any(feature = "utf8-indexing", feature = "utf16-indexing")),
+        default
+    )]
+    CodePoint,
+}
+
+impl TextEncoding {
+    /// The width of `c` in this encoding
+    fn char_width(self, c: char) -> usize {
+        match self {
+            TextEncoding::Utf8 => c.len_utf8(),
+            TextEncoding::Utf16 => c.len_utf16(),
+            TextEncoding::CodePoint => 1,
+        }
+    }
+
+    /// The width of an element of a text object with `value` in this encoding
+    ///
+    /// Elements which are not text, e.g. block markers, are as wide as the object replacement
+    /// character which stands for them in [`crate::ReadDoc::text()`].
+    fn value_width(self, value: &Value<'_>) -> usize {
+        match value {
+            Value::Scalar(s) => match s.as_ref() {
+                ScalarValue::Str(s) => s.chars().map(|c| self.char_width(c)).sum(),
+                _ => self.char_width('\u{fffc}'),
+            },
+            Value::Object(_) => self.char_width('\u{fffc}'),
+        }
+    }
+}
+
+/// How many elements [`Widths`] keeps in a chunk before splitting it
+const CHUNK_LEN: usize = 256;
+
+/// The widths in the `from` and `to` encodings of each element of a text object
+///
+/// The widths are kept in chunks along with the total width of each chunk, so finding an index
+/// only visits the elements of the chunk it is in.
+#[derive(Debug, Default)]
+struct Widths {
+    chunks: Vec<Chunk>,
+}
+
+#[derive(Debug, Default)]
+struct Chunk {
+    widths: Vec<(usize, usize)>,
+    from: usize,
+    to: usize,
+}
+
+impl Chunk {
+    fn new(widths: Vec<(usize, usize)>) -> Self {
+        let (from, to) = widths
+            .iter()
+            .fold((0, 0), |(from, to), w| (from + w.0, to + w.1));
+        Chunk { widths, from, to }
+    }
+}
+
+impl Widths {
+    fn new<I: Iterator<Item = (usize, usize)>>(widths: I) -> Self {
+        let mut result = Widths::default();
+        result.insert(0, widths);
+        result
+    }
+
+    /// Find `index` in the `from` encoding, returning the position of the element it is just
+    /// after and the index in the `to` encoding
+    fn locate(&self, index: usize) -> Result<(usize, usize), AutomergeError> {
+        if index == 0 {
+            return Ok((0, 0));
+        }
+        let (mut pos, mut from_width, mut to_width) = (0, 0, 0);
+        for chunk in &self.chunks {
+            if from_width + chunk.from < index {
+                pos += chunk.widths.len();
+                from_width += chunk.from;
+                to_width += chunk.to;
+                continue;
+            }
+            for (from, to) in &chunk.widths {
+                pos += 1;
+                from_width += from;
+                to_width += to;
+                if from_width > index {
+                    break;
+                }
+                if from_width == index {
+                    return Ok((pos, to_width));
+                }
+            }
+            break;
+        }
+        // `index` is past the end or in the middle of a character
+        Err(AutomergeError::InvalidIndex(index))
+    }
+
+    /// The chunk containing the element at `pos` and the offset of the element in the chunk, or
+    /// the number of chunks if `pos` is past the end
+    fn chunk_at(&self, mut pos: usize) -> (usize, usize) {
+        for (i, chunk) in self.chunks.iter().enumerate() {
+            if pos < chunk.widths.len() {
+                return (i, pos);
+            }
+            pos -= chunk.widths.len();
+        }
+        (self.chunks.len(), pos)
+    }
+
+    fn insert<I: Iterator<Item = (usize, usize)>>(&mut self, pos: usize, widths: I) {
+        let (i, offset) = match self.chunk_at(pos) {
+            // appending goes on the end of the last chunk
+            (i, _) if i == self.chunks.len() => match self.chunks.last() {
+                Some(last) => (i - 1, last.widths.len()),
+                None => {
+                    self.chunks.push(Chunk::default());
+                    (0, 0)
+                }
+            },
+            found => found,
+        };
+        let chunk = &mut self.chunks[i];
+        let tail = chunk.widths.split_off(offset);
+        chunk.widths.extend(widths);
+        chunk.widths.extend(tail);
+        let widths = std::mem::take(&mut chunk.widths);
+        let split = widths
+            .chunks(CHUNK_LEN)
+            .map(|w| Chunk::new(w.to_vec()))
+            .collect::<Vec<_>>();
+        self.chunks.splice(i..=i, split);
+    }
+
+    fn remove(&mut self, start: usize, end: usize) {
+        let (i, mut offset) = self.chunk_at(start);
+        let mut len = end - start;
+        for chunk in self.chunks.iter_mut().skip(i) {
+            if len == 0 {
+                break;
+            }
+            let end = chunk.widths.len().min(offset + len);
+            for (from, to) in chunk.widths.drain(offset..end) {
+                chunk.from -= from;
+                chunk.to -= to;
+                len -= 1;
+            }
+            offset = 0;
+        }
+        self.chunks.retain(|chunk| !chunk.widths.is_empty());
+    }
+
+    fn set(&mut self, pos: usize, width: (usize, usize)) {
+        let (i, offset) = self.chunk_at(pos);
+        if let Some(chunk) = self.chunks.get_mut(i) {
+            if let Some(old) = chunk.widths.get_mut(offset) {
+                chunk.from = chunk.from - old.0 + width.0;
+                chunk.to = chunk.to - old.1 + width.1;
+                *old = width;
+            }
+        }
+    }
+}
+
+/// Translates the indices of text patches from one [`TextEncoding`] to another
+///
+/// Converting an index requires the widths of the characters before it, so the converter keeps
+/// the widths of the characters of each text object it has seen a patch for, starting from the
+/// state of the document at the heads it was created with and updated by each patch it converts. This means patches must be
+/// passed to [`Self::convert()`] in the order they were produced, starting with the first patch
+/// which applies to the document as at those heads.
+///
+/// ```
+/// # use automerge::{AutoCommit, ObjType, ReadDoc, ROOT, transaction::Transactable};
+/// # use automerge::patches::{TextEncoding, TextIndexConverter};
+/// # use automerge::PatchAction;
+/// let mut doc = AutoCommit::new();
+/// let text = doc.put_object(&ROOT, "text", ObjType::Text).unwrap();
+/// doc.splice_text(&text, 0, 0, "🐻").unwrap();
+/// let before = doc.get_heads();
+/// doc.splice_text(&text, 1, 0, "!").unwrap();
+/// let after = doc.get_heads();
+///
+/// let patches = doc.diff(&before, &after);
+/// let mut converter = TextIndexConverter::new(&doc, &before, TextEncoding::CodePoint, TextEncoding::Utf16);
+/// let converted = converter.convert(&patches[0]).unwrap();
+/// // the bear is two UTF-16 code units wide
+/// assert!(matches!(converted.action, PatchAction::SpliceText { index: 2, .. }));
+/// ```
+#[derive(Debug)]
+pub struct TextIndexConverter<'a, R> {
+    doc: &'a R,
+    heads: Vec<ChangeHash>,
+    from: TextEncoding,
+    to: TextEncoding,
+    texts: HashMap<ObjId, Widths>,
+}
+
+impl<'a, R: ReadDoc> TextIndexConverter<'a, R> {
+    /// Create a converter for patches which start from the state of `doc` as at `heads`
+    pub fn new(doc: &'a R, heads: &[ChangeHash], from: TextEncoding, to: TextEncoding) -> Self {
+        Self {
+            doc,
+            heads: heads.to_vec(),
+            from,
+            to,
+            texts: HashMap::new(),
+        }
+    }
+
+    /// Convert the indices of `patch` if it applies to a text object
+    ///
+    /// Patches to other objects are returned unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AutomergeError::InvalidIndex`] if an index in `patch` is past the end of the
+    /// text or is in the middle of a character in the `from` encoding.
+    pub fn convert(&mut self, patch: &Patch) -> Result<Patch, AutomergeError> {
+        if self.doc.object_type(&patch.obj).ok() != Some(ObjType::Text) {
+            return Ok(patch.clone());
+        }
+        let (from, to) = (self.from, self.to);
+        if !self.texts.contains_key(&patch.obj) {
+            let widths = Widths::new(
+                self.doc
+                    .list_range_at(&patch.obj, .., &self.heads)
+                    .map(|item| (from.value_width(&item.value), to.value_width(&item.value))),
+            );
+            self.texts.insert(patch.obj.clone(), widths);
+        }
+        let text = self.texts.get_mut(&patch.obj).unwrap();
+        let action = match &patch.action {
+            PatchAction::SpliceText {
+                index,
+                value,
+                marks,
+            } => {
+                let (pos, converted) = text.locate(*index)?;
+                let inserted = value.make_string();
+                text.insert(
+                    pos,
+                    inserted
+                        .chars()
+                        .map(|c| (from.char_width(c), to.char_width(c))),
+                );
+                PatchAction::SpliceText {
+                    index: converted,
+                    value: value.clone(),
+                    marks: marks.clone(),
+                }
+            }
+            PatchAction::Insert { index, values } => {
+                let (pos, converted) = text.locate(*index)?;
+                text.insert(
+                    pos,
+                    values
+                        .iter()
+                        .map(|(value, _, _)| (from.value_width(value), to.value_width(value))),
+                );
+                PatchAction::Insert {
+                    index: converted,
+                    values: values.clone(),
+                }
+            }
+            PatchAction::DeleteSeq { index, length } => {
+                let (start, converted_start) = text.locate(*index)?;
+                let (end, converted_end) = text.locate(*index + *length)?;
+                text.remove(start, end);
+                PatchAction::DeleteSeq {
+                    index: converted_start,
+                    length: converted_end - converted_start,
+                }
+            }
+            PatchAction::PutSeq {
+                index,
+                value,
+                conflict,
+            } => {
+                let (pos, converted) = text.locate(*index)?;
+                text.set(pos, (from.value_width(&value.0), to.value_width(&value.0)));
+                PatchAction::PutSeq {
+                    index: converted,
+                    value: value.clone(),
+                    conflict: *conflict,
+                }
+            }
+            PatchAction::Increment {
+                prop: Prop::Seq(index),
+                value,
+            } => PatchAction::Increment {
+                prop: Prop::Seq(text.locate(*index)?.1),
+                value: *value,
+            },
+            PatchAction::Conflict {
+                prop: Prop::Seq(index),
+            } => PatchAction::Conflict {
+                prop: Prop::Seq(text.locate(*index)?.1),
+            },
+            PatchAction::Mark { marks } => PatchAction::Mark {
+                marks: marks
+                    .iter()
+                    .map(|m| {
+                        Ok(Mark {
+                            start: text.locate(m.start)?.1,
+                            end: text.locate(m.end)?.1,
+                            data: m.data.clone(),
+                        })
+                    })
+                    .collect::<Result<_, AutomergeError>>()?,
+            },
+            other => other.clone(),
+        };
+        Ok(Patch {
+            obj: patch.obj.clone(),
+            path: patch.path.clone(),
+            action,
+        })
+    }
+}
+
+impl Patch {
+    /// Convert the indices of this patch from `from` to `to` if it applies to a text object
+    ///
+    /// `heads` must be the heads of `doc` which this patch applies to, i.e. the state of the
+    /// document just before the patch. To convert a sequence of patches use a
+    /// [`TextIndexConverter`], which tracks the state of the text between patches.
+    pub fn convert_text_representation<R: ReadDoc>(
+        &self,
+        doc: &R,
+        heads: &[ChangeHash],
+        from: TextEncoding,
+        to: TextEncoding,
+    ) -> Result<Patch, AutomergeError> {
+        TextIndexConverter::new(doc, heads, from, to).convert(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TextEncoding, TextIndexConverter};
+    use crate::{transaction::Transactable, AutoCommit, ObjType, PatchAction, ReadDoc, ROOT};
+
+    #[test]
+    fn converts_indices_after_wide_characters() {
+        let mut doc = AutoCommit::new();
+        let text = doc.put_object(&ROOT, "text", ObjType::Text).unwrap();
+        doc.splice_text(&text, 0, 0, "a😀b").unwrap();
+        let before = doc.get_heads();
+        let end = doc.length(&text);
+        doc.splice_text(&text, end, 0, "x").unwrap();
+        doc.splice_text(&text, end + 1, 0, "y").unwrap();
+        let after = doc.get_heads();
+        let patches = doc.diff(&before, &after);
+
+        let indices = |to| {
+            let mut converter = TextIndexConverter::new(&doc, &before, TextEncoding::default(), to);
+            patches
+                .iter()
+                .map(|p| match converter.convert(p).unwrap().action {
+                    PatchAction::SpliceText { index, .. } => index,
+                    other => panic!("unexpected action {:?}", other),
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(indices(TextEncoding::CodePoint)[0], 3);
+        assert_eq!(indices(TextEncoding::Utf16)[0], 4);
+        assert_eq!(indices(TextEncoding::Utf8)[0], 6);
+    }
+
+    #[test]
+    fn index_inside_a_character_is_an_error() {
+        let mut doc = AutoCommit::new();
+        let text = doc.put_object(&ROOT, "text", ObjType::Text).unwrap();
+        doc.splice_text(&text, 0, 0, "😀").unwrap();
+        let before = doc.get_heads();
+        doc.splice_text(&text, doc.length(&text), 0, "x").unwrap();
+        let after = doc.get_heads();
+        let mut patch = doc.diff(&before, &after).remove(0);
+        if let PatchAction::SpliceText { index, .. } = &mut patch.action {
+            *index = 1;
+        }
+        assert!(patch
+            .convert_text_representation(&doc, &before, TextEncoding::Utf16, TextEncoding::Utf8)
+            .is_err());
+    }
+
+    #[test]
+    fn converts_edits_across_many_chunks() {
+        let mut doc = AutoCommit::new();
+        let text = doc.put_object(&ROOT, "text", ObjType::Text).unwrap();
+        doc.splice_text(&text, 0, 0, &"a😀".repeat(1000)).unwrap();
+        let before = doc.get_heads();
+        let utf16 = |doc: &AutoCommit| doc.text(&text).unwrap().encode_utf16().collect::<Vec<_>>();
+        let mut expected = utf16(&doc);
+        doc.splice_text(&text, 250, 20, "bear 🐻").unwrap();
+        doc.splice_text(&text, 1500, 600, "").unwrap();
+        doc.splice_text(&text, doc.length(&text), 0, "end").unwrap();
+        doc.splice_text(&text, 0, 1, "").unwrap();
+        let after = doc.get_heads();
+
+        let patches = doc.diff(&before, &after);
+        let native = TextEncoding::default();
+        let mut converter = TextIndexConverter::new(&doc, &before, native, TextEncoding::Utf16);
+        for patch in patches {
+            match converter.convert(&patch).unwrap().action {
+                PatchAction::SpliceText { index, value, .. } => {
+                    let inserted = value.make_string().encode_utf16().collect::<Vec<_>>();
+                    expected.splice(index..index, inserted);
+                }
+                PatchAction::DeleteSeq { index, length } => {
+                    expected.drain(index..index + length);
+                }
+                other => panic!("unexpected action {:?}", other),
+            }
+        }
+        assert_eq!(expected, utf16(&doc));
+    }
+}
@@ -4,6 +4,7 @@ use std::sync::Arc;
 
 use crate::marks::MarkSet;
 use crate::read::ReadDocInternal;
+use crate::system;
 use crate::{ObjId, Prop, ReadDoc, Value};
 
 use super::{Patch, PatchAction};
@@ -14,6 +15,7 @@ pub(crate) struct PatchBuilder<'a, R> {
     patches: Vec<Patch>,
     last_mark_set: Option<Arc<MarkSet>>, // keep this around for a quick pointer equality test
     visible_paths: Option<HashMap<ObjId, Vec<(ObjId, Prop)>>>,
+    coalesce_text: bool,
     doc: &'a R,
 }
 
@@ -31,9 +33,48 @@ impl<'a, R: ReadDocInternal> PatchBuilder<'a, R> {
             patches: Vec::new(),
             last_mark_set: None,
             visible_paths: path_lookup,
+            coalesce_text: false,
             doc,
         }
     }
+
+    /// Also merge deletions of text which run backwards or remove the whole of the text just
+    /// spliced in, see [`crate::PatchLog::with_coalesced_text()`]
+    pub(crate) fn with_coalesced_text(mut self, coalesce: bool) -> Self {
+        self.coalesce_text = coalesce;
+        self
+    }
+
+    /// Continue building patches from a state returned by [`Self::suspend()`]
+    pub(crate) fn resume(doc: &'a R, state: PatchBuilderState) -> Self {
+        Self {
+            patches: state.patches,
+            last_mark_set: state.last_mark_set,
+            visible_paths: state.visible_paths,
+            coalesce_text: state.coalesce_text,
+            doc,
+        }
+    }
+
+    /// Detach the builder from the document it reads from, so it can be resumed with another
+    /// borrow of the same document
+    pub(crate) fn suspend(self) -> PatchBuilderState {
+        PatchBuilderState {
+            patches: self.patches,
+            last_mark_set: self.last_mark_set,
+            visible_paths: self.visible_paths,
+            coalesce_text: self.coalesce_text,
+        }
+    }
+}
+
+/// A [`PatchBuilder`] without its document, see [`PatchBuilder::suspend()`]
+#[derive(Debug, Clone)]
+pub(crate) struct PatchBuilderState {
+    patches: Vec<Patch>,
+    last_mark_set: Option<Arc<MarkSet>>,
+    visible_paths: Option<HashMap<ObjId, Vec<(ObjId, Prop)>>>,
+    coalesce_text: bool,
 }
 
 impl<'a, R: ReadDoc> PatchBuilder<'a, R> {
@@ -55,6 +96,14 @@ impl<'a, R: ReadDoc> PatchBuilder<'a, R> {
         std::mem::take(&mut self.patches)
     }
 
+    /// Take every patch except the last, which later events may still be merged into
+    pub(crate) fn take_finished_patches(&mut self) -> Vec<Patch> {
+        let last = self.patches.pop();
+        let finished = std::mem::take(&mut self.patches);
+        self.patches.extend(last);
+        finished
+    }
+
     pub(crate) fn insert(
         &mut self,
         obj: ObjId,
@@ -136,6 +185,16 @@ impl<'a, R: ReadDoc> PatchBuilder<'a, R> {
                     }
                     return;
                 }
+                if self.coalesce_text && index <= range.start && index + length >= range.end {
+                    // the spliced text is deleted along with text around it, which is the same
+                    // as only deleting the text around it
+                    let remaining = length - value.len();
+                    self.patches.pop();
+                    if remaining > 0 {
+                        self.delete_seq(obj, index, remaining);
+                    }
+                    return;
+                }
             }
             Some(PatchAction::Insert {
                 index: tail_index,
@@ -162,6 +221,12 @@ impl<'a, R: ReadDoc> PatchBuilder<'a, R> {
                     *tail_length += length;
                     return;
                 }
+                if self.coalesce_text && index + length == *tail_index {
+                    // deleting backwards, as with backspace
+                    *tail_index = index;
+                    *tail_length += length;
+                    return;
+                }
             }
             _ => {}
         }
@@ -172,6 +237,9 @@ impl<'a, R: ReadDoc> PatchBuilder<'a, R> {
     }
 
     pub(crate) fn delete_map(&mut self, obj: ObjId, key: &str) {
+        if system::is_system_prop(&obj, &key.into()) {
+            return;
+        }
         if let Some(path) = self.get_path(&obj) {
             let action = PatchAction::DeleteMap {
                 key: key.to_owned(),
@@ -187,6 +255,9 @@ impl<'a, R: ReadDoc> PatchBuilder<'a, R> {
         tagged_value: (Value<'_>, ObjId),
         conflict: bool,
     ) {
+        if system::is_system_prop(&obj, &prop) {
+            return;
+        }
         if let Some(path) = self.get_path(&obj) {
             let value = (tagged_value.0.to_owned(), tagged_value.1);
             let action = match prop {
@@ -206,6 +277,9 @@ impl<'a, R: ReadDoc> PatchBuilder<'a, R> {
     }
 
     pub(crate) fn increment(&mut self, obj: ObjId, prop: Prop, tagged_value: (i64, ObjId)) {
+        if system::is_system_prop(&obj, &prop) {
+            return;
+        }
         if let Some(path) = self.get_path(&obj) {
             let value = tagged_value.0;
             let action = PatchAction::Increment { prop, value };
@@ -230,6 +304,9 @@ impl<'a, R: ReadDoc> PatchBuilder<'a, R> {
     }
 
     pub(crate) fn flag_conflict(&mut self, obj: ObjId, prop: Prop) {
+        if system::is_system_prop(&obj, &prop) {
+            return;
+        }
         let conflict = match maybe_append(&mut self.patches, &obj) {
             Some(PatchAction::PutMap { key, conflict, .. })
                 if Some(key.as_str()) == prop.as_str() =>
@@ -10,7 +10,7 @@ use std::collections::BTreeSet;
 use std::collections::HashSet;
 use std::sync::Arc;
 
-use super::{PatchBuilder, TextRepresentation};
+use super::{PatchBuilder, PatchBuilderState, TextRepresentation};
 
 /// A record of changes made to a document
 ///
@@ -46,6 +46,7 @@ pub struct PatchLog {
     expose: HashSet<OpId>,
     active: bool,
     text_rep: TextRepresentation,
+    coalesce_text: bool,
     pub(crate) heads: Option<Vec<ChangeHash>>,
 }
 
@@ -122,9 +123,22 @@ impl PatchLog {
             events: vec![],
             heads: None,
             text_rep,
+            coalesce_text: false,
         }
     }
 
+    /// Merge more of the edits made to a text object into single patches
+    ///
+    /// Consecutive insertions and deletions at the same place are always merged. With this set
+    /// deleting backwards, as a user holding backspace does, produces a single
+    /// [`crate::PatchAction::DeleteSeq`] and deleting text which was just inserted along with
+    /// some of the text around it produces only the deletion of the text around it. Editors
+    /// then receive one patch per contiguous edit rather than one per character.
+    pub fn with_coalesced_text(mut self, coalesce: bool) -> Self {
+        self.coalesce_text = coalesce;
+        self
+    }
+
     /// Create a new [`PatchLog`] which doesn't record any changes.
     ///
     /// See also: [`PatchLog::new()`] for a more detailed explanation.
@@ -324,9 +338,23 @@ impl PatchLog {
         let expose = ExposeQueue(self.expose.iter().map(|id| doc.id_to_exid(*id)).collect());
         if let Some(heads) = self.heads.as_ref() {
             let read_doc = ReadDocAt { doc, heads };
-            Self::make_patches_inner(&self.events, expose, doc, &read_doc, self.text_rep)
+            Self::make_patches_inner(
+                &self.events,
+                expose,
+                doc,
+                &read_doc,
+                self.text_rep,
+                self.coalesce_text,
+            )
         } else {
-            Self::make_patches_inner(&self.events, expose, doc, doc, self.text_rep)
+            Self::make_patches_inner(
+                &self.events,
+                expose,
+                doc,
+                doc,
+                self.text_rep,
+                self.coalesce_text,
+            )
         }
     }
 
@@ -336,79 +364,20 @@ impl PatchLog {
         doc: &Automerge,
         read_doc: &R,
         text_rep: TextRepresentation,
+        coalesce_text: bool,
     ) -> Vec<Patch> {
-        let mut patch_builder = PatchBuilder::new(read_doc, Some(events.len()));
+        let mut patch_builder =
+            PatchBuilder::new(read_doc, Some(events.len())).with_coalesced_text(coalesce_text);
         for (obj, event) in events {
-            let exid = doc.id_to_exid(obj.0);
-            // ignore events on objects in the expose queue
-            // incremental updates are ignored and a observation
-            // of the final state is used b/c observers did not see
-            // past state changes
-            if expose_queue.should_skip(&exid) {
-                continue;
-            }
-            // any objects exposed BEFORE exid get observed here
-            expose_queue.pump_queue(&exid, &mut patch_builder, doc, read_doc, text_rep);
-            match event {
-                Event::PutMap {
-                    key,
-                    value,
-                    id,
-                    conflict,
-                } => {
-                    let opid = doc.id_to_exid(*id);
-                    patch_builder.put(exid, key.into(), (value.into(), opid), *conflict);
-                }
-                Event::DeleteMap { key } => {
-                    patch_builder.delete_map(exid, key);
-                }
-                Event::IncrementMap { key, n, id } => {
-                    let opid = doc.id_to_exid(*id);
-                    patch_builder.increment(exid, key.into(), (*n, opid));
-                }
-                Event::FlagConflictMap { key } => {
-                    patch_builder.flag_conflict(exid, key.into());
-                }
-                Event::PutSeq {
-                    index,
-                    value,
-                    id,
-                    conflict,
-                } => {
-                    let opid = doc.id_to_exid(*id);
-                    patch_builder.put(exid, index.into(), (value.into(), opid), *conflict);
-                }
-                Event::Insert {
-                    index,
-                    value,
-                    id,
-                    conflict,
-                    //marks,
-                } => {
-                    let opid = doc.id_to_exid(*id);
-                    patch_builder.insert(
-                        exid,
-                        *index,
-                        (value.into(), opid),
-                        *conflict,
-                        //marks.clone(),
-                    );
-                }
-                Event::DeleteSeq { index, num } => {
-                    patch_builder.delete_seq(exid, *index, *num);
-                }
-                Event::IncrementSeq { index, n, id } => {
-                    let opid = doc.id_to_exid(*id);
-                    patch_builder.increment(exid, index.into(), (*n, opid));
-                }
-                Event::FlagConflictSeq { index } => {
-                    patch_builder.flag_conflict(exid, index.into());
-                }
-                Event::Splice { index, text, marks } => {
-                    patch_builder.splice_text(exid, *index, text, marks.clone());
-                }
-                Event::Mark { marks } => patch_builder.mark(exid, marks.clone().into_iter()),
-            }
+            Self::log_event(
+                obj,
+                event,
+                &mut expose_queue,
+                &mut patch_builder,
+                doc,
+                read_doc,
+                text_rep,
+            );
         }
         // any objects exposed AFTER all other events get exposed here
         expose_queue.flush_queue(&mut patch_builder, doc, read_doc, text_rep);
@@ -416,6 +385,113 @@ impl PatchLog {
         patch_builder.take_patches()
     }
 
+    pub(crate) fn make_patches_iter<'a>(&'a mut self, doc: &'a Automerge) -> PatchIter<'a> {
+        self.events.sort_by(|a, b| doc.ops().osd.lamport_cmp(a, b));
+        let expose_queue = ExposeQueue(self.expose.iter().map(|id| doc.id_to_exid(*id)).collect());
+        let heads = self.heads.as_deref();
+        let builder = match heads {
+            Some(heads) => {
+                let read_doc = ReadDocAt { doc, heads };
+                PatchBuilder::new(&read_doc, Some(self.events.len()))
+                    .with_coalesced_text(self.coalesce_text)
+                    .suspend()
+            }
+            None => PatchBuilder::new(doc, Some(self.events.len()))
+                .with_coalesced_text(self.coalesce_text)
+                .suspend(),
+        };
+        PatchIter {
+            doc,
+            heads,
+            events: self.events.iter(),
+            expose_queue,
+            text_rep: self.text_rep,
+            builder: Some(builder),
+            ready: Vec::new().into_iter(),
+        }
+    }
+
+    fn log_event<R: ReadDocInternal>(
+        obj: &ObjId,
+        event: &Event,
+        expose_queue: &mut ExposeQueue,
+        patch_builder: &mut PatchBuilder<'_, R>,
+        doc: &Automerge,
+        read_doc: &R,
+        text_rep: TextRepresentation,
+    ) {
+        let exid = doc.id_to_exid(obj.0);
+        // ignore events on objects in the expose queue
+        // incremental updates are ignored and a observation
+        // of the final state is used b/c observers did not see
+        // past state changes
+        if expose_queue.should_skip(&exid) {
+            return;
+        }
+        // any objects exposed BEFORE exid get observed here
+        expose_queue.pump_queue(&exid, patch_builder, doc, read_doc, text_rep);
+        match event {
+            Event::PutMap {
+                key,
+                value,
+                id,
+                conflict,
+            } => {
+                let opid = doc.id_to_exid(*id);
+                patch_builder.put(exid, key.into(), (value.into(), opid), *conflict);
+            }
+            Event::DeleteMap { key } => {
+                patch_builder.delete_map(exid, key);
+            }
+            Event::IncrementMap { key, n, id } => {
+                let opid = doc.id_to_exid(*id);
+                patch_builder.increment(exid, key.into(), (*n, opid));
+            }
+            Event::FlagConflictMap { key } => {
+                patch_builder.flag_conflict(exid, key.into());
+            }
+            Event::PutSeq {
+                index,
+                value,
+                id,
+                conflict,
+            } => {
+                let opid = doc.id_to_exid(*id);
+                patch_builder.put(exid, index.into(), (value.into(), opid), *conflict);
+            }
+            Event::Insert {
+                index,
+                value,
+                id,
+                conflict,
+                //marks,
+            } => {
+                let opid = doc.id_to_exid(*id);
+                patch_builder.insert(
+                    exid,
+                    *index,
+                    (value.into(), opid),
+                    *conflict,
+                    //marks.clone(),
+                );
+            }
+            Event::DeleteSeq { index, num } => {
+                patch_builder.delete_seq(exid, *index, *num);
+            }
+            Event::IncrementSeq { index, n, id } => {
+                let opid = doc.id_to_exid(*id);
+                patch_builder.increment(exid, index.into(), (*n, opid));
+            }
+            Event::FlagConflictSeq { index } => {
+                patch_builder.flag_conflict(exid, index.into());
+            }
+            Event::Splice { index, text, marks } => {
+                patch_builder.splice_text(exid, *index, text, marks.clone());
+            }
+            Event::Mark { marks } => patch_builder.mark(exid, marks.clone().into_iter()),
+        }
+    }
+
     pub(crate) fn truncate(&mut self) {
         self.active = true;
         self.events.truncate(0);
@@ -428,6 +504,7 @@ impl PatchLog {
             expose: HashSet::new(),
             events: Default::default(),
             text_rep: self.text_rep,
+            coalesce_text: self.coalesce_text,
             heads: None,
         }
     }
@@ -451,6 +528,72 @@ impl AsRef<OpId> for &(ObjId, Event) {
     }
 }
 
+/// An iterator over the patches in a [`PatchLog`] which builds them as it goes, returned by
+/// [`crate::Automerge::make_patches_iter()`]
+#[derive(Debug)]
+pub(crate) struct PatchIter<'a> {
+    doc: &'a Automerge,
+    heads: Option<&'a [ChangeHash]>,
+    events: std::slice::Iter<'a, (ObjId, Event)>,
+    expose_queue: ExposeQueue,
+    text_rep: TextRepresentation,
+    // `None` once every event has been turned into patches
+    builder: Option<PatchBuilderState>,
+    ready: std::vec::IntoIter<Patch>,
+}
+
+impl<'a> PatchIter<'a> {
+    /// Log the next event, or flush the expose queue if there are no more events, returning any
+    /// patches which can no longer change
+    fn step<R: ReadDocInternal>(&mut self, read_doc: &R, state: PatchBuilderState) -> Vec<Patch> {
+        let mut patch_builder = PatchBuilder::resume(read_doc, state);
+        match self.events.next() {
+            Some((obj, event)) => {
+                PatchLog::log_event(
+                    obj,
+                    event,
+                    &mut self.expose_queue,
+                    &mut patch_builder,
+                    self.doc,
+                    read_doc,
+                    self.text_rep,
+                );
+                let finished = patch_builder.take_finished_patches();
+                self.builder = Some(patch_builder.suspend());
+                finished
+            }
+            None => {
+                self.expose_queue.flush_queue(
+                    &mut patch_builder,
+                    self.doc,
+                    read_doc,
+                    self.text_rep,
+                );
+                patch_builder.take_patches()
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for PatchIter<'a> {
+    type Item = Patch;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(patch) = self.ready.next() {
+                return Some(patch);
+            }
+            let state = self.builder.take()?;
+            let doc = self.doc;
+            let finished = match self.heads {
+                Some(heads) => self.step(&ReadDocAt { doc, heads }, state),
+                None => self.step(doc, state),
+            };
+            self.ready = finished.into_iter();
+        }
+    }
+}
+
 #[derive(Clone, Default, PartialEq, Debug)]
 struct ExposeQueue(BTreeSet<ExId>);
 
@@ -544,6 +687,7 @@ impl ExposeQueue {
                     patch_builder.put(exid.clone(), key.into(), (value, id), conflict);
                 }
             }
+            ObjType::Unknown(_) => {}
         }
         Some(())
     }
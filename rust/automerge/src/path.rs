@@ -0,0 +1,253 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::exid::ExId;
+use crate::types::Clock;
+use crate::{Automerge, AutomergeError, ObjType, Prop, ReadDoc, Value};
+
+/// A path from the root of a document to a value in it
+///
+/// Paths can be parsed from strings in the style of [JSON Pointer][1], for example
+/// `/todos/3/title` is the path to the `title` key of the fourth element of the `todos` list in
+/// the root map. As in JSON Pointer `~1` is an escaped `/` and `~0` is an escaped `~`.
+///
+/// Whether a segment is a map key or a list index is decided when the path is resolved against a
+/// document. A segment which is a number written without leading zeros is an index if the object
+/// it is resolved against is a list or text object and a key if the object is a map, so
+/// `/counts/3` will find the key `"3"` in a map. Any other segment is always a key, so `/counts/03`
+/// finds the key `"03"` rather than `"3"`.
+///
+/// Paths are resolved by [`crate::ReadDoc::get_at_path()`],
+/// [`crate::transaction::Transactable::put_at_path()`] and
+/// [`crate::transaction::Transactable::delete_at_path()`].
+///
+/// [1]: https://datatracker.ietf.org/doc/html/rfc6901
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Path(Vec<Prop>);
+
+impl Path {
+    /// The path to the root object
+    pub fn root() -> Self {
+        Self(Vec::new())
+    }
+
+    /// Whether this path refers to the root object
+    pub fn is_root(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The number of segments in this path
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether this path has no segments, this is the same as [`Self::is_root()`]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Append `prop` to the end of this path
+    pub fn push<P: Into<Prop>>(&mut self, prop: P) {
+        self.0.push(prop.into())
+    }
+
+    /// Return a new path with `prop` appended to it
+    pub fn join<P: Into<Prop>>(&self, prop: P) -> Self {
+        let mut path = self.clone();
+        path.push(prop);
+        path
+    }
+
+    /// The path to the object containing the value this path refers to
+    ///
+    /// Returns `None` if this is the root path
+    pub fn parent(&self) -> Option<Path> {
+        self.0.split_last().map(|(_, parent)| Path(parent.to_vec()))
+    }
+
+    /// The last segment of this path
+    pub fn last(&self) -> Option<&Prop> {
+        self.0.last()
+    }
+
+    /// Iterate over the segments of this path
+    pub fn iter(&self) -> impl Iterator<Item = &Prop> {
+        self.0.iter()
+    }
+}
+
+impl From<Vec<Prop>> for Path {
+    fn from(props: Vec<Prop>) -> Self {
+        Self(props)
+    }
+}
+
+impl<P: Into<Prop>> FromIterator<P> for Path {
+    fn from_iter<T: IntoIterator<Item = P>>(iter: T) -> Self {
+        Self(iter.into_iter().map(Into::into).collect())
+    }
+}
+
+impl From<Path> for Vec<Prop> {
+    fn from(path: Path) -> Self {
+        path.0
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for prop in &self.0 {
+            match prop {
+                Prop::Map(key) => write!(f, "/{}", key.replace('~', "~0").replace('/', "~1"))?,
+                Prop::Seq(index) => write!(f, "/{}", index)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum ParsePathError {
+    #[error("path must be empty or start with a '/'")]
+    MissingLeadingSlash,
+    #[error("invalid escape sequence in path segment `{0}`")]
+    InvalidEscape(String),
+}
+
+impl FromStr for Path {
+    type Err = ParsePathError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Ok(Path::root());
+        }
+        let Some(rest) = s.strip_prefix('/') else {
+            return Err(ParsePathError::MissingLeadingSlash);
+        };
+        rest.split('/').map(parse_segment).collect()
+    }
+}
+
+fn parse_segment(segment: &str) -> Result<Prop, ParsePathError> {
+    // only segments which an index displays as are parsed as indexes, so that resolving the
+    // segment against a map finds the key exactly as it was written
+    if let Ok(index) = segment.parse::<usize>() {
+        if index.to_string() == segment {
+            return Ok(Prop::Seq(index));
+        }
+    }
+    let mut key = String::with_capacity(segment.len());
+    let mut chars = segment.chars();
+    while let Some(c) = chars.next() {
+        if c == '~' {
+            match chars.next() {
+                Some('0') => key.push('~'),
+                Some('1') => key.push('/'),
+                _ => return Err(ParsePathError::InvalidEscape(segment.to_string())),
+            }
+        } else {
+            key.push(c);
+        }
+    }
+    Ok(Prop::Map(key))
+}
+
+impl Automerge {
+    /// Resolve `path` to the object containing the value it refers to and the prop of the value
+    /// within that object
+    pub(crate) fn resolve_path_for(
+        &self,
+        path: &Path,
+        clock: Option<Clock>,
+    ) -> Result<(ExId, Prop), AutomergeError> {
+        resolve(
+            path,
+            |obj, prop| self.get_for(obj, prop, clock.clone()),
+            |obj| Ok(self.exid_to_obj(obj)?.typ),
+        )
+    }
+}
+
+/// Resolve `path` to the object containing the value it refers to and the prop of the value
+/// within that object, reading `doc` as it is now
+pub(crate) fn resolve_path<R: ReadDoc + ?Sized>(
+    doc: &R,
+    path: &Path,
+) -> Result<(ExId, Prop), AutomergeError> {
+    resolve(
+        path,
+        |obj, prop| doc.get(obj, prop),
+        |obj| doc.object_type(obj),
+    )
+}
+
+fn resolve<'a, G, T>(path: &Path, get: G, object_type: T) -> Result<(ExId, Prop), AutomergeError>
+where
+    G: Fn(&ExId, Prop) -> Result<Option<(Value<'a>, ExId)>, AutomergeError>,
+    T: Fn(&ExId) -> Result<ObjType, AutomergeError>,
+{
+    let Some((last, parents)) = path.0.split_last() else {
+        return Err(AutomergeError::InvalidPath(path.to_string()));
+    };
+    let mut obj = ExId::Root;
+    for prop in parents {
+        let prop = coerce_path_prop(object_type(&obj)?, prop, path)?;
+        match get(&obj, prop)? {
+            Some((Value::Object(_), id)) => obj = id,
+            _ => return Err(AutomergeError::InvalidPath(path.to_string())),
+        }
+    }
+    let prop = coerce_path_prop(object_type(&obj)?, last, path)?;
+    Ok((obj, prop))
+}
+
+fn coerce_path_prop(typ: ObjType, prop: &Prop, path: &Path) -> Result<Prop, AutomergeError> {
+    match (typ, prop) {
+        (ObjType::Map | ObjType::Table, Prop::Seq(index)) => Ok(Prop::Map(index.to_string())),
+        (ObjType::List | ObjType::Text, Prop::Map(_)) => {
+            Err(AutomergeError::InvalidPath(path.to_string()))
+        }
+        (_, prop) => Ok(prop.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ParsePathError, Path};
+    use crate::Prop;
+
+    #[test]
+    fn parse_and_display_round_trip() {
+        let path: Path = "/todos/3/a~1b~0c".parse().unwrap();
+        assert_eq!(
+            Vec::<Prop>::from(path.clone()),
+            vec![
+                Prop::Map("todos".into()),
+                Prop::Seq(3),
+                Prop::Map("a/b~c".into()),
+            ]
+        );
+        assert_eq!(path.to_string(), "/todos/3/a~1b~0c");
+        assert_eq!("".parse::<Path>().unwrap(), Path::root());
+        for key in ["03", "+3", "1e3"] {
+            let path: Path = format!("/{}", key).parse().unwrap();
+            assert_eq!(Vec::<Prop>::from(path), vec![Prop::Map(key.into())]);
+        }
+        assert_eq!(
+            "/".parse::<Path>().unwrap(),
+            Path::from(vec![Prop::Map("".into())])
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            "todos".parse::<Path>(),
+            Err(ParsePathError::MissingLeadingSlash)
+        );
+        assert_eq!(
+            "/a~2".parse::<Path>(),
+            Err(ParsePathError::InvalidEscape("a~2".into()))
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::columnar::column_range::encoded_value_len;
+use crate::types::{Key, ObjId, ObjType, OpType};
+use crate::{Automerge, Path, Prop};
+
+/// The number of operations and bytes of values in one part of a document, returned by
+/// [`Automerge::size_by_path()`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathStats {
+    pub path: Path,
+    /// The number of operations in this part of the document, including operations which have
+    /// since been overwritten or deleted
+    pub ops: usize,
+    /// The number of bytes the values of the operations take up when encoded
+    pub bytes: usize,
+}
+
+/// Where the ops of an object are counted
+struct Bucket {
+    path: Path,
+    /// Whether ops in the object are counted against the paths of their keys rather than the
+    /// path of the object. This is false for objects which are not visible as they have no path
+    /// and for text, where a path per character is not useful.
+    descend: bool,
+}
+
+impl Automerge {
+    /// The number of operations and bytes of values in each subtree of the document down to
+    /// `depth`, ordered by path
+    ///
+    /// Every operation in the document is counted against the path of the key it was made at,
+    /// truncated to `depth` props, so with a depth of 1 there is one entry for each key in the
+    /// root of the document with everything below that key counted against it. Operations in
+    /// objects which have been deleted are counted against the key the deleted object was at, and
+    /// all the operations in a text object are counted against the text object.
+    pub fn size_by_path(&self, depth: usize) -> Vec<PathStats> {
+        let mut totals = BTreeMap::<Vec<Prop>, (usize, usize)>::new();
+        let mut buckets = HashMap::<ObjId, Bucket>::new();
+        buckets.insert(
+            ObjId::root(),
+            Bucket {
+                path: Path::root(),
+                descend: true,
+            },
+        );
+
+        // objects are iterated in causal order so we have always seen the op which created an
+        // object before we get to its ops
+        for (obj, ops) in self.ops().iter_objs() {
+            let Some(Bucket { path, descend }) = buckets.remove(&obj.id) else {
+                continue;
+            };
+            let descend = descend && obj.typ != ObjType::Text && path.len() < depth;
+            let mut index = 0;
+            let mut last_elem: Option<(Key, bool)> = None;
+            for op_idx in ops {
+                let op = op_idx.as_op(self.osd());
+                let key = op.elemid_or_key();
+                let visible = op.visible();
+                let prop = match key {
+                    Key::Map(prop) => Some(Prop::Map(self.osd().props.get(prop).clone())),
+                    Key::Seq(_) => {
+                        match last_elem {
+                            Some((last, was_visible)) if last != key => {
+                                index += usize::from(was_visible);
+                                last_elem = Some((key, visible));
+                            }
+                            Some((_, ref mut was_visible)) => *was_visible |= visible,
+                            None => last_elem = Some((key, visible)),
+                        }
+                        visible.then_some(Prop::Seq(index))
+                    }
+                };
+                let (op_path, op_descends) = match prop {
+                    Some(prop) if descend => (path.join(prop), visible),
+                    _ => (path.clone(), false),
+                };
+
+                if let OpType::Make(_) = op.action() {
+                    buckets.insert(
+                        ObjId(*op.id()),
+                        Bucket {
+                            path: op_path.clone(),
+                            descend: op_descends,
+                        },
+                    );
+                }
+                let bytes = match op.action() {
+                    OpType::Put(value) => encoded_value_len(value),
+                    OpType::MarkBegin(_, data) => data.name.len() + encoded_value_len(&data.value),
+                    _ => 0,
+                };
+                let total = totals.entry(op_path.iter().cloned().collect()).or_default();
+                total.0 += 1;
+                total.1 += bytes;
+            }
+        }
+
+        totals
+            .into_iter()
+            .map(|(props, (ops, bytes))| PathStats {
+                path: props.into_iter().collect(),
+                ops,
+                bytes,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{transaction::Transactable, AutoCommit, ObjType, Path, ReadDoc, ROOT};
+
+    #[test]
+    fn ops_are_counted_against_their_subtree() {
+        let mut doc = AutoCommit::new();
+        doc.put(&ROOT, "title", "notes").unwrap();
+        let items = doc.put_object(&ROOT, "items", ObjType::List).unwrap();
+        let first = doc.insert_object(&items, 0, ObjType::Map).unwrap();
+        doc.put(&first, "text", "abc").unwrap();
+        doc.insert(&items, 1, "second").unwrap();
+        let body = doc.put_object(&ROOT, "body", ObjType::Text).unwrap();
+        doc.splice_text(&body, 0, 0, "hello").unwrap();
+        let old = doc.put_object(&ROOT, "old", ObjType::Map).unwrap();
+        doc.put(&old, "x", 1).unwrap();
+        doc.delete(&ROOT, "old").unwrap();
+
+        let stats = doc
+            .size_by_path(1)
+            .into_iter()
+            .map(|s| (s.path.to_string(), s.ops, s.bytes))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            stats,
+            [
+                ("/body".to_string(), 6, 5),
+                ("/items".to_string(), 4, 9),
+                ("/old".to_string(), 2, 1),
+                ("/title".to_string(), 1, 5),
+            ]
+        );
+
+        let stats = doc.size_by_path(2);
+        let items = stats
+            .iter()
+            .filter(|s| s.path.iter().next() == Some(&"items".into()))
+            .map(|s| (s.path.to_string(), s.ops))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            items,
+            [
+                ("/items".to_string(), 1),
+                ("/items/0".to_string(), 2),
+                ("/items/1".to_string(), 1)
+            ]
+        );
+        assert_eq!(doc.size_by_path(0)[0].path, Path::root());
+        assert_eq!(doc.size_by_path(0)[0].ops, doc.stats().num_ops as usize);
+    }
+}
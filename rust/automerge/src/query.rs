@@ -1,22 +1,27 @@
 use crate::marks::MarkData;
 use crate::op_set::Op;
 use crate::op_tree::{OpSetData, OpTree, OpTreeNode};
+use crate::patches::TextEncoding;
 use crate::types::{Key, ListEncoding, OpId, OpType};
 use fxhash::FxBuildHasher;
 use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
 
+mod display_width;
 mod insert;
 mod list_state;
 mod nth;
 mod opid;
 mod seek_mark;
+mod text_index;
 
+pub(crate) use display_width::DisplayWidth;
 pub(crate) use insert::InsertNth;
 pub(crate) use list_state::{ListState, RichTextQueryState};
 pub(crate) use nth::Nth;
 pub(crate) use opid::{OpIdSearch, SimpleOpIdSearch};
 pub(crate) use seek_mark::SeekMark;
+pub(crate) use text_index::ConvertTextIndex;
 
 // use a struct for the args for clarity as they are passed up the update chain in the optree
 #[derive(Debug, Clone)]
@@ -52,14 +57,38 @@ pub(crate) enum QueryResult {
     Finish,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Default)]
 struct TextWidth {
     width: usize,
+    /// The widths of the visible text in each [`TextEncoding`], so that indices can be converted
+    /// between encodings without visiting every character
+    utf8: usize,
+    utf16: usize,
+    code_points: usize,
+    /// The display width of the visible text, only maintained with the `display-width-index`
+    /// feature as it costs a unicode width lookup for every op inserted into the tree
+    #[cfg(feature = "display-width-index")]
+    display: usize,
 }
 
 impl TextWidth {
+    fn in_encoding(&self, encoding: TextEncoding) -> usize {
+        match encoding {
+            TextEncoding::Utf8 => self.utf8,
+            TextEncoding::Utf16 => self.utf16,
+            TextEncoding::CodePoint => self.code_points,
+        }
+    }
+
     fn add_op(&mut self, op: Op<'_>) {
         self.width += op.width(ListEncoding::Text);
+        self.utf8 += op.text_width(TextEncoding::Utf8);
+        self.utf16 += op.text_width(TextEncoding::Utf16);
+        self.code_points += op.text_width(TextEncoding::CodePoint);
+        #[cfg(feature = "display-width-index")]
+        {
+            self.display += op.display_width();
+        }
     }
 
     fn remove_op(&mut self, op: Op<'_>) {
@@ -91,10 +120,28 @@ impl TextWidth {
         // Really this is a sign that we should be tracking the type of the Index (List or Text) at
         // the type level, but for now we just look the other way.
         self.width = self.width.saturating_sub(op.width(ListEncoding::Text));
+        self.utf8 = self.utf8.saturating_sub(op.text_width(TextEncoding::Utf8));
+        self.utf16 = self
+            .utf16
+            .saturating_sub(op.text_width(TextEncoding::Utf16));
+        self.code_points = self
+            .code_points
+            .saturating_sub(op.text_width(TextEncoding::CodePoint));
+        #[cfg(feature = "display-width-index")]
+        {
+            self.display = self.display.saturating_sub(op.display_width());
+        }
     }
 
     fn merge(&mut self, other: &TextWidth) {
         self.width += other.width;
+        self.utf8 += other.utf8;
+        self.utf16 += other.utf16;
+        self.code_points += other.code_points;
+        #[cfg(feature = "display-width-index")]
+        {
+            self.display += other.display;
+        }
     }
 }
 
@@ -120,7 +167,7 @@ impl Index {
     pub(crate) fn new() -> Self {
         Index {
             visible: Default::default(),
-            visible_text: TextWidth { width: 0 },
+            visible_text: TextWidth::default(),
             ops: Default::default(),
             never_seen_puts: true,
             mark_begin: Default::default(),
@@ -137,6 +184,23 @@ impl Index {
         }
     }
 
+    /// Get the width of the visible text in this index in `encoding`
+    pub(crate) fn text_width(&self, encoding: TextEncoding) -> usize {
+        self.visible_text.in_encoding(encoding)
+    }
+
+    /// Get the display width of the visible text in this index, if it is being maintained
+    pub(crate) fn display_width(&self) -> Option<usize> {
+        #[cfg(feature = "display-width-index")]
+        {
+            Some(self.visible_text.display)
+        }
+        #[cfg(not(feature = "display-width-index"))]
+        {
+            None
+        }
+    }
+
     pub(crate) fn has_visible(&self, seen: &Key) -> bool {
         self.visible.contains_key(seen)
     }
@@ -0,0 +1,90 @@
+use crate::op_set::Op;
+use crate::op_tree::OpTreeNode;
+use crate::query::{Index, ListState, OpSetData, QueryResult, TreeQuery};
+use crate::types::{Clock, Key, ListEncoding};
+use std::fmt::Debug;
+
+/// The DisplayWidth query sums the display widths of the visible characters whose indexes fall
+/// in `start..end`. Nodes which lie entirely inside the range are skipped using the display
+/// width in their index when that is being maintained.
+#[derive(Debug, Clone)]
+pub(crate) struct DisplayWidth {
+    list_state: ListState,
+    clock: Option<Clock>,
+    start: usize,
+    end: usize,
+    last_key: Option<Key>,
+    last_width: usize,
+    pub(crate) width: usize,
+}
+
+impl DisplayWidth {
+    pub(crate) fn new(start: usize, end: usize, clock: Option<Clock>) -> Self {
+        DisplayWidth {
+            list_state: ListState::new(ListEncoding::Text, usize::MAX),
+            clock,
+            start,
+            end,
+            last_key: None,
+            last_width: 0,
+            width: 0,
+        }
+    }
+}
+
+impl<'a> TreeQuery<'a> for DisplayWidth {
+    fn query_node(
+        &mut self,
+        child: &'a OpTreeNode,
+        index: &'a Index,
+        osd: &OpSetData,
+    ) -> QueryResult {
+        self.list_state.check_if_node_is_clean(index);
+        if self.list_state.index() >= self.end {
+            return QueryResult::Finish;
+        }
+        if self.clock.is_some() {
+            return QueryResult::Descend;
+        }
+        let Some(display) = index.display_width() else {
+            return QueryResult::Descend;
+        };
+        let start = self.list_state.index();
+        let end = start + index.visible_len(ListEncoding::Text);
+        if start < self.start || end > self.end {
+            return QueryResult::Descend;
+        }
+        // process_node only skips the node if it is clean, otherwise conflicting puts may mean
+        // the index doesn't describe the visible text
+        let result = self.list_state.process_node(child, index, osd, None);
+        if result == QueryResult::Next {
+            self.width += display;
+            self.last_key = None;
+        }
+        result
+    }
+
+    fn query_element(&mut self, op: Op<'a>) -> QueryResult {
+        if op.insert() && self.list_state.index() >= self.end {
+            return QueryResult::Finish;
+        }
+        let visible = op.visible_at(self.clock.as_ref());
+        let key = op.elemid_or_key();
+        self.list_state.process_op(op, key, visible);
+        if visible {
+            let index = self.list_state.last_index();
+            if index >= self.start && index < self.end {
+                let width = op.display_width();
+                if self.last_key == Some(key) {
+                    // a conflicting value for the character we've just counted, the last one wins
+                    self.width = self.width - self.last_width + width;
+                } else {
+                    self.width += width;
+                }
+                self.last_key = Some(key);
+                self.last_width = width;
+            }
+        }
+        QueryResult::Next
+    }
+}
@@ -0,0 +1,94 @@
+use crate::op_set::Op;
+use crate::op_tree::OpTreeNode;
+use crate::patches::TextEncoding;
+use crate::query::{Index, ListState, OpSetData, QueryResult, TreeQuery};
+use crate::types::{Clock, Key, ListEncoding};
+use std::fmt::Debug;
+
+/// The ConvertTextIndex query measures the visible text before `index` in `from` in the encoding
+/// `to`. Nodes which end before `index` are skipped using the widths in each encoding in their
+/// index.
+#[derive(Debug, Clone)]
+pub(crate) struct ConvertTextIndex {
+    list_state: ListState,
+    clock: Option<Clock>,
+    index: usize,
+    from: TextEncoding,
+    to: TextEncoding,
+    last_key: Option<Key>,
+    last_width: (usize, usize),
+    /// The width in `from` of the text which has been measured, which is `index` unless `index`
+    /// is in the middle of a character or past the end of the text
+    pub(crate) from_width: usize,
+    /// The width in `to` of the text which has been measured
+    pub(crate) to_width: usize,
+}
+
+impl ConvertTextIndex {
+    pub(crate) fn new(
+        index: usize,
+        from: TextEncoding,
+        to: TextEncoding,
+        clock: Option<Clock>,
+    ) -> Self {
+        ConvertTextIndex {
+            list_state: ListState::new(ListEncoding::Text, usize::MAX),
+            clock,
+            index,
+            from,
+            to,
+            last_key: None,
+            last_width: (0, 0),
+            from_width: 0,
+            to_width: 0,
+        }
+    }
+}
+
+impl<'a> TreeQuery<'a> for ConvertTextIndex {
+    fn query_node(
+        &mut self,
+        child: &'a OpTreeNode,
+        index: &'a Index,
+        osd: &OpSetData,
+    ) -> QueryResult {
+        self.list_state.check_if_node_is_clean(index);
+        // once `index` is reached the node is descended into even so, as it may start with a
+        // conflicting value for the last character measured
+        if self.clock.is_some() || self.from_width + index.text_width(self.from) >= self.index {
+            return QueryResult::Descend;
+        }
+        // process_node only skips the node if it is clean, otherwise conflicting puts may mean
+        // the index doesn't describe the visible text
+        let result = self.list_state.process_node(child, index, osd, None);
+        if result == QueryResult::Next {
+            self.from_width += index.text_width(self.from);
+            self.to_width += index.text_width(self.to);
+            self.last_key = None;
+        }
+        result
+    }
+
+    fn query_element(&mut self, op: Op<'a>) -> QueryResult {
+        if op.insert() && self.from_width >= self.index {
+            return QueryResult::Finish;
+        }
+        let visible = op.visible_at(self.clock.as_ref());
+        let key = op.elemid_or_key();
+        self.list_state.process_op(op, key, visible);
+        if visible {
+            let width = (op.text_width(self.from), op.text_width(self.to));
+            if self.last_key == Some(key) {
+                // a conflicting value for the character we've just measured, the last one wins
+                self.from_width = self.from_width - self.last_width.0 + width.0;
+                self.to_width = self.to_width - self.last_width.1 + width.1;
+            } else {
+                self.from_width += width.0;
+                self.to_width += width.1;
+            }
+            self.last_key = Some(key);
+            self.last_width = width;
+        }
+        QueryResult::Next
+    }
+}
@@ -1,15 +1,21 @@
 use crate::{
+    annotation,
     error::AutomergeError,
     exid::ExId,
     hydrate,
     iter::Spans,
     iter::{Keys, ListRange, MapRange, Values},
-    marks::{Mark, MarkSet},
+    lines,
+    marks::{self, Mark, MarkChange, MarkSet},
     parents::Parents,
-    Change, ChangeHash, Cursor, ObjType, Prop, Value,
+    path::{self, Path},
+    structural, text_navigation, Affinity, Change, ChangeHash, Cursor, ObjType, Prop, Value,
 };
 
-use std::{collections::HashMap, ops::RangeBounds};
+use std::{
+    collections::HashMap,
+    ops::{Range, RangeBounds},
+};
 
 /// Methods for reading values from an automerge document
 ///
@@ -130,6 +136,22 @@ pub trait ReadDoc {
     /// See [`Self::length()`]
     fn length_at<O: AsRef<ExId>>(&self, obj: O, heads: &[ChangeHash]) -> usize;
 
+    /// Get the number of lines in the lines object `obj`, see [`crate::transaction::Transactable::insert_line()`]
+    fn line_count<O: AsRef<ExId>>(&self, obj: O) -> Result<usize, AutomergeError> {
+        lines::line_count(self, obj.as_ref())
+    }
+
+    /// Get the lines of the lines object `obj` whose line numbers are in `range`
+    ///
+    /// Returns an error if `obj` is not a list or any of the lines is not a string.
+    fn lines_range<O: AsRef<ExId>, R: RangeBounds<usize>>(
+        &self,
+        obj: O,
+        range: R,
+    ) -> Result<Vec<String>, AutomergeError> {
+        lines::lines_range(self, obj.as_ref(), range)
+    }
+
     /// Get the type of this object, if it is an object.
     fn object_type<O: AsRef<ExId>>(&self, obj: O) -> Result<ObjType, AutomergeError>;
 
@@ -143,6 +165,25 @@ pub trait ReadDoc {
         heads: &[ChangeHash],
     ) -> Result<Vec<Mark<'_>>, AutomergeError>;
 
+    /// Get the ranges of the sequence `obj` whose marks differ between `from` and `to`
+    ///
+    /// The ranges are indices into the sequence as at `to`. Only elements which exist in both
+    /// versions are compared, so inserting text is not reported as adding marks to it.
+    fn mark_diff<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        from: &[ChangeHash],
+        to: &[ChangeHash],
+    ) -> Result<Vec<MarkChange>, AutomergeError> {
+        marks::mark_diff(self, obj.as_ref(), from, to)
+    }
+
+    /// Get the names and values of the marks covering the character at `index` of a sequence
+    ///
+    /// If `heads` is `Some` the marks are read as at those heads. This only builds the marks for
+    /// one character rather than the full list of marks as [`Self::marks()`] does, but it still
+    /// has to pass every node of the op tree before `index` to find the marks which start there,
+    /// so it costs more the further into the sequence `index` is.
     fn get_marks<O: AsRef<ExId>>(
         &self,
         obj: O,
@@ -153,6 +194,31 @@ pub trait ReadDoc {
     /// Get the string represented by the given text object.
     fn text<O: AsRef<ExId>>(&self, obj: O) -> Result<String, AutomergeError>;
 
+    /// Get the range of the run of word characters, whitespace or punctuation in the text object
+    /// `obj` which contains the character at `index`
+    ///
+    /// The start and end of the range are where ctrl-left and ctrl-right should move a caret at
+    /// `index`. An `index` at the end of the text gives an empty range there.
+    fn text_word_boundaries<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        index: usize,
+    ) -> Result<Range<usize>, AutomergeError> {
+        text_navigation::word_boundaries(self, obj.as_ref(), index)
+    }
+
+    /// Get the range of the line in the text object `obj` which contains `index`
+    ///
+    /// Lines are ended by newlines and block markers, neither of which is part of the range. This
+    /// is where home and end should move a caret at `index`.
+    fn line_range<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        index: usize,
+    ) -> Result<Range<usize>, AutomergeError> {
+        text_navigation::line_range(self, obj.as_ref(), index)
+    }
+
     /// Get the string represented by the given text object as at `heads`, see
     /// [`Self::text()`]
     fn text_at<O: AsRef<ExId>>(
@@ -162,6 +228,11 @@ pub trait ReadDoc {
     ) -> Result<String, AutomergeError>;
 
     /// Return the sequence of text and block markers in the text object `obj`
+    ///
+    /// The text is split into runs over which the same set of marks is active, each run is a
+    /// [`crate::iter::Span::Text`] with the text of the run and the marks over it, and adjacent
+    /// runs always have different marks. This is usually more convenient for rendering rich
+    /// text than combining [`Self::text()`] with [`Self::marks()`].
     fn spans<O: AsRef<ExId>>(&self, obj: O) -> Result<Spans<'_>, AutomergeError>;
 
     /// Return the sequence of text and block markers in the text object `obj` as at `heads`
@@ -185,6 +256,30 @@ pub trait ReadDoc {
         at: Option<&[ChangeHash]>,
     ) -> Result<Cursor, AutomergeError>;
 
+    /// Like [`Self::get_cursor()`] but with the [`Affinity`] of the cursor set to `affinity`
+    ///
+    /// A cursor with [`Affinity::BeforeInsert`] records the element before `position` as well,
+    /// which it needs to stay before elements inserted concurrently at its position.
+    fn get_cursor_with_affinity<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        position: usize,
+        at: Option<&[ChangeHash]>,
+        affinity: Affinity,
+    ) -> Result<Cursor, AutomergeError> {
+        let cursor = self.get_cursor(obj.as_ref(), position, at)?;
+        match affinity {
+            Affinity::AfterInsert => Ok(cursor),
+            Affinity::BeforeInsert => {
+                let before = match position.checked_sub(1) {
+                    Some(prev) => Some(self.get_cursor(obj.as_ref(), prev, at)?),
+                    None => None,
+                };
+                Ok(cursor.with_before(before).with_affinity(affinity))
+            }
+        }
+    }
+
     /// Translate Cursor in a Sequence into an absolute position of type [`usize`].
     ///
     /// Applicable only for Sequences (either [`ObjType::List`] or [`ObjType::Text`]).
@@ -218,6 +313,55 @@ pub trait ReadDoc {
         prop: P,
     ) -> Result<Option<(Value<'_>, ExId)>, AutomergeError>;
 
+    /// Get the value at `path`, see [`Path`] for details of how paths are resolved
+    ///
+    /// This is equivalent to looking up each object along `path` with [`Self::get()`] and then
+    /// calling [`Self::get()`] on the last object.
+    ///
+    /// ### Errors
+    ///
+    /// Returns [`AutomergeError::InvalidPath`] if `path` is the root path or if any of the
+    /// segments of `path` before the last one do not refer to an object.
+    fn get_at_path(&self, path: &Path) -> Result<Option<(Value<'_>, ExId)>, AutomergeError> {
+        let (obj, prop) = path::resolve_path(self, path)?;
+        self.get(obj, prop)
+    }
+
+    /// Whether the objects `a` and `b` have the same contents
+    ///
+    /// Maps are equal if they have the same keys with equal values, lists if they have equal
+    /// values in the same order and text objects if they have the same text. Nested objects are
+    /// compared recursively without hydrating them, and the comparison stops at the first
+    /// difference. Only the current values are compared, not conflicts, marks or history.
+    fn deep_equals<A: AsRef<ExId>, B: AsRef<ExId>>(
+        &self,
+        a: A,
+        b: B,
+    ) -> Result<bool, AutomergeError> {
+        structural::deep_equals(self, a.as_ref(), b.as_ref())
+    }
+
+    /// The paths, relative to `a` and `b`, at which the objects differ, see [`Self::deep_equals()`]
+    ///
+    /// A key or index which is only present in one of the objects is reported as a difference
+    /// at that key or index. Text objects and objects of different types are reported as a
+    /// single difference at the path of the object.
+    fn structural_diff<A: AsRef<ExId>, B: AsRef<ExId>>(
+        &self,
+        a: A,
+        b: B,
+    ) -> Result<Vec<Path>, AutomergeError> {
+        structural::structural_diff(self, a.as_ref(), b.as_ref())
+    }
+
+    /// The annotations in the text object `obj`, see [`crate::annotation`]
+    fn annotations<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+    ) -> Result<Vec<annotation::Annotation>, AutomergeError> {
+        annotation::annotations(self, obj.as_ref())
+    }
+
     /// Get the value of the given key as at `heads`, see [`Self::get()`]
     fn get_at<O: AsRef<ExId>, P: Into<Prop>>(
         &self,
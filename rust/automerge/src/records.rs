@@ -0,0 +1,146 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::exid::ExId;
+use crate::types::{Clock, Key, ObjId};
+use crate::{hydrate, Automerge, AutomergeError, ChangeHash, ObjType, Value};
+
+/// A row level change to a list between two sets of heads, returned by
+/// [`Automerge::diff_records()`]
+///
+/// Rows are identified by the ID of the list element they are in rather than by index, so a row
+/// which has moved because rows were inserted or removed before it is not reported as changed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecordDiff {
+    /// A row was inserted
+    Added {
+        /// The ID of the list element the row is in
+        id: ExId,
+        /// The index of the row in the list as at the `after` heads
+        index: usize,
+        value: hydrate::Value,
+    },
+    /// A row was removed
+    Removed {
+        /// The ID of the list element the row was in
+        id: ExId,
+        /// The index of the row in the list as at the `before` heads
+        index: usize,
+        value: hydrate::Value,
+    },
+    /// The value of a row changed
+    Modified {
+        /// The ID of the list element the row is in
+        id: ExId,
+        /// The index of the row in the list as at the `after` heads
+        index: usize,
+        before: hydrate::Value,
+        after: hydrate::Value,
+        /// The fields which changed if the row is a map both before and after, otherwise empty
+        fields: Vec<FieldDiff>,
+    },
+}
+
+/// A change to one field of a row in a [`RecordDiff::Modified`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldDiff {
+    pub key: String,
+    /// The value of the field before the change, `None` if the field was added
+    pub before: Option<hydrate::Value>,
+    /// The value of the field after the change, `None` if the field was removed
+    pub after: Option<hydrate::Value>,
+}
+
+impl Automerge {
+    /// Compare the rows of the list `obj` as at `before` and `after`
+    ///
+    /// This is intended for lists of maps which are displayed as tables, where index based
+    /// patches from [`Self::diff()`] are hard to turn into row level updates. Removed rows are
+    /// returned first in the order they were in at `before`, followed by added and modified rows
+    /// in the order they are in at `after`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AutomergeError::InvalidOp`] if `obj` is not a list
+    pub fn diff_records(
+        &self,
+        obj: &ExId,
+        before: &[ChangeHash],
+        after: &[ChangeHash],
+    ) -> Result<Vec<RecordDiff>, AutomergeError> {
+        let obj = self.exid_to_obj(obj)?;
+        if obj.typ != ObjType::List {
+            return Err(AutomergeError::InvalidOp(obj.typ));
+        }
+        let before = self.records_at(&obj.id, self.clock_at(before));
+        let after = self.records_at(&obj.id, self.clock_at(after));
+        let after_ids = after.iter().map(|(id, _)| id).collect::<BTreeSet<_>>();
+
+        let mut diffs = before
+            .iter()
+            .enumerate()
+            .filter(|(_, (id, _))| !after_ids.contains(id))
+            .map(|(index, (id, value))| RecordDiff::Removed {
+                id: id.clone(),
+                index,
+                value: value.clone(),
+            })
+            .collect::<Vec<_>>();
+        let before = before.into_iter().collect::<HashMap<_, _>>();
+        for (index, (id, after)) in after.into_iter().enumerate() {
+            match before.get(&id) {
+                None => diffs.push(RecordDiff::Added {
+                    id,
+                    index,
+                    value: after,
+                }),
+                Some(before) if *before != after => diffs.push(RecordDiff::Modified {
+                    id,
+                    index,
+                    fields: diff_fields(before, &after),
+                    before: before.clone(),
+                    after,
+                }),
+                Some(_) => {}
+            }
+        }
+        Ok(diffs)
+    }
+
+    fn records_at(&self, obj: &ObjId, clock: Clock) -> Vec<(ExId, hydrate::Value)> {
+        self.ops()
+            .top_ops(obj, Some(clock.clone()))
+            .filter_map(|top| {
+                let Key::Seq(elem) = top.op.elemid_or_key() else {
+                    return None;
+                };
+                let value = match top.op.value_at(Some(&clock)) {
+                    Value::Object(_) => self.hydrate_op(top.op, Some(&clock)),
+                    Value::Scalar(s) => hydrate::Value::Scalar(s.into_owned()),
+                };
+                Some((self.ops().id_to_exid(elem.0), value))
+            })
+            .collect()
+    }
+}
+
+fn diff_fields(before: &hydrate::Value, after: &hydrate::Value) -> Vec<FieldDiff> {
+    let (hydrate::Value::Map(before), hydrate::Value::Map(after)) = (before, after) else {
+        return Vec::new();
+    };
+    let keys = before
+        .iter()
+        .chain(after.iter())
+        .map(|(key, _)| key)
+        .collect::<BTreeSet<_>>();
+    keys.into_iter()
+        .filter_map(|key| {
+            let before = before.get(key).cloned();
+            let after = after.get(key).cloned();
+            (before != after).then(|| FieldDiff {
+                key: key.clone(),
+                before,
+                after,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,179 @@
+//! Objects which have been made read-only
+//!
+//! [`Transactable::seal_object()`] freezes an object at the point it is called. After that any
+//! local change to the object fails with [`AutomergeError::ObjectSealed`], and changes to the
+//! object made by other peers which did not know about the seal when they made them are ignored
+//! when reading the object, so every peer ends up with the object as it was when it was sealed.
+//! Sealing an object does not seal the objects nested in it.
+//!
+//! A seal is an op with the action [`OpType::Seal`] at the key of the object in its parent, whose
+//! predecessor is the op which created the object. Like an increment of a counter it doesn't
+//! overwrite its predecessor, so the object stays where it is. The op records the heads the seal
+//! was made on, which together with the ID of the op gives the set of ops which are visible in
+//! the sealed object. Documents containing seals can't be loaded by versions of automerge which
+//! don't know about them.
+//!
+//! Patches follow the same rule, so when a seal arrives after changes to the sealed object which
+//! were made concurrently with it the values those changes set are removed again.
+#[cfg(doc)]
+use crate::{transaction::Transactable, AutomergeError, OpType};
+
+use crate::clock::Clock;
+use crate::op_set::OpIdx;
+use crate::types::{ObjId, OpId, HASH_SIZE};
+use crate::{ChangeHash, ScalarValue};
+use fxhash::FxBuildHasher;
+use std::collections::HashMap;
+
+/// The seals in an op set
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Seals {
+    by_obj: HashMap<ObjId, Vec<Seal>, FxBuildHasher>,
+    /// Seal ops which have been added to the op set but not to `by_obj` yet, as working out the
+    /// ops they cover needs the change graph
+    unclocked: Vec<OpIdx>,
+}
+
+#[derive(Debug, Clone)]
+struct Seal {
+    id: OpId,
+    /// The ops which were made before the seal
+    clock: Clock,
+}
+
+impl Seals {
+    pub(crate) fn added(&mut self, idx: OpIdx) {
+        self.unclocked.push(idx);
+    }
+
+    /// The seal ops added since this was last called
+    pub(crate) fn take_unclocked(&mut self) -> Vec<OpIdx> {
+        std::mem::take(&mut self.unclocked)
+    }
+
+    /// Record that the seal `id` on `obj` covers the ops in `clock`
+    pub(crate) fn insert(&mut self, obj: ObjId, id: OpId, clock: Clock) {
+        self.by_obj.entry(obj).or_default().push(Seal { id, clock });
+    }
+
+    pub(crate) fn removed(&mut self, idx: OpIdx, id: &OpId) {
+        self.unclocked.retain(|i| *i != idx);
+        for seals in self.by_obj.values_mut() {
+            seals.retain(|seal| seal.id != *id);
+        }
+        self.by_obj.retain(|_, seals| !seals.is_empty());
+    }
+
+    /// The ops which are visible in `obj` as at `clock`, which is `clock` limited to the ops
+    /// which were made before `obj` was sealed, if it has been
+    ///
+    /// If the object was sealed concurrently by several peers the ops which were made before any
+    /// of the seals are visible.
+    pub(crate) fn clock(&self, obj: &ObjId, clock: Option<Clock>) -> Option<Clock> {
+        let Some(seals) = self.by_obj.get(obj) else {
+            return clock;
+        };
+        let mut sealed: Option<Clock> = None;
+        for seal in seals
+            .iter()
+            .filter(|seal| clock.as_ref().map_or(true, |c| c.covers(&seal.id)))
+        {
+            sealed = Some(match sealed {
+                Some(c) => Clock::merge(&c, &seal.clock),
+                None => seal.clock.clone(),
+            });
+        }
+        match (sealed, clock) {
+            (Some(sealed), Some(clock)) => Some(Clock::intersect(&clock, &sealed)),
+            (sealed, clock) => sealed.or(clock),
+        }
+    }
+
+    /// Like [`Self::clock()`] but `None` if `obj` has never been sealed, which saves copying
+    /// `clock` for the objects which haven't
+    pub(crate) fn sealed_clock(&self, obj: &ObjId, clock: &Clock) -> Option<Clock> {
+        if self.by_obj.contains_key(obj) {
+            self.clock(obj, Some(clock.clone()))
+        } else {
+            None
+        }
+    }
+
+    /// Whether `obj` has been sealed as at `clock`
+    pub(crate) fn is_sealed(&self, obj: &ObjId, clock: Option<&Clock>) -> bool {
+        self.by_obj.get(obj).is_some_and(|seals| {
+            seals
+                .iter()
+                .any(|seal| clock.map_or(true, |c| c.covers(&seal.id)))
+        })
+    }
+
+    /// Whether the op `id` in `obj` is hidden by a seal on `obj`
+    pub(crate) fn hides(&self, obj: &ObjId, id: &OpId) -> bool {
+        self.by_obj
+            .get(obj)
+            .is_some_and(|seals| seals.iter().all(|seal| !seal.clock.covers(id)))
+    }
+}
+
+/// The value a seal made on `heads` is stored with
+pub(crate) fn heads_to_value(heads: &[ChangeHash]) -> ScalarValue {
+    ScalarValue::Bytes(heads.iter().flat_map(|h| h.0).collect())
+}
+
+/// The heads a seal stored as `bytes` was made on
+pub(crate) fn heads_from_bytes(bytes: &[u8]) -> Vec<ChangeHash> {
+    bytes
+        .chunks_exact(HASH_SIZE)
+        .filter_map(|h| ChangeHash::try_from(h).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{transaction::Transactable, AutoCommit, AutomergeError, ObjType, ReadDoc, ROOT};
+
+    #[test]
+    fn sealed_objects_ignore_concurrent_changes() {
+        let mut doc = AutoCommit::new();
+        let settings = doc.put_object(&ROOT, "settings", ObjType::Map).unwrap();
+        doc.put(&settings, "theme", "dark").unwrap();
+        let mut other = doc.fork();
+
+        doc.seal_object(&settings).unwrap();
+        assert!(doc.is_sealed(&settings).unwrap());
+        assert!(matches!(
+            doc.put(&settings, "theme", "light"),
+            Err(AutomergeError::ObjectSealed(_))
+        ));
+        assert!(doc.seal_object(&ROOT).is_err());
+
+        other.put(&settings, "theme", "light").unwrap();
+        other.put(&settings, "font", "serif").unwrap();
+        let mut merged = other.fork();
+        merged.merge(&mut doc).unwrap();
+        doc.merge(&mut other).unwrap();
+
+        for doc in [&doc, &merged] {
+            assert_eq!(doc.keys(&settings).collect::<Vec<_>>(), ["theme"]);
+            let (theme, _) = doc.get(&settings, "theme").unwrap().unwrap();
+            assert_eq!(theme.to_str(), Some("dark"));
+        }
+        // the rest of the document is unaffected
+        other.put(&ROOT, "title", "notes").unwrap();
+        doc.merge(&mut other).unwrap();
+        assert!(doc.get(&ROOT, "title").unwrap().is_some());
+        assert_eq!(doc.keys(&ROOT).collect::<Vec<_>>(), ["settings", "title"]);
+
+        // the seal survives saving and loading
+        let mut loaded = AutoCommit::load(&doc.save()).unwrap();
+        assert!(loaded.is_sealed(&settings).unwrap());
+        assert_eq!(loaded.keys(&settings).collect::<Vec<_>>(), ["theme"]);
+        assert!(loaded.put(&settings, "theme", "light").is_err());
+
+        // peers which receive the seal can't change the object either
+        other.merge(&mut doc).unwrap();
+        assert!(other.is_sealed(&settings).unwrap());
+        assert!(other.delete(&settings, "theme").is_err());
+    }
+}
@@ -2,14 +2,28 @@ use std::ops::Range;
 
 pub(crate) mod change;
 mod chunk;
+pub(crate) mod chunked;
+mod codec;
 mod columns;
 pub(crate) mod convert;
 mod document;
+pub(crate) mod encrypted;
+mod inspect;
 pub(crate) mod load;
 pub(crate) mod parse;
+pub(crate) mod salvage;
 pub(crate) mod save;
+mod validate;
 
+pub use chunked::{BlobId, ChunkedSave};
+pub(crate) use codec::Codec;
+pub use codec::Compression;
+pub use inspect::{
+    inspect, ChunkKind, ChunkReport, ColumnReport, ColumnSection, InspectError, SaveReport,
+};
 pub use load::VerificationMode;
+pub use salvage::{SalvageReport, SkippedChunk};
+pub use validate::{validate, DocumentInfo, ValidateError};
 pub(crate) use {
     change::{AsChangeOp, Change, ChangeOp, Compressed, ReadChangeOpError},
     chunk::{CheckSum, Chunk, ChunkType, Header},
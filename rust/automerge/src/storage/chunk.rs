@@ -66,7 +66,7 @@ impl<'a> Chunk<'a> {
                 }
                 Chunk::Change(change)
             }
-            ChunkType::Document => {
+            ChunkType::Document | ChunkType::ZstdDocument => {
                 let (remaining, doc) =
                     Document::parse(chunk_input, header).map_err(|e| e.lift())?;
                 if !remaining.is_empty() {
@@ -115,6 +115,8 @@ pub(crate) enum ChunkType {
     Document,
     Change,
     Compressed,
+    /// A document whose compressed columns use zstd rather than DEFLATE
+    ZstdDocument,
 }
 
 impl TryFrom<u8> for ChunkType {
@@ -125,6 +127,8 @@ impl TryFrom<u8> for ChunkType {
             0 => Ok(Self::Document),
             1 => Ok(Self::Change),
             2 => Ok(Self::Compressed),
+            // 3 is the type of an encrypted chunk, see `super::encrypted`
+            4 => Ok(Self::ZstdDocument),
             other => Err(other),
         }
     }
@@ -136,6 +140,7 @@ impl From<ChunkType> for u8 {
             ChunkType::Document => 0,
             ChunkType::Change => 1,
             ChunkType::Compressed => 2,
+            ChunkType::ZstdDocument => 4,
         }
     }
 }
@@ -192,6 +197,23 @@ impl Header {
         }
     }
 
+    /// A header for a chunk whose data is `pieces` concatenated, for writing a chunk without
+    /// first copying its parts into one buffer
+    pub(crate) fn for_pieces(chunk_type: ChunkType, pieces: &[&[u8]]) -> Self {
+        let hash = hash_pieces(chunk_type, pieces);
+        let data_len = pieces.iter().map(|piece| piece.len()).sum::<usize>();
+        Self {
+            hash,
+            checksum: hash.checksum().into(),
+            data_len,
+            header_size: MAGIC_BYTES.len()
+                + 4 // checksum
+                + 1 // chunk type
+                + (ulebsize(data_len as u64) as usize),
+            chunk_type,
+        }
+    }
+
     /// Returns a header with the same checksum but with a different chunk type and data length.
     /// This is primarily useful when processing compressed chunks, where the checksum is actually
     /// derived from the uncompressed data.
@@ -274,6 +296,10 @@ impl Header {
         self.hash
     }
 
+    pub(crate) fn chunk_type(&self) -> ChunkType {
+        self.chunk_type
+    }
+
     pub(crate) fn checksum_valid(&self) -> bool {
         CheckSum(self.hash.checksum()) == self.checksum
     }
@@ -284,10 +310,19 @@ impl Header {
 }
 
 fn hash(typ: ChunkType, data: &[u8]) -> ChangeHash {
-    let mut out = vec![u8::from(typ)];
-    leb128::write::unsigned(&mut out, data.len() as u64).unwrap();
-    out.extend(data);
-    let hash_result = Sha256::digest(out);
-    let array: [u8; 32] = hash_result.into();
+    hash_pieces(typ, &[data])
+}
+
+/// The hash of a chunk whose data is `pieces` concatenated
+fn hash_pieces(typ: ChunkType, pieces: &[&[u8]]) -> ChangeHash {
+    let data_len = pieces.iter().map(|piece| piece.len()).sum::<usize>();
+    let mut prefix = vec![u8::from(typ)];
+    leb128::write::unsigned(&mut prefix, data_len as u64).unwrap();
+    let mut hasher = Sha256::new();
+    hasher.update(prefix);
+    for piece in pieces {
+        hasher.update(piece);
+    }
+    let array: [u8; 32] = hasher.finalize().into();
     ChangeHash(array)
 }
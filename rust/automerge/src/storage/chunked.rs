@@ -0,0 +1,140 @@
+use sha2::{Digest, Sha256};
+
+use super::{chunk::error, load, parse, MAGIC_BYTES};
+use crate::ChangeHash;
+
+/// The chunk type of a manifest, which loaders that don't know about chunked saves reject as an
+/// unknown chunk type
+const MANIFEST_CHUNK_TYPE: u8 = 5;
+
+/// The content address of a blob in a [`ChunkedSave`], the SHA-256 hash of its bytes
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BlobId([u8; 32]);
+
+impl BlobId {
+    pub(crate) fn of(blob: &[u8]) -> Self {
+        Self(Sha256::digest(blob).into())
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for BlobId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&hex::encode(self.0))
+    }
+}
+
+impl std::fmt::Debug for BlobId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("BlobId").field(&hex::encode(self.0)).finish()
+    }
+}
+
+/// A document split into blobs for storing in an object store, see
+/// [`crate::Automerge::save_chunked()`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkedSave {
+    /// Lists the blobs which make up the document, this is what is passed to
+    /// [`crate::Automerge::load_chunked()`]
+    pub manifest: Vec<u8>,
+    /// The blobs which make up the document, each stored under its ID, in the order they have to
+    /// be loaded in
+    pub blobs: Vec<(BlobId, Vec<u8>)>,
+}
+
+/// The contents of a manifest
+pub(crate) struct Manifest {
+    pub(crate) heads: Vec<ChangeHash>,
+    pub(crate) blobs: Vec<BlobId>,
+}
+
+/// Group the encoded changes in `changes`, which are in causal order, into blobs of at most
+/// `target_size` bytes, or of a single change if the change is larger than that
+///
+/// The same history always splits into the same blobs, and adding changes to it only changes the
+/// last blob and adds new ones, so saving a document again only has to store the new blobs.
+pub(crate) fn split<I: IntoIterator<Item = Vec<u8>>>(
+    changes: I,
+    target_size: usize,
+) -> Vec<(BlobId, Vec<u8>)> {
+    let mut blobs = Vec::new();
+    let mut blob = Vec::new();
+    for change in changes {
+        if !blob.is_empty() && blob.len() + change.len() > target_size {
+            blobs.push(std::mem::take(&mut blob));
+        }
+        blob.extend(change);
+    }
+    if !blob.is_empty() {
+        blobs.push(blob);
+    }
+    blobs
+        .into_iter()
+        .map(|blob| (BlobId::of(&blob), blob))
+        .collect()
+}
+
+impl Manifest {
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        leb128::write::unsigned(&mut data, self.heads.len() as u64).unwrap();
+        for head in &self.heads {
+            data.extend(head.as_bytes());
+        }
+        leb128::write::unsigned(&mut data, self.blobs.len() as u64).unwrap();
+        for blob in &self.blobs {
+            data.extend(blob.as_bytes());
+        }
+
+        let mut out = Vec::with_capacity(data.len() + 16);
+        out.extend(MAGIC_BYTES);
+        out.extend(&Sha256::digest(&data)[..4]);
+        out.push(MANIFEST_CHUNK_TYPE);
+        leb128::write::unsigned(&mut out, data.len() as u64).unwrap();
+        out.extend(data);
+        out
+    }
+
+    pub(crate) fn decode(input: &[u8]) -> Result<Self, load::Error> {
+        let (_, (checksum, data)) = Self::parse_header(parse::Input::new(input))
+            .map_err(|e| load::Error::Parse(Box::new(e)))?;
+        if Sha256::digest(data)[..4] != checksum {
+            return Err(load::Error::BadChecksum);
+        }
+        let (i, heads) =
+            parse::length_prefixed(parse::change_hash::<error::Header>)(parse::Input::new(data))
+                .map_err(|e| load::Error::Parse(Box::new(e)))?;
+        let (i, blobs) = parse::length_prefixed(parse::change_hash::<error::Header>)(i)
+            .map_err(|e| load::Error::Parse(Box::new(e)))?;
+        if !i.is_empty() {
+            return Err(load::Error::LeftoverData);
+        }
+        Ok(Self {
+            heads,
+            blobs: blobs.into_iter().map(|hash| BlobId(hash.0)).collect(),
+        })
+    }
+
+    /// The checksum and data of the manifest chunk at the start of `input`
+    fn parse_header(
+        input: parse::Input<'_>,
+    ) -> parse::ParseResult<'_, ([u8; 4], &[u8]), error::Header> {
+        let (i, magic) = parse::take4(input)?;
+        if magic != MAGIC_BYTES {
+            return Err(parse::ParseError::Error(error::Header::InvalidMagicBytes));
+        }
+        let (i, checksum) = parse::take4(i)?;
+        let (i, chunk_type) = parse::take1(i)?;
+        if chunk_type != MANIFEST_CHUNK_TYPE {
+            return Err(parse::ParseError::Error(error::Header::UnknownChunkType(
+                chunk_type,
+            )));
+        }
+        let (i, len) = parse::leb128_u64(i)?;
+        let (i, data) = parse::take_n(len as usize, i)?;
+        Ok((i, (checksum, data)))
+    }
+}
@@ -71,6 +71,12 @@ impl From<u32> for ColumnId {
     }
 }
 
+impl From<ColumnId> for u32 {
+    fn from(id: ColumnId) -> Self {
+        id.0
+    }
+}
+
 impl std::fmt::Debug for ColumnId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         self.0.fmt(f)
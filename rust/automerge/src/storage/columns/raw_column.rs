@@ -1,6 +1,6 @@
-use std::{io::Read, marker::PhantomData, ops::Range};
+use std::{marker::PhantomData, ops::Range};
 
-use crate::storage::parse;
+use crate::storage::{parse, Codec};
 
 use super::{compression, ColumnSpec};
 
@@ -46,17 +46,25 @@ impl<T: compression::ColumnCompression> RawColumn<T> {
         self.data.clone()
     }
 
-    fn compress(&self, input: &[u8], out: &mut Vec<u8>, threshold: usize) -> (ColumnSpec, usize) {
+    /// Write this column to `out`, compressing it with `codec` if it is at least `threshold` bytes
+    ///
+    /// The deflate bit of the column specification marks the column as compressed with whichever
+    /// codec the chunk uses, see [`Codec`].
+    fn compress(
+        &self,
+        input: &[u8],
+        out: &mut Vec<u8>,
+        threshold: usize,
+        codec: Codec,
+    ) -> (ColumnSpec, usize) {
         let (spec, len) = if self.data.len() < threshold || self.spec.deflate() {
             out.extend(&input[self.data.clone()]);
             (self.spec, self.data.len())
         } else {
-            let mut deflater = flate2::bufread::DeflateEncoder::new(
-                &input[self.data.clone()],
-                flate2::Compression::default(),
-            );
-            //This unwrap should be okay as we're reading and writing to in memory buffers
-            (self.spec.deflated(), deflater.read_to_end(out).unwrap())
+            (
+                self.spec.deflated(),
+                codec.compress(&input[self.data.clone()], out),
+            )
         };
         (spec, len)
     }
@@ -77,10 +85,12 @@ impl<T: compression::ColumnCompression> RawColumn<T> {
         &self,
         input: &[u8],
         out: &mut Vec<u8>,
+        codec: Codec,
     ) -> Result<(ColumnSpec, usize), ParseError> {
         let len = if self.spec.deflate() {
-            let mut inflater = flate2::bufread::DeflateDecoder::new(&input[self.data.clone()]);
-            inflater.read_to_end(out).map_err(ParseError::Deflate)?
+            codec
+                .decompress(&input[self.data.clone()], out)
+                .map_err(ParseError::Decompress)?
         } else {
             out.extend(&input[self.data.clone()]);
             self.data.len()
@@ -106,7 +116,8 @@ impl<T: compression::ColumnCompression> RawColumns<T> {
         Some(RawColumns(result))
     }
 
-    /// Write each column in `input` represented by `self` into `out`, possibly compressing.
+    /// Write each column in `input` represented by `self` into `out`, possibly compressing with
+    /// `codec`.
     ///
     /// # Returns
     /// The `RawColumns` corresponding to the data written to `out`
@@ -118,11 +129,12 @@ impl<T: compression::ColumnCompression> RawColumns<T> {
         input: &[u8],
         out: &mut Vec<u8>,
         threshold: usize,
+        codec: Codec,
     ) -> RawColumns<compression::Unknown> {
         let mut result = Vec::with_capacity(self.0.len());
         let mut start = 0;
         for col in &self.0 {
-            let (spec, len) = col.compress(input, out, threshold);
+            let (spec, len) = col.compress(input, out, threshold, codec);
             result.push(RawColumn {
                 spec,
                 data: start..(start + len),
@@ -134,6 +146,7 @@ impl<T: compression::ColumnCompression> RawColumns<T> {
     }
 
     /// Read each column from `input` and write to `out`, decompressing any compressed columns
+    /// with `codec`
     ///
     /// # Returns
     /// The `RawColumns` corresponding to the data written to `out`
@@ -144,6 +157,7 @@ impl<T: compression::ColumnCompression> RawColumns<T> {
         &self,
         input: &[u8],
         out: &mut Vec<u8>,
+        codec: Codec,
     ) -> Result<RawColumns<compression::Uncompressed>, ParseError> {
         let mut result = Vec::with_capacity(self.0.len());
         let mut start = 0;
@@ -152,7 +166,7 @@ impl<T: compression::ColumnCompression> RawColumns<T> {
                 out.extend(&input[decomp.data.clone()]);
                 (decomp.spec, decomp.data.len())
             } else {
-                col.decompress(input, out)?
+                col.decompress(input, out, codec)?
             };
             result.push(RawColumn {
                 spec,
@@ -198,7 +212,7 @@ pub(crate) enum ParseError {
     #[error(transparent)]
     Leb128(#[from] parse::leb128::Error),
     #[error(transparent)]
-    Deflate(#[from] std::io::Error),
+    Decompress(#[from] std::io::Error),
 }
 
 impl RawColumns<compression::Unknown> {
@@ -93,6 +93,7 @@ impl<'a> AsChangeOp<'a> for OpWithMetadata<'a> {
             OpType::Increment(i) => Cow::Owned(ScalarValue::Int(*i)),
             OpType::Put(s) => Cow::Borrowed(s),
             OpType::MarkBegin(_, MarkData { value, .. }) => Cow::Borrowed(value),
+            OpType::Seal(heads) => Cow::Owned(crate::seal::heads_to_value(heads)),
         }
     }
 
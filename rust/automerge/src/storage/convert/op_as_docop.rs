@@ -89,6 +89,7 @@ impl<'a> AsDocOp<'a> for OpAsDocOp<'a> {
             OpType::Put(v) => Cow::Borrowed(v),
             OpType::Increment(i) => Cow::Owned(ScalarValue::Int(*i)),
             OpType::MarkBegin(_, MarkData { value, .. }) => Cow::Borrowed(value),
+            OpType::Seal(heads) => Cow::Owned(crate::seal::heads_to_value(heads)),
             _ => Cow::Owned(ScalarValue::Null),
         }
     }
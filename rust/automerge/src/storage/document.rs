@@ -1,6 +1,6 @@
 use std::{borrow::Cow, ops::Range};
 
-use super::{parse, shift_range, ChunkType, Columns, Header, RawColumns};
+use super::{parse, shift_range, ChunkType, Codec, Columns, Header, RawColumns};
 
 use crate::{convert, ActorId, ChangeHash};
 
@@ -15,7 +15,7 @@ mod compression;
 #[allow(dead_code)]
 pub(crate) enum CompressConfig {
     None,
-    Threshold(usize),
+    Threshold(usize, Codec),
 }
 
 #[derive(Debug, Clone)]
@@ -172,7 +172,7 @@ impl<'a> Document<'a> {
                 data: ops,
                 raw_columns: ops_meta,
             },
-            extra_args: (),
+            extra_args: Codec::for_chunk(header.chunk_type()),
         })
         .map_err(|e| parse::ParseError::Error(ParseError::RawColumns(e)))?;
 
@@ -271,7 +271,7 @@ impl<'a> Document<'a> {
         let op_bytes = shift_range(ops_start..ops_end, header.len());
         let change_bytes = shift_range(change_start..change_end, header.len());
 
-        let compressed_bytes = if let CompressConfig::Threshold(threshold) = compress {
+        let compressed_bytes = if let CompressConfig::Threshold(threshold, codec) = compress {
             let compressed = Cow::Owned(compression::compress(compression::Args {
                 prefix: prefix_len + header.len(),
                 suffix: suffix_start + header.len(),
@@ -287,6 +287,7 @@ impl<'a> Document<'a> {
                 extra_args: compression::CompressArgs {
                     threshold,
                     original_header_len: header_len,
+                    codec,
                 },
             }));
             Some(compressed)
@@ -308,6 +309,96 @@ impl<'a> Document<'a> {
         }
     }
 
+    /// Encode a document chunk as [`Self::new()`] does and write it to `out`
+    ///
+    /// The column data has to be encoded before the header can be written, as the header
+    /// contains a checksum of it, but each part of the chunk is written as it is rather than
+    /// copying them all into one buffer, and the uncompressed columns are dropped as soon as they
+    /// have been compressed.
+    pub(crate) fn write_new<'b, I, C, IC, D, O, W>(
+        mut actors: Vec<ActorId>,
+        heads_with_indices: Vec<(ChangeHash, usize)>,
+        ops: I,
+        changes: IC,
+        compress: CompressConfig,
+        out: &mut W,
+    ) -> std::io::Result<()>
+    where
+        I: Iterator<Item = D> + Clone + ExactSizeIterator,
+        O: convert::OpId<usize>,
+        D: AsDocOp<'b, OpId = O>,
+        C: AsChangeMeta<'b>,
+        IC: Iterator<Item = C> + Clone,
+        W: std::io::Write,
+    {
+        let mut ops_out = Vec::new();
+        let ops_meta = DocOpColumns::encode(ops, &mut ops_out);
+
+        let mut change_out = Vec::new();
+        let change_meta = DocChangeColumns::encode(changes, &mut change_out);
+        actors.sort_unstable();
+
+        let mut prefix = Vec::new();
+        leb128::write::unsigned(&mut prefix, actors.len() as u64).unwrap();
+        for actor in &actors {
+            leb128::write::unsigned(&mut prefix, actor.to_bytes().len() as u64).unwrap();
+            prefix.extend(actor.to_bytes());
+        }
+        leb128::write::unsigned(&mut prefix, heads_with_indices.len() as u64).unwrap();
+        for (head, _) in &heads_with_indices {
+            prefix.extend(head.as_bytes());
+        }
+
+        let mut suffix = Vec::new();
+        for (_, index) in &heads_with_indices {
+            leb128::write::unsigned(&mut suffix, *index as u64).unwrap();
+        }
+
+        let mut meta = Vec::new();
+        let (change_data, ops_data, chunk_type) = match compress {
+            CompressConfig::Threshold(threshold, codec) => {
+                let mut change_data = Vec::new();
+                let change_cols = change_meta.raw_columns().compress(
+                    &change_out,
+                    &mut change_data,
+                    threshold,
+                    codec,
+                );
+                change_cols.write(&mut meta);
+                drop(change_out);
+                let mut ops_data = Vec::new();
+                let ops_cols =
+                    ops_meta
+                        .raw_columns()
+                        .compress(&ops_out, &mut ops_data, threshold, codec);
+                ops_cols.write(&mut meta);
+                // as in `compression::compress`
+                let chunk_type =
+                    if change_cols.uncompressed().is_none() || ops_cols.uncompressed().is_none() {
+                        codec.document_chunk_type()
+                    } else {
+                        ChunkType::Document
+                    };
+                (change_data, ops_data, chunk_type)
+            }
+            CompressConfig::None => {
+                change_meta.raw_columns().write(&mut meta);
+                ops_meta.raw_columns().write(&mut meta);
+                (change_out, ops_out, ChunkType::Document)
+            }
+        };
+
+        let pieces = [prefix.as_slice(), &meta, &change_data, &ops_data, &suffix];
+        let header = Header::for_pieces(chunk_type, &pieces);
+        let mut header_bytes = Vec::with_capacity(header.len());
+        header.write(&mut header_bytes);
+        out.write_all(&header_bytes)?;
+        for piece in pieces {
+            out.write_all(piece)?;
+        }
+        Ok(())
+    }
+
     pub(crate) fn iter_ops(
         &'a self,
     ) -> impl Iterator<Item = Result<DocOp, ReadDocOpError>> + Clone + 'a {
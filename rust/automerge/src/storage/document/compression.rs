@@ -2,7 +2,7 @@ use std::{borrow::Cow, convert::Infallible, ops::Range};
 
 use crate::storage::{
     columns::{compression, raw_column},
-    shift_range, ChunkType, Header, RawColumns,
+    shift_range, Codec, Header, RawColumns,
 };
 
 pub(super) struct Args<'a, T: compression::ColumnCompression, DirArgs> {
@@ -23,12 +23,14 @@ pub(super) struct Args<'a, T: compression::ColumnCompression, DirArgs> {
 pub(super) struct CompressArgs {
     pub(super) threshold: usize,
     pub(super) original_header_len: usize,
+    pub(super) codec: Codec,
 }
 
 /// Compress a document chunk returning the compressed bytes
 pub(super) fn compress(args: Args<'_, compression::Uncompressed, CompressArgs>) -> Vec<u8> {
     let header_len = args.extra_args.original_header_len;
     let threshold = args.extra_args.threshold;
+    let codec = args.extra_args.codec;
     // Wrap in a closure so we can use `?` in the construction but still force the compiler
     // to check that the error type is `Infallible`
     let result: Result<_, Infallible> = (|| {
@@ -37,6 +39,7 @@ pub(super) fn compress(args: Args<'_, compression::Uncompressed, CompressArgs>)
             Compressing {
                 threshold,
                 header_len,
+                codec,
             },
         )
         .changes()?
@@ -48,9 +51,11 @@ pub(super) fn compress(args: Args<'_, compression::Uncompressed, CompressArgs>)
     result.unwrap()
 }
 
+/// Decompress the columns of a document chunk whose compressed columns use `args.extra_args`
 pub(super) fn decompress<'a>(
-    args: Args<'a, compression::Unknown, ()>,
+    args: Args<'a, compression::Unknown, Codec>,
 ) -> Result<Decompressed<'a>, raw_column::ParseError> {
+    let codec = args.extra_args;
     match (
         args.changes.raw_columns.uncompressed(),
         args.ops.raw_columns.uncompressed(),
@@ -64,7 +69,7 @@ pub(super) fn decompress<'a>(
             op_bytes: args.ops.data,
         }),
         _ => Ok(
-            Compression::<'a, Decompressing, _>::new(args, Decompressing)
+            Compression::<'a, Decompressing, _>::new(args, Decompressing(codec))
                 .changes()?
                 .ops()?
                 .write_data()
@@ -128,6 +133,7 @@ trait Direction: std::fmt::Debug {
 struct Compressing {
     threshold: usize,
     header_len: usize,
+    codec: Codec,
 }
 
 impl Direction for Compressing {
@@ -144,9 +150,9 @@ impl Direction for Compressing {
         meta_out: &mut Vec<u8>,
     ) -> Result<Cols<Self::Out>, Self::Error> {
         let start = out.len();
-        let raw_columns = cols
-            .raw_columns
-            .compress(&input[cols.data.clone()], out, self.threshold);
+        let raw_columns =
+            cols.raw_columns
+                .compress(&input[cols.data.clone()], out, self.threshold, self.codec);
         raw_columns.write(meta_out);
         Ok(Cols {
             data: start..out.len(),
@@ -156,13 +162,13 @@ impl Direction for Compressing {
 }
 
 #[derive(Debug)]
-struct Decompressing;
+struct Decompressing(Codec);
 
 impl Direction for Decompressing {
     type Error = raw_column::ParseError;
     type Out = compression::Uncompressed;
     type In = compression::Unknown;
-    type Args = ();
+    type Args = Codec;
 
     fn process(
         &self,
@@ -174,7 +180,7 @@ impl Direction for Decompressing {
         let start = out.len();
         let raw_columns = cols
             .raw_columns
-            .uncompress(&input[cols.data.clone()], out)?;
+            .uncompress(&input[cols.data.clone()], out, self.0)?;
         raw_columns.write(meta_out);
         Ok(Cols {
             data: start..out.len(),
@@ -345,9 +351,22 @@ impl<'a> Compression<'a, Decompressing, Finished<Decompressing>> {
 
 impl<'a> Compression<'a, Compressing, Finished<Compressing>> {
     fn finish(self) -> Vec<u8> {
-        let Finished { out, .. } = self.state;
+        let Finished {
+            out,
+            change_cols,
+            ops_cols,
+            ..
+        } = self.state;
+        // a chunk with nothing compressed can be loaded whichever codec it would have used
+        let compressed = change_cols.raw_columns.uncompressed().is_none()
+            || ops_cols.raw_columns.uncompressed().is_none();
+        let chunk_type = if compressed {
+            self.direction.codec.document_chunk_type()
+        } else {
+            Codec::default().document_chunk_type()
+        };
         let headerless = &out[self.direction.header_len..];
-        let header = Header::new(ChunkType::Document, headerless);
+        let header = Header::new(chunk_type, headerless);
         let mut result = Vec::with_capacity(header.len() + out.len());
         header.write(&mut result);
         result.extend(headerless);
@@ -0,0 +1,90 @@
+use sha2::{Digest, Sha256};
+
+use super::{chunk::error, load, parse, MAGIC_BYTES};
+use crate::sync::PayloadCipher;
+
+/// The chunk type of an encrypted chunk, which loaders that don't know about encryption reject as
+/// an unknown chunk type
+const ENCRYPTED_CHUNK_TYPE: u8 = 3;
+
+/// Encrypt each of `chunks`, which are whole chunks including their headers
+///
+/// Each chunk is sealed whole, so nothing about the original chunk is left in the clear. The
+/// header of an encrypted chunk has the checksum of the sealed bytes, so corruption can be told
+/// apart from a wrong key, and the position of the chunk and the number of chunks are sealed as
+/// associated data, so the chunks of an encrypted document can't be reordered or dropped.
+pub(crate) fn encrypt_chunks<'a, C, I>(chunks: I, cipher: &C) -> Vec<u8>
+where
+    C: PayloadCipher + ?Sized,
+    I: IntoIterator<Item = &'a [u8]>,
+    I::IntoIter: ExactSizeIterator,
+{
+    let chunks = chunks.into_iter();
+    let count = chunks.len();
+    let mut out = Vec::new();
+    for (index, chunk) in chunks.enumerate() {
+        let sealed = cipher.seal_with_associated_data(chunk, &associated_data(index, count));
+        out.extend(MAGIC_BYTES);
+        out.extend(&Sha256::digest(&sealed)[..4]);
+        out.push(ENCRYPTED_CHUNK_TYPE);
+        leb128::write::unsigned(&mut out, sealed.len() as u64).unwrap();
+        out.extend(sealed);
+    }
+    out
+}
+
+/// Decrypt the chunks written by [`encrypt_chunks()`]
+pub(crate) fn decrypt_chunks<C: PayloadCipher + ?Sized>(
+    data: &[u8],
+    cipher: &C,
+) -> Result<Vec<u8>, load::Error> {
+    let mut sealed_chunks = Vec::new();
+    let mut input = parse::Input::new(data);
+    while !input.is_empty() {
+        let (remaining, (checksum, chunk_type, sealed)) =
+            split_chunk(input).map_err(|e| load::Error::Parse(Box::new(e)))?;
+        if chunk_type != ENCRYPTED_CHUNK_TYPE {
+            return Err(load::Error::NotEncrypted);
+        }
+        if Sha256::digest(sealed)[..4] != checksum {
+            return Err(load::Error::BadChecksum);
+        }
+        sealed_chunks.push(sealed);
+        input = remaining.reset();
+    }
+
+    let count = sealed_chunks.len();
+    let mut out = Vec::with_capacity(data.len());
+    for (index, sealed) in sealed_chunks.into_iter().enumerate() {
+        let chunk = cipher
+            .open_with_associated_data(sealed, &associated_data(index, count))
+            .map_err(|_| load::Error::Decrypt)?;
+        out.extend(chunk);
+    }
+    Ok(out)
+}
+
+/// The header fields of an encrypted chunk which aren't derived from the sealed bytes, along
+/// with its position in the document
+fn associated_data(index: usize, count: usize) -> Vec<u8> {
+    let mut data = MAGIC_BYTES.to_vec();
+    data.push(ENCRYPTED_CHUNK_TYPE);
+    data.extend((index as u64).to_be_bytes());
+    data.extend((count as u64).to_be_bytes());
+    data
+}
+
+/// The checksum, type and data of the chunk at the start of `input`
+fn split_chunk(
+    input: parse::Input<'_>,
+) -> parse::ParseResult<'_, ([u8; 4], u8, &[u8]), error::Header> {
+    let (i, magic) = parse::take4(input)?;
+    if magic != MAGIC_BYTES {
+        return Err(parse::ParseError::Error(error::Header::InvalidMagicBytes));
+    }
+    let (i, checksum) = parse::take4(i)?;
+    let (i, chunk_type) = parse::take1(i)?;
+    let (i, len) = parse::leb128_u64(i)?;
+    let (i, data) = parse::take_n(len as usize, i)?;
+    Ok((i, (checksum, chunk_type, data)))
+}
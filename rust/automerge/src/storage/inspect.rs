@@ -0,0 +1,366 @@
+use std::io::Read;
+
+use super::{
+    change, chunk, columns::compression::ColumnCompression, document, parse, Chunk, ChunkType,
+    Header, RawColumn, RawColumns,
+};
+use crate::{ActorId, ChangeHash};
+
+/// The most a compressed change is decompressed to when describing it, as the input to
+/// [`inspect()`] may be untrusted
+const MAX_DECOMPRESSED_CHANGE: u64 = 256 * 1024 * 1024;
+
+/// A description of the chunks in a saved document, returned by [`inspect()`]
+///
+/// The binary format has no version number, the kinds of chunk and the columns within them are
+/// what determine whether a given version of automerge can read a file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveReport {
+    /// The chunks which could be read, in the order they appear in the input
+    pub chunks: Vec<ChunkReport>,
+    /// Why we stopped reading chunks before the end of the input, if we did
+    pub error: Option<InspectError>,
+}
+
+impl SaveReport {
+    /// Whether every chunk could be read and has a valid checksum
+    pub fn is_valid(&self) -> bool {
+        self.error.is_none()
+            && self
+                .chunks
+                .iter()
+                .all(|c| c.checksum_valid && c.error.is_none())
+    }
+
+    /// The number of distinct actors across all chunks
+    pub fn num_actors(&self) -> usize {
+        let mut actors = self
+            .chunks
+            .iter()
+            .flat_map(|c| c.actors.iter())
+            .collect::<Vec<_>>();
+        actors.sort();
+        actors.dedup();
+        actors.len()
+    }
+}
+
+/// A description of one chunk in a saved document
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkReport {
+    /// The offset of the start of the chunk in the input
+    pub offset: usize,
+    /// The length of the chunk including its header
+    pub len: usize,
+    pub kind: ChunkKind,
+    /// Whether the checksum in the header matches the contents of the chunk
+    pub checksum_valid: bool,
+    /// The actors referenced by the chunk, for a change the author is first
+    pub actors: Vec<ActorId>,
+    /// For a document chunk the heads of the document, for a change its dependencies
+    pub heads: Vec<ChangeHash>,
+    /// The number of changes in the chunk, one for a change chunk
+    pub changes: usize,
+    /// The number of operations in the chunk
+    pub ops: usize,
+    /// The columns of the chunk in the order they appear
+    pub columns: Vec<ColumnReport>,
+    /// Why the contents of the chunk could not be decoded, if they could not
+    pub error: Option<String>,
+}
+
+/// The kind of a chunk in a saved document
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkKind {
+    /// A whole document, as produced by [`crate::Automerge::save()`]
+    Document,
+    /// A whole document with columns compressed by zstd, see [`crate::Compression`]
+    ZstdDocument,
+    /// A single change, as produced by [`crate::Automerge::save_incremental()`]
+    Change,
+    /// A single change compressed with DEFLATE
+    CompressedChange,
+}
+
+/// Which part of a chunk a column belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnSection {
+    /// The change metadata of a document chunk
+    Changes,
+    /// The operations of a document or change chunk
+    Ops,
+}
+
+/// A description of one column in a chunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnReport {
+    pub section: ColumnSection,
+    /// The column ID from the column specification
+    pub id: u32,
+    /// The name of the column type, e.g. "DeltaInteger"
+    pub column_type: String,
+    /// Whether the column data is compressed, with zstd in a [`ChunkKind::ZstdDocument`] and with
+    /// DEFLATE otherwise
+    pub compressed: bool,
+    /// The length of the column data as stored
+    pub len: usize,
+}
+
+/// Why [`inspect()`] could not read any more chunks
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum InspectError {
+    #[error("invalid magic bytes at offset {0}")]
+    InvalidMagicBytes(usize),
+    #[error("unknown chunk type {chunk_type} at offset {offset}")]
+    UnknownChunkType { offset: usize, chunk_type: u8 },
+    #[error("chunk at offset {0} is truncated")]
+    Truncated(usize),
+    #[error("invalid chunk header at offset {offset}: {reason}")]
+    InvalidHeader { offset: usize, reason: String },
+}
+
+/// Describe the chunks in `bytes` without loading them into a document
+///
+/// This reads as much of the input as it can, so for a file which fails to load the report shows
+/// which chunk is the problem and why. Unlike [`crate::Automerge::load()`] this does not check
+/// that the changes in the file are causally complete or apply them.
+pub fn inspect(bytes: &[u8]) -> SaveReport {
+    let mut chunks = Vec::new();
+    let mut input = parse::Input::new(bytes);
+    let mut offset = 0;
+    while !input.is_empty() {
+        let header = match Header::parse::<chunk::error::Header>(input) {
+            Ok((_, header)) => header,
+            Err(e) => {
+                return SaveReport {
+                    chunks,
+                    error: Some(header_error(offset, e)),
+                }
+            }
+        };
+        let len = header.len() + header.data_bytes().len();
+        let parse::Split {
+            first: chunk_input,
+            remaining,
+        } = input.split(len);
+        chunks.push(inspect_chunk(
+            offset,
+            chunk_input.unconsumed_bytes(),
+            header,
+        ));
+        input = remaining.reset();
+        offset += len;
+    }
+    SaveReport {
+        chunks,
+        error: None,
+    }
+}
+
+fn header_error(offset: usize, err: parse::ParseError<chunk::error::Header>) -> InspectError {
+    match err {
+        parse::ParseError::Incomplete(_) => InspectError::Truncated(offset),
+        parse::ParseError::Error(chunk::error::Header::InvalidMagicBytes) => {
+            InspectError::InvalidMagicBytes(offset)
+        }
+        parse::ParseError::Error(chunk::error::Header::UnknownChunkType(chunk_type)) => {
+            InspectError::UnknownChunkType { offset, chunk_type }
+        }
+        parse::ParseError::Error(e) => InspectError::InvalidHeader {
+            offset,
+            reason: e.to_string(),
+        },
+    }
+}
+
+fn inspect_chunk(offset: usize, bytes: &[u8], header: Header) -> ChunkReport {
+    let data = &bytes[header.data_bytes()];
+    let kind = match header.chunk_type() {
+        ChunkType::Document => ChunkKind::Document,
+        ChunkType::ZstdDocument => ChunkKind::ZstdDocument,
+        ChunkType::Change => ChunkKind::Change,
+        ChunkType::Compressed => ChunkKind::CompressedChange,
+    };
+    let mut report = ChunkReport {
+        offset,
+        len: bytes.len(),
+        kind,
+        checksum_valid: header.checksum_valid(),
+        actors: Vec::new(),
+        heads: Vec::new(),
+        changes: 0,
+        ops: 0,
+        columns: Vec::new(),
+        error: None,
+    };
+    let columns = match kind {
+        ChunkKind::Document | ChunkKind::ZstdDocument => document_columns(data),
+        ChunkKind::Change => change_columns(data),
+        ChunkKind::CompressedChange => {
+            let mut decompressed = Vec::new();
+            match flate2::bufread::DeflateDecoder::new(data)
+                .take(MAX_DECOMPRESSED_CHANGE + 1)
+                .read_to_end(&mut decompressed)
+            {
+                Ok(_) if decompressed.len() as u64 > MAX_DECOMPRESSED_CHANGE => Err(format!(
+                    "change decompresses to more than {} bytes",
+                    MAX_DECOMPRESSED_CHANGE
+                )),
+                Ok(_) => change_columns(&decompressed),
+                Err(e) => Err(e.to_string()),
+            }
+        }
+    };
+    match columns {
+        Ok(columns) => report.columns = columns,
+        Err(e) => {
+            report.error = Some(e);
+            return report;
+        }
+    }
+
+    let chunk = match Chunk::parse(parse::Input::new(bytes)) {
+        Ok((_, chunk)) => chunk,
+        Err(e) => {
+            report.error = Some(e.to_string());
+            return report;
+        }
+    };
+    // the checksum of a compressed change is the checksum of the uncompressed change
+    report.checksum_valid = chunk.checksum_valid();
+    match chunk {
+        Chunk::Document(doc) => {
+            report.actors = doc.actors().to_vec();
+            report.heads = doc.heads().to_vec();
+            report.changes = doc.iter_changes().count();
+            report.ops = doc.iter_ops().count();
+            report.error = doc
+                .iter_changes()
+                .find_map(|c| c.err().map(|e| e.to_string()))
+                .or_else(|| doc.iter_ops().find_map(|o| o.err().map(|e| e.to_string())));
+        }
+        Chunk::Change(change) | Chunk::CompressedChange(change, _) => {
+            report.actors = std::iter::once(change.actor().clone())
+                .chain(change.other_actors().iter().cloned())
+                .collect();
+            report.heads = change.dependencies().to_vec();
+            report.changes = 1;
+            report.ops = change.iter_ops().count();
+            report.error = change
+                .iter_ops()
+                .find_map(|o| o.err().map(|e| e.to_string()));
+        }
+    }
+    report
+}
+
+fn document_columns(data: &[u8]) -> Result<Vec<ColumnReport>, String> {
+    let parsed = (|| -> parse::ParseResult<'_, _, document::ParseError> {
+        let i = parse::Input::new(data);
+        let (i, _actors) = parse::length_prefixed(parse::actor_id)(i)?;
+        let (i, _heads) = parse::length_prefixed(parse::change_hash)(i)?;
+        let (i, change_meta) = RawColumns::parse::<document::ParseError>(i)?;
+        let (i, ops_meta) = RawColumns::parse::<document::ParseError>(i)?;
+        Ok((i, (change_meta, ops_meta)))
+    })();
+    let (_, (change_meta, ops_meta)) = parsed.map_err(|e| e.to_string())?;
+    Ok(change_meta
+        .iter()
+        .map(|c| column_report(ColumnSection::Changes, c))
+        .chain(
+            ops_meta
+                .iter()
+                .map(|c| column_report(ColumnSection::Ops, c)),
+        )
+        .collect())
+}
+
+fn change_columns(data: &[u8]) -> Result<Vec<ColumnReport>, String> {
+    let parsed = (|| -> parse::ParseResult<'_, _, change::ParseError> {
+        let i = parse::Input::new(data);
+        let (i, _deps) = parse::length_prefixed(parse::change_hash)(i)?;
+        let (i, _actor) = parse::actor_id(i)?;
+        let (i, _seq) = parse::leb128_u64(i)?;
+        let (i, _start_op) = parse::leb128_u64(i)?;
+        let (i, _timestamp) = parse::leb128_i64(i)?;
+        let (i, message_len) = parse::leb128_u64(i)?;
+        let (i, _message) = parse::take_n(message_len as usize, i)?;
+        let (i, _other_actors) = parse::length_prefixed(parse::actor_id)(i)?;
+        RawColumns::parse::<change::ParseError>(i)
+    })();
+    let (_, ops_meta) = parsed.map_err(|e| e.to_string())?;
+    Ok(ops_meta
+        .iter()
+        .map(|c| column_report(ColumnSection::Ops, c))
+        .collect())
+}
+
+fn column_report<T: ColumnCompression>(
+    section: ColumnSection,
+    column: &RawColumn<T>,
+) -> ColumnReport {
+    let spec = column.spec();
+    ColumnReport {
+        section,
+        id: spec.id().into(),
+        column_type: spec.col_type().to_string(),
+        compressed: spec.deflate(),
+        len: column.data().len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{inspect, ChunkKind, ColumnSection, InspectError};
+    use crate::{transaction::Transactable, AutoCommit, ROOT};
+
+    #[test]
+    fn describes_documents_and_incremental_changes() {
+        let mut doc = AutoCommit::new();
+        doc.put(&ROOT, "a", 1).unwrap();
+        let mut bytes = doc.save();
+        doc.put(&ROOT, "b", "two").unwrap();
+        bytes.extend(doc.save_incremental());
+
+        let report = inspect(&bytes);
+        assert!(report.is_valid());
+        assert_eq!(report.num_actors(), 1);
+        assert_eq!(report.chunks.len(), 2);
+        let (document, change) = (&report.chunks[0], &report.chunks[1]);
+        assert_eq!(document.kind, ChunkKind::Document);
+        assert_eq!((document.changes, document.ops), (1, 1));
+        assert!(document
+            .columns
+            .iter()
+            .any(|c| c.section == ColumnSection::Changes));
+        assert_eq!(change.kind, ChunkKind::Change);
+        assert_eq!(change.offset, document.len);
+        assert_eq!(change.heads, document.heads);
+        assert_eq!((change.changes, change.ops), (1, 1));
+    }
+
+    #[test]
+    fn reports_where_reading_stopped() {
+        let mut doc = AutoCommit::new();
+        doc.put(&ROOT, "a", 1).unwrap();
+        let mut bytes = doc.save();
+        let len = bytes.len();
+
+        bytes.extend([1, 2, 3, 4, 5]);
+        let report = inspect(&bytes);
+        assert_eq!(report.chunks.len(), 1);
+        assert_eq!(report.error, Some(InspectError::InvalidMagicBytes(len)));
+
+        bytes.truncate(len - 1);
+        let report = inspect(&bytes);
+        assert!(report.chunks.is_empty());
+        assert_eq!(report.error, Some(InspectError::Truncated(0)));
+
+        let mut corrupted = doc.save();
+        let last = corrupted.len() - 1;
+        corrupted[last] ^= 0xff;
+        let report = inspect(&corrupted);
+        assert!(!report.is_valid());
+        assert!(!report.chunks[0].checksum_valid);
+    }
+}
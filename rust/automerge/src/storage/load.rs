@@ -1,3 +1,5 @@
+use std::io::Read;
+
 use tracing::instrument;
 
 use crate::{
@@ -25,6 +27,75 @@ pub enum Error {
     InflateDocument(Box<dyn std::error::Error + Send + Sync + 'static>),
     #[error("bad checksum")]
     BadChecksum,
+    #[error("unable to read chunk: {0}")]
+    Io(#[source] std::io::Error),
+    #[error("expected an encrypted chunk")]
+    NotEncrypted,
+    #[error("unable to decrypt chunk")]
+    Decrypt,
+    #[error("unable to fetch blob {blob}: {error}")]
+    Fetch {
+        blob: storage::BlobId,
+        error: Box<dyn std::error::Error + Send + Sync + 'static>,
+    },
+    #[error("blob {0} does not match its ID")]
+    BlobMismatch(storage::BlobId),
+    #[error("the blobs listed in the manifest don't have the heads it lists")]
+    ManifestHeads,
+}
+
+/// The longest a LEB128 encoded `u64` can be
+const MAX_LEB128_LEN: usize = 10;
+
+/// Read the next chunk from `reader`, returning its type and all of its bytes including the
+/// header, or `None` if `reader` is at the end of its input
+pub(crate) fn read_chunk<R: Read>(
+    reader: &mut R,
+) -> Result<Option<(storage::ChunkType, Vec<u8>)>, Error> {
+    // the magic bytes, a four byte checksum and the chunk type
+    let mut chunk = vec![0; storage::MAGIC_BYTES.len() + 5];
+    let first_read = loop {
+        match reader.read(&mut chunk[..1]) {
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            other => break other.map_err(Error::Io)?,
+        }
+    };
+    if first_read == 0 {
+        return Ok(None);
+    }
+    reader.read_exact(&mut chunk[1..]).map_err(Error::Io)?;
+    if chunk[..storage::MAGIC_BYTES.len()] != storage::MAGIC_BYTES {
+        return Err(Error::Parse("invalid magic bytes".into()));
+    }
+    let raw_chunk_type = chunk[chunk.len() - 1];
+    let chunk_type = storage::ChunkType::try_from(raw_chunk_type)
+        .map_err(|t| Error::Parse(format!("unknown chunk type: {}", t).into()))?;
+
+    let len_start = chunk.len();
+    loop {
+        let mut byte = [0];
+        reader.read_exact(&mut byte).map_err(Error::Io)?;
+        chunk.push(byte[0]);
+        if byte[0] & 0x80 == 0 {
+            break;
+        }
+        if chunk.len() - len_start >= MAX_LEB128_LEN {
+            return Err(Error::Parse("chunk length is too long".into()));
+        }
+    }
+    let data_len =
+        leb128::read::unsigned(&mut &chunk[len_start..]).map_err(|e| Error::Parse(Box::new(e)))?;
+
+    // read through `take` so a corrupt length can't make us allocate more than the input holds
+    let data_start = chunk.len();
+    reader
+        .take(data_len)
+        .read_to_end(&mut chunk)
+        .map_err(Error::Io)?;
+    if ((chunk.len() - data_start) as u64) < data_len {
+        return Err(Error::Io(std::io::ErrorKind::UnexpectedEof.into()));
+    }
+    Ok(Some((chunk_type, chunk)))
 }
 
 pub(crate) enum LoadedChanges<'a> {
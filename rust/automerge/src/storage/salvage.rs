@@ -0,0 +1,82 @@
+use super::{chunk, load, parse, Header, MAGIC_BYTES};
+use crate::{Change, ChangeHash};
+
+/// What was lost loading a document with [`crate::Automerge::load_salvage()`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SalvageReport {
+    /// The parts of the input which were skipped, in the order they appear in the input
+    pub skipped: Vec<SkippedChunk>,
+    /// The changes which were read but not applied, because they depend on changes which were
+    /// lost or because they could not be applied
+    pub dropped_changes: Vec<ChangeHash>,
+    /// The dependencies of the dropped changes which were not found anywhere in the input
+    pub missing_deps: Vec<ChangeHash>,
+}
+
+impl SalvageReport {
+    /// Whether everything in the input was loaded
+    pub fn is_complete(&self) -> bool {
+        self.skipped.is_empty() && self.dropped_changes.is_empty()
+    }
+}
+
+/// A part of the input to [`crate::Automerge::load_salvage()`] which could not be read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedChunk {
+    /// The offset of the skipped bytes in the input
+    pub offset: usize,
+    /// The number of bytes skipped
+    pub len: usize,
+    /// Why the bytes were skipped
+    pub reason: String,
+}
+
+/// Read the changes from every chunk in `bytes` which can be read, skipping the rest
+///
+/// A chunk whose header can be read but which fails to load, for example because its checksum
+/// doesn't match, is skipped as a whole. If the header itself can't be read there is no way to
+/// tell where the chunk ends, so we skip ahead to the next place the magic bytes appear, which
+/// is most likely the start of the next chunk.
+pub(crate) fn read_chunks(bytes: &[u8]) -> (Vec<Change>, Vec<SkippedChunk>) {
+    let mut changes = Vec::new();
+    let mut skipped = Vec::new();
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let input = parse::Input::new(&bytes[offset..]);
+        let len = match Header::parse::<chunk::error::Header>(input) {
+            Ok((_, header)) => header.len() + header.data_bytes().len(),
+            Err(e) => {
+                let next = next_magic_bytes(bytes, offset + 1);
+                skipped.push(SkippedChunk {
+                    offset,
+                    len: next - offset,
+                    reason: e.to_string(),
+                });
+                offset = next;
+                continue;
+            }
+        };
+        let chunk = parse::Input::new(&bytes[offset..offset + len]);
+        match load::load_changes(chunk) {
+            load::LoadedChanges::Complete(loaded) => changes.extend(loaded),
+            load::LoadedChanges::Partial { error, .. } => skipped.push(SkippedChunk {
+                offset,
+                len,
+                reason: error.to_string(),
+            }),
+        }
+        offset += len;
+    }
+    (changes, skipped)
+}
+
+/// The offset of the first magic bytes in `bytes` at or after `from`, or the end of `bytes`
+fn next_magic_bytes(bytes: &[u8], from: usize) -> usize {
+    bytes
+        .get(from..)
+        .and_then(|rest| {
+            rest.windows(MAGIC_BYTES.len())
+                .position(|window| window == MAGIC_BYTES)
+        })
+        .map_or(bytes.len(), |pos| from + pos)
+}
@@ -6,7 +6,8 @@ use itertools::Itertools;
 use crate::{
     indexed_cache::IndexedCache,
     storage::{
-        change::DEFLATE_MIN_SIZE, convert::op_as_docop, AsChangeMeta, CompressConfig, Document,
+        change::DEFLATE_MIN_SIZE, convert::op_as_docop, AsChangeMeta, Codec, CompressConfig,
+        Document,
     },
     types::{ActorId, ObjId, Op},
     Change, ChangeHash,
@@ -31,16 +32,7 @@ where
     I: Iterator<Item = &'a Change> + Clone + 'a,
     O: Iterator<Item = (&'a ObjId, Op<'a>)> + Clone + ExactSizeIterator,
 {
-    let mut actor_lookup = HashMap::with_capacity(actors.len());
-    let mut actor_ids = changes
-        .clone()
-        .map(|c| c.actor_id().clone())
-        .unique()
-        .collect::<Vec<_>>();
-    actor_ids.sort();
-    for (index, actor_id) in actor_ids.iter().enumerate() {
-        actor_lookup.insert(actors.lookup(actor_id).unwrap(), index);
-    }
+    let (actor_ids, actor_lookup) = doc_actors(changes.clone(), actors);
 
     let doc_ops = ops
         .clone()
@@ -59,11 +51,82 @@ where
         hash_graph.heads_with_indices(heads.to_vec()),
         doc_ops,
         changes,
-        config.unwrap_or(CompressConfig::Threshold(DEFLATE_MIN_SIZE)),
+        config.unwrap_or(CompressConfig::Threshold(
+            DEFLATE_MIN_SIZE,
+            Codec::default(),
+        )),
     );
     doc.into_bytes()
 }
 
+/// Like [`save_document()`] but writing the document chunk to `out` as it is encoded, see
+/// [`Document::write_new()`]
+///
+/// # Panics
+///
+/// As for [`save_document()`]
+pub(crate) fn save_document_to_writer<'a, I, O, W>(
+    changes: I,
+    ops: O,
+    actors: &'a IndexedCache<ActorId>,
+    props: &IndexedCache<String>,
+    heads: &[ChangeHash],
+    config: Option<CompressConfig>,
+    out: &mut W,
+) -> std::io::Result<()>
+where
+    I: Iterator<Item = &'a Change> + Clone + 'a,
+    O: Iterator<Item = (&'a ObjId, Op<'a>)> + Clone + ExactSizeIterator,
+    W: std::io::Write,
+{
+    let (actor_ids, actor_lookup) = doc_actors(changes.clone(), actors);
+
+    let doc_ops = ops
+        .clone()
+        .map(|(_obj, op)| op_as_docop(&actor_lookup, props, op));
+
+    let hash_graph = HashGraph::new(changes.clone());
+    let changes = changes.map(|c| ChangeWithGraph {
+        actors,
+        actor_lookup: &actor_lookup,
+        change: c,
+        graph: &hash_graph,
+    });
+
+    Document::write_new(
+        actor_ids,
+        hash_graph.heads_with_indices(heads.to_vec()),
+        doc_ops,
+        changes,
+        config.unwrap_or(CompressConfig::Threshold(
+            DEFLATE_MIN_SIZE,
+            Codec::default(),
+        )),
+        out,
+    )
+}
+
+/// The actors of `changes` in the order they are stored in a document, and the index of each in
+/// that order keyed by their index in `actors`
+fn doc_actors<'a, I>(
+    changes: I,
+    actors: &IndexedCache<ActorId>,
+) -> (Vec<ActorId>, HashMap<usize, usize>)
+where
+    I: Iterator<Item = &'a Change>,
+{
+    let mut actor_lookup = HashMap::with_capacity(actors.len());
+    let mut actor_ids = changes
+        .map(|c| c.actor_id().clone())
+        .unique()
+        .collect::<Vec<_>>();
+    actor_ids.sort();
+    for (index, actor_id) in actor_ids.iter().enumerate() {
+        actor_lookup.insert(actors.lookup(actor_id).unwrap(), index);
+    }
+    (actor_ids, actor_lookup)
+}
+
 struct HashGraph {
     index_by_hash: HashMap<ChangeHash, usize, FxBuildHasher>,
 }
@@ -0,0 +1,174 @@
+use std::collections::BTreeSet;
+
+use super::{load, parse, Chunk, Document};
+use crate::{
+    columnar::column_range::Key,
+    types::{ElemId, OpId},
+    ChangeHash,
+};
+
+/// A summary of a saved document which passed [`validate()`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocumentInfo {
+    /// The heads of the document the input would load as
+    pub heads: Vec<ChangeHash>,
+    /// The number of distinct actors across all chunks
+    pub num_actors: usize,
+    /// The number of changes in the input, a change which is stored more than once is counted
+    /// each time
+    pub num_changes: usize,
+}
+
+/// Why [`validate()`] rejected its input
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ValidateError {
+    #[error("unable to parse chunk at offset {offset}: {reason}")]
+    InvalidChunk { offset: usize, reason: String },
+    #[error("bad checksum for chunk at offset {0}")]
+    BadChecksum(usize),
+    #[error("invalid columns in chunk at offset {offset}: {reason}")]
+    InvalidColumns { offset: usize, reason: String },
+    #[error("chunk at offset {0} refers to an actor it doesn't have")]
+    ActorOutOfRange(usize),
+    #[error("chunk at offset {0} refers to a dependency it doesn't have")]
+    DependencyOutOfRange(usize),
+    #[error("invalid document chunk at offset {offset}: {reason}")]
+    InvalidDocument { offset: usize, reason: String },
+    #[error("missing dependencies: {0:?}")]
+    MissingDependencies(Vec<ChangeHash>),
+}
+
+/// Check that `bytes` would load as a document without loading it
+///
+/// This checks the checksum of every chunk, that every column decodes, that every actor and
+/// dependency a chunk refers to by index is in range, and that the dependencies of every change
+/// are somewhere in the input. It doesn't build the document, so it's much cheaper than
+/// [`crate::Automerge::load()`], which makes it suitable for checking uploads before accepting
+/// them. The one exception is a change which depends on a change in the history of a document
+/// chunk other than its heads, in which case the hashes of that history have to be worked out,
+/// which is about as expensive as loading the document chunk.
+///
+/// Unlike [`crate::Automerge::load()`] a trailing chunk which can't be parsed is an error rather
+/// than being ignored.
+pub fn validate(bytes: &[u8]) -> Result<DocumentInfo, ValidateError> {
+    let mut documents = Vec::new();
+    let mut actors = BTreeSet::new();
+    let mut known = BTreeSet::new();
+    let mut deps = BTreeSet::new();
+    let mut num_changes = 0;
+
+    let mut input = parse::Input::new(bytes);
+    while !input.is_empty() {
+        let offset = bytes.len() - input.unconsumed_bytes().len();
+        let (remaining, chunk) = Chunk::parse(input).map_err(|e| ValidateError::InvalidChunk {
+            offset,
+            reason: e.to_string(),
+        })?;
+        if !chunk.checksum_valid() {
+            return Err(ValidateError::BadChecksum(offset));
+        }
+        match chunk {
+            Chunk::Document(doc) => {
+                num_changes += validate_document(offset, &doc)?;
+                actors.extend(doc.actors().iter().cloned());
+                known.extend(doc.heads().iter().copied());
+                documents.push((offset, doc));
+            }
+            Chunk::Change(change) | Chunk::CompressedChange(change, _) => {
+                let num_actors = 1 + change.other_actors().len();
+                for op in change.iter_ops() {
+                    let op = op.map_err(|e| ValidateError::InvalidColumns {
+                        offset,
+                        reason: e.to_string(),
+                    })?;
+                    let opids = op.pred.iter().chain(Some(op.obj.opid()));
+                    if !opids
+                        .chain(elem_opid(&op.key))
+                        .all(|o| in_range(o, num_actors))
+                    {
+                        return Err(ValidateError::ActorOutOfRange(offset));
+                    }
+                }
+                actors.insert(change.actor().clone());
+                actors.extend(change.other_actors().iter().cloned());
+                known.insert(change.hash());
+                deps.extend(change.dependencies().iter().copied());
+                num_changes += 1;
+            }
+        }
+        input = remaining.reset();
+    }
+
+    let mut missing = deps.difference(&known).copied().collect::<BTreeSet<_>>();
+    // the dependencies which aren't heads of a document may be somewhere in its history
+    for (offset, doc) in &documents {
+        if missing.is_empty() {
+            break;
+        }
+        let history = load::reconstruct_opset(doc, load::VerificationMode::Check).map_err(|e| {
+            ValidateError::InvalidDocument {
+                offset: *offset,
+                reason: e.to_string(),
+            }
+        })?;
+        for change in &history.changes {
+            missing.remove(&change.hash());
+        }
+    }
+    if !missing.is_empty() {
+        return Err(ValidateError::MissingDependencies(
+            missing.into_iter().collect(),
+        ));
+    }
+
+    Ok(DocumentInfo {
+        heads: known.difference(&deps).copied().collect(),
+        num_actors: actors.len(),
+        num_changes,
+    })
+}
+
+/// Check the columns of `doc` and that its indices are in range, returning the number of changes
+fn validate_document(offset: usize, doc: &Document<'_>) -> Result<usize, ValidateError> {
+    let num_actors = doc.actors().len();
+    let invalid_columns = |e: &dyn std::error::Error| ValidateError::InvalidColumns {
+        offset,
+        reason: e.to_string(),
+    };
+    let mut num_changes = 0;
+    for change in doc.iter_changes() {
+        let change = change.map_err(|e| invalid_columns(&e))?;
+        if change.actor >= num_actors {
+            return Err(ValidateError::ActorOutOfRange(offset));
+        }
+        // changes can only depend on the changes before them
+        if change.deps.iter().any(|dep| *dep >= num_changes as u64) {
+            return Err(ValidateError::DependencyOutOfRange(offset));
+        }
+        num_changes += 1;
+    }
+    for op in doc.iter_ops() {
+        let op = op.map_err(|e| invalid_columns(&e))?;
+        let opids = op.succ.iter().chain([&op.id, op.object.opid()]);
+        if !opids
+            .chain(elem_opid(&op.key))
+            .all(|o| in_range(o, num_actors))
+        {
+            return Err(ValidateError::ActorOutOfRange(offset));
+        }
+    }
+    Ok(num_changes)
+}
+
+fn elem_opid(key: &Key) -> Option<&OpId> {
+    match key {
+        Key::Elem(ElemId(opid)) => Some(opid),
+        Key::Prop(_) => None,
+    }
+}
+
+/// Whether the actor of `opid` is one of `num_actors`, the root object and the head of a sequence
+/// have no actor
+fn in_range(opid: &OpId, num_actors: usize) -> bool {
+    opid.counter() == 0 || opid.actor() < num_actors
+}
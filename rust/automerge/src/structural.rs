@@ -0,0 +1,189 @@
+use itertools::{EitherOrBoth, Itertools};
+
+use crate::exid::ExId;
+use crate::{AutomergeError, ObjType, Path, ReadDoc, Value};
+
+/// Where to record the paths at which two objects differ, `None` if we only need to know whether
+/// they differ at all
+type Differences = Option<Vec<Path>>;
+
+pub(crate) fn deep_equals<R: ReadDoc + ?Sized>(
+    doc: &R,
+    a: &ExId,
+    b: &ExId,
+) -> Result<bool, AutomergeError> {
+    compare_objects(doc, a, b, &Path::root(), &mut None)
+}
+
+pub(crate) fn structural_diff<R: ReadDoc + ?Sized>(
+    doc: &R,
+    a: &ExId,
+    b: &ExId,
+) -> Result<Vec<Path>, AutomergeError> {
+    let mut differences = Some(Vec::new());
+    compare_objects(doc, a, b, &Path::root(), &mut differences)?;
+    Ok(differences.unwrap_or_default())
+}
+
+/// Compare the objects `a` and `b`, returning whether they are equal
+///
+/// If `differences` is `None` this returns as soon as a difference is found, otherwise every
+/// path at which they differ is added to it.
+fn compare_objects<R: ReadDoc + ?Sized>(
+    doc: &R,
+    a: &ExId,
+    b: &ExId,
+    path: &Path,
+    differences: &mut Differences,
+) -> Result<bool, AutomergeError> {
+    if a == b {
+        return Ok(true);
+    }
+    let (a_type, b_type) = (doc.object_type(a)?, doc.object_type(b)?);
+    match (a_type, b_type) {
+        (ObjType::Text, ObjType::Text) => {
+            if doc.text(a)? == doc.text(b)? {
+                Ok(true)
+            } else {
+                Ok(differ(path, differences))
+            }
+        }
+        (ObjType::Map | ObjType::Table, ObjType::Map | ObjType::Table) if a_type == b_type => {
+            compare_maps(doc, a, b, path, differences)
+        }
+        (ObjType::List, ObjType::List) => compare_lists(doc, a, b, path, differences),
+        _ => Ok(differ(path, differences)),
+    }
+}
+
+fn compare_maps<R: ReadDoc + ?Sized>(
+    doc: &R,
+    a: &ExId,
+    b: &ExId,
+    path: &Path,
+    differences: &mut Differences,
+) -> Result<bool, AutomergeError> {
+    let mut equal = true;
+    // both iterators are in key order so we can walk them together
+    let pairs = doc
+        .map_range(a, ..)
+        .merge_join_by(doc.map_range(b, ..), |x, y| x.key.cmp(y.key));
+    for pair in pairs {
+        let same = match pair {
+            EitherOrBoth::Both(x, y) => compare_values(
+                doc,
+                (x.value, &x.id),
+                (y.value, &y.id),
+                &path.join(x.key),
+                differences,
+            )?,
+            EitherOrBoth::Left(x) => differ(&path.join(x.key), differences),
+            EitherOrBoth::Right(y) => differ(&path.join(y.key), differences),
+        };
+        equal &= same;
+        if !equal && differences.is_none() {
+            break;
+        }
+    }
+    Ok(equal)
+}
+
+fn compare_lists<R: ReadDoc + ?Sized>(
+    doc: &R,
+    a: &ExId,
+    b: &ExId,
+    path: &Path,
+    differences: &mut Differences,
+) -> Result<bool, AutomergeError> {
+    if differences.is_none() && doc.length(a) != doc.length(b) {
+        return Ok(false);
+    }
+    let mut equal = true;
+    for pair in doc.list_range(a, ..).zip_longest(doc.list_range(b, ..)) {
+        let same = match pair {
+            EitherOrBoth::Both(x, y) => compare_values(
+                doc,
+                (x.value, &x.id),
+                (y.value, &y.id),
+                &path.join(x.index),
+                differences,
+            )?,
+            EitherOrBoth::Left(x) => differ(&path.join(x.index), differences),
+            EitherOrBoth::Right(y) => differ(&path.join(y.index), differences),
+        };
+        equal &= same;
+        if !equal && differences.is_none() {
+            break;
+        }
+    }
+    Ok(equal)
+}
+
+fn compare_values<R: ReadDoc + ?Sized>(
+    doc: &R,
+    a: (Value<'_>, &ExId),
+    b: (Value<'_>, &ExId),
+    path: &Path,
+    differences: &mut Differences,
+) -> Result<bool, AutomergeError> {
+    match (a.0, b.0) {
+        (Value::Object(_), Value::Object(_)) => compare_objects(doc, a.1, b.1, path, differences),
+        (Value::Scalar(x), Value::Scalar(y)) if x == y => Ok(true),
+        _ => Ok(differ(path, differences)),
+    }
+}
+
+/// Record that the objects differ at `path`, returning `false`
+fn differ(path: &Path, differences: &mut Differences) -> bool {
+    if let Some(differences) = differences {
+        differences.push(path.clone());
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{transaction::Transactable, AutoCommit, ObjType, Path, ReadDoc, ROOT};
+
+    #[test]
+    fn reports_paths_which_differ() {
+        let mut doc = AutoCommit::new();
+        let mut shopping_list = |key: &str| {
+            let list = doc.put_object(&ROOT, key, ObjType::Map).unwrap();
+            doc.put(&list, "title", "shopping").unwrap();
+            let items = doc.put_object(&list, "items", ObjType::List).unwrap();
+            for (index, (name, done)) in [("eggs", false), ("milk", true)].into_iter().enumerate() {
+                let item = doc.insert_object(&items, index, ObjType::Map).unwrap();
+                doc.put(&item, "name", name).unwrap();
+                doc.put(&item, "done", done).unwrap();
+            }
+            let tags = doc.put_object(&list, "tags", ObjType::List).unwrap();
+            doc.insert(&tags, 0, "a").unwrap();
+            list
+        };
+        let a = shopping_list("a");
+        let b = shopping_list("b");
+        assert!(doc.deep_equals(&a, &b).unwrap());
+        assert!(doc.structural_diff(&a, &b).unwrap().is_empty());
+
+        let (_, items) = doc.get(&b, "items").unwrap().unwrap();
+        let (_, milk) = doc.get(&items, 1).unwrap().unwrap();
+        doc.put(&milk, "done", false).unwrap();
+        let (_, tags) = doc.get(&b, "tags").unwrap().unwrap();
+        doc.insert(&tags, 1, "b").unwrap();
+        doc.put(&b, "extra", 1).unwrap();
+
+        assert!(!doc.deep_equals(&a, &b).unwrap());
+        let paths = doc
+            .structural_diff(&a, &b)
+            .unwrap()
+            .into_iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(paths, ["/extra", "/items/1/done", "/tags/1"]);
+
+        // objects of different types differ at their root
+        let text = doc.put_object(&ROOT, "text", ObjType::Text).unwrap();
+        assert_eq!(doc.structural_diff(&a, &text).unwrap(), [Path::root()]);
+    }
+}
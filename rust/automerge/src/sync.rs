@@ -69,25 +69,47 @@
 
 use itertools::Itertools;
 use serde::ser::SerializeMap;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io::Read;
 
 use crate::{
+    exid::ExId,
     patches::{PatchLog, TextRepresentation},
-    storage::{parse, ReadChangeOpError},
+    storage::{load, parse, ReadChangeOpError},
+    transaction::Transactable,
+    types::{ObjId, OpType},
     Automerge, AutomergeError, Change, ChangeHash, ReadDoc,
 };
 
+mod batch;
 mod bloom;
+mod config;
+#[cfg(feature = "sync-driver")]
+pub mod driver;
+mod envelope;
+mod hub;
 mod message_builder;
+mod multi_doc;
+mod policy;
+mod priority;
+mod relay;
 mod state;
 use message_builder::MessageBuilder;
 
 #[cfg(test)]
 mod v1_compat_test;
 
-pub use bloom::{BloomFilter, DecodeError as DecodeBloomError};
+pub use batch::SyncMessages;
+pub use bloom::{BloomFilter, BloomFilterConfig, DecodeError as DecodeBloomError};
+pub use config::Config;
+pub use envelope::{Envelope, OpenError, PayloadCipher};
+pub use hub::{Hub, PeerProgress};
+pub use multi_doc::{MultiDocMessage, MultiDocState};
+pub use policy::{RejectedChange, SyncPolicy};
+pub use priority::Priority;
+pub use relay::Relay;
 pub use state::DecodeError as DecodeStateError;
-pub use state::{Have, State};
+pub use state::{Have, Progress, State, Summary};
 
 /// A document which can take part in the sync protocol
 ///
@@ -105,6 +127,64 @@ pub trait SyncDoc {
     ///                 current state of the document due to the received sync message
     fn generate_sync_message(&self, sync_state: &mut State) -> Option<Message>;
 
+    /// Like [`Self::generate_sync_message()`] but keeping the encoded message within
+    /// `max_message_size` bytes, for transports which can only carry small messages
+    ///
+    /// Changes which don't fit are left for later messages. The other end acknowledges each
+    /// message as usual and the next call after that picks up where the last one left off, so a
+    /// large set of changes is spread across several round trips. Changes are never split, so a
+    /// message carrying a single change which is larger than `max_message_size` on its own is
+    /// still sent, as is the part of the message describing our heads and the bloom filter.
+    fn generate_sync_message_with_max_size(
+        &self,
+        sync_state: &mut State,
+        max_message_size: usize,
+    ) -> Option<Message>;
+
+    /// Generate all the messages needed to send the remote peer the changes it is missing, each
+    /// kept within `max_message_size` bytes as in [`Self::generate_sync_message_with_max_size()`]
+    ///
+    /// This is for catching up a peer which has just connected, rather than waiting for the peer
+    /// to acknowledge each message before sending the next the whole backlog can be streamed at
+    /// once. The first message is the one [`Self::generate_sync_message_with_max_size()`] would
+    /// return, so if we don't know what the peer has yet that is the only message and the rest
+    /// follow once it has replied. The iterator ends once there are no more changes to send.
+    fn generate_sync_messages_iter<'a>(
+        &'a self,
+        sync_state: &'a mut State,
+        max_message_size: usize,
+    ) -> SyncMessages<'a>;
+
+    /// Like [`Self::generate_sync_message()`] but only sending the changes the remote peer needs
+    /// to see `obj` and everything beneath it
+    ///
+    /// This is for peers which only care about one part of a large document. The changes sent
+    /// are those which contain an operation on `obj` or one of its descendants, or which create
+    /// one of those objects or their ancestors, along with all of their dependencies. Changes
+    /// are never split, and a change can't be applied without its dependencies, so the remote
+    /// may still receive operations outside of `obj` which happened causally before an
+    /// operation within it. Changes which only touch other parts of the document and which
+    /// nothing in the subtree depends on are held back.
+    ///
+    /// The heads advertised to the remote are the heads of the changes we are willing to send,
+    /// so a peer which is only ever synced this way considers itself in sync once it has the
+    /// subtree.
+    fn generate_sync_message_for<O: AsRef<ExId>>(
+        &self,
+        sync_state: &mut State,
+        obj: O,
+    ) -> Result<Option<Message>, AutomergeError>;
+
+    /// Like [`Self::generate_sync_message()`] but syncing as `config` says, rather than as
+    /// [`Config::default()`] does
+    ///
+    /// This takes any ephemeral payloads queued on `config` with [`Config::send_ephemeral()`].
+    fn generate_sync_message_with_config(
+        &self,
+        sync_state: &mut State,
+        config: &mut Config,
+    ) -> Option<Message>;
+
     /// Apply a received sync message to this document and `sync_state`
     fn receive_sync_message(
         &mut self,
@@ -131,10 +211,42 @@ pub trait SyncDoc {
         message: Message,
         patch_log: &mut PatchLog,
     ) -> Result<(), AutomergeError>;
+
+    /// Like [`Self::receive_sync_message()`] but syncing as `config` says, rather than as
+    /// [`Config::default()`] does
+    fn receive_sync_message_with_config(
+        &mut self,
+        sync_state: &mut State,
+        config: &Config,
+        message: Message,
+    ) -> Result<(), AutomergeError>;
 }
 
 const MESSAGE_TYPE_SYNC: u8 = 0x42; // first byte of a sync message, for identification
 const MESSAGE_TYPE_SYNC_V2: u8 = 0x43; // first byte of a sync message, for identification
+const MESSAGE_TYPE_SYNC_COMPRESSED: u8 = 0x44; // precedes the type of a message with compressed changes
+const MESSAGE_TYPE_SYNC_SEALED: u8 = 0x45; // precedes the rest of a message with sealed changes
+const MESSAGE_TYPE_SYNC_KEEPALIVE: u8 = 0x46; // the whole of a keepalive message
+
+/// The most the compressed changes in a message may decompress to, so a small message from a
+/// peer can't make us allocate an unbounded amount of memory
+const MAX_DECOMPRESSED_CHANGES: u64 = 256 * 1024 * 1024;
+
+/// Decompress the changes in a message, failing if they decompress to more than `limit` bytes
+fn inflate_changes(compressed: &[u8], limit: u64) -> Result<Vec<u8>, ReadMessageError> {
+    let mut decompressed = Vec::new();
+    flate2::bufread::DeflateDecoder::new(compressed)
+        .take(limit + 1)
+        .read_to_end(&mut decompressed)
+        .map_err(ReadMessageError::Decompress)?;
+    if decompressed.len() as u64 > limit {
+        return Err(ReadMessageError::Decompress(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("changes decompress to more than {} bytes", limit),
+        )));
+    }
+    Ok(decompressed)
+}
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum MessageVersion {
@@ -165,126 +277,44 @@ impl MessageVersion {
 
 impl SyncDoc for Automerge {
     fn generate_sync_message(&self, sync_state: &mut State) -> Option<Message> {
-        let our_heads = self.get_heads();
-
-        let our_need = self.get_missing_deps(sync_state.their_heads.as_ref().unwrap_or(&vec![]));
-
-        let their_heads_set = if let Some(ref heads) = sync_state.their_heads {
-            heads.iter().collect::<HashSet<_>>()
-        } else {
-            HashSet::new()
-        };
-        let our_have = if our_need.iter().all(|hash| their_heads_set.contains(hash)) {
-            vec![self.make_bloom_filter(sync_state.shared_heads.clone())]
-        } else {
-            Vec::new()
-        };
-
-        if let Some(ref their_have) = sync_state.their_have {
-            if let Some(first_have) = their_have.first().as_ref() {
-                if !first_have
-                    .last_sync
-                    .iter()
-                    .all(|hash| self.get_change_by_hash(hash).is_some())
-                {
-                    let reset_msg = Message {
-                        heads: our_heads,
-                        need: Vec::new(),
-                        have: vec![Have::default()],
-                        changes: ChunkList::empty(),
-                        supported_capabilities: Some(vec![
-                            Capability::MessageV1,
-                            Capability::MessageV2,
-                        ]),
-                        version: MessageVersion::V1,
-                    };
-                    return Some(reset_msg);
-                }
-            }
-        }
-
-        let (message_builder, sent_hashes) = if let (Some(their_have), Some(their_need)) = (
-            sync_state.their_have.as_ref(),
-            sync_state.their_need.as_ref(),
-        ) {
-            let send_doc = sync_state
-                .their_heads
-                .as_ref()
-                .map(|h| h.is_empty())
-                .unwrap_or(false)
-                && !sync_state.have_responded
-                && sync_state.supports_v2_messages();
-
-            if send_doc {
-                let hashes = self
-                    .get_changes(&[])
-                    .iter()
-                    .map(|c| c.hash())
-                    .collect::<Vec<_>>();
-                (MessageBuilder::new_v2(self.save()), hashes)
-            } else {
-                let all_changes = self
-                    .get_changes_to_send(their_have, their_need)
-                    .expect("Should have only used hashes that are in the document");
-                // deduplicate the changes to send with those we have already sent and clone it now
-                let changes = all_changes
-                    .into_iter()
-                    .filter(|change| !sync_state.sent_hashes.contains(&change.hash()));
-                let hashes = changes.clone().map(|c| c.hash()).collect::<Vec<_>>();
-                if sync_state.supports_v2_messages() {
-                    let encoded = changes
-                        .into_iter()
-                        .flat_map(|c| c.raw_bytes().to_vec())
-                        .collect::<Vec<_>>();
-                    (MessageBuilder::new_v2(encoded), hashes)
-                } else {
-                    (MessageBuilder::new_v1(changes), hashes)
-                }
-            }
-        } else if sync_state.supports_v2_messages() {
-            (MessageBuilder::new_v2(Vec::new()), Vec::new())
-        } else {
-            (MessageBuilder::new_v1(std::iter::empty()), Vec::new())
-        };
-
-        let heads_unchanged = sync_state.last_sent_heads == our_heads;
-
-        let heads_equal = if let Some(their_heads) = sync_state.their_heads.as_ref() {
-            their_heads == &our_heads
-        } else {
-            false
-        };
-
-        if heads_unchanged && sync_state.have_responded {
-            if heads_equal && !message_builder.has_changes_to_send() {
-                return None;
-            }
-            if sync_state.in_flight {
-                return None;
-            }
-        }
+        self.generate_sync_message_inner(sync_state, &mut Config::default(), None)
+    }
 
-        // Only send the supported capabilities in the first message, the other end will store them
-        // in it's sync state and use them for subsequent messages
-        let supported_capabilities = if sync_state.have_responded {
-            None
-        } else {
-            Some(vec![Capability::MessageV1, Capability::MessageV2])
+    fn generate_sync_message_with_max_size(
+        &self,
+        sync_state: &mut State,
+        max_message_size: usize,
+    ) -> Option<Message> {
+        let mut config = Config {
+            max_message_size: Some(max_message_size),
+            ..Default::default()
         };
+        self.generate_sync_message_inner(sync_state, &mut config, None)
+    }
 
-        sync_state.have_responded = true;
-        sync_state.last_sent_heads.clone_from(&our_heads);
-        sync_state.sent_hashes.extend(sent_hashes);
+    fn generate_sync_messages_iter<'a>(
+        &'a self,
+        sync_state: &'a mut State,
+        max_message_size: usize,
+    ) -> SyncMessages<'a> {
+        SyncMessages::new(self, sync_state, max_message_size)
+    }
 
-        let sync_message = message_builder
-            .heads(our_heads)
-            .have(our_have)
-            .need(our_need)
-            .supported_capabilities(supported_capabilities)
-            .build();
+    fn generate_sync_message_for<O: AsRef<ExId>>(
+        &self,
+        sync_state: &mut State,
+        obj: O,
+    ) -> Result<Option<Message>, AutomergeError> {
+        let scope = self.subtree_changes(obj.as_ref())?;
+        Ok(self.generate_sync_message_inner(sync_state, &mut Config::default(), Some(&scope)))
+    }
 
-        sync_state.in_flight = true;
-        Some(sync_message)
+    fn generate_sync_message_with_config(
+        &self,
+        sync_state: &mut State,
+        config: &mut Config,
+    ) -> Option<Message> {
+        self.generate_sync_message_inner(sync_state, config, None)
     }
 
     fn receive_sync_message(
@@ -293,7 +323,7 @@ impl SyncDoc for Automerge {
         message: Message,
     ) -> Result<(), AutomergeError> {
         let mut patch_log = PatchLog::inactive(TextRepresentation::default());
-        self.receive_sync_message_inner(sync_state, message, &mut patch_log)
+        self.receive_sync_message_inner(sync_state, &Config::default(), message, &mut patch_log)
     }
 
     fn receive_sync_message_log_patches(
@@ -302,17 +332,126 @@ impl SyncDoc for Automerge {
         message: Message,
         patch_log: &mut PatchLog,
     ) -> Result<(), AutomergeError> {
-        self.receive_sync_message_inner(sync_state, message, patch_log)
+        self.receive_sync_message_inner(sync_state, &Config::default(), message, patch_log)
+    }
+
+    fn receive_sync_message_with_config(
+        &mut self,
+        sync_state: &mut State,
+        config: &Config,
+        message: Message,
+    ) -> Result<(), AutomergeError> {
+        let mut patch_log = PatchLog::inactive(TextRepresentation::default());
+        self.receive_sync_message_inner(sync_state, config, message, &mut patch_log)
     }
 }
 
 impl Automerge {
-    fn make_bloom_filter(&self, last_sync: Vec<ChangeHash>) -> Have {
-        let new_changes = self.get_changes(&last_sync);
+    /// A saved document with the current state of this one but none of its history
+    fn state_snapshot(&self) -> Vec<u8> {
+        let mut snapshot = Automerge::new();
+        let mut tx = snapshot.transaction();
+        tx.update_object(&crate::ROOT, &self.hydrate(None))
+            .expect("the root of a hydrated document is a map");
+        tx.commit();
+        snapshot.save()
+    }
+
+    /// The hashes of the changes which touch `obj` or any of its descendants, or which create
+    /// one of its ancestors
+    fn subtree_changes(&self, obj: &ExId) -> Result<HashSet<ChangeHash>, AutomergeError> {
+        let root = self.exid_to_obj(obj)?.id;
+        let mut opids = Vec::new();
+        for parent in self.parents(obj)? {
+            opids.push(parent.obj);
+        }
+        opids.push(obj.clone());
+
+        let mut objects = vec![root];
+        while let Some(obj) = objects.pop() {
+            for op in self.ops().iter_ops(&obj) {
+                opids.push(self.id_to_exid(*op.id()));
+                opids.extend(op.succ().map(|succ| self.id_to_exid(*succ.id())));
+                if let OpType::Make(_) = op.action() {
+                    objects.push(ObjId(*op.id()));
+                }
+            }
+        }
+        Ok(opids
+            .iter()
+            .filter_map(|opid| self.hash_for_opid(opid))
+            .collect())
+    }
+}
+
+/// The parts of a document the sync protocol needs, implemented by [`Automerge`] and by
+/// [`Relay`], which syncs changes without a document
+pub(crate) trait SyncSource: std::fmt::Debug {
+    fn sync_heads(&self) -> Vec<ChangeHash>;
+
+    fn sync_missing_deps(&self, heads: &[ChangeHash]) -> Vec<ChangeHash>;
+
+    /// The changes which are not ancestors of `have_deps`, in causal order
+    fn sync_changes(&self, have_deps: &[ChangeHash]) -> Vec<&Change>;
+
+    fn sync_change_by_hash(&self, hash: &ChangeHash) -> Option<&Change>;
+
+    /// Remove the changes which are ancestors of `heads`
+    fn sync_filter_changes(
+        &self,
+        heads: &[ChangeHash],
+        changes: &mut BTreeSet<ChangeHash>,
+    ) -> Result<(), AutomergeError>;
+
+    fn sync_num_changes(&self) -> u64;
+
+    /// The whole document, for sending to a peer with nothing, if this source has one
+    fn sync_whole_document(&self) -> Option<Vec<u8>>;
+
+    /// A snapshot for a peer which asked for one, see [`Config::bootstrap_from_snapshot`]
+    fn sync_snapshot(&self) -> Option<Vec<u8>>;
+
+    fn sync_apply_changes(
+        &mut self,
+        policy: &SyncPolicy,
+        changes: ChunkList,
+        patch_log: &mut PatchLog,
+    ) -> Result<(), AutomergeError>;
+
+    /// The changes we are prepared to share with a peer which only wants the changes in
+    /// `scope`, which is `scope` and everything the peer already has, along with all of their
+    /// dependencies. Returns the heads of those changes and the hashes of the changes.
+    fn scoped_changes(
+        &self,
+        scope: &HashSet<ChangeHash>,
+        their_heads: &[ChangeHash],
+    ) -> (Vec<ChangeHash>, HashSet<ChangeHash>) {
+        let mut shared = HashSet::new();
+        let mut stack = scope.iter().chain(their_heads).copied().collect::<Vec<_>>();
+        let mut not_heads = HashSet::<ChangeHash>::new();
+        while let Some(hash) = stack.pop() {
+            if let Some(change) = self.sync_change_by_hash(&hash) {
+                if shared.insert(hash) {
+                    not_heads.extend(change.deps());
+                    stack.extend(change.deps());
+                }
+            }
+        }
+        let mut heads = shared
+            .iter()
+            .filter(|hash| !not_heads.contains(hash))
+            .copied()
+            .collect::<Vec<_>>();
+        heads.sort();
+        (heads, shared)
+    }
+
+    fn make_bloom_filter(&self, last_sync: Vec<ChangeHash>, config: BloomFilterConfig) -> Have {
+        let new_changes = self.sync_changes(&last_sync);
         let hashes = new_changes.iter().map(|change| change.hash());
         Have {
             last_sync,
-            bloom: BloomFilter::from_hashes(hashes),
+            bloom: BloomFilter::from_hashes_with_config(hashes, config),
         }
     }
 
@@ -324,7 +463,7 @@ impl Automerge {
         if have.is_empty() {
             Ok(need
                 .iter()
-                .filter_map(|hash| self.get_change_by_hash(hash))
+                .filter_map(|hash| self.sync_change_by_hash(hash))
                 .collect())
         } else {
             let mut last_sync_hashes = HashSet::new();
@@ -337,7 +476,7 @@ impl Automerge {
             }
             let last_sync_hashes = last_sync_hashes.into_iter().copied().collect::<Vec<_>>();
 
-            let changes = self.get_changes(&last_sync_hashes);
+            let changes = self.sync_changes(&last_sync_hashes);
 
             let mut change_hashes = HashSet::with_capacity(changes.len());
             let mut dependents: HashMap<ChangeHash, Vec<ChangeHash>> = HashMap::new();
@@ -372,7 +511,7 @@ impl Automerge {
             let mut changes_to_send = Vec::new();
             for hash in need {
                 if !hashes_to_send.contains(hash) {
-                    if let Some(change) = self.get_change_by_hash(hash) {
+                    if let Some(change) = self.sync_change_by_hash(hash) {
                         changes_to_send.push(change);
                     }
                 }
@@ -387,87 +526,462 @@ impl Automerge {
         }
     }
 
-    pub(crate) fn receive_sync_message_inner(
-        &mut self,
+    fn generate_sync_message_inner(
+        &self,
         sync_state: &mut State,
-        message: Message,
-        patch_log: &mut PatchLog,
-    ) -> Result<(), AutomergeError> {
-        sync_state.in_flight = false;
-        let before_heads = self.get_heads();
-
-        let Message {
-            heads: message_heads,
-            changes: message_changes,
-            need: message_need,
-            have: message_have,
-            supported_capabilities,
-            ..
-        } = message;
-
-        if let Some(caps) = supported_capabilities {
-            sync_state.their_capabilities = Some(caps);
+        config: &mut Config,
+        scope: Option<&HashSet<ChangeHash>>,
+    ) -> Option<Message> {
+        let sent_before = sync_state.sent_hashes.len();
+        let mut remaining = None;
+        let message =
+            self.generate_sync_message_unrecorded(sync_state, config, scope, &mut remaining)?;
+        let bytes = message.changes.iter().map(|c| c.len()).sum::<usize>()
+            + message.snapshot.as_ref().map(|s| s.len()).unwrap_or(0);
+        let changes = sync_state.sent_hashes.len().saturating_sub(sent_before);
+        sync_state.record_sent(changes, bytes, remaining);
+        let mut message = message;
+        if let Some(envelope) = &config.envelope {
+            envelope.seal(&mut message);
         }
+        Some(message)
+    }
 
-        let changes_is_empty = message_changes.is_empty();
-        if !changes_is_empty {
-            for change in &message_changes.0 {
-                self.load_incremental_log_patches(change, patch_log)?;
-            }
-            sync_state.shared_heads = advance_heads(
-                &before_heads.iter().collect(),
-                &self.get_heads().into_iter().collect(),
-                &sync_state.shared_heads,
-            );
+    fn generate_sync_message_unrecorded(
+        &self,
+        sync_state: &mut State,
+        config: &mut Config,
+        scope: Option<&HashSet<ChangeHash>>,
+        remaining: &mut Option<usize>,
+    ) -> Option<Message> {
+        let scope = scope.map(|scope| {
+            self.scoped_changes(scope, sync_state.their_heads.as_deref().unwrap_or(&[]))
+        });
+        // a peer which has told us what it supports and doesn't understand ephemeral payloads
+        // would just drop them
+        if sync_state.their_capabilities.is_some()
+            && !sync_state.remote_supports(&Capability::Ephemeral)
+        {
+            config.pending_ephemeral.clear();
         }
+        let our_heads = match scope {
+            Some((ref heads, _)) => heads.clone(),
+            None => self.sync_heads(),
+        };
+        let scope = scope.map(|(_, shared)| shared);
+        let our_capabilities = config.our_capabilities();
 
-        // trim down the sent hashes to those that we know they haven't seen
-        self.filter_changes(&message_heads, &mut sync_state.sent_hashes)?;
+        let our_need = self.sync_missing_deps(sync_state.their_heads.as_ref().unwrap_or(&vec![]));
 
-        if changes_is_empty && message_heads == before_heads {
-            sync_state.last_sent_heads.clone_from(&message_heads);
-        }
+        let their_heads_set = if let Some(ref heads) = sync_state.their_heads {
+            heads.iter().collect::<HashSet<_>>()
+        } else {
+            HashSet::new()
+        };
+        let our_have = if our_need.iter().all(|hash| their_heads_set.contains(hash)) {
+            vec![self.make_bloom_filter(sync_state.shared_heads.clone(), config.bloom_filter)]
+        } else {
+            Vec::new()
+        };
 
-        let known_heads = message_heads
-            .iter()
-            .filter(|head| self.get_change_by_hash(head).is_some())
-            .collect::<Vec<_>>();
-        if known_heads.len() == message_heads.len() {
-            sync_state.shared_heads.clone_from(&message_heads);
-            // If the remote peer has lost all its data, reset our state to perform a full resync
-            if message_heads.is_empty() {
-                sync_state.last_sent_heads = Default::default();
-                sync_state.sent_hashes = Default::default();
+        if let Some(ref their_have) = sync_state.their_have {
+            if let Some(first_have) = their_have.first().as_ref() {
+                if !first_have
+                    .last_sync
+                    .iter()
+                    .all(|hash| self.sync_change_by_hash(hash).is_some())
+                {
+                    let reset_msg = Message {
+                        heads: our_heads,
+                        need: Vec::new(),
+                        have: vec![Have::default()],
+                        changes: ChunkList::empty(),
+                        supported_capabilities: Some(our_capabilities),
+                        version: MessageVersion::V1,
+                        ephemeral: std::mem::take(&mut config.pending_ephemeral),
+                        snapshot: None,
+                        compression: Compression::None,
+                        sealed: false,
+                        keepalive: false,
+                    };
+                    return Some(reset_msg);
+                }
             }
-        } else {
-            sync_state.shared_heads = sync_state
-                .shared_heads
-                .iter()
-                .chain(known_heads)
-                .copied()
-                .unique()
-                .sorted()
-                .collect::<Vec<_>>();
         }
 
-        sync_state.their_have = Some(message_have);
-        sync_state.their_heads = Some(message_heads);
-        sync_state.their_need = Some(message_need);
+        // what is left of the budget for changes once the rest of the message is accounted for
+        let budget = config.max_message_size.map(|max| {
+            let header = Message {
+                heads: our_heads.clone(),
+                need: our_need.clone(),
+                have: our_have.clone(),
+                changes: ChunkList::empty(),
+                supported_capabilities: (!sync_state.have_responded)
+                    .then(|| our_capabilities.clone()),
+                version: MessageVersion::V1,
+                ephemeral: config.pending_ephemeral.clone(),
+                snapshot: None,
+                compression: Compression::None,
+                sealed: false,
+                keepalive: false,
+            };
+            max.saturating_sub(header.encode().len() + CHANGE_PREFIX_LEN)
+        });
 
-        Ok(())
-    }
-}
+        // a new peer which asked for a snapshot gets one in place of any changes in our first
+        // message to it, the history follows in later messages
+        let send_snapshot = sync_state
+            .their_heads
+            .as_ref()
+            .map(|h| h.is_empty())
+            .unwrap_or(false)
+            && !sync_state.have_responded
+            && scope.is_none()
+            && !our_heads.is_empty()
+            && sync_state
+                .their_capabilities
+                .as_ref()
+                .map(|caps| caps.contains(&Capability::StateSnapshot))
+                .unwrap_or(false);
 
-#[derive(Debug, thiserror::Error)]
-pub enum ReadMessageError {
-    #[error("expected {expected_one_of:?} but found {found}")]
-    WrongType { expected_one_of: Vec<u8>, found: u8 },
-    #[error("{0}")]
+        let snapshot = if send_snapshot {
+            self.sync_snapshot()
+        } else {
+            None
+        };
+
+        let (message_builder, sent_hashes) = if let Some(snapshot) = snapshot {
+            let builder = if sync_state.supports_v2_messages() {
+                MessageBuilder::new_v2(Vec::new())
+            } else {
+                MessageBuilder::new_v1(std::iter::empty())
+            };
+            (builder.snapshot(snapshot), Vec::new())
+        } else if let (Some(their_have), Some(their_need)) = (
+            sync_state.their_have.as_ref(),
+            sync_state.their_need.as_ref(),
+        ) {
+            let send_doc = sync_state
+                .their_heads
+                .as_ref()
+                .map(|h| h.is_empty())
+                .unwrap_or(false)
+                && !sync_state.have_responded
+                && scope.is_none()
+                && sync_state.supports_v2_messages();
+
+            let doc = if send_doc {
+                self.sync_whole_document()
+            } else {
+                None
+            };
+            // if the whole document doesn't fit in the budget send the changes instead, which
+            // can be spread over several messages
+            let doc = doc.filter(|doc| budget.map(|b| doc.len() <= b).unwrap_or(true));
+            if let Some(doc) = doc {
+                let hashes = self
+                    .sync_changes(&[])
+                    .iter()
+                    .map(|c| c.hash())
+                    .collect::<Vec<_>>();
+                *remaining = Some(0);
+                (MessageBuilder::new_v2(doc), hashes)
+            } else {
+                let all_changes = self
+                    .get_changes_to_send(their_have, their_need)
+                    .expect("Should have only used hashes that are in the document");
+                // deduplicate the changes to send with those we have already sent and clone it now
+                let changes = all_changes
+                    .into_iter()
+                    .filter(|change| !sync_state.sent_hashes.contains(&change.hash()))
+                    .filter(|change| {
+                        scope
+                            .as_ref()
+                            .map(|scope| scope.contains(&change.hash()))
+                            .unwrap_or(true)
+                    })
+                    .collect::<Vec<_>>();
+                let changes = match &config.priority {
+                    Some(priority) => priority.order(changes),
+                    None => changes,
+                };
+                let to_send = changes.len();
+                let changes = within_budget(changes, budget).into_iter();
+                *remaining = Some(to_send - changes.len());
+                let hashes = changes.clone().map(|c| c.hash()).collect::<Vec<_>>();
+                if sync_state.supports_v2_messages() {
+                    let encoded = changes
+                        .into_iter()
+                        .flat_map(|c| c.raw_bytes().to_vec())
+                        .collect::<Vec<_>>();
+                    (MessageBuilder::new_v2(encoded), hashes)
+                } else {
+                    (MessageBuilder::new_v1(changes), hashes)
+                }
+            }
+        } else if sync_state.supports_v2_messages() {
+            (MessageBuilder::new_v2(Vec::new()), Vec::new())
+        } else {
+            (MessageBuilder::new_v1(std::iter::empty()), Vec::new())
+        };
+
+        let heads_unchanged = sync_state.last_sent_heads == our_heads;
+
+        let heads_equal = if let Some(their_heads) = sync_state.their_heads.as_ref() {
+            their_heads == &our_heads
+        } else {
+            false
+        };
+
+        let ephemeral = std::mem::take(&mut config.pending_ephemeral);
+
+        if heads_unchanged && sync_state.have_responded {
+            let nothing_to_send = heads_equal && !message_builder.has_changes_to_send();
+            if nothing_to_send || sync_state.in_flight {
+                if ephemeral.is_empty() {
+                    return None;
+                }
+                // there's nothing to sync, or we're waiting for a reply, so just send the
+                // ephemeral payloads without touching the sync state
+                return Some(Message {
+                    heads: our_heads,
+                    need: our_need,
+                    have: our_have,
+                    changes: ChunkList::empty(),
+                    supported_capabilities: Some(our_capabilities),
+                    version: MessageVersion::V1,
+                    ephemeral,
+                    snapshot: None,
+                    compression: Compression::None,
+                    sealed: false,
+                    keepalive: false,
+                });
+            }
+        }
+
+        // Only send the supported capabilities in the first message, the other end will store them
+        // in it's sync state and use them for subsequent messages. They are also sent alongside
+        // ephemeral payloads as the encoding needs them to be present.
+        let supported_capabilities = if sync_state.have_responded && ephemeral.is_empty() {
+            None
+        } else {
+            Some(our_capabilities)
+        };
+
+        sync_state.have_responded = true;
+        sync_state.last_sent_heads.clone_from(&our_heads);
+        sync_state.sent_hashes.extend(sent_hashes);
+
+        let sync_message = message_builder
+            .heads(our_heads)
+            .have(our_have)
+            .need(our_need)
+            .supported_capabilities(supported_capabilities)
+            .ephemeral(ephemeral)
+            .compression(if sync_state.remote_supports(&Capability::Compression) {
+                config.compression
+            } else {
+                Compression::None
+            })
+            .build();
+
+        sync_state.in_flight = true;
+        Some(sync_message)
+    }
+
+    fn receive_sync_message_inner(
+        &mut self,
+        sync_state: &mut State,
+        config: &Config,
+        mut message: Message,
+        patch_log: &mut PatchLog,
+    ) -> Result<(), AutomergeError> {
+        if message.keepalive {
+            sync_state.record_received(false, 0, 0);
+            return Ok(());
+        }
+        match &config.envelope {
+            Some(envelope) => envelope.open(&mut message)?,
+            None if message.sealed => {
+                return Err(AutomergeError::InvalidEnvelope(
+                    "the peer sent sealed changes but we have no envelope to open them".to_string(),
+                ))
+            }
+            None => {}
+        }
+        let replied = sync_state.in_flight;
+        sync_state.in_flight = false;
+        let before_heads = self.sync_heads();
+        let changes_before = self.sync_num_changes();
+        let bytes = message.changes.iter().map(|c| c.len()).sum::<usize>();
+
+        let Message {
+            heads: message_heads,
+            changes: message_changes,
+            need: message_need,
+            have: message_have,
+            supported_capabilities,
+            ..
+        } = message;
+
+        if let Some(caps) = supported_capabilities {
+            sync_state.their_capabilities = Some(caps);
+        }
+
+        let changes_is_empty = message_changes.is_empty();
+        if !changes_is_empty {
+            self.sync_apply_changes(&config.policy, message_changes, patch_log)?;
+            sync_state.shared_heads = advance_heads(
+                &before_heads.iter().collect(),
+                &self.sync_heads().into_iter().collect(),
+                &sync_state.shared_heads,
+            );
+        }
+
+        // trim down the sent hashes to those that we know they haven't seen
+        self.sync_filter_changes(&message_heads, &mut sync_state.sent_hashes)?;
+
+        if changes_is_empty && message_heads == before_heads {
+            sync_state.last_sent_heads.clone_from(&message_heads);
+        }
+
+        let known_heads = message_heads
+            .iter()
+            .filter(|head| self.sync_change_by_hash(head).is_some())
+            .collect::<Vec<_>>();
+        if known_heads.len() == message_heads.len() {
+            sync_state.shared_heads.clone_from(&message_heads);
+            // If the remote peer has lost all its data, reset our state to perform a full resync
+            if message_heads.is_empty() {
+                sync_state.last_sent_heads = Default::default();
+                sync_state.sent_hashes = Default::default();
+            }
+        } else {
+            sync_state.shared_heads = sync_state
+                .shared_heads
+                .iter()
+                .chain(known_heads)
+                .copied()
+                .unique()
+                .sorted()
+                .collect::<Vec<_>>();
+        }
+
+        sync_state.their_have = Some(message_have);
+        sync_state.their_heads = Some(message_heads);
+        sync_state.their_need = Some(message_need);
+
+        let changes = self.sync_num_changes().saturating_sub(changes_before) as usize;
+        sync_state.record_received(replied, changes, bytes);
+
+        Ok(())
+    }
+}
+
+impl SyncSource for Automerge {
+    fn sync_heads(&self) -> Vec<ChangeHash> {
+        self.get_heads()
+    }
+
+    fn sync_missing_deps(&self, heads: &[ChangeHash]) -> Vec<ChangeHash> {
+        ReadDoc::get_missing_deps(self, heads)
+    }
+
+    fn sync_changes(&self, have_deps: &[ChangeHash]) -> Vec<&Change> {
+        self.get_changes(have_deps)
+    }
+
+    fn sync_change_by_hash(&self, hash: &ChangeHash) -> Option<&Change> {
+        ReadDoc::get_change_by_hash(self, hash)
+    }
+
+    fn sync_filter_changes(
+        &self,
+        heads: &[ChangeHash],
+        changes: &mut BTreeSet<ChangeHash>,
+    ) -> Result<(), AutomergeError> {
+        self.filter_changes(heads, changes)
+    }
+
+    fn sync_num_changes(&self) -> u64 {
+        self.stats().num_changes
+    }
+
+    fn sync_whole_document(&self) -> Option<Vec<u8>> {
+        Some(self.save())
+    }
+
+    fn sync_snapshot(&self) -> Option<Vec<u8>> {
+        Some(self.state_snapshot())
+    }
+
+    fn sync_apply_changes(
+        &mut self,
+        policy: &SyncPolicy,
+        changes: ChunkList,
+        patch_log: &mut PatchLog,
+    ) -> Result<(), AutomergeError> {
+        if policy.accepts_all() {
+            for change in &changes.0 {
+                self.load_incremental_log_patches(change, patch_log)?;
+            }
+        } else {
+            let changes = policy.filter(load_sync_changes(&changes))?;
+            self.apply_changes_log_patches(changes, patch_log)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse the changes in `chunks`, keeping whatever can be parsed from a corrupt chunk
+fn load_sync_changes(chunks: &ChunkList) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for chunk in &chunks.0 {
+        match load::load_changes(parse::Input::new(chunk)) {
+            load::LoadedChanges::Complete(c) => changes.extend(c),
+            load::LoadedChanges::Partial { error, loaded, .. } => {
+                tracing::warn!(successful_chunks=loaded.len(), err=?error, "partial load");
+                changes.extend(loaded)
+            }
+        }
+    }
+    changes
+}
+
+/// The size of the length prefix we allow for each change when working out how many changes fit
+/// in a message, which is the most a `u32` length takes in LEB128
+const CHANGE_PREFIX_LEN: usize = 5;
+
+/// The longest prefix of `changes` whose encoding fits in `budget` bytes, but always at least one
+/// change so that a change which is too large on its own doesn't stop the sync
+fn within_budget(changes: Vec<&Change>, budget: Option<usize>) -> Vec<&Change> {
+    let Some(budget) = budget else {
+        return changes;
+    };
+    let mut used = 0;
+    let mut count = 0;
+    for change in &changes {
+        used += change.raw_bytes().len() + CHANGE_PREFIX_LEN;
+        if used > budget && count > 0 {
+            break;
+        }
+        count += 1;
+    }
+    changes.into_iter().take(count).collect()
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReadMessageError {
+    #[error("expected {expected_one_of:?} but found {found}")]
+    WrongType { expected_one_of: Vec<u8>, found: u8 },
+    #[error("{0}")]
     Parse(String),
     #[error(transparent)]
     ReadChangeOps(#[from] ReadChangeOpError),
     #[error("not enough input")]
     NotEnoughInput,
+    #[error("unable to decompress changes: {0}")]
+    Decompress(std::io::Error),
 }
 
 impl From<parse::leb128::Error> for ReadMessageError {
@@ -519,7 +1033,11 @@ impl From<parse::ParseError<ReadMessageError>> for ReadMessageError {
 /// the advertised capabilities on the sync state. This allows new implementations to discover if
 /// the remote peer supports the V2 message format (the `Capability::MessageV2` capability) and if
 /// so send a V2 message.
+///
+/// New sections are added to messages as the protocol grows, so messages can't be built with a
+/// struct literal, start from [`Message::default()`] and set the fields instead.
 #[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
 pub struct Message {
     /// The heads of the sender.
     pub heads: Vec<ChangeHash>,
@@ -538,6 +1056,63 @@ pub struct Message {
     pub supported_capabilities: Option<Vec<Capability>>,
     /// What version to encode this message as
     pub version: MessageVersion,
+    /// Ephemeral payloads, such as cursor positions or presence, for the recipient's application
+    ///
+    /// These are never applied to the document, [`SyncDoc::receive_sync_message()`] ignores them,
+    /// so read them from the message before receiving it. Queue payloads to send with
+    /// [`Config::send_ephemeral()`]. Implementations which predate ephemeral payloads ignore them.
+    pub ephemeral: Vec<Vec<u8>>,
+    /// A snapshot of the sender's current state, sent in place of changes to a new peer which
+    /// asked for one with [`Config::bootstrap_from_snapshot`]
+    ///
+    /// The snapshot is a saved document with none of the sender's history, load it with
+    /// [`Automerge::load()`] to show the document straight away while the history is synced into
+    /// the real document by the following messages. The snapshot shares no changes with the real
+    /// document so it should be thrown away rather than merged once syncing has finished.
+    pub snapshot: Option<Vec<u8>>,
+    /// How to compress [`Self::changes`] when encoding the message
+    ///
+    /// Only peers which advertise [`Capability::Compression`] can decode compressed messages,
+    /// [`SyncDoc::generate_sync_message_with_config()`] uses [`Config::compression`] for them and
+    /// [`Compression::None`] for everyone else. The changes are sent uncompressed if compressing
+    /// them doesn't make the message smaller.
+    pub compression: Compression,
+    /// Whether [`Self::changes`] and [`Self::snapshot`] are sealed by an [`Envelope`]
+    pub sealed: bool,
+    /// Whether this is a keepalive, which only tells the recipient the sender is still there
+    ///
+    /// A keepalive carries nothing else, it is encoded as a single byte and receiving it doesn't
+    /// change the sync. Create one with [`State::keepalive()`].
+    pub keepalive: bool,
+}
+
+/// An empty V1 message, with no heads, needs, bloom filters or changes
+impl Default for Message {
+    fn default() -> Self {
+        Self {
+            heads: Vec::new(),
+            need: Vec::new(),
+            have: Vec::new(),
+            changes: ChunkList::empty(),
+            supported_capabilities: None,
+            version: MessageVersion::V1,
+            ephemeral: Vec::new(),
+            snapshot: None,
+            compression: Compression::None,
+            sealed: false,
+            keepalive: false,
+        }
+    }
+}
+
+/// A compression scheme for the changes in a sync [`Message`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Compression {
+    #[default]
+    None,
+    /// DEFLATE, as used for compressing the columns of saved documents
+    Deflate,
 }
 
 /// An array of changes, each of which should be passed to [`Automerge::load_incremental()`]
@@ -597,6 +1172,26 @@ impl serde::Serialize for Message {
     }
 }
 
+/// Parse one of the optional sections at the end of a message, which is [`None`] if the message
+/// ends before it. A lenient parse treats a section it can't read as the end of the message.
+fn parse_optional<'a, O, F>(
+    input: parse::Input<'a>,
+    lenient: bool,
+    mut parser: F,
+) -> parse::ParseResult<'a, Option<O>, ReadMessageError>
+where
+    F: FnMut(parse::Input<'a>) -> parse::ParseResult<'a, O, ReadMessageError>,
+{
+    if input.is_empty() {
+        return Ok((input, None));
+    }
+    match parser(input) {
+        Ok((i, value)) => Ok((i, Some(value))),
+        Err(_) if lenient => Ok((parse::Input::empty(), None)),
+        Err(e) => Err(e),
+    }
+}
+
 fn parse_have(input: parse::Input<'_>) -> parse::ParseResult<'_, Have, ReadMessageError> {
     let (i, last_sync) = parse::length_prefixed(parse::change_hash)(input)?;
     let (i, bloom_bytes) = parse::length_prefixed_bytes(i)?;
@@ -614,20 +1209,93 @@ impl Message {
         }
     }
 
+    /// A keepalive message, see [`Self::keepalive`]
+    pub(crate) fn new_keepalive() -> Self {
+        Self {
+            heads: Vec::new(),
+            need: Vec::new(),
+            have: Vec::new(),
+            changes: ChunkList::empty(),
+            supported_capabilities: None,
+            version: MessageVersion::V1,
+            ephemeral: Vec::new(),
+            snapshot: None,
+            compression: Compression::None,
+            sealed: false,
+            keepalive: true,
+        }
+    }
+
+    /// Like [`Self::decode()`] but tolerating messages from newer versions of the protocol
+    ///
+    /// Newer versions add sections to the end of messages, which [`Self::decode()`] already
+    /// skips, but may also change the layout of the optional sections after the changes. This
+    /// keeps whatever could be read from the optional sections rather than failing, so a fleet
+    /// running a mix of versions can keep syncing while an upgrade rolls out. The heads, needs,
+    /// bloom filters and changes are always required, a message missing any of them is still an
+    /// error.
+    pub fn decode_lenient(input: &[u8]) -> Result<Self, ReadMessageError> {
+        match Self::parse_with(parse::Input::new(input), true) {
+            Ok((_, msg)) => Ok(msg),
+            Err(parse::ParseError::Error(e)) => Err(e),
+            Err(parse::ParseError::Incomplete(_)) => Err(ReadMessageError::NotEnoughInput),
+        }
+    }
+
     pub(crate) fn parse(input: parse::Input<'_>) -> parse::ParseResult<'_, Self, ReadMessageError> {
-        let (i, message_version) = MessageVersion::parse(input)?;
+        Self::parse_with(input, false)
+    }
+
+    fn parse_with(
+        input: parse::Input<'_>,
+        lenient: bool,
+    ) -> parse::ParseResult<'_, Self, ReadMessageError> {
+        if input.unconsumed_bytes().first() == Some(&MESSAGE_TYPE_SYNC_KEEPALIVE) {
+            return Ok((parse::take1(input)?.0, Self::new_keepalive()));
+        }
+        let (i, sealed) = if input.unconsumed_bytes().first() == Some(&MESSAGE_TYPE_SYNC_SEALED) {
+            (parse::take1(input)?.0, true)
+        } else {
+            (input, false)
+        };
+        let (i, compression) =
+            if i.unconsumed_bytes().first() == Some(&MESSAGE_TYPE_SYNC_COMPRESSED) {
+                (parse::take1(i)?.0, Compression::Deflate)
+            } else {
+                (i, Compression::None)
+            };
+        let (i, message_version) = MessageVersion::parse(i)?;
 
         let (i, heads) = parse::length_prefixed(parse::change_hash)(i)?;
         let (i, need) = parse::length_prefixed(parse::change_hash)(i)?;
         let (i, have) = parse::length_prefixed(parse_have)(i)?;
 
-        let (i, changes) = ChunkList::parse(i)?;
-        let (i, supported_capabilities) = if !i.is_empty() {
-            let (i, caps) = parse::length_prefixed(Capability::parse)(i)?;
-            (i, Some(caps))
-        } else {
-            (i, None)
+        let (i, changes) = match compression {
+            Compression::None => ChunkList::parse(i)?,
+            Compression::Deflate => {
+                let (i, compressed) = parse::length_prefixed_bytes(i)?;
+                let decompressed = inflate_changes(compressed, MAX_DECOMPRESSED_CHANGES)
+                    .map_err(parse::ParseError::Error)?;
+                let (_, changes) = ChunkList::parse(parse::Input::new(&decompressed))?;
+                (i, changes)
+            }
         };
+        let (i, supported_capabilities) = parse_optional(i, lenient, |i| {
+            let (i, caps) = parse::length_prefixed(Capability::parse)(i)?;
+            // an empty list is only written to make room for the ephemeral payloads after it
+            Ok((i, Some(caps).filter(|caps| !caps.is_empty())))
+        })?;
+        let (i, ephemeral) = parse_optional(i, lenient, |i| {
+            let payload_parser = |i| {
+                let (i, bytes) = parse::length_prefixed_bytes(i)?;
+                Ok((i, bytes.to_vec()))
+            };
+            parse::length_prefixed(payload_parser)(i)
+        })?;
+        let (i, snapshot) = parse_optional(i, lenient, |i| {
+            let (i, bytes) = parse::length_prefixed_bytes(i)?;
+            Ok((i, bytes.to_vec()))
+        })?;
         Ok((
             i,
             Message {
@@ -635,14 +1303,49 @@ impl Message {
                 need,
                 have,
                 changes,
-                supported_capabilities,
+                supported_capabilities: supported_capabilities.flatten(),
                 version: message_version,
+                ephemeral: ephemeral.unwrap_or_default(),
+                snapshot,
+                compression,
+                sealed,
+                keepalive: false,
             },
         ))
     }
 
     pub fn encode(self) -> Vec<u8> {
-        let mut buf = vec![self.version.encode()];
+        if self.keepalive {
+            return vec![MESSAGE_TYPE_SYNC_KEEPALIVE];
+        }
+        let mut changes = Vec::new();
+        encode_many(&mut changes, self.changes.iter(), |buf, change| {
+            leb128::write::unsigned(buf, change.len() as u64).unwrap();
+            buf.extend::<&[u8]>(change.as_ref())
+        });
+        let compressed = match self.compression {
+            Compression::None => None,
+            Compression::Deflate => {
+                let mut compressed = Vec::new();
+                flate2::bufread::DeflateEncoder::new(
+                    changes.as_slice(),
+                    flate2::Compression::default(),
+                )
+                .read_to_end(&mut compressed)
+                .expect("compressing into a Vec should not fail");
+                // the compressed form needs an extra type byte and length prefix
+                Some(compressed).filter(|c| c.len() + 1 + CHANGE_PREFIX_LEN < changes.len())
+            }
+        };
+
+        let mut buf = Vec::new();
+        if self.sealed {
+            buf.push(MESSAGE_TYPE_SYNC_SEALED);
+        }
+        if compressed.is_some() {
+            buf.push(MESSAGE_TYPE_SYNC_COMPRESSED);
+        }
+        buf.push(self.version.encode());
 
         encode_hashes(&mut buf, &self.heads);
         encode_hashes(&mut buf, &self.need);
@@ -652,26 +1355,69 @@ impl Message {
             buf.extend(h.bloom.to_bytes());
         });
 
-        encode_many(&mut buf, self.changes.iter(), |buf, change| {
-            leb128::write::unsigned(buf, change.len() as u64).unwrap();
-            buf.extend::<&[u8]>(change.as_ref())
-        });
+        if let Some(compressed) = compressed {
+            leb128::write::unsigned(&mut buf, compressed.len() as u64).unwrap();
+            buf.extend(compressed);
+        } else {
+            buf.extend(changes);
+        }
 
-        if let Some(supported_capabilities) = self.supported_capabilities {
+        // each of the optional trailing sections has to be written if any after it are
+        let has_snapshot = self.snapshot.is_some();
+        let has_ephemeral = !self.ephemeral.is_empty() || has_snapshot;
+        if self.supported_capabilities.is_some() || has_ephemeral {
+            let supported_capabilities = self.supported_capabilities.unwrap_or_default();
             encode_many(&mut buf, supported_capabilities.iter(), |buf, cap| {
                 cap.encode(buf);
             });
         }
 
+        if has_ephemeral {
+            encode_many(&mut buf, self.ephemeral.iter(), |buf, payload| {
+                leb128::write::unsigned(buf, payload.len() as u64).unwrap();
+                buf.extend(payload)
+            });
+        }
+
+        if let Some(snapshot) = self.snapshot {
+            leb128::write::unsigned(&mut buf, snapshot.len() as u64).unwrap();
+            buf.extend(snapshot);
+        }
+
         buf
     }
 }
 
+/// A protocol feature which a peer supports
+///
+/// Each peer sends the capabilities it supports in its first message, see
+/// [`Message::supported_capabilities`], and features beyond the original protocol are only used
+/// once the other end has said it understands them, see [`State::remote_supports()`].
+/// Capabilities this version of the library doesn't know about are kept as
+/// [`Capability::Unknown`], so newer peers can introduce features without breaking older ones.
+/// More capabilities are added as the protocol grows, so matches on this need a wildcard arm.
 #[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
 pub enum Capability {
     #[default]
     MessageV1,
     MessageV2,
+    /// The peer would like a snapshot of the current state of the document when it first syncs,
+    /// see [`Config::bootstrap_from_snapshot`]
+    StateSnapshot,
+    /// The peer understands ephemeral payloads, see [`Config::send_ephemeral()`]
+    Ephemeral,
+    /// The peer can receive changes spread over several messages, see
+    /// [`SyncDoc::generate_sync_message_with_max_size()`]
+    Chunking,
+    /// The peer can decode messages with compressed changes, see [`Message::compression`]
+    Compression,
+    /// The peer only wants part of the history or part of the document. This library does not
+    /// act on the capability, it is reserved so applications can negotiate it with
+    /// [`Config::extra_capabilities`]
+    Shallow,
+    /// The peer understands keepalive messages, see [`State::keepalive()`]
+    Keepalive,
     Unknown(u8),
 }
 
@@ -680,6 +1426,12 @@ impl Capability {
         match self {
             Capability::MessageV1 => out.push(0x01),
             Capability::MessageV2 => out.push(0x02),
+            Capability::StateSnapshot => out.push(0x03),
+            Capability::Ephemeral => out.push(0x04),
+            Capability::Chunking => out.push(0x05),
+            Capability::Compression => out.push(0x06),
+            Capability::Shallow => out.push(0x07),
+            Capability::Keepalive => out.push(0x08),
             Capability::Unknown(v) => out.push(*v),
         }
     }
@@ -689,6 +1441,12 @@ impl Capability {
         match v {
             0x01 => Ok((i, Self::MessageV1)),
             0x02 => Ok((i, Self::MessageV2)),
+            0x03 => Ok((i, Self::StateSnapshot)),
+            0x04 => Ok((i, Self::Ephemeral)),
+            0x05 => Ok((i, Self::Chunking)),
+            0x06 => Ok((i, Self::Compression)),
+            0x07 => Ok((i, Self::Shallow)),
+            0x08 => Ok((i, Self::Keepalive)),
             _ => Ok((i, Self::Unknown(v))),
         }
     }
@@ -784,6 +1542,8 @@ mod tests {
                 Just(Some(vec![Capability::MessageV2])),
                 Just(Some(vec![Capability::MessageV1, Capability::MessageV2])),
             ],
+            ephemeral in proptest::collection::vec(proptest::collection::vec(any::<u8>(), 0..10), 0..3),
+            snapshot in proptest::option::of(proptest::collection::vec(any::<u8>(), 0..10)),
         ) -> Message {
             Message {
                 heads,
@@ -792,6 +1552,11 @@ mod tests {
                 changes: changes.into_iter().map(|c| c.raw_bytes().to_vec()).collect::<Vec<Vec<u8>>>().into(),
                 supported_capabilities,
                 version: MessageVersion::V1,
+                ephemeral,
+                snapshot,
+                compression: Compression::None,
+                sealed: false,
+                keepalive: false,
             }
         }
     }
@@ -808,6 +1573,8 @@ mod tests {
                 Just(Some(vec![Capability::MessageV2])),
                 Just(Some(vec![Capability::MessageV1, Capability::MessageV2])),
             ],
+            ephemeral in proptest::collection::vec(proptest::collection::vec(any::<u8>(), 0..10), 0..3),
+            snapshot in proptest::option::of(proptest::collection::vec(any::<u8>(), 0..10)),
         ) -> Message {
             Message {
                 heads,
@@ -816,6 +1583,11 @@ mod tests {
                 changes: ChunkList::from(raw),
                 supported_capabilities,
                 version: MessageVersion::V2,
+                ephemeral,
+                snapshot,
+                compression: Compression::None,
+                sealed: false,
+                keepalive: false,
             }
         }
     }
@@ -833,6 +1605,11 @@ mod tests {
             changes: ChunkList::empty(),
             supported_capabilities: None,
             version: MessageVersion::V2,
+            ephemeral: Vec::new(),
+            snapshot: None,
+            compression: Compression::None,
+            sealed: false,
+            keepalive: false,
         };
         let encoded = msg.encode();
         Message::parse(Input::new(&encoded)).unwrap();
@@ -846,7 +1623,55 @@ mod tests {
             assert!(i.is_empty());
             assert_eq!(msg, decoded);
         }
-    }
+
+        #[test]
+        fn encode_decode_lenient_message(msg in gen_sync_message()) {
+            let encoded = msg.clone().encode();
+            assert_eq!(Message::decode_lenient(&encoded).unwrap(), msg);
+        }
+
+        #[test]
+        fn decode_lenient_skips_unreadable_sections(
+            msg in gen_sync_message(),
+            extra in proptest::collection::vec(any::<u8>(), 1..20),
+        ) {
+            let msg = Message {
+                supported_capabilities: None,
+                ephemeral: Vec::new(),
+                snapshot: None,
+                ..msg
+            };
+            let mut encoded = msg.clone().encode();
+            encoded.extend(extra);
+            let decoded = Message::decode_lenient(&encoded).unwrap();
+            assert_eq!(decoded.heads, msg.heads);
+            assert_eq!(decoded.need, msg.need);
+            assert_eq!(decoded.have, msg.have);
+            assert_eq!(decoded.changes, msg.changes);
+        }
+
+        #[test]
+        fn decoding_arbitrary_bytes_does_not_panic(
+            bytes in proptest::collection::vec(any::<u8>(), 0..200),
+        ) {
+            let _ = Message::decode(&bytes);
+            let _ = Message::decode_lenient(&bytes);
+        }
+    }
+
+    #[test]
+    fn decode_lenient_accepts_a_changed_capability_section() {
+        let msg = Message {
+            version: MessageVersion::V2,
+            keepalive: false,
+            ..Message::new_keepalive()
+        };
+        let mut encoded = msg.clone().encode();
+        // a capability list claiming more entries than there are bytes for
+        encoded.extend([0x05, 0x01]);
+        assert!(Message::decode(&encoded).is_err());
+        assert_eq!(Message::decode_lenient(&encoded).unwrap(), msg);
+    }
 
     #[test]
     fn generate_sync_message_twice_does_nothing() {
@@ -1237,14 +2062,36 @@ mod tests {
         b: &mut crate::AutoCommit,
         a_sync_state: &mut State,
         b_sync_state: &mut State,
+    ) {
+        sync_with_config(
+            a,
+            b,
+            a_sync_state,
+            &mut Config::default(),
+            b_sync_state,
+            &mut Config::default(),
+        )
+    }
+
+    fn sync_with_config(
+        a: &mut crate::AutoCommit,
+        b: &mut crate::AutoCommit,
+        a_sync_state: &mut State,
+        a_config: &mut Config,
+        b_sync_state: &mut State,
+        b_config: &mut Config,
     ) {
         //function sync(a: Automerge, b: Automerge, aSyncState = initSyncState(), bSyncState = initSyncState()) {
         const MAX_ITER: usize = 10;
         let mut iterations = 0;
 
         loop {
-            let a_to_b = a.sync().generate_sync_message(a_sync_state);
-            let b_to_a = b.sync().generate_sync_message(b_sync_state);
+            let a_to_b = a
+                .sync()
+                .generate_sync_message_with_config(a_sync_state, a_config);
+            let b_to_a = b
+                .sync()
+                .generate_sync_message_with_config(b_sync_state, b_config);
             if a_to_b.is_none() && b_to_a.is_none() {
                 break;
             }
@@ -1252,10 +2099,14 @@ mod tests {
                 panic!("failed to sync in {} iterations", MAX_ITER);
             }
             if let Some(msg) = a_to_b {
-                b.sync().receive_sync_message(b_sync_state, msg).unwrap()
+                b.sync()
+                    .receive_sync_message_with_config(b_sync_state, b_config, msg)
+                    .unwrap()
             }
             if let Some(msg) = b_to_a {
-                a.sync().receive_sync_message(a_sync_state, msg).unwrap()
+                a.sync()
+                    .receive_sync_message_with_config(a_sync_state, a_config, msg)
+                    .unwrap()
             }
             iterations += 1;
         }
@@ -1287,4 +2138,1021 @@ mod tests {
         let (_, chunk) = Chunk::parse(Input::new(&changes.0[0])).unwrap();
         assert!(matches!(chunk, Chunk::Document(_)));
     }
+
+    #[test]
+    fn resumable_sync_state_remembers_what_the_peer_told_us() {
+        let mut doc1 = crate::AutoCommit::new();
+        let mut doc2 = crate::AutoCommit::new();
+        doc1.put(crate::ROOT, "x", 1).unwrap();
+        let mut s1 = State::new();
+        let mut s2 = State::new();
+        sync(&mut doc1, &mut doc2, &mut s1, &mut s2);
+        doc1.put(crate::ROOT, "x", 2).unwrap();
+
+        let resumed = State::decode(&s1.encode_resumable()).unwrap();
+        assert_eq!(resumed.shared_heads, s1.shared_heads);
+        assert_eq!(resumed.their_heads, s1.their_heads);
+        assert_eq!(resumed.their_need, s1.their_need);
+        assert_eq!(resumed.their_have, s1.their_have);
+        assert_eq!(resumed.their_capabilities, s1.their_capabilities);
+        assert!(resumed.sent_hashes.is_empty() && !resumed.in_flight && !resumed.have_responded);
+
+        // a resumed state can send the new change in the first message, a reset one cannot
+        let mut resumed = resumed;
+        let msg = doc1.sync().generate_sync_message(&mut resumed).unwrap();
+        assert_eq!(msg.changes.len(), 1);
+        let mut reset = State::decode(&s1.encode()).unwrap();
+        let msg = doc1.sync().generate_sync_message(&mut reset).unwrap();
+        assert!(msg.changes.is_empty());
+
+        let mut encoded = s1.encode_resumable();
+        encoded[1] += 1;
+        assert!(matches!(
+            State::decode(&encoded),
+            Err(DecodeStateError::UnsupportedVersion { .. })
+        ));
+    }
+
+    #[test]
+    fn generate_sync_message_with_max_size_spreads_changes_over_messages() {
+        const MAX_SIZE: usize = 400;
+        let mut doc1 = crate::AutoCommit::new();
+        let mut doc2 = crate::AutoCommit::new();
+        for i in 0..20 {
+            doc1.put(
+                crate::ROOT,
+                format!("key{}", i),
+                "a value which takes up some room",
+            )
+            .unwrap();
+            doc1.commit();
+        }
+        let mut s1 = State::new();
+        let mut s2 = State::new();
+
+        let mut messages_with_changes = 0;
+        for _ in 0..100 {
+            let a_to_b = doc1
+                .sync()
+                .generate_sync_message_with_max_size(&mut s1, MAX_SIZE);
+            let b_to_a = doc2
+                .sync()
+                .generate_sync_message_with_max_size(&mut s2, MAX_SIZE);
+            if a_to_b.is_none() && b_to_a.is_none() {
+                break;
+            }
+            if let Some(msg) = a_to_b {
+                if !msg.changes.is_empty() {
+                    messages_with_changes += 1;
+                }
+                let encoded = msg.encode();
+                assert!(
+                    encoded.len() <= MAX_SIZE,
+                    "message was {} bytes",
+                    encoded.len()
+                );
+                let msg = Message::decode(&encoded).unwrap();
+                doc2.sync().receive_sync_message(&mut s2, msg).unwrap();
+            }
+            if let Some(msg) = b_to_a {
+                doc1.sync().receive_sync_message(&mut s1, msg).unwrap();
+            }
+        }
+        assert_eq!(doc1.get_heads(), doc2.get_heads());
+        assert!(messages_with_changes > 1);
+    }
+
+    #[test]
+    fn ephemeral_payloads_are_delivered_without_changing_the_document() {
+        let mut doc1 = crate::AutoCommit::new();
+        let mut doc2 = crate::AutoCommit::new();
+        doc1.put(crate::ROOT, "x", 1).unwrap();
+        let mut s1 = State::new();
+        let mut s2 = State::new();
+        sync(&mut doc1, &mut doc2, &mut s1, &mut s2);
+        assert!(doc1.sync().generate_sync_message(&mut s1).is_none());
+
+        let mut c1 = Config::default();
+        c1.send_ephemeral(b"cursor at 3".to_vec());
+        let msg = doc1
+            .sync()
+            .generate_sync_message_with_config(&mut s1, &mut c1)
+            .unwrap();
+        let msg = Message::decode(&msg.encode()).unwrap();
+        assert_eq!(msg.ephemeral, vec![b"cursor at 3".to_vec()]);
+        assert_eq!(msg.supported_capabilities, Some(c1.our_capabilities()));
+        let heads = doc2.get_heads();
+        doc2.sync().receive_sync_message(&mut s2, msg).unwrap();
+        assert_eq!(doc2.get_heads(), heads);
+
+        // the payloads are only sent once and don't disturb the rest of the sync
+        assert!(doc1.sync().generate_sync_message(&mut s1).is_none());
+        doc1.put(crate::ROOT, "x", 2).unwrap();
+        c1.send_ephemeral(b"cursor at 4".to_vec());
+        sync_with_config(
+            &mut doc1,
+            &mut doc2,
+            &mut s1,
+            &mut c1,
+            &mut s2,
+            &mut Config::default(),
+        );
+        assert_eq!(doc1.get_heads(), doc2.get_heads());
+        assert!(c1.pending_ephemeral.is_empty());
+    }
+
+    #[test]
+    fn sync_policy_rejects_or_drops_disallowed_changes() {
+        let alice = ActorId::try_from("aaaa").unwrap();
+        let mallory = ActorId::try_from("bbbb").unwrap();
+        let mut server = crate::AutoCommit::new();
+        let mut peer = crate::AutoCommit::new().with_actor(mallory.clone());
+        peer.put(crate::ROOT, "x", "from mallory").unwrap();
+
+        // a read only peer's changes make the message fail without touching the document
+        let mut server_state = State::new();
+        let mut server_config = Config {
+            policy: SyncPolicy::read_only(),
+            ..Config::default()
+        };
+        let mut peer_state = State::new();
+        let msg = peer.sync().generate_sync_message(&mut peer_state).unwrap();
+        server
+            .sync()
+            .receive_sync_message_with_config(&mut server_state, &server_config, msg)
+            .unwrap();
+        let msg = server
+            .sync()
+            .generate_sync_message_with_config(&mut server_state, &mut server_config)
+            .unwrap();
+        peer.sync()
+            .receive_sync_message(&mut peer_state, msg)
+            .unwrap();
+        let msg = peer.sync().generate_sync_message(&mut peer_state).unwrap();
+        let err = server
+            .sync()
+            .receive_sync_message_with_config(&mut server_state, &server_config, msg)
+            .unwrap_err();
+        let AutomergeError::RejectedChanges(rejected) = err else {
+            panic!("unexpected error {:?}", err);
+        };
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected[0].actor, mallory);
+        assert!(server.get_heads().is_empty());
+
+        // dropping keeps the allowed changes and leaves out the rest
+        let mut other = crate::AutoCommit::new().with_actor(alice.clone());
+        other.put(crate::ROOT, "y", "from alice").unwrap();
+        peer.merge(&mut other).unwrap();
+        let mut server = crate::AutoCommit::new();
+        let mut server_state = State::new();
+        let mut server_config = Config {
+            policy: SyncPolicy::allow_actors([alice.clone()]).dropping_disallowed(),
+            ..Config::default()
+        };
+        let mut peer_state = State::new();
+        for _ in 0..3 {
+            if let Some(msg) = peer.sync().generate_sync_message(&mut peer_state) {
+                server
+                    .sync()
+                    .receive_sync_message_with_config(&mut server_state, &server_config, msg)
+                    .unwrap();
+            }
+            if let Some(msg) = server
+                .sync()
+                .generate_sync_message_with_config(&mut server_state, &mut server_config)
+            {
+                peer.sync()
+                    .receive_sync_message(&mut peer_state, msg)
+                    .unwrap();
+            }
+        }
+        assert_eq!(server.get_heads(), other.get_heads());
+        assert!(server.get(crate::ROOT, "x").unwrap().is_none());
+    }
+
+    #[test]
+    fn new_peer_can_bootstrap_from_a_state_snapshot() {
+        let mut doc1 = crate::AutoCommit::new();
+        for i in 0..10 {
+            doc1.put(crate::ROOT, "x", i).unwrap();
+            doc1.commit();
+        }
+        let mut doc2 = crate::AutoCommit::new();
+        let mut s1 = State::new();
+        let mut s2 = State::new();
+        let mut c2 = Config {
+            bootstrap_from_snapshot: true,
+            ..Config::default()
+        };
+
+        let msg = doc2
+            .sync()
+            .generate_sync_message_with_config(&mut s2, &mut c2)
+            .unwrap();
+        doc1.sync().receive_sync_message(&mut s1, msg).unwrap();
+        let msg = doc1.sync().generate_sync_message(&mut s1).unwrap();
+        let msg = Message::decode(&msg.encode()).unwrap();
+        assert!(msg.changes.is_empty());
+        let snapshot = crate::Automerge::load(msg.snapshot.as_ref().unwrap()).unwrap();
+        assert_eq!(snapshot.get_changes(&[]).len(), 1);
+        assert_eq!(snapshot.hydrate(None), doc1.document().hydrate(None));
+
+        // the history follows in the usual way
+        doc2.sync()
+            .receive_sync_message_with_config(&mut s2, &c2, msg)
+            .unwrap();
+        sync_with_config(
+            &mut doc1,
+            &mut doc2,
+            &mut s1,
+            &mut Config::default(),
+            &mut s2,
+            &mut c2,
+        );
+        assert_eq!(doc1.get_heads(), doc2.get_heads());
+        assert_eq!(doc2.get_changes(&[]).len(), 10);
+    }
+
+    #[test]
+    fn sync_can_be_scoped_to_a_subtree() {
+        let mut doc1 = crate::AutoCommit::new();
+        let a = doc1
+            .put_object(crate::ROOT, "a", crate::ObjType::Map)
+            .unwrap();
+        let b = doc1
+            .put_object(crate::ROOT, "b", crate::ObjType::Map)
+            .unwrap();
+        doc1.commit();
+        let list = doc1.put_object(&a, "list", crate::ObjType::List).unwrap();
+        doc1.insert(&list, 0, "in a").unwrap();
+        doc1.commit();
+        let mut doc3 = doc1.fork();
+        doc3.put(&b, "x", "in b").unwrap();
+        doc1.merge(&mut doc3).unwrap();
+
+        let mut doc2 = crate::AutoCommit::new();
+        let mut s1 = State::new();
+        let mut s2 = State::new();
+        let mut iterations = 0;
+        loop {
+            let msg1 = doc1.sync().generate_sync_message_for(&mut s1, &a).unwrap();
+            if let Some(msg) = msg1.clone() {
+                doc2.sync().receive_sync_message(&mut s2, msg).unwrap();
+            }
+            let msg2 = doc2.sync().generate_sync_message(&mut s2);
+            if let Some(msg) = msg2.clone() {
+                doc1.sync().receive_sync_message(&mut s1, msg).unwrap();
+            }
+            if msg1.is_none() && msg2.is_none() {
+                break;
+            }
+            iterations += 1;
+            assert!(iterations < 10, "did not synchronize");
+        }
+
+        let list = doc2.get(&a, "list").unwrap().unwrap().1;
+        assert_eq!(
+            doc2.get(&list, 0).unwrap().unwrap().0,
+            crate::Value::from("in a")
+        );
+        assert!(doc2.get(&b, "x").unwrap().is_none());
+        assert_eq!(doc2.get_changes(&[]).len(), 2);
+    }
+
+    #[test]
+    fn capabilities_are_negotiated_in_the_first_messages() {
+        let mut doc1 = crate::AutoCommit::new();
+        doc1.put(crate::ROOT, "x", 1).unwrap();
+        let mut doc2 = crate::AutoCommit::new();
+        let mut s1 = State::new();
+        let mut s2 = State::new();
+        let mut c1 = Config::default();
+        c1.extra_capabilities.push(Capability::Shallow);
+        assert!(!s2.remote_supports(&Capability::Shallow));
+
+        sync_with_config(
+            &mut doc1,
+            &mut doc2,
+            &mut s1,
+            &mut c1,
+            &mut s2,
+            &mut Config::default(),
+        );
+        assert!(s2.remote_supports(&Capability::Shallow));
+        assert!(s2.remote_supports(&Capability::Ephemeral));
+        assert!(s1.remote_supports(&Capability::Compression));
+        assert!(!s1.remote_supports(&Capability::Shallow));
+
+        // unknown capabilities from newer peers survive a round trip
+        let msg = Message {
+            heads: Vec::new(),
+            need: Vec::new(),
+            have: Vec::new(),
+            changes: ChunkList::empty(),
+            supported_capabilities: Some(vec![Capability::Shallow, Capability::Unknown(0x7f)]),
+            version: MessageVersion::V1,
+            ephemeral: Vec::new(),
+            snapshot: None,
+            compression: Compression::None,
+            sealed: false,
+            keepalive: false,
+        };
+        assert_eq!(Message::decode(&msg.clone().encode()).unwrap(), msg);
+
+        // ephemeral payloads are not sent to peers which don't understand them
+        let mut old_peer = State::new();
+        old_peer.their_capabilities = Some(vec![Capability::MessageV1]);
+        let mut old_peer_config = Config::default();
+        old_peer_config.send_ephemeral(b"cursor".to_vec());
+        let msg = doc1
+            .sync()
+            .generate_sync_message_with_config(&mut old_peer, &mut old_peer_config)
+            .unwrap();
+        assert!(msg.ephemeral.is_empty());
+    }
+
+    #[test]
+    fn changes_are_compressed_for_peers_which_support_it() {
+        let mut doc1 = crate::AutoCommit::new();
+        let text = doc1
+            .put_object(crate::ROOT, "text", crate::ObjType::Text)
+            .unwrap();
+        for _ in 0..50 {
+            doc1.splice_text(&text, 0, 0, "the quick brown fox ")
+                .unwrap();
+            doc1.commit();
+        }
+        let mut doc2 = crate::AutoCommit::new();
+        let mut s1 = State::new();
+        let mut s2 = State::new();
+        let mut c1 = Config {
+            compression: Compression::Deflate,
+            ..Config::default()
+        };
+
+        let msg = doc2.sync().generate_sync_message(&mut s2).unwrap();
+        doc1.sync()
+            .receive_sync_message_with_config(&mut s1, &c1, msg)
+            .unwrap();
+        let msg = doc1
+            .sync()
+            .generate_sync_message_with_config(&mut s1, &mut c1)
+            .unwrap();
+        assert_eq!(msg.compression, Compression::Deflate);
+        let compressed = msg.clone().encode();
+        assert_eq!(compressed[0], MESSAGE_TYPE_SYNC_COMPRESSED);
+        let uncompressed = Message {
+            compression: Compression::None,
+            ..msg.clone()
+        }
+        .encode();
+        assert!(compressed.len() < uncompressed.len());
+        assert_eq!(Message::decode(&compressed).unwrap(), msg);
+
+        doc2.sync().receive_sync_message(&mut s2, msg).unwrap();
+        sync_with_config(
+            &mut doc1,
+            &mut doc2,
+            &mut s1,
+            &mut c1,
+            &mut s2,
+            &mut Config::default(),
+        );
+        assert_eq!(doc1.text(&text).unwrap(), doc2.text(&text).unwrap());
+
+        // peers which don't support compression get uncompressed messages
+        let mut s3 = State::new();
+        s3.their_capabilities = Some(vec![Capability::MessageV1, Capability::MessageV2]);
+        s3.their_have = Some(vec![Have::default()]);
+        s3.their_need = Some(Vec::new());
+        s3.their_heads = Some(Vec::new());
+        let msg = doc1
+            .sync()
+            .generate_sync_message_with_config(&mut s3, &mut c1)
+            .unwrap();
+        assert_eq!(msg.compression, Compression::None);
+    }
+
+    #[test]
+    fn sync_progress_is_tracked() {
+        let mut doc1 = crate::AutoCommit::new();
+        for i in 0..5 {
+            doc1.put(crate::ROOT, "x", i).unwrap();
+            doc1.commit();
+        }
+        let mut doc2 = crate::AutoCommit::new();
+        let mut s1 = State::new();
+        let mut s2 = State::new();
+        assert_eq!(s1.progress(), Progress::default());
+
+        sync(&mut doc1, &mut doc2, &mut s1, &mut s2);
+        let p1 = s1.progress();
+        let p2 = s2.progress();
+        // the helper sends messages in both directions at once, so some changes are sent twice
+        assert!(p1.changes_sent >= 5);
+        assert_eq!(p2.changes_received, 5);
+        assert_eq!(p1.bytes_sent, p2.bytes_received);
+        assert!(p1.bytes_sent > 0);
+        assert_eq!(p1.messages_sent, p2.messages_received);
+        assert_eq!(p2.messages_sent, p1.messages_received);
+        assert!(p1.round_trips > 0);
+        assert_eq!(p1.estimated_changes_remaining, 0);
+
+        // changes which don't fit in a size limited message are still to come
+        let mut doc3 = crate::AutoCommit::new();
+        let mut s1 = State::new();
+        let mut s3 = State::new();
+        let msg = doc3.sync().generate_sync_message(&mut s3).unwrap();
+        doc1.sync().receive_sync_message(&mut s1, msg).unwrap();
+        doc1.sync()
+            .generate_sync_message_with_max_size(&mut s1, 1)
+            .unwrap();
+        assert_eq!(s1.progress().changes_sent, 1);
+        assert_eq!(s1.progress().estimated_changes_remaining, 4);
+    }
+
+    #[test]
+    fn bloom_filters_can_be_sized_adaptively() {
+        fn hash(i: u64) -> ChangeHash {
+            let mut bytes = [0; 32];
+            let mixed = i.wrapping_add(1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+            bytes[..8].copy_from_slice(&mixed.to_le_bytes());
+            bytes[8..16].copy_from_slice(&mixed.rotate_left(29).to_le_bytes());
+            ChangeHash(bytes)
+        }
+        let present = (0..10_000).map(hash).collect::<Vec<_>>();
+        let absent = (10_000..20_000).map(hash).collect::<Vec<_>>();
+        let false_positives = |bloom: &BloomFilter| {
+            absent
+                .iter()
+                .filter(|hash| bloom.contains_hash(hash))
+                .count()
+        };
+
+        assert_eq!(
+            BloomFilterConfig::with_false_positive_rate(0.01),
+            BloomFilterConfig::default()
+        );
+        let fixed = BloomFilter::from_hashes(present.iter());
+        let adaptive =
+            BloomFilter::from_hashes_with_config(present.iter(), BloomFilterConfig::Adaptive);
+        assert!(present.iter().all(|hash| adaptive.contains_hash(hash)));
+        assert!(false_positives(&adaptive) < false_positives(&fixed));
+        assert!(false_positives(&adaptive) <= 10);
+        assert!(adaptive.to_bytes().len() > fixed.to_bytes().len());
+
+        // the parameters travel with the filter
+        let decoded = BloomFilter::try_from(adaptive.to_bytes().as_slice()).unwrap();
+        assert_eq!(decoded, adaptive);
+
+        // and peers with different configurations can sync
+        let mut doc1 = crate::AutoCommit::new();
+        let mut doc2 = crate::AutoCommit::new();
+        for i in 0..10 {
+            doc1.put(crate::ROOT, format!("a{}", i), i).unwrap();
+            doc1.commit();
+            doc2.put(crate::ROOT, format!("b{}", i), i).unwrap();
+            doc2.commit();
+        }
+        let mut s1 = State::new();
+        let mut s2 = State::new();
+        let mut c1 = Config {
+            bloom_filter: BloomFilterConfig::Adaptive,
+            ..Config::default()
+        };
+        let mut c2 = Config {
+            bloom_filter: BloomFilterConfig::with_false_positive_rate(0.001),
+            ..Config::default()
+        };
+        sync_with_config(&mut doc1, &mut doc2, &mut s1, &mut c1, &mut s2, &mut c2);
+        assert_eq!(doc1.get_heads(), doc2.get_heads());
+    }
+
+    #[test]
+    fn sync_state_can_be_compacted() {
+        let mut doc1 = crate::AutoCommit::new();
+        let mut hashes = Vec::new();
+        for i in 0..5 {
+            doc1.put(crate::ROOT, "x", i).unwrap();
+            hashes.push(doc1.commit().unwrap());
+        }
+        let mut doc2 = crate::AutoCommit::new();
+        let mut s1 = State::new();
+        let mut s2 = State::new();
+        sync(&mut doc1, &mut doc2, &mut s1, &mut s2);
+
+        // a state which has picked up ancestors of its heads along the way
+        let mut state = s1.clone();
+        state.shared_heads = hashes.clone();
+        state.shared_heads.sort();
+        state.sent_hashes = hashes.iter().copied().collect();
+        state.compact(doc1.document());
+        assert_eq!(state.shared_heads, doc1.get_heads());
+        assert!(state.sent_hashes.is_empty());
+
+        // compacting doesn't change what gets synced
+        doc1.put(crate::ROOT, "y", 1).unwrap();
+        doc1.commit();
+        sync(&mut doc1, &mut doc2, &mut state, &mut s2);
+        assert_eq!(doc1.get_heads(), doc2.get_heads());
+
+        let mut compacted = s1.clone();
+        compacted.compact(doc1.document());
+        assert_eq!(compacted.shared_heads, s1.shared_heads);
+    }
+
+    #[test]
+    fn sync_policy_can_validate_changes() {
+        let mut peer = crate::AutoCommit::new();
+        peer.put(crate::ROOT, "small", 1).unwrap();
+        peer.commit();
+        let list = peer
+            .put_object(crate::ROOT, "big", crate::ObjType::List)
+            .unwrap();
+        for i in 0..20 {
+            peer.insert(&list, i, i as i64).unwrap();
+        }
+        let big = peer.commit().unwrap();
+
+        let quota = |change: &Change| {
+            if change.len() > 10 {
+                Err(format!("{} ops is over the quota", change.len()))
+            } else {
+                Ok(())
+            }
+        };
+
+        let mut server = crate::AutoCommit::new();
+        let mut server_state = State::new();
+        let mut server_config = Config {
+            policy: SyncPolicy::accept_all().validate_with(quota),
+            ..Config::default()
+        };
+        let mut peer_state = State::new();
+        let msg = server
+            .sync()
+            .generate_sync_message_with_config(&mut server_state, &mut server_config)
+            .unwrap();
+        peer.sync()
+            .receive_sync_message(&mut peer_state, msg)
+            .unwrap();
+        let msg = peer.sync().generate_sync_message(&mut peer_state).unwrap();
+        let err = server
+            .sync()
+            .receive_sync_message_with_config(&mut server_state, &server_config, msg)
+            .unwrap_err();
+        let AutomergeError::RejectedChanges(rejected) = err else {
+            panic!("unexpected error {:?}", err);
+        };
+        assert_eq!(rejected.len(), 1);
+        assert_eq!(rejected[0].hash, big);
+        assert_eq!(rejected[0].reason, "21 ops is over the quota");
+
+        // dropping keeps the changes within the quota
+        let mut server = crate::AutoCommit::new();
+        let mut server_state = State::new();
+        let mut server_config = Config {
+            policy: SyncPolicy::accept_all()
+                .validate_with(quota)
+                .dropping_disallowed(),
+            ..Config::default()
+        };
+        let mut peer_state = State::new();
+        for _ in 0..3 {
+            if let Some(msg) = peer.sync().generate_sync_message(&mut peer_state) {
+                server
+                    .sync()
+                    .receive_sync_message_with_config(&mut server_state, &server_config, msg)
+                    .unwrap();
+            }
+            if let Some(msg) = server
+                .sync()
+                .generate_sync_message_with_config(&mut server_state, &mut server_config)
+            {
+                peer.sync()
+                    .receive_sync_message(&mut peer_state, msg)
+                    .unwrap();
+            }
+        }
+        assert!(server.get(crate::ROOT, "small").unwrap().is_some());
+        assert!(server.get(crate::ROOT, "big").unwrap().is_none());
+    }
+
+    #[test]
+    fn the_backlog_can_be_streamed_to_a_new_peer() {
+        let mut doc1 = crate::AutoCommit::new();
+        for i in 0..20 {
+            doc1.put(crate::ROOT, format!("key{}", i), "some value")
+                .unwrap();
+            doc1.commit();
+        }
+        let mut doc2 = crate::AutoCommit::new();
+        let mut s1 = State::new();
+        let mut s2 = State::new();
+
+        // the first message only tells the peer what we have
+        let msgs = doc1
+            .sync()
+            .generate_sync_messages_iter(&mut s1, 200)
+            .collect::<Vec<_>>();
+        assert_eq!(msgs.len(), 1);
+        for msg in msgs {
+            doc2.sync().receive_sync_message(&mut s2, msg).unwrap();
+        }
+        let msg = doc2.sync().generate_sync_message(&mut s2).unwrap();
+        doc1.sync().receive_sync_message(&mut s1, msg).unwrap();
+
+        // once it has replied the whole backlog is sent without waiting for acknowledgements
+        let msgs = doc1
+            .sync()
+            .generate_sync_messages_iter(&mut s1, 200)
+            .collect::<Vec<_>>();
+        assert!(msgs.len() > 1);
+        assert!(msgs.iter().all(|msg| msg.clone().encode().len() <= 400));
+        assert!(s1.in_flight);
+        for msg in msgs {
+            doc2.sync().receive_sync_message(&mut s2, msg).unwrap();
+        }
+        assert_eq!(doc2.get_heads(), doc1.get_heads());
+
+        sync(&mut doc1, &mut doc2, &mut s1, &mut s2);
+        assert_eq!(doc2.get_heads(), doc1.get_heads());
+    }
+
+    /// Not a real cipher, just enough to tell sealed bytes from plain ones and spot tampering
+    struct XorCipher(u8);
+
+    impl PayloadCipher for XorCipher {
+        fn seal(&self, plaintext: &[u8]) -> Vec<u8> {
+            let tag = plaintext.iter().fold(0_u8, |acc, b| acc.wrapping_add(*b));
+            let mut sealed = plaintext.iter().map(|b| b ^ self.0).collect::<Vec<_>>();
+            sealed.push(tag);
+            sealed
+        }
+
+        fn open(&self, sealed: &[u8]) -> Result<Vec<u8>, OpenError> {
+            let (tag, body) = sealed.split_last().ok_or(OpenError)?;
+            let plaintext = body.iter().map(|b| b ^ self.0).collect::<Vec<_>>();
+            if plaintext.iter().fold(0_u8, |acc, b| acc.wrapping_add(*b)) == *tag {
+                Ok(plaintext)
+            } else {
+                Err(OpenError)
+            }
+        }
+    }
+
+    #[test]
+    fn changes_can_be_sealed_in_an_envelope() {
+        let mut doc1 = crate::AutoCommit::new();
+        doc1.put(crate::ROOT, "secret", "attack at dawn").unwrap();
+        let mut doc2 = crate::AutoCommit::new();
+        let mut s1 = State::new();
+        let mut s2 = State::new();
+        let mut c1 = Config {
+            envelope: Some(Envelope::new(XorCipher(0x5a))),
+            ..Config::default()
+        };
+        let mut c2 = Config {
+            envelope: Some(Envelope::new(XorCipher(0x5a))),
+            ..Config::default()
+        };
+
+        let msg = doc2
+            .sync()
+            .generate_sync_message_with_config(&mut s2, &mut c2)
+            .unwrap();
+        doc1.sync()
+            .receive_sync_message_with_config(&mut s1, &c1, msg)
+            .unwrap();
+        let msg = doc1
+            .sync()
+            .generate_sync_message_with_config(&mut s1, &mut c1)
+            .unwrap();
+        assert!(msg.sealed);
+        // a relay can read the heads but not the changes
+        let encoded = msg.encode();
+        let relayed = Message::decode(&encoded).unwrap();
+        assert_eq!(relayed.heads, doc1.get_heads());
+        assert!(!encoded
+            .windows(b"attack at dawn".len())
+            .any(|w| w == b"attack at dawn"));
+
+        // or change them
+        let mut tampered = relayed.clone();
+        let mut chunk = tampered.changes.iter().next().unwrap().to_vec();
+        chunk[0] ^= 1;
+        tampered.changes = ChunkList::from(vec![chunk]);
+        let err = doc2
+            .sync()
+            .receive_sync_message_with_config(&mut s2.clone(), &c2, tampered)
+            .unwrap_err();
+        assert!(matches!(err, AutomergeError::InvalidEnvelope(_)));
+
+        doc2.sync()
+            .receive_sync_message_with_config(&mut s2, &c2, relayed)
+            .unwrap();
+        sync_with_config(&mut doc1, &mut doc2, &mut s1, &mut c1, &mut s2, &mut c2);
+        assert_eq!(
+            doc2.get(crate::ROOT, "secret").unwrap().unwrap().0,
+            crate::Value::from("attack at dawn")
+        );
+
+        // peers without the envelope can't take part
+        let mut doc3 = crate::AutoCommit::new();
+        let mut s3 = State::new();
+        let mut s1 = State::new();
+        let msg = doc3.sync().generate_sync_message(&mut s3).unwrap();
+        doc1.sync()
+            .receive_sync_message_with_config(&mut s1, &c1, msg)
+            .unwrap();
+        let msg = doc1
+            .sync()
+            .generate_sync_message_with_config(&mut s1, &mut c1)
+            .unwrap();
+        let err = doc3.sync().receive_sync_message(&mut s3, msg).unwrap_err();
+        assert!(matches!(err, AutomergeError::InvalidEnvelope(_)));
+    }
+
+    #[test]
+    fn sync_state_summary() {
+        let mut doc1 = crate::AutoCommit::new();
+        doc1.put(crate::ROOT, "x", 1).unwrap();
+        let mut doc2 = crate::AutoCommit::new();
+        let mut s1 = State::new();
+        let mut s2 = State::new();
+
+        let summary = s1.summary();
+        assert_eq!(summary.their_heads, None);
+        assert!(!summary.in_flight);
+
+        let msg = doc2.sync().generate_sync_message(&mut s2).unwrap();
+        doc1.sync().receive_sync_message(&mut s1, msg).unwrap();
+        let msg = doc1.sync().generate_sync_message(&mut s1).unwrap();
+        let summary = s1.summary();
+        assert_eq!(summary.their_heads, Some(Vec::new()));
+        assert!(summary.in_flight);
+        assert_eq!(summary.last_sent_heads, doc1.get_heads());
+        assert_eq!(summary.unacknowledged_changes, doc1.get_heads());
+        assert!(summary.progress.last_message_bytes_sent > 0);
+        assert!(format!("{:?}", summary).contains("unacknowledged_changes"));
+
+        doc2.sync().receive_sync_message(&mut s2, msg).unwrap();
+        sync(&mut doc1, &mut doc2, &mut s1, &mut s2);
+        let summary = s1.summary();
+        assert_eq!(summary.shared_heads, doc1.get_heads());
+        assert!(summary.unacknowledged_changes.is_empty());
+    }
+
+    fn sync_with_relay(
+        doc: &mut crate::AutoCommit,
+        relay: &mut Relay,
+        doc_state: &mut State,
+        relay_state: &mut State,
+    ) {
+        for _ in 0..10 {
+            let to_relay = doc.sync().generate_sync_message(doc_state);
+            let to_doc = relay.generate_sync_message(relay_state);
+            if to_relay.is_none() && to_doc.is_none() {
+                return;
+            }
+            if let Some(msg) = to_relay {
+                relay.receive_sync_message(relay_state, msg).unwrap();
+            }
+            if let Some(msg) = to_doc {
+                doc.sync().receive_sync_message(doc_state, msg).unwrap();
+            }
+        }
+        panic!("failed to sync with the relay");
+    }
+
+    #[test]
+    fn docs_can_sync_through_a_relay() {
+        let mut doc1 = crate::AutoCommit::new();
+        doc1.put(crate::ROOT, "x", 1).unwrap();
+        doc1.commit();
+        let mut doc2 = crate::AutoCommit::new();
+        doc2.put(crate::ROOT, "y", 2).unwrap();
+        doc2.commit();
+        let mut relay = Relay::new();
+        let (mut s1, mut r1, mut s2, mut r2) =
+            (State::new(), State::new(), State::new(), State::new());
+
+        sync_with_relay(&mut doc1, &mut relay, &mut s1, &mut r1);
+        sync_with_relay(&mut doc2, &mut relay, &mut s2, &mut r2);
+        sync_with_relay(&mut doc1, &mut relay, &mut s1, &mut r1);
+        assert_eq!(doc1.get_heads(), doc2.get_heads());
+        assert_eq!(relay.heads(), doc1.get_heads());
+        assert_eq!(relay.len(), 2);
+
+        doc2.put(crate::ROOT, "z", 3).unwrap();
+        doc2.commit();
+        sync_with_relay(&mut doc2, &mut relay, &mut s2, &mut r2);
+        sync_with_relay(&mut doc1, &mut relay, &mut s1, &mut r1);
+        assert_eq!(doc1.get(crate::ROOT, "z").unwrap().unwrap().0, 3.into());
+        assert_eq!(relay.heads(), doc2.get_heads());
+        assert!(relay
+            .generate_sync_message_for(&mut State::new(), crate::ROOT)
+            .is_err());
+    }
+
+    #[test]
+    fn relay_holds_back_changes_until_their_deps_arrive() {
+        let mut doc = crate::AutoCommit::new();
+        doc.put(crate::ROOT, "x", 1).unwrap();
+        doc.commit();
+        doc.put(crate::ROOT, "x", 2).unwrap();
+        doc.commit();
+        let changes = doc
+            .get_changes(&[])
+            .into_iter()
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut relay = Relay::new();
+        relay.add_changes(vec![changes[1].clone()]);
+        assert!(relay.is_empty());
+        assert_eq!(relay.pending_changes_len(), 1);
+        assert_eq!(relay.missing_deps(), vec![changes[0].hash()]);
+
+        relay.add_changes(vec![changes[0].clone()]);
+        assert_eq!(relay.len(), 2);
+        assert_eq!(relay.pending_changes_len(), 0);
+        assert_eq!(relay.heads(), doc.get_heads());
+    }
+
+    #[test]
+    fn keepalives_are_tracked_without_changing_the_sync() {
+        let mut doc1 = crate::AutoCommit::new();
+        doc1.put(crate::ROOT, "x", 1).unwrap();
+        let mut doc2 = crate::AutoCommit::new();
+        let mut s1 = State::new();
+        let mut s2 = State::new();
+        assert!(s1.keepalive().is_none());
+        assert_eq!(s2.poll_liveness(10), None);
+
+        sync(&mut doc1, &mut doc2, &mut s1, &mut s2);
+        assert_eq!(s2.poll_liveness(20), Some(20));
+        assert_eq!(s2.poll_liveness(30), Some(20));
+
+        let keepalive = s1.keepalive().unwrap();
+        let encoded = keepalive.clone().encode();
+        assert_eq!(encoded, vec![MESSAGE_TYPE_SYNC_KEEPALIVE]);
+        let decoded = Message::decode(&encoded).unwrap();
+        assert_eq!(decoded, keepalive);
+
+        let before = s2.clone();
+        doc2.sync().receive_sync_message(&mut s2, decoded).unwrap();
+        assert_eq!(s2.poll_liveness(40), Some(40));
+        assert_eq!(s2.last_heard_from(), Some(40));
+        assert_eq!(s2.shared_heads, before.shared_heads);
+        assert_eq!(s2.in_flight, before.in_flight);
+        assert!(doc2.sync().generate_sync_message(&mut s2).is_none());
+    }
+
+    #[test]
+    fn high_priority_changes_are_sent_first() {
+        const MAX_SIZE: usize = 400;
+        let mut doc1 = crate::AutoCommit::new();
+        for i in 0..20 {
+            doc1.put(
+                crate::ROOT,
+                format!("key{}", i),
+                "a value which takes up some room",
+            )
+            .unwrap();
+            doc1.commit();
+        }
+        let mut viewer = crate::AutoCommit::new();
+        viewer.put(crate::ROOT, "viewport", "edit").unwrap();
+        viewer.commit();
+        doc1.merge(&mut viewer).unwrap();
+        let viewer_actor = viewer.get_actor().clone();
+
+        let mut doc2 = crate::AutoCommit::new();
+        let mut s1 = State::new();
+        let mut c1 = Config {
+            priority: Some(Priority::actors([viewer_actor])),
+            max_message_size: Some(MAX_SIZE),
+            ..Config::default()
+        };
+        let mut s2 = State::new();
+
+        let mut first_batch = None;
+        for _ in 0..100 {
+            let a_to_b = doc1
+                .sync()
+                .generate_sync_message_with_config(&mut s1, &mut c1);
+            let b_to_a = doc2.sync().generate_sync_message(&mut s2);
+            if a_to_b.is_none() && b_to_a.is_none() {
+                break;
+            }
+            if let Some(msg) = a_to_b {
+                let has_changes = !msg.changes.is_empty();
+                doc2.sync().receive_sync_message(&mut s2, msg).unwrap();
+                if has_changes && first_batch.is_none() {
+                    first_batch = Some(doc2.get(crate::ROOT, "viewport").unwrap().is_some());
+                }
+            }
+            if let Some(msg) = b_to_a {
+                doc1.sync()
+                    .receive_sync_message_with_config(&mut s1, &c1, msg)
+                    .unwrap();
+            }
+        }
+        assert_eq!(first_batch, Some(true));
+        assert_eq!(doc1.get_heads(), doc2.get_heads());
+    }
+
+    #[test]
+    fn many_documents_sync_over_one_connection() {
+        let ids = (0..10).map(|i| format!("doc{}", i)).collect::<Vec<_>>();
+        let mut server = HashMap::new();
+        for id in &ids {
+            let mut doc = crate::AutoCommit::new();
+            doc.put(crate::ROOT, "id", id.as_str()).unwrap();
+            server.insert(id.clone(), doc);
+        }
+        let mut client: HashMap<String, crate::AutoCommit> = ids
+            .iter()
+            .map(|id| (id.clone(), crate::AutoCommit::new()))
+            .collect();
+        let mut server_state = MultiDocState::new();
+        let mut client_state = MultiDocState::new();
+
+        fn generate(
+            docs: &mut HashMap<String, crate::AutoCommit>,
+            state: &mut MultiDocState,
+        ) -> Option<Vec<u8>> {
+            let docs = docs
+                .iter_mut()
+                .map(|(id, doc)| (id.as_str(), doc.sync()))
+                .collect::<Vec<_>>();
+            state
+                .generate_message(docs.iter().map(|(id, doc)| (*id, doc)))
+                .map(MultiDocMessage::encode)
+        }
+
+        fn receive(
+            docs: &mut HashMap<String, crate::AutoCommit>,
+            state: &mut MultiDocState,
+            encoded: &[u8],
+        ) {
+            for (id, message) in MultiDocMessage::decode(encoded).unwrap() {
+                let doc = docs.get_mut(&id).unwrap();
+                state
+                    .receive_message(&id, &mut doc.sync(), message)
+                    .unwrap();
+            }
+        }
+
+        // every client document is empty, so the client sends the same message for all of them
+        let first = generate(&mut client, &mut client_state).unwrap();
+        let single = client["doc0"]
+            .clone()
+            .sync()
+            .generate_sync_message(&mut State::new())
+            .unwrap()
+            .encode();
+        // the message is only encoded once, each document just adds its ID and an index
+        // (the type byte, two counts and the length of the message take a byte each)
+        assert_eq!(first.len(), 4 + single.len() + ids.len() * 6);
+        assert_eq!(MultiDocMessage::decode(&first).unwrap().len(), ids.len());
+        receive(&mut server, &mut server_state, &first);
+
+        for _ in 0..10 {
+            let to_client = generate(&mut server, &mut server_state);
+            let to_server = generate(&mut client, &mut client_state);
+            if to_client.is_none() && to_server.is_none() {
+                break;
+            }
+            if let Some(msg) = to_client {
+                receive(&mut client, &mut client_state, &msg);
+            }
+            if let Some(msg) = to_server {
+                receive(&mut server, &mut server_state, &msg);
+            }
+        }
+        for id in &ids {
+            let client_heads = client.get_mut(id).unwrap().get_heads();
+            assert_eq!(client_heads, server.get_mut(id).unwrap().get_heads());
+        }
+        assert_eq!(client_state.documents().count(), ids.len());
+    }
+
+    #[test]
+    fn compressed_changes_which_inflate_past_the_limit_are_rejected() {
+        use std::io::Write;
+        let mut encoder =
+            flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::best());
+        encoder.write_all(&[0; 10_000]).unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert!(compressed.len() < 100);
+
+        assert_eq!(inflate_changes(&compressed, 10_000).unwrap().len(), 10_000);
+        assert!(matches!(
+            inflate_changes(&compressed, 9_999),
+            Err(ReadMessageError::Decompress(_))
+        ));
+    }
 }
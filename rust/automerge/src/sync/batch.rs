@@ -0,0 +1,62 @@
+use super::{Config, Message, State, SyncSource};
+
+/// The messages needed to send a peer everything it is missing, returned by
+/// [`super::SyncDoc::generate_sync_messages_iter()`]
+///
+/// Each message is generated as the iterator is advanced, so messages can be sent as they are
+/// produced without holding the whole backlog in memory.
+#[derive(Debug)]
+pub struct SyncMessages<'a> {
+    doc: &'a dyn SyncSource,
+    state: &'a mut State,
+    config: Config,
+    started: bool,
+    done: bool,
+}
+
+impl<'a> SyncMessages<'a> {
+    pub(crate) fn new(
+        doc: &'a dyn SyncSource,
+        state: &'a mut State,
+        max_message_size: usize,
+    ) -> Self {
+        Self {
+            doc,
+            state,
+            config: Config {
+                max_message_size: Some(max_message_size),
+                ..Default::default()
+            },
+            started: false,
+            done: false,
+        }
+    }
+}
+
+impl Iterator for SyncMessages<'_> {
+    type Item = Message;
+
+    fn next(&mut self) -> Option<Message> {
+        if self.done {
+            return None;
+        }
+        if self.started {
+            // we don't wait for the peer to acknowledge each message, the changes already sent
+            // are remembered in the state so each message carries the next batch
+            self.state.in_flight = false;
+        }
+        let message = self
+            .doc
+            .generate_sync_message_inner(self.state, &mut self.config, None);
+        let message = match message {
+            Some(message) if !self.started || !message.changes.is_empty() => message,
+            _ => {
+                self.done = true;
+                self.state.in_flight = self.started;
+                return None;
+            }
+        };
+        self.started = true;
+        Some(message)
+    }
+}
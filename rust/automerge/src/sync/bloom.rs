@@ -9,6 +9,68 @@ use crate::ChangeHash;
 const BITS_PER_ENTRY: u32 = 10;
 const NUM_PROBES: u32 = 7;
 
+/// How to size the bloom filters we send to a peer, see [`super::Config::bloom_filter`]
+///
+/// A bloom filter summarises the changes we have added since the last sync. When it wrongly
+/// claims to contain a change the peer has and we don't, the peer won't send it until a later
+/// round trip, so a lower false positive rate means fewer wasted round trips at the cost of
+/// larger messages. The parameters are part of each encoded filter, so peers using different
+/// configurations can still sync with each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BloomFilterConfig {
+    /// Use the same parameters however many changes are in the filter
+    Fixed {
+        /// The number of bits in the filter for each change
+        bits_per_entry: u32,
+        /// The number of bits set for each change
+        num_probes: u32,
+    },
+    /// Lower the false positive rate as the number of changes in the filter grows, so that
+    /// roughly one change per filter is a false positive, down to a rate of one in a million
+    Adaptive,
+}
+
+impl Default for BloomFilterConfig {
+    /// A 1% false positive rate
+    fn default() -> Self {
+        Self::Fixed {
+            bits_per_entry: BITS_PER_ENTRY,
+            num_probes: NUM_PROBES,
+        }
+    }
+}
+
+impl BloomFilterConfig {
+    /// A fixed configuration with the given false positive rate, which is clamped to between one
+    /// in a million and one half
+    pub fn with_false_positive_rate(rate: f64) -> Self {
+        let (bits_per_entry, num_probes) = optimal_parameters(rate);
+        Self::Fixed {
+            bits_per_entry,
+            num_probes,
+        }
+    }
+
+    fn parameters(&self, num_entries: u32) -> (u32, u32) {
+        match self {
+            Self::Fixed {
+                bits_per_entry,
+                num_probes,
+            } => ((*bits_per_entry).max(1), (*num_probes).max(1)),
+            Self::Adaptive => optimal_parameters((1.0 / f64::from(num_entries.max(1))).min(0.01)),
+        }
+    }
+}
+
+/// The bits per entry and number of probes which give a false positive rate of `rate`
+fn optimal_parameters(rate: f64) -> (u32, u32) {
+    let rate = rate.clamp(1e-6, 0.5);
+    let ln2 = std::f64::consts::LN_2;
+    let bits_per_entry = (-rate.ln() / (ln2 * ln2)).ceil();
+    let num_probes = (bits_per_entry * ln2).round().max(1.0);
+    (bits_per_entry as u32, num_probes as u32)
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize)]
 pub struct BloomFilter {
     num_entries: u32,
@@ -123,9 +185,16 @@ impl BloomFilter {
     }
 
     pub fn from_hashes<H: Borrow<ChangeHash>>(hashes: impl ExactSizeIterator<Item = H>) -> Self {
+        Self::from_hashes_with_config(hashes, BloomFilterConfig::default())
+    }
+
+    /// Like [`Self::from_hashes()`] but sized according to `config`
+    pub fn from_hashes_with_config<H: Borrow<ChangeHash>>(
+        hashes: impl ExactSizeIterator<Item = H>,
+        config: BloomFilterConfig,
+    ) -> Self {
         let num_entries = hashes.len() as u32;
-        let num_bits_per_entry = BITS_PER_ENTRY;
-        let num_probes = NUM_PROBES;
+        let (num_bits_per_entry, num_probes) = config.parameters(num_entries);
         let bits = vec![0; bits_capacity(num_entries, num_bits_per_entry)];
         let mut filter = Self {
             num_entries,
@@ -0,0 +1,96 @@
+use super::{BloomFilterConfig, Capability, Compression, Envelope, Priority, SyncPolicy};
+
+/// How to sync with a peer, kept apart from the [`super::State`] of the sync
+///
+/// The [`super::State`] for a peer records how far the sync has got and can be persisted,
+/// compared and cloned, a `Config` is what the application decides about the peer and is never
+/// persisted. Pass it to [`super::SyncDoc::generate_sync_message_with_config()`] and
+/// [`super::SyncDoc::receive_sync_message_with_config()`], the methods which don't take one use
+/// [`Config::default()`].
+///
+/// ```
+/// # use automerge::sync::{Compression, Config, State, SyncDoc, SyncPolicy};
+/// # use automerge::Automerge;
+/// let doc = Automerge::new();
+/// let mut state = State::new();
+/// let mut config = Config::default();
+/// config.policy = SyncPolicy::read_only();
+/// config.compression = Compression::Deflate;
+/// config.send_ephemeral(b"cursor at 3".to_vec());
+/// let message = doc.generate_sync_message_with_config(&mut state, &mut config);
+/// ```
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct Config {
+    /// Which changes to accept from the peer
+    pub policy: SyncPolicy,
+
+    /// Whether to ask the peer for a snapshot of the document's current state when we don't
+    /// have any of it yet
+    ///
+    /// The peer sends the snapshot in [`super::Message::snapshot`] before any of the history, so
+    /// a new peer can show the document before the whole history has arrived.
+    pub bootstrap_from_snapshot: bool,
+
+    /// Capabilities to advertise to the peer on top of the ones this library implements
+    ///
+    /// This lets applications layer their own protocol features on top of sync messages and only
+    /// use them once [`super::State::remote_supports()`] says the peer understands them.
+    pub extra_capabilities: Vec<Capability>,
+
+    /// How to compress the changes we send to the peer if it supports compression, see
+    /// [`super::Message::compression`]
+    pub compression: Compression,
+
+    /// How to size the bloom filters we send to the peer
+    pub bloom_filter: BloomFilterConfig,
+
+    /// Seal the changes in messages to and from the peer, see [`Envelope`]
+    pub envelope: Option<Envelope>,
+
+    /// Which changes to send the peer first when they don't all fit in one message, see
+    /// [`Priority`]
+    pub priority: Option<Priority>,
+
+    /// Keep each message within this many bytes, as
+    /// [`super::SyncDoc::generate_sync_message_with_max_size()`] does
+    pub max_message_size: Option<usize>,
+
+    /// Ephemeral payloads waiting to be sent in the next sync message, see
+    /// [`Self::send_ephemeral()`]
+    pub pending_ephemeral: Vec<Vec<u8>>,
+}
+
+impl Config {
+    /// Queue `payload` to be sent to the peer in the next sync message
+    ///
+    /// Ephemeral payloads are for state such as cursor positions or presence which the peer
+    /// should see but which should not be recorded in the document. The next call to
+    /// [`super::SyncDoc::generate_sync_message_with_config()`] returns a message carrying the
+    /// queued payloads even if there is nothing to sync, and the peer finds them in
+    /// [`super::Message::ephemeral`]. Payloads are lost if the message carrying them is.
+    pub fn send_ephemeral(&mut self, payload: Vec<u8>) {
+        self.pending_ephemeral.push(payload);
+    }
+
+    /// The capabilities we advertise to the peer
+    pub(crate) fn our_capabilities(&self) -> Vec<Capability> {
+        let mut caps = vec![
+            Capability::MessageV1,
+            Capability::MessageV2,
+            Capability::Ephemeral,
+            Capability::Chunking,
+            Capability::Compression,
+            Capability::Keepalive,
+        ];
+        if self.bootstrap_from_snapshot {
+            caps.push(Capability::StateSnapshot);
+        }
+        for cap in &self.extra_capabilities {
+            if !caps.contains(cap) {
+                caps.push(cap.clone());
+            }
+        }
+        caps
+    }
+}
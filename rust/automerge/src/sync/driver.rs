@@ -0,0 +1,338 @@
+//! An async driver which runs the sync protocol over a byte stream
+//!
+//! This module is only available with the `sync-driver` feature. A [`SyncDriver`] owns a
+//! document, the [`State`] for one peer and a transport implementing tokio's [`AsyncRead`] and
+//! [`AsyncWrite`], such as a TCP stream or a websocket adapter. Messages are framed on the
+//! transport with a four byte big endian length prefix, frames longer than
+//! [`SyncDriver::with_max_message_len()`] are rejected before anything is allocated for them.
+//! Patches for the changes received from the peer are sent down the channel returned by
+//! [`SyncDriver::new()`], changes made locally while the driver is running are sent to it with
+//! the sender returned by [`SyncDriver::local_changes()`].
+//!
+//! ```no_run
+//! # async fn example<S>(stream: S) -> Result<(), automerge::sync::driver::DriverError>
+//! # where
+//! #     S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+//! # {
+//! use automerge::{sync::driver::SyncDriver, Automerge};
+//!
+//! let (mut driver, patches) = SyncDriver::new(Automerge::new(), stream);
+//! // the patches can be consumed on another task while the driver runs
+//! driver.run().await?;
+//! let doc = driver.into_doc();
+//! # Ok(())
+//! # }
+//! ```
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+use super::{Config, Message, ReadMessageError, State, SyncDoc, SyncSource};
+use crate::patches::TextRepresentation;
+use crate::{Automerge, AutomergeError, Change, Patch, PatchLog};
+
+/// The longest message a driver accepts unless [`SyncDriver::with_max_message_len()`] is used
+pub const DEFAULT_MAX_MESSAGE_LEN: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum DriverError {
+    #[error("error reading or writing the transport: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("received an invalid sync message: {0}")]
+    InvalidMessage(#[from] ReadMessageError),
+    #[error(transparent)]
+    Automerge(#[from] AutomergeError),
+    #[error("message of {0} bytes is too large to send")]
+    MessageTooLarge(usize),
+    #[error("peer sent a message of {0} bytes, which is more than the maximum of {1}")]
+    ReceivedMessageTooLarge(usize, usize),
+}
+
+/// Runs the sync protocol for a document with one peer over `T`, see the [module
+/// documentation](self)
+#[derive(Debug)]
+pub struct SyncDriver<T> {
+    doc: Automerge,
+    state: State,
+    config: Config,
+    transport: T,
+    patches: UnboundedSender<Vec<Patch>>,
+    local_tx: UnboundedSender<Vec<Change>>,
+    local_rx: UnboundedReceiver<Vec<Change>>,
+    max_message_len: usize,
+    // bytes read from the transport which don't make up a whole frame yet
+    inbox: Vec<u8>,
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> SyncDriver<T> {
+    /// Create a driver syncing `doc` over `transport`, along with the receiving end of the channel
+    /// patches are sent to
+    ///
+    /// Dropping the receiver is fine if the patches are not needed.
+    pub fn new(doc: Automerge, transport: T) -> (Self, UnboundedReceiver<Vec<Patch>>) {
+        let (tx, rx) = unbounded_channel();
+        let (local_tx, local_rx) = unbounded_channel();
+        let driver = SyncDriver {
+            doc,
+            state: State::new(),
+            config: Config::default(),
+            transport,
+            patches: tx,
+            local_tx,
+            local_rx,
+            max_message_len: DEFAULT_MAX_MESSAGE_LEN,
+            inbox: Vec::new(),
+        };
+        (driver, rx)
+    }
+
+    /// Reject messages from the peer longer than `max_message_len` bytes, rather than
+    /// [`DEFAULT_MAX_MESSAGE_LEN`]
+    pub fn with_max_message_len(mut self, max_message_len: usize) -> Self {
+        self.max_message_len = max_message_len;
+        self
+    }
+
+    /// Start from a previously persisted sync state rather than a fresh one
+    pub fn with_state(mut self, state: State) -> Self {
+        self.state = state;
+        self
+    }
+
+    /// Sync as `config` says rather than as [`Config::default()`] does
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn doc(&self) -> &Automerge {
+        &self.doc
+    }
+
+    /// Get the document to make local changes, call [`Self::send_message()`] afterwards to send
+    /// them to the peer
+    pub fn doc_mut(&mut self) -> &mut Automerge {
+        &mut self.doc
+    }
+
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    pub fn into_doc(self) -> Automerge {
+        self.doc
+    }
+
+    pub fn into_parts(self) -> (Automerge, State, T) {
+        (self.doc, self.state, self.transport)
+    }
+
+    /// A sender for changes made locally while [`Self::run()`] is running
+    ///
+    /// Changes sent here are applied to the document and sent to the peer straight away, rather
+    /// than waiting for the peer's next message.
+    pub fn local_changes(&self) -> UnboundedSender<Vec<Change>> {
+        self.local_tx.clone()
+    }
+
+    /// Send a sync message to the peer if there is anything to tell them
+    ///
+    /// Returns whether a message was sent.
+    pub async fn send_message(&mut self) -> Result<bool, DriverError> {
+        let Some(msg) = self
+            .doc
+            .generate_sync_message_with_config(&mut self.state, &mut self.config)
+        else {
+            return Ok(false);
+        };
+        let encoded = msg.encode();
+        let len = u32::try_from(encoded.len())
+            .map_err(|_| DriverError::MessageTooLarge(encoded.len()))?;
+        self.transport.write_all(&len.to_be_bytes()).await?;
+        self.transport.write_all(&encoded).await?;
+        self.transport.flush().await?;
+        Ok(true)
+    }
+
+    /// Wait for a message from the peer and apply it
+    ///
+    /// Returns `false` if the peer closed the transport rather than sending a message.
+    pub async fn receive_message(&mut self) -> Result<bool, DriverError> {
+        match read_frame(&mut self.transport, &mut self.inbox, self.max_message_len).await? {
+            Some(encoded) => {
+                self.apply_message(&encoded)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn apply_message(&mut self, encoded: &[u8]) -> Result<(), DriverError> {
+        let msg = Message::decode(encoded)?;
+        let mut patch_log = PatchLog::active(TextRepresentation::default());
+        self.doc
+            .receive_sync_message_inner(&mut self.state, &self.config, msg, &mut patch_log)?;
+        let patches = self.doc.make_patches(&mut patch_log);
+        if !patches.is_empty() {
+            // nobody listening for patches is not an error
+            let _ = self.patches.send(patches);
+        }
+        Ok(())
+    }
+
+    /// Exchange messages with the peer until it closes the transport
+    ///
+    /// Each message received, and each batch of changes sent to [`Self::local_changes()`], is
+    /// followed by a message to the peer if there is anything new to tell them.
+    pub async fn run(&mut self) -> Result<(), DriverError> {
+        self.send_message().await?;
+        loop {
+            let event = tokio::select! {
+                frame = read_frame(&mut self.transport, &mut self.inbox, self.max_message_len) => {
+                    Event::Frame(frame?)
+                }
+                Some(changes) = self.local_rx.recv() => Event::LocalChanges(changes),
+            };
+            match event {
+                Event::Frame(Some(encoded)) => self.apply_message(&encoded)?,
+                Event::Frame(None) => return Ok(()),
+                Event::LocalChanges(changes) => self.doc.apply_changes(changes)?,
+            }
+            self.send_message().await?;
+        }
+    }
+}
+
+enum Event {
+    Frame(Option<Vec<u8>>),
+    LocalChanges(Vec<Change>),
+}
+
+/// Read the next frame from `transport`, or `None` if it was closed between frames
+///
+/// Partial frames are kept in `inbox`, so this is cancel safe and can be used in `select!`.
+async fn read_frame<T: AsyncRead + Unpin>(
+    transport: &mut T,
+    inbox: &mut Vec<u8>,
+    max_len: usize,
+) -> Result<Option<Vec<u8>>, DriverError> {
+    loop {
+        if let Some(len) = inbox.get(..4) {
+            let len = u32::from_be_bytes(len.try_into().unwrap()) as usize;
+            if len > max_len {
+                return Err(DriverError::ReceivedMessageTooLarge(len, max_len));
+            }
+            if inbox.len() >= 4 + len {
+                let frame = inbox[4..4 + len].to_vec();
+                inbox.drain(..4 + len);
+                return Ok(Some(frame));
+            }
+        }
+        inbox.reserve(4096);
+        if transport.read_buf(inbox).await? == 0 {
+            if inbox.is_empty() {
+                return Ok(None);
+            }
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::Transactable;
+    use crate::ROOT;
+
+    #[test]
+    fn drivers_sync_documents_and_report_patches() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let (left, right) = tokio::io::duplex(1024);
+            let mut doc1 = Automerge::new();
+            let mut tx = doc1.transaction();
+            tx.put(ROOT, "key", "value").unwrap();
+            tx.commit();
+
+            let (mut driver1, _) = SyncDriver::new(doc1, left);
+            let (mut driver2, mut patches) = SyncDriver::new(Automerge::new(), right);
+            loop {
+                let sent1 = driver1.send_message().await.unwrap();
+                if sent1 {
+                    driver2.receive_message().await.unwrap();
+                }
+                let sent2 = driver2.send_message().await.unwrap();
+                if sent2 {
+                    driver1.receive_message().await.unwrap();
+                }
+                if !sent1 && !sent2 {
+                    break;
+                }
+            }
+
+            assert_eq!(driver1.doc().get_heads(), driver2.doc().get_heads());
+            let received = patches.try_recv().unwrap();
+            assert_eq!(received.len(), 1);
+
+            let (_, _, left) = driver1.into_parts();
+            drop(left);
+            assert!(!driver2.receive_message().await.unwrap());
+        });
+    }
+    #[test]
+    fn messages_longer_than_the_maximum_are_rejected() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let (left, right) = tokio::io::duplex(1024);
+            let mut doc1 = Automerge::new();
+            let mut tx = doc1.transaction();
+            tx.put(ROOT, "key", "value").unwrap();
+            tx.commit();
+
+            let (mut driver1, _) = SyncDriver::new(doc1, left);
+            let (driver2, _) = SyncDriver::new(Automerge::new(), right);
+            let mut driver2 = driver2.with_max_message_len(8);
+            assert!(driver1.send_message().await.unwrap());
+            assert!(matches!(
+                driver2.receive_message().await,
+                Err(DriverError::ReceivedMessageTooLarge(_, 8))
+            ));
+        });
+    }
+
+    #[test]
+    fn running_drivers_send_local_changes() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let (left, right) = tokio::io::duplex(1024);
+            let (mut driver1, _) = SyncDriver::new(Automerge::new(), left);
+            let (mut driver2, _) = SyncDriver::new(Automerge::new(), right);
+
+            let mut doc = Automerge::new();
+            let mut tx = doc.transaction();
+            tx.put(ROOT, "key", "value").unwrap();
+            tx.commit();
+            let heads = doc.get_heads();
+            let expected = heads.clone();
+            let changes = doc.get_changes(&[]).into_iter().cloned().collect();
+            driver2.local_changes().send(changes).unwrap();
+
+            let peer = async move {
+                while driver1.doc().get_heads() != heads {
+                    assert!(driver1.receive_message().await.unwrap());
+                    driver1.send_message().await.unwrap();
+                }
+                // closing the transport stops the other driver
+                drop(driver1.into_parts());
+            };
+            let (result, ()) = tokio::join!(driver2.run(), peer);
+            result.unwrap();
+            assert_eq!(driver2.doc().get_heads(), expected);
+        });
+    }
+}
@@ -0,0 +1,129 @@
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use sha2::{Digest, Sha256};
+
+use super::{encode_many, ChunkList, Compression, Message};
+use crate::storage::parse;
+use crate::AutomergeError;
+
+/// An authenticated cipher for the changes in sync messages, see [`Envelope`]
+///
+/// Implement this with an AEAD such as ChaCha20-Poly1305 or AES-GCM and a key shared by the
+/// peers. Each call to [`Self::seal()`] must use a fresh nonce, which should be included in the
+/// sealed bytes so that [`Self::open()`] can find it.
+pub trait PayloadCipher: Send + Sync {
+    /// Encrypt and authenticate `plaintext`
+    fn seal(&self, plaintext: &[u8]) -> Vec<u8>;
+
+    /// Check and decrypt bytes produced by [`Self::seal()`]
+    fn open(&self, sealed: &[u8]) -> Result<Vec<u8>, OpenError>;
+
+    /// Encrypt and authenticate `plaintext` along with `associated_data`, which is authenticated
+    /// but neither encrypted nor included in the output
+    ///
+    /// The same associated data has to be passed to [`Self::open_with_associated_data()`]. The
+    /// default seals a hash of the associated data in front of the plaintext, an AEAD can
+    /// override this to pass the associated data to the cipher directly.
+    fn seal_with_associated_data(&self, plaintext: &[u8], associated_data: &[u8]) -> Vec<u8> {
+        let mut input = Sha256::digest(associated_data).to_vec();
+        input.extend(plaintext);
+        self.seal(&input)
+    }
+
+    /// Check and decrypt bytes produced by [`Self::seal_with_associated_data()`], failing if
+    /// `associated_data` isn't what they were sealed with
+    fn open_with_associated_data(
+        &self,
+        sealed: &[u8],
+        associated_data: &[u8],
+    ) -> Result<Vec<u8>, OpenError> {
+        let mut opened = self.open(sealed)?;
+        let digest = Sha256::digest(associated_data);
+        if opened.get(..digest.len()) != Some(&digest[..]) {
+            return Err(OpenError);
+        }
+        opened.drain(..digest.len());
+        Ok(opened)
+    }
+}
+
+/// Returned by [`PayloadCipher::open()`] for a payload which could not be authenticated
+#[derive(Debug, thiserror::Error)]
+#[error("unable to open sealed payload")]
+pub struct OpenError;
+
+/// Seals the changes and snapshots in sync messages with a [`PayloadCipher`]
+///
+/// Set an envelope on the [`super::Config`] for a peer with [`super::Config::envelope`] and the
+/// changes and snapshots in messages to the peer are sealed, while the heads, needs, bloom
+/// filters and capabilities are left in the clear. This lets an untrusted relay route messages
+/// and even take part in working out what each side is missing without being able to read or
+/// alter the document. Once an envelope is set messages from the peer must be sealed too, a
+/// message with changes which aren't sealed fails with [`AutomergeError::InvalidEnvelope`].
+///
+/// Sealed payloads can't be compressed, so [`Message::compression`] is not used for them.
+#[derive(Clone)]
+pub struct Envelope(Arc<dyn PayloadCipher>);
+
+impl Envelope {
+    pub fn new<C: PayloadCipher + 'static>(cipher: C) -> Self {
+        Self(Arc::new(cipher))
+    }
+
+    /// Replace the changes and snapshot in `message` with their sealed form
+    pub(crate) fn seal(&self, message: &mut Message) {
+        if !message.changes.is_empty() {
+            let mut plaintext = Vec::new();
+            encode_many(&mut plaintext, message.changes.iter(), |buf, change| {
+                leb128::write::unsigned(buf, change.len() as u64).unwrap();
+                buf.extend::<&[u8]>(change)
+            });
+            message.changes = ChunkList::from(vec![self.0.seal(&plaintext)]);
+        }
+        if let Some(snapshot) = message.snapshot.as_mut() {
+            *snapshot = self.0.seal(snapshot);
+        }
+        message.compression = Compression::None;
+        message.sealed = true;
+    }
+
+    /// Open the changes and snapshot sealed by [`Self::seal()`]
+    pub(crate) fn open(&self, message: &mut Message) -> Result<(), AutomergeError> {
+        if !message.sealed {
+            if message.changes.is_empty() && message.snapshot.is_none() {
+                return Ok(());
+            }
+            return Err(AutomergeError::InvalidEnvelope(
+                "the peer sent changes which were not sealed".to_string(),
+            ));
+        }
+        let open = |sealed: &[u8]| {
+            self.0
+                .open(sealed)
+                .map_err(|e| AutomergeError::InvalidEnvelope(e.to_string()))
+        };
+        if !message.changes.is_empty() {
+            if message.changes.len() != 1 {
+                return Err(AutomergeError::InvalidEnvelope(
+                    "expected a single sealed chunk of changes".to_string(),
+                ));
+            }
+            let plaintext = open(message.changes.iter().next().unwrap())?;
+            let (_, changes) = ChunkList::parse(parse::Input::new(&plaintext))
+                .map_err(|e| AutomergeError::InvalidEnvelope(e.to_string()))?;
+            message.changes = changes;
+        }
+        if let Some(snapshot) = message.snapshot.as_mut() {
+            *snapshot = open(snapshot)?;
+        }
+        message.sealed = false;
+        Ok(())
+    }
+}
+
+impl Debug for Envelope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Envelope")
+    }
+}
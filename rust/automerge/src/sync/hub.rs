@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use super::{Config, Message, State, SyncDoc};
+use crate::{Automerge, AutomergeError, ChangeHash};
+
+/// The sync states for every peer a document is being synced with
+///
+/// A hub keeps a [`State`] and a [`Config`] for each peer, identified by any `P` the application
+/// uses for them, and generates and receives messages for them. A change only needs sending to a peer once,
+/// so after receiving changes from one peer [`Self::generate_messages()`] forwards them to the
+/// other peers and not back to the one they came from. A peer which has not been added yet is
+/// added the first time a message is received from it.
+///
+/// ```
+/// # use automerge::{sync::{Hub, SyncDoc}, transaction::Transactable, AutoCommit, ROOT};
+/// let mut server = AutoCommit::new();
+/// server.put(ROOT, "key", "value").unwrap();
+/// let mut hub = Hub::new();
+/// hub.add_peer("alice");
+/// hub.add_peer("bob");
+/// for (peer, message) in hub.generate_messages(&server.sync()) {
+///     // send `message` to `peer`
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Hub<P> {
+    peers: HashMap<P, Peer>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Peer {
+    state: State,
+    config: Config,
+}
+
+/// How far along syncing with one peer is, returned by [`Hub::progress()`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerProgress {
+    /// Whether the peer's heads are the same as ours
+    pub in_sync: bool,
+    /// The heads the peer last told us about, `None` if we have not heard from them
+    pub their_heads: Option<Vec<ChangeHash>>,
+    /// The heads we know both we and the peer have
+    pub shared_heads: Vec<ChangeHash>,
+    /// The number of changes sent to the peer which they have not yet confirmed having
+    pub unconfirmed_changes: usize,
+}
+
+impl<P: Eq + Hash + Clone> Hub<P> {
+    pub fn new() -> Self {
+        Self {
+            peers: HashMap::new(),
+        }
+    }
+
+    /// Start syncing with `peer` from scratch, this does nothing if `peer` is already known
+    pub fn add_peer(&mut self, peer: P) {
+        self.peers.entry(peer).or_default();
+    }
+
+    /// Start syncing with `peer` from a persisted `state`, replacing any state already held
+    /// for it
+    pub fn add_peer_with_state(&mut self, peer: P, state: State) {
+        self.peers.entry(peer).or_default().state = state;
+    }
+
+    /// Stop syncing with `peer`, returning its state so that it can be persisted
+    pub fn remove_peer(&mut self, peer: &P) -> Option<State> {
+        self.peers.remove(peer).map(|p| p.state)
+    }
+
+    pub fn peers(&self) -> impl Iterator<Item = &P> {
+        self.peers.keys()
+    }
+
+    pub fn state(&self, peer: &P) -> Option<&State> {
+        self.peers.get(peer).map(|p| &p.state)
+    }
+
+    /// The [`Config`] used to sync with `peer`, if it is known
+    pub fn config_mut(&mut self, peer: &P) -> Option<&mut Config> {
+        self.peers.get_mut(peer).map(|p| &mut p.config)
+    }
+
+    /// Compact the state of every peer, see [`State::compact()`]
+    pub fn compact(&mut self, doc: &Automerge) {
+        for peer in self.peers.values_mut() {
+            peer.state.compact(doc);
+        }
+    }
+
+    /// Generate a message for `peer`, if it is known and there is anything to tell it
+    pub fn generate_message<D: SyncDoc + ?Sized>(&mut self, doc: &D, peer: &P) -> Option<Message> {
+        let Peer { state, config } = self.peers.get_mut(peer)?;
+        doc.generate_sync_message_with_config(state, config)
+    }
+
+    /// Generate a message for every peer which there is anything to tell
+    pub fn generate_messages<D: SyncDoc + ?Sized>(&mut self, doc: &D) -> Vec<(P, Message)> {
+        self.peers
+            .iter_mut()
+            .filter_map(|(peer, Peer { state, config })| {
+                doc.generate_sync_message_with_config(state, config)
+                    .map(|msg| (peer.clone(), msg))
+            })
+            .collect()
+    }
+
+    /// Apply a message received from `peer` to `doc`
+    pub fn receive_message<D: SyncDoc + ?Sized>(
+        &mut self,
+        doc: &mut D,
+        peer: P,
+        message: Message,
+    ) -> Result<(), AutomergeError> {
+        let Peer { state, config } = self.peers.entry(peer).or_default();
+        doc.receive_sync_message_with_config(state, config, message)
+    }
+
+    /// How far along syncing with `peer` is, given the current heads of the document
+    pub fn progress(&self, peer: &P, our_heads: &[ChangeHash]) -> Option<PeerProgress> {
+        let state = &self.peers.get(peer)?.state;
+        Some(PeerProgress {
+            in_sync: state.their_heads.as_deref() == Some(our_heads),
+            their_heads: state.their_heads.clone(),
+            shared_heads: state.shared_heads.clone(),
+            unconfirmed_changes: state.sent_hashes.len(),
+        })
+    }
+}
+
+impl<P: Eq + Hash + Clone> Default for Hub<P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::Transactable;
+    use crate::{AutoCommit, ROOT};
+
+    #[test]
+    fn hub_forwards_changes_between_peers() {
+        let mut server = AutoCommit::new();
+        let mut hub = Hub::new();
+        let mut clients = [AutoCommit::new(), AutoCommit::new(), AutoCommit::new()];
+        let mut client_states = [State::new(), State::new(), State::new()];
+        clients[0].put(ROOT, "from", "client 0").unwrap();
+
+        for _ in 0..20 {
+            let mut quiet = true;
+            for (i, (client, state)) in clients.iter_mut().zip(client_states.iter_mut()).enumerate()
+            {
+                if let Some(msg) = client.sync().generate_sync_message(state) {
+                    quiet = false;
+                    hub.receive_message(&mut server.sync(), i, msg).unwrap();
+                }
+            }
+            for (i, msg) in hub.generate_messages(&server.sync()) {
+                quiet = false;
+                clients[i]
+                    .sync()
+                    .receive_sync_message(&mut client_states[i], msg)
+                    .unwrap();
+            }
+            if quiet {
+                break;
+            }
+        }
+
+        let heads = server.get_heads();
+        for (i, client) in clients.iter_mut().enumerate() {
+            assert_eq!(client.get_heads(), heads);
+            let progress = hub.progress(&i, &heads).unwrap();
+            assert!(progress.in_sync);
+            assert_eq!(progress.unconfirmed_changes, 0);
+        }
+        assert_eq!(hub.peers().count(), 3);
+        assert!(hub.remove_peer(&0).is_some());
+        assert!(hub.progress(&0, &heads).is_none());
+    }
+}
@@ -1,6 +1,6 @@
 use crate::{Change, ChangeHash};
 
-use super::{Capability, Have, Message, MessageVersion};
+use super::{Capability, Compression, Have, Message, MessageVersion};
 
 pub(super) struct MessageBuilder {
     heads: Vec<ChangeHash>,
@@ -9,6 +9,9 @@ pub(super) struct MessageBuilder {
     changes: Vec<Vec<u8>>,
     supported_capabilities: Option<Vec<Capability>>,
     version: MessageVersion,
+    ephemeral: Vec<Vec<u8>>,
+    snapshot: Option<Vec<u8>>,
+    compression: Compression,
 }
 
 impl MessageBuilder {
@@ -20,6 +23,9 @@ impl MessageBuilder {
             changes: changes.map(|c| c.raw_bytes().to_vec()).collect(),
             supported_capabilities: None,
             version: MessageVersion::V1,
+            ephemeral: Vec::new(),
+            snapshot: None,
+            compression: Compression::None,
         }
     }
 
@@ -35,6 +41,9 @@ impl MessageBuilder {
             have: Vec::new(),
             supported_capabilities: None,
             version: MessageVersion::V2,
+            ephemeral: Vec::new(),
+            snapshot: None,
+            compression: Compression::None,
         }
     }
 
@@ -61,6 +70,21 @@ impl MessageBuilder {
         self
     }
 
+    pub(super) fn ephemeral(mut self, ephemeral: Vec<Vec<u8>>) -> Self {
+        self.ephemeral = ephemeral;
+        self
+    }
+
+    pub(super) fn snapshot(mut self, snapshot: Vec<u8>) -> Self {
+        self.snapshot = Some(snapshot);
+        self
+    }
+
+    pub(super) fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
     pub(super) fn build(self) -> Message {
         Message {
             heads: self.heads,
@@ -69,6 +93,11 @@ impl MessageBuilder {
             changes: super::ChunkList::from(self.changes),
             supported_capabilities: self.supported_capabilities,
             version: self.version,
+            ephemeral: self.ephemeral,
+            snapshot: self.snapshot,
+            compression: self.compression,
+            sealed: false,
+            keepalive: false,
         }
     }
 
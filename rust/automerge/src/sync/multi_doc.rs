@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+
+use super::{encode_many, Config, Message, ReadMessageError, State, SyncDoc};
+use crate::storage::parse;
+use crate::AutomergeError;
+
+const MESSAGE_TYPE_MULTI_DOC: u8 = 0x47; // first byte of a multi document message
+
+/// The sync states for many documents synced with one peer over a single connection
+///
+/// A server which holds many documents for each client would otherwise need a connection, or
+/// its own framing, for each document. A `MultiDocState` keeps a [`State`] for each document,
+/// identified by the document ID the application uses for it, and bundles the messages for all
+/// of them into one [`MultiDocMessage`] which is sent in a single frame. A document which has not
+/// been added yet is added the first time a message is generated or received for it.
+///
+/// ```
+/// # use std::collections::HashMap;
+/// # use automerge::{sync::{MultiDocMessage, MultiDocState}, transaction::Transactable, AutoCommit, ROOT};
+/// let mut ours = HashMap::new();
+/// for id in ["a", "b"] {
+///     let mut doc = AutoCommit::new();
+///     doc.put(ROOT, "id", id).unwrap();
+///     ours.insert(id.to_string(), doc);
+/// }
+/// let mut theirs: HashMap<String, AutoCommit> = HashMap::new();
+/// let (mut our_state, mut their_state) = (MultiDocState::new(), MultiDocState::new());
+///
+/// let docs = ours
+///     .iter_mut()
+///     .map(|(id, doc)| (id.as_str(), doc.sync()))
+///     .collect::<Vec<_>>();
+/// let message = our_state
+///     .generate_message(docs.iter().map(|(id, doc)| (*id, doc)))
+///     .unwrap();
+/// let message = MultiDocMessage::decode(&message.encode()).unwrap();
+/// for (id, message) in message {
+///     let doc = theirs.entry(id.clone()).or_default();
+///     their_state.receive_message(&id, &mut doc.sync(), message).unwrap();
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MultiDocState {
+    states: HashMap<String, State>,
+    configs: HashMap<String, Config>,
+}
+
+impl MultiDocState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_document(&mut self, doc_id: &str) {
+        self.states.entry(doc_id.to_string()).or_default();
+    }
+
+    /// Add a document with an existing sync state, for example one restored with
+    /// [`State::decode()`]
+    pub fn add_document_with_state(&mut self, doc_id: &str, state: State) {
+        self.states.insert(doc_id.to_string(), state);
+    }
+
+    pub fn remove_document(&mut self, doc_id: &str) -> Option<State> {
+        self.configs.remove(doc_id);
+        self.states.remove(doc_id)
+    }
+
+    pub fn documents(&self) -> impl Iterator<Item = &str> {
+        self.states.keys().map(|id| id.as_str())
+    }
+
+    pub fn state(&self, doc_id: &str) -> Option<&State> {
+        self.states.get(doc_id)
+    }
+
+    pub fn state_mut(&mut self, doc_id: &str) -> Option<&mut State> {
+        self.states.get_mut(doc_id)
+    }
+
+    /// The [`Config`] used to sync `doc_id`, if it has been added
+    pub fn config_mut(&mut self, doc_id: &str) -> Option<&mut Config> {
+        if !self.states.contains_key(doc_id) {
+            return None;
+        }
+        Some(self.configs.entry(doc_id.to_string()).or_default())
+    }
+
+    /// Generate the messages for each of `docs` and bundle them into one message, or [`None`] if
+    /// none of them have anything to send
+    pub fn generate_message<'a, D, I>(&mut self, docs: I) -> Option<MultiDocMessage>
+    where
+        D: SyncDoc + ?Sized + 'a,
+        I: IntoIterator<Item = (&'a str, &'a D)>,
+    {
+        let mut message = MultiDocMessage::default();
+        for (doc_id, doc) in docs {
+            let state = self.states.entry(doc_id.to_string()).or_default();
+            let config = self.configs.entry(doc_id.to_string()).or_default();
+            if let Some(doc_message) = doc.generate_sync_message_with_config(state, config) {
+                message.push(doc_id, doc_message);
+            }
+        }
+        Some(message).filter(|message| !message.is_empty())
+    }
+
+    /// Receive the part of a [`MultiDocMessage`] for `doc_id`
+    pub fn receive_message<D: SyncDoc + ?Sized>(
+        &mut self,
+        doc_id: &str,
+        doc: &mut D,
+        message: Message,
+    ) -> Result<(), AutomergeError> {
+        let state = self.states.entry(doc_id.to_string()).or_default();
+        let config = self.configs.entry(doc_id.to_string()).or_default();
+        doc.receive_sync_message_with_config(state, config, message)
+    }
+}
+
+/// The sync messages for several documents, sent over one connection, see [`MultiDocState`]
+///
+/// A message identical to one for another document in the bundle, as the first message for each
+/// of many documents the sender doesn't have yet or which are already in sync often is, is only
+/// encoded once.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MultiDocMessage {
+    messages: Vec<(String, Message)>,
+}
+
+impl MultiDocMessage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, doc_id: &str, message: Message) {
+        self.messages.push((doc_id.to_string(), message));
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (&str, &Message)> {
+        self.messages
+            .iter()
+            .map(|(doc_id, message)| (doc_id.as_str(), message))
+    }
+
+    pub fn encode(self) -> Vec<u8> {
+        let mut bodies: Vec<Vec<u8>> = Vec::new();
+        let mut index: HashMap<Vec<u8>, usize> = HashMap::new();
+        let mut entries = Vec::with_capacity(self.messages.len());
+        for (doc_id, message) in self.messages {
+            let body = message.encode();
+            let body_index = *index.entry(body.clone()).or_insert_with(|| {
+                bodies.push(body);
+                bodies.len() - 1
+            });
+            entries.push((doc_id, body_index));
+        }
+
+        let mut buf = vec![MESSAGE_TYPE_MULTI_DOC];
+        encode_many(&mut buf, bodies.iter(), |buf, body| {
+            leb128::write::unsigned(buf, body.len() as u64).unwrap();
+            buf.extend(body)
+        });
+        encode_many(&mut buf, entries.iter(), |buf, (doc_id, body_index)| {
+            leb128::write::unsigned(buf, doc_id.len() as u64).unwrap();
+            buf.extend(doc_id.as_bytes());
+            leb128::write::unsigned(buf, *body_index as u64).unwrap();
+        });
+        buf
+    }
+
+    pub fn decode(input: &[u8]) -> Result<Self, ReadMessageError> {
+        match Self::parse(parse::Input::new(input)) {
+            Ok((_, msg)) => Ok(msg),
+            Err(parse::ParseError::Error(e)) => Err(e),
+            Err(parse::ParseError::Incomplete(_)) => Err(ReadMessageError::NotEnoughInput),
+        }
+    }
+
+    fn parse(input: parse::Input<'_>) -> parse::ParseResult<'_, Self, ReadMessageError> {
+        let (i, first_byte) = parse::take1(input)?;
+        if first_byte != MESSAGE_TYPE_MULTI_DOC {
+            return Err(parse::ParseError::Error(ReadMessageError::WrongType {
+                expected_one_of: vec![MESSAGE_TYPE_MULTI_DOC],
+                found: first_byte,
+            }));
+        }
+        let body_parser = |i| {
+            let (i, bytes) = parse::length_prefixed_bytes(i)?;
+            let message = Message::decode(bytes).map_err(parse::ParseError::Error)?;
+            Ok((i, message))
+        };
+        let (i, bodies) = parse::length_prefixed(body_parser)(i)?;
+        let entry_parser = |i| {
+            let (i, doc_id) = parse::length_prefixed_bytes(i)?;
+            let doc_id = String::from_utf8(doc_id.to_vec()).map_err(|_| {
+                parse::ParseError::Error(ReadMessageError::Parse(
+                    "document ID was not valid UTF-8".to_string(),
+                ))
+            })?;
+            let (i, body_index) = parse::leb128_u64(i)?;
+            let message = bodies.get(body_index as usize).cloned().ok_or_else(|| {
+                parse::ParseError::Error(ReadMessageError::Parse(format!(
+                    "message index {} is out of bounds",
+                    body_index
+                )))
+            })?;
+            Ok((i, (doc_id, message)))
+        };
+        let (i, messages) = parse::length_prefixed(entry_parser)(i)?;
+        Ok((i, Self { messages }))
+    }
+}
+
+impl IntoIterator for MultiDocMessage {
+    type Item = (String, Message);
+    type IntoIter = std::vec::IntoIter<(String, Message)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.messages.into_iter()
+    }
+}
@@ -0,0 +1,154 @@
+use std::collections::{BTreeSet, HashSet};
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use crate::{ActorId, AutomergeError, Change, ChangeHash};
+
+/// Which changes to accept from a peer
+///
+/// The policy for a peer is [`super::Config::policy`] and is checked by
+/// [`super::SyncDoc::receive_sync_message_with_config()`] before any changes are applied. By default every
+/// change is accepted. Changes which the policy does not allow either make the whole message
+/// fail with [`AutomergeError::RejectedChanges`], or with [`Self::dropping_disallowed()`] are
+/// left out, along with any changes which depend on them, while the rest are applied.
+///
+/// Checks beyond which actor made a change, such as quotas on the size of changes or which
+/// objects a peer may modify, can be added with [`Self::validate_with()`].
+#[derive(Debug, Clone, Default)]
+pub struct SyncPolicy {
+    allowed_actors: Option<BTreeSet<ActorId>>,
+    drop_disallowed: bool,
+    validator: Option<Validator>,
+}
+
+type ValidatorFn = dyn Fn(&Change) -> Result<(), String> + Send + Sync;
+
+/// A validator passed to [`SyncPolicy::validate_with()`]
+#[derive(Clone)]
+struct Validator(Arc<ValidatorFn>);
+
+impl Debug for Validator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Validator")
+    }
+}
+
+/// A change which a [`SyncPolicy`] did not allow, see [`AutomergeError::RejectedChanges`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RejectedChange {
+    /// The hash of the change
+    pub hash: ChangeHash,
+    /// The actor which made the change
+    pub actor: ActorId,
+    /// Why the change was rejected
+    pub reason: String,
+}
+
+impl SyncPolicy {
+    /// Accept every change, this is the default
+    pub fn accept_all() -> Self {
+        Self::default()
+    }
+
+    /// Accept no changes at all, for peers which may only read the document
+    pub fn read_only() -> Self {
+        Self {
+            allowed_actors: Some(BTreeSet::new()),
+            ..Default::default()
+        }
+    }
+
+    /// Only accept changes made by `actors`
+    pub fn allow_actors<I: IntoIterator<Item = ActorId>>(actors: I) -> Self {
+        Self {
+            allowed_actors: Some(actors.into_iter().collect()),
+            ..Default::default()
+        }
+    }
+
+    /// Leave out changes which are not allowed rather than rejecting the whole message
+    pub fn dropping_disallowed(mut self) -> Self {
+        self.drop_disallowed = true;
+        self
+    }
+
+    /// Also check each change with `validate`, which returns the reason for rejecting a change
+    /// as an error
+    ///
+    /// `validate` sees each [`Change`] before it is applied, so it can look at the actor, the
+    /// size of the change ([`Change::raw_bytes()`]) and the operations it contains
+    /// ([`Change::iter_ops()`]) to enforce quotas or permissions. Only changes from actors the
+    /// policy otherwise allows are passed to it.
+    ///
+    /// ```
+    /// # use automerge::sync::SyncPolicy;
+    /// let policy = SyncPolicy::accept_all().validate_with(|change| {
+    ///     if change.len() > 1000 {
+    ///         Err(format!("{} ops is more than the limit of 1000", change.len()))
+    ///     } else {
+    ///         Ok(())
+    ///     }
+    /// });
+    /// ```
+    pub fn validate_with<F>(mut self, validate: F) -> Self
+    where
+        F: Fn(&Change) -> Result<(), String> + Send + Sync + 'static,
+    {
+        self.validator = Some(Validator(Arc::new(validate)));
+        self
+    }
+
+    pub(crate) fn accepts_all(&self) -> bool {
+        self.allowed_actors.is_none() && self.validator.is_none()
+    }
+
+    /// Why this policy doesn't allow `change`, if it doesn't
+    fn check(&self, change: &Change) -> Result<(), String> {
+        if let Some(actors) = &self.allowed_actors {
+            if !actors.contains(change.actor_id()) {
+                return Err(format!("actor {} is not allowed", change.actor_id()));
+            }
+        }
+        match &self.validator {
+            Some(Validator(validate)) => validate(change),
+            None => Ok(()),
+        }
+    }
+
+    /// The changes from `changes` which this policy allows to be applied
+    pub(crate) fn filter(&self, changes: Vec<Change>) -> Result<Vec<Change>, AutomergeError> {
+        if self.accepts_all() {
+            return Ok(changes);
+        }
+        if !self.drop_disallowed {
+            let rejected = changes
+                .iter()
+                .filter_map(|change| {
+                    self.check(change).err().map(|reason| RejectedChange {
+                        hash: change.hash(),
+                        actor: change.actor_id().clone(),
+                        reason,
+                    })
+                })
+                .collect::<Vec<_>>();
+            if !rejected.is_empty() {
+                return Err(AutomergeError::RejectedChanges(rejected));
+            }
+            return Ok(changes);
+        }
+        // changes arrive in causal order, so a change's dependencies are always dropped before it
+        // is looked at
+        let mut dropped = HashSet::new();
+        Ok(changes
+            .into_iter()
+            .filter(|change| {
+                let keep = !change.deps().iter().any(|dep| dropped.contains(dep))
+                    && self.check(change).is_ok();
+                if !keep {
+                    dropped.insert(change.hash());
+                }
+                keep
+            })
+            .collect())
+    }
+}
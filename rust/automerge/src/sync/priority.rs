@@ -0,0 +1,76 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use crate::{ActorId, Change, ChangeHash};
+
+type PriorityFn = dyn Fn(&Change) -> i32 + Send + Sync;
+
+/// Decides which changes to send a peer first, see [`super::Config::priority`]
+///
+/// When a peer is missing more changes than fit in one message, see
+/// [`super::SyncDoc::generate_sync_message_with_max_size()`], the changes with the highest
+/// priority are sent first, so for example the edits a user is looking at can arrive before the
+/// bulk of the history. A change can't be applied without its dependencies, so the dependencies
+/// of a change are sent with at least the same priority as the change itself, and changes of the
+/// same priority are sent in the order they were made. Changes get priority `0` unless they are
+/// given another.
+///
+/// ```
+/// # use automerge::{sync::{Config, Priority}, ActorId};
+/// let viewer = ActorId::random();
+/// let mut config = Config::default();
+/// config.priority = Some(Priority::new(move |change| {
+///     if change.actor_id() == &viewer && change.len() < 100 {
+///         1
+///     } else {
+///         0
+///     }
+/// }));
+/// ```
+#[derive(Clone)]
+pub struct Priority(Arc<PriorityFn>);
+
+impl Priority {
+    /// Give each change the priority returned by `priority`, higher priorities are sent first
+    pub fn new<F>(priority: F) -> Self
+    where
+        F: Fn(&Change) -> i32 + Send + Sync + 'static,
+    {
+        Self(Arc::new(priority))
+    }
+
+    /// Send the changes made by `actors` before any others
+    pub fn actors<I: IntoIterator<Item = ActorId>>(actors: I) -> Self {
+        let actors = actors.into_iter().collect::<BTreeSet<_>>();
+        Self::new(move |change| i32::from(actors.contains(change.actor_id())))
+    }
+
+    /// `changes`, which are in causal order, sorted so the highest priority changes come first
+    /// while every change still comes after its dependencies
+    pub(crate) fn order<'a>(&self, changes: Vec<&'a Change>) -> Vec<&'a Change> {
+        let mut priorities = changes
+            .iter()
+            .map(|change| (change.hash(), (self.0)(change)))
+            .collect::<HashMap<ChangeHash, i32>>();
+        // a dependency is needed as soon as anything which depends on it is
+        for change in changes.iter().rev() {
+            let priority = priorities[&change.hash()];
+            for dep in change.deps() {
+                if let Some(dep_priority) = priorities.get_mut(dep) {
+                    *dep_priority = (*dep_priority).max(priority);
+                }
+            }
+        }
+        let mut changes = changes;
+        // the sort is stable so changes of the same priority stay in causal order
+        changes.sort_by_key(|change| std::cmp::Reverse(priorities[&change.hash()]));
+        changes
+    }
+}
+
+impl Debug for Priority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Priority")
+    }
+}
@@ -0,0 +1,286 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use super::{
+    load_sync_changes, ChunkList, Config, Message, State, SyncDoc, SyncMessages, SyncPolicy,
+    SyncSource,
+};
+use crate::{
+    exid::ExId, patches::TextRepresentation, Automerge, AutomergeError, Change, ChangeHash,
+    PatchLog,
+};
+
+/// Takes part in the sync protocol with only the changes of a document, never the document
+/// itself
+///
+/// A relay sits between peers which can't reach each other directly, receiving changes from one
+/// peer and forwarding them to the others. It only looks at the hashes and dependencies of the
+/// changes it holds, so it never has to load them into an [`Automerge`], which keeps it cheap
+/// enough to run for many documents at once and means it never needs to understand the
+/// contents of the changes, which may be sealed with an [`super::Envelope`] the relay doesn't
+/// have the key for.
+///
+/// Changes whose dependencies haven't arrived yet are held back until they have, as a document
+/// would, and the relay never sends a peer the whole document in one go or a snapshot, as it
+/// has neither. [`SyncDoc::generate_sync_message_for()`] always fails as a relay doesn't know
+/// which changes touch which objects.
+///
+/// ```
+/// # use automerge::{sync::{Relay, State, SyncDoc}, transaction::Transactable, AutoCommit, ROOT};
+/// let mut doc = AutoCommit::new();
+/// doc.put(ROOT, "key", "value").unwrap();
+/// let mut relay = Relay::new();
+/// let (mut doc_state, mut relay_state) = (State::new(), State::new());
+/// loop {
+///     let Some(message) = doc.sync().generate_sync_message(&mut doc_state) else {
+///         break;
+///     };
+///     relay.receive_sync_message(&mut relay_state, message).unwrap();
+///     if let Some(reply) = relay.generate_sync_message(&mut relay_state) {
+///         doc.sync().receive_sync_message(&mut doc_state, reply).unwrap();
+///     }
+/// }
+/// assert_eq!(relay.heads(), doc.get_heads());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Relay {
+    /// The changes we have all the dependencies of, in causal order
+    changes: Vec<Change>,
+    index: HashMap<ChangeHash, usize>,
+    heads: BTreeSet<ChangeHash>,
+    /// Changes which are waiting for their dependencies
+    queue: Vec<Change>,
+}
+
+impl Relay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A relay which starts out with `changes`, for instance ones it stored before restarting
+    pub fn from_changes<I: IntoIterator<Item = Change>>(changes: I) -> Self {
+        let mut relay = Self::new();
+        relay.add_changes(changes);
+        relay
+    }
+
+    /// The heads of the changes we have all the dependencies of
+    pub fn heads(&self) -> Vec<ChangeHash> {
+        self.heads.iter().copied().collect()
+    }
+
+    /// The number of changes we have all the dependencies of
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// The changes we have all the dependencies of, in causal order
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+
+    pub fn get_change_by_hash(&self, hash: &ChangeHash) -> Option<&Change> {
+        self.index.get(hash).map(|i| &self.changes[*i])
+    }
+
+    /// The number of changes which are waiting for their dependencies
+    pub fn pending_changes_len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// The dependencies of the changes we are holding back which we haven't received yet
+    pub fn missing_deps(&self) -> Vec<ChangeHash> {
+        self.sync_missing_deps(&[])
+    }
+
+    /// Add `changes`, holding back any whose dependencies we don't have yet
+    pub fn add_changes<I: IntoIterator<Item = Change>>(&mut self, changes: I) {
+        for change in changes {
+            let hash = change.hash();
+            if self.index.contains_key(&hash) || self.queue.iter().any(|c| c.hash() == hash) {
+                continue;
+            }
+            if !self.is_ready(&change) {
+                self.queue.push(change);
+                continue;
+            }
+            self.insert(change);
+            while let Some(i) = self.queue.iter().position(|c| self.is_ready(c)) {
+                let change = self.queue.remove(i);
+                self.insert(change);
+            }
+        }
+    }
+
+    fn is_ready(&self, change: &Change) -> bool {
+        change.deps().iter().all(|dep| self.index.contains_key(dep))
+    }
+
+    fn insert(&mut self, change: Change) {
+        for dep in change.deps() {
+            self.heads.remove(dep);
+        }
+        self.heads.insert(change.hash());
+        self.index.insert(change.hash(), self.changes.len());
+        self.changes.push(change);
+    }
+
+    /// The hashes of `heads` and all their ancestors which we have
+    fn ancestors(&self, heads: &[ChangeHash]) -> HashSet<ChangeHash> {
+        let mut seen = HashSet::new();
+        let mut to_visit = heads.to_vec();
+        while let Some(hash) = to_visit.pop() {
+            if let Some(change) = self.get_change_by_hash(&hash) {
+                if seen.insert(hash) {
+                    to_visit.extend(change.deps());
+                }
+            }
+        }
+        seen
+    }
+}
+
+impl SyncSource for Relay {
+    fn sync_heads(&self) -> Vec<ChangeHash> {
+        self.heads()
+    }
+
+    fn sync_missing_deps(&self, heads: &[ChangeHash]) -> Vec<ChangeHash> {
+        let in_queue: HashSet<_> = self.queue.iter().map(|change| change.hash()).collect();
+        let mut missing = self
+            .queue
+            .iter()
+            .flat_map(|change| change.deps())
+            .chain(heads)
+            .filter(|hash| !self.index.contains_key(hash) && !in_queue.contains(hash))
+            .copied()
+            .collect::<Vec<_>>();
+        missing.sort();
+        missing.dedup();
+        missing
+    }
+
+    fn sync_changes(&self, have_deps: &[ChangeHash]) -> Vec<&Change> {
+        let have = self.ancestors(have_deps);
+        self.changes
+            .iter()
+            .filter(|change| !have.contains(&change.hash()))
+            .collect()
+    }
+
+    fn sync_change_by_hash(&self, hash: &ChangeHash) -> Option<&Change> {
+        self.get_change_by_hash(hash)
+    }
+
+    fn sync_filter_changes(
+        &self,
+        heads: &[ChangeHash],
+        changes: &mut BTreeSet<ChangeHash>,
+    ) -> Result<(), AutomergeError> {
+        let ancestors = self.ancestors(heads);
+        changes.retain(|hash| !ancestors.contains(hash));
+        Ok(())
+    }
+
+    fn sync_num_changes(&self) -> u64 {
+        self.changes.len() as u64
+    }
+
+    fn sync_whole_document(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    fn sync_snapshot(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    fn sync_apply_changes(
+        &mut self,
+        policy: &SyncPolicy,
+        changes: ChunkList,
+        _patch_log: &mut PatchLog,
+    ) -> Result<(), AutomergeError> {
+        let changes = policy.filter(load_sync_changes(&changes))?;
+        self.add_changes(changes);
+        Ok(())
+    }
+}
+
+impl SyncDoc for Relay {
+    fn generate_sync_message(&self, sync_state: &mut State) -> Option<Message> {
+        self.generate_sync_message_inner(sync_state, &mut Config::default(), None)
+    }
+
+    fn generate_sync_message_with_max_size(
+        &self,
+        sync_state: &mut State,
+        max_message_size: usize,
+    ) -> Option<Message> {
+        let mut config = Config {
+            max_message_size: Some(max_message_size),
+            ..Default::default()
+        };
+        self.generate_sync_message_inner(sync_state, &mut config, None)
+    }
+
+    fn generate_sync_messages_iter<'a>(
+        &'a self,
+        sync_state: &'a mut State,
+        max_message_size: usize,
+    ) -> SyncMessages<'a> {
+        SyncMessages::new(self, sync_state, max_message_size)
+    }
+
+    fn generate_sync_message_for<O: AsRef<ExId>>(
+        &self,
+        _sync_state: &mut State,
+        obj: O,
+    ) -> Result<Option<Message>, AutomergeError> {
+        Err(AutomergeError::InvalidObjId(obj.as_ref().to_string()))
+    }
+
+    fn generate_sync_message_with_config(
+        &self,
+        sync_state: &mut State,
+        config: &mut Config,
+    ) -> Option<Message> {
+        self.generate_sync_message_inner(sync_state, config, None)
+    }
+
+    fn receive_sync_message(
+        &mut self,
+        sync_state: &mut State,
+        message: Message,
+    ) -> Result<(), AutomergeError> {
+        let mut patch_log = PatchLog::inactive(TextRepresentation::default());
+        self.receive_sync_message_inner(sync_state, &Config::default(), message, &mut patch_log)
+    }
+
+    fn receive_sync_message_log_patches(
+        &mut self,
+        sync_state: &mut State,
+        message: Message,
+        patch_log: &mut PatchLog,
+    ) -> Result<(), AutomergeError> {
+        self.receive_sync_message_inner(sync_state, &Config::default(), message, patch_log)
+    }
+
+    fn receive_sync_message_with_config(
+        &mut self,
+        sync_state: &mut State,
+        config: &Config,
+        message: Message,
+    ) -> Result<(), AutomergeError> {
+        let mut patch_log = PatchLog::inactive(TextRepresentation::default());
+        self.receive_sync_message_inner(sync_state, config, message, &mut patch_log)
+    }
+}
+
+impl From<&Automerge> for Relay {
+    fn from(doc: &Automerge) -> Self {
+        Self::from_changes(doc.get_changes(&[]).into_iter().cloned())
+    }
+}
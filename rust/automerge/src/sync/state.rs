@@ -2,11 +2,17 @@ use std::collections::BTreeSet;
 
 #[cfg(doc)]
 use super::SyncDoc;
-use super::{encode_hashes, BloomFilter, Capability};
+use super::{
+    encode_hashes, encode_many, parse_have, BloomFilter, Capability, Message, ReadMessageError,
+};
 use crate::storage::parse;
-use crate::ChangeHash;
+use crate::{Automerge, ChangeHash, Frontier};
 
 const SYNC_STATE_TYPE: u8 = 0x43; // first byte of an encoded sync state, for identification
+const RESUMABLE_SYNC_STATE_TYPE: u8 = 0x44; // first byte of a state from `encode_resumable`
+/// The version of the format written by [`State::encode_resumable()`], bump this whenever the
+/// format changes so that states written by other versions are rejected rather than misread
+const RESUMABLE_SYNC_STATE_VERSION: u8 = 1;
 
 #[derive(Debug, thiserror::Error)]
 pub enum DecodeError {
@@ -16,6 +22,8 @@ pub enum DecodeError {
     WrongType { expected_one_of: Vec<u8>, found: u8 },
     #[error("not enough input")]
     NotEnoughInput,
+    #[error("unsupported sync state version {found}, expected {expected}")]
+    UnsupportedVersion { expected: u8, found: u8 },
 }
 
 impl From<parse::leb128::Error> for DecodeError {
@@ -24,11 +32,18 @@ impl From<parse::leb128::Error> for DecodeError {
     }
 }
 
+impl From<ReadMessageError> for DecodeError {
+    fn from(e: ReadMessageError) -> Self {
+        Self::Parse(e.to_string())
+    }
+}
+
 /// The state of synchronisation with a peer.
 ///
 /// This should be persisted using [`Self::encode()`] when you know you will be interacting with the
 /// same peer in multiple sessions. [`Self::encode()`] only encodes state which should be reused
-/// across connections.
+/// across connections. To carry on a session with the same peer after a restart use
+/// [`Self::encode_resumable()`] instead.
 #[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
 pub struct State {
     /// The hashes which we know both peers have
@@ -60,6 +75,66 @@ pub struct State {
 
     /// The capabilities the other side has said they have
     pub their_capabilities: Option<Vec<Capability>>,
+
+    progress: Progress,
+    /// Whether a message has been received since the last [`Self::poll_liveness()`]
+    heard_from: bool,
+    last_heard_from: Option<i64>,
+}
+
+/// What a [`State`] knows about the peer, returned by [`State::summary()`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Summary {
+    /// The heads we know both we and the peer have
+    pub shared_heads: Vec<ChangeHash>,
+    /// Our heads as of the last message we sent
+    pub last_sent_heads: Vec<ChangeHash>,
+    /// The heads the peer last told us about, `None` if we have not heard from them
+    pub their_heads: Option<Vec<ChangeHash>>,
+    /// The changes the peer last asked for by hash
+    pub their_need: Option<Vec<ChangeHash>>,
+    /// The capabilities the peer has said it supports
+    pub their_capabilities: Option<Vec<Capability>>,
+    /// Whether we are waiting for the peer to reply to a message
+    pub in_flight: bool,
+    /// Whether we have sent the peer a message yet
+    pub have_responded: bool,
+    /// The changes we have sent which the peer has not yet confirmed having
+    pub unacknowledged_changes: Vec<ChangeHash>,
+    /// The counts from [`State::progress()`]
+    pub progress: Progress,
+    /// The time from [`State::last_heard_from()`]
+    pub last_heard_from: Option<i64>,
+}
+
+/// How a sync with one peer is going, see [`State::progress()`]
+///
+/// The counts are for the lifetime of the [`State`], they are not persisted by
+/// [`State::encode()`]. Byte counts are of the changes and snapshots carried by messages, not of
+/// the encoded messages.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Progress {
+    /// The number of messages generated for the peer
+    pub messages_sent: usize,
+    /// The number of messages received from the peer
+    pub messages_received: usize,
+    /// The number of times the peer replied to a message we sent
+    pub round_trips: usize,
+    /// The number of changes sent to the peer
+    pub changes_sent: usize,
+    /// The number of changes from the peer which were added to the document
+    pub changes_received: usize,
+    /// The size of the changes sent to the peer
+    pub bytes_sent: usize,
+    /// The size of the changes received from the peer
+    pub bytes_received: usize,
+    /// The size of the changes in the last message sent to the peer
+    pub last_message_bytes_sent: usize,
+    /// The size of the changes in the last message received from the peer
+    pub last_message_bytes_received: usize,
+    /// The number of changes we know the peer is missing which didn't fit in the last message we
+    /// sent, because of a size limit or because they depend on changes still in flight
+    pub estimated_changes_remaining: usize,
 }
 
 /// A summary of the changes that the sender of the message already has.
@@ -85,6 +160,54 @@ impl State {
         buf
     }
 
+    /// Encode the state along with what we last heard from the other end
+    ///
+    /// Decoding this with [`Self::decode()`] gives a state which remembers the heads, needs,
+    /// bloom filters and capabilities the peer last sent, so that the first message generated
+    /// after a restart can send the changes they are missing straight away rather than waiting
+    /// for another round trip. What we sent is not kept, as any messages which were in flight
+    /// when the process stopped may have been lost.
+    ///
+    /// The encoding is versioned, [`Self::decode()`] fails with
+    /// [`DecodeError::UnsupportedVersion`] for a state written by a different version, in which
+    /// case start again from [`Self::new()`].
+    pub fn encode_resumable(&self) -> Vec<u8> {
+        fn encode_opt<T>(buf: &mut Vec<u8>, value: Option<&T>, f: impl Fn(&mut Vec<u8>, &T)) {
+            match value {
+                Some(value) => {
+                    buf.push(1);
+                    f(buf, value)
+                }
+                None => buf.push(0),
+            }
+        }
+        fn encode_hash_list(buf: &mut Vec<u8>, hashes: &[ChangeHash]) {
+            encode_many(buf, hashes.iter(), |buf, hash| buf.extend(hash.as_bytes()))
+        }
+
+        let mut buf = vec![RESUMABLE_SYNC_STATE_TYPE, RESUMABLE_SYNC_STATE_VERSION];
+        encode_hashes(&mut buf, &self.shared_heads);
+        encode_opt(&mut buf, self.their_heads.as_ref(), |buf, h| {
+            encode_hash_list(buf, h)
+        });
+        encode_opt(&mut buf, self.their_need.as_ref(), |buf, h| {
+            encode_hash_list(buf, h)
+        });
+        encode_opt(&mut buf, self.their_have.as_ref(), |buf, have| {
+            encode_many(buf, have.iter(), |buf, h| {
+                encode_hash_list(buf, &h.last_sync);
+                let bloom = h.bloom.to_bytes();
+                leb128::write::unsigned(buf, bloom.len() as u64).unwrap();
+                buf.extend(bloom);
+            })
+        });
+        encode_opt(&mut buf, self.their_capabilities.as_ref(), |buf, caps| {
+            encode_many(buf, caps.iter(), |buf, cap| cap.encode(buf))
+        });
+        buf
+    }
+
+    /// Decode a state written by [`Self::encode()`] or [`Self::encode_resumable()`]
     pub fn decode(input: &[u8]) -> Result<Self, DecodeError> {
         let input = parse::Input::new(input);
         match Self::parse(input) {
@@ -96,9 +219,12 @@ impl State {
 
     pub(crate) fn parse(input: parse::Input<'_>) -> parse::ParseResult<'_, Self, DecodeError> {
         let (i, record_type) = parse::take1(input)?;
+        if record_type == RESUMABLE_SYNC_STATE_TYPE {
+            return Self::parse_resumable(i);
+        }
         if record_type != SYNC_STATE_TYPE {
             return Err(parse::ParseError::Error(DecodeError::WrongType {
-                expected_one_of: vec![SYNC_STATE_TYPE],
+                expected_one_of: vec![SYNC_STATE_TYPE, RESUMABLE_SYNC_STATE_TYPE],
                 found: record_type,
             }));
         }
@@ -116,14 +242,206 @@ impl State {
                 in_flight: false,
                 have_responded: false,
                 their_capabilities: None,
+                progress: Progress::default(),
+                heard_from: false,
+                last_heard_from: None,
             },
         ))
     }
 
-    pub(crate) fn supports_v2_messages(&self) -> bool {
+    fn parse_resumable(input: parse::Input<'_>) -> parse::ParseResult<'_, Self, DecodeError> {
+        fn parse_opt<'a, T>(
+            input: parse::Input<'a>,
+            mut f: impl FnMut(parse::Input<'a>) -> parse::ParseResult<'a, T, DecodeError>,
+        ) -> parse::ParseResult<'a, Option<T>, DecodeError> {
+            let (i, present) = parse::take1(input)?;
+            match present {
+                0 => Ok((i, None)),
+                1 => {
+                    let (i, value) = f(i)?;
+                    Ok((i, Some(value)))
+                }
+                other => Err(parse::ParseError::Error(DecodeError::Parse(format!(
+                    "invalid option tag {}",
+                    other
+                )))),
+            }
+        }
+
+        let (i, version) = parse::take1(input)?;
+        if version != RESUMABLE_SYNC_STATE_VERSION {
+            return Err(parse::ParseError::Error(DecodeError::UnsupportedVersion {
+                expected: RESUMABLE_SYNC_STATE_VERSION,
+                found: version,
+            }));
+        }
+        let (i, shared_heads) = parse::length_prefixed(parse::change_hash)(i)?;
+        let (i, their_heads) = parse_opt(i, parse::length_prefixed(parse::change_hash))?;
+        let (i, their_need) = parse_opt(i, parse::length_prefixed(parse::change_hash))?;
+        let (i, their_have) = parse_opt(i, |i| {
+            parse::length_prefixed(parse_have)(i).map_err(|e| e.lift())
+        })?;
+        let (i, their_capabilities) = parse_opt(i, |i| {
+            parse::length_prefixed(Capability::parse)(i).map_err(|e| e.lift())
+        })?;
+        Ok((
+            i,
+            Self {
+                shared_heads,
+                last_sent_heads: Vec::new(),
+                their_heads,
+                their_need,
+                their_have,
+                sent_hashes: BTreeSet::new(),
+                in_flight: false,
+                have_responded: false,
+                their_capabilities,
+                progress: Progress::default(),
+                heard_from: false,
+                last_heard_from: None,
+            },
+        ))
+    }
+
+    /// A keepalive message to send to the peer, or [`None`] if it doesn't understand them
+    ///
+    /// Send one of these when there has been nothing else to send for a while so that the peer
+    /// knows we are still there, see [`Self::poll_liveness()`]. Receiving a keepalive doesn't
+    /// change the sync, so they can be sent at any time, even while a message is in flight. Only
+    /// peers which advertise [`Capability::Keepalive`] can decode them, so until the first message
+    /// from the peer has been received this returns [`None`].
+    pub fn keepalive(&mut self) -> Option<Message> {
+        if !self.remote_supports(&Capability::Keepalive) {
+            return None;
+        }
+        self.progress.messages_sent += 1;
+        Some(Message::new_keepalive())
+    }
+
+    /// Update and return [`Self::last_heard_from()`], `now` being the current time in
+    /// milliseconds since the epoch
+    ///
+    /// If any message, including a keepalive, has been received from the peer since the last
+    /// call then `now` becomes the time we last heard from them. Call this periodically and treat
+    /// the peer as gone if it is too far in the past. The time is only as accurate as the interval
+    /// between calls.
+    pub fn poll_liveness(&mut self, now: i64) -> Option<i64> {
+        if std::mem::take(&mut self.heard_from) {
+            self.last_heard_from = Some(now);
+        }
+        self.last_heard_from
+    }
+
+    /// The time passed to [`Self::poll_liveness()`] when we last noticed a message from the peer,
+    /// or [`None`] if we haven't heard from them yet. This is not persisted by
+    /// [`Self::encode()`].
+    pub fn last_heard_from(&self) -> Option<i64> {
+        self.last_heard_from
+    }
+
+    /// Drop anything this state tracks which is implied by something else it tracks
+    ///
+    /// On a long lived connection [`Self::shared_heads`] can pick up hashes which are ancestors
+    /// of other shared heads, and [`Self::sent_hashes`] can keep changes the peer has since
+    /// acknowledged indirectly. Neither affects which changes are sent, so compacting doesn't
+    /// change the outcome of a sync, it just keeps the state and its encoding small. `doc` must
+    /// be the document this state is used to sync.
+    pub fn compact(&mut self, doc: &Automerge) {
+        let mut heads = self.shared_heads.clone();
+        heads.sort();
+        heads.dedup();
+        self.shared_heads = heads
+            .iter()
+            .filter(|head| {
+                let others = heads
+                    .iter()
+                    .filter(|other| other != head)
+                    .copied()
+                    .collect::<Vec<_>>();
+                let mut this = BTreeSet::from([**head]);
+                // ignore the error, it only means we don't have some of the heads
+                let _ = doc.filter_changes(&others, &mut this);
+                !this.is_empty()
+            })
+            .copied()
+            .collect();
+
+        let mut known = self.shared_heads.clone();
+        known.extend(self.their_heads.iter().flatten());
+        let _ = doc.filter_changes(&known, &mut self.sent_hashes);
+    }
+
+    /// How the sync with the peer is going
+    ///
+    /// Use this to show progress while a large document syncs, or to spot a peer which has
+    /// stopped replying, as [`Progress::round_trips`] stops increasing while [`Self::in_flight`]
+    /// stays set.
+    pub fn progress(&self) -> Progress {
+        self.progress
+    }
+
+    pub(crate) fn record_sent(&mut self, changes: usize, bytes: usize, remaining: Option<usize>) {
+        self.progress.messages_sent += 1;
+        self.progress.changes_sent += changes;
+        self.progress.bytes_sent += bytes;
+        self.progress.last_message_bytes_sent = bytes;
+        if let Some(remaining) = remaining {
+            self.progress.estimated_changes_remaining = remaining;
+        }
+    }
+
+    pub(crate) fn record_received(&mut self, replied: bool, changes: usize, bytes: usize) {
+        self.progress.messages_received += 1;
+        self.heard_from = true;
+        if replied {
+            self.progress.round_trips += 1;
+        }
+        self.progress.changes_received += changes;
+        self.progress.bytes_received += bytes;
+        self.progress.last_message_bytes_received = bytes;
+    }
+
+    /// A snapshot of what this state knows about the peer, for logging and debugging
+    ///
+    /// When a sync doesn't converge compare the summaries on each side, for example
+    /// [`Summary::their_need`] lists changes the peer asked for which we don't have, and a
+    /// [`Summary::in_flight`] which never clears means the peer isn't replying.
+    pub fn summary(&self) -> Summary {
+        Summary {
+            shared_heads: self.shared_heads.clone(),
+            last_sent_heads: self.last_sent_heads.clone(),
+            their_heads: self.their_heads.clone(),
+            their_need: self.their_need.clone(),
+            their_capabilities: self.their_capabilities.clone(),
+            in_flight: self.in_flight,
+            have_responded: self.have_responded,
+            unacknowledged_changes: self.sent_hashes.iter().copied().collect(),
+            progress: self.progress,
+            last_heard_from: self.last_heard_from,
+        }
+    }
+
+    /// The heads which we know both peers have, as a [`Frontier`]
+    pub fn shared_frontier(&self) -> Frontier {
+        self.shared_heads.as_slice().into()
+    }
+
+    /// The heads we last received from the other end, as a [`Frontier`]
+    pub fn their_frontier(&self) -> Option<Frontier> {
+        self.their_heads.as_deref().map(Frontier::from)
+    }
+
+    /// Whether the peer has said it supports `capability`
+    ///
+    /// This is `false` until the first message from the peer has been received.
+    pub fn remote_supports(&self, capability: &Capability) -> bool {
         self.their_capabilities
             .as_ref()
-            .map(|caps| caps.contains(&Capability::MessageV2))
+            .map(|caps| caps.contains(capability))
             .unwrap_or(false)
     }
+
+    pub(crate) fn supports_v2_messages(&self) -> bool {
+        self.remote_supports(&Capability::MessageV2)
+    }
 }
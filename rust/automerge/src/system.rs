@@ -0,0 +1,107 @@
+//! The object in which automerge records metadata about a document
+//!
+//! Features such as [`crate::actor_metadata`], [`crate::expiry`] and [`crate::multi_value`]
+//! record what they need in the document itself so that every peer agrees on it. They all keep
+//! it in one map at the key [`SYSTEM_KEY`] in the root of the document, each under its own key in
+//! that map, so they don't take up keys which applications may want to use.
+//!
+//! The system object is hidden from reads of the document: it is not returned by
+//! [`crate::ReadDoc::get()`], [`crate::ReadDoc::keys()`], [`crate::ReadDoc::map_range()`] or
+//! [`crate::ReadDoc::length()`] on the root, is left out when the document is hydrated and
+//! changes to it don't produce patches. It is synced and saved like the rest of the document.
+use crate::exid::ExId;
+use crate::transaction::Transactable;
+use crate::types::{Clock, ListEncoding, ObjId as InternalObjId, OpType};
+use crate::{Automerge, AutomergeError, ObjType, Prop, ReadDoc, Value, ROOT};
+
+/// The key in the root map under which the system object is stored
+pub const SYSTEM_KEY: &str = "automerge:system";
+
+/// Access to the system object, which [`ReadDoc`] hides
+pub(crate) trait ReadSystem: ReadDoc {
+    /// The system objects in the document
+    ///
+    /// There is more than one if several peers created the system object concurrently.
+    fn system_objects(&self) -> Vec<ExId>;
+
+    /// The maps at `name` in the system objects, in the order the system objects were created
+    fn system_maps(&self, name: &str) -> Result<Vec<ExId>, AutomergeError> {
+        let mut maps = Vec::new();
+        for system in self.system_objects() {
+            for (value, map) in self.get_all(&system, name)? {
+                if let Value::Object(ObjType::Map) = value {
+                    maps.push(map);
+                }
+            }
+        }
+        Ok(maps)
+    }
+}
+
+/// The map at `name` in the system object, creating it and the system object if they don't exist
+pub(crate) fn system_map<T: Transactable + ReadSystem + ?Sized>(
+    tx: &mut T,
+    name: &str,
+) -> Result<ExId, AutomergeError> {
+    if let Some(map) = tx.system_maps(name)?.into_iter().next() {
+        return Ok(map);
+    }
+    let system = match tx.system_objects().into_iter().next() {
+        Some(system) => system,
+        None => tx.put_object(ROOT, SYSTEM_KEY, ObjType::Map)?,
+    };
+    tx.put_object(&system, name, ObjType::Map)
+}
+
+/// Whether `prop` of `obj` is the system object
+pub(crate) fn is_system_prop(obj: &ExId, prop: &Prop) -> bool {
+    *obj == ExId::Root && prop.as_str() == Some(SYSTEM_KEY)
+}
+
+impl Automerge {
+    pub(crate) fn system_objects_for(&self, clock: Option<Clock>) -> Vec<ExId> {
+        self.ops()
+            .seek_ops_by_prop(
+                &InternalObjId::root(),
+                Prop::Map(SYSTEM_KEY.to_string()),
+                ListEncoding::List,
+                clock.as_ref(),
+            )
+            .ops
+            .into_iter()
+            .filter(|op| matches!(op.action(), OpType::Make(ObjType::Map)))
+            .map(|op| op.exid())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SYSTEM_KEY;
+    use crate::{hydrate_map, transaction::Transactable, ActorId, AutoCommit, ReadDoc, ROOT};
+
+    #[test]
+    fn the_system_object_is_hidden() {
+        let mut doc = AutoCommit::new();
+        doc.put(&ROOT, "title", "notes").unwrap();
+        doc.set_actor_metadata(&ActorId::random(), &Default::default())
+            .unwrap();
+        let mut patch_doc = AutoCommit::new();
+        patch_doc.update_diff_cursor();
+        patch_doc.merge(&mut doc).unwrap();
+
+        assert!(doc.get(&ROOT, SYSTEM_KEY).unwrap().is_none());
+        assert!(doc.get_all(&ROOT, SYSTEM_KEY).unwrap().is_empty());
+        assert_eq!(doc.keys(&ROOT).collect::<Vec<_>>(), vec!["title"]);
+        assert_eq!(doc.map_range(&ROOT, ..).count(), 1);
+        assert_eq!(doc.values(&ROOT).count(), 1);
+        assert_eq!(doc.length(&ROOT), 1);
+        assert_eq!(
+            doc.hydrate(&ROOT, None).unwrap(),
+            hydrate_map! {"title" => "notes"}.into()
+        );
+        let patches = patch_doc.diff_incremental();
+        assert_eq!(patches.len(), 1);
+        assert_eq!(patches[0].path, vec![]);
+    }
+}
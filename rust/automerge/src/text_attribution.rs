@@ -0,0 +1,71 @@
+use crate::exid::ExId;
+use crate::text_value::TextValue;
+use crate::{ActorId, Automerge, AutomergeError, ChangeHash, ObjType, ReadDoc, ScalarValue, Value};
+
+/// A run of text which was inserted by one change, returned by
+/// [`Automerge::attribute_text()`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextAttribution {
+    /// The index of the first character of the run
+    pub start: usize,
+    /// The index after the last character of the run
+    pub end: usize,
+    /// The text of the run, block markers appear as `\u{fffc}`
+    pub text: String,
+    /// The actor which inserted the text
+    pub actor: ActorId,
+    /// The hash of the change which inserted the text
+    ///
+    /// This is `None` if the text was inserted in a transaction which has not yet been committed
+    pub hash: Option<ChangeHash>,
+    /// The timestamp of the change which inserted the text, `None` if the change is not yet
+    /// committed
+    pub timestamp: Option<i64>,
+}
+
+pub(crate) fn attribute_text<R: ReadDoc + ?Sized>(
+    doc: &R,
+    automerge: &Automerge,
+    obj: &ExId,
+    heads: Option<&[ChangeHash]>,
+) -> Result<Vec<TextAttribution>, AutomergeError> {
+    let typ = doc.object_type(obj)?;
+    if typ != ObjType::Text {
+        return Err(AutomergeError::InvalidOp(typ));
+    }
+    let items = match heads {
+        Some(heads) => doc.list_range_at(obj, .., heads),
+        None => doc.list_range(obj, ..),
+    };
+    let mut runs: Vec<TextAttribution> = Vec::new();
+    for item in items {
+        let text = match &item.value {
+            Value::Scalar(s) => match s.as_ref() {
+                ScalarValue::Str(s) => s.as_str(),
+                _ => "\u{fffc}",
+            },
+            Value::Object(_) => "\u{fffc}",
+        };
+        let end = item.index + TextValue::width(text);
+        // characters in text are never overwritten so the operation which created the value is
+        // the one which inserted it
+        let Some(meta) = automerge.op_meta_for(&item.id) else {
+            continue;
+        };
+        match runs.last_mut() {
+            Some(run) if run.actor == meta.actor && run.hash == meta.hash => {
+                run.end = end;
+                run.text.push_str(text);
+            }
+            _ => runs.push(TextAttribution {
+                start: item.index,
+                end,
+                text: text.to_string(),
+                actor: meta.actor,
+                hash: meta.hash,
+                timestamp: meta.timestamp,
+            }),
+        }
+    }
+    Ok(runs)
+}
@@ -20,6 +20,7 @@ pub(crate) fn myers_diff<'a, S: AsRef<str>>(
     new: S,
 ) -> Result<(), crate::AutomergeError> {
     let old = doc.text(text_obj)?;
+    let new = tx.canonicalize_line_endings(doc, text_obj, 0, new.as_ref())?;
     let new = new.as_ref();
     let old_graphemes = old.graphemes(true).collect::<Vec<&str>>();
     let new_graphemes = new.graphemes(true).collect::<Vec<&str>>();
@@ -0,0 +1,427 @@
+//! Rendering text objects with marks and blocks as HTML or Markdown, and importing them back
+//!
+//! Which marks and blocks become which tags is set by a [`TagMapping`]. [`TagMapping::html()`]
+//! and [`TagMapping::markdown()`] map the common marks (`bold`, `italic`, `code`, `link`) and
+//! block types (`paragraph`, `heading`, `unordered-list-item`, `ordered-list-item`), and more can
+//! be added with [`TagMapping::with_mark()`] and [`TagMapping::with_block()`]. Marks and blocks
+//! with no mapping are left out of the output, as are embeds.
+//!
+//! [`import()`] only understands the marks in the mapping whose tags do not include the value of
+//! the mark, every mark it creates has the value `true`. Block tags are dropped and the end of a
+//! block becomes a newline rather than creating block markers.
+use std::collections::BTreeMap;
+
+use crate::exid::ExId;
+use crate::iter::Span;
+use crate::marks::{ExpandMark, Mark};
+use crate::text_value::TextValue;
+use crate::transaction::Transactable;
+use crate::{hydrate, AutomergeError, ReadDoc, ScalarValue};
+
+/// The placeholder in the opening tag of a mark which is replaced with the value of the mark
+pub const VALUE_PLACEHOLDER: &str = "{value}";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Syntax {
+    Html,
+    Markdown,
+}
+
+/// The tags used for marks and blocks by [`export()`] and [`import()`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagMapping {
+    syntax: Syntax,
+    marks: BTreeMap<String, (String, String)>,
+    blocks: BTreeMap<String, (String, String)>,
+    default_block: (String, String),
+}
+
+impl TagMapping {
+    pub fn html() -> Self {
+        Self {
+            syntax: Syntax::Html,
+            marks: BTreeMap::new(),
+            blocks: BTreeMap::new(),
+            default_block: ("<p>".to_string(), "</p>".to_string()),
+        }
+        .with_mark("bold", "<strong>", "</strong>")
+        .with_mark("italic", "<em>", "</em>")
+        .with_mark("code", "<code>", "</code>")
+        .with_mark("link", "<a href=\"{value}\">", "</a>")
+        .with_block("paragraph", "<p>", "</p>")
+        .with_block("heading", "<h1>", "</h1>")
+        .with_block("unordered-list-item", "<li>", "</li>")
+        .with_block("ordered-list-item", "<li>", "</li>")
+    }
+
+    pub fn markdown() -> Self {
+        Self {
+            syntax: Syntax::Markdown,
+            marks: BTreeMap::new(),
+            blocks: BTreeMap::new(),
+            default_block: (String::new(), "\n\n".to_string()),
+        }
+        .with_mark("bold", "**", "**")
+        .with_mark("italic", "_", "_")
+        .with_mark("code", "`", "`")
+        .with_mark("link", "[", "]({value})")
+        .with_block("paragraph", "", "\n\n")
+        .with_block("heading", "# ", "\n\n")
+        .with_block("unordered-list-item", "- ", "\n")
+        .with_block("ordered-list-item", "1. ", "\n")
+    }
+
+    /// Render marks named `name` between `open` and `close`
+    ///
+    /// [`VALUE_PLACEHOLDER`] in either tag is replaced with the value of the mark.
+    pub fn with_mark(mut self, name: &str, open: &str, close: &str) -> Self {
+        self.marks
+            .insert(name.to_string(), (open.to_string(), close.to_string()));
+        self
+    }
+
+    /// Render the text following a block marker whose `type` is `block_type` between `open` and
+    /// `close`
+    pub fn with_block(mut self, block_type: &str, open: &str, close: &str) -> Self {
+        self.blocks.insert(
+            block_type.to_string(),
+            (open.to_string(), close.to_string()),
+        );
+        self
+    }
+
+    fn escape(&self, s: &str) -> String {
+        let mut result = String::with_capacity(s.len());
+        for c in s.chars() {
+            match (self.syntax, c) {
+                (Syntax::Html, '&') => result.push_str("&amp;"),
+                (Syntax::Html, '<') => result.push_str("&lt;"),
+                (Syntax::Html, '>') => result.push_str("&gt;"),
+                (Syntax::Html, '"') => result.push_str("&quot;"),
+                (Syntax::Markdown, '\\' | '*' | '_' | '`' | '[' | ']' | '#') => {
+                    result.push('\\');
+                    result.push(c);
+                }
+                _ => result.push(c),
+            }
+        }
+        result
+    }
+
+    /// If `input` starts with an escaped character return the character and the length of the
+    /// escape
+    fn unescape(&self, input: &str) -> Option<(char, usize)> {
+        match self.syntax {
+            Syntax::Html => {
+                let end = input.char_indices().take(8).find(|(_, c)| *c == ';')?.0;
+                let c = match &input[..=end] {
+                    "&amp;" => '&',
+                    "&lt;" => '<',
+                    "&gt;" => '>',
+                    "&quot;" => '"',
+                    "&#39;" => '\'',
+                    _ => return None,
+                };
+                Some((c, end + 1))
+            }
+            Syntax::Markdown => {
+                let mut chars = input.chars();
+                if chars.next()? != '\\' {
+                    return None;
+                }
+                let c = chars.next()?;
+                Some((c, 1 + c.len_utf8()))
+            }
+        }
+    }
+
+    fn tag(&self, tag: &str, value: &ScalarValue) -> String {
+        if tag.contains(VALUE_PLACEHOLDER) {
+            let value = match value.to_str() {
+                Some(s) => s.to_string(),
+                None => value.to_string(),
+            };
+            tag.replace(VALUE_PLACEHOLDER, &self.escape(&value))
+        } else {
+            tag.to_string()
+        }
+    }
+
+    fn block_tags(&self, block: &hydrate::Map) -> &(String, String) {
+        match block.get("type") {
+            Some(hydrate::Value::Scalar(ScalarValue::Str(t))) => {
+                self.blocks.get(t.as_str()).unwrap_or(&self.default_block)
+            }
+            _ => &self.default_block,
+        }
+    }
+}
+
+/// Whether a mark with `value` should be rendered, unmarks and marks set to `false` are not
+fn is_set(value: &ScalarValue) -> bool {
+    !matches!(value, ScalarValue::Null | ScalarValue::Boolean(false))
+}
+
+/// Render the text object `obj` using `mapping`
+pub fn export<R: ReadDoc + ?Sized>(
+    doc: &R,
+    obj: &ExId,
+    mapping: &TagMapping,
+) -> Result<String, AutomergeError> {
+    let mut out = String::new();
+    // the marks which are open, innermost last, with the tag which closes them
+    let mut open: Vec<(String, ScalarValue, String)> = Vec::new();
+    let mut block_close: Option<String> = None;
+    for span in doc.spans(obj)? {
+        match span {
+            Span::Text(text, marks) => {
+                let wanted = marks
+                    .iter()
+                    .flat_map(|m| m.iter())
+                    .filter(|(name, value)| mapping.marks.contains_key(*name) && is_set(value))
+                    .collect::<Vec<_>>();
+                // tags have to nest so close everything from the first mark which has changed
+                let keep = open
+                    .iter()
+                    .take_while(|(name, value, _)| wanted.contains(&(name.as_str(), value)))
+                    .count();
+                while open.len() > keep {
+                    out.push_str(&open.pop().unwrap().2);
+                }
+                for (name, value) in wanted {
+                    if open.iter().any(|(n, _, _)| n == name) {
+                        continue;
+                    }
+                    let (open_tag, close_tag) = &mapping.marks[name];
+                    out.push_str(&mapping.tag(open_tag, value));
+                    open.push((
+                        name.to_string(),
+                        value.clone(),
+                        mapping.tag(close_tag, value),
+                    ));
+                }
+                out.push_str(&mapping.escape(&text));
+            }
+            Span::Block(block) => {
+                while let Some((_, _, close)) = open.pop() {
+                    out.push_str(&close);
+                }
+                if let Some(close) = block_close.take() {
+                    out.push_str(&close);
+                }
+                let (open_tag, close_tag) = mapping.block_tags(&block);
+                out.push_str(open_tag);
+                block_close = Some(close_tag.clone());
+            }
+            Span::Embed(_) => {}
+        }
+    }
+    while let Some((_, _, close)) = open.pop() {
+        out.push_str(&close);
+    }
+    if let Some(close) = block_close {
+        out.push_str(&close);
+    }
+    Ok(out)
+}
+
+/// Insert `input`, rendered with `mapping`, into the text object `obj` at `index`, marking the
+/// text which was between the tags of a mark with that mark
+pub fn import<T: Transactable + ?Sized>(
+    tx: &mut T,
+    obj: &ExId,
+    index: usize,
+    input: &str,
+    mapping: &TagMapping,
+) -> Result<(), AutomergeError> {
+    let (text, marks) = parse(input, mapping);
+    tx.splice_text(obj, index, 0, &text)?;
+    for (name, start, end) in marks {
+        if end > start {
+            tx.mark(
+                obj,
+                Mark::new(name, true, index + start, index + end),
+                ExpandMark::default(),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Split `input` into the plain text and the marks in it, with the start and end of each mark
+/// as indices into the text
+fn parse(input: &str, mapping: &TagMapping) -> (String, Vec<(String, usize, usize)>) {
+    let mut text = String::new();
+    let mut width = 0;
+    let mut marks = Vec::new();
+    let mut open: Vec<(&str, usize)> = Vec::new();
+    // the marks we can parse, longest opening tag first so `**` is found before `*`
+    let mut tags = mapping
+        .marks
+        .iter()
+        .filter(|(_, (o, c))| !o.is_empty() && !c.is_empty() && !o.contains(VALUE_PLACEHOLDER))
+        .collect::<Vec<_>>();
+    tags.sort_by_key(|(_, (o, _))| std::cmp::Reverse(o.len()));
+    let mut blocks = mapping
+        .blocks
+        .values()
+        .chain(std::iter::once(&mapping.default_block))
+        .collect::<Vec<_>>();
+    blocks.sort_by_key(|(o, _)| std::cmp::Reverse(o.len()));
+
+    let mut rest = input;
+    'outer: while !rest.is_empty() {
+        if let Some((c, len)) = mapping.unescape(rest) {
+            text.push(c);
+            width += TextValue::width(c.encode_utf8(&mut [0; 4]));
+            rest = &rest[len..];
+            continue;
+        }
+        // the innermost mark is closed first
+        for i in (0..open.len()).rev() {
+            let (name, start) = open[i];
+            let close = &mapping.marks[name].1;
+            if rest.starts_with(close.as_str()) {
+                open.remove(i);
+                marks.push((name.to_string(), start, width));
+                rest = &rest[close.len()..];
+                continue 'outer;
+            }
+        }
+        for (name, (open_tag, _)) in &tags {
+            if rest.starts_with(open_tag.as_str()) && !open.iter().any(|(n, _)| n == name) {
+                open.push((name.as_str(), width));
+                rest = &rest[open_tag.len()..];
+                continue 'outer;
+            }
+        }
+        let at_line_start = text.is_empty() || text.ends_with('\n');
+        for (open_tag, close_tag) in &blocks {
+            if at_line_start && !open_tag.is_empty() && rest.starts_with(open_tag.as_str()) {
+                rest = &rest[open_tag.len()..];
+                continue 'outer;
+            }
+            if !close_tag.trim().is_empty() && rest.starts_with(close_tag.as_str()) {
+                text.push('\n');
+                width += TextValue::width("\n");
+                rest = &rest[close_tag.len()..];
+                continue 'outer;
+            }
+        }
+        let c = rest.chars().next().unwrap();
+        text.push(c);
+        width += TextValue::width(&rest[..c.len_utf8()]);
+        rest = &rest[c.len_utf8()..];
+    }
+    // marks which were never closed cover the rest of the text
+    for (name, start) in open {
+        marks.push((name.to_string(), start, width));
+    }
+    (text, marks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{export, import, TagMapping};
+    use crate::marks::{ExpandMark, Mark};
+    use crate::text_value::TextValue;
+    use crate::{hydrate_map, transaction::Transactable, AutoCommit, ObjType, ReadDoc, ROOT};
+
+    #[test]
+    fn text_round_trips_through_markdown_and_html() {
+        // indices are in the document's encoding, in which a block marker may be wider than 1
+        let block_width = TextValue::width("\u{fffc}");
+        let mut doc = AutoCommit::new();
+        let text = doc.put_object(&ROOT, "text", ObjType::Text).unwrap();
+        let block = doc.split_block(&text, 0).unwrap();
+        doc.update_object(&block, &hydrate_map! {"type" => "heading"}.into())
+            .unwrap();
+        doc.splice_text(&text, block_width, 0, "Notes").unwrap();
+        let block = doc.split_block(&text, block_width + 5).unwrap();
+        doc.update_object(&block, &hydrate_map! {"type" => "paragraph"}.into())
+            .unwrap();
+        let start = 2 * block_width + 5;
+        doc.splice_text(&text, start, 0, "a bold and *plain* <b>")
+            .unwrap();
+        doc.mark(
+            &text,
+            Mark::new("bold".into(), true, start + 2, start + 10),
+            ExpandMark::None,
+        )
+        .unwrap();
+        doc.mark(
+            &text,
+            Mark::new("italic".into(), true, start + 7, start + 10),
+            ExpandMark::None,
+        )
+        .unwrap();
+        doc.mark(
+            &text,
+            Mark::new("link".into(), "https://example.com", start, start + 1),
+            ExpandMark::None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            export(&doc, &text, &TagMapping::markdown()).unwrap(),
+            "# Notes\n\n[a](https://example.com) **bold _and_** \\*plain\\* <b>\n\n"
+        );
+        assert_eq!(
+            export(&doc, &text, &TagMapping::html()).unwrap(),
+            "<h1>Notes</h1><p><a href=\"https://example.com\">a</a> \
+             <strong>bold <em>and</em></strong> *plain* &lt;b&gt;</p>"
+        );
+
+        let mut imported = AutoCommit::new();
+        let copy = imported.put_object(&ROOT, "text", ObjType::Text).unwrap();
+        let markdown = "some **bold _and_** \\*plain\\*";
+        import(&mut imported, &copy, 0, markdown, &TagMapping::markdown()).unwrap();
+        assert_eq!(imported.text(&copy).unwrap(), "some bold and *plain*");
+        let marks = imported
+            .marks(&copy)
+            .unwrap()
+            .into_iter()
+            .map(|m| (m.name().to_string(), m.start, m.end))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            marks,
+            [("bold".to_string(), 5, 13), ("italic".to_string(), 10, 13)]
+        );
+        assert_eq!(
+            export(&imported, &copy, &TagMapping::markdown()).unwrap(),
+            markdown
+        );
+
+        let html = "<p>x &amp; <em>y</em></p>";
+        import(&mut imported, &copy, 0, html, &TagMapping::html()).unwrap();
+        assert!(imported.text(&copy).unwrap().starts_with("x & y\n"));
+    }
+    #[test]
+    fn imported_marks_are_in_the_documents_encoding() {
+        let mut doc = AutoCommit::new();
+        let text = doc.put_object(&ROOT, "text", ObjType::Text).unwrap();
+        doc.splice_text(&text, 0, 0, "😀").unwrap();
+        let markdown = "é **ü** ö";
+        let at = TextValue::width("😀");
+        import(&mut doc, &text, at, markdown, &TagMapping::markdown()).unwrap();
+        assert_eq!(doc.text(&text).unwrap(), "😀é ü ö");
+        let marks = doc.marks(&text).unwrap();
+        assert_eq!(marks.len(), 1);
+        assert_eq!(marks[0].start, at + TextValue::width("é "));
+        assert_eq!(marks[0].end, at + TextValue::width("é ü"));
+        assert_eq!(
+            export(&doc, &text, &TagMapping::markdown()).unwrap(),
+            "😀é **ü** ö"
+        );
+    }
+
+    #[cfg(feature = "utf8-indexing")]
+    #[test]
+    fn imported_marks_use_byte_offsets_with_utf8_indexing() {
+        let mut doc = AutoCommit::new();
+        let text = doc.put_object(&ROOT, "text", ObjType::Text).unwrap();
+        import(&mut doc, &text, 0, "é **ü**", &TagMapping::markdown()).unwrap();
+        let marks = doc.marks(&text).unwrap();
+        assert_eq!((marks[0].start, marks[0].end), (3, 5));
+    }
+}
@@ -0,0 +1,294 @@
+use std::io::Read;
+
+use crate::exid::ExId;
+use crate::iter::EMBED_KEY;
+use crate::marks::{ExpandMark, Mark};
+use crate::patches::TextEncoding;
+use crate::text_value::TextValue;
+use crate::transaction::Transactable;
+use crate::{Affinity, AutomergeError, ChangeHash, Cursor, ObjType, ReadDoc};
+
+/// Conversion of indices into text between [`TextEncoding`]s, which [`ReadDoc`] leaves out as it
+/// only indexes text in the encoding this build uses
+pub(crate) trait ReadTextIndex: ReadDoc {
+    /// Convert `index` into `obj` as at `heads` from `from` to `to`
+    fn convert_text_index(
+        &self,
+        obj: &ExId,
+        heads: Option<&[ChangeHash]>,
+        index: usize,
+        from: TextEncoding,
+        to: TextEncoding,
+    ) -> Result<usize, AutomergeError>;
+}
+
+/// Convert `index` into the text object `obj` as at `heads` from `from` to `to`
+///
+/// Indices into objects other than text are the same in every encoding so they are returned
+/// unchanged.
+pub(crate) fn convert_index<R: ReadTextIndex + ?Sized>(
+    doc: &R,
+    obj: &ExId,
+    heads: Option<&[ChangeHash]>,
+    index: usize,
+    from: TextEncoding,
+    to: TextEncoding,
+) -> Result<usize, AutomergeError> {
+    if from == to {
+        return Ok(index);
+    }
+    doc.convert_text_index(obj, heads, index, from, to)
+}
+
+pub(crate) fn splice_text<T: Transactable + ReadTextIndex + ?Sized>(
+    tx: &mut T,
+    obj: &ExId,
+    pos: usize,
+    del: isize,
+    text: &str,
+    encoding: TextEncoding,
+) -> Result<(), AutomergeError> {
+    let native = TextEncoding::default();
+    // a negative `del` deletes backwards from `pos`
+    let (start, end) = if del < 0 {
+        let start = pos
+            .checked_sub(del.unsigned_abs())
+            .ok_or(AutomergeError::InvalidIndex(pos))?;
+        (start, pos)
+    } else {
+        (pos, pos + del as usize)
+    };
+    let start = convert_index(tx, obj, None, start, encoding, native)?;
+    let end = convert_index(tx, obj, None, end, encoding, native)?;
+    tx.splice_text(obj, start, (end - start) as isize, text)
+}
+
+pub(crate) fn splice_text_with_cursors<T: Transactable + ?Sized>(
+    tx: &mut T,
+    obj: &ExId,
+    pos: usize,
+    del: isize,
+    text: &str,
+    affinity: Affinity,
+) -> Result<(Cursor, Cursor), AutomergeError> {
+    let width = TextValue::width(text);
+    if width == 0 {
+        // there are no inserted characters for the cursors to point at
+        return Err(AutomergeError::InvalidIndex(pos));
+    }
+    tx.splice_text(obj, pos, del, text)?;
+    let start = if del < 0 {
+        pos - del.unsigned_abs()
+    } else {
+        pos
+    };
+    let first = tx.get_cursor_with_affinity(obj, start, None, affinity)?;
+    let last = tx.get_cursor_with_affinity(obj, start + width - 1, None, affinity)?;
+    Ok((first, last))
+}
+
+/// How many bytes [`splice_text_from_reader`] reads at a time
+const READ_CHUNK_LEN: usize = 64 * 1024;
+
+pub(crate) fn splice_text_from_reader<T: Transactable + ?Sized, R: Read>(
+    tx: &mut T,
+    obj: &ExId,
+    pos: usize,
+    del: isize,
+    mut reader: R,
+    limit: Option<usize>,
+) -> Result<usize, AutomergeError> {
+    let typ = tx.object_type(obj)?;
+    if typ != ObjType::Text {
+        return Err(AutomergeError::InvalidOp(typ));
+    }
+    if del != 0 {
+        tx.splice_text(obj, pos, del, "")?;
+    }
+    let start = if del < 0 {
+        pos - del.unsigned_abs()
+    } else {
+        pos
+    };
+    let base = tx.length(obj);
+    let mut buf = vec![0; READ_CHUNK_LEN];
+    // bytes which have been read but not yet inserted, because they end part way through a
+    // character or with a carriage return which may be followed by a newline
+    let mut pending = Vec::new();
+    let mut total = 0;
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(AutomergeError::ReadText(e)),
+        };
+        total += n;
+        if let Some(limit) = limit {
+            if total > limit {
+                return Err(AutomergeError::SpliceTooLarge(limit));
+            }
+        }
+        let eof = n == 0;
+        pending.extend_from_slice(&buf[..n]);
+        let text = match std::str::from_utf8(&pending) {
+            Ok(text) => text,
+            Err(e) if e.error_len().is_none() && !eof => {
+                // the rest of the character is in the next chunk
+                std::str::from_utf8(&pending[..e.valid_up_to()]).unwrap_or_default()
+            }
+            Err(e) => {
+                return Err(AutomergeError::InvalidCharacter(
+                    total - pending.len() + e.valid_up_to(),
+                ))
+            }
+        };
+        let text = match text.strip_suffix('\r') {
+            Some(rest) if !eof => rest,
+            _ => text,
+        };
+        if !text.is_empty() {
+            let index = start + (tx.length(obj) - base);
+            tx.splice_text(obj, index, 0, text)?;
+            let used = text.len();
+            pending.drain(..used);
+        }
+        if eof {
+            return Ok(tx.length(obj) - base);
+        }
+    }
+}
+
+pub(crate) fn insert_embed<T: Transactable + ?Sized>(
+    tx: &mut T,
+    obj: &ExId,
+    index: usize,
+) -> Result<ExId, AutomergeError> {
+    let typ = tx.object_type(obj)?;
+    if typ != ObjType::Text {
+        return Err(AutomergeError::InvalidOp(typ));
+    }
+    let embed = tx.insert_object(obj, index, ObjType::Map)?;
+    tx.put(&embed, EMBED_KEY, true)?;
+    Ok(embed)
+}
+
+pub(crate) fn mark<T: Transactable + ReadTextIndex + ?Sized>(
+    tx: &mut T,
+    obj: &ExId,
+    mut mark: Mark<'_>,
+    expand: ExpandMark,
+    encoding: TextEncoding,
+) -> Result<(), AutomergeError> {
+    let native = TextEncoding::default();
+    mark.start = convert_index(tx, obj, None, mark.start, encoding, native)?;
+    mark.end = convert_index(tx, obj, None, mark.end, encoding, native)?;
+    tx.mark(obj, mark, expand)
+}
+
+pub(crate) fn get_cursor<R: ReadTextIndex + ?Sized>(
+    doc: &R,
+    obj: &ExId,
+    position: usize,
+    at: Option<&[ChangeHash]>,
+    encoding: TextEncoding,
+) -> Result<Cursor, AutomergeError> {
+    let position = convert_index(doc, obj, at, position, encoding, TextEncoding::default())?;
+    doc.get_cursor(obj, position, at)
+}
+
+pub(crate) fn get_cursor_position<R: ReadTextIndex + ?Sized>(
+    doc: &R,
+    obj: &ExId,
+    cursor: &Cursor,
+    at: Option<&[ChangeHash]>,
+    encoding: TextEncoding,
+) -> Result<usize, AutomergeError> {
+    let position = doc.get_cursor_position(obj, cursor, at)?;
+    convert_index(doc, obj, at, position, TextEncoding::default(), encoding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::convert_index;
+    use crate::marks::{ExpandMark, Mark};
+    use crate::patches::TextEncoding;
+    use crate::text_value::TextValue;
+    use crate::{transaction::Transactable, AutoCommit, ObjType, ReadDoc, ROOT};
+
+    #[test]
+    fn indices_can_be_given_in_any_encoding() {
+        let mut doc = AutoCommit::new();
+        let text = doc.put_object(&ROOT, "text", ObjType::Text).unwrap();
+        doc.splice_text_with_encoding(&text, 0, 0, "🐻 bear", TextEncoding::Utf8)
+            .unwrap();
+        assert_eq!(doc.text_len(&text, TextEncoding::Utf8).unwrap(), 9);
+        assert_eq!(doc.text_len(&text, TextEncoding::Utf16).unwrap(), 7);
+        assert_eq!(doc.text_len(&text, TextEncoding::CodePoint).unwrap(), 6);
+
+        // "bear" starts after the two UTF-16 code units of the emoji and a space
+        doc.splice_text_with_encoding(&text, 3, 4, "cub", TextEncoding::Utf16)
+            .unwrap();
+        assert_eq!(doc.text(&text).unwrap(), "🐻 cub");
+        // an index in the middle of a character is an error
+        assert!(doc
+            .splice_text_with_encoding(&text, 1, 0, "x", TextEncoding::Utf16)
+            .is_err());
+
+        doc.mark_with_encoding(
+            &text,
+            Mark::new("bold".into(), true, 5, 8),
+            ExpandMark::None,
+            TextEncoding::Utf8,
+        )
+        .unwrap();
+        let marks = doc.marks(&text).unwrap();
+        let native = TextEncoding::default();
+        assert_eq!(marks[0].start, doc.text_len(&text, native).unwrap() - 3);
+
+        let cursor = doc
+            .get_cursor_with_encoding(&text, 3, None, TextEncoding::Utf16)
+            .unwrap();
+        assert_eq!(
+            doc.get_cursor_position_with_encoding(&text, &cursor, None, TextEncoding::Utf8)
+                .unwrap(),
+            5
+        );
+    }
+
+    #[test]
+    fn indices_are_converted_across_many_nodes_and_at_old_heads() {
+        let mut doc = AutoCommit::new();
+        let text = doc.put_object(&ROOT, "text", ObjType::Text).unwrap();
+        let old = "a🐻é".repeat(200);
+        doc.splice_text(&text, 0, 0, &old).unwrap();
+        let heads = doc.get_heads();
+        doc.splice_text(&text, 0, 0, "🐻").unwrap();
+        let clean = doc.text(&text).unwrap();
+        let mut dirty = doc.fork();
+        // a conflicting put means the widths in the index can't be used to skip nodes
+        dirty.put(&text, 400, "xyz").unwrap();
+
+        let native = TextEncoding::default();
+        for (doc, heads, s) in [
+            (&doc, Some(&heads[..]), old),
+            (&doc, None, clean),
+            (&dirty, None, dirty.text(&text).unwrap()),
+        ] {
+            let (mut native_width, mut utf16_width) = (0, 0);
+            for (i, c) in s.chars().enumerate() {
+                if i % 7 == 0 {
+                    assert_eq!(
+                        convert_index(doc, &text, heads, native_width, native, TextEncoding::Utf16)
+                            .unwrap(),
+                        utf16_width
+                    );
+                }
+                native_width += TextValue::width(c.encode_utf8(&mut [0; 4]));
+                utf16_width += c.len_utf16();
+            }
+            if heads.is_none() {
+                assert_eq!(doc.text_len(&text, TextEncoding::Utf8).unwrap(), s.len());
+            }
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use std::ops::Range;
+
+use crate::exid::ExId;
+use crate::text_value::TextValue;
+use crate::{AutomergeError, ObjType, ReadDoc};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Word,
+    Space,
+    Punctuation,
+}
+
+impl CharClass {
+    fn of(ch: char) -> Self {
+        if ch.is_alphanumeric() || ch == '_' {
+            CharClass::Word
+        } else if ch.is_whitespace() || ch == '\u{fffc}' {
+            CharClass::Space
+        } else {
+            CharClass::Punctuation
+        }
+    }
+}
+
+/// Newlines and block markers both end a line
+fn is_line_break(ch: char) -> bool {
+    ch == '\n' || ch == '\u{fffc}'
+}
+
+/// The characters of the text object `obj` with their width in the native text encoding
+fn chars<'a, R: ReadDoc + ?Sized>(
+    doc: &'a R,
+    obj: &ExId,
+) -> Result<impl Iterator<Item = (char, usize)> + 'a, AutomergeError> {
+    let typ = doc.object_type(obj)?;
+    if typ != ObjType::Text {
+        return Err(AutomergeError::InvalidOp(typ));
+    }
+    Ok(doc.list_range(obj, ..).flat_map(|item| {
+        // block markers and other non-string elements count as a single object replacement
+        // character
+        let text = item.value.to_str().unwrap_or("\u{fffc}").to_string();
+        text.chars()
+            .map(|ch| {
+                let mut buf = [0; 4];
+                (ch, TextValue::width(ch.encode_utf8(&mut buf)))
+            })
+            .collect::<Vec<_>>()
+    }))
+}
+
+pub(crate) fn word_boundaries<R: ReadDoc + ?Sized>(
+    doc: &R,
+    obj: &ExId,
+    index: usize,
+) -> Result<Range<usize>, AutomergeError> {
+    let mut start = 0;
+    let mut pos = 0;
+    let mut prev = None;
+    for (ch, width) in chars(doc, obj)? {
+        let class = CharClass::of(ch);
+        if prev.is_some_and(|prev| prev != class) {
+            if pos > index {
+                return Ok(start..pos);
+            }
+            start = pos;
+        }
+        prev = Some(class);
+        pos += width;
+    }
+    match index.cmp(&pos) {
+        std::cmp::Ordering::Less => Ok(start..pos),
+        std::cmp::Ordering::Equal => Ok(pos..pos),
+        std::cmp::Ordering::Greater => Err(AutomergeError::InvalidIndex(index)),
+    }
+}
+
+pub(crate) fn line_range<R: ReadDoc + ?Sized>(
+    doc: &R,
+    obj: &ExId,
+    index: usize,
+) -> Result<Range<usize>, AutomergeError> {
+    let mut start = 0;
+    let mut pos = 0;
+    for (ch, width) in chars(doc, obj)? {
+        if is_line_break(ch) {
+            if pos >= index {
+                return Ok(start..pos);
+            }
+            start = pos + width;
+        }
+        pos += width;
+    }
+    if index > pos {
+        return Err(AutomergeError::InvalidIndex(index));
+    }
+    Ok(start..pos)
+}
@@ -0,0 +1,95 @@
+use std::collections::VecDeque;
+
+use crate::cursor::Cursor;
+use crate::exid::ExId;
+use crate::text_value::TextValue;
+use crate::types::{Clock, OpId};
+use crate::{Automerge, AutomergeError, ObjType};
+
+/// An occurrence of a string in a text object, returned by [`crate::Automerge::find_text()`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextMatch {
+    /// The index of the first character of the match
+    pub index: usize,
+    /// A cursor on the first character of the match, which keeps pointing at the match as the
+    /// text around it is edited
+    pub cursor: Cursor,
+}
+
+/// Where a character of the text came from, kept for the last `needle.len()` characters so that
+/// the start of a match is known when its last character is seen
+#[derive(Clone, Copy)]
+struct CharPos {
+    index: usize,
+    id: OpId,
+}
+
+pub(crate) fn find_text(
+    doc: &Automerge,
+    obj: &ExId,
+    needle: &str,
+    clock: Option<Clock>,
+) -> Result<Vec<TextMatch>, AutomergeError> {
+    let obj = doc.exid_to_obj(obj)?;
+    let clock = doc.ops().seals.clock(&obj.id, clock);
+    if obj.typ != ObjType::Text {
+        return Err(AutomergeError::InvalidOp(obj.typ));
+    }
+    let needle: Vec<char> = needle.chars().collect();
+    if needle.is_empty() {
+        return Ok(Vec::new());
+    }
+    let failure = failure_table(&needle);
+
+    let osd = doc.osd();
+    let mut matches = Vec::new();
+    let mut window = VecDeque::with_capacity(needle.len());
+    let mut matched = 0;
+    let mut index = 0;
+    // the text is matched one op at a time as it is read from the op set, so the whole string is
+    // never built up in memory
+    for top in doc.ops().top_ops(&obj.id, clock) {
+        let chunk = top.op.as_str();
+        let id = *top.op.id();
+        for ch in chunk.chars() {
+            if window.len() == needle.len() {
+                window.pop_front();
+            }
+            window.push_back(CharPos { index, id });
+            while matched > 0 && needle[matched] != ch {
+                matched = failure[matched - 1];
+            }
+            if needle[matched] == ch {
+                matched += 1;
+            }
+            if matched == needle.len() {
+                let start = window[window.len() - needle.len()];
+                matches.push(TextMatch {
+                    index: start.index,
+                    cursor: Cursor::new(start.id, osd),
+                });
+                matched = 0;
+                window.clear();
+            }
+        }
+        index += TextValue::width(chunk);
+    }
+    Ok(matches)
+}
+
+/// The Knuth-Morris-Pratt failure function, `table[i]` is the length of the longest proper prefix
+/// of `needle[..=i]` which is also a suffix of it
+fn failure_table(needle: &[char]) -> Vec<usize> {
+    let mut table = vec![0; needle.len()];
+    let mut len = 0;
+    for i in 1..needle.len() {
+        while len > 0 && needle[i] != needle[len] {
+            len = table[len - 1];
+        }
+        if needle[i] == needle[len] {
+            len += 1;
+        }
+        table[i] = len;
+    }
+    table
+}
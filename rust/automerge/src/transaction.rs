@@ -2,13 +2,20 @@ mod commit;
 mod inner;
 mod manual_transaction;
 mod result;
+mod sorted;
 mod transactable;
+mod unified_diff;
 
 pub use self::commit::CommitOptions;
-pub use self::transactable::{BlockOrText, Transactable};
-pub(crate) use inner::{TransactionArgs, TransactionInner};
+pub use self::inner::{CanonicalizedLineEnding, LINE_ENDINGS_KEY};
+pub use self::transactable::{BlockOrText, SpliceItem, Transactable};
+pub(crate) use inner::{
+    canonicalizes_line_endings, set_canonicalize_line_endings, TransactionArgs, TransactionInner,
+};
 pub use manual_transaction::Transaction;
 pub use result::Failure;
 pub use result::Success;
+pub(crate) use sorted::insert_sorted;
+pub(crate) use unified_diff::apply_unified_diff;
 
 pub type Result<O, E> = std::result::Result<Success<O>, Failure<E>>;
@@ -1,10 +1,17 @@
+use crate::ChangeMetadata;
+
 /// Optional metadata for a commit.
+///
+/// The metadata stored in the extra bytes of the commit is private, set it with
+/// [`Self::with_metadata()`] or [`Self::set_metadata()`].
 #[derive(Debug, Default)]
 pub struct CommitOptions {
     /// A message which describes the commit
     pub message: Option<String>,
     /// The unix timestamp (in seconds) of the commit (purely advisory, not used in conflict resolution)
     pub time: Option<i64>,
+    /// Tagged values to store in the extra bytes of the commit
+    pub(crate) metadata: ChangeMetadata,
 }
 
 impl CommitOptions {
@@ -31,4 +38,16 @@ impl CommitOptions {
         self.time = Some(time);
         self
     }
+
+    /// Store `value` under `tag` in the metadata of the commit.
+    pub fn with_metadata<V: Into<Vec<u8>>>(mut self, tag: u64, value: V) -> Self {
+        self.metadata.insert(tag, value);
+        self
+    }
+
+    /// Store `value` under `tag` in the metadata of the commit.
+    pub fn set_metadata<V: Into<Vec<u8>>>(&mut self, tag: u64, value: V) -> &mut Self {
+        self.metadata.insert(tag, value);
+        self
+    }
 }
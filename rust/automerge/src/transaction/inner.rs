@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use std::collections::HashSet;
 use std::num::NonZeroU64;
 use std::sync::Arc;
@@ -9,9 +10,12 @@ use crate::op_set::{ChangeOpIter, OpIdx, OpIdxRange};
 use crate::patches::{PatchLog, TextRepresentation};
 use crate::query::{self, OpIdSearch};
 use crate::storage::Change as StoredChange;
+use crate::system::{self, ReadSystem};
+use crate::text_value::TextValue;
+use crate::transaction::{CommitOptions, SpliceItem, Transactable};
 use crate::types::{Clock, Key, ListEncoding, ObjMeta, OpId};
 use crate::{op_tree::OpSetData, types::OpBuilder, Automerge, Change, ChangeHash, Prop};
-use crate::{AutomergeError, ObjType, OpType, ReadDoc, ScalarValue};
+use crate::{AutomergeError, ChangeMetadata, ObjType, OpType, Path, ReadDoc, ScalarValue};
 
 #[derive(Debug, Clone)]
 pub(crate) struct TransactionInner {
@@ -20,9 +24,48 @@ pub(crate) struct TransactionInner {
     start_op: NonZeroU64,
     time: i64,
     message: Option<String>,
+    metadata: ChangeMetadata,
     deps: Vec<ChangeHash>,
     scope: Option<Clock>,
     idx_range: OpIdxRange,
+    canonicalized_line_endings: Vec<CanonicalizedLineEnding>,
+}
+
+/// A `\r\n` which was replaced with `\n` when it was inserted into a text object, see
+/// [`crate::AutoCommit::set_canonicalize_line_endings()`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CanonicalizedLineEnding {
+    /// The text object the line ending was inserted into
+    pub obj: ExId,
+    /// The index of the `\n` which was inserted, as at the time it was inserted
+    pub index: usize,
+}
+
+/// The key in the [system object](crate::system) under which the line ending settings are stored
+///
+/// The map at this key has a boolean at the key `canonicalize` which is true if `\r\n` is
+/// replaced with `\n` in inserted text.
+pub const LINE_ENDINGS_KEY: &str = "line_endings";
+
+const CANONICALIZE: &str = "canonicalize";
+
+pub(crate) fn set_canonicalize_line_endings<T: Transactable + ReadSystem + ?Sized>(
+    tx: &mut T,
+    enabled: bool,
+) -> Result<(), AutomergeError> {
+    let settings = system::system_map(tx, LINE_ENDINGS_KEY)?;
+    tx.put(&settings, CANONICALIZE, enabled)
+}
+
+pub(crate) fn canonicalizes_line_endings<R: ReadSystem + ?Sized>(
+    doc: &R,
+) -> Result<bool, AutomergeError> {
+    for settings in doc.system_maps(LINE_ENDINGS_KEY)? {
+        if let Some((value, _)) = doc.get(&settings, CANONICALIZE)? {
+            return Ok(value.to_bool() == Some(true));
+        }
+    }
+    Ok(false)
 }
 
 /// Arguments required to create a new transaction
@@ -59,9 +102,11 @@ impl TransactionInner {
             start_op,
             time: 0,
             message: None,
+            metadata: ChangeMetadata::default(),
             idx_range,
             deps,
             scope,
+            canonicalized_line_endings: Vec::new(),
         }
     }
 
@@ -69,47 +114,72 @@ impl TransactionInner {
     pub(crate) fn empty(
         doc: &mut Automerge,
         args: TransactionArgs,
-        message: Option<String>,
-        time: Option<i64>,
+        options: CommitOptions,
     ) -> ChangeHash {
-        Self::new(args).commit_impl(doc, message, time)
+        Self::new(args).commit_impl(doc, options)
     }
 
     pub(crate) fn pending_ops(&self) -> usize {
         self.idx_range.len()
     }
 
+    pub(crate) fn canonicalized_line_endings(&self) -> &[CanonicalizedLineEnding] {
+        &self.canonicalized_line_endings
+    }
+
+    /// Replace every `\r\n` in `text`, which is to be inserted into `obj` at `index`, with `\n`
+    /// if `doc` canonicalizes line endings, recording where each one ends up
+    pub(crate) fn canonicalize_line_endings<'t>(
+        &mut self,
+        doc: &Automerge,
+        obj: &ExId,
+        index: usize,
+        text: &'t str,
+    ) -> Result<Cow<'t, str>, AutomergeError> {
+        if !text.contains("\r\n") || !canonicalizes_line_endings(doc)? {
+            return Ok(Cow::Borrowed(text));
+        }
+        let mut result = String::with_capacity(text.len());
+        let mut width = 0;
+        for (i, line) in text.split("\r\n").enumerate() {
+            if i > 0 {
+                self.canonicalized_line_endings
+                    .push(CanonicalizedLineEnding {
+                        obj: obj.clone(),
+                        index: index + width,
+                    });
+                result.push('\n');
+                width += 1;
+            }
+            result.push_str(line);
+            width += TextValue::width(line);
+        }
+        Ok(Cow::Owned(result))
+    }
+
     /// Commit the operations performed in this transaction, returning the hashes corresponding to
     /// the new heads.
     ///
     /// Returns `None` if there were no operations to commit
     #[tracing::instrument(skip(self, doc))]
-    pub(crate) fn commit(
-        self,
-        doc: &mut Automerge,
-        message: Option<String>,
-        time: Option<i64>,
-    ) -> Option<ChangeHash> {
+    pub(crate) fn commit(self, doc: &mut Automerge, options: CommitOptions) -> Option<ChangeHash> {
         if self.pending_ops() == 0 {
             return None;
         }
-        Some(self.commit_impl(doc, message, time))
+        Some(self.commit_impl(doc, options))
     }
 
-    pub(crate) fn commit_impl(
-        mut self,
-        doc: &mut Automerge,
-        message: Option<String>,
-        time: Option<i64>,
-    ) -> ChangeHash {
-        if message.is_some() {
-            self.message = message;
+    pub(crate) fn commit_impl(mut self, doc: &mut Automerge, options: CommitOptions) -> ChangeHash {
+        if options.message.is_some() {
+            self.message = options.message;
         }
 
-        if let Some(t) = time {
+        if let Some(t) = options.time {
             self.time = t;
         }
 
+        self.metadata = options.metadata;
+
         let num_ops = self.pending_ops();
         let change = self.export(doc.osd());
         let hash = change.hash();
@@ -135,15 +205,17 @@ impl TransactionInner {
 
         let actor = osd.actors.get(self.actor).clone();
         let deps = self.deps.clone();
-        let stored = match StoredChange::builder()
+        let mut builder = StoredChange::builder()
             .with_actor(actor)
             .with_seq(self.seq)
             .with_start_op(self.start_op)
             .with_message(self.message.clone())
             .with_dependencies(deps)
-            .with_timestamp(self.time)
-            .build(self.operations(osd).map(op_as_actor_id))
-        {
+            .with_timestamp(self.time);
+        if !self.metadata.is_empty() {
+            builder = builder.with_extra_bytes(self.metadata.to_bytes());
+        }
+        let stored = match builder.build(self.operations(osd).map(op_as_actor_id)) {
             Ok(s) => s,
             Err(PredOutOfOrder) => {
                 // SAFETY: types::Op::preds is `types::OpIds` which ensures ops are always sorted
@@ -196,6 +268,13 @@ impl TransactionInner {
                 doc.ops_mut().remove(&obj, pos);
             }
         }
+        // the transaction may have changed the settings
+        if let Err(e) = doc.refresh_mark_merge() {
+            tracing::warn!(err=?e, "failed to reload mark merge settings after rollback");
+        }
+        if let Err(e) = doc.refresh_counter_overflow() {
+            tracing::warn!(err=?e, "failed to reload counter overflow settings after rollback");
+        }
 
         num
     }
@@ -221,7 +300,7 @@ impl TransactionInner {
         prop: P,
         value: V,
     ) -> Result<(), AutomergeError> {
-        let obj = doc.exid_to_obj(ex_obj)?;
+        let obj = doc.exid_to_writable_obj(ex_obj)?;
         let value = value.into();
         let prop = prop.into();
         match (&prop, obj.typ) {
@@ -255,7 +334,10 @@ impl TransactionInner {
         prop: P,
         value: ObjType,
     ) -> Result<ExId, AutomergeError> {
-        let obj = doc.exid_to_obj(ex_obj)?;
+        if let ObjType::Unknown(_) = value {
+            return Err(AutomergeError::InvalidOp(value));
+        }
+        let obj = doc.exid_to_writable_obj(ex_obj)?;
         let prop = prop.into();
         match (&prop, obj.typ) {
             (Prop::Map(_), ObjType::Map) => Ok(()),
@@ -317,7 +399,7 @@ impl TransactionInner {
         index: usize,
         value: V,
     ) -> Result<(), AutomergeError> {
-        let obj = doc.exid_to_obj(ex_obj)?;
+        let obj = doc.exid_to_writable_obj(ex_obj)?;
         if !matches!(obj.typ, ObjType::List | ObjType::Text) {
             return Err(AutomergeError::InvalidOp(obj.typ));
         }
@@ -335,7 +417,10 @@ impl TransactionInner {
         index: usize,
         value: ObjType,
     ) -> Result<ExId, AutomergeError> {
-        let obj = doc.exid_to_obj(ex_obj)?;
+        if let ObjType::Unknown(_) = value {
+            return Err(AutomergeError::InvalidOp(value));
+        }
+        let obj = doc.exid_to_writable_obj(ex_obj)?;
         if !matches!(obj.typ, ObjType::List | ObjType::Text) {
             return Err(AutomergeError::InvalidOp(obj.typ));
         }
@@ -350,16 +435,25 @@ impl TransactionInner {
         obj: &ObjMeta,
         index: usize,
         action: OpType,
+    ) -> Result<OpIdx, AutomergeError> {
+        let encoding = patch_log.text_rep().encoding(obj.typ);
+        self.do_insert_with_encoding(doc, patch_log, obj, index, encoding, action)
+    }
+
+    fn do_insert_with_encoding(
+        &mut self,
+        doc: &mut Automerge,
+        patch_log: &mut PatchLog,
+        obj: &ObjMeta,
+        index: usize,
+        encoding: ListEncoding,
+        action: OpType,
     ) -> Result<OpIdx, AutomergeError> {
         let id = self.next_id();
 
         let query = doc.ops().search(
             &obj.id,
-            query::InsertNth::new(
-                index,
-                patch_log.text_rep().encoding(obj.typ),
-                self.scope.clone(),
-            ),
+            query::InsertNth::new(index, encoding, self.scope.clone()),
         );
         let marks = query.marks(doc.osd());
         let pos = query.pos();
@@ -503,6 +597,44 @@ impl TransactionInner {
         Ok(Some(idx))
     }
 
+    /// Seal `obj` with an op at its key in its parent whose predecessor is the op which created
+    /// it, see [`crate::seal`]
+    pub(crate) fn seal_object(
+        &mut self,
+        doc: &mut Automerge,
+        ex_obj: &ExId,
+    ) -> Result<(), AutomergeError> {
+        if *ex_obj == ExId::Root {
+            return Err(AutomergeError::SealRoot);
+        }
+        let obj = doc.exid_to_obj(ex_obj)?;
+        if doc.ops().seals.is_sealed(&obj.id, self.scope.as_ref()) {
+            return Ok(());
+        }
+        let make = doc
+            .ops()
+            .object_op(&obj.id)
+            .ok_or(AutomergeError::NotAnObject)?;
+        let parent = *make.obj();
+        let op = OpBuilder {
+            id: self.next_id(),
+            action: OpType::Seal(self.deps.clone()),
+            key: *make.key(),
+            insert: false,
+        };
+        let pred = doc.osd().sorted_opids(std::iter::once(obj.id.0));
+        let idx = doc
+            .ops_mut()
+            .load_with_range(parent, op, &mut self.idx_range);
+        let found = doc
+            .ops()
+            .find_op_without_patch_log(&parent, idx.as_op(doc.osd()), &pred);
+        doc.ops_mut().add_succ(&parent, &found.succ, idx);
+        doc.ops_mut().insert(found.pos, &parent, idx);
+        doc.clock_seals();
+        Ok(())
+    }
+
     pub(crate) fn increment<P: Into<Prop>>(
         &mut self,
         doc: &mut Automerge,
@@ -511,8 +643,10 @@ impl TransactionInner {
         prop: P,
         value: i64,
     ) -> Result<(), AutomergeError> {
-        let obj = doc.exid_to_obj(obj)?;
-        self.local_op(doc, patch_log, &obj, prop.into(), OpType::Increment(value))?;
+        let obj = doc.exid_to_writable_obj(obj)?;
+        let prop = prop.into();
+        doc.check_counter_increment(&obj, &prop, value)?;
+        self.local_op(doc, patch_log, &obj, prop, OpType::Increment(value))?;
         Ok(())
     }
 
@@ -523,7 +657,7 @@ impl TransactionInner {
         ex_obj: &ExId,
         prop: P,
     ) -> Result<(), AutomergeError> {
-        let obj = doc.exid_to_obj(ex_obj)?;
+        let obj = doc.exid_to_writable_obj(ex_obj)?;
         let prop = prop.into();
         if obj.typ == ObjType::Text {
             let index = prop.as_index().ok_or(AutomergeError::InvalidOp(obj.typ))?;
@@ -555,7 +689,7 @@ impl TransactionInner {
         del: isize,
         vals: impl IntoIterator<Item = ScalarValue>,
     ) -> Result<(), AutomergeError> {
-        let obj = doc.exid_to_obj(ex_obj)?;
+        let obj = doc.exid_to_writable_obj(ex_obj)?;
         if !matches!(obj.typ, ObjType::List | ObjType::Text) {
             return Err(AutomergeError::InvalidOp(obj.typ));
         }
@@ -574,6 +708,136 @@ impl TransactionInner {
         Ok(())
     }
 
+    pub(crate) fn splice_full(
+        &mut self,
+        doc: &mut Automerge,
+        patch_log: &mut PatchLog,
+        ex_obj: &ExId,
+        index: usize,
+        del: isize,
+        items: impl IntoIterator<Item = SpliceItem>,
+    ) -> Result<Vec<ExId>, AutomergeError> {
+        let obj = doc.exid_to_writable_obj(ex_obj)?;
+        if !matches!(obj.typ, ObjType::List | ObjType::Text) {
+            return Err(AutomergeError::InvalidOp(obj.typ));
+        }
+        // indices into text are in the text encoding, as for `splice_text`
+        let is_text = obj.typ == ObjType::Text;
+        let mut index = self.inner_splice(
+            doc,
+            patch_log,
+            SpliceArgs {
+                obj: obj.clone(),
+                index,
+                del,
+                values: Vec::new(),
+                splice_type: if is_text {
+                    SpliceType::Text("")
+                } else {
+                    SpliceType::List
+                },
+            },
+        )?;
+        let encoding = if is_text {
+            ListEncoding::Text
+        } else {
+            ListEncoding::List
+        };
+        let mut created = Vec::new();
+        // runs of scalars, or of strings in text, are inserted with a single splice
+        let mut scalars = Vec::new();
+        for item in items {
+            #[cfg(feature = "json")]
+            let mut children = None;
+            let action = match item {
+                SpliceItem::Scalar(scalar) if !is_text || scalar.is_str() => {
+                    scalars.push(scalar);
+                    continue;
+                }
+                #[cfg(feature = "json")]
+                SpliceItem::Json(value) if !value.is_object() && !value.is_array() => {
+                    let scalar = json_scalar(&value);
+                    if !is_text || scalar.is_str() {
+                        scalars.push(scalar);
+                        continue;
+                    }
+                    OpType::Put(scalar)
+                }
+                SpliceItem::Scalar(scalar) => OpType::Put(scalar),
+                SpliceItem::Object(typ @ ObjType::Unknown(_)) => {
+                    return Err(AutomergeError::InvalidOp(typ));
+                }
+                SpliceItem::Object(typ) => typ.into(),
+                #[cfg(feature = "json")]
+                SpliceItem::Json(value) => {
+                    let typ = if value.is_object() {
+                        ObjType::Map
+                    } else {
+                        ObjType::List
+                    };
+                    children = Some(value);
+                    typ.into()
+                }
+            };
+            index = self.flush_scalars(doc, patch_log, &obj, index, &mut scalars)?;
+            let idx =
+                self.do_insert_with_encoding(doc, patch_log, &obj, index, encoding, action)?;
+            let op = idx.as_op(doc.osd());
+            index += op.width(encoding);
+            if let OpType::Make(_) = op.action() {
+                let id = op.exid();
+                created.push(id.clone());
+                #[cfg(feature = "json")]
+                if let Some(value) = children {
+                    self.put_json_children(doc, patch_log, &id, &value, &mut created)?;
+                }
+            }
+        }
+        self.flush_scalars(doc, patch_log, &obj, index, &mut scalars)?;
+        Ok(created)
+    }
+
+    /// Insert `scalars` at `index` and return the index after them
+    fn flush_scalars(
+        &mut self,
+        doc: &mut Automerge,
+        patch_log: &mut PatchLog,
+        obj: &ObjMeta,
+        index: usize,
+        scalars: &mut Vec<ScalarValue>,
+    ) -> Result<usize, AutomergeError> {
+        if scalars.is_empty() {
+            return Ok(index);
+        }
+        let values = std::mem::take(scalars);
+        if obj.typ == ObjType::Text {
+            let text = values.iter().filter_map(|v| v.to_str()).collect::<String>();
+            self.inner_splice(
+                doc,
+                patch_log,
+                SpliceArgs {
+                    obj: obj.clone(),
+                    index,
+                    del: 0,
+                    values,
+                    splice_type: SpliceType::Text(&text),
+                },
+            )
+        } else {
+            self.inner_splice(
+                doc,
+                patch_log,
+                SpliceArgs {
+                    obj: obj.clone(),
+                    index,
+                    del: 0,
+                    values,
+                    splice_type: SpliceType::List,
+                },
+            )
+        }
+    }
+
     /// Splice string into a text object
     pub(crate) fn splice_text(
         &mut self,
@@ -584,10 +848,17 @@ impl TransactionInner {
         del: isize,
         text: &str,
     ) -> Result<(), AutomergeError> {
-        let obj = doc.exid_to_obj(ex_obj)?;
+        let obj = doc.exid_to_writable_obj(ex_obj)?;
         if obj.typ != ObjType::Text {
             return Err(AutomergeError::InvalidOp(obj.typ));
         }
+        let start = if del < 0 {
+            index.saturating_sub(del.unsigned_abs())
+        } else {
+            index
+        };
+        let text = self.canonicalize_line_endings(doc, ex_obj, start, text)?;
+        let text = text.as_ref();
         let values = text.chars().map(ScalarValue::from).collect();
         self.inner_splice(
             doc,
@@ -599,7 +870,8 @@ impl TransactionInner {
                 values,
                 splice_type: SpliceType::Text(text),
             },
-        )
+        )?;
+        Ok(())
     }
 
     fn inner_splice(
@@ -613,7 +885,7 @@ impl TransactionInner {
             values,
             splice_type,
         }: SpliceArgs<'_>,
-    ) -> Result<(), AutomergeError> {
+    ) -> Result<usize, AutomergeError> {
         if del < 0 {
             if let Some(n) = index.checked_add_signed(del) {
                 index = n;
@@ -711,8 +983,9 @@ impl TransactionInner {
                     }
                 }
             }
+            return Ok(cursor);
         }
-        Ok(())
+        Ok(index)
     }
 
     pub(crate) fn mark(
@@ -736,7 +1009,7 @@ impl TransactionInner {
             // "b" and end at the anchor point after "a". This is nonsensical so we ignore it.
             return Ok(());
         }
-        let obj = doc.exid_to_obj(ex_obj)?;
+        let obj = doc.exid_to_writable_obj(ex_obj)?;
         let action = OpType::MarkBegin(expand.before(), mark.data.clone().into_owned());
 
         self.do_insert(doc, patch_log, &obj, mark.start, action)?;
@@ -775,7 +1048,7 @@ impl TransactionInner {
         ex_obj: &ExId,
         index: usize,
     ) -> Result<ExId, AutomergeError> {
-        let obj = doc.exid_to_obj(ex_obj)?;
+        let obj = doc.exid_to_writable_obj(ex_obj)?;
         if obj.typ != ObjType::Text {
             return Err(AutomergeError::InvalidOp(obj.typ));
         }
@@ -825,7 +1098,7 @@ impl TransactionInner {
         text: &ExId,
         index: usize,
     ) -> Result<(), AutomergeError> {
-        let text_obj = doc.exid_to_obj(text)?;
+        let text_obj = doc.exid_to_writable_obj(text)?;
 
         if text_obj.typ != ObjType::Text {
             return Err(AutomergeError::InvalidOp(text_obj.typ));
@@ -941,6 +1214,18 @@ impl TransactionInner {
                     Prop::Map(key) => patch_log.delete_map(obj.id, &key),
                 }
             } else if let Some(value) = op.get_increment_value() {
+                // the increment has already been applied to the counter
+                let value = op
+                    .pred()
+                    .find_map(|p| match p.action() {
+                        OpType::Put(ScalarValue::Counter(c)) => {
+                            let mut before = c.clone();
+                            before.increment(value.wrapping_neg());
+                            Some(before.value_change(value, p.saturates()))
+                        }
+                        _ => None,
+                    })
+                    .unwrap_or(value);
                 patch_log.increment(obj.id, &prop, value, *op.id());
             } else {
                 patch_log.put(obj.id, &prop, op.value().into(), *op.id(), false, false);
@@ -955,7 +1240,7 @@ impl TransactionInner {
         obj: &ExId,
         new_value: &crate::hydrate::Value,
     ) -> Result<(), crate::error::UpdateObjectError> {
-        let obj_meta = doc.exid_to_obj(obj)?;
+        let obj_meta = doc.exid_to_writable_obj(obj)?;
         match (obj_meta.typ, new_value) {
             (ObjType::Map, crate::hydrate::Value::Map(map)) => {
                 Ok(self.update_map(doc, patch_log, obj, map)?)
@@ -984,7 +1269,7 @@ impl TransactionInner {
         new_value: &crate::hydrate::Map,
     ) -> Result<(), AutomergeError> {
         let mut delenda = HashSet::new();
-        let obj = doc.exid_to_obj(map)?;
+        let obj = doc.exid_to_writable_obj(map)?;
         let current_vals = doc
             .ops()
             .map_range(&obj.id, .., self.scope.clone())
@@ -1129,6 +1414,103 @@ impl TransactionInner {
         }
     }
 
+    pub(crate) fn put_at_path<V: Into<ScalarValue>>(
+        &mut self,
+        doc: &mut Automerge,
+        patch_log: &mut PatchLog,
+        path: &Path,
+        value: V,
+    ) -> Result<(), AutomergeError> {
+        let (obj, prop) = doc.resolve_path_for(path, self.scope.clone())?;
+        self.put(doc, patch_log, &obj, prop, value)
+    }
+
+    pub(crate) fn delete_at_path(
+        &mut self,
+        doc: &mut Automerge,
+        patch_log: &mut PatchLog,
+        path: &Path,
+    ) -> Result<(), AutomergeError> {
+        let (obj, prop) = doc.resolve_path_for(path, self.scope.clone())?;
+        self.delete(doc, patch_log, &obj, prop)
+    }
+
+    /// Set `prop` in `obj` to `value`, creating maps and lists for any JSON objects and arrays
+    ///
+    /// Returns the IDs of all the objects which were created, in the order they were created
+    #[cfg(feature = "json")]
+    pub(crate) fn put_json(
+        &mut self,
+        doc: &mut Automerge,
+        patch_log: &mut PatchLog,
+        obj: &ExId,
+        prop: Prop,
+        value: &serde_json::Value,
+    ) -> Result<Vec<ExId>, AutomergeError> {
+        let mut created = Vec::new();
+        self.put_json_value(doc, patch_log, obj, prop, false, value, &mut created)?;
+        Ok(created)
+    }
+
+    #[cfg(feature = "json")]
+    #[allow(clippy::too_many_arguments)]
+    fn put_json_value(
+        &mut self,
+        doc: &mut Automerge,
+        patch_log: &mut PatchLog,
+        parent: &ExId,
+        prop: Prop,
+        insert: bool,
+        value: &serde_json::Value,
+        created: &mut Vec<ExId>,
+    ) -> Result<(), AutomergeError> {
+        let obj_type = match value {
+            serde_json::Value::Object(_) => ObjType::Map,
+            serde_json::Value::Array(_) => ObjType::List,
+            scalar => {
+                let scalar = json_scalar(scalar);
+                return match (insert, prop) {
+                    (true, Prop::Seq(index)) => self.insert(doc, patch_log, parent, index, scalar),
+                    (_, prop) => self.put(doc, patch_log, parent, prop, scalar),
+                };
+            }
+        };
+        let id = match (insert, prop) {
+            (true, Prop::Seq(index)) => {
+                self.insert_object(doc, patch_log, parent, index, obj_type)?
+            }
+            (_, prop) => self.put_object(doc, patch_log, parent, prop, obj_type)?,
+        };
+        created.push(id.clone());
+        self.put_json_children(doc, patch_log, &id, value, created)
+    }
+
+    /// Fill the newly created object `id` with the contents of the JSON object or array `value`
+    #[cfg(feature = "json")]
+    fn put_json_children(
+        &mut self,
+        doc: &mut Automerge,
+        patch_log: &mut PatchLog,
+        id: &ExId,
+        value: &serde_json::Value,
+        created: &mut Vec<ExId>,
+    ) -> Result<(), AutomergeError> {
+        match value {
+            serde_json::Value::Object(map) => {
+                for (key, value) in map {
+                    self.put_json_value(doc, patch_log, id, key.into(), false, value, created)?;
+                }
+            }
+            serde_json::Value::Array(items) => {
+                for (index, value) in items.iter().enumerate() {
+                    self.put_json_value(doc, patch_log, id, index.into(), true, value, created)?;
+                }
+            }
+            _ => unreachable!("scalars are handled above"),
+        }
+        Ok(())
+    }
+
     pub(crate) fn get_scope(&self) -> &Option<Clock> {
         &self.scope
     }
@@ -1138,6 +1520,31 @@ impl TransactionInner {
     }
 }
 
+/// Convert a JSON scalar to a [`ScalarValue`]
+///
+/// Integers which fit in an `i64` become [`ScalarValue::Int`], larger positive integers become
+/// [`ScalarValue::Uint`] and everything else becomes [`ScalarValue::F64`]
+#[cfg(feature = "json")]
+fn json_scalar(value: &serde_json::Value) -> ScalarValue {
+    match value {
+        serde_json::Value::Null => ScalarValue::Null,
+        serde_json::Value::Bool(b) => ScalarValue::Boolean(*b),
+        serde_json::Value::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                ScalarValue::Int(i)
+            } else if let Some(u) = n.as_u64() {
+                ScalarValue::Uint(u)
+            } else {
+                ScalarValue::F64(n.as_f64().unwrap_or(f64::NAN))
+            }
+        }
+        serde_json::Value::String(s) => ScalarValue::Str(s.into()),
+        serde_json::Value::Array(_) | serde_json::Value::Object(_) => {
+            unreachable!("objects are not scalars")
+        }
+    }
+}
+
 enum SpliceType<'a> {
     List,
     Text(&'a str),
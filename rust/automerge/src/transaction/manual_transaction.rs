@@ -1,15 +1,35 @@
+use std::io::Read;
 use std::ops::RangeBounds;
 
+use crate::actor_metadata::{self, ActorMetadata};
+use crate::annotation::{self, AnnotationId};
+use crate::counter_overflow::{self, CounterOverflow};
 use crate::exid::ExId;
+use crate::expiry;
 use crate::iter::Spans;
-use crate::iter::{Keys, ListRange, MapRange, Values};
-use crate::marks::{ExpandMark, Mark, MarkSet};
-use crate::patches::PatchLog;
+use crate::iter::{
+    Keys, KeysRange, ListRange, MapPage, MapPageToken, MapRange, MarkBoundaries, TextChunks, Values,
+};
+use crate::lines;
+use crate::marks::{self, ExpandMark, Mark, MarkMerge, MarkSet};
+use crate::multi_value;
+use crate::patches::{PatchLog, TextEncoding};
+use crate::system::ReadSystem;
+use crate::text_attribution::{self, TextAttribution};
+use crate::text_index::{self, ReadTextIndex};
+use crate::text_search::TextMatch;
 use crate::types::Clock;
 use crate::{hydrate, AutomergeError};
-use crate::{Automerge, ChangeHash, Cursor, ObjType, Parents, Prop, ReadDoc, ScalarValue, Value};
+use crate::{ActorId, Affinity};
+use crate::{
+    Automerge, ChangeHash, Conflicts, Cursor, LastModified, ObjType, OpMeta, Parents, Path, Prop,
+    ReadDoc, ScalarValue, Value,
+};
 
-use super::{CommitOptions, Transactable, TransactionArgs, TransactionInner};
+use super::{
+    CanonicalizedLineEnding, CommitOptions, SpliceItem, Transactable, TransactionArgs,
+    TransactionInner,
+};
 
 /// A transaction on a document.
 /// Transactions group operations into a single change so that no other operations can happen
@@ -58,11 +78,56 @@ impl<'a> Transaction<'a> {
         args: TransactionArgs,
         opts: CommitOptions,
     ) -> ChangeHash {
-        TransactionInner::empty(doc, args, opts.message, opts.time)
+        TransactionInner::empty(doc, args, opts)
     }
 }
 
 impl<'a> Transaction<'a> {
+    /// See [`crate::AutoCommit::set_canonicalize_line_endings()`]
+    pub fn set_canonicalize_line_endings(&mut self, enabled: bool) -> Result<(), AutomergeError> {
+        super::set_canonicalize_line_endings(self, enabled)
+    }
+
+    /// Where `\r\n` was replaced with `\n` in this transaction, see
+    /// [`crate::AutoCommit::set_canonicalize_line_endings()`]
+    pub fn canonicalized_line_endings(&self) -> &[CanonicalizedLineEnding] {
+        self.inner.as_ref().unwrap().canonicalized_line_endings()
+    }
+
+    /// See [`crate::AutoCommit::set_mark_expand()`]
+    pub fn set_mark_expand(
+        &mut self,
+        name: &str,
+        expand: ExpandMark,
+    ) -> Result<(), AutomergeError> {
+        marks::set_mark_expand(self, name, expand)
+    }
+
+    /// See [`crate::AutoCommit::set_mark_merge()`]
+    pub fn set_mark_merge(&mut self, name: &str, merge: MarkMerge) -> Result<(), AutomergeError> {
+        marks::set_mark_merge(self, name, merge)?;
+        self.doc.refresh_mark_merge()
+    }
+
+    /// See [`crate::AutoCommit::set_counter_overflow()`]
+    pub fn set_counter_overflow(
+        &mut self,
+        overflow: CounterOverflow,
+    ) -> Result<(), AutomergeError> {
+        counter_overflow::set_counter_overflow(self, overflow)?;
+        self.doc.refresh_counter_overflow()
+    }
+
+    /// See [`crate::AutoCommit::set_counter_overflow_for_keys()`]
+    pub fn set_counter_overflow_for_keys(
+        &mut self,
+        pattern: &str,
+        overflow: CounterOverflow,
+    ) -> Result<(), AutomergeError> {
+        counter_overflow::set_counter_overflow_for_keys(self, pattern, overflow)?;
+        self.doc.refresh_counter_overflow()
+    }
+
     /// Get the heads of the document before this transaction was started.
     pub fn get_heads(&self) -> Vec<ChangeHash> {
         self.doc.get_heads()
@@ -72,7 +137,7 @@ impl<'a> Transaction<'a> {
     /// the new heads.
     pub fn commit(mut self) -> (Option<ChangeHash>, PatchLog) {
         let tx = self.inner.take().unwrap();
-        let hash = tx.commit(self.doc, None, None);
+        let hash = tx.commit(self.doc, CommitOptions::default());
         // TODO - remove this clone
         (hash, self.patch_log.clone())
     }
@@ -95,7 +160,7 @@ impl<'a> Transaction<'a> {
     /// ```
     pub fn commit_with(mut self, options: CommitOptions) -> (Option<ChangeHash>, PatchLog) {
         let tx = self.inner.take().unwrap();
-        let hash = tx.commit(self.doc, options.message, options.time);
+        let hash = tx.commit(self.doc, options);
         // TODO - remove this clone
         (hash, self.patch_log.clone())
     }
@@ -123,6 +188,260 @@ impl<'a> Transaction<'a> {
     }
 }
 
+impl<'a> ReadSystem for Transaction<'a> {
+    fn system_objects(&self) -> Vec<ExId> {
+        self.doc.system_objects_for(self.get_scope(None))
+    }
+}
+
+impl<'a> ReadTextIndex for Transaction<'a> {
+    fn convert_text_index(
+        &self,
+        obj: &ExId,
+        heads: Option<&[ChangeHash]>,
+        index: usize,
+        from: TextEncoding,
+        to: TextEncoding,
+    ) -> Result<usize, AutomergeError> {
+        self.doc
+            .convert_text_index_for(obj, index, from, to, self.get_scope(heads))
+    }
+}
+
+impl<'a> Transaction<'a> {
+    /// See [`Automerge::keys_range()`]
+    pub fn keys_range<'b, O: AsRef<ExId>, R: RangeBounds<String> + 'b>(
+        &'b self,
+        obj: O,
+        range: R,
+    ) -> KeysRange<'b, R> {
+        self.doc
+            .keys_range_for(obj.as_ref(), range, self.get_scope(None))
+    }
+
+    /// See [`Automerge::keys_range_at()`]
+    pub fn keys_range_at<'b, O: AsRef<ExId>, R: RangeBounds<String> + 'b>(
+        &'b self,
+        obj: O,
+        range: R,
+        heads: &[ChangeHash],
+    ) -> KeysRange<'b, R> {
+        self.doc
+            .keys_range_for(obj.as_ref(), range, self.get_scope(Some(heads)))
+    }
+
+    /// See [`Automerge::map_page()`]
+    pub fn map_page<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        prefix: &str,
+        limit: usize,
+        continue_from: Option<&MapPageToken>,
+    ) -> MapPage<'_> {
+        self.doc.map_page_for(
+            obj.as_ref(),
+            prefix,
+            limit,
+            continue_from,
+            self.get_scope(None),
+        )
+    }
+
+    /// See [`Automerge::map_page_at()`]
+    pub fn map_page_at<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        prefix: &str,
+        limit: usize,
+        continue_from: Option<&MapPageToken>,
+        heads: &[ChangeHash],
+    ) -> MapPage<'_> {
+        self.doc.map_page_for(
+            obj.as_ref(),
+            prefix,
+            limit,
+            continue_from,
+            self.get_scope(Some(heads)),
+        )
+    }
+
+    /// See [`Automerge::text_chunks()`]
+    pub fn text_chunks<O: AsRef<ExId>>(&self, obj: O) -> Result<TextChunks<'_>, AutomergeError> {
+        self.doc.text_chunks_for(obj.as_ref(), self.get_scope(None))
+    }
+
+    /// See [`Automerge::text_display_width()`]
+    pub fn text_display_width<O: AsRef<ExId>, R: RangeBounds<usize>>(
+        &self,
+        obj: O,
+        range: R,
+    ) -> Result<usize, AutomergeError> {
+        self.doc
+            .text_display_width_for(obj.as_ref(), range, self.get_scope(None))
+    }
+
+    /// See [`Automerge::find_text()`]
+    pub fn find_text<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        needle: &str,
+    ) -> Result<Vec<TextMatch>, AutomergeError> {
+        self.doc
+            .find_text_for(obj.as_ref(), needle, self.get_scope(None))
+    }
+
+    /// See [`Automerge::get_unexpired()`]
+    pub fn get_unexpired<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+        now: i64,
+    ) -> Result<Option<(Value<'_>, ExId)>, AutomergeError> {
+        expiry::get_unexpired(self, obj.as_ref(), prop.into(), now)
+    }
+
+    /// See [`Automerge::actor_metadata()`]
+    pub fn actor_metadata(&self, actor: &ActorId) -> Result<Option<ActorMetadata>, AutomergeError> {
+        actor_metadata::actor_metadata(self, actor)
+    }
+
+    /// See [`Automerge::is_multi_value()`]
+    pub fn is_multi_value<O: AsRef<ExId>>(&self, obj: O) -> Result<bool, AutomergeError> {
+        multi_value::is_multi_value(self, obj.as_ref())
+    }
+
+    /// See [`Automerge::get_all_values()`]
+    pub fn get_all_values<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+    ) -> Result<Vec<(Value<'_>, ExId)>, AutomergeError> {
+        multi_value::get_all_values(self, obj.as_ref(), prop.into())
+    }
+
+    /// See [`Automerge::mark_boundaries()`]
+    pub fn mark_boundaries<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+    ) -> Result<MarkBoundaries<'_>, AutomergeError> {
+        self.doc
+            .mark_boundaries_for(obj.as_ref(), self.get_scope(None))
+    }
+
+    /// See [`Automerge::mark_boundaries_at()`]
+    pub fn mark_boundaries_at<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        heads: &[ChangeHash],
+    ) -> Result<MarkBoundaries<'_>, AutomergeError> {
+        self.doc
+            .mark_boundaries_for(obj.as_ref(), self.get_scope(Some(heads)))
+    }
+
+    /// See [`Automerge::get_by_elem_id()`]
+    pub fn get_by_elem_id<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        elem_id: &ExId,
+    ) -> Result<Option<(usize, Value<'_>, ExId)>, AutomergeError> {
+        self.doc
+            .get_by_elem_id_for(obj.as_ref(), elem_id, self.get_scope(None))
+    }
+
+    /// See [`Automerge::is_sealed()`]
+    pub fn is_sealed<O: AsRef<ExId>>(&self, obj: O) -> Result<bool, AutomergeError> {
+        self.doc.is_sealed_for(obj.as_ref(), self.get_scope(None))
+    }
+
+    /// See [`Automerge::attribute_text()`]
+    pub fn attribute_text<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        heads: Option<&[ChangeHash]>,
+    ) -> Result<Vec<TextAttribution>, AutomergeError> {
+        text_attribution::attribute_text(self, self.doc, obj.as_ref(), heads)
+    }
+
+    /// See [`Automerge::get_cursor_with_encoding()`]
+    pub fn get_cursor_with_encoding<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        position: usize,
+        at: Option<&[ChangeHash]>,
+        encoding: TextEncoding,
+    ) -> Result<Cursor, AutomergeError> {
+        text_index::get_cursor(self, obj.as_ref(), position, at, encoding)
+    }
+
+    /// See [`Automerge::get_cursor_position_with_encoding()`]
+    pub fn get_cursor_position_with_encoding<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        cursor: &Cursor,
+        at: Option<&[ChangeHash]>,
+        encoding: TextEncoding,
+    ) -> Result<usize, AutomergeError> {
+        text_index::get_cursor_position(self, obj.as_ref(), cursor, at, encoding)
+    }
+
+    /// See [`Automerge::text_len()`]
+    pub fn text_len<O: AsRef<ExId>>(
+        &self,
+        obj: O,
+        encoding: TextEncoding,
+    ) -> Result<usize, AutomergeError> {
+        self.doc
+            .text_width_for(obj.as_ref(), encoding, self.get_scope(None))
+    }
+
+    /// See [`Automerge::get_conflicts()`]
+    pub fn get_conflicts<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+    ) -> Result<Conflicts<'_>, AutomergeError> {
+        self.doc
+            .get_conflicts_for(obj.as_ref(), prop.into(), self.get_scope(None))
+    }
+
+    /// See [`Automerge::get_conflicts_at()`]
+    pub fn get_conflicts_at<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+        heads: &[ChangeHash],
+    ) -> Result<Conflicts<'_>, AutomergeError> {
+        self.doc
+            .get_conflicts_for(obj.as_ref(), prop.into(), self.get_scope(Some(heads)))
+    }
+
+    /// See [`Automerge::last_modified()`]
+    pub fn last_modified<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+    ) -> Result<Option<LastModified>, AutomergeError> {
+        self.doc
+            .last_modified_for(obj.as_ref(), prop.into(), self.get_scope(None))
+    }
+
+    /// See [`Automerge::last_modified_at()`]
+    pub fn last_modified_at<O: AsRef<ExId>, P: Into<Prop>>(
+        &self,
+        obj: O,
+        prop: P,
+        heads: &[ChangeHash],
+    ) -> Result<Option<LastModified>, AutomergeError> {
+        self.doc
+            .last_modified_for(obj.as_ref(), prop.into(), self.get_scope(Some(heads)))
+    }
+
+    /// See [`Automerge::op_meta()`]
+    pub fn op_meta(&self, id: &ExId) -> Option<OpMeta> {
+        self.doc.op_meta_for(id)
+    }
+}
+
 impl<'a> ReadDoc for Transaction<'a> {
     fn keys<O: AsRef<ExId>>(&self, obj: O) -> Keys<'_> {
         self.doc.keys_for(obj.as_ref(), self.get_scope(None))
@@ -364,6 +683,156 @@ impl<'a> Transactable for Transaction<'a> {
         self.do_tx(|tx, doc, hist| tx.put_object(doc, hist, obj.as_ref(), prop, value))
     }
 
+    #[cfg(feature = "json")]
+    fn put_json<O: AsRef<ExId>, P: Into<Prop>>(
+        &mut self,
+        obj: O,
+        prop: P,
+        value: &serde_json::Value,
+    ) -> Result<Vec<ExId>, AutomergeError> {
+        self.do_tx(|tx, doc, hist| tx.put_json(doc, hist, obj.as_ref(), prop.into(), value))
+    }
+
+    fn put_at_path<V: Into<ScalarValue>>(
+        &mut self,
+        path: &Path,
+        value: V,
+    ) -> Result<(), AutomergeError> {
+        self.do_tx(|tx, doc, hist| tx.put_at_path(doc, hist, path, value))
+    }
+
+    fn delete_at_path(&mut self, path: &Path) -> Result<(), AutomergeError> {
+        self.do_tx(|tx, doc, hist| tx.delete_at_path(doc, hist, path))
+    }
+
+    fn put_with_expiry<O: AsRef<ExId>, P: Into<Prop>, V: Into<ScalarValue>>(
+        &mut self,
+        obj: O,
+        prop: P,
+        value: V,
+        expires_at: i64,
+    ) -> Result<ExId, AutomergeError> {
+        expiry::put_with_expiry(self, obj.as_ref(), prop.into(), value.into(), expires_at)
+    }
+
+    fn gc_expired(&mut self, now: i64) -> Result<usize, AutomergeError> {
+        expiry::gc_expired(self, now)
+    }
+
+    fn set_actor_metadata(
+        &mut self,
+        actor: &ActorId,
+        metadata: &ActorMetadata,
+    ) -> Result<(), AutomergeError> {
+        actor_metadata::set_actor_metadata(self, actor, metadata)
+    }
+
+    fn set_multi_value<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        enabled: bool,
+    ) -> Result<(), AutomergeError> {
+        multi_value::set_multi_value(self, obj.as_ref(), enabled)
+    }
+
+    fn seal_object<O: AsRef<ExId>>(&mut self, obj: O) -> Result<(), AutomergeError> {
+        self.do_tx(|tx, doc, _| tx.seal_object(doc, obj.as_ref()))
+    }
+
+    fn splice_text_from_reader<O: AsRef<ExId>, R: Read>(
+        &mut self,
+        obj: O,
+        pos: usize,
+        del: isize,
+        reader: R,
+        limit: Option<usize>,
+    ) -> Result<usize, AutomergeError> {
+        text_index::splice_text_from_reader(self, obj.as_ref(), pos, del, reader, limit)
+    }
+
+    fn insert_line<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        index: usize,
+        line: &str,
+    ) -> Result<(), AutomergeError> {
+        lines::insert_line(self, obj.as_ref(), index, line)
+    }
+
+    fn insert_embed<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        index: usize,
+    ) -> Result<ExId, AutomergeError> {
+        text_index::insert_embed(self, obj.as_ref(), index)
+    }
+
+    fn unmark_where<O, F>(&mut self, obj: O, predicate: F) -> Result<(), AutomergeError>
+    where
+        O: AsRef<ExId>,
+        F: FnMut(&Mark<'_>) -> bool,
+    {
+        marks::unmark_where(self, obj.as_ref(), predicate)
+    }
+
+    fn add_annotation<O: AsRef<ExId>, V: Into<ScalarValue>>(
+        &mut self,
+        obj: O,
+        start: usize,
+        end: usize,
+        payload: V,
+    ) -> Result<AnnotationId, AutomergeError> {
+        annotation::add_annotation(self, obj.as_ref(), start, end, payload.into())
+    }
+
+    fn remove_annotation<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        id: &AnnotationId,
+    ) -> Result<(), AutomergeError> {
+        annotation::remove_annotation(self, obj.as_ref(), id)
+    }
+
+    fn splice_text_with_encoding<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        pos: usize,
+        del: isize,
+        text: &str,
+        encoding: TextEncoding,
+    ) -> Result<(), AutomergeError> {
+        text_index::splice_text(self, obj.as_ref(), pos, del, text, encoding)
+    }
+
+    fn splice_text_with_cursors<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        pos: usize,
+        del: isize,
+        text: &str,
+        affinity: Affinity,
+    ) -> Result<(Cursor, Cursor), AutomergeError> {
+        text_index::splice_text_with_cursors(self, obj.as_ref(), pos, del, text, affinity)
+    }
+
+    fn mark_with_encoding<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        mark: Mark<'_>,
+        expand: ExpandMark,
+        encoding: TextEncoding,
+    ) -> Result<(), AutomergeError> {
+        text_index::mark(self, obj.as_ref(), mark, expand, encoding)
+    }
+
+    fn apply_unified_diff<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        diff: &str,
+    ) -> Result<(), crate::error::UnifiedDiffError> {
+        crate::transaction::apply_unified_diff(self, obj.as_ref(), diff)
+    }
+
     fn insert<O: AsRef<ExId>, V: Into<ScalarValue>>(
         &mut self,
         obj: O,
@@ -412,6 +881,16 @@ impl<'a> Transactable for Transaction<'a> {
         Ok(())
     }
 
+    fn splice_full<O: AsRef<ExId>, I: IntoIterator<Item = SpliceItem>>(
+        &mut self,
+        obj: O,
+        pos: usize,
+        del: isize,
+        items: I,
+    ) -> Result<Vec<ExId>, AutomergeError> {
+        self.do_tx(|tx, doc, hist| tx.splice_full(doc, hist, obj.as_ref(), pos, del, items))
+    }
+
     fn splice_text<O: AsRef<ExId>>(
         &mut self,
         obj: O,
@@ -432,6 +911,15 @@ impl<'a> Transactable for Transaction<'a> {
         self.do_tx(|tx, doc, hist| tx.mark(doc, hist, obj.as_ref(), mark, expand))
     }
 
+    fn mark_with_default_expand<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        mark: Mark<'_>,
+    ) -> Result<(), AutomergeError> {
+        let expand = marks::mark_expand(self, mark.name())?;
+        self.mark(obj, mark, expand)
+    }
+
     fn unmark<O: AsRef<ExId>>(
         &mut self,
         obj: O,
@@ -0,0 +1,63 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+
+use crate::exid::ExId;
+use crate::{AutomergeError, ObjType, ScalarValue, Value};
+
+use super::Transactable;
+
+pub(crate) fn insert_sorted<T, F>(
+    tx: &mut T,
+    obj: &ExId,
+    value: ScalarValue,
+    mut compare: F,
+) -> Result<usize, AutomergeError>
+where
+    T: Transactable + ?Sized,
+    F: FnMut(&Value<'_>, &Value<'_>) -> Ordering,
+{
+    let typ = tx.object_type(obj)?;
+    if typ != ObjType::List {
+        return Err(AutomergeError::InvalidOp(typ));
+    }
+    let new = Value::Scalar(Cow::Borrowed(&value));
+    // find the first element which sorts after `value` so that equal elements keep the order
+    // they were inserted in
+    let (mut low, mut high) = (0, tx.length(obj));
+    while low < high {
+        let mid = low + (high - low) / 2;
+        let (existing, _) = tx.get(obj, mid)?.ok_or(AutomergeError::InvalidIndex(mid))?;
+        if compare(&existing, &new) == Ordering::Greater {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+    tx.insert(obj, low, value)?;
+    Ok(low)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{transaction::Transactable, AutoCommit, ObjType, ReadDoc, ScalarValue, ROOT};
+
+    #[test]
+    fn equal_values_keep_insertion_order() {
+        let mut doc = AutoCommit::new();
+        let list = doc.put_object(&ROOT, "list", ObjType::List).unwrap();
+        let by_first_char = |a: &crate::Value<'_>, b: &crate::Value<'_>| {
+            a.to_str().unwrap()[..1].cmp(&b.to_str().unwrap()[..1])
+        };
+        for word in ["banana", "apple", "cherry", "blueberry", "avocado"] {
+            doc.insert_sorted(&list, word, by_first_char).unwrap();
+        }
+        let words = doc
+            .values(&list)
+            .map(|(v, _)| v.to_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(words, ["apple", "avocado", "banana", "blueberry", "cherry"]);
+        assert!(doc
+            .insert_sorted(&ROOT, ScalarValue::Null, by_first_char)
+            .is_err());
+    }
+}
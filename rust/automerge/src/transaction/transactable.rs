@@ -1,8 +1,16 @@
 use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::io::Read;
 
+use crate::actor_metadata::ActorMetadata;
+use crate::annotation::AnnotationId;
 use crate::exid::ExId;
 use crate::marks::{ExpandMark, Mark};
-use crate::{AutomergeError, ChangeHash, ObjType, Prop, ReadDoc, ScalarValue};
+use crate::patches::TextEncoding;
+use crate::{
+    ActorId, Affinity, AutomergeError, ChangeHash, Cursor, ObjType, Path, Prop, ReadDoc,
+    ScalarValue, Value,
+};
 
 /// A way of mutating a document within a single change.
 pub trait Transactable: ReadDoc {
@@ -43,6 +51,129 @@ pub trait Transactable: ReadDoc {
         object: ObjType,
     ) -> Result<ExId, AutomergeError>;
 
+    /// Set the value of `prop` in `obj` to `value` and record that it expires at `expires_at`
+    ///
+    /// Once it has expired [`crate::Automerge::get_unexpired()`] treats the value as absent and
+    /// [`Self::gc_expired()`] deletes it. Expiry times are stored in the document, see
+    /// [`crate::expiry`], so every peer agrees on when a value expires. The expiry only applies
+    /// to the value written by this call, if it is overwritten the new value does not expire.
+    ///
+    /// # Returns
+    ///
+    /// The ID of the operation which wrote the value
+    fn put_with_expiry<O: AsRef<ExId>, P: Into<Prop>, V: Into<ScalarValue>>(
+        &mut self,
+        obj: O,
+        prop: P,
+        value: V,
+        expires_at: i64,
+    ) -> Result<ExId, AutomergeError>;
+
+    /// Delete every value written with [`Self::put_with_expiry()`] which has expired as at
+    /// `now`, returning the number of values deleted
+    ///
+    /// This also removes the recorded expiry times of values which have since been overwritten
+    /// or deleted.
+    fn gc_expired(&mut self, now: i64) -> Result<usize, AutomergeError>;
+
+    /// Record display metadata for `actor` in the document, see [`crate::actor_metadata`]
+    ///
+    /// Fields of `metadata` which are `None` leave the current value, if any, unchanged.
+    fn set_actor_metadata(
+        &mut self,
+        actor: &ActorId,
+        metadata: &ActorMetadata,
+    ) -> Result<(), AutomergeError>;
+
+    /// Mark `obj` as a multi-value register, or not, see [`crate::multi_value`]
+    ///
+    /// This is recorded in the document so every peer reads the object the same way.
+    fn set_multi_value<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        enabled: bool,
+    ) -> Result<(), AutomergeError>;
+
+    /// Annotate the characters from `start` up to `end` in the text object `obj` with
+    /// `payload`, see [`crate::annotation`]
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `payload` is null.
+    fn add_annotation<O: AsRef<ExId>, V: Into<ScalarValue>>(
+        &mut self,
+        obj: O,
+        start: usize,
+        end: usize,
+        payload: V,
+    ) -> Result<AnnotationId, AutomergeError>;
+
+    /// Remove the annotation `id` from the text object `obj`, doing nothing if there is no such
+    /// annotation
+    fn remove_annotation<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        id: &AnnotationId,
+    ) -> Result<(), AutomergeError>;
+
+    /// Make `obj` read-only, see [`crate::seal`]
+    ///
+    /// Any change to `obj` after this fails with [`AutomergeError::ObjectSealed`], and changes
+    /// other peers make to it concurrently are ignored once they are merged. A seal cannot be
+    /// removed.
+    fn seal_object<O: AsRef<ExId>>(&mut self, obj: O) -> Result<(), AutomergeError>;
+
+    /// Set the value of property `P` in object `obj` to the JSON value `value`
+    ///
+    /// JSON objects and arrays are recursively created as [`ObjType::Map`] and [`ObjType::List`]
+    /// objects, strings become [`ScalarValue::Str`] and numbers become [`ScalarValue::Int`],
+    /// [`ScalarValue::Uint`] or [`ScalarValue::F64`] depending on whether they are integers.
+    /// This requires the `json` feature.
+    ///
+    /// # Returns
+    ///
+    /// The ids of all the objects which were created, in the order in which they were created.
+    /// If `value` is a map or list then the first id is the id of `value` itself. If `value` is
+    /// a scalar this will be empty.
+    ///
+    /// # Errors
+    ///
+    /// This will return an error if
+    /// - The object does not exist
+    /// - The key is the wrong type for the object
+    /// - The key does not exist in the object
+    #[cfg(feature = "json")]
+    fn put_json<O: AsRef<ExId>, P: Into<Prop>>(
+        &mut self,
+        obj: O,
+        prop: P,
+        value: &serde_json::Value,
+    ) -> Result<Vec<ExId>, AutomergeError>;
+
+    /// Set the value at `path` to `value`, see [`Path`] for details of how paths are resolved
+    ///
+    /// # Errors
+    ///
+    /// This will return an error if
+    /// - `path` is the root path
+    /// - Any segment of `path` before the last one does not refer to an object
+    /// - The last segment of `path` is the wrong type for the object it refers to
+    fn put_at_path<V: Into<ScalarValue>>(
+        &mut self,
+        path: &Path,
+        value: V,
+    ) -> Result<(), AutomergeError>;
+
+    /// Delete the value at `path`, see [`Path`] for details of how paths are resolved
+    ///
+    /// # Errors
+    ///
+    /// This will return an error if
+    /// - `path` is the root path
+    /// - Any segment of `path` before the last one does not refer to an object
+    /// - The last segment of `path` is the wrong type for the object it refers to
+    fn delete_at_path(&mut self, path: &Path) -> Result<(), AutomergeError>;
+
     /// Insert a value into a list at the given index.
     fn insert<O: AsRef<ExId>, V: Into<ScalarValue>>(
         &mut self,
@@ -51,6 +182,34 @@ pub trait Transactable: ReadDoc {
         value: V,
     ) -> Result<(), AutomergeError>;
 
+    /// Insert `value` into the list `obj` at the position given by `compare`, returning the index
+    /// it was inserted at
+    ///
+    /// The list must already be sorted by `compare`, which is called with an element of the list
+    /// and the new value as for [`slice::sort_by()`]. The position is found with a binary search
+    /// so only a logarithmic number of elements are read. The value is inserted after any
+    /// elements which compare equal to it.
+    ///
+    /// Concurrent insertions by other peers are not ordered by `compare`, so a list which is
+    /// edited concurrently may need to be re-sorted after merging.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AutomergeError::InvalidOp`] if `obj` is not a list
+    fn insert_sorted<O, V, F>(
+        &mut self,
+        obj: O,
+        value: V,
+        compare: F,
+    ) -> Result<usize, AutomergeError>
+    where
+        O: AsRef<ExId>,
+        V: Into<ScalarValue>,
+        F: FnMut(&Value<'_>, &Value<'_>) -> Ordering,
+    {
+        super::insert_sorted(self, obj.as_ref(), value.into(), compare)
+    }
+
     /// Insert an object into a list at the given index.
     fn insert_object<O: AsRef<ExId>>(
         &mut self,
@@ -85,6 +244,27 @@ pub trait Transactable: ReadDoc {
         vals: V,
     ) -> Result<(), AutomergeError>;
 
+    /// Like [`Self::splice()`] but the inserted values can be objects
+    ///
+    /// Each item is inserted at `pos` onwards, see [`SpliceItem`] for the kinds of value which
+    /// can be inserted. This is much faster than calling [`Self::insert_object()`] once per item
+    /// when inserting many rows.
+    ///
+    /// For text objects `pos` and `del` are in the units text is indexed in, as for
+    /// [`Self::splice_text()`].
+    ///
+    /// # Returns
+    ///
+    /// The ids of all the objects which were created, in the order in which they were created.
+    /// The id of an object is always before the ids of the objects nested in it.
+    fn splice_full<O: AsRef<ExId>, I: IntoIterator<Item = SpliceItem>>(
+        &mut self,
+        obj: O,
+        pos: usize,
+        del: isize,
+        items: I,
+    ) -> Result<Vec<ExId>, AutomergeError>;
+
     /// Like [`Self::splice`] but for text.
     fn splice_text<O: AsRef<ExId>>(
         &mut self,
@@ -102,6 +282,53 @@ pub trait Transactable: ReadDoc {
         expand: ExpandMark,
     ) -> Result<(), AutomergeError>;
 
+    /// Mark a sequence using the expand behaviour set for the name of the mark with
+    /// [`crate::AutoCommit::set_mark_expand()`]
+    fn mark_with_default_expand<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        mark: Mark<'_>,
+    ) -> Result<(), AutomergeError>;
+
+    /// Like [`Self::splice_text()`] but with `pos` and `del` in `encoding` rather than the
+    /// encoding this build uses for text indices
+    ///
+    /// This lets clients which measure text in different units, e.g. a JavaScript front end
+    /// using UTF-16 and a Rust back end using code points, edit the same document using their
+    /// own indices.
+    fn splice_text_with_encoding<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        pos: usize,
+        del: isize,
+        text: &str,
+        encoding: TextEncoding,
+    ) -> Result<(), AutomergeError>;
+
+    /// Like [`Self::splice_text()`] but returns cursors pointing at the first and the last of the
+    /// inserted characters, both with the given `affinity`
+    ///
+    /// This lets callers track the inserted region without asking for the cursors separately.
+    /// Returns an error without changing the text if `text` is empty.
+    fn splice_text_with_cursors<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        pos: usize,
+        del: isize,
+        text: &str,
+        affinity: Affinity,
+    ) -> Result<(Cursor, Cursor), AutomergeError>;
+
+    /// Like [`Self::mark()`] but with the start and end of `mark` in `encoding` rather than the
+    /// encoding this build uses for text indices
+    fn mark_with_encoding<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        mark: Mark<'_>,
+        expand: ExpandMark,
+        encoding: TextEncoding,
+    ) -> Result<(), AutomergeError>;
+
     /// Remove a Mark from a sequence
     fn unmark<O: AsRef<ExId>>(
         &mut self,
@@ -112,6 +339,58 @@ pub trait Transactable: ReadDoc {
         expand: ExpandMark,
     ) -> Result<(), AutomergeError>;
 
+    /// Remove every mark on a sequence for which `predicate` returns true
+    ///
+    /// The predicate is called with each of the marks returned by [`ReadDoc::marks()`], so it
+    /// can look at the name, the value and the range of the mark. Touching ranges of the same
+    /// name which are removed are removed with a single unmark.
+    fn unmark_where<O, F>(&mut self, obj: O, predicate: F) -> Result<(), AutomergeError>
+    where
+        O: AsRef<ExId>,
+        F: FnMut(&Mark<'_>) -> bool;
+
+    /// Like [`Self::splice_text()`] but reading the text to insert from `reader`
+    ///
+    /// The text is read and inserted a chunk at a time so the whole of a very large paste never
+    /// has to be held in memory at once. If `limit` is given then reading more than `limit`
+    /// bytes is an error, as is text which is not valid UTF-8. Any text which was inserted
+    /// before an error remains in the transaction, so roll it back to discard a failed paste.
+    ///
+    /// Returns the width of the inserted text.
+    fn splice_text_from_reader<O: AsRef<ExId>, R: Read>(
+        &mut self,
+        obj: O,
+        pos: usize,
+        del: isize,
+        reader: R,
+        limit: Option<usize>,
+    ) -> Result<usize, AutomergeError>;
+
+    /// Insert `line` into the lines object `obj` so that it becomes line number `index`
+    ///
+    /// A lines object is a list of strings which stores each line as a single operation rather
+    /// than one per character, see [`crate::ReadDoc::lines_range()`]. Returns an error if `obj`
+    /// is not a list or `line` contains a newline.
+    fn insert_line<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        index: usize,
+        line: &str,
+    ) -> Result<(), AutomergeError>;
+
+    /// Insert an object, such as a reference to an image, into the text object `obj` at `index`
+    ///
+    /// The embed takes up one character of the text, like a block marker, and is a map which can
+    /// be filled in with the normal methods of modifying a map. It differs from a block marker in
+    /// having the key [`crate::iter::EMBED_KEY`], which makes it a
+    /// [`crate::iter::Span::Embed`] rather than a [`crate::iter::Span::Block`] in
+    /// [`ReadDoc::spans()`].
+    fn insert_embed<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        index: usize,
+    ) -> Result<ExId, AutomergeError>;
+
     /// Insert a block marker into the text object `obj` at the given index.
     ///
     /// # Returns
@@ -142,6 +421,25 @@ pub trait Transactable: ReadDoc {
         new_text: I,
     ) -> Result<(), AutomergeError>;
 
+    /// Apply a unified diff, as produced by `diff -u` or `git diff`, to the text object `obj`
+    ///
+    /// Each hunk becomes a splice of the lines it changes, so concurrent edits to other parts of
+    /// the text merge as they would if the changes had been typed in. The context and removed
+    /// lines of every hunk must match the current text exactly, hunks are not moved to find
+    /// their context, and if any hunk does not match nothing is changed. Only the hunks are read
+    /// so the diff should contain the changes to a single file.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::error::UnifiedDiffError::Parse`] if the diff is not valid,
+    /// [`crate::error::UnifiedDiffError::ContextMismatch`] if a hunk does not match the text and
+    /// [`AutomergeError::InvalidOp`] if `obj` is not a text object.
+    fn apply_unified_diff<O: AsRef<ExId>>(
+        &mut self,
+        obj: O,
+        diff: &str,
+    ) -> Result<(), crate::error::UnifiedDiffError>;
+
     /// The heads this transaction will be based on
     fn base_heads(&self) -> Vec<ChangeHash>;
 
@@ -152,6 +450,9 @@ pub trait Transactable: ReadDoc {
     /// which don't merge as well as directly capturing the user input actions, but
     /// sometimes it's not possible to capture user input and this is the best you
     /// can do.
+    ///
+    /// The diff is computed over grapheme clusters and only the graphemes which changed are
+    /// deleted or inserted, so marks and cursors on the rest of the text are preserved.
     fn update_text<S: AsRef<str>>(&mut self, obj: &ExId, new_text: S)
         -> Result<(), AutomergeError>;
 
@@ -167,3 +468,43 @@ pub enum BlockOrText<'a> {
     Block(crate::hydrate::Map),
     Text(Cow<'a, str>),
 }
+
+/// A value to insert with [`Transactable::splice_full()`]
+#[derive(Debug, PartialEq, Clone)]
+pub enum SpliceItem {
+    /// A scalar value
+    Scalar(ScalarValue),
+    /// A new empty object
+    Object(ObjType),
+    /// A JSON value, objects and arrays are created as in [`Transactable::put_json()`]
+    #[cfg(feature = "json")]
+    Json(serde_json::Value),
+}
+
+impl From<ScalarValue> for SpliceItem {
+    fn from(value: ScalarValue) -> Self {
+        SpliceItem::Scalar(value)
+    }
+}
+
+impl From<ObjType> for SpliceItem {
+    fn from(value: ObjType) -> Self {
+        SpliceItem::Object(value)
+    }
+}
+
+#[cfg(feature = "json")]
+impl From<serde_json::Value> for SpliceItem {
+    fn from(value: serde_json::Value) -> Self {
+        SpliceItem::Json(value)
+    }
+}
+
+impl<'a> From<Value<'a>> for SpliceItem {
+    fn from(value: Value<'a>) -> Self {
+        match value {
+            Value::Object(typ) => SpliceItem::Object(typ),
+            Value::Scalar(scalar) => SpliceItem::Scalar(scalar.into_owned()),
+        }
+    }
+}
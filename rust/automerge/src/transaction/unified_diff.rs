@@ -0,0 +1,230 @@
+use crate::error::UnifiedDiffError;
+use crate::exid::ExId;
+use crate::text_value::TextValue;
+use crate::{AutomergeError, ObjType};
+
+use super::Transactable;
+
+/// One hunk of a unified diff
+#[derive(Debug, Default)]
+struct Hunk<'a> {
+    /// The line of the diff the hunk header is on, for errors
+    line: usize,
+    /// The index of the first line of the old text the hunk applies to
+    start: usize,
+    old_len: usize,
+    new_len: usize,
+    /// The context and removed lines, including their line endings
+    old: Vec<&'a str>,
+    /// The context and added lines, including their line endings
+    new: Vec<&'a str>,
+}
+
+impl<'a> Hunk<'a> {
+    fn is_complete(&self) -> bool {
+        self.old.len() == self.old_len && self.new.len() == self.new_len
+    }
+}
+
+pub(crate) fn apply_unified_diff<T: Transactable + ?Sized>(
+    tx: &mut T,
+    obj: &ExId,
+    diff: &str,
+) -> Result<(), UnifiedDiffError> {
+    let typ = tx.object_type(obj)?;
+    if typ != ObjType::Text {
+        return Err(AutomergeError::InvalidOp(typ).into());
+    }
+    let hunks = parse(diff)?;
+    let text = tx.text(obj)?;
+    let lines = text.split_inclusive('\n').collect::<Vec<_>>();
+
+    // check every hunk before changing anything so a diff which does not apply leaves the text
+    // as it was
+    let mut splices = Vec::with_capacity(hunks.len());
+    let mut next_line = 0;
+    let mut pos = 0;
+    for hunk in &hunks {
+        let end = hunk.start + hunk.old.len();
+        if hunk.start < next_line || end > lines.len() || lines[hunk.start..end] != hunk.old {
+            return Err(UnifiedDiffError::ContextMismatch { line: hunk.line });
+        }
+        pos += lines[next_line..hunk.start]
+            .iter()
+            .map(|l| TextValue::width(l))
+            .sum::<usize>();
+        let del = hunk.old.iter().map(|l| TextValue::width(l)).sum::<usize>();
+        splices.push((pos, del, hunk.new.concat()));
+        pos += del;
+        next_line = end;
+    }
+
+    // splice from the end so the positions of earlier hunks stay the same
+    for (pos, del, insert) in splices.into_iter().rev() {
+        tx.splice_text(obj, pos, del as isize, &insert)?;
+    }
+    Ok(())
+}
+
+fn parse(diff: &str) -> Result<Vec<Hunk<'_>>, UnifiedDiffError> {
+    let mut hunks = Vec::new();
+    let mut current: Option<Hunk<'_>> = None;
+    let mut last_prefix = None;
+    for (n, line) in diff.split_inclusive('\n').enumerate() {
+        let n = n + 1;
+        if line.starts_with('\\') {
+            // "\ No newline at end of file" applies to the line before it
+            let Some(hunk) = current.as_mut() else {
+                return Err(UnifiedDiffError::Parse {
+                    line: n,
+                    reason: "no newline marker outside a hunk",
+                });
+            };
+            if matches!(last_prefix, Some(' ' | '-')) {
+                strip_newline(hunk.old.last_mut());
+            }
+            if matches!(last_prefix, Some(' ' | '+')) {
+                strip_newline(hunk.new.last_mut());
+            }
+        } else if let Some(hunk) = current.as_mut().filter(|h| !h.is_complete()) {
+            // some tools strip the space from empty context lines
+            let (prefix, content) = match line.chars().next() {
+                Some(prefix @ (' ' | '-' | '+')) => (prefix, &line[1..]),
+                Some('\n') => (' ', line),
+                _ => {
+                    return Err(UnifiedDiffError::Parse {
+                        line: n,
+                        reason: "expected a context, removed or added line",
+                    })
+                }
+            };
+            match prefix {
+                ' ' => {
+                    hunk.old.push(content);
+                    hunk.new.push(content);
+                }
+                '-' => hunk.old.push(content),
+                _ => hunk.new.push(content),
+            }
+            if hunk.old.len() > hunk.old_len || hunk.new.len() > hunk.new_len {
+                return Err(UnifiedDiffError::Parse {
+                    line: n,
+                    reason: "the hunk has more lines than its header says",
+                });
+            }
+            last_prefix = Some(prefix);
+        } else if line.starts_with("@@") {
+            if let Some(hunk) = current.take() {
+                hunks.push(hunk);
+            }
+            current = Some(parse_header(line, n)?);
+        } else if current.is_some() && !line.starts_with("diff ") {
+            // anything else between hunks is part of the header of the next file
+            if !(line.starts_with("---") || line.starts_with("+++") || line.starts_with("index ")) {
+                return Err(UnifiedDiffError::Parse {
+                    line: n,
+                    reason: "unexpected line after the end of a hunk",
+                });
+            }
+        }
+    }
+    if let Some(hunk) = current {
+        if !hunk.is_complete() {
+            return Err(UnifiedDiffError::Parse {
+                line: hunk.line,
+                reason: "the hunk has fewer lines than its header says",
+            });
+        }
+        hunks.push(hunk);
+    }
+    Ok(hunks)
+}
+
+fn strip_newline(line: Option<&mut &str>) {
+    if let Some(line) = line {
+        *line = line.strip_suffix('\n').unwrap_or(line);
+    }
+}
+
+/// Parse a hunk header of the form `@@ -start,len +start,len @@`
+fn parse_header(line: &str, n: usize) -> Result<Hunk<'_>, UnifiedDiffError> {
+    let invalid = UnifiedDiffError::Parse {
+        line: n,
+        reason: "invalid hunk header",
+    };
+    let mut parts = line.split_whitespace().skip(1);
+    let (Some(old), Some(new)) = (parts.next(), parts.next()) else {
+        return Err(invalid);
+    };
+    let range = |s: Option<&str>| -> Option<(usize, usize)> {
+        let mut nums = s?.splitn(2, ',');
+        let start = nums.next()?.parse().ok()?;
+        let len = nums.next().map(|l| l.parse().ok()).unwrap_or(Some(1))?;
+        Some((start, len))
+    };
+    let (Some((start, old_len)), Some((_, new_len))) =
+        (range(old.strip_prefix('-')), range(new.strip_prefix('+')))
+    else {
+        return Err(invalid);
+    };
+    Ok(Hunk {
+        line: n,
+        // an empty range starts after the line it names rather than at it
+        start: if old_len == 0 {
+            start
+        } else {
+            start.saturating_sub(1)
+        },
+        old_len,
+        new_len,
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::UnifiedDiffError;
+    use crate::{transaction::Transactable, AutoCommit, ObjType, ReadDoc, ROOT};
+
+    #[test]
+    fn applies_hunks_to_text() {
+        let mut doc = AutoCommit::new();
+        let text = doc.put_object(&ROOT, "text", ObjType::Text).unwrap();
+        doc.splice_text(&text, 0, 0, "one\ntwo\nthree\nfour\nfive\nsix")
+            .unwrap();
+        let diff = "\
+--- a/notes.txt
++++ b/notes.txt
+@@ -0,0 +1 @@
++zero
+@@ -2,3 +3,2 @@
+ two
+-three
+-four
++3 and 4
+@@ -6 +6,2 @@
+-six
+\\ No newline at end of file
++six
++seven
+";
+        doc.apply_unified_diff(&text, diff).unwrap();
+        assert_eq!(
+            doc.text(&text).unwrap(),
+            "zero\none\ntwo\n3 and 4\nfive\nsix\nseven\n"
+        );
+
+        // context which does not match leaves the text alone
+        let before = doc.text(&text).unwrap();
+        let stale = "@@ -1,2 +1,2 @@\n zero\n-two\n+2\n";
+        assert!(matches!(
+            doc.apply_unified_diff(&text, stale),
+            Err(UnifiedDiffError::ContextMismatch { line: 1 })
+        ));
+        assert!(matches!(
+            doc.apply_unified_diff(&text, "@@ -1,2 +1 @@\n zero\n"),
+            Err(UnifiedDiffError::Parse { line: 1, .. })
+        ));
+        assert_eq!(doc.text(&text).unwrap(), before);
+    }
+}
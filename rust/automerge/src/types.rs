@@ -155,8 +155,11 @@ impl fmt::Display for ActorId {
 }
 
 /// The type of an object
+///
+/// New object types may be added, so matches on this need a wildcard arm.
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Copy, Hash)]
 #[serde(rename_all = "camelCase", untagged)]
+#[non_exhaustive]
 pub enum ObjType {
     /// A map
     Map,
@@ -166,6 +169,11 @@ pub enum ObjType {
     List,
     /// A sequence of characters
     Text,
+    /// An object created by a newer version of automerge with the given action code
+    ///
+    /// Objects of unknown types are loaded and saved unchanged but cannot be read into or
+    /// modified.
+    Unknown(u64),
 }
 
 impl ObjType {
@@ -199,10 +207,20 @@ impl fmt::Display for ObjType {
             ObjType::Table => write!(f, "table"),
             ObjType::List => write!(f, "list"),
             ObjType::Text => write!(f, "text"),
+            ObjType::Unknown(code) => write!(f, "unknown({})", code),
         }
     }
 }
 
+/// The actions reserved for creating objects of types added after this version
+///
+/// Ops with these actions are loaded as creating an [`ObjType::Unknown`], ops with any other
+/// action we don't know are rejected as we can't tell what they do.
+pub(crate) const UNKNOWN_OBJ_ACTIONS: std::ops::RangeInclusive<u64> = 8..=15;
+
+/// The action of an op which seals an object, see [`OpType::Seal`]
+pub(crate) const SEAL_ACTION: u64 = 16;
+
 #[derive(PartialEq, Debug, Clone)]
 pub enum OpType {
     Make(ObjType),
@@ -211,6 +229,11 @@ pub enum OpType {
     Put(ScalarValue),
     MarkBegin(bool, MarkData),
     MarkEnd(bool),
+    /// Seal the object created by the predecessor of this op, see [`crate::seal`]
+    ///
+    /// The hashes are the heads the seal was made on, the ops in the sealed object which they
+    /// cover are the only ones visible in it.
+    Seal(Vec<ChangeHash>),
 }
 
 impl OpType {
@@ -227,6 +250,8 @@ impl OpType {
             Self::Increment(_) => 5,
             Self::Make(ObjType::Table) => 6,
             Self::MarkBegin(_, _) | Self::MarkEnd(_) => 7,
+            Self::Make(ObjType::Unknown(code)) => *code,
+            Self::Seal(_) => SEAL_ACTION,
         }
     }
 
@@ -242,6 +267,14 @@ impl OpType {
             },
             6 => Ok(()),
             7 => Ok(()),
+            // objects of types added by newer versions are loaded as objects of an unknown type,
+            // any other action we can't preserve
+            a if UNKNOWN_OBJ_ACTIONS.contains(&a) => Ok(()),
+            // a seal we can't read the heads of can't be preserved either
+            SEAL_ACTION => match value {
+                ScalarValue::Bytes(heads) if heads.len() % HASH_SIZE == 0 => Ok(()),
+                _ => Err(error::InvalidOpType::UnknownAction(action)),
+            },
             _ => Err(error::InvalidOpType::UnknownAction(action)),
         }
     }
@@ -268,7 +301,11 @@ impl OpType {
                 Some(name) => Self::MarkBegin(expand, MarkData { name, value }),
                 None => Self::MarkEnd(expand),
             },
-            _ => unreachable!("validate_action_and_value returned UnknownAction"),
+            SEAL_ACTION => match value {
+                ScalarValue::Bytes(heads) => Self::Seal(crate::seal::heads_from_bytes(&heads)),
+                _ => unreachable!("validate_action_and_value returned UnknownAction"),
+            },
+            _ => Self::Make(ObjType::Unknown(action)),
         }
     }
 
@@ -289,6 +326,10 @@ impl OpType {
     pub(crate) fn is_block(&self) -> bool {
         &OpType::Make(ObjType::Map) == self
     }
+
+    pub(crate) fn is_seal(&self) -> bool {
+        matches!(&self, OpType::Seal(_))
+    }
 }
 
 impl From<ObjType> for OpType {
@@ -365,15 +365,132 @@ pub(crate) enum DataType {
     Undefined,
 }
 
+/// The value of a counter
+///
+/// A counter may have bounds, created with [`ScalarValue::bounded_counter()`]. The bounds are
+/// stored with the counter and the value of a bounded counter saturates at them: the value is the
+/// sum of the initial value and every increment, clamped to the bounds. Clamping the total
+/// rather than each increment means that concurrent increments produce the same value whatever
+/// order they are applied in, at the cost that a counter which has been incremented past a bound
+/// must be decremented by the excess before its value moves away from the bound.
+///
+/// The value of a counter without bounds wraps around when it passes `i64::MIN` or `i64::MAX`
+/// unless the document's [`crate::CounterOverflow`] setting for it says otherwise.
 #[derive(Debug, Clone)]
 pub struct Counter {
     pub(crate) start: i64,
+    /// The sum of `start` and every increment, wrapped to an `i64`
     pub(crate) current: i64,
+    /// The bounds of a bounded counter and the number of times `current` has wrapped, boxed so
+    /// that unbounded counters which have never wrapped (and so every `ScalarValue`) don't pay
+    /// for them
+    pub(crate) extra: Option<Box<CounterExtra>>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct CounterExtra {
+    /// The `(min, max)` bounds of a bounded counter
+    bounds: Option<(i64, i64)>,
+    /// The number of times `current` has wrapped, negative if it wrapped past `i64::MIN`
+    wraps: i32,
 }
 
 impl Counter {
     pub(crate) fn increment(&mut self, inc: i64) {
-        self.current += inc;
+        let (current, wrapped) = self.current.overflowing_add(inc);
+        self.current = current;
+        if wrapped {
+            self.set_wraps(self.wraps() + if inc > 0 { 1 } else { -1 });
+        }
+    }
+
+    fn wraps(&self) -> i32 {
+        self.extra.as_ref().map(|e| e.wraps).unwrap_or(0)
+    }
+
+    fn set_wraps(&mut self, wraps: i32) {
+        let bounds = self.extra.as_ref().and_then(|e| e.bounds);
+        self.extra =
+            (bounds.is_some() || wraps != 0).then(|| Box::new(CounterExtra { bounds, wraps }));
+    }
+
+    /// The sum of the initial value and every increment
+    pub(crate) fn total(&self) -> i128 {
+        self.current as i128 + ((self.wraps() as i128) << 64)
+    }
+
+    /// Whether the total of this counter is outside the range of an `i64`
+    pub(crate) fn has_wrapped(&self) -> bool {
+        self.wraps() != 0
+    }
+
+    /// This counter with its total clamped to the range of an `i64` rather than wrapped, which
+    /// is how counters whose [`crate::CounterOverflow`] setting is not `Wrap` are read
+    pub(crate) fn saturated(&self) -> Counter {
+        let mut saturated = Counter {
+            current: self.total().clamp(i64::MIN as i128, i64::MAX as i128) as i64,
+            ..self.clone()
+        };
+        saturated.set_wraps(0);
+        saturated
+    }
+
+    /// The change in the value of this counter when `inc` is added to it, where `saturate` is
+    /// whether the counter is read as [`Self::saturated()`]
+    pub(crate) fn value_change(&self, inc: i64, saturate: bool) -> i64 {
+        let mut after = self.clone();
+        after.increment(inc);
+        if saturate {
+            after
+                .saturated()
+                .value()
+                .wrapping_sub(self.saturated().value())
+        } else {
+            after.value().wrapping_sub(self.value())
+        }
+    }
+
+    /// A counter with the same bounds as this one and the value `start + inc`
+    pub(crate) fn with_increment(&self, inc: i128) -> Counter {
+        let total = self.start as i128 + inc;
+        let mut counter = Counter {
+            current: total as i64,
+            ..self.clone()
+        };
+        counter.set_wraps(((total - (total as i64) as i128) >> 64) as i32);
+        counter
+    }
+
+    /// The current value of the counter
+    pub fn value(&self) -> i64 {
+        if self.is_bounded() {
+            self.total().clamp(self.min() as i128, self.max() as i128) as i64
+        } else {
+            self.current
+        }
+    }
+
+    /// The lower bound of the counter, `i64::MIN` for an unbounded counter
+    pub fn min(&self) -> i64 {
+        self.extra
+            .as_ref()
+            .and_then(|e| e.bounds)
+            .map(|b| b.0)
+            .unwrap_or(i64::MIN)
+    }
+
+    /// The upper bound of the counter, `i64::MAX` for an unbounded counter
+    pub fn max(&self) -> i64 {
+        self.extra
+            .as_ref()
+            .and_then(|e| e.bounds)
+            .map(|b| b.1)
+            .unwrap_or(i64::MAX)
+    }
+
+    /// Whether this counter was created with bounds
+    pub fn is_bounded(&self) -> bool {
+        self.min() != i64::MIN || self.max() != i64::MAX
     }
 }
 
@@ -382,13 +499,13 @@ impl Serialize for Counter {
     where
         S: Serializer,
     {
-        serializer.serialize_i64(self.current)
+        serializer.serialize_i64(self.value())
     }
 }
 
 impl fmt::Display for Counter {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.current)
+        write!(f, "{}", self.value())
     }
 }
 
@@ -397,40 +514,38 @@ impl From<i64> for Counter {
         Counter {
             start: n,
             current: n,
+            extra: None,
         }
     }
 }
 
 impl From<&i64> for Counter {
     fn from(n: &i64) -> Self {
-        Counter {
-            start: *n,
-            current: *n,
-        }
+        Counter::from(*n)
     }
 }
 
 impl From<&Counter> for i64 {
     fn from(val: &Counter) -> Self {
-        val.current
+        val.value()
     }
 }
 
 impl From<Counter> for i64 {
     fn from(val: Counter) -> Self {
-        val.current
+        val.value()
     }
 }
 
 impl From<&Counter> for u64 {
     fn from(val: &Counter) -> Self {
-        val.current as u64
+        val.value() as u64
     }
 }
 
 impl From<&Counter> for f64 {
     fn from(val: &Counter) -> Self {
-        val.current as f64
+        val.value() as f64
     }
 }
 
@@ -456,7 +571,7 @@ pub enum ScalarValue {
 
 impl PartialEq for Counter {
     fn eq(&self, other: &Self) -> bool {
-        self.current == other.current
+        self.value() == other.value() && self.min() == other.min() && self.max() == other.max()
     }
 }
 
@@ -656,6 +771,29 @@ impl ScalarValue {
     pub fn counter(n: i64) -> ScalarValue {
         ScalarValue::Counter(n.into())
     }
+
+    /// A counter whose value saturates at `min` and `max`, see [`Counter`]
+    ///
+    /// Bounded counters are stored as counters with their bounds after the starting value.
+    /// Versions of automerge which predate them fail to load changes and documents containing
+    /// them, so all peers must support them before they are used. Counters without bounds are
+    /// stored as before.
+    ///
+    /// # Panics
+    ///
+    /// If `min > max`
+    pub fn bounded_counter(n: i64, min: i64, max: i64) -> ScalarValue {
+        assert!(min <= max, "counter bounds must satisfy min <= max");
+        ScalarValue::Counter(Counter {
+            extra: (min != i64::MIN || max != i64::MAX).then(|| {
+                Box::new(CounterExtra {
+                    bounds: Some((min, max)),
+                    wraps: 0,
+                })
+            }),
+            ..Counter::from(n)
+        })
+    }
 }
 
 impl From<&str> for ScalarValue {
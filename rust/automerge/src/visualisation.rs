@@ -245,6 +245,7 @@ impl OpTableRow {
             crate::OpType::Increment(v) => format!("inc {}", v),
             crate::OpType::MarkBegin(_, m) => format!("markBegin {}", m),
             crate::OpType::MarkEnd(m) => format!("markEnd {}", m),
+            crate::OpType::Seal(_) => "seal".to_string(),
         };
         let prop = match op.key() {
             crate::types::Key::Map(k) => osd.props[*k].clone(),
@@ -60,7 +60,9 @@ fn update_blocks_change_block_properties() {
         .spans(&text)
         .unwrap()
         .map(|s| match s {
-            automerge::iter::Span::Block(b) => BlockOrText::Block(b),
+            automerge::iter::Span::Block(b) | automerge::iter::Span::Embed(b) => {
+                BlockOrText::Block(b)
+            }
             automerge::iter::Span::Text(t, _) => BlockOrText::Text(std::borrow::Cow::Owned(t)),
         })
         .collect::<Vec<_>>();
@@ -118,7 +120,9 @@ fn update_blocks_updates_text() {
         .spans(&text)
         .unwrap()
         .map(|s| match s {
-            automerge::iter::Span::Block(b) => BlockOrText::Block(b),
+            automerge::iter::Span::Block(b) | automerge::iter::Span::Embed(b) => {
+                BlockOrText::Block(b)
+            }
             automerge::iter::Span::Text(t, _) => BlockOrText::Text(std::borrow::Cow::Owned(t)),
         })
         .collect::<Vec<_>>();
@@ -289,3 +293,46 @@ fn update_spans_delete_attribute() {
         })]
     );
 }
+
+#[test]
+fn embeds_are_distinguished_from_blocks_and_text() {
+    let mut doc = automerge::Automerge::new();
+    let mut tx = doc.transaction();
+    let text = tx.put_object(ROOT, "text", ObjType::Text).unwrap();
+    tx.splice_text(&text, 0, 0, "see  here").unwrap();
+    tx.commit();
+    let cursor = doc.get_cursor(&text, 5, None).unwrap();
+
+    let mut tx = doc.transaction();
+    let image = tx.insert_embed(&text, 4).unwrap();
+    tx.put(&image, "src", "cat.png").unwrap();
+    tx.commit();
+
+    assert_eq!(doc.text(&text).unwrap(), "see \u{fffc} here");
+    assert_eq!(doc.length(&text), 10);
+    assert_eq!(doc.get_cursor_position(&text, &cursor, None).unwrap(), 6);
+    assert_eq!(
+        doc.spans(&text).unwrap().collect::<Vec<_>>(),
+        vec![
+            automerge::iter::Span::Text("see ".to_string(), None),
+            automerge::iter::Span::Embed(hydrate_map! {
+                automerge::iter::EMBED_KEY => true,
+                "src" => "cat.png",
+            }),
+            automerge::iter::Span::Text(" here".to_string(), None),
+        ]
+    );
+
+    let mut tx = doc.transaction();
+    assert!(tx.insert_embed(ROOT, 0).is_err());
+
+    // blocks with properties of their own which happen to look like markers stay blocks
+    let block = tx.split_block(&text, 0).unwrap();
+    tx.put(&block, "_embed", true).unwrap();
+    tx.put(&block, automerge::iter::EMBED_KEY, "no").unwrap();
+    tx.commit();
+    assert!(matches!(
+        doc.spans(&text).unwrap().next(),
+        Some(automerge::iter::Span::Block(_))
+    ));
+}
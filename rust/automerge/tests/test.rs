@@ -3,10 +3,12 @@ use automerge::op_tree::B;
 use automerge::patches::TextRepresentation;
 use automerge::transaction::{CommitOptions, Transactable};
 use automerge::{
-    sync::SyncDoc, ActorId, AutoCommit, Automerge, AutomergeError, Change, ExpandedChange, ObjId,
-    ObjType, Patch, PatchAction, PatchLog, Prop, ReadDoc, ScalarValue, SequenceTree, Value, ROOT,
+    sync::SyncDoc, ActorId, AutoCommit, Automerge, AutomergeError, Change, ChangeHash,
+    ExpandedChange, ObjId, ObjType, Patch, PatchAction, PatchLog, Prop, ReadDoc, ScalarValue,
+    SequenceTree, Value, ROOT,
 };
 use std::fs;
+use std::ops::Bound;
 
 // set up logging for all the tests
 use test_log::test;
@@ -2276,3 +2278,1495 @@ fn stats_smoke_test() {
     assert_eq!(stats.num_changes, 2);
     assert_eq!(stats.num_ops, 2);
 }
+
+#[cfg(feature = "json")]
+#[test]
+fn put_json_creates_nested_objects() {
+    let mut doc = AutoCommit::new();
+    let created = doc
+        .put_json(
+            &ROOT,
+            "todos",
+            &serde_json::json!([
+                {"title": "write code", "done": false, "tags": ["work", 1, 2.5]},
+                null,
+            ]),
+        )
+        .unwrap();
+    assert_eq!(created.len(), 3);
+    assert_eq!(doc.object_type(&created[0]).unwrap(), ObjType::List);
+    assert_eq!(doc.object_type(&created[1]).unwrap(), ObjType::Map);
+    assert_eq!(doc.object_type(&created[2]).unwrap(), ObjType::List);
+    assert_doc!(
+        &doc,
+        map! {
+            "todos" => {
+                list![
+                    {
+                        map! {
+                            "title" => { "write code" },
+                            "done" => { false },
+                            "tags" => { list![{ "work" }, { 1 }, { 2.5 }] },
+                        }
+                    },
+                    { ScalarValue::Null },
+                ]
+            }
+        }
+    );
+
+    let tags = created[2].clone();
+    let created = doc.put_json(&tags, 0, &serde_json::json!("home")).unwrap();
+    assert!(created.is_empty());
+    assert_eq!(
+        doc.get(&tags, 0).unwrap().unwrap().0,
+        Value::Scalar(std::borrow::Cow::Owned("home".into()))
+    );
+}
+
+#[test]
+fn get_put_and_delete_at_path() {
+    let mut doc = AutoCommit::new();
+    let todos = doc.put_object(&ROOT, "todos", ObjType::List).unwrap();
+    for (index, title) in ["one", "two"].into_iter().enumerate() {
+        let todo = doc.insert_object(&todos, index, ObjType::Map).unwrap();
+        doc.put(&todo, "title", title).unwrap();
+    }
+    let counts = doc.put_object(&ROOT, "counts", ObjType::Map).unwrap();
+    doc.put(&counts, "3", 3).unwrap();
+    doc.put(&counts, "03", "zero three").unwrap();
+
+    let path: automerge::Path = "/todos/1/title".parse().unwrap();
+    let (value, _) = doc.get_at_path(&path).unwrap().unwrap();
+    assert_eq!(value.into_string().unwrap(), "two");
+    let (value, _) = doc
+        .get_at_path(&"/counts/3".parse().unwrap())
+        .unwrap()
+        .unwrap();
+    assert_eq!(value, Value::int(3));
+    let (value, _) = doc
+        .get_at_path(&"/counts/03".parse().unwrap())
+        .unwrap()
+        .unwrap();
+    assert_eq!(value.into_string().unwrap(), "zero three");
+
+    doc.put_at_path(&path, "three").unwrap();
+    let (value, _) = doc.get_at_path(&path).unwrap().unwrap();
+    assert_eq!(value.into_string().unwrap(), "three");
+
+    doc.delete_at_path(&path).unwrap();
+    assert_eq!(doc.get_at_path(&path).unwrap(), None);
+
+    assert!(matches!(
+        doc.get_at_path(&"/todos/5/title".parse().unwrap()),
+        Err(AutomergeError::InvalidPath(_))
+    ));
+    assert!(matches!(
+        doc.get_at_path(&"/todos/first".parse().unwrap()),
+        Err(AutomergeError::InvalidPath(_))
+    ));
+}
+
+#[test]
+fn get_conflicts_includes_provenance() {
+    let actor1 = ActorId::from(b"aaaa".to_vec());
+    let actor2 = ActorId::from(b"bbbb".to_vec());
+    let mut doc1 = Automerge::new().with_actor(actor1.clone());
+    let mut doc2 = Automerge::new().with_actor(actor2.clone());
+    doc1.transact_with::<_, _, AutomergeError, _>(
+        |_| {
+            CommitOptions::default()
+                .with_message("from 1")
+                .with_time(100)
+        },
+        |tx| tx.put(ROOT, "field", "one"),
+    )
+    .unwrap();
+    doc2.transact_with::<_, _, AutomergeError, _>(
+        |_| CommitOptions::default().with_time(200),
+        |tx| tx.put(ROOT, "field", "two"),
+    )
+    .unwrap();
+    doc1.merge(&mut doc2).unwrap();
+
+    let conflicts = doc1.get_conflicts(ROOT, "field").unwrap();
+    assert!(conflicts.is_conflicted());
+    let values = conflicts.iter().collect::<Vec<_>>();
+    assert_eq!(values[0].actor, actor1);
+    assert_eq!(values[0].timestamp, Some(100));
+    assert_eq!(values[0].message.as_deref(), Some("from 1"));
+    assert_eq!(values[1].actor, actor2);
+    assert_eq!(values[1].timestamp, Some(200));
+    assert_eq!(values[1].message, None);
+    assert_eq!(
+        values[1].hash,
+        doc2.get_last_local_change().map(|c| c.hash())
+    );
+    assert_eq!(
+        conflicts.winner().map(|c| &c.value),
+        doc1.get(ROOT, "field").unwrap().as_ref().map(|(v, _)| v)
+    );
+
+    let mut doc3 = AutoCommit::new();
+    doc3.put(ROOT, "field", 1).unwrap();
+    let pending = doc3.get_conflicts(ROOT, "field").unwrap();
+    assert_eq!(pending.winner().unwrap().hash, None);
+}
+
+#[test]
+fn rebase_from_replays_fork_changes_as_new_changes() {
+    let actor = ActorId::from(b"aaaa".to_vec());
+    let mut doc = AutoCommit::new().with_actor(actor.clone());
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "hello world").unwrap();
+    doc.put(ROOT, "count", ScalarValue::counter(1)).unwrap();
+    doc.commit();
+
+    let mut fork = doc.fork();
+    let fork_actor = fork.get_actor().clone();
+    // insert after "hello" and create a nested object
+    fork.splice_text(&text, 5, 0, " there").unwrap();
+    let list = fork.put_object(ROOT, "list", ObjType::List).unwrap();
+    fork.insert(&list, 0, "a").unwrap();
+    fork.commit();
+    fork.insert(&list, 1, "b").unwrap();
+    fork.increment(ROOT, "count", 2).unwrap();
+    fork.commit();
+
+    // concurrently insert text at the start, shifting the indices the fork edited at
+    doc.splice_text(&text, 0, 0, "oh, ").unwrap();
+    doc.commit();
+    let heads_before = doc.get_heads();
+
+    let hashes = doc.rebase_from(fork).unwrap();
+    assert_eq!(hashes.len(), 2);
+    assert_eq!(doc.text(&text).unwrap(), "oh, hello there world");
+    assert_eq!(
+        doc.get(ROOT, "count").unwrap().unwrap().0,
+        Value::counter(3)
+    );
+    let (_, list) = doc.get(ROOT, "list").unwrap().unwrap();
+    assert_eq!(doc.length(&list), 2);
+    assert_eq!(doc.get(&list, 1).unwrap().unwrap().0, Value::str("b"));
+
+    let first = doc.get_change_by_hash(&hashes[0]).unwrap().clone();
+    assert_eq!(first.actor_id(), &actor);
+    assert_eq!(first.deps(), heads_before.as_slice());
+    let second = doc.get_change_by_hash(&hashes[1]).unwrap();
+    assert_eq!(second.deps(), &[hashes[0]]);
+    assert!(doc
+        .get_changes(&[])
+        .iter()
+        .all(|c| c.actor_id() != &fork_actor));
+}
+
+#[test]
+fn last_modified_returns_provenance_of_visible_value() {
+    let actor1 = ActorId::from(b"aaaa".to_vec());
+    let actor2 = ActorId::from(b"bbbb".to_vec());
+    let mut doc = AutoCommit::new().with_actor(actor1.clone());
+    doc.put(ROOT, "field", "one").unwrap();
+    assert_eq!(
+        doc.last_modified(ROOT, "field").unwrap().unwrap().hash,
+        None
+    );
+    doc.commit_with(CommitOptions::default().with_time(100));
+    let heads = doc.get_heads();
+
+    let mut fork = doc.fork().with_actor(actor2.clone());
+    fork.put(ROOT, "field", "two").unwrap();
+    fork.commit_with(CommitOptions::default().with_time(200));
+    doc.merge(&mut fork).unwrap();
+
+    let modified = doc.last_modified(ROOT, "field").unwrap().unwrap();
+    assert_eq!(modified.actor, actor2);
+    assert_eq!(modified.timestamp, Some(200));
+    assert_eq!(modified.hash, fork.get_heads().first().copied());
+    assert_eq!(modified.id, doc.get(ROOT, "field").unwrap().unwrap().1);
+
+    let before = doc
+        .last_modified_at(ROOT, "field", &heads)
+        .unwrap()
+        .unwrap();
+    assert_eq!(before.actor, actor1);
+    assert_eq!(before.timestamp, Some(100));
+    assert_eq!(before.hash, heads.first().copied());
+
+    assert_eq!(doc.last_modified(ROOT, "missing").unwrap(), None);
+}
+
+#[test]
+fn map_page_with_prefix_and_continuation() {
+    let mut doc = AutoCommit::new();
+    for i in 0..10 {
+        doc.put(ROOT, format!("user:{:02}", i), i).unwrap();
+        doc.put(ROOT, format!("team:{:02}", i), i).unwrap();
+    }
+    doc.put(ROOT, "user", "not a user").unwrap();
+
+    let page = doc.map_page(ROOT, "user:", 4, None);
+    let keys = page.items.iter().map(|i| i.key).collect::<Vec<_>>();
+    assert_eq!(keys, vec!["user:00", "user:01", "user:02", "user:03"]);
+    let token = page.next.unwrap();
+    assert_eq!(token.key(), "user:03");
+
+    // mutations between pages don't invalidate the token
+    doc.delete(ROOT, "user:04").unwrap();
+    doc.put(ROOT, "user:01a", "before the token").unwrap();
+    doc.put(ROOT, "user:05a", "after the token").unwrap();
+
+    let page = doc.map_page(ROOT, "user:", 4, Some(&token));
+    let keys = page.items.iter().map(|i| i.key).collect::<Vec<_>>();
+    assert_eq!(keys, vec!["user:05", "user:05a", "user:06", "user:07"]);
+    let page = doc.map_page(ROOT, "user:", 4, page.next.as_ref());
+    let keys = page.items.iter().map(|i| i.key).collect::<Vec<_>>();
+    assert_eq!(keys, vec!["user:08", "user:09"]);
+    assert_eq!(page.next, None);
+
+    let all = doc.map_page(ROOT, "", 100, None);
+    assert_eq!(all.items.len(), doc.length(ROOT));
+    assert_eq!(all.next, None);
+
+    // a limit of zero is no limit rather than an empty page which never finishes
+    let rest = doc.map_page(ROOT, "user:", 0, Some(&token));
+    assert_eq!(rest.items.len(), 6);
+    assert_eq!(rest.next, None);
+}
+
+#[test]
+fn map_range_starting_between_keys() {
+    let mut doc = AutoCommit::new();
+    for key in ["a", "c", "e", "g"] {
+        doc.put(ROOT, key, key).unwrap();
+    }
+    let keys = doc
+        .map_range(ROOT, "b".to_string().."f".to_string())
+        .map(|i| i.key)
+        .collect::<Vec<_>>();
+    assert_eq!(keys, vec!["c", "e"]);
+    let keys = doc
+        .map_range(ROOT, (Bound::Excluded("c".to_string()), Bound::Unbounded))
+        .map(|i| i.key)
+        .collect::<Vec<_>>();
+    assert_eq!(keys, vec!["e", "g"]);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn splice_full_inserts_objects_and_json() {
+    use automerge::transaction::SpliceItem;
+    let mut doc = AutoCommit::new();
+    let list = doc.put_object(ROOT, "rows", ObjType::List).unwrap();
+    doc.splice(&list, 0, 0, ["first".into(), "last".into()])
+        .unwrap();
+
+    let created = doc
+        .splice_full(
+            &list,
+            1,
+            0,
+            [
+                SpliceItem::from(ObjType::Map),
+                ScalarValue::Int(1).into(),
+                serde_json::json!({"name": "row", "tags": ["a"]}).into(),
+                serde_json::json!(2).into(),
+            ],
+        )
+        .unwrap();
+    assert_eq!(created.len(), 3);
+    assert_eq!(doc.length(&list), 6);
+    assert_eq!(
+        doc.get(&list, 1).unwrap().unwrap(),
+        (Value::Object(ObjType::Map), created[0].clone())
+    );
+    assert_eq!(doc.get(&list, 2).unwrap().unwrap().0, Value::int(1));
+    assert_eq!(doc.get(&list, 3).unwrap().unwrap().1, created[1]);
+    assert_eq!(doc.get(&created[1], "tags").unwrap().unwrap().1, created[2]);
+    assert_eq!(doc.get(&list, 4).unwrap().unwrap().0, Value::int(2));
+    assert_eq!(doc.get(&list, 5).unwrap().unwrap().0, Value::str("last"));
+
+    // negative deletes remove the items before `pos`
+    let created = doc
+        .splice_full(&list, 3, -2, [SpliceItem::from(ObjType::List)])
+        .unwrap();
+    assert_eq!(doc.length(&list), 5);
+    assert_eq!(doc.get(&list, 1).unwrap().unwrap().1, created[0]);
+}
+
+#[test]
+fn splice_full_indexes_text_like_splice_text() {
+    use automerge::transaction::SpliceItem;
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "🐻").unwrap();
+    // the width of the bear in the encoding text is indexed with
+    let bear = doc.length(&text);
+    doc.splice_text(&text, bear, 0, "ab").unwrap();
+    let mut other = doc.fork();
+
+    let created = doc
+        .splice_full(
+            &text,
+            bear,
+            1,
+            [
+                ScalarValue::from("x").into(),
+                SpliceItem::from(ObjType::Map),
+                ScalarValue::from("y").into(),
+            ],
+        )
+        .unwrap();
+    assert_eq!(created.len(), 1);
+    assert_eq!(doc.text(&text).unwrap(), "🐻x\u{fffc}yb");
+
+    // negative deletes count back from `pos` in the same units
+    other.splice_text(&text, bear, 1, "xy").unwrap();
+    for doc in [&mut doc, &mut other] {
+        let before_b = doc.length(&text) - 1;
+        doc.splice_full(&text, before_b, -((before_b - bear) as isize), [])
+            .unwrap();
+        assert_eq!(doc.text(&text).unwrap(), "🐻b");
+    }
+}
+
+#[test]
+fn diff_records_reports_row_level_changes() {
+    use automerge::{FieldDiff, RecordDiff};
+    let mut doc = AutoCommit::new();
+    let rows = doc.put_object(ROOT, "rows", ObjType::List).unwrap();
+    let mut ids = Vec::new();
+    for (i, name) in ["alice", "bob", "carol"].into_iter().enumerate() {
+        let row = doc.insert_object(&rows, i, ObjType::Map).unwrap();
+        doc.put(&row, "name", name).unwrap();
+        doc.put(&row, "score", ScalarValue::counter(0)).unwrap();
+        ids.push(row);
+    }
+    let before = doc.get_heads();
+
+    doc.delete(&rows, 0).unwrap();
+    doc.increment(&ids[2], "score", 5).unwrap();
+    doc.put(&ids[2], "team", "red").unwrap();
+    let dave = doc.insert_object(&rows, 0, ObjType::Map).unwrap();
+    doc.put(&dave, "name", "dave").unwrap();
+    let after = doc.get_heads();
+
+    // bob is unchanged so isn't reported even though the rows around him changed
+    let diffs = doc.diff_records(&rows, &before, &after).unwrap();
+    assert_eq!(diffs.len(), 3);
+    assert!(matches!(&diffs[0], RecordDiff::Removed { id, index: 0, .. } if *id == ids[0]));
+    assert!(matches!(&diffs[1], RecordDiff::Added { id, index: 0, .. } if *id == dave));
+    let RecordDiff::Modified {
+        id, index, fields, ..
+    } = &diffs[2]
+    else {
+        panic!("expected a modified row, got {:?}", diffs[2]);
+    };
+    assert_eq!((id, *index), (&ids[2], 2));
+    assert_eq!(
+        fields.iter().map(|f| f.key.as_str()).collect::<Vec<_>>(),
+        vec!["score", "team"]
+    );
+    assert_eq!(
+        fields[1],
+        FieldDiff {
+            key: "team".into(),
+            before: None,
+            after: Some(ScalarValue::from("red").into()),
+        }
+    );
+
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    assert!(doc.diff_records(&text, &before, &after).is_err());
+}
+
+#[test]
+fn compare_clocks_of_heads() {
+    let mut doc1 = AutoCommit::new();
+    doc1.put(ROOT, "a", 1).unwrap();
+    let base = doc1.get_heads();
+    let mut doc2 = doc1.fork();
+    doc1.put(ROOT, "b", 1).unwrap();
+    doc2.put(ROOT, "c", 1).unwrap();
+    let heads1 = doc1.get_heads();
+    let heads2 = doc2.get_heads();
+    doc1.merge(&mut doc2).unwrap();
+    let merged = doc1.get_heads();
+
+    let base = doc1.clock_at(&base);
+    let clock1 = doc1.clock_at(&heads1);
+    let clock2 = doc1.clock_at(&heads2);
+    let merged = doc1.clock_at(&merged);
+    assert!(base.is_ancestor_of(&clock1));
+    assert!(clock1.is_ancestor_of(&merged));
+    assert!(clock2.is_ancestor_of(&merged));
+    assert!(!merged.is_ancestor_of(&clock1));
+    assert!(clock1.concurrent_with(&clock2));
+    assert!(!clock1.concurrent_with(&merged));
+}
+
+#[test]
+fn op_meta_for_conflicting_values() {
+    let actor1 = ActorId::from(b"aaaa".to_vec());
+    let actor2 = ActorId::from(b"bbbb".to_vec());
+    let mut doc1 = AutoCommit::new().with_actor(actor1.clone());
+    let mut doc2 = AutoCommit::new().with_actor(actor2.clone());
+    doc1.put(ROOT, "field", "one").unwrap();
+    doc1.commit_with(CommitOptions::default().with_time(100));
+    doc2.put(ROOT, "field", "two").unwrap();
+    doc2.commit_with(CommitOptions::default().with_time(200));
+    doc1.merge(&mut doc2).unwrap();
+
+    let metas = doc1
+        .get_all(ROOT, "field")
+        .unwrap()
+        .into_iter()
+        .map(|(_, id)| doc1.op_meta(&id).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        metas
+            .iter()
+            .map(|m| (&m.actor, m.counter, m.timestamp))
+            .collect::<Vec<_>>(),
+        vec![(&actor1, 1, Some(100)), (&actor2, 1, Some(200))]
+    );
+    let (_, id) = doc1.values(ROOT).next().unwrap();
+    assert_eq!(doc1.op_meta(&id), metas.last().cloned());
+    assert_eq!(doc1.op_meta(&ROOT), None);
+
+    doc1.put(ROOT, "field", "three").unwrap();
+    let (_, id) = doc1.get(ROOT, "field").unwrap().unwrap();
+    let pending = doc1.op_meta(&id).unwrap();
+    assert_eq!((pending.hash, pending.timestamp), (None, None));
+}
+
+#[test]
+fn fork_at_with_actor() {
+    let mut doc = AutoCommit::new();
+    doc.put(ROOT, "a", 1).unwrap();
+    let heads = doc.get_heads();
+    let mut other = doc.fork();
+    other.put(ROOT, "b", 2).unwrap();
+    doc.put(ROOT, "c", 3).unwrap();
+    doc.merge(&mut other).unwrap();
+
+    let actor = ActorId::from(b"fork".to_vec());
+    let mut old = doc.fork_at_with_actor(&heads, actor.clone()).unwrap();
+    assert_eq!(old.get_actor(), &actor);
+    assert_eq!(old.get_heads(), heads);
+    assert_eq!(old.keys(ROOT).collect::<Vec<_>>(), vec!["a"]);
+
+    let current = doc.get_heads();
+    let mut new = doc.fork_at_with_actor(&current, actor.clone()).unwrap();
+    assert_eq!(new.get_actor(), &actor);
+    assert_eq!(new.get_heads(), current);
+    assert_eq!(new.keys(ROOT).collect::<Vec<_>>(), vec!["a", "b", "c"]);
+
+    assert!(matches!(
+        doc.fork_at_with_actor(&[ChangeHash([0; 32])], actor),
+        Err(AutomergeError::InvalidHash(_))
+    ));
+}
+
+#[test]
+fn bounded_counters_saturate() {
+    let mut doc1 = AutoCommit::new();
+    doc1.put(ROOT, "stock", ScalarValue::bounded_counter(5, 0, 10))
+        .unwrap();
+    doc1.commit();
+    let mut doc2 = doc1.fork();
+    let mut doc3 = doc1.fork();
+    doc2.increment(ROOT, "stock", -4).unwrap();
+    doc3.increment(ROOT, "stock", -4).unwrap();
+    doc1.merge(&mut doc2).unwrap();
+    doc1.merge(&mut doc3).unwrap();
+    assert_eq!(
+        doc1.get(ROOT, "stock").unwrap().unwrap().0,
+        Value::from(ScalarValue::bounded_counter(0, 0, 10))
+    );
+
+    // the bounds survive a save and load
+    let mut loaded = AutoCommit::load(&doc1.save()).unwrap();
+    loaded.increment(ROOT, "stock", 20).unwrap();
+    let (value, _) = loaded.get(ROOT, "stock").unwrap().unwrap();
+    assert_eq!(value.to_i64(), Some(10));
+
+    // patches report the change in the clamped value
+    loaded.update_diff_cursor();
+    loaded.increment(ROOT, "stock", -1).unwrap();
+    let patches = loaded.diff_incremental();
+    assert_eq!(patches.len(), 1);
+    assert!(matches!(
+        patches[0].action,
+        PatchAction::Increment { value: 0, .. }
+    ));
+}
+
+#[test]
+fn apply_changes_to_multiple_patch_logs() {
+    let mut doc = Automerge::new();
+    let mut tx = doc.transaction();
+    let text = tx.put_object(ROOT, "text", ObjType::Text).unwrap();
+    tx.splice_text(&text, 0, 0, "hello").unwrap();
+    tx.commit();
+    let mut other = doc.fork();
+    let mut tx = other.transaction();
+    tx.splice_text(&text, 5, 0, " world").unwrap();
+    tx.put(ROOT, "count", 1).unwrap();
+    tx.commit();
+
+    let mut expected = Vec::new();
+    for text_rep in [TextRepresentation::String, TextRepresentation::Array] {
+        let mut single = doc.clone();
+        let mut log = PatchLog::active(text_rep);
+        single.merge_and_log_patches(&mut other, &mut log).unwrap();
+        expected.push(single.make_patches(&mut log));
+    }
+
+    let mut string_log = PatchLog::active(TextRepresentation::String);
+    let mut array_log = PatchLog::active(TextRepresentation::Array);
+    let mut inactive_log = PatchLog::inactive(TextRepresentation::String);
+    doc.merge_and_log_patches_multi(
+        &mut other,
+        &mut [&mut string_log, &mut inactive_log, &mut array_log],
+    )
+    .unwrap();
+    assert_eq!(doc.make_patches(&mut string_log), expected[0]);
+    assert_eq!(doc.make_patches(&mut array_log), expected[1]);
+    assert!(doc.make_patches(&mut inactive_log).is_empty());
+}
+
+#[test]
+fn keys_range_at_only_visits_keys_in_range() {
+    let mut doc = AutoCommit::new();
+    for key in ["a", "b", "c", "d"] {
+        doc.put(&ROOT, key, 1).unwrap();
+    }
+    let heads = doc.get_heads();
+    doc.put(&ROOT, "bb", 2).unwrap();
+    doc.delete(&ROOT, "c").unwrap();
+
+    let range = |start: &str, end: &str| start.to_string()..end.to_string();
+    assert_eq!(
+        doc.keys_range(&ROOT, range("b", "d")).collect::<Vec<_>>(),
+        vec!["b", "bb"]
+    );
+    assert_eq!(
+        doc.keys_range_at(&ROOT, range("b", "d"), &heads)
+            .collect::<Vec<_>>(),
+        vec!["b", "c"]
+    );
+    assert_eq!(
+        doc.keys_range_at(&ROOT, "c".to_string().., &heads)
+            .collect::<Vec<_>>(),
+        vec!["c", "d"]
+    );
+    assert_eq!(
+        doc.map_range_at(&ROOT, ..="b".to_string(), &heads)
+            .map(|item| item.key)
+            .collect::<Vec<_>>(),
+        vec!["a", "b"]
+    );
+}
+
+#[test]
+fn counter_overflow_policies() {
+    use automerge::CounterOverflow;
+
+    let mut doc = AutoCommit::new();
+    doc.put(&ROOT, "views", ScalarValue::counter(i64::MAX - 1))
+        .unwrap();
+    doc.put(&ROOT, "billing_total", ScalarValue::counter(i64::MAX - 1))
+        .unwrap();
+    doc.commit();
+    let mut other = doc.fork();
+
+    // the default wraps, as before
+    doc.increment(&ROOT, "views", 2).unwrap();
+    assert_eq!(
+        doc.get(&ROOT, "views").unwrap().unwrap().0,
+        Value::counter(i64::MIN)
+    );
+
+    // the setting decides how the total is read, so it applies to earlier increments too
+    doc.set_counter_overflow(CounterOverflow::Saturate).unwrap();
+    doc.set_counter_overflow_for_keys("billing_*", CounterOverflow::Error)
+        .unwrap();
+    assert_eq!(
+        doc.get(&ROOT, "views").unwrap().unwrap().0,
+        Value::counter(i64::MAX)
+    );
+    doc.increment(&ROOT, "views", -3).unwrap();
+    assert_eq!(
+        doc.get(&ROOT, "views").unwrap().unwrap().0,
+        Value::counter(i64::MAX - 2)
+    );
+    assert_eq!(
+        doc.increment(&ROOT, "billing_total", 2),
+        Err(AutomergeError::CounterOverflow {
+            value: i64::MAX - 1,
+            increment: 2
+        })
+    );
+    doc.increment(&ROOT, "billing_total", 1).unwrap();
+    doc.commit();
+
+    // a concurrent increment which takes the total past the limit is applied and the value stops
+    // at the limit on every peer
+    other.increment(&ROOT, "billing_total", 1).unwrap();
+    doc.merge(&mut other).unwrap();
+    other.merge(&mut doc).unwrap();
+    assert_eq!(doc.get_heads(), other.get_heads());
+    let mut loaded = AutoCommit::load(&doc.save()).unwrap();
+    for doc in [&doc, &other, &loaded] {
+        assert_eq!(
+            doc.get(&ROOT, "billing_total").unwrap().unwrap().0,
+            Value::counter(i64::MAX)
+        );
+    }
+    assert_eq!(
+        loaded.increment(&ROOT, "billing_total", 1),
+        Err(AutomergeError::CounterOverflow {
+            value: i64::MAX,
+            increment: 1
+        })
+    );
+
+    doc.set_counter_overflow_for_keys("billing_*", CounterOverflow::Wrap)
+        .unwrap();
+    assert_eq!(
+        doc.get(&ROOT, "billing_total").unwrap().unwrap().0,
+        Value::counter(i64::MIN)
+    );
+}
+
+#[test]
+fn concurrent_overflowing_increments_sync() {
+    use automerge::CounterOverflow;
+
+    let mut a = AutoCommit::new();
+    a.set_counter_overflow(CounterOverflow::Error).unwrap();
+    a.put(&ROOT, "total", ScalarValue::counter(i64::MAX - 10))
+        .unwrap();
+    a.commit();
+    let mut b = a.fork();
+    a.increment(&ROOT, "total", 6).unwrap();
+    b.increment(&ROOT, "total", 6).unwrap();
+    a.commit();
+    b.commit();
+
+    let sync = |a: &mut AutoCommit, b: &mut AutoCommit| {
+        let mut a_state = automerge::sync::State::new();
+        let mut b_state = automerge::sync::State::new();
+        for _ in 0..10 {
+            let a_msg = a.sync().generate_sync_message(&mut a_state);
+            if let Some(msg) = &a_msg {
+                b.sync()
+                    .receive_sync_message(&mut b_state, msg.clone())
+                    .unwrap();
+            }
+            let b_msg = b.sync().generate_sync_message(&mut b_state);
+            if let Some(msg) = &b_msg {
+                a.sync()
+                    .receive_sync_message(&mut a_state, msg.clone())
+                    .unwrap();
+            }
+            if a_msg.is_none() && b_msg.is_none() {
+                return;
+            }
+        }
+        panic!("the peers did not finish syncing");
+    };
+    sync(&mut a, &mut b);
+    assert_eq!(a.get_heads(), b.get_heads());
+    for doc in [&a, &b] {
+        assert_eq!(
+            doc.get(&ROOT, "total").unwrap().unwrap().0,
+            Value::counter(i64::MAX)
+        );
+    }
+
+    // later changes are not held up
+    a.put(&ROOT, "note", "done").unwrap();
+    sync(&mut a, &mut b);
+    assert!(b.get(&ROOT, "note").unwrap().is_some());
+}
+
+#[test]
+fn changes_waiting_for_dependencies_can_be_limited() {
+    let mut source = AutoCommit::new();
+    let mut changes = Vec::new();
+    for i in 0..5 {
+        source.put(&ROOT, "x", i).unwrap();
+        changes.push(source.get_last_local_change().unwrap().clone());
+    }
+
+    let mut doc = AutoCommit::new();
+    doc.set_max_pending_changes(Some(2));
+    doc.apply_changes(changes[1..3].to_vec()).unwrap();
+    assert_eq!(doc.pending_changes_len(), 2);
+    assert_eq!(doc.missing_deps(), vec![changes[0].hash()]);
+    assert_eq!(
+        doc.apply_changes(changes[3..].to_vec()),
+        Err(AutomergeError::TooManyPendingChanges(2))
+    );
+    assert_eq!(doc.pending_changes_len(), 2);
+
+    // the queued changes are applied once their dependency arrives
+    doc.apply_changes(vec![changes[0].clone()]).unwrap();
+    assert_eq!(doc.pending_changes_len(), 0);
+    assert_eq!(doc.get(&ROOT, "x").unwrap().unwrap().0, Value::int(2));
+
+    let mut doc = AutoCommit::new();
+    doc.apply_changes(changes[2..].to_vec()).unwrap();
+    let pending = doc.take_pending_changes();
+    assert_eq!(pending.len(), 3);
+    assert!(doc.pending_changes().is_empty());
+    assert!(doc.missing_deps().is_empty());
+}
+
+#[test]
+fn save_diff_only_contains_new_changes() {
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, &"hello world ".repeat(100))
+        .unwrap();
+    let saved = doc.save();
+    assert!(automerge::save_diff(&saved, doc.document())
+        .unwrap()
+        .is_empty());
+
+    doc.splice_text(&text, 0, 0, "!").unwrap();
+    let mut remote = AutoCommit::new();
+    remote.put(ROOT, "other", 1).unwrap();
+    doc.merge(&mut remote).unwrap();
+    let diff = automerge::save_diff(&saved, doc.document()).unwrap();
+    assert!(diff.len() < saved.len());
+
+    let mut loaded = AutoCommit::load(&saved).unwrap();
+    loaded.load_incremental(&diff).unwrap();
+    assert_eq!(loaded.get_heads(), doc.get_heads());
+
+    let appended = [saved, diff].concat();
+    let mut loaded = AutoCommit::load(&appended).unwrap();
+    assert_eq!(loaded.get_heads(), doc.get_heads());
+    let more = automerge::save_diff(&appended, doc.document()).unwrap();
+    assert!(more.is_empty());
+}
+
+#[test]
+fn load_from_reader_reads_one_chunk_at_a_time() {
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "hello").unwrap();
+    let mut saved = doc.save();
+    doc.splice_text(&text, 5, 0, " world").unwrap();
+    doc.put(ROOT, "key", "value").unwrap();
+    saved.extend(doc.save_incremental());
+
+    // a reader which only returns a few bytes at a time
+    let reader = std::io::BufReader::with_capacity(3, saved.as_slice());
+    let loaded = Automerge::load_from_reader(reader).unwrap();
+    assert_eq!(loaded.get_heads(), doc.get_heads());
+    assert_eq!(loaded.text(&text).unwrap(), "hello world");
+    assert_eq!(
+        loaded.save(),
+        Automerge::load(&saved).unwrap().save(),
+        "loading from a reader should match loading from bytes"
+    );
+
+    let empty = Automerge::load_from_reader(std::io::empty()).unwrap();
+    assert!(empty.get_heads().is_empty());
+
+    let truncated = &saved[..saved.len() - 1];
+    assert!(Automerge::load_from_reader(truncated).is_err());
+    assert!(Automerge::load_from_reader(&b"not an automerge document"[..]).is_err());
+}
+
+#[test]
+fn save_to_writer_matches_save() {
+    let mut doc = AutoCommit::new();
+    let mut written = Vec::new();
+    doc.save_to_writer(&mut written).unwrap();
+    assert_eq!(written, doc.save());
+
+    // enough data for the columns to be compressed
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    for i in 0..200 {
+        doc.splice_text(&text, 0, 0, &format!("line {}\n", i))
+            .unwrap();
+        doc.put(ROOT, format!("key{}", i % 10), i).unwrap();
+        doc.commit();
+    }
+    let mut written = Vec::new();
+    doc.save_to_writer(&mut written).unwrap();
+    assert_eq!(written, doc.save());
+
+    // a change whose dependencies we don't have is written after the document
+    let mut other = doc.fork();
+    other.put(ROOT, "missing", 1).unwrap();
+    other.commit();
+    other.put(ROOT, "orphan", 2).unwrap();
+    other.commit();
+    let orphan = other.get_last_local_change().unwrap().clone();
+    doc.apply_changes([orphan]).unwrap();
+    let mut written = Vec::new();
+    doc.save_to_writer(&mut written).unwrap();
+    assert_eq!(written, doc.save());
+    let loaded = Automerge::load(&written).unwrap();
+    assert_eq!(loaded.get_heads(), doc.get_heads());
+}
+
+/// Not a real cipher, just enough to tell sealed bytes from plain ones and spot tampering
+struct XorCipher(u8);
+
+impl automerge::sync::PayloadCipher for XorCipher {
+    fn seal(&self, plaintext: &[u8]) -> Vec<u8> {
+        let tag = plaintext.iter().fold(0_u8, |acc, b| acc.wrapping_add(*b));
+        let mut sealed = plaintext.iter().map(|b| b ^ self.0).collect::<Vec<_>>();
+        sealed.push(tag);
+        sealed
+    }
+
+    fn open(&self, sealed: &[u8]) -> Result<Vec<u8>, automerge::sync::OpenError> {
+        let (tag, body) = sealed.split_last().ok_or(automerge::sync::OpenError)?;
+        let plaintext = body.iter().map(|b| b ^ self.0).collect::<Vec<_>>();
+        if plaintext.iter().fold(0_u8, |acc, b| acc.wrapping_add(*b)) == *tag {
+            Ok(plaintext)
+        } else {
+            Err(automerge::sync::OpenError)
+        }
+    }
+}
+
+#[test]
+fn encrypted_documents_round_trip() {
+    let mut doc = AutoCommit::new();
+    doc.put(ROOT, "secret", "attack at dawn").unwrap();
+    doc.commit();
+    doc.put(ROOT, "more", "retreat at dusk").unwrap();
+    let plain = doc.save();
+    let data = doc.save_encrypted(&XorCipher(0x5a));
+    assert!(!data
+        .windows(b"attack at dawn".len())
+        .any(|w| w == b"attack at dawn"));
+
+    let mut loaded = AutoCommit::load_encrypted(&data, &XorCipher(0x5a)).unwrap();
+    assert_eq!(loaded.get_heads(), doc.get_heads());
+    assert_eq!(
+        loaded.get(ROOT, "secret").unwrap().unwrap().0,
+        doc.get(ROOT, "secret").unwrap().unwrap().0
+    );
+
+    assert!(AutoCommit::load(&data).is_err());
+    assert!(AutoCommit::load_encrypted(&data, &XorCipher(0x33)).is_err());
+    assert!(AutoCommit::load_encrypted(&plain, &XorCipher(0x5a)).is_err());
+    let mut tampered = data.clone();
+    let last = tampered.len() - 2;
+    tampered[last] ^= 1;
+    assert!(AutoCommit::load_encrypted(&tampered, &XorCipher(0x5a)).is_err());
+    // the cleartext checksum is of the sealed bytes, not a fingerprint of the document
+    assert_ne!(data[4..8], plain[4..8]);
+}
+
+#[test]
+fn encrypted_chunks_cannot_be_reordered_or_dropped() {
+    let mut doc = AutoCommit::new();
+    doc.put(ROOT, "a", 1).unwrap();
+    let mut other = doc.fork();
+    other.put(ROOT, "b", 2).unwrap();
+    other.commit();
+    other.put(ROOT, "c", 3).unwrap();
+    other.commit();
+    // only the last change, so it is kept as an orphan in a chunk of its own
+    let orphan = other.get_last_local_change().unwrap().clone();
+    doc.apply_changes([orphan]).unwrap();
+
+    let cipher = XorCipher(0x5a);
+    let data = doc.save_encrypted(&cipher);
+    let mut loaded = AutoCommit::load_encrypted(&data, &cipher).unwrap();
+    assert_eq!(loaded.save(), doc.save());
+
+    // the magic bytes, checksum and chunk type are followed by the LEB128 length of the chunk
+    let mut len_bytes = &data[9..];
+    let len = leb128::read::unsigned(&mut len_bytes).unwrap() as usize;
+    let first_len = data.len() - len_bytes.len() + len;
+    let (first, second) = data.split_at(first_len);
+    assert!(AutoCommit::load_encrypted(first, &cipher).is_err());
+    assert!(AutoCommit::load_encrypted(&[second, first].concat(), &cipher).is_err());
+}
+
+#[test]
+fn validate_checks_documents_without_loading_them() {
+    let mut doc1 = AutoCommit::new();
+    doc1.put(ROOT, "a", 1).unwrap();
+    let list = doc1.put_object(ROOT, "list", ObjType::List).unwrap();
+    doc1.insert(&list, 0, "x").unwrap();
+    doc1.commit();
+    let mut doc2 = doc1.fork();
+    doc2.put(ROOT, "b", 2).unwrap();
+    doc1.insert(&list, 1, "y").unwrap();
+    doc1.merge(&mut doc2).unwrap();
+    let mut bytes = doc1.save();
+    doc1.put(ROOT, "c", 3).unwrap();
+    bytes.extend(doc1.save_incremental());
+
+    let info = automerge::validate(&bytes).unwrap();
+    assert_eq!(info.heads, doc1.get_heads());
+    assert_eq!(info.num_actors, 2);
+    assert_eq!(info.num_changes, 4);
+
+    // a change which depends on a change in the history of a document which isn't a head
+    let mut doc3 = AutoCommit::load(&doc2.save()).unwrap();
+    doc3.put(ROOT, "d", 4).unwrap();
+    let mut bytes = doc1.save();
+    bytes.extend(doc3.save_incremental());
+    let info = automerge::validate(&bytes).unwrap();
+    assert_eq!(info.heads.len(), 2);
+
+    let mut corrupted = doc1.save();
+    // the first byte of the checksum
+    corrupted[4] ^= 0xff;
+    assert!(matches!(
+        automerge::validate(&corrupted),
+        Err(automerge::ValidateError::BadChecksum(0))
+    ));
+
+    let mut doc4 = doc1.fork();
+    doc4.put(ROOT, "e", 5).unwrap();
+    doc4.commit();
+    doc4.put(ROOT, "f", 6).unwrap();
+    doc4.commit();
+    let changes = doc4.get_changes(&doc1.get_heads());
+    let missing = changes[0].hash();
+    let mut bytes = doc1.save();
+    bytes.extend(changes[1].raw_bytes());
+    assert_eq!(
+        automerge::validate(&bytes),
+        Err(automerge::ValidateError::MissingDependencies(vec![missing]))
+    );
+}
+
+fn large_text_doc() -> AutoCommit {
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    for i in 0..200 {
+        doc.splice_text(&text, 0, 0, &format!("line {} of the document\n", i))
+            .unwrap();
+        doc.commit();
+    }
+    doc
+}
+
+#[test]
+fn save_with_a_compression_level() {
+    let mut doc = large_text_doc();
+    let save = |doc: &mut AutoCommit, compression| {
+        doc.save_with_options(automerge::SaveOptions {
+            compression: Some(compression),
+            ..Default::default()
+        })
+    };
+    let none = save(&mut doc, automerge::Compression::None);
+    let fast = save(&mut doc, automerge::Compression::Deflate { level: 1 });
+    let best = save(&mut doc, automerge::Compression::Deflate { level: 9 });
+    assert_eq!(none, doc.save_nocompress());
+    assert_eq!(
+        save(&mut doc, automerge::Compression::default()),
+        doc.save()
+    );
+    assert!(best.len() <= fast.len() && fast.len() < none.len());
+    for bytes in [none, fast, best] {
+        let mut loaded = AutoCommit::load(&bytes).unwrap();
+        assert_eq!(loaded.get_heads(), doc.get_heads());
+        assert_eq!(loaded.save(), doc.save());
+    }
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn save_with_zstd_compression() {
+    let mut doc = large_text_doc();
+    let bytes = doc.save_with_options(automerge::SaveOptions {
+        compression: Some(automerge::Compression::Zstd { level: 19 }),
+        ..Default::default()
+    });
+    let report = automerge::inspect(&bytes);
+    assert!(report.is_valid());
+    assert_eq!(report.chunks[0].kind, automerge::ChunkKind::ZstdDocument);
+    assert!(report.chunks[0].columns.iter().any(|c| c.compressed));
+
+    let mut loaded = AutoCommit::load(&bytes).unwrap();
+    assert_eq!(loaded.get_heads(), doc.get_heads());
+    assert_eq!(loaded.save(), doc.save());
+    assert!(automerge::validate(&bytes).is_ok());
+
+    // a document too small to compress is saved as an ordinary document chunk
+    let mut small = AutoCommit::new();
+    small.put(ROOT, "a", 1).unwrap();
+    let bytes = small.save_with_options(automerge::SaveOptions {
+        compression: Some(automerge::Compression::Zstd { level: 3 }),
+        ..Default::default()
+    });
+    assert_eq!(bytes, small.save());
+}
+
+#[test]
+fn chunked_saves_load_from_a_blob_store() {
+    let mut doc = large_text_doc();
+    let save = doc.save_chunked(1024);
+    assert!(save.blobs.len() > 1);
+    let mut store = save
+        .blobs
+        .into_iter()
+        .collect::<std::collections::HashMap<_, _>>();
+
+    let fetch = |store: &std::collections::HashMap<automerge::BlobId, Vec<u8>>,
+                 id: &automerge::BlobId| {
+        store.get(id).cloned().ok_or("missing blob")
+    };
+    let mut loaded = AutoCommit::load_chunked(&save.manifest, |id| fetch(&store, id)).unwrap();
+    assert_eq!(loaded.get_heads(), doc.get_heads());
+    assert_eq!(loaded.save(), doc.save());
+
+    // saving again after a change reuses all but the last blob
+    let text = doc.get(ROOT, "text").unwrap().unwrap().1;
+    doc.splice_text(&text, 0, 0, "a new line\n").unwrap();
+    let again = doc.save_chunked(1024);
+    let new_blobs = again
+        .blobs
+        .iter()
+        .filter(|(id, _)| !store.contains_key(id))
+        .count();
+    assert!(new_blobs <= 2);
+    let again_blob_ids = again.blobs.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+    store.extend(again.blobs);
+    let mut loaded = AutoCommit::load_chunked(&again.manifest, |id| fetch(&store, id)).unwrap();
+    assert_eq!(loaded.get_heads(), doc.get_heads());
+
+    // the store also holds blobs of the first save which the new manifest doesn't use
+    let first = *again_blob_ids.first().unwrap();
+    store.insert(first, b"not the blob".to_vec());
+    assert!(AutoCommit::load_chunked(&again.manifest, |id| fetch(&store, id)).is_err());
+    assert!(AutoCommit::load_chunked(&again.manifest, |_| Err("offline")).is_err());
+    assert!(AutoCommit::load(&again.manifest).is_err());
+}
+
+#[test]
+fn load_salvage_skips_corrupt_chunks() {
+    let mut doc = AutoCommit::new();
+    doc.put(ROOT, "a", 1).unwrap();
+    let mut bytes = doc.save();
+    let doc_heads = doc.get_heads();
+    doc.put(ROOT, "b", 2).unwrap();
+    let middle = doc.save_incremental();
+    let lost = doc.get_heads()[0];
+    doc.put(ROOT, "c", 3).unwrap();
+    let last = doc.save_incremental();
+    let dropped = doc.get_heads()[0];
+
+    let (mut loaded, report) = AutoCommit::load_salvage(&[&bytes[..], &middle, &last].concat());
+    assert!(report.is_complete());
+    assert_eq!(loaded.get_heads(), doc.get_heads());
+
+    // flip a bit in the checksum of the middle chunk, the last change depends on it
+    let middle_offset = bytes.len();
+    bytes.extend(&middle);
+    bytes[middle_offset + 4] ^= 1;
+    bytes.extend(&last);
+    assert!(AutoCommit::load(&bytes).is_err());
+    let (mut loaded, report) = AutoCommit::load_salvage(&bytes);
+    assert_eq!(loaded.get_heads(), doc_heads);
+    assert_eq!(loaded.get(ROOT, "a").unwrap().unwrap().0, 1.into());
+    assert_eq!(report.skipped.len(), 1);
+    assert_eq!(report.skipped[0].offset, middle_offset);
+    assert_eq!(report.skipped[0].len, middle.len());
+    assert_eq!(report.dropped_changes, vec![dropped]);
+    assert_eq!(report.missing_deps, vec![lost]);
+
+    // garbage where a chunk header should be is skipped up to the next chunk
+    let mut bytes = doc.save();
+    let garbage_offset = bytes.len();
+    bytes.extend([1, 2, 3]);
+    bytes.extend(&doc.save_after(&doc_heads)[..]);
+    let (mut loaded, report) = AutoCommit::load_salvage(&bytes);
+    assert_eq!(loaded.get_heads(), doc.get_heads());
+    assert_eq!(report.skipped.len(), 1);
+    assert_eq!(
+        (report.skipped[0].offset, report.skipped[0].len),
+        (garbage_offset, 3)
+    );
+    assert!(report.dropped_changes.is_empty());
+}
+
+#[test]
+fn identical_concurrent_puts_can_be_collapsed() {
+    let mut doc = AutoCommit::new();
+    doc.put(&ROOT, "status", "init").unwrap();
+    let mut server1 = doc.fork();
+    let mut server2 = doc.fork();
+    let mut server3 = doc.fork();
+    server1.put(&ROOT, "status", "done").unwrap();
+    server2.put(&ROOT, "status", "done").unwrap();
+    server3.put(&ROOT, "status", "failed").unwrap();
+    doc.merge(&mut server1).unwrap();
+    doc.merge(&mut server2).unwrap();
+    doc.merge(&mut server3).unwrap();
+    let heads = doc.get_heads();
+
+    assert_eq!(doc.get_all(&ROOT, "status").unwrap().len(), 3);
+
+    doc.set_collapse_identical_conflicts(true);
+    let mut agreed = doc.fork();
+    agreed.put(&ROOT, "count", 1).unwrap();
+    let mut peer = agreed.fork();
+    agreed.put(&ROOT, "count", 2).unwrap();
+    peer.put(&ROOT, "count", 2).unwrap();
+    agreed.merge(&mut peer).unwrap();
+    agreed.set_collapse_identical_conflicts(true);
+    let conflicts = agreed
+        .map_range(&ROOT, ..)
+        .map(|item| (item.key, item.conflict))
+        .collect::<Vec<_>>();
+    assert_eq!(conflicts, vec![("count", false), ("status", true)]);
+    let values = doc
+        .get_all(&ROOT, "status")
+        .unwrap()
+        .into_iter()
+        .map(|(v, _)| v)
+        .collect::<Vec<_>>();
+    assert_eq!(values.len(), 2);
+    assert!(values.contains(&Value::str("done")));
+    assert!(values.contains(&Value::str("failed")));
+    // the winner is unchanged and the history still contains every write
+    assert_eq!(
+        doc.get(&ROOT, "status").unwrap().unwrap().0,
+        values.last().unwrap().clone()
+    );
+    assert_eq!(doc.get_heads(), heads);
+    assert_eq!(heads.len(), 3);
+}
+
+#[test]
+fn state_hash_depends_only_on_heads() {
+    let mut doc1 = AutoCommit::new();
+    doc1.put(&ROOT, "a", 1).unwrap();
+    let mut doc2 = doc1.fork();
+    assert!(doc1.same_state_as(&mut doc2));
+    assert_eq!(doc1.state_hash(), doc2.state_hash());
+
+    doc2.put(&ROOT, "b", 2).unwrap();
+    assert!(!doc1.same_state_as(&mut doc2));
+    assert_ne!(doc1.state_hash(), doc2.state_hash());
+
+    doc1.merge(&mut doc2).unwrap();
+    let loaded = Automerge::load(&doc1.save()).unwrap();
+    assert!(loaded.same_state_as(doc2.document()));
+    assert_eq!(loaded.state_hash(), doc2.state_hash());
+}
+
+#[test]
+fn make_patches_iter_matches_make_patches() {
+    let mut doc = AutoCommit::new();
+    let list = doc.put_object(&ROOT, "list", ObjType::List).unwrap();
+    let text = doc.put_object(&ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "hello").unwrap();
+    let mut other = doc.fork();
+    for i in 0..200 {
+        other.insert(&list, i, i as i64).unwrap();
+    }
+    other.delete(&list, 10).unwrap();
+    other.splice_text(&text, 5, 0, " world").unwrap();
+    let nested = other.put_object(&ROOT, "nested", ObjType::Map).unwrap();
+    other.put(&nested, "a", 1).unwrap();
+    doc.put(&ROOT, "local", "value").unwrap();
+
+    let mut doc = doc.document().clone();
+    let mut patch_log = PatchLog::active(TextRepresentation::String);
+    doc.merge_and_log_patches(&mut other.document().clone(), &mut patch_log)
+        .unwrap();
+
+    let expected = doc.make_patches(&mut patch_log.clone());
+    assert!(!expected.is_empty());
+    let streamed = doc.make_patches_iter(&mut patch_log).collect::<Vec<_>>();
+    assert_eq!(streamed, expected);
+}
+
+#[test]
+fn view_at_reads_historical_state_through_read_doc() {
+    fn summarize<R: ReadDoc>(doc: &R, list: &ObjId, text: &ObjId) -> (usize, String, Vec<String>) {
+        (
+            doc.length(list),
+            doc.text(text).unwrap(),
+            doc.keys(ROOT).collect(),
+        )
+    }
+
+    let mut doc = AutoCommit::new();
+    let list = doc.put_object(&ROOT, "list", ObjType::List).unwrap();
+    let text = doc.put_object(&ROOT, "text", ObjType::Text).unwrap();
+    doc.insert(&list, 0, 1).unwrap();
+    doc.splice_text(&text, 0, 0, "hello").unwrap();
+    let heads = doc.get_heads();
+    let before = summarize(&doc, &list, &text);
+
+    doc.insert(&list, 1, 2).unwrap();
+    doc.splice_text(&text, 5, 0, " world").unwrap();
+    doc.put(&ROOT, "later", true).unwrap();
+    let latest = doc.get_heads();
+
+    let view = doc.view_at(&heads);
+    assert_eq!(view.heads(), heads.as_slice());
+    assert_eq!(summarize(&view, &list, &text), before);
+    assert_eq!(view.get(&ROOT, "later").unwrap(), None);
+    assert_eq!(
+        view.hydrate(&ROOT, None).unwrap(),
+        doc.hydrate(&ROOT, Some(&heads)).unwrap()
+    );
+    // methods which take heads read at those heads rather than the heads of the view
+    assert_eq!(view.text_at(&text, &latest).unwrap(), "hello world");
+}
+
+#[test]
+fn cursor_affinity_decides_side_of_concurrent_inserts() {
+    use automerge::{Affinity, Cursor};
+
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(&ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "hello world").unwrap();
+    let after = doc.get_cursor(&text, 5, None).unwrap();
+    let before = doc
+        .get_cursor_with_affinity(&text, 5, None, Affinity::BeforeInsert)
+        .unwrap();
+    let start = doc
+        .get_cursor_with_affinity(&text, 0, None, Affinity::BeforeInsert)
+        .unwrap();
+    // cursors which follow inserts are encoded as they were before affinities existed
+    assert_eq!(after.to_bytes()[0], 0);
+    assert!(!after.to_string().contains('~'));
+    assert_eq!(
+        doc.get_cursor_with_affinity(&text, 5, None, Affinity::AfterInsert)
+            .unwrap(),
+        after
+    );
+
+    let mut other = doc.fork();
+    other.splice_text(&text, 5, 0, ",").unwrap();
+    other.splice_text(&text, 0, 0, ">").unwrap();
+    doc.merge(&mut other).unwrap();
+    assert_eq!(doc.text(&text).unwrap(), ">hello, world");
+
+    assert_eq!(doc.get_cursor_position(&text, &after, None).unwrap(), 7);
+    assert_eq!(doc.get_cursor_position(&text, &before, None).unwrap(), 6);
+    assert_eq!(doc.get_cursor_position(&text, &start, None).unwrap(), 0);
+
+    // deleting the element before the cursor leaves it where that element was
+    doc.splice_text(&text, 5, 1, "").unwrap();
+    assert_eq!(doc.get_cursor_position(&text, &before, None).unwrap(), 5);
+
+    for cursor in [&after, &before, &start] {
+        assert_eq!(&Cursor::try_from(cursor.to_string()).unwrap(), cursor);
+        assert_eq!(&Cursor::try_from(cursor.to_bytes()).unwrap(), cursor);
+    }
+    // cursors which don't know the element before them always follow inserts
+    let legacy = after.clone().with_affinity(Affinity::BeforeInsert);
+    assert_eq!(doc.get_cursor_position(&text, &legacy, None).unwrap(), 6);
+}
+
+#[test]
+fn frontier_can_be_used_as_heads() {
+    let mut doc1 = AutoCommit::new();
+    doc1.put(&ROOT, "a", 1).unwrap();
+    let mut doc2 = doc1.fork();
+    let before = doc1.get_frontier();
+    doc1.put(&ROOT, "b", 2).unwrap();
+    doc2.put(&ROOT, "c", 3).unwrap();
+    doc1.merge(&mut doc2).unwrap();
+    doc2.merge(&mut doc1).unwrap();
+
+    let after = doc1.get_frontier();
+    assert_eq!(after.len(), 2);
+    assert_eq!(after, doc2.get_frontier());
+    assert_eq!(after.digest(), doc1.state_hash());
+    assert_eq!(doc1.diff(&before, &after).len(), 2);
+
+    let mut s1 = automerge::sync::State::new();
+    let mut s2 = automerge::sync::State::new();
+    let mut doc3 = AutoCommit::new();
+    loop {
+        let Some(msg) = doc1.sync().generate_sync_message(&mut s1) else {
+            break;
+        };
+        doc3.sync().receive_sync_message(&mut s2, msg).unwrap();
+        if let Some(reply) = doc3.sync().generate_sync_message(&mut s2) {
+            doc1.sync().receive_sync_message(&mut s1, reply).unwrap();
+        }
+    }
+    assert_eq!(s1.shared_frontier(), after);
+    assert_eq!(s2.their_frontier(), Some(after));
+}
+
+#[test]
+fn list_elements_can_be_found_by_elem_id() {
+    let mut doc = AutoCommit::new();
+    let list = doc.put_object(&ROOT, "list", ObjType::List).unwrap();
+    for (i, item) in ["a", "b", "c"].into_iter().enumerate() {
+        doc.insert(&list, i, item).unwrap();
+    }
+    let elem_ids = doc
+        .list_range(&list, ..)
+        .map(|item| item.elem_id)
+        .collect::<Vec<_>>();
+
+    let mut other = doc.fork();
+    other.insert(&list, 0, "z").unwrap();
+    other.put(&list, 3, "C").unwrap();
+    doc.delete(&list, 1).unwrap();
+    doc.merge(&mut other).unwrap();
+    assert_eq!(
+        doc.list_range(&list, ..)
+            .map(|item| item.elem_id)
+            .collect::<Vec<_>>()[1..],
+        [elem_ids[0].clone(), elem_ids[2].clone()]
+    );
+
+    let (index, value, _) = doc.get_by_elem_id(&list, &elem_ids[0]).unwrap().unwrap();
+    assert_eq!((index, value.to_str()), (1, Some("a")));
+    // overwriting an element keeps its ID
+    let (index, value, _) = doc.get_by_elem_id(&list, &elem_ids[2]).unwrap().unwrap();
+    assert_eq!((index, value.to_str()), (2, Some("C")));
+    assert_eq!(doc.get_by_elem_id(&list, &elem_ids[1]).unwrap(), None);
+
+    // element IDs survive being encoded
+    let decoded = ObjId::try_from(elem_ids[2].to_bytes().as_slice()).unwrap();
+    assert_eq!(
+        doc.get_by_elem_id(&list, &decoded)
+            .unwrap()
+            .map(|(i, _, _)| i),
+        Some(2)
+    );
+    assert!(doc.get_by_elem_id(&ROOT, &elem_ids[0]).is_err());
+}
+
+#[test]
+fn coalesced_text_patches_merge_backwards_deletes() {
+    let mut doc = Automerge::new();
+    let mut tx = doc.transaction();
+    let text = tx.put_object(ROOT, "text", ObjType::Text).unwrap();
+    tx.splice_text(&text, 0, 0, "hello world").unwrap();
+    tx.commit();
+
+    let edit = |doc: &mut Automerge, coalesce: bool| {
+        let mut doc = doc.clone();
+        let log = PatchLog::active(TextRepresentation::String).with_coalesced_text(coalesce);
+        let mut tx = doc.transaction_log_patches(log);
+        // backspace three times at the end of the text
+        for index in [11, 10, 9] {
+            tx.splice_text(&text, index, -1, "").unwrap();
+        }
+        // type after "hello" then delete what was typed along with the characters around it
+        tx.splice_text(&text, 5, 0, "abc").unwrap();
+        tx.splice_text(&text, 4, 5, "").unwrap();
+        let (_, mut log) = tx.commit();
+        assert_eq!(doc.text(&text).unwrap(), "hellwo");
+        doc.make_patches(&mut log)
+            .into_iter()
+            .map(|patch| patch.action)
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        edit(&mut doc, true),
+        vec![
+            PatchAction::DeleteSeq {
+                index: 8,
+                length: 3
+            },
+            PatchAction::DeleteSeq {
+                index: 4,
+                length: 2
+            },
+        ]
+    );
+    assert_eq!(edit(&mut doc, false).len(), 5);
+}
+
+#[test]
+fn sealed_objects_converge_when_peers_change_them_concurrently() {
+    let mut doc = Automerge::new();
+    let mut tx = doc.transaction();
+    let list = tx.put_object(&ROOT, "items", ObjType::List).unwrap();
+    tx.insert(&list, 0, "a").unwrap();
+    tx.commit();
+    let mut other = doc.fork();
+
+    let mut tx = doc.transaction();
+    tx.seal_object(&list).unwrap();
+    assert!(tx.is_sealed(&list).unwrap());
+    assert!(matches!(
+        tx.insert(&list, 1, "b"),
+        Err(AutomergeError::ObjectSealed(_))
+    ));
+    tx.commit();
+
+    let mut tx = other.transaction();
+    tx.insert(&list, 1, "b").unwrap();
+    tx.put(&ROOT, "count", 1).unwrap();
+    tx.commit();
+    let unsealed = other.get_heads();
+
+    doc.merge(&mut other).unwrap();
+    other.merge(&mut doc).unwrap();
+    let loaded = Automerge::load(&doc.save()).unwrap();
+    for doc in [&doc, &other, &loaded] {
+        assert!(doc.is_sealed(&list).unwrap());
+        assert_eq!(doc.length(&list), 1);
+        // the seal is not stored as a value the user can see
+        assert_eq!(doc.keys(&ROOT).collect::<Vec<_>>(), ["count", "items"]);
+        // reading at heads which don't include the seal sees the concurrent change
+        assert_eq!(doc.length_at(&list, &unsealed), 2);
+    }
+    assert_eq!(doc.get_heads(), other.get_heads());
+}
+
+#[test]
+fn patches_agree_with_reads_of_sealed_objects() {
+    let mut doc = AutoCommit::new();
+    let settings = doc.put_object(&ROOT, "settings", ObjType::Map).unwrap();
+    doc.put(&settings, "theme", "dark").unwrap();
+    let mut other = doc.fork();
+    doc.seal_object(&settings).unwrap();
+    other.put(&settings, "theme", "light").unwrap();
+    other.put(&settings, "font", "serif").unwrap();
+
+    let settings_patches = |patches: Vec<Patch>| {
+        patches
+            .into_iter()
+            .filter(|p| p.obj == settings)
+            .map(|p| match p.action {
+                PatchAction::PutMap { key, value, .. } => {
+                    format!("put {} {}", key, value.0.to_str().unwrap())
+                }
+                PatchAction::DeleteMap { key } => format!("delete {}", key),
+                action => panic!("unexpected patch {:?}", action),
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // the seal arrives after the concurrent changes have been applied
+    other.update_diff_cursor();
+    other.merge(&mut doc).unwrap();
+    let mut patches = settings_patches(other.diff_incremental());
+    patches.sort();
+    assert_eq!(patches, ["delete font", "put theme dark"]);
+
+    // the seal arrives before the concurrent changes
+    other.fork().merge(&mut doc).unwrap();
+    doc.update_diff_cursor();
+    doc.merge(&mut other).unwrap();
+    assert!(settings_patches(doc.diff_incremental()).is_empty());
+
+    for doc in [&mut doc, &mut other] {
+        let heads = doc.get_heads();
+        assert_eq!(settings_patches(doc.diff(&[], &heads)), ["put theme dark"]);
+    }
+}
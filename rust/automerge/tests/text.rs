@@ -3,10 +3,12 @@ use std::str::FromStr;
 use automerge::{
     hydrate_list, hydrate_map,
     iter::Span,
-    marks::{ExpandMark, Mark},
+    marks::{ExpandMark, Mark, MarkMerge},
     op_tree::B,
+    patches::TextEncoding,
     transaction::Transactable,
-    ActorId, AutoCommit, ObjType, Patch, PatchAction, ReadDoc, ScalarValue, ROOT,
+    ActorId, Affinity, AutoCommit, AutomergeError, ObjType, Patch, PatchAction, ReadDoc,
+    ScalarValue, ROOT,
 };
 use proptest::strategy::Strategy;
 use test_log::test;
@@ -50,6 +52,651 @@ fn update_text_big_ole_graphemes() {
     assert_eq!(doc.text(&text).unwrap(), "left👨‍👩‍👧👨‍👩‍👦‍👦right");
 }
 
+#[test]
+fn update_text_keeps_marks_and_cursors_on_unchanged_text() {
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "the quick fox").unwrap();
+    doc.mark(
+        &text,
+        Mark::new("bold".into(), true, 10, 13),
+        ExpandMark::None,
+    )
+    .unwrap();
+    let cursor = doc.get_cursor(&text, 10, None).unwrap();
+
+    doc.update_text(&text, "the very quick fox").unwrap();
+
+    assert_eq!(doc.text(&text).unwrap(), "the very quick fox");
+    let marks = doc.marks(&text).unwrap();
+    assert_eq!(marks.len(), 1);
+    assert_eq!((marks[0].start, marks[0].end), (15, 18));
+    assert_eq!(doc.get_cursor_position(&text, &cursor, None).unwrap(), 15);
+}
+
+#[test]
+fn marks_use_the_expand_set_for_their_name() {
+    let mut doc = AutoCommit::new();
+    doc.set_mark_expand("bold", ExpandMark::Both).unwrap();
+    doc.set_mark_expand("comment", ExpandMark::None).unwrap();
+    assert_eq!(doc.mark_expand("italic").unwrap(), ExpandMark::After);
+
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "abc def").unwrap();
+    doc.mark_with_default_expand(&text, Mark::new("bold".into(), true, 0, 3))
+        .unwrap();
+    doc.mark_with_default_expand(&text, Mark::new("comment".into(), "c1", 4, 7))
+        .unwrap();
+
+    // a peer with no settings inserting concurrently at the boundaries
+    let mut other = AutoCommit::new();
+    other.merge(&mut doc).unwrap();
+    other.splice_text(&text, 7, 0, "!").unwrap();
+    other.splice_text(&text, 3, 0, "x").unwrap();
+    doc.merge(&mut other).unwrap();
+
+    assert_eq!(doc.text(&text).unwrap(), "abcx def!");
+    let marks = doc
+        .marks(&text)
+        .unwrap()
+        .into_iter()
+        .map(|m| (m.name().to_string(), m.start, m.end))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        marks,
+        [("bold".to_string(), 0, 4), ("comment".to_string(), 5, 8)]
+    );
+
+    // the settings are part of the document so peers use them too
+    assert_eq!(other.mark_expand("bold").unwrap(), ExpandMark::Both);
+    assert!(other.keys(ROOT).eq(["text"]));
+}
+
+#[test]
+fn splice_text_with_cursors_brackets_the_inserted_text() {
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "hello world").unwrap();
+
+    let (start, end) = doc
+        .splice_text_with_cursors(&text, 6, 5, "there", Affinity::BeforeInsert)
+        .unwrap();
+    assert_eq!(doc.text(&text).unwrap(), "hello there");
+    assert_eq!(start.affinity(), Affinity::BeforeInsert);
+    assert_eq!(end.affinity(), Affinity::BeforeInsert);
+    assert_eq!(doc.get_cursor_position(&text, &start, None).unwrap(), 6);
+    assert_eq!(doc.get_cursor_position(&text, &end, None).unwrap(), 10);
+
+    doc.splice_text(&text, 0, 0, ">> ").unwrap();
+    assert_eq!(doc.get_cursor_position(&text, &start, None).unwrap(), 9);
+    assert_eq!(doc.get_cursor_position(&text, &end, None).unwrap(), 13);
+
+    let (start, end) = doc
+        .splice_text_with_cursors(&text, 2, -2, "!", Affinity::AfterInsert)
+        .unwrap();
+    assert_eq!(doc.text(&text).unwrap(), "! hello there");
+    assert_eq!(doc.get_cursor_position(&text, &start, None).unwrap(), 0);
+    assert_eq!(doc.get_cursor_position(&text, &end, None).unwrap(), 0);
+
+    assert!(doc
+        .splice_text_with_cursors(&text, 0, 1, "", Affinity::AfterInsert)
+        .is_err());
+    assert_eq!(doc.text(&text).unwrap(), "! hello there");
+}
+
+#[test]
+fn get_marks_returns_the_marks_covering_one_index() {
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "hello big world").unwrap();
+    doc.mark(
+        &text,
+        Mark::new("bold".into(), true, 0, 9),
+        ExpandMark::None,
+    )
+    .unwrap();
+    doc.mark(
+        &text,
+        Mark::new("link".into(), "https://example.com", 6, 15),
+        ExpandMark::None,
+    )
+    .unwrap();
+
+    let names = |index| {
+        doc.get_marks(&text, index, None)
+            .unwrap()
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(names(0), [("bold".to_string(), ScalarValue::Boolean(true))]);
+    assert_eq!(
+        names(7),
+        [
+            ("bold".to_string(), ScalarValue::Boolean(true)),
+            ("link".to_string(), ScalarValue::from("https://example.com")),
+        ]
+    );
+    assert_eq!(
+        names(9),
+        [("link".to_string(), ScalarValue::from("https://example.com"))]
+    );
+}
+
+#[test]
+fn text_chunks_concatenate_to_the_text() {
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "hello world").unwrap();
+    doc.split_block(&text, 5).unwrap();
+    let heads = doc.get_heads();
+    doc.splice_text(&text, 0, 5, "goodbye").unwrap();
+
+    let chunks = doc.text_chunks(&text).unwrap().collect::<String>();
+    assert_eq!(chunks, doc.text(&text).unwrap());
+
+    let old = doc
+        .view_at(&heads)
+        .text_chunks(&text)
+        .unwrap()
+        .collect::<String>();
+    assert_eq!(old, doc.text_at(&text, &heads).unwrap());
+}
+
+#[test]
+fn word_and_line_boundaries() {
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "hello, world\nsecond line")
+        .unwrap();
+
+    assert_eq!(doc.text_word_boundaries(&text, 0).unwrap(), 0..5);
+    assert_eq!(doc.text_word_boundaries(&text, 4).unwrap(), 0..5);
+    assert_eq!(doc.text_word_boundaries(&text, 5).unwrap(), 5..6);
+    assert_eq!(doc.text_word_boundaries(&text, 6).unwrap(), 6..7);
+    assert_eq!(doc.text_word_boundaries(&text, 8).unwrap(), 7..12);
+    assert_eq!(doc.text_word_boundaries(&text, 20).unwrap(), 20..24);
+    assert_eq!(doc.text_word_boundaries(&text, 24).unwrap(), 24..24);
+    assert!(doc.text_word_boundaries(&text, 25).is_err());
+
+    assert_eq!(doc.line_range(&text, 3).unwrap(), 0..12);
+    assert_eq!(doc.line_range(&text, 12).unwrap(), 0..12);
+    assert_eq!(doc.line_range(&text, 13).unwrap(), 13..24);
+    assert_eq!(doc.line_range(&text, 24).unwrap(), 13..24);
+    assert!(doc.line_range(&text, 25).is_err());
+
+    assert!(doc.line_range(ROOT, 0).is_err());
+}
+
+#[test]
+fn text_is_attributed_to_the_changes_which_inserted_it() {
+    let mut alice = AutoCommit::new().with_actor(ActorId::from([1]));
+    let text = alice.put_object(ROOT, "text", ObjType::Text).unwrap();
+    alice.splice_text(&text, 0, 0, "hello world").unwrap();
+    alice.commit();
+    let first = alice.get_heads();
+
+    let mut bob = alice.fork().with_actor(ActorId::from([2]));
+    bob.splice_text(&text, 5, 0, " big").unwrap();
+    bob.commit();
+    alice.merge(&mut bob).unwrap();
+
+    let runs = alice
+        .attribute_text(&text, None)
+        .unwrap()
+        .into_iter()
+        .map(|run| (run.start, run.end, run.text, run.actor, run.hash))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        runs,
+        [
+            (
+                0,
+                5,
+                "hello".to_string(),
+                ActorId::from([1]),
+                Some(first[0])
+            ),
+            (
+                5,
+                9,
+                " big".to_string(),
+                ActorId::from([2]),
+                Some(bob.get_heads()[0])
+            ),
+            (
+                9,
+                15,
+                " world".to_string(),
+                ActorId::from([1]),
+                Some(first[0])
+            ),
+        ]
+    );
+
+    let runs = alice.attribute_text(&text, Some(&first)).unwrap();
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0].text, "hello world");
+}
+
+#[test]
+fn unmark_where_removes_matching_marks() {
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "hello big world").unwrap();
+    doc.mark(
+        &text,
+        Mark::new("bold".into(), true, 0, 5),
+        ExpandMark::None,
+    )
+    .unwrap();
+    doc.mark(
+        &text,
+        Mark::new("comment".into(), "a", 2, 8),
+        ExpandMark::None,
+    )
+    .unwrap();
+    doc.mark(
+        &text,
+        Mark::new("comment".into(), "b", 10, 15),
+        ExpandMark::None,
+    )
+    .unwrap();
+
+    let marks = |doc: &AutoCommit| {
+        doc.marks(&text)
+            .unwrap()
+            .into_iter()
+            .map(|m| (m.name().to_string(), m.value().clone(), m.start, m.end))
+            .collect::<Vec<_>>()
+    };
+
+    doc.unmark_where(&text, |mark| mark.value() == &ScalarValue::from("b"))
+        .unwrap();
+    assert_eq!(
+        marks(&doc),
+        [
+            ("bold".to_string(), ScalarValue::Boolean(true), 0, 5),
+            ("comment".to_string(), ScalarValue::from("a"), 2, 8),
+        ]
+    );
+
+    let before = doc.pending_ops();
+    doc.unmark_where(&text, |mark| mark.name() == "comment")
+        .unwrap();
+    // a single unmark is a begin and an end op
+    assert_eq!(doc.pending_ops() - before, 2);
+    assert_eq!(
+        marks(&doc),
+        [("bold".to_string(), ScalarValue::Boolean(true), 0, 5)]
+    );
+}
+
+#[test]
+fn line_endings_can_be_canonicalized() {
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "a\r\nb").unwrap();
+    assert_eq!(doc.text(&text).unwrap(), "a\r\nb");
+    assert!(doc.canonicalized_line_endings().is_empty());
+
+    doc.set_canonicalize_line_endings(true).unwrap();
+    doc.splice_text(&text, 4, 0, "\r\nc\r\n\r\nd").unwrap();
+    assert_eq!(doc.text(&text).unwrap(), "a\r\nb\nc\n\nd");
+    let indices = doc
+        .canonicalized_line_endings()
+        .iter()
+        .map(|c| (c.obj.clone(), c.index))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        indices,
+        [(text.clone(), 4), (text.clone(), 6), (text.clone(), 7)]
+    );
+
+    doc.commit();
+    assert!(doc.canonicalized_line_endings().is_empty());
+
+    doc.update_text(&text, "x\r\ny").unwrap();
+    assert_eq!(doc.text(&text).unwrap(), "x\ny");
+    assert_eq!(doc.canonicalized_line_endings().len(), 1);
+    assert_eq!(doc.canonicalized_line_endings()[0].index, 1);
+
+    // the setting is part of the document so peers canonicalize too
+    let mut other = doc.fork();
+    assert!(other.canonicalizes_line_endings().unwrap());
+    other.splice_text(&text, 0, 0, "w\r\n").unwrap();
+    assert_eq!(other.text(&text).unwrap(), "w\nx\ny");
+}
+
+#[test]
+fn mark_diff_reports_changed_formatting() {
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "hello world").unwrap();
+    doc.mark(
+        &text,
+        Mark::new("bold".into(), true, 0, 5),
+        ExpandMark::None,
+    )
+    .unwrap();
+    doc.mark(
+        &text,
+        Mark::new("comment".into(), "a", 6, 11),
+        ExpandMark::None,
+    )
+    .unwrap();
+    let from = doc.get_heads();
+
+    doc.splice_text(&text, 0, 0, ">> ").unwrap();
+    doc.unmark(&text, "bold", 3, 5, ExpandMark::None).unwrap();
+    doc.mark(
+        &text,
+        Mark::new("italic".into(), true, 3, 8),
+        ExpandMark::None,
+    )
+    .unwrap();
+    doc.mark(
+        &text,
+        Mark::new("comment".into(), "b", 9, 11),
+        ExpandMark::None,
+    )
+    .unwrap();
+    let to = doc.get_heads();
+
+    let diff = doc
+        .mark_diff(&text, &from, &to)
+        .unwrap()
+        .into_iter()
+        .map(|c| (c.name, c.start, c.end, c.before, c.after))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        diff,
+        [
+            (
+                "bold".to_string(),
+                3,
+                5,
+                Some(ScalarValue::Boolean(true)),
+                None
+            ),
+            (
+                "italic".to_string(),
+                3,
+                8,
+                None,
+                Some(ScalarValue::Boolean(true))
+            ),
+            (
+                "comment".to_string(),
+                9,
+                11,
+                Some(ScalarValue::from("a")),
+                Some(ScalarValue::from("b"))
+            ),
+        ]
+    );
+    assert!(doc.mark_diff(&text, &to, &to).unwrap().is_empty());
+}
+
+#[test]
+fn lines_objects_store_one_op_per_line_and_can_be_marked() {
+    let mut doc = AutoCommit::new();
+    let lines = doc.put_object(ROOT, "lines", ObjType::List).unwrap();
+    doc.insert_line(&lines, 0, "first line").unwrap();
+    doc.insert_line(&lines, 1, "third line").unwrap();
+    doc.insert_line(&lines, 1, "second line").unwrap();
+    assert!(matches!(
+        doc.insert_line(&lines, 0, "bad\nline"),
+        Err(AutomergeError::NewlineInLine)
+    ));
+
+    assert_eq!(doc.line_count(&lines).unwrap(), 3);
+    assert_eq!(
+        doc.lines_range(&lines, 1..).unwrap(),
+        vec!["second line".to_string(), "third line".to_string()]
+    );
+
+    doc.mark(
+        &lines,
+        Mark::new("highlight".to_string(), true, 1, 2),
+        ExpandMark::None,
+    )
+    .unwrap();
+    let marks = doc.marks(&lines).unwrap();
+    assert_eq!(marks.len(), 1);
+    assert_eq!((marks[0].start, marks[0].end), (1, 2));
+
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    assert!(doc.insert_line(&text, 0, "nope").is_err());
+    assert!(doc.line_count(&text).is_err());
+}
+
+#[test]
+fn find_text_returns_indexes_and_cursors_of_matches() {
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "abababa and aba").unwrap();
+
+    let matches = doc.find_text(&text, "aba").unwrap();
+    let indexes = matches.iter().map(|m| m.index).collect::<Vec<_>>();
+    assert_eq!(indexes, vec![0, 4, 12]);
+    assert!(doc.find_text(&text, "xyz").unwrap().is_empty());
+    assert!(doc.find_text(&text, "").unwrap().is_empty());
+
+    let heads = doc.get_heads();
+    doc.splice_text(&text, 0, 0, "...").unwrap();
+    assert_eq!(
+        doc.get_cursor_position(&text, &matches[2].cursor, None)
+            .unwrap(),
+        15
+    );
+    assert_eq!(
+        doc.find_text(&text, "and").unwrap()[0].index,
+        doc.text(&text).unwrap().find("and").unwrap()
+    );
+    let old = doc.view_at(&heads);
+    assert_eq!(old.find_text(&text, "and").unwrap()[0].index, 8);
+
+    let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+    assert!(doc.find_text(&list, "a").is_err());
+}
+
+#[test]
+fn text_display_width_counts_wide_characters_twice() {
+    fn expected<R: ReadDoc>(
+        doc: &R,
+        text: &automerge::ObjId,
+        range: std::ops::Range<usize>,
+    ) -> usize {
+        doc.list_range(text, ..)
+            .filter(|item| range.contains(&item.index))
+            .map(|item| match item.value.to_str() {
+                Some("\n") => 0,
+                Some(s) if s.is_ascii() => 1,
+                _ => 2,
+            })
+            .sum()
+    }
+
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    let line = "abc 漢字 def\n";
+    for _ in 0..50 {
+        let len = doc.length(&text);
+        doc.splice_text(&text, len, 0, line).unwrap();
+    }
+    let heads = doc.get_heads();
+    doc.splice_text(&text, 0, 4, "").unwrap();
+    let len = doc.length(&text);
+
+    assert_eq!(
+        doc.text_display_width(&text, ..).unwrap(),
+        expected(&doc, &text, 0..len)
+    );
+    for (start, end) in [(0, 0), (0, 1), (3, 17), (5, 300), (100, len)] {
+        assert_eq!(
+            doc.text_display_width(&text, start..end).unwrap(),
+            expected(&doc, &text, start..end),
+        );
+    }
+    let old = doc.view_at(&heads);
+    let old_len = old.length(&text);
+    assert_eq!(
+        old.text_display_width(&text, 10..old_len).unwrap(),
+        expected(&old, &text, 10..old_len)
+    );
+
+    let list = doc.put_object(ROOT, "list", ObjType::List).unwrap();
+    assert!(doc.text_display_width(&list, ..).is_err());
+}
+
+#[test]
+fn splice_text_from_reader_inserts_text_a_chunk_at_a_time() {
+    // a reader which hands out a few bytes at a time, splitting characters and line endings
+    struct Trickle<'a>(&'a [u8]);
+
+    impl<'a> std::io::Read for Trickle<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.0.len().min(buf.len()).min(3);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    let mut doc = AutoCommit::new();
+    doc.set_canonicalize_line_endings(true).unwrap();
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "hello world").unwrap();
+
+    let pasted = "ab\r\nünïcödé 漢字\r\nend";
+    let width = doc
+        .splice_text_from_reader(&text, 6, 5, Trickle(pasted.as_bytes()), None)
+        .unwrap();
+    let expected = pasted.replace("\r\n", "\n");
+    assert_eq!(doc.text(&text).unwrap(), format!("hello {}", expected));
+    assert_eq!(width, doc.length(&text) - 6);
+
+    let len = doc.length(&text);
+    assert!(matches!(
+        doc.splice_text_from_reader(&text, len, 0, Trickle(b"too long"), Some(4)),
+        Err(AutomergeError::SpliceTooLarge(4))
+    ));
+    assert!(matches!(
+        doc.splice_text_from_reader(&text, 0, 0, Trickle(b"ok\xffno"), None),
+        Err(AutomergeError::InvalidCharacter(2))
+    ));
+}
+
+#[test]
+fn marks_and_spans_can_be_read_at_old_heads() {
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "hello world").unwrap();
+    doc.mark(
+        &text,
+        Mark::new("bold".into(), true, 0, 5),
+        ExpandMark::None,
+    )
+    .unwrap();
+    let heads = doc.get_heads();
+
+    doc.unmark(&text, "bold", 0, 5, ExpandMark::None).unwrap();
+    doc.mark(
+        &text,
+        Mark::new("italic".into(), true, 6, 11),
+        ExpandMark::None,
+    )
+    .unwrap();
+    doc.splice_text(&text, 0, 0, ">> ").unwrap();
+
+    let marks = doc.marks_at(&text, &heads).unwrap();
+    assert_eq!(marks.len(), 1);
+    assert_eq!(
+        (marks[0].name(), marks[0].start, marks[0].end),
+        ("bold", 0, 5)
+    );
+
+    let spans = doc
+        .spans_at(&text, &heads)
+        .unwrap()
+        .map(|span| match span {
+            Span::Text(text, marks) => (text, marks.map(|m| m.len()).unwrap_or(0)),
+            Span::Block(_) | Span::Embed(_) => panic!("unexpected block"),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(spans, [("hello".to_string(), 1), (" world".to_string(), 0)]);
+
+    let marks = doc.marks(&text).unwrap();
+    assert_eq!(marks.len(), 1);
+    assert_eq!(marks[0].name(), "italic");
+}
+
+#[test]
+fn overlapping_mark_values_are_merged_with_the_mark_merge_setting() {
+    let mut doc = AutoCommit::new().with_actor(ActorId::from_str("bbbb").unwrap());
+    doc.set_mark_merge("size", MarkMerge::Max).unwrap();
+    doc.set_mark_merge("class", MarkMerge::Union).unwrap();
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    doc.splice_text(&text, 0, 0, "abcdefgh").unwrap();
+
+    let mut other = doc.fork().with_actor(ActorId::from_str("aaaa").unwrap());
+    doc.mark(&text, Mark::new("size".into(), 16, 0, 5), ExpandMark::None)
+        .unwrap();
+    doc.mark(
+        &text,
+        Mark::new("class".into(), "red", 0, 5),
+        ExpandMark::None,
+    )
+    .unwrap();
+    other
+        .mark(&text, Mark::new("size".into(), 12, 3, 8), ExpandMark::None)
+        .unwrap();
+    other
+        .mark(
+            &text,
+            Mark::new("class".into(), "big red", 3, 8),
+            ExpandMark::None,
+        )
+        .unwrap();
+    doc.merge(&mut other).unwrap();
+
+    let marks = doc
+        .marks(&text)
+        .unwrap()
+        .into_iter()
+        .map(|m| (m.name().to_string(), m.value().to_string(), m.start, m.end))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        marks,
+        [
+            ("class".to_string(), "\"red\"".to_string(), 0, 3),
+            ("class".to_string(), "\"big red\"".to_string(), 3, 8),
+            ("size".to_string(), "16".to_string(), 0, 5),
+            ("size".to_string(), "12".to_string(), 5, 8),
+        ]
+    );
+
+    // the settings are part of the document so peers merge the values the same way
+    other.merge(&mut doc).unwrap();
+    assert_eq!(other.marks(&text).unwrap(), doc.marks(&text).unwrap());
+    let loaded = AutoCommit::load(&doc.save()).unwrap();
+    assert_eq!(loaded.marks(&text).unwrap(), doc.marks(&text).unwrap());
+
+    // an unmark clears the values of the marks before it
+    doc.unmark(&text, "size", 0, 8, ExpandMark::None).unwrap();
+    doc.mark(&text, Mark::new("size".into(), 10, 0, 2), ExpandMark::None)
+        .unwrap();
+    let sizes = doc
+        .marks(&text)
+        .unwrap()
+        .into_iter()
+        .filter(|m| m.name() == "size")
+        .map(|m| (m.value().to_string(), m.start, m.end))
+        .collect::<Vec<_>>();
+    assert_eq!(sizes, [("10".to_string(), 0, 2)]);
+}
+
 macro_rules! assert_marks {
     ($marks:expr, $expected:expr) => {
         let marks = $marks
@@ -382,7 +1029,7 @@ fn insertions_after_noexpand_spans_are_not_marked() {
     let mut new_blocks = spans
         .map(|s| match s {
             Span::Text(s, _) => automerge::BlockOrText::Text(s.into()),
-            Span::Block(m) => automerge::BlockOrText::Block(m),
+            Span::Block(m) | Span::Embed(m) => automerge::BlockOrText::Block(m),
         })
         .collect::<Vec<_>>();
     new_blocks.push(automerge::BlockOrText::Block(hydrate_map! {
@@ -661,6 +1308,56 @@ fn test_remote_patches_for_marks_with_expand_after() {
     assert_eq!(patches_a, patches_b);
 }
 
+#[test]
+fn text_indices_in_other_encodings_span_many_nodes_and_old_heads() {
+    let mut doc = AutoCommit::new();
+    let text = doc.put_object(ROOT, "text", ObjType::Text).unwrap();
+    let chunk = "a🐻é";
+    doc.splice_text_with_encoding(&text, 0, 0, &chunk.repeat(300), TextEncoding::CodePoint)
+        .unwrap();
+    let heads = doc.get_heads();
+
+    let mut other = doc.fork();
+    other
+        .splice_text_with_encoding(&text, 0, 0, "🐻", TextEncoding::Utf8)
+        .unwrap();
+    // replace the "a" of chunk 250, which is well past the first node of the text
+    let utf16_pos = 250 * chunk.encode_utf16().count();
+    doc.splice_text_with_encoding(&text, utf16_pos, 1, "b", TextEncoding::Utf16)
+        .unwrap();
+    doc.merge(&mut other).unwrap();
+
+    let expected = format!("🐻{}b🐻é{}", chunk.repeat(250), chunk.repeat(49));
+    assert_eq!(doc.text(&text).unwrap(), expected);
+    assert_eq!(
+        doc.text_len(&text, TextEncoding::Utf8).unwrap(),
+        expected.len()
+    );
+    assert_eq!(
+        doc.text_len(&text, TextEncoding::Utf16).unwrap(),
+        expected.encode_utf16().count()
+    );
+    assert_eq!(
+        doc.text_len(&text, TextEncoding::CodePoint).unwrap(),
+        expected.chars().count()
+    );
+
+    // a cursor made at the old heads on the bear of chunk 250 is found in any encoding
+    let cursor = doc
+        .get_cursor_with_encoding(&text, 250 * 3 + 1, Some(&heads), TextEncoding::CodePoint)
+        .unwrap();
+    assert_eq!(
+        doc.get_cursor_position_with_encoding(&text, &cursor, Some(&heads), TextEncoding::Utf8)
+            .unwrap(),
+        250 * chunk.len() + 1
+    );
+    assert_eq!(
+        doc.get_cursor_position_with_encoding(&text, &cursor, None, TextEncoding::Utf16)
+            .unwrap(),
+        '🐻'.len_utf16() + utf16_pos + 1
+    );
+}
+
 proptest::proptest! {
     #[test]
     fn marks_are_okay(scenario in arb_scenario()) {
@@ -695,7 +1392,7 @@ proptest::proptest! {
 
         let span_chars = spans.iter().map(|span| match span {
             Span::Text(text, _) => text.clone(),
-            Span::Block(_) => "\n".to_string(),
+            Span::Block(_) | Span::Embed(_) => "\n".to_string(),
         }).collect::<String>();
         if !span_chars.chars().eq(expected_chars.chars()) {
             println!("scenario: {:?}", scenario);